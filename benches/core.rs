@@ -0,0 +1,92 @@
+use std::convert::TryFrom;
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use rand::SeedableRng;
+use rand::rngs::SmallRng;
+use tch::{Device, Kind, Tensor};
+use tch_geometric::algo::neighbor_sampling::{IdentityFilter, UnweightedSampler, neighbor_sampling_homogenous};
+use tch_geometric::algo::spmm::{Reduce, spmm};
+use tch_geometric::data::{CooGraphStorage, CscGraph, CscGraphStorage, CsrGraph, CsrGraphStorage, erdos_renyi, ind2ptr};
+
+// Set to also run the 1e8 edge scale, which needs around 10 GiB of memory
+const LARGE_ENV: &str = "TCH_GEOMETRIC_BENCH_LARGE";
+
+// (edges, seed) of every scale, graphs have an average degree of 10
+fn scales() -> Vec<(i64, u64)> {
+    let mut scales = vec![(10_000, 0), (1_000_000, 1)];
+    if std::env::var_os(LARGE_ENV).is_some() {
+        scales.push((100_000_000, 2));
+    }
+    scales
+}
+
+fn graph(m: i64, seed: u64) -> CooGraphStorage {
+    erdos_renyi(m / 10, m, seed).unwrap()
+}
+
+fn bench_ind2ptr(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ind2ptr");
+    group.sample_size(10);
+    for (m, seed) in scales() {
+        let coo = graph(m, seed);
+        let (row, _) = coo.row().sort(0, false);
+        group.throughput(Throughput::Elements(m as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(m), &row, |b, row| b.iter(|| {
+            ind2ptr(row, coo.size.0).unwrap()
+        }));
+    }
+    group.finish();
+}
+
+fn bench_coo_to_csr(c: &mut Criterion) {
+    let mut group = c.benchmark_group("coo_to_csr");
+    group.sample_size(10);
+    for (m, seed) in scales() {
+        let coo = graph(m, seed);
+        group.throughput(Throughput::Elements(m as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(m), &coo, |b, coo| b.iter(|| {
+            CsrGraphStorage::try_from(coo).unwrap()
+        }));
+    }
+    group.finish();
+}
+
+fn bench_spmm(c: &mut Criterion) {
+    let f = 16;
+    let mut group = c.benchmark_group("spmm_sum");
+    group.sample_size(10);
+    for (m, seed) in scales() {
+        let coo = graph(m, seed);
+        let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let x = Tensor::rand(&[coo.size.1, f], (Kind::Float, Device::Cpu));
+        group.throughput(Throughput::Elements(m as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(m), &x, |b, x| b.iter(|| {
+            spmm(&graph, x, Reduce::Sum).unwrap()
+        }));
+    }
+    group.finish();
+}
+
+fn bench_neighbor_sampling(c: &mut Criterion) {
+    let inputs: Vec<i64> = (0..1000).collect();
+    let inputs_state = vec![(); inputs.len()];
+    let num_neighbors = [15, 10, 5];
+
+    let mut group = c.benchmark_group("neighbor_sampling");
+    for (m, seed) in scales() {
+        let coo = graph(m, seed);
+        let graph_data = CscGraphStorage::try_from(&coo).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(m), &graph, |b, graph| b.iter_batched(
+            || SmallRng::seed_from_u64(0),
+            |mut rng| neighbor_sampling_homogenous(
+                &mut rng, graph, &inputs, &num_neighbors, &UnweightedSampler::<false>, &IdentityFilter, &inputs_state,
+            ),
+            BatchSize::SmallInput,
+        ));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_ind2ptr, bench_coo_to_csr, bench_spmm, bench_neighbor_sampling);
+criterion_main!(benches);
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use criterion::{Criterion, criterion_group, criterion_main};
+use rand::{Rng, SeedableRng};
+use rand::rngs::SmallRng;
+use tch::{Device, Kind, Tensor};
+use tch_geometric::algo::neighbor_sampling::{IdentityFilter, UnweightedSampler, WeightedReplacementSampler, neighbor_sampling_homogenous, neighbor_sampling_homogenous_dedup};
+use tch_geometric::data::{CooGraphStorage, CscGraph, CscGraphStorage, EdgeAttrSource, MmapCscGraphStorage, NeighborSource};
+
+fn bench_neighbor_sources(c: &mut Criterion) {
+    let (n, e) = (100_000_i64, 2_000_000_i64);
+    let row_col = Tensor::randint(n, &[2, e], (Kind::Int64, Device::Cpu));
+    let graph_data = CscGraphStorage::try_from(&CooGraphStorage::new(row_col, (n, n))).unwrap();
+    let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+    let dir = std::env::temp_dir().join(format!("tch_geometric_bench_{}", std::process::id()));
+    graph_data.save(&dir).unwrap();
+    let mmap_data = MmapCscGraphStorage::load(&dir).unwrap();
+
+    let inputs: Vec<i64> = (0..1024).collect();
+    let inputs_state = vec![(); inputs.len()];
+    let num_neighbors = [15, 10, 5];
+
+    // The csc graph is the monomorphized baseline the other backends are compared against
+    let mut group = c.benchmark_group("neighbor_sampling_backends");
+    let backends: [(&str, &dyn NeighborSource); 2] = [("csc_dyn", &graph), ("mmap_dyn", &mmap_data)];
+    group.bench_function("csc", |b| b.iter(|| neighbor_sampling_homogenous(
+        &mut SmallRng::seed_from_u64(0), &graph, &inputs, &num_neighbors,
+        &UnweightedSampler::<false>, &IdentityFilter, &inputs_state,
+    )));
+    group.bench_function("mmap", |b| b.iter(|| neighbor_sampling_homogenous(
+        &mut SmallRng::seed_from_u64(0), &mmap_data, &inputs, &num_neighbors,
+        &UnweightedSampler::<false>, &IdentityFilter, &inputs_state,
+    )));
+    for (name, backend) in backends.iter() {
+        group.bench_function(*name, |b| b.iter(|| neighbor_sampling_homogenous(
+            &mut SmallRng::seed_from_u64(0), *backend, &inputs, &num_neighbors,
+            &UnweightedSampler::<false>, &IdentityFilter, &inputs_state,
+        )));
+    }
+    group.finish();
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+// Baseline for the in-sampler dedup: unique the sampled nodes afterwards and remap the edges
+fn unique_and_remap(samples: &[i64], rows: &[i64], cols: &[i64]) -> (Vec<i64>, Vec<i64>, Vec<i64>) {
+    let mut local_ids: HashMap<i64, i64> = HashMap::new();
+    let mut node_ids = Vec::new();
+    let remap: Vec<i64> = samples.iter()
+        .map(|v| *local_ids.entry(*v).or_insert_with(|| {
+            node_ids.push(*v);
+            node_ids.len() as i64 - 1
+        }))
+        .collect();
+    let rows = rows.iter().map(|r| remap[*r as usize]).collect();
+    let cols = cols.iter().map(|c| remap[*c as usize]).collect();
+    (node_ids, rows, cols)
+}
+
+fn bench_dedup(c: &mut Criterion) {
+    let (n, e) = (100_000_i64, 2_000_000_i64);
+    let row_col = Tensor::randint(n, &[2, e], (Kind::Int64, Device::Cpu));
+    let graph_data = CscGraphStorage::try_from(&CooGraphStorage::new(row_col, (n, n))).unwrap();
+    let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+    let inputs: Vec<i64> = (0..1024).collect();
+    let inputs_state = vec![(); inputs.len()];
+    let num_neighbors = [15, 10, 5];
+
+    let mut group = c.benchmark_group("neighbor_sampling_dedup");
+    group.bench_function("post_hoc_unique", |b| b.iter(|| {
+        let (samples, edges, _) = neighbor_sampling_homogenous(
+            &mut SmallRng::seed_from_u64(0), &graph, &inputs, &num_neighbors,
+            &UnweightedSampler::<false>, &IdentityFilter, &inputs_state,
+        );
+        unique_and_remap(&samples, &edges.rows, &edges.cols)
+    }));
+    group.bench_function("in_sampler", |b| b.iter(|| neighbor_sampling_homogenous_dedup(
+        &mut SmallRng::seed_from_u64(0), &graph, &inputs, &num_neighbors,
+        &UnweightedSampler::<false>, &IdentityFilter, &inputs_state,
+    )));
+    group.finish();
+}
+
+// Many batches over the same graph, the cdf of the sampler is built once outside of the loop. There is no
+// stateless sampler with the same distribution to compare against, `WeightedSampler` samples without replacement.
+fn bench_weighted_replacement(c: &mut Criterion) {
+    let (n, e) = (100_000_i64, 2_000_000_i64);
+    let row_col = Tensor::randint(n, &[2, e], (Kind::Int64, Device::Cpu));
+    let graph_data = CscGraphStorage::try_from(&CooGraphStorage::new(row_col, (n, n))).unwrap();
+    let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+    let mut rng = SmallRng::seed_from_u64(0);
+    let weights_data: Vec<f64> = (0..e).map(|_| rng.gen_range(0.1..10.0)).collect();
+
+    let inputs: Vec<i64> = (0..1024).collect();
+    let inputs_state = vec![(); inputs.len()];
+    let num_neighbors = [15, 10, 5];
+    let batches = 16;
+
+    let source = EdgeAttrSource::new(&graph).with_weights(&weights_data);
+    let sampler = WeightedReplacementSampler::new(&source).unwrap();
+    let mut group = c.benchmark_group("neighbor_sampling_weighted_replacement");
+    group.bench_function("sample", |b| b.iter(|| for i in 0..batches {
+        neighbor_sampling_homogenous(
+            &mut SmallRng::seed_from_u64(i), &graph, &inputs, &num_neighbors,
+            &sampler, &IdentityFilter, &inputs_state,
+        );
+    }));
+    group.bench_function("build", |b| b.iter(|| WeightedReplacementSampler::new(&source)));
+    group.finish();
+}
+
+criterion_group!(benches, bench_neighbor_sources, bench_dedup, bench_weighted_replacement);
+criterion_main!(benches);
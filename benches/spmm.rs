@@ -0,0 +1,78 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use criterion::{Criterion, criterion_group, criterion_main};
+use tch::{Device, Kind, Tensor};
+use tch_geometric::algo::spmm::{Reduce, spmm, spmm_multi, spmm_multi_chunked};
+use tch_geometric::data::{CooGraphStorage, CsrGraph, CsrGraphStorage};
+
+// Tracks the peak of the Rust heap, tensor storage allocated by libtorch is not included. That is only the outputs and
+// a tensor per chunk here, spmm reads the float x in place instead of copying it as doubles.
+struct PeakAlloc;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(current, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: PeakAlloc = PeakAlloc;
+
+fn bench_spmm_multi(c: &mut Criterion) {
+    let (n, e, f) = (10_000_i64, 200_000_i64, 64_i64);
+    let row_col = Tensor::randint(n, &[2, e], (Kind::Int64, Device::Cpu));
+    let coo = CooGraphStorage::new(row_col, (n, n));
+    let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+    let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+    let x = Tensor::rand(&[n, f], (Kind::Float, Device::Cpu));
+    let reduces = [Reduce::Sum, Reduce::Max, Reduce::Mean];
+
+    let mut group = c.benchmark_group("spmm_sum_max_mean");
+    group.bench_function("separate", |b| b.iter(|| {
+        reduces.iter().map(|reduce| spmm(&graph, &x, *reduce).unwrap()).collect::<Vec<_>>()
+    }));
+    group.bench_function("multi", |b| b.iter(|| spmm_multi(&graph, &x, &reduces).unwrap()));
+    group.finish();
+}
+
+fn bench_spmm_chunked(c: &mut Criterion) {
+    let (n, e, f) = (20_000_i64, 200_000_i64, 512_i64);
+    let row_col = Tensor::randint(n, &[2, e], (Kind::Int64, Device::Cpu));
+    let coo = CooGraphStorage::new(row_col, (n, n));
+    let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+    let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+    let x = Tensor::rand(&[n, f], (Kind::Float, Device::Cpu));
+    let reduces = [Reduce::Sum, Reduce::Max];
+
+    // The working set grows with the chunk, the unchunked run has to hold all output rows at once
+    let chunk_sizes = [256, 4096, usize::MAX];
+    let peaks: Vec<usize> = chunk_sizes.iter().map(|&chunk_rows| {
+        PEAK.store(CURRENT.load(Ordering::Relaxed), Ordering::Relaxed);
+        let baseline = CURRENT.load(Ordering::Relaxed);
+        spmm_multi_chunked(&graph, &x, &reduces, Some(chunk_rows)).unwrap();
+        PEAK.load(Ordering::Relaxed) - baseline
+    }).collect();
+    assert!(peaks.windows(2).all(|w| w[0] < w[1]), "peak heap growth per chunk size: {:?}", peaks);
+
+    let mut group = c.benchmark_group("spmm_chunked");
+    for chunk_rows in chunk_sizes {
+        group.bench_function(format!("chunk_rows_{}", chunk_rows), |b| b.iter(|| {
+            spmm_multi_chunked(&graph, &x, &reduces, Some(chunk_rows)).unwrap()
+        }));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_spmm_multi, bench_spmm_chunked);
+criterion_main!(benches);
@@ -0,0 +1,35 @@
+import dgl
+import dgl.function as fn
+import torch
+import torch_geometric as pyg
+
+import tch_geometric as thg
+
+num_neighbors = [4, 3]
+
+dataset = pyg.datasets.FakeDataset()
+data = dataset[0]
+
+col_ptrs, row_indices, perm = thg.loader.to_csc(data)
+
+start = torch.tensor([0, 1, 2, 3, 4, 5, 6, 7], dtype=torch.long)
+samples, rows, cols, edge_index, layer_offsets = thg.native.neighbor_sampling_homogenous(
+    col_ptrs, row_indices, start, num_neighbors, None, None, dedup=True
+)
+blocks = thg.native.to_dgl_blocks(samples, rows, cols, edge_index, layer_offsets, perm)
+
+
+# Blocks come in hop order, dgl runs them from the outermost hop inwards. tests/test_dgl_blocks.py checks the
+# aggregation against the original edge index.
+h = data.x[samples]
+for src, dst, num_src_nodes, num_dst_nodes, src_nid, dst_nid, eid in reversed(blocks):
+    block = dgl.create_block((src, dst), num_src_nodes=num_src_nodes, num_dst_nodes=num_dst_nodes)
+    block.srcdata[dgl.NID] = src_nid
+    block.dstdata[dgl.NID] = dst_nid
+    block.edata[dgl.EID] = eid
+
+    block.srcdata['h'] = h[:num_src_nodes]
+    block.update_all(fn.copy_u('h', 'm'), fn.sum('m', 'h'))
+    h = block.dstdata['h']
+
+assert h.shape[0] == len(start)
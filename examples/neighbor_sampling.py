@@ -46,4 +46,13 @@ samples, rows, cols, edge_index, layer_offsets = thg.native.neighbor_sampling_ho
 batch = filter_data(data, samples, rows, cols, edge_index, perm)
 
 layer = SAGEConv((-1, -1), 32)
-output = layer(x=batch.x, edge_index=batch.edge_index)
\ No newline at end of file
+output = layer(x=batch.x, edge_index=batch.edge_index)
+# Seeds without sampled neighbors get a self loop, its edge index is -1
+start = torch.tensor([0, 1, 2, 3, 4, 5, 6, 7], dtype=torch.long)
+samples, rows, cols, edge_index, layer_offsets = thg.native.neighbor_sampling_homogenous(
+    col_ptrs, row_indices, start, num_neighbors,
+    filter=(thg.loader.TemporalEdgeFilter((0, 0), timestamps), initial_timestamps[:len(start)]),
+    lonely_seed_policy='self_loop',
+)
+seeds_with_edges = cols[:layer_offsets[1][1] if len(layer_offsets) > 1 else len(cols)].unique()
+assert len(seeds_with_edges) == len(start)
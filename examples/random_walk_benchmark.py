@@ -0,0 +1,34 @@
+import time
+
+import torch
+import torch_geometric as pyg
+from torch_cluster import random_walk as cluster_random_walk
+import tch_geometric as thg
+
+walk_length = 20
+walks_per_node = 10
+repeats = 5
+
+dataset = pyg.datasets.FakeDataset(avg_num_nodes=100000, avg_degree=10)
+data = dataset[0]
+num_nodes = data.num_nodes
+row, col = data.edge_index
+
+row_ptrs, col_indices, perm = thg.to_csr(data.edge_index, num_nodes)
+start = torch.arange(num_nodes).repeat(walks_per_node)
+
+
+def bench(name, fn):
+    fn()
+    begin = time.perf_counter()
+    for _ in range(repeats):
+        fn()
+    elapsed = (time.perf_counter() - begin) / repeats
+    print(f'{name:<24} {elapsed * 1000:8.1f} ms ({len(start) / elapsed / 1e6:.2f}M walks/s)')
+
+
+print(f'{len(start)} walks of length {walk_length} on {num_nodes} nodes, {data.num_edges} edges')
+bench('torch_cluster uniform', lambda: cluster_random_walk(row, col, start, walk_length, num_nodes=num_nodes))
+bench('tch_geometric uniform', lambda: thg.uniform_random_walk(row_ptrs, col_indices, start, walk_length))
+bench('torch_cluster node2vec', lambda: cluster_random_walk(row, col, start, walk_length, 1.0, 0.5, num_nodes=num_nodes))
+bench('tch_geometric node2vec', lambda: thg.random_walk(row_ptrs, col_indices, start, walk_length, 1.0, 0.5))
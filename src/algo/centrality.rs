@@ -0,0 +1,134 @@
+use rayon::prelude::*;
+use tch::Tensor;
+use crate::data::CsrGraph;
+use crate::utils::{NodeIdx, parallel, TensorConversionError, TensorResult};
+
+const KATZ_MAX_ITER: usize = 1000;
+const KATZ_TOL: f64 = 1e-8;
+
+// y = A x, the graph is assumed to be undirected
+fn propagate(graph: &CsrGraph, x: &[f64]) -> Vec<f64> {
+    parallel::install(|| (0..graph.node_count() as NodeIdx).into_par_iter()
+        .map(|v| graph.neighbors_slice(v).iter().map(|u| x[*u as usize]).sum())
+        .collect())
+}
+
+fn l1_distance(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b.iter()).map(|(x, y)| (x - y).abs()).sum()
+}
+
+// Degree divided by the maximum possible degree n - 1
+pub fn degree_centrality(graph: &CsrGraph) -> Tensor {
+    let n = graph.node_count();
+    let scale = if n > 1 { 1.0 / (n - 1) as f64 } else { 1.0 };
+    let centrality: Vec<f64> = (0..n as NodeIdx)
+        .map(|v| graph.out_degree(v) as f64 * scale)
+        .collect();
+    Tensor::of_slice(&centrality)
+}
+
+// Power iteration on A + I, the shift keeps bipartite graphs (e.g. stars) from oscillating without changing the
+// leading eigenvector. The result is normalized to unit length. On disconnected graphs the mass concentrates in
+// the component with the largest eigenvalue and the remaining components decay towards 0, so the values are
+// only comparable within a component.
+pub fn eigenvector_centrality(
+    graph: &CsrGraph,
+    max_iter: usize,
+    tol: f64,
+) -> TensorResult<Tensor> {
+    let n = graph.node_count();
+    if n == 0 {
+        return Ok(Tensor::of_slice::<f64>(&[]));
+    }
+
+    let mut x = vec![1.0 / n as f64; n];
+    for _ in 0..max_iter {
+        let mut next = propagate(graph, &x);
+        next.iter_mut().zip(x.iter()).for_each(|(y, x)| *y += x);
+
+        // Normalizing every iteration avoids overflow
+        let norm = next.iter().map(|y| y * y).sum::<f64>().sqrt();
+        if norm == 0.0 {
+            return Ok(Tensor::of_slice(&next));
+        }
+        next.iter_mut().for_each(|y| *y /= norm);
+
+        let converged = l1_distance(&next, &x) < n as f64 * tol;
+        x = next;
+        if converged {
+            return Ok(Tensor::of_slice(&x));
+        }
+    }
+
+    Err(TensorConversionError::Unknown(format!("eigenvector centrality did not converge in {} iterations", max_iter)))
+}
+
+// Fixed point of x = alpha A x + beta, which only exists for alpha < 1 / lambda_max. The result is not normalized.
+pub fn katz_centrality(
+    graph: &CsrGraph,
+    alpha: f64,
+    beta: f64,
+) -> TensorResult<Tensor> {
+    let n = graph.node_count();
+    let mut x = vec![0.0; n];
+    for _ in 0..KATZ_MAX_ITER {
+        let next: Vec<f64> = propagate(graph, &x).into_iter().map(|y| alpha * y + beta).collect();
+        if next.iter().any(|y| !y.is_finite()) {
+            break;
+        }
+
+        let converged = l1_distance(&next, &x) < n as f64 * KATZ_TOL;
+        x = next;
+        if converged {
+            return Ok(Tensor::of_slice(&x));
+        }
+    }
+
+    Err(TensorConversionError::Unknown(format!(
+        "katz centrality did not converge, alpha {} is likely larger than 1 / lambda_max", alpha
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use tch::Tensor;
+    use crate::data::{CooGraphStorage, CsrGraph, CsrGraphStorage};
+    use super::{degree_centrality, eigenvector_centrality, katz_centrality};
+
+    fn assert_close(actual: &Tensor, expected: &[f64]) {
+        let actual: Vec<f64> = actual.into();
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert!((a - e).abs() < 1e-6, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_centrality() {
+        // Star with center 0 and 4 leaves
+        let n = 5;
+        let rows: Vec<i64> = (1..n).flat_map(|v| [0, v]).collect();
+        let cols: Vec<i64> = (1..n).flat_map(|v| [v, 0]).collect();
+        let coo = CooGraphStorage::new(Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0), (n, n));
+        let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        assert_close(&degree_centrality(&graph), &[1.0, 0.25, 0.25, 0.25, 0.25]);
+
+        // The leading eigenvector of a star is (1 / sqrt(2), 1 / sqrt(2 (n - 1)), ...)
+        let center = 1.0 / 2.0_f64.sqrt();
+        let leaf = 1.0 / (2.0 * (n - 1) as f64).sqrt();
+        let centrality = eigenvector_centrality(&graph, 100, 1e-10).unwrap();
+        assert_close(&centrality, &[center, leaf, leaf, leaf, leaf]);
+        assert!(eigenvector_centrality(&graph, 1, 1e-10).is_err());
+
+        // x_center = alpha * 4 * x_leaf + beta and x_leaf = alpha * x_center + beta
+        let (alpha, beta) = (0.1, 1.0);
+        let x_center = beta * (1.0 + 4.0 * alpha) / (1.0 - 4.0 * alpha * alpha);
+        let x_leaf = alpha * x_center + beta;
+        assert_close(&katz_centrality(&graph, alpha, beta).unwrap(), &[x_center, x_leaf, x_leaf, x_leaf, x_leaf]);
+        // lambda_max of the star is 2
+        assert!(katz_centrality(&graph, 0.9, beta).is_err());
+    }
+}
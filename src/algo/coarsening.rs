@@ -0,0 +1,231 @@
+use tch::{Kind, Tensor};
+use crate::data::{CooGraphStorage, CsrGraph};
+use crate::utils::{NodeIdx, TensorConversionError, TensorResult, try_tensor_to_slice};
+
+pub fn heavy_edge_matching(
+    graph: &CsrGraph,
+    weights: Option<&[f64]>,
+) -> Vec<NodeIdx> {
+    let node_count = graph.node_count();
+    let mut matching: Vec<NodeIdx> = vec![-1; node_count];
+
+    for u in 0..node_count as NodeIdx {
+        if matching[u as usize] != -1 {
+            continue;
+        }
+
+        // Pick the heaviest edge towards a node that is still unmatched
+        let mut best: Option<(NodeIdx, f64)> = None;
+        for edge_ptr in graph.neighbors_range(u) {
+            let v = graph.get_by_ptr(edge_ptr);
+            if v == u || matching[v as usize] != -1 {
+                continue;
+            }
+
+            let w = weights.map_or(1.0, |weights| weights[edge_ptr]);
+            match best {
+                Some((_, best_w)) if best_w >= w => {}
+                _ => best = Some((v, w)),
+            }
+        }
+
+        match best {
+            Some((v, _)) => {
+                matching[u as usize] = v;
+                matching[v as usize] = u;
+            }
+            None => matching[u as usize] = u,
+        }
+    }
+
+    matching
+}
+
+// The coarse graph is simple, edges between the same pair of clusters are coalesced
+pub fn coarsen_by_matching(
+    graph: &CsrGraph,
+    weights: Option<&Tensor>,
+) -> TensorResult<(CooGraphStorage, Tensor)> {
+    let weights_data = weights.map(try_tensor_to_slice::<f64>).transpose()?;
+    let matching = heavy_edge_matching(graph, weights_data);
+
+    // Number supernodes by the first node of each matched pair
+    let mut cluster: Vec<NodeIdx> = vec![-1; matching.len()];
+    let mut cluster_count = 0;
+    for (u, v) in matching.iter().cloned().enumerate() {
+        if cluster[u] == -1 {
+            cluster[u] = cluster_count;
+            cluster[v as usize] = cluster_count;
+            cluster_count += 1;
+        }
+    }
+
+    let mut edges: Vec<(NodeIdx, NodeIdx)> = Vec::with_capacity(graph.edge_count());
+    for u in 0..graph.node_count() {
+        for v in graph.neighbors_slice(u as NodeIdx) {
+            let (cu, cv) = (cluster[u], cluster[*v as usize]);
+            if cu != cv {
+                edges.push((cu, cv));
+            }
+        }
+    }
+    edges.sort_unstable();
+    edges.dedup();
+
+    let (rows, cols): (Vec<NodeIdx>, Vec<NodeIdx>) = edges.into_iter().unzip();
+    let row_col = Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0);
+
+    Ok((
+        CooGraphStorage::new(row_col, (cluster_count, cluster_count)),
+        Tensor::of_slice(&cluster),
+    ))
+}
+
+// Graclus clustering as used by PyG's graclus pooling. Nodes are visited in order, every node that is still unmarked is
+// matched to the unmarked neighbor maximizing the normalized cut weight w(u, v) (1 / d(u) + 1 / d(v)), with d the
+// weighted degree, and both are marked. Nodes without an unmarked neighbor are marked as their own cluster. Unlike
+// `heavy_edge_matching` the degree normalization prefers pairing low degree nodes, so hubs are matched last.
+// Clusters are numbered in the order they are formed. `weights` holds one weight per csr edge position.
+pub fn graclus(
+    graph: &CsrGraph,
+    weights: Option<&Tensor>,
+) -> TensorResult<Tensor> {
+    let weights = weights.map(|w| w.to_kind(Kind::Double).contiguous());
+    let weights_data = weights.as_ref().map(try_tensor_to_slice::<f64>).transpose()?;
+    if let Some(w) = weights_data.filter(|w| w.len() != graph.edge_count()) {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "weights must have {} entries, got {}", graph.edge_count(), w.len()
+        ))));
+    }
+
+    let weight = |edge_ptr: usize| weights_data.map_or(1.0, |w| w[edge_ptr]);
+    let degree: Vec<f64> = (0..graph.node_count() as NodeIdx)
+        .map(|u| graph.neighbors_range(u).map(weight).sum())
+        .collect();
+
+    let mut cluster: Vec<NodeIdx> = vec![-1; graph.node_count()];
+    let mut cluster_count = 0;
+    for u in 0..graph.node_count() as NodeIdx {
+        if cluster[u as usize] != -1 {
+            continue;
+        }
+
+        let mut best: Option<(NodeIdx, f64)> = None;
+        for edge_ptr in graph.neighbors_range(u) {
+            let v = graph.get_by_ptr(edge_ptr);
+            if v == u || cluster[v as usize] != -1 {
+                continue;
+            }
+
+            let score = weight(edge_ptr) * (1.0 / degree[u as usize] + 1.0 / degree[v as usize]);
+            match best {
+                Some((_, best_score)) if best_score >= score => {}
+                _ => best = Some((v, score)),
+            }
+        }
+
+        cluster[u as usize] = cluster_count;
+        if let Some((v, _)) = best {
+            cluster[v as usize] = cluster_count;
+        }
+        cluster_count += 1;
+    }
+
+    Ok(Tensor::of_slice(&cluster))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use tch::Tensor;
+    use crate::data::{CooGraphStorage, CsrGraph, CsrGraphStorage};
+    use crate::utils::NodeIdx;
+
+    fn path_graph(n: i64) -> CooGraphStorage {
+        let mut rows = Vec::new();
+        let mut cols = Vec::new();
+        for i in 0..n - 1 {
+            rows.extend_from_slice(&[i, i + 1]);
+            cols.extend_from_slice(&[i + 1, i]);
+        }
+        let row_col = Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0);
+        CooGraphStorage::new(row_col, (n, n))
+    }
+
+    #[test]
+    fn test_coarsen_by_matching() {
+        let graph_data = CsrGraphStorage::try_from(&path_graph(10)).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let (coarse, cluster) = super::coarsen_by_matching(&graph, None).unwrap();
+        let cluster: Vec<NodeIdx> = cluster.into();
+        assert_eq!(coarse.size, (5, 5));
+        assert_eq!(cluster, vec![0, 0, 1, 1, 2, 2, 3, 3, 4, 4]);
+        // The coarsened graph is again a path
+        assert_eq!(coarse.row_col.size(), vec![2, 8]);
+
+        let graph_data = CsrGraphStorage::try_from(&path_graph(7)).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let (coarse, cluster) = super::coarsen_by_matching(&graph, None).unwrap();
+        let cluster: Vec<NodeIdx> = cluster.into();
+        assert_eq!(coarse.size, (4, 4));
+        assert_eq!(cluster[6], 3);
+    }
+
+    #[test]
+    fn test_coarsen_by_matching_weighted() {
+        // Node 0 is connected to both 1 and 2, the 0 - 2 edge is the heaviest
+        let row_col = Tensor::stack(&[Tensor::of_slice(&[0_i64, 1, 0, 2]), Tensor::of_slice(&[1_i64, 0, 2, 0])], 0);
+        let graph_data = CsrGraphStorage::try_from(&CooGraphStorage::new(row_col, (3, 3))).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let mut weights = vec![0.0; graph.edge_count()];
+        for u in 0..graph.node_count() as i64 {
+            for edge_ptr in graph.neighbors_range(u) {
+                let v = graph.get_by_ptr(edge_ptr);
+                weights[edge_ptr] = if u + v == 2 { 5.0 } else { 1.0 };
+            }
+        }
+        let weights = Tensor::of_slice(&weights);
+
+        let (coarse, cluster) = super::coarsen_by_matching(&graph, Some(&weights)).unwrap();
+        let cluster: Vec<NodeIdx> = cluster.into();
+        assert_eq!(coarse.size, (2, 2));
+        assert_eq!(cluster, vec![0, 1, 0]);
+
+        let (_, cluster) = super::coarsen_by_matching(&graph, None).unwrap();
+        let cluster: Vec<NodeIdx> = cluster.into();
+        assert_eq!(cluster, vec![0, 0, 1]);
+    }
+
+    #[test]
+    fn test_graclus() {
+        let graph_data = CsrGraphStorage::try_from(&path_graph(4)).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let cluster: Vec<NodeIdx> = super::graclus(&graph, None).unwrap().into();
+        assert_eq!(cluster, vec![0, 0, 1, 1]);
+
+        // Star with center 0 and a heavy 1 - 2 edge: the normalized cut pairs the center with its lowest degree leaf
+        // and leaves 1 - 2 to each other, plain heavy edge matching pairs the center with the first leaf
+        let rows = [0_i64, 1, 0, 2, 0, 3, 1, 2];
+        let cols = [1_i64, 0, 2, 0, 3, 0, 2, 1];
+        let coo = CooGraphStorage::new(Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0), (4, 4));
+        let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let weights: Vec<f64> = (0..4_i64)
+            .flat_map(|u| graph.neighbors_slice(u).iter().map(move |v| if u + v == 3 && u * v == 2 { 2.0 } else { 1.0 }))
+            .collect();
+        let cluster: Vec<NodeIdx> = super::graclus(&graph, Some(&Tensor::of_slice(&weights))).unwrap().into();
+        assert_eq!(cluster, vec![0, 1, 1, 0]);
+        assert_eq!(super::heavy_edge_matching(&graph, Some(&weights))[0], 1);
+
+        // Isolated nodes are their own cluster
+        let coo = CooGraphStorage::new(Tensor::of_slice(&[0_i64, 1]).view([2, 1]), (3, 3));
+        let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let cluster: Vec<NodeIdx> = super::graclus(&graph, None).unwrap().into();
+        assert_eq!(cluster, vec![0, 0, 1]);
+
+        assert!(super::graclus(&graph, Some(&Tensor::of_slice(&[1.0_f64, 2.0]))).is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,548 @@
+use std::fmt;
+use rand::seq::SliceRandom;
+use rayon::prelude::*;
+use tch::{Kind, Tensor};
+use crate::data::{CooGraphStorage, CscGraph, CsrGraph, EdgeAttr};
+use crate::utils::{TensorConversionError, TensorResult, parallel, try_tensor_to_slice};
+use crate::utils::random::RngPool;
+use crate::utils::types::IndexType;
+
+fn in_degrees<Ptr: IndexType, Ix: IndexType>(graph: &CscGraph<Ptr, Ix>) -> Vec<usize> {
+    (0..graph.node_count()).map(|i| graph.in_degree(Ix::new(i))).collect()
+}
+
+pub fn degree_histogram<Ptr: IndexType, Ix: IndexType>(
+    graph: &CscGraph<Ptr, Ix>,
+    max_bins: Option<i64>,
+) -> TensorResult<Tensor> {
+    let degrees = in_degrees(graph);
+    let max_degree = degrees.iter().cloned().max().unwrap_or(0);
+
+    let bins = match max_bins {
+        Some(bins) if bins < 2 => {
+            return Err(TensorConversionError::Unknown(format!("max_bins must be at least 2, got {}", bins)));
+        }
+        Some(bins) if (bins as usize) <= max_degree => bins as usize,
+        _ => {
+            let mut counts = vec![0_i64; max_degree + 1];
+            for d in degrees {
+                counts[d] += 1;
+            }
+            return Ok(Tensor::of_slice(&counts));
+        }
+    };
+
+    // Bin 0 holds the isolated nodes, the other bins are log-spaced over [1, max_degree + 1)
+    let log_max = ((max_degree + 1) as f64).ln();
+    let mut counts = vec![0_i64; bins];
+    for d in degrees {
+        let bin = if d == 0 {
+            0
+        } else {
+            (1 + ((bins - 1) as f64 * (d as f64).ln() / log_max) as usize).min(bins - 1)
+        };
+        counts[bin] += 1;
+    }
+    Ok(Tensor::of_slice(&counts))
+}
+
+// Sums the out-edge weights of every node, `weights` is aligned with the csr indices
+pub fn weighted_degree<Ptr: IndexType, Ix: IndexType>(
+    graph: &CsrGraph<Ptr, Ix>,
+    weights: &Tensor,
+) -> TensorResult<Tensor> {
+    if weights.numel() != graph.edge_count() {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "weights must have {} values, got {}", graph.edge_count(), weights.numel()
+        ))));
+    }
+
+    let weights_data = weights.to_kind(Kind::Double).contiguous();
+    let weights_attr = EdgeAttr::new(try_tensor_to_slice::<f64>(&weights_data)?);
+    let strength: Vec<f64> = (0..graph.node_count())
+        .map(|i| weights_attr.get_range(graph.neighbors_range(Ix::new(i))).iter().sum())
+        .collect();
+
+    Ok(Tensor::of_slice(&strength).to_kind(weights.kind()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    In,
+    Out,
+}
+
+// Degrees straight from the edge list without building a csr/csc, out-degree counts rows and in-degree counts cols
+pub fn coo_degree(coo: &CooGraphStorage, direction: Direction) -> Tensor {
+    let (index, num_nodes) = match direction {
+        Direction::Out => (coo.row(), coo.size.0),
+        Direction::In => (coo.col(), coo.size.1),
+    };
+    index.to_kind(Kind::Int64).bincount::<Tensor>(None, num_nodes)
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphSummary {
+    pub node_count: usize,
+    pub edge_count: usize,
+    pub min_degree: usize,
+    pub mean_degree: f64,
+    pub median_degree: f64,
+    pub max_degree: usize,
+    pub isolated_nodes: usize,
+    pub power_law_alpha: Option<f64>,
+}
+
+impl fmt::Display for GraphSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "nodes={} edges={} degree(min={} mean={:.3} median={} max={}) isolated={}",
+            self.node_count, self.edge_count, self.min_degree, self.mean_degree,
+            self.median_degree, self.max_degree, self.isolated_nodes,
+        )?;
+        if let Some(alpha) = self.power_law_alpha {
+            write!(f, " alpha={:.3}", alpha)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn graph_summary<Ptr: IndexType, Ix: IndexType>(
+    graph: &CscGraph<Ptr, Ix>,
+) -> GraphSummary {
+    let mut degrees = in_degrees(graph);
+    degrees.sort_unstable();
+
+    let node_count = degrees.len();
+    let edge_count = graph.edge_count();
+    let median_degree = match node_count {
+        0 => 0.0,
+        n if n % 2 == 0 => (degrees[n / 2 - 1] + degrees[n / 2]) as f64 / 2.0,
+        n => degrees[n / 2] as f64,
+    };
+    let mean_degree = if node_count > 0 { edge_count as f64 / node_count as f64 } else { 0.0 };
+    let isolated_nodes = degrees.iter().take_while(|&&d| d == 0).count();
+
+    // Discrete Clauset MLE with the smallest positive degree as x_min
+    let positive = &degrees[isolated_nodes..];
+    let power_law_alpha = positive.first().map(|&x_min| {
+        let x_min = x_min as f64 - 0.5;
+        let log_sum: f64 = positive.iter().map(|&d| (d as f64 / x_min).ln()).sum();
+        1.0 + positive.len() as f64 / log_sum
+    });
+
+    GraphSummary {
+        node_count,
+        edge_count,
+        min_degree: degrees.first().cloned().unwrap_or(0),
+        mean_degree,
+        median_degree,
+        max_degree: degrees.last().cloned().unwrap_or(0),
+        isolated_nodes,
+        power_law_alpha,
+    }
+}
+
+// Pearson correlation of the in-degrees of both endpoints over all edges, none if it is undefined because there
+// are no edges or all endpoints have the same degree
+pub fn degree_assortativity<Ptr: IndexType + Sync, Ix: IndexType + Sync>(
+    graph: &CscGraph<Ptr, Ix>,
+) -> Option<f64> {
+    let degrees = in_degrees(graph);
+    // Per destination: sums of x = deg(src), y = deg(dst), x^2, y^2 and xy
+    let (sx, sy, sxx, syy, sxy) = parallel::install(|| (0..graph.node_count()).into_par_iter()
+        .map(|v| {
+            let y = degrees[v] as f64;
+            graph.neighbors_slice(Ix::new(v)).iter().fold((0.0, 0.0, 0.0, 0.0, 0.0), |acc, u| {
+                let x = degrees[u.index()] as f64;
+                (acc.0 + x, acc.1 + y, acc.2 + x * x, acc.3 + y * y, acc.4 + x * y)
+            })
+        })
+        .reduce(|| (0.0, 0.0, 0.0, 0.0, 0.0), |a, b| (a.0 + b.0, a.1 + b.1, a.2 + b.2, a.3 + b.3, a.4 + b.4)));
+
+    let m = graph.edge_count() as f64;
+    let cov = sxy / m - (sx / m) * (sy / m);
+    let var = (sxx / m - (sx / m).powi(2)) * (syy / m - (sy / m).powi(2));
+    if graph.edge_count() == 0 || var <= 0.0 {
+        None
+    } else {
+        Some(cov / var.sqrt())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HomophilyKind {
+    // Fraction of edges that connect nodes of the same label
+    Edge,
+    // Mean over the nodes of the fraction of neighbors with the same label
+    Node,
+    // Class insensitive homophily of Lim et al., sum_k max(0, h_k - |C_k| / n) / (C - 1) with h_k the edge
+    // homophily of the edges into class k and C the number of labels that occur, gaps in the label ids don't count
+    ClassInsensitive,
+}
+
+// Homophily of `labels` over the edges (u, v) of `graph`, nodes labeled -1 and their edges are left out. Node
+// homophily only averages over nodes with labeled neighbors. None if no labeled edge is left, or for the class
+// insensitive homophily with less than two classes.
+pub fn label_homophily<Ptr: IndexType + Sync, Ix: IndexType + Sync>(
+    graph: &CscGraph<Ptr, Ix>,
+    labels: &Tensor,
+    kind: HomophilyKind,
+) -> TensorResult<Option<f64>> {
+    let n = graph.node_count();
+    if labels.size() != vec![n as i64] {
+        return Err(TensorConversionError::InvalidShape(Some(format!("labels must be of shape [{}], got {:?}", n, labels.size()))));
+    }
+    let labels = labels.to_kind(Kind::Int64).contiguous();
+    let labels_data = try_tensor_to_slice::<i64>(&labels)?;
+    if let Some(l) = labels_data.iter().find(|&&l| l < -1) {
+        return Err(TensorConversionError::Unknown(format!("labels must be -1 (unlabeled) or non negative, got {}", l)));
+    }
+
+    // Labeled neighbors and those of them with the same label, per labeled destination
+    let counts: Vec<(usize, usize)> = parallel::install(|| (0..n).into_par_iter()
+        .map(|v| {
+            let label = labels_data[v];
+            if label < 0 {
+                return (0, 0);
+            }
+            graph.neighbors_slice(Ix::new(v)).iter()
+                .map(|u| labels_data[u.index()])
+                .filter(|l| *l >= 0)
+                .fold((0, 0), |(total, same), l| (total + 1, same + (l == label) as usize))
+        })
+        .collect());
+
+    let total: usize = counts.iter().map(|c| c.0).sum();
+    if total == 0 {
+        return Ok(None);
+    }
+    Ok(match kind {
+        HomophilyKind::Edge => Some(counts.iter().map(|c| c.1).sum::<usize>() as f64 / total as f64),
+        HomophilyKind::Node => {
+            let ratios: Vec<f64> = counts.iter().filter(|c| c.0 > 0).map(|(t, s)| *s as f64 / *t as f64).collect();
+            Some(ratios.iter().sum::<f64>() / ratios.len() as f64)
+        }
+        HomophilyKind::ClassInsensitive => {
+            let num_classes = labels_data.iter().max().map_or(0, |l| (*l + 1).max(0) as usize);
+            let mut class_size = vec![0_usize; num_classes];
+            let mut class_total = vec![0_usize; num_classes];
+            let mut class_same = vec![0_usize; num_classes];
+            for (label, (t, s)) in labels_data.iter().zip(counts.iter()).filter(|(l, _)| **l >= 0) {
+                class_size[*label as usize] += 1;
+                class_total[*label as usize] += t;
+                class_same[*label as usize] += s;
+            }
+            let present = class_size.iter().filter(|size| **size > 0).count();
+            if present < 2 {
+                return Ok(None);
+            }
+            let labeled = class_size.iter().sum::<usize>() as f64;
+            let h: f64 = (0..num_classes)
+                .filter(|k| class_total[*k] > 0)
+                .map(|k| (class_same[k] as f64 / class_total[k] as f64 - class_size[k] as f64 / labeled).max(0.0))
+                .sum();
+            Some(h / (present - 1) as f64)
+        }
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchingStrategy {
+    // Buckets one after the other, so consecutive batches hold seeds of similar degree
+    Homogeneous,
+    // Buckets interleaved round robin, so every window of num_buckets seeds takes one seed from each bucket
+    Balanced,
+}
+
+// Reordered seeds of `bucket_seeds_by_degree`, buckets[i] is the bucket of seeds[i] and bucket
+// b holds bucket_ptrs[b + 1] - bucket_ptrs[b] seeds. With the homogeneous strategy these are also its positions.
+pub struct SeedBuckets {
+    pub seeds: Tensor,
+    pub buckets: Tensor,
+    pub bucket_ptrs: Tensor,
+}
+
+// Splits `seeds` into `num_buckets` equally sized buckets of increasing in-degree. Ties keep the input order, unless
+// `shuffle_within` shuffles bucket b with stream b of `RngPool::new(seed)`.
+pub fn bucket_seeds_by_degree<Ptr: IndexType, Ix: IndexType>(
+    graph: &CscGraph<Ptr, Ix>,
+    seeds: &Tensor,
+    num_buckets: usize,
+    shuffle_within: bool,
+    seed: u64,
+    strategy: BatchingStrategy,
+) -> TensorResult<SeedBuckets> {
+    if num_buckets == 0 {
+        return Err(TensorConversionError::Unknown("num_buckets must be positive".to_string()));
+    }
+    let seeds = seeds.to_kind(Kind::Int64).contiguous();
+    let seeds_data = try_tensor_to_slice::<i64>(&seeds)?;
+    let n = graph.node_count() as i64;
+    if let Some(v) = seeds_data.iter().find(|&&v| v < 0 || v >= n) {
+        return Err(TensorConversionError::Unknown(format!("seed {} is out of bounds", v)));
+    }
+
+    let mut sorted = seeds_data.to_vec();
+    sorted.sort_by_key(|v| graph.in_degree(Ix::new(*v as usize)));
+    let bucket_ptrs: Vec<usize> = (0..=num_buckets).map(|b| b * sorted.len() / num_buckets).collect();
+    if shuffle_within {
+        let pool = RngPool::new(seed);
+        for (b, bucket) in bucket_ptrs.windows(2).enumerate() {
+            sorted[bucket[0]..bucket[1]].shuffle(&mut pool.stream(&[b as u64]));
+        }
+    }
+
+    let (ordered, buckets): (Vec<i64>, Vec<i64>) = match strategy {
+        BatchingStrategy::Homogeneous => bucket_ptrs.windows(2).enumerate()
+            .flat_map(|(b, bucket)| sorted[bucket[0]..bucket[1]].iter().map(move |v| (*v, b as i64)))
+            .unzip(),
+        BatchingStrategy::Balanced => {
+            let largest = bucket_ptrs.windows(2).map(|bucket| bucket[1] - bucket[0]).max().unwrap_or(0);
+            (0..largest)
+                .flat_map(|i| bucket_ptrs.windows(2).enumerate()
+                    .filter(move |(_, bucket)| bucket[0] + i < bucket[1])
+                    .map(move |(b, bucket)| (b, bucket[0] + i)))
+                .map(|(b, i)| (sorted[i], b as i64))
+                .unzip()
+        }
+    };
+
+    let bucket_ptrs: Vec<i64> = bucket_ptrs.iter().map(|ptr| *ptr as i64).collect();
+    Ok(SeedBuckets {
+        seeds: Tensor::of_slice(&ordered),
+        buckets: Tensor::of_slice(&buckets),
+        bucket_ptrs: Tensor::of_slice(&bucket_ptrs),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use tch::{Device, Kind, Tensor};
+    use crate::data::{CooGraphStorage, CscGraph, CscGraphStorage, CsrGraph, CsrGraphStorage};
+
+    fn undirected_graph(n: i64, edges: &[(i64, i64)]) -> CscGraphStorage {
+        let mut rows = Vec::new();
+        let mut cols = Vec::new();
+        for (u, v) in edges.iter().cloned() {
+            rows.extend_from_slice(&[u, v]);
+            cols.extend_from_slice(&[v, u]);
+        }
+        let row_col = Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0);
+        CscGraphStorage::try_from(&CooGraphStorage::new(row_col, (n, n))).unwrap()
+    }
+
+    #[test]
+    fn test_degree_histogram() {
+        // Star around node 0 with an extra isolated node 5
+        let graph_data = undirected_graph(6, &[(0, 1), (0, 2), (0, 3), (0, 4)]);
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let hist: Vec<i64> = super::degree_histogram(&graph, None).unwrap().into();
+        assert_eq!(hist, vec![1, 4, 0, 0, 1]);
+        let hist: Vec<i64> = super::degree_histogram(&graph, Some(3)).unwrap().into();
+        assert_eq!(hist, vec![1, 4, 1]);
+        assert!(super::degree_histogram(&graph, Some(1)).is_err());
+
+        let summary = super::graph_summary(&graph);
+        assert_eq!(summary.node_count, 6);
+        assert_eq!(summary.edge_count, 8);
+        assert_eq!(summary.min_degree, 0);
+        assert_eq!(summary.max_degree, 4);
+        assert_eq!(summary.median_degree, 1.0);
+        assert!((summary.mean_degree - 8.0 / 6.0).abs() < 1e-9);
+        assert_eq!(summary.isolated_nodes, 1);
+        let expected_alpha = 1.0 + 5.0 / (7.0 * 2.0_f64.ln());
+        assert!((summary.power_law_alpha.unwrap() - expected_alpha).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_graph_summary_regular() {
+        // A cycle where every node has degree 2
+        let edges: Vec<(i64, i64)> = (0..5).map(|i| (i, (i + 1) % 5)).collect();
+        let graph_data = undirected_graph(5, &edges);
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let hist: Vec<i64> = super::degree_histogram(&graph, None).unwrap().into();
+        assert_eq!(hist, vec![0, 0, 5]);
+
+        let summary = super::graph_summary(&graph);
+        assert_eq!((summary.min_degree, summary.max_degree), (2, 2));
+        assert_eq!(summary.mean_degree, 2.0);
+        assert_eq!(summary.median_degree, 2.0);
+        assert_eq!(summary.isolated_nodes, 0);
+        let expected_alpha = 1.0 + 1.0 / (2.0 / 1.5_f64).ln();
+        assert!((summary.power_law_alpha.unwrap() - expected_alpha).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_degree_assortativity() {
+        // Leaves only connect to the hub
+        let graph_data = undirected_graph(4, &[(0, 1), (0, 2), (0, 3)]);
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        assert!((super::degree_assortativity(&graph).unwrap() + 1.0).abs() < 1e-12);
+
+        let graph_data = undirected_graph(4, &[(0, 1), (1, 2), (2, 3)]);
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        assert!((super::degree_assortativity(&graph).unwrap() + 0.5).abs() < 1e-12);
+
+        // Undefined for regular graphs and graphs without edges
+        let edges: Vec<(i64, i64)> = (0..5).map(|i| (i, (i + 1) % 5)).collect();
+        let graph_data = undirected_graph(5, &edges);
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        assert_eq!(super::degree_assortativity(&graph), None);
+        let graph_data = undirected_graph(3, &[]);
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        assert_eq!(super::degree_assortativity(&graph), None);
+    }
+
+    #[test]
+    fn test_label_homophily() {
+        use super::HomophilyKind;
+
+        let homophily = |graph: &CscGraph, labels: &[i64], kind| {
+            super::label_homophily(graph, &Tensor::of_slice(labels), kind).unwrap()
+        };
+        let close = |a: Option<f64>, b: f64| (a.unwrap() - b).abs() < 1e-12;
+
+        // Triangle 0, 1, 2 with the tail 2 - 3 - 5, node 4 hangs off node 0 and is unlabeled
+        let graph_data = undirected_graph(6, &[(0, 1), (0, 2), (1, 2), (2, 3), (3, 5), (0, 4)]);
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let labels = [0, 0, 1, 1, -1, 1];
+        assert!(close(homophily(&graph, &labels, HomophilyKind::Edge), 0.6));
+        assert!(close(homophily(&graph, &labels, HomophilyKind::Node), 2.0 / 3.0));
+        // Class 0: h = 2 / 4 with 2 of 5 nodes, class 1: h = 4 / 6 with 3 of 5 nodes
+        assert!(close(homophily(&graph, &labels, HomophilyKind::ClassInsensitive), 0.1 + 1.0 / 15.0));
+        // Only the labels that occur are classes
+        let sparse_labels = [0, 0, 5, 5, -1, 5];
+        assert!(close(homophily(&graph, &sparse_labels, HomophilyKind::ClassInsensitive), 0.1 + 1.0 / 15.0));
+
+        // A single label is perfectly homophilous
+        let labels = [3, 3, 3, 3, 3, 3];
+        assert!(close(homophily(&graph, &labels, HomophilyKind::Edge), 1.0));
+        assert!(close(homophily(&graph, &labels, HomophilyKind::Node), 1.0));
+        assert_eq!(homophily(&graph, &[0, 0, 0, 0, 0, 0], HomophilyKind::ClassInsensitive), None);
+        assert_eq!(homophily(&graph, &labels, HomophilyKind::ClassInsensitive), None);
+
+        // Alternating labels on an even cycle never agree
+        let edges: Vec<(i64, i64)> = (0..6).map(|i| (i, (i + 1) % 6)).collect();
+        let graph_data = undirected_graph(6, &edges);
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let labels = [0, 1, 0, 1, 0, 1];
+        for kind in [HomophilyKind::Edge, HomophilyKind::Node, HomophilyKind::ClassInsensitive] {
+            assert!(close(homophily(&graph, &labels, kind), 0.0));
+        }
+
+        assert_eq!(homophily(&graph, &[-1; 6], HomophilyKind::Edge), None);
+        assert!(super::label_homophily(&graph, &Tensor::of_slice(&[0_i64, 1]), HomophilyKind::Edge).is_err());
+        assert!(super::label_homophily(&graph, &Tensor::of_slice(&[0_i64, 1, 0, 1, 0, -2]), HomophilyKind::Edge).is_err());
+    }
+
+    #[test]
+    fn test_coo_degree() {
+        use super::Direction;
+
+        let rows = Tensor::of_slice(&[0_i64, 0, 1, 2, 2, 2, 2]);
+        let cols = Tensor::of_slice(&[1_i64, 2, 0, 0, 1, 3, 3]);
+        let coo = CooGraphStorage::new(Tensor::stack(&[rows, cols], 0), (5, 5));
+        let csc_data = CscGraphStorage::try_from(&coo).unwrap();
+        let csc = CscGraph::<i64, i64>::try_from(&csc_data).unwrap();
+        let csr_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let csr = CsrGraph::<i64, i64>::try_from(&csr_data).unwrap();
+
+        let in_degree: Vec<i64> = super::coo_degree(&coo, Direction::In).into();
+        let expected: Vec<i64> = (0..5).map(|i| csc.in_degree(i) as i64).collect();
+        assert_eq!(in_degree, expected);
+        assert_eq!(in_degree, vec![2, 2, 1, 2, 0]);
+
+        let out_degree: Vec<i64> = super::coo_degree(&coo, Direction::Out).into();
+        let expected: Vec<i64> = (0..5).map(|i| csr.out_degree(i) as i64).collect();
+        assert_eq!(out_degree, expected);
+
+        let empty = CooGraphStorage::new(Tensor::zeros(&[2, 0], (Kind::Int64, Device::Cpu)), (3, 3));
+        let degree: Vec<i64> = super::coo_degree(&empty, Direction::In).into();
+        assert_eq!(degree, vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn test_weighted_degree() {
+        let rows = Tensor::of_slice(&[0_i64, 0, 1, 2, 2, 2]);
+        let cols = Tensor::of_slice(&[1_i64, 2, 0, 0, 1, 3]);
+        let coo = CooGraphStorage::new(Tensor::stack(&[rows, cols], 0), (4, 4));
+        let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let ones = Tensor::ones(&[graph.edge_count() as i64], (Kind::Float, Device::Cpu));
+        let strength: Vec<f32> = super::weighted_degree(&graph, &ones).unwrap().into();
+        let degree: Vec<f32> = (0..4).map(|i| graph.out_degree(i) as f32).collect();
+        assert_eq!(strength, degree);
+
+        let weights = Tensor::of_slice(&[0.5_f64, 1.5, 2.0, 1.0, 1.0, 0.25]);
+        let strength: Vec<f64> = super::weighted_degree(&graph, &weights).unwrap().into();
+        assert_eq!(strength, vec![2.0, 2.0, 2.25, 0.0]);
+
+        assert!(super::weighted_degree(&graph, &Tensor::of_slice(&[1.0_f64])).is_err());
+    }
+
+    #[test]
+    fn test_bucket_seeds_by_degree() {
+        use super::BatchingStrategy;
+
+        let (_x, _, coo) = crate::data::load_karate_graph();
+        let graph_data = CscGraphStorage::try_from(&coo).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let seeds: Vec<i64> = (0..34).rev().collect();
+        let bucket = |shuffle_within, seed, strategy| {
+            let buckets = super::bucket_seeds_by_degree(&graph, &Tensor::of_slice(&seeds), 4, shuffle_within, seed, strategy).unwrap();
+            let ordered: Vec<i64> = buckets.seeds.into();
+            let ids: Vec<i64> = buckets.buckets.into();
+            let ptrs: Vec<i64> = buckets.bucket_ptrs.into();
+            (ordered, ids, ptrs)
+        };
+
+        for shuffle_within in [false, true] {
+            let (ordered, ids, ptrs) = bucket(shuffle_within, 0, BatchingStrategy::Homogeneous);
+            assert_eq!(ptrs, vec![0, 8, 17, 25, 34]);
+            let mut sorted = ordered.clone();
+            sorted.sort_unstable();
+            assert_eq!(sorted, (0..34).collect::<Vec<_>>());
+
+            // Every bucket is contiguous and its degrees lie below those of the next bucket
+            let degrees: Vec<usize> = ordered.iter().map(|v| graph.in_degree(*v)).collect();
+            for b in 0..4 {
+                let (begin, end) = (ptrs[b] as usize, ptrs[b + 1] as usize);
+                assert!(ids[begin..end].iter().all(|id| *id == b as i64));
+                if b < 3 {
+                    let max = degrees[begin..end].iter().max().unwrap();
+                    assert!(max <= degrees[end..ptrs[b + 2] as usize].iter().min().unwrap());
+                }
+            }
+            if !shuffle_within {
+                assert!(degrees.windows(2).all(|w| w[0] <= w[1]));
+            }
+        }
+
+        // Balanced batches take one seed of every bucket in turn from the same buckets
+        let (homogeneous, homogeneous_ids, _) = bucket(true, 3, BatchingStrategy::Homogeneous);
+        let (balanced, ids, ptrs) = bucket(true, 3, BatchingStrategy::Balanced);
+        assert_eq!(ptrs, vec![0, 8, 17, 25, 34]);
+        assert_eq!(&ids[..8], &[0, 1, 2, 3, 0, 1, 2, 3]);
+        for b in 0..4 {
+            let members: Vec<i64> = balanced.iter().zip(ids.iter()).filter(|(_, id)| **id == b).map(|(v, _)| *v).collect();
+            let expected: Vec<i64> = homogeneous.iter().zip(homogeneous_ids.iter()).filter(|(_, id)| **id == b).map(|(v, _)| *v).collect();
+            assert_eq!(members, expected);
+        }
+
+        // Shuffling only depends on the seed
+        assert_eq!(bucket(true, 7, BatchingStrategy::Homogeneous), bucket(true, 7, BatchingStrategy::Homogeneous));
+        assert_ne!(bucket(true, 7, BatchingStrategy::Homogeneous).0, bucket(true, 8, BatchingStrategy::Homogeneous).0);
+        assert_eq!(bucket(false, 7, BatchingStrategy::Balanced), bucket(false, 8, BatchingStrategy::Balanced));
+
+        assert!(super::bucket_seeds_by_degree(&graph, &Tensor::of_slice(&seeds), 0, false, 0, BatchingStrategy::Balanced).is_err());
+        assert!(super::bucket_seeds_by_degree(&graph, &Tensor::of_slice(&[34_i64]), 2, false, 0, BatchingStrategy::Balanced).is_err());
+        let empty = super::bucket_seeds_by_degree(&graph, &Tensor::of_slice::<i64>(&[]), 3, true, 0, BatchingStrategy::Balanced).unwrap();
+        assert_eq!(empty.seeds.size(), vec![0]);
+    }
+}
@@ -0,0 +1,300 @@
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use rayon::prelude::*;
+use tch::Tensor;
+use crate::data::{CscGraph, CscGraphStorage, NeighborOrdering};
+use crate::utils::{NodeIdx, TensorConversionError, TensorResult, parallel};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiffusionMethod {
+    // Approximate personalized PageRank through residual pushes, the error per entry is at most eps * degree
+    Ppr { alpha: f64, eps: f64 },
+    // Heat kernel exp(-t) sum_k t^k / k! T^k truncated after `num_terms` terms, entries below eps are pruned
+    // after every step
+    Heat { t: f64, num_terms: usize, eps: f64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SparsifyMethod {
+    // Keeps the k largest entries of every column
+    TopK(usize),
+    // Keeps the entries of at least the given value
+    Threshold(f64),
+}
+
+fn degree(graph: &CscGraph, v: NodeIdx) -> usize {
+    graph.neighbors_slice(v).len()
+}
+
+// Push-based PPR from `seed`, a residual of node u is pushed once it reaches eps * deg(u)
+fn ppr_push(graph: &CscGraph, seed: NodeIdx, alpha: f64, eps: f64) -> HashMap<NodeIdx, f64> {
+    let mut p: HashMap<NodeIdx, f64> = HashMap::new();
+    let mut r: HashMap<NodeIdx, f64> = HashMap::new();
+    let mut queue = VecDeque::new();
+    r.insert(seed, 1.0);
+    queue.push_back(seed);
+
+    while let Some(u) = queue.pop_front() {
+        let residual = r.remove(&u).unwrap_or(0.0);
+        let d = degree(graph, u);
+        *p.entry(u).or_insert(0.0) += alpha * residual;
+        // Mass reaching nodes without neighbors leaves the walk, the same as the zero column of T
+        if d == 0 {
+            continue;
+        }
+
+        let share = (1.0 - alpha) * residual / d as f64;
+        for v in graph.neighbors_slice(u) {
+            let r_v = r.entry(*v).or_insert(0.0);
+            let was_queued = *r_v >= eps * degree(graph, *v).max(1) as f64;
+            *r_v += share;
+            if !was_queued && *r_v >= eps * degree(graph, *v).max(1) as f64 {
+                queue.push_back(*v);
+            }
+        }
+    }
+
+    p
+}
+
+fn heat_series(graph: &CscGraph, seed: NodeIdx, t: f64, num_terms: usize, eps: f64) -> HashMap<NodeIdx, f64> {
+    let mut h: HashMap<NodeIdx, f64> = HashMap::new();
+    let mut x: HashMap<NodeIdx, f64> = HashMap::new();
+    x.insert(seed, 1.0);
+
+    let mut coefficient = (-t).exp();
+    for k in 0..num_terms {
+        for (v, value) in x.iter() {
+            *h.entry(*v).or_insert(0.0) += coefficient * value;
+        }
+        coefficient *= t / (k + 1) as f64;
+
+        let mut next: HashMap<NodeIdx, f64> = HashMap::new();
+        for (u, value) in x.iter() {
+            let neighbors = graph.neighbors_slice(*u);
+            for v in neighbors {
+                *next.entry(*v).or_insert(0.0) += value / neighbors.len() as f64;
+            }
+        }
+        next.retain(|_, value| *value >= eps);
+        x = next;
+    }
+
+    h
+}
+
+fn sparsify_column(column: HashMap<NodeIdx, f64>, sparsify: SparsifyMethod) -> Vec<(NodeIdx, f64)> {
+    let mut entries: Vec<(NodeIdx, f64)> = column.into_iter().collect();
+    match sparsify {
+        SparsifyMethod::TopK(k) => {
+            entries.sort_unstable_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+            entries.truncate(k);
+        }
+        SparsifyMethod::Threshold(eps) => entries.retain(|(_, value)| *value >= eps),
+    }
+    entries.sort_unstable_by_key(|(v, _)| *v);
+    entries
+}
+
+// Graph diffusion convolution preprocessing with the random walk transition matrix T = A D^-1, walks follow
+// the stored neighbors so the graph is typically undirected. Column s of the result holds the diffusion
+// vector of seed s after sparsification, the weights are aligned with the edge positions. Every seed is
+// diffused and pruned on its own in parallel, so the dense diffusion matrix is never materialized.
+pub fn gdc(
+    storage: &CscGraphStorage,
+    method: DiffusionMethod,
+    sparsify: SparsifyMethod,
+) -> TensorResult<(CscGraphStorage, Tensor)> {
+    match method {
+        DiffusionMethod::Ppr { alpha, eps } => {
+            if !(alpha > 0.0 && alpha < 1.0) {
+                return Err(TensorConversionError::Unknown(format!("alpha must be in (0, 1), got {}", alpha)));
+            }
+            if eps.is_nan() || eps <= 0.0 {
+                return Err(TensorConversionError::Unknown(format!("eps must be positive, got {}", eps)));
+            }
+        }
+        DiffusionMethod::Heat { t, eps, .. } => {
+            if t.is_nan() || t < 0.0 || eps.is_nan() || eps < 0.0 {
+                return Err(TensorConversionError::Unknown(format!("t and eps must not be negative, got {} and {}", t, eps)));
+            }
+        }
+    }
+    let graph = CscGraph::<i64, i64>::try_from(storage)?;
+    let n = graph.node_count();
+    if let Some(v) = graph.indices.iter().find(|&&v| v < 0 || v as usize >= n) {
+        return Err(TensorConversionError::Unknown(format!("node {} is out of bounds for a square graph of {} nodes", v, n)));
+    }
+
+    let columns: Vec<Vec<(NodeIdx, f64)>> = parallel::install(|| (0..n as NodeIdx).into_par_iter()
+        .map(|seed| {
+            let column = match method {
+                DiffusionMethod::Ppr { alpha, eps } => ppr_push(&graph, seed, alpha, eps),
+                DiffusionMethod::Heat { t, num_terms, eps } => heat_series(&graph, seed, t, num_terms, eps),
+            };
+            sparsify_column(column, sparsify)
+        })
+        .collect());
+
+    let mut ptrs = Vec::with_capacity(n + 1);
+    let mut indices = Vec::new();
+    let mut weights = Vec::new();
+    ptrs.push(0_i64);
+    for column in columns {
+        for (v, value) in column {
+            indices.push(v);
+            weights.push(value);
+        }
+        ptrs.push(indices.len() as i64);
+    }
+
+    Ok((
+        CscGraphStorage::new(Tensor::of_slice(&ptrs), Tensor::of_slice(&indices), None)
+            .with_ordering(NeighborOrdering::ById),
+        Tensor::of_slice(&weights),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::SmallRng;
+    use tch::Tensor;
+    use crate::algo::gdc::{DiffusionMethod, SparsifyMethod, gdc};
+    use crate::data::{CooGraphStorage, CscGraphStorage};
+
+    // Dense transition matrix T[v][u] = 1 / deg(u) for every neighbor v of u
+    fn transition(n: usize, adj: &[Vec<bool>]) -> Vec<Vec<f64>> {
+        let mut t = vec![vec![0.0; n]; n];
+        for (u, row) in adj.iter().enumerate() {
+            let d = row.iter().filter(|x| **x).count();
+            for (v, is_edge) in row.iter().enumerate() {
+                if *is_edge {
+                    t[v][u] = 1.0 / d as f64;
+                }
+            }
+        }
+        t
+    }
+
+    fn inverse(mut a: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+        let n = a.len();
+        let mut inv: Vec<Vec<f64>> = (0..n).map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect();
+        for col in 0..n {
+            let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().partial_cmp(&a[j][col].abs()).unwrap()).unwrap();
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+            let scale = a[col][col];
+            for j in 0..n {
+                a[col][j] /= scale;
+                inv[col][j] /= scale;
+            }
+            for i in 0..n {
+                if i != col && a[i][col] != 0.0 {
+                    let factor = a[i][col];
+                    for j in 0..n {
+                        a[i][j] -= factor * a[col][j];
+                        inv[i][j] -= factor * inv[col][j];
+                    }
+                }
+            }
+        }
+        inv
+    }
+
+    fn to_dense(n: usize, storage: &CscGraphStorage, weights: &Tensor) -> Vec<Vec<f64>> {
+        let ptrs: Vec<i64> = (&storage.ptrs).into();
+        let indices: Vec<i64> = (&storage.indices).into();
+        let weights: Vec<f64> = weights.into();
+        let mut dense = vec![vec![0.0; n]; n];
+        for col in 0..n {
+            for e in ptrs[col] as usize..ptrs[col + 1] as usize {
+                dense[indices[e] as usize][col] = weights[e];
+            }
+        }
+        dense
+    }
+
+    #[test]
+    fn test_gdc() {
+        let n = 50;
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut adj = vec![vec![false; n]; n];
+        let (mut rows, mut cols) = (Vec::new(), Vec::new());
+        // A path keeps the graph connected
+        let edges: Vec<(usize, usize)> = (0..n)
+            .flat_map(|u| (u + 1..n).map(move |v| (u, v)))
+            .filter(|(u, v)| *v == u + 1 || rng.gen_bool(0.08))
+            .collect();
+        for (u, v) in edges {
+            adj[u][v] = true;
+            adj[v][u] = true;
+            rows.extend_from_slice(&[u as i64, v as i64]);
+            cols.extend_from_slice(&[v as i64, u as i64]);
+        }
+        let coo = CooGraphStorage::new(Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0), (n as i64, n as i64));
+        let storage = CscGraphStorage::try_from(&coo).unwrap();
+        let t = transition(n, &adj);
+
+        // Closed form PPR: alpha (I - (1 - alpha) T)^-1
+        let alpha = 0.15;
+        let system: Vec<Vec<f64>> = (0..n)
+            .map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 } - (1.0 - alpha) * t[i][j]).collect())
+            .collect();
+        let expected: Vec<Vec<f64>> = inverse(system).into_iter()
+            .map(|row| row.into_iter().map(|x| alpha * x).collect())
+            .collect();
+
+        let eps = 1e-7;
+        let (out, weights) = gdc(&storage, DiffusionMethod::Ppr { alpha, eps }, SparsifyMethod::Threshold(0.0)).unwrap();
+        out.validate().unwrap();
+        let dense = to_dense(n, &out, &weights);
+        for i in 0..n {
+            for j in 0..n {
+                assert!((dense[i][j] - expected[i][j]).abs() < 1e-4, "({}, {}): {} vs {}", i, j, dense[i][j], expected[i][j]);
+            }
+        }
+
+        // Top-k keeps the largest entries of every column
+        let k = 5;
+        let (out, weights) = gdc(&storage, DiffusionMethod::Ppr { alpha, eps }, SparsifyMethod::TopK(k)).unwrap();
+        let ptrs: Vec<i64> = (&out.ptrs).into();
+        let indices: Vec<i64> = (&out.indices).into();
+        let weights: Vec<f64> = weights.into();
+        for col in 0..n {
+            let range = ptrs[col] as usize..ptrs[col + 1] as usize;
+            assert_eq!(range.len(), k);
+            assert!(indices[range.clone()].windows(2).all(|w| w[0] < w[1]));
+            let smallest_kept = weights[range].iter().cloned().fold(f64::INFINITY, f64::min);
+            let mut column: Vec<f64> = (0..n).map(|i| expected[i][col]).collect();
+            column.sort_by(|a, b| b.partial_cmp(a).unwrap());
+            assert!((smallest_kept - column[k - 1]).abs() < 1e-4);
+        }
+
+        // Heat kernel against the dense truncated series
+        let (time, num_terms) = (2.0_f64, 20);
+        let mut expected = vec![vec![0.0; n]; n];
+        let mut power: Vec<Vec<f64>> = (0..n).map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect();
+        let mut coefficient = (-time).exp();
+        for k in 0..num_terms {
+            for i in 0..n {
+                for j in 0..n {
+                    expected[i][j] += coefficient * power[i][j];
+                }
+            }
+            coefficient *= time / (k + 1) as f64;
+            power = (0..n).map(|i| (0..n).map(|j| (0..n).map(|l| t[i][l] * power[l][j]).sum()).collect()).collect();
+        }
+        let (out, weights) = gdc(&storage, DiffusionMethod::Heat { t: time, num_terms, eps: 1e-9 }, SparsifyMethod::Threshold(1e-4)).unwrap();
+        let dense = to_dense(n, &out, &weights);
+        for i in 0..n {
+            for j in 0..n {
+                let expected = if expected[i][j] >= 1e-4 { expected[i][j] } else { 0.0 };
+                assert!((dense[i][j] - expected).abs() < 1e-6);
+            }
+        }
+
+        assert!(gdc(&storage, DiffusionMethod::Ppr { alpha: 1.5, eps }, SparsifyMethod::TopK(k)).is_err());
+    }
+}
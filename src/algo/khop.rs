@@ -0,0 +1,775 @@
+use std::collections::HashMap;
+use rayon::prelude::*;
+use tch::{Kind, Tensor};
+use crate::data::{CooGraphStorage, CscGraph, CsrGraph, SparseGraph};
+use crate::utils::{NodeIdx, parallel, TensorConversionError, TensorResult, try_tensor_to_slice, try_tensor_to_slice_mut};
+
+fn check_seeds<Ty>(graph: &SparseGraph<Ty>, seeds: &[NodeIdx]) -> TensorResult<()> {
+    if let Some(v) = seeds.iter().find(|&&v| !(0..graph.node_count() as NodeIdx).contains(&v)) {
+        return Err(TensorConversionError::Unknown(format!("seed {} is out of bounds", v)));
+    }
+    Ok(())
+}
+
+fn seeds_data(graph: &CscGraph, seeds: &Tensor) -> TensorResult<Vec<NodeIdx>> {
+    let seeds = seeds.to_kind(Kind::Int64).contiguous();
+    let seeds_data = try_tensor_to_slice::<i64>(&seeds)?;
+    check_seeds(graph, seeds_data)?;
+    Ok(seeds_data.to_vec())
+}
+
+struct Visited {
+    bits: Vec<u64>,
+    frontier: Vec<NodeIdx>,
+    next: Vec<NodeIdx>,
+    // Every node whose bit is set, so only those have to be cleared again
+    touched: Vec<NodeIdx>,
+}
+
+impl Visited {
+    fn new(n: usize) -> Self {
+        Visited { bits: vec![0; n / 64 + 1], frontier: Vec::new(), next: Vec::new(), touched: Vec::new() }
+    }
+
+    fn insert(&mut self, v: NodeIdx) -> bool {
+        let (word, bit) = (v as usize / 64, 1 << (v as usize % 64));
+        let is_new = self.bits[word] & bit == 0;
+        if is_new {
+            self.bits[word] |= bit;
+            self.touched.push(v);
+        }
+        is_new
+    }
+
+    fn clear(&mut self) {
+        for v in self.touched.drain(..) {
+            self.bits[v as usize / 64] = 0;
+        }
+        self.frontier.clear();
+        self.next.clear();
+    }
+}
+
+// Number of unique nodes within 1..=k hops of every seed (counting the seed itself), following the incoming edges
+// like the samplers do. Returns a [S, k] tensor of cumulative counts. Each thread keeps one visited bitset of
+// N / 8 bytes that is cleared between seeds, the node sets themselves are never materialized.
+pub fn khop_sizes(
+    graph: &CscGraph,
+    seeds: &Tensor,
+    k: usize,
+) -> TensorResult<Tensor> {
+    let seeds_data = seeds_data(graph, seeds)?;
+    let mut sizes = Tensor::zeros(&[seeds_data.len() as i64, k as i64], (Kind::Int64, seeds.device()));
+    if seeds_data.is_empty() || k == 0 {
+        return Ok(sizes);
+    }
+
+    let sizes_data = try_tensor_to_slice_mut::<i64>(&mut sizes)?;
+    expand_sizes(graph, &seeds_data, sizes_data, k);
+
+    Ok(sizes)
+}
+
+// Fills the [S, k] cumulative sizes, `sizes` has to be non empty
+fn expand_sizes<Ty: Sync>(graph: &SparseGraph<Ty>, seeds: &[NodeIdx], sizes: &mut [i64], k: usize) {
+    parallel::install(|| sizes.par_chunks_mut(k)
+        .zip(seeds.par_iter())
+        .for_each_init(|| Visited::new(graph.node_count()), |visited, (row, seed)| {
+            visited.insert(*seed);
+            visited.frontier.push(*seed);
+            for size in row.iter_mut() {
+                for i in 0..visited.frontier.len() {
+                    for u in graph.neighbors_slice(visited.frontier[i]) {
+                        if visited.insert(*u) {
+                            visited.next.push(*u);
+                        }
+                    }
+                }
+                std::mem::swap(&mut visited.frontier, &mut visited.next);
+                visited.next.clear();
+                *size = visited.touched.len() as i64;
+            }
+            visited.clear();
+        }));
+}
+
+// Number of unique nodes within `num_hops` hops of every node (counting the node itself) along the outgoing edges,
+// the size of its k hop subgraph without building it. Same bitset BFS as `khop_sizes`, only the last hop is kept.
+pub fn k_hop_size(
+    graph: &CsrGraph,
+    nodes: &[NodeIdx],
+    num_hops: i64,
+) -> TensorResult<Tensor> {
+    if num_hops < 0 {
+        return Err(TensorConversionError::Unknown(format!("num_hops must be non negative, got {}", num_hops)));
+    }
+    check_seeds(graph, nodes)?;
+    if num_hops == 0 || nodes.is_empty() {
+        return Ok(Tensor::ones(&[nodes.len() as i64], (Kind::Int64, tch::Device::Cpu)));
+    }
+
+    let k = num_hops as usize;
+    let mut sizes = vec![0; nodes.len() * k];
+    expand_sizes(graph, nodes, &mut sizes, k);
+    let last: Vec<i64> = sizes.chunks(k).map(|row| row[k - 1]).collect();
+    Ok(Tensor::of_slice(&last))
+}
+
+// The L hop ego network of an anchor node as a standalone graph: `graph` holds every edge between the retained
+// nodes in local ids, `nodes` maps the local ids (ascending global ids) back, `anchor` is the local id of the anchor
+// and `edge_mask` selects the retained edge positions of the original csc graph.
+pub struct EgoNetwork {
+    pub graph: CooGraphStorage,
+    pub nodes: Tensor,
+    pub anchor: i64,
+    pub edge_mask: Tensor,
+}
+
+struct EgoNetworkData {
+    rows: Vec<i64>,
+    cols: Vec<i64>,
+    nodes: Vec<NodeIdx>,
+    anchor: i64,
+    edge_positions: Vec<usize>,
+}
+
+fn ego_network_data(graph: &CscGraph, anchor: NodeIdx, num_hops: usize, max_nodes: usize) -> EgoNetworkData {
+    // Expand along the incoming edges hop by hop, when max_nodes binds the earlier hops and then lower ids win
+    let mut hops: HashMap<NodeIdx, usize> = HashMap::new();
+    hops.insert(anchor, 0);
+    let mut frontier = vec![anchor];
+    for hop in 1..=num_hops {
+        if frontier.is_empty() || hops.len() >= max_nodes {
+            break;
+        }
+        let mut next: Vec<NodeIdx> = frontier.iter()
+            .flat_map(|v| graph.neighbors_slice(*v).iter().cloned())
+            .filter(|u| !hops.contains_key(u))
+            .collect();
+        next.sort_unstable();
+        next.dedup();
+        next.truncate(max_nodes - hops.len());
+        for u in next.iter() {
+            hops.insert(*u, hop);
+        }
+        frontier = next;
+    }
+
+    let mut nodes: Vec<NodeIdx> = hops.keys().cloned().collect();
+    nodes.sort_unstable();
+    let local: HashMap<NodeIdx, i64> = nodes.iter().enumerate().map(|(i, v)| (*v, i as i64)).collect();
+    let (mut rows, mut cols, mut edge_positions) = (Vec::new(), Vec::new(), Vec::new());
+    for (j, v) in nodes.iter().enumerate() {
+        for p in graph.neighbors_range(*v) {
+            if let Some(i) = local.get(&graph.indices[p]) {
+                rows.push(*i);
+                cols.push(j as i64);
+                edge_positions.push(p);
+            }
+        }
+    }
+
+    EgoNetworkData { rows, cols, anchor: local[&anchor], nodes, edge_positions }
+}
+
+fn check_max_nodes(max_nodes: Option<usize>) -> TensorResult<usize> {
+    match max_nodes {
+        Some(0) => Err(TensorConversionError::Unknown("max_nodes must be positive".to_string())),
+        Some(max_nodes) => Ok(max_nodes),
+        None => Ok(usize::MAX),
+    }
+}
+
+impl EgoNetworkData {
+    fn into_ego_network(self, num_edges: usize) -> EgoNetwork {
+        let n = self.nodes.len() as i64;
+        let row_col = Tensor::stack(&[Tensor::of_slice(&self.rows), Tensor::of_slice(&self.cols)], 0);
+        let mut mask = vec![false; num_edges];
+        for p in self.edge_positions {
+            mask[p] = true;
+        }
+        EgoNetwork {
+            graph: CooGraphStorage::new(row_col, (n, n)),
+            nodes: Tensor::of_slice(&self.nodes),
+            anchor: self.anchor,
+            edge_mask: Tensor::of_slice(&mask),
+        }
+    }
+}
+
+pub fn ego_network(
+    graph: &CscGraph,
+    anchor: NodeIdx,
+    num_hops: usize,
+    max_nodes: Option<usize>,
+) -> TensorResult<EgoNetwork> {
+    let max_nodes = check_max_nodes(max_nodes)?;
+    check_seeds(graph, &[anchor])?;
+    Ok(ego_network_data(graph, anchor, num_hops, max_nodes).into_ego_network(graph.indices.len()))
+}
+
+// `ego_network` of every anchor, the networks are expanded in parallel
+pub fn ego_networks(
+    graph: &CscGraph,
+    anchors: &Tensor,
+    num_hops: usize,
+    max_nodes: Option<usize>,
+) -> TensorResult<Vec<EgoNetwork>> {
+    let max_nodes = check_max_nodes(max_nodes)?;
+    let anchors = seeds_data(graph, anchors)?;
+    let networks: Vec<EgoNetworkData> = parallel::install(|| anchors.par_iter()
+        .map(|anchor| ego_network_data(graph, *anchor, num_hops, max_nodes))
+        .collect());
+    Ok(networks.into_iter().map(|network| network.into_ego_network(graph.indices.len())).collect())
+}
+
+// SEAL subgraphs of a batch of (u, v) pairs packed back to back: pair i owns nodes[node_offsets[i]..node_offsets[i + 1]]
+// (global ids, u and v first) with their double radius labels, and the local edges
+// row_col[:, edge_offsets[i]..edge_offsets[i + 1]]. Edges between u and v themselves are left out.
+pub struct SealSubgraphs {
+    pub nodes: Tensor,
+    pub row_col: Tensor,
+    pub labels: Tensor,
+    pub node_offsets: Tensor,
+    pub edge_offsets: Tensor,
+}
+
+struct SealSubgraphData {
+    nodes: Vec<NodeIdx>,
+    rows: Vec<i64>,
+    cols: Vec<i64>,
+    labels: Vec<i64>,
+}
+
+// Hop distances from `source` over the undirected local adjacency without passing through `blocked`, -1 for nodes
+// further than `max_dist` or not reachable
+fn truncated_bfs(adj: &[Vec<usize>], source: usize, blocked: usize, max_dist: usize) -> Vec<i64> {
+    let mut dist = vec![-1; adj.len()];
+    dist[source] = 0;
+    let mut frontier = vec![source];
+    for d in 1..=max_dist {
+        let mut next = Vec::new();
+        for w in frontier.iter() {
+            for x in adj[*w].iter().cloned() {
+                if x != blocked && dist[x] < 0 {
+                    dist[x] = d as i64;
+                    next.push(x);
+                }
+            }
+        }
+        if next.is_empty() {
+            break;
+        }
+        frontier = next;
+    }
+    dist
+}
+
+// Double radius node label from the distances to both targets, 0 if either could not be reached
+fn drnl(du: i64, dv: i64) -> i64 {
+    if du < 0 || dv < 0 {
+        return 0;
+    }
+    let (half, odd) = ((du + dv) / 2, (du + dv) % 2);
+    1 + du.min(dv) + half * (half + odd - 1)
+}
+
+fn seal_subgraph_data(graph: &CscGraph, u: NodeIdx, v: NodeIdx, num_hops: usize, max_nodes: usize) -> SealSubgraphData {
+    // Union of both neighborhoods expanded together, when max_nodes binds the earlier hops and then lower ids win
+    let mut nodes = vec![u];
+    if v != u {
+        nodes.push(v);
+    }
+    let mut local: HashMap<NodeIdx, usize> = nodes.iter().enumerate().map(|(i, w)| (*w, i)).collect();
+    let mut frontier = nodes.clone();
+    for _ in 0..num_hops {
+        if frontier.is_empty() || nodes.len() >= max_nodes {
+            break;
+        }
+        let mut next: Vec<NodeIdx> = frontier.iter()
+            .flat_map(|w| graph.neighbors_slice(*w).iter().cloned())
+            .filter(|x| !local.contains_key(x))
+            .collect();
+        next.sort_unstable();
+        next.dedup();
+        next.truncate(max_nodes - nodes.len());
+        for x in next.iter() {
+            local.insert(*x, nodes.len());
+            nodes.push(*x);
+        }
+        frontier = next;
+    }
+
+    let target = local[&v];
+    let (mut rows, mut cols) = (Vec::new(), Vec::new());
+    let mut adj = vec![Vec::new(); nodes.len()];
+    for (j, w) in nodes.iter().enumerate() {
+        for x in graph.neighbors_slice(*w) {
+            match local.get(x) {
+                Some(&i) if !((i == 0 && j == target) || (i == target && j == 0)) => {
+                    rows.push(i as i64);
+                    cols.push(j as i64);
+                    adj[i].push(j);
+                    adj[j].push(i);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Distances to one target are taken with the other one removed, as SEAL does
+    let max_dist = 2 * num_hops;
+    let dist_u = truncated_bfs(&adj, 0, target, max_dist);
+    let dist_v = truncated_bfs(&adj, target, 0, max_dist);
+    let labels = (0..nodes.len())
+        .map(|i| if i == 0 || i == target { 1 } else { drnl(dist_u[i], dist_v[i]) })
+        .collect();
+
+    SealSubgraphData { nodes, rows, cols, labels }
+}
+
+// SEAL style enclosing subgraphs of the [2, P] `pairs` from the union of the `num_hops` hop neighborhoods of both
+// ends, along the incoming edges of a (symmetric) graph. Every subgraph keeps at most `max_nodes_per_pair` nodes,
+// and node labels use distances within the subgraph truncated at 2 * num_hops. Pairs are extracted in parallel.
+pub fn seal_subgraphs(
+    graph: &CscGraph,
+    pairs: &Tensor,
+    num_hops: usize,
+    max_nodes_per_pair: Option<usize>,
+) -> TensorResult<SealSubgraphs> {
+    if pairs.dim() != 2 || pairs.size()[0] != 2 {
+        return Err(TensorConversionError::InvalidShape(Some(format!("pairs must be of shape [2, P], got {:?}", pairs.size()))));
+    }
+    let max_nodes = match max_nodes_per_pair {
+        Some(max_nodes) if max_nodes < 2 => {
+            return Err(TensorConversionError::Unknown(format!("max_nodes_per_pair must be at least 2, got {}", max_nodes)));
+        }
+        Some(max_nodes) => max_nodes,
+        None => usize::MAX,
+    };
+    let pairs = pairs.to_kind(Kind::Int64).contiguous();
+    let pairs_data = try_tensor_to_slice::<i64>(&pairs)?;
+    check_seeds(graph, pairs_data)?;
+    let (us, vs) = pairs_data.split_at(pairs_data.len() / 2);
+
+    let subgraphs: Vec<SealSubgraphData> = parallel::install(|| us.par_iter().zip(vs.par_iter())
+        .map(|(u, v)| seal_subgraph_data(graph, *u, *v, num_hops, max_nodes))
+        .collect());
+
+    let (mut nodes, mut rows, mut cols, mut labels) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    let (mut node_offsets, mut edge_offsets) = (vec![0_i64], vec![0_i64]);
+    for subgraph in subgraphs {
+        nodes.extend(subgraph.nodes);
+        rows.extend(subgraph.rows);
+        cols.extend(subgraph.cols);
+        labels.extend(subgraph.labels);
+        node_offsets.push(nodes.len() as i64);
+        edge_offsets.push(rows.len() as i64);
+    }
+
+    Ok(SealSubgraphs {
+        nodes: Tensor::of_slice(&nodes),
+        row_col: Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0),
+        labels: Tensor::of_slice(&labels),
+        node_offsets: Tensor::of_slice(&node_offsets),
+        edge_offsets: Tensor::of_slice(&edge_offsets),
+    })
+}
+
+fn hash_node(v: NodeIdx) -> u64 {
+    // splitmix64 finalizer
+    let mut x = (v as u64).wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+fn hll_estimate(registers: &[u8]) -> f64 {
+    let m = registers.len() as f64;
+    let alpha = match registers.len() {
+        16 => 0.673,
+        32 => 0.697,
+        64 => 0.709,
+        _ => 0.7213 / (1.0 + 1.079 / m),
+    };
+    let sum: f64 = registers.iter().map(|r| 2.0_f64.powi(-(*r as i32))).sum();
+    let estimate = alpha * m * m / sum;
+
+    // Linear counting is more accurate for small cardinalities
+    let zeros = registers.iter().filter(|r| **r == 0).count();
+    if estimate <= 2.5 * m && zeros > 0 {
+        m * (m / zeros as f64).ln()
+    } else {
+        estimate
+    }
+}
+
+// The nodes within k hops of the seeds in order of distance, with the end of every distance in that order. Only these
+// feed into the k hop neighborhoods of the seeds.
+fn hop_layers(graph: &CscGraph, seeds: &[NodeIdx], k: usize) -> (Vec<NodeIdx>, Vec<usize>) {
+    let mut index: HashMap<NodeIdx, usize> = HashMap::new();
+    let mut nodes = Vec::new();
+    for seed in seeds {
+        if !index.contains_key(seed) {
+            index.insert(*seed, nodes.len());
+            nodes.push(*seed);
+        }
+    }
+    let mut layer_ends = vec![nodes.len()];
+    for _ in 0..k {
+        let start = layer_ends.len().checked_sub(2).map_or(0, |l| layer_ends[l]);
+        for i in start..nodes.len() {
+            for u in graph.neighbors_slice(nodes[i]) {
+                if !index.contains_key(u) {
+                    index.insert(*u, nodes.len());
+                    nodes.push(*u);
+                }
+            }
+        }
+        layer_ends.push(nodes.len());
+    }
+    (nodes, layer_ends)
+}
+
+// Approximate `khop_sizes` with HyperLogLog sketches (HyperANF): every node within k hops of the seeds holds
+// 2^precision one byte registers of its neighborhood, which is grown one hop per pass over their edges. Hop h only
+// has to update the nodes within k - 1 - h hops, so memory is two sketches per node of the k hop ball of the seeds
+// rather than of the whole graph or a bitset per thread. The relative standard error of every count is about
+// 1.04 / sqrt(2^precision).
+pub fn khop_sizes_approx(
+    graph: &CscGraph,
+    seeds: &Tensor,
+    k: usize,
+    precision: u8,
+) -> TensorResult<Tensor> {
+    if !(4..=16).contains(&precision) {
+        return Err(TensorConversionError::Unknown(format!("precision must be in [4, 16], got {}", precision)));
+    }
+    let seeds_data = seeds_data(graph, seeds)?;
+    let m = 1_usize << precision;
+    let (nodes, layer_ends) = hop_layers(graph, &seeds_data, k);
+    let index: HashMap<NodeIdx, usize> = nodes.iter().enumerate().map(|(i, v)| (*v, i)).collect();
+
+    let mut registers = vec![0_u8; nodes.len() * m];
+    for (v, sketch) in nodes.iter().zip(registers.chunks_mut(m)) {
+        let hash = hash_node(*v);
+        let rank = ((hash << precision) | (1 << (precision - 1))).leading_zeros() + 1;
+        sketch[(hash >> (64 - precision)) as usize] = rank as u8;
+    }
+
+    let mut sizes = vec![0.0; seeds_data.len() * k];
+    let mut next = registers.clone();
+    for hop in 0..k {
+        let active = layer_ends[k - 1 - hop];
+        parallel::install(|| next[..active * m].par_chunks_mut(m).enumerate().for_each(|(i, sketch)| {
+            sketch.copy_from_slice(&registers[i * m..(i + 1) * m]);
+            for u in graph.neighbors_slice(nodes[i]) {
+                let u = index[u];
+                let other = &registers[u * m..(u + 1) * m];
+                sketch.iter_mut().zip(other.iter()).for_each(|(r, o)| *r = (*r).max(*o));
+            }
+        }));
+        std::mem::swap(&mut registers, &mut next);
+
+        for (i, seed) in seeds_data.iter().enumerate() {
+            let v = index[seed];
+            sizes[i * k + hop] = hll_estimate(&registers[v * m..(v + 1) * m]);
+        }
+    }
+
+    Ok(Tensor::of_slice(&sizes).view([seeds_data.len() as i64, k as i64]).to_device(seeds.device()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::convert::TryFrom;
+    use rand::{Rng, SeedableRng};
+    use tch::Tensor;
+    use crate::data::{CooGraphStorage, CscGraph, CscGraphStorage, CsrGraph, CsrGraphStorage, load_karate_graph};
+    use crate::data::transform::subgraph;
+    use crate::utils::NodeIdx;
+    use super::{ego_network, ego_networks, hop_layers, k_hop_size, khop_sizes, khop_sizes_approx, seal_subgraphs};
+
+    fn expand_sizes(graph: &CscGraph, seed: NodeIdx, k: usize) -> Vec<i64> {
+        let mut nodes: HashSet<NodeIdx> = [seed].iter().cloned().collect();
+        (0..k).map(|_| {
+            let neighbors: Vec<NodeIdx> = nodes.iter().flat_map(|v| graph.neighbors_slice(*v).iter().cloned()).collect();
+            nodes.extend(neighbors);
+            nodes.len() as i64
+        }).collect()
+    }
+
+    #[test]
+    fn test_khop_sizes() {
+        let (_x, _, coo) = load_karate_graph();
+        let graph_data = CscGraphStorage::try_from(&coo).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let seeds: Vec<i64> = (0..34).collect();
+        let sizes = khop_sizes(&graph, &Tensor::of_slice(&seeds), 3).unwrap();
+        assert_eq!(sizes.size(), vec![34, 3]);
+        let sizes: Vec<i64> = sizes.view([-1]).into();
+        for (i, seed) in seeds.iter().enumerate() {
+            assert_eq!(sizes[i * 3..(i + 1) * 3].to_vec(), expand_sizes(&graph, *seed, 3));
+        }
+
+        // Directed path 0 -> 1 -> 2 -> 3, node 3 reaches the others through its incoming edges
+        let coo = CooGraphStorage::new(Tensor::of_slice(&[0_i64, 1, 2, 1, 2, 3]).view([2, 3]), (4, 4));
+        let graph_data = CscGraphStorage::try_from(&coo).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let sizes: Vec<i64> = khop_sizes(&graph, &Tensor::of_slice(&[3_i64, 0]), 4).unwrap().view([-1]).into();
+        assert_eq!(sizes, vec![2, 3, 4, 4, 1, 1, 1, 1]);
+
+        assert_eq!(khop_sizes(&graph, &Tensor::of_slice(&[3_i64]), 0).unwrap().size(), vec![1, 0]);
+        assert!(khop_sizes(&graph, &Tensor::of_slice(&[4_i64]), 2).is_err());
+    }
+
+    #[test]
+    fn test_k_hop_size() {
+        // Directed, so outgoing and incoming neighborhoods differ
+        let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+        let n = 40;
+        let rows: Vec<i64> = (0..80).map(|_| rng.gen_range(0..n)).collect();
+        let cols: Vec<i64> = (0..80).map(|_| rng.gen_range(0..n)).collect();
+        let coo = CooGraphStorage::new(Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0), (n, n));
+        let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let nodes: Vec<i64> = (0..n).collect();
+        for num_hops in 0..4 {
+            let sizes: Vec<i64> = k_hop_size(&graph, &nodes, num_hops).unwrap().into();
+            for (node, size) in nodes.iter().zip(sizes) {
+                // Materialize the k hop subgraph and measure it
+                let mut reached: HashSet<NodeIdx> = [*node].iter().cloned().collect();
+                let mut frontier = vec![*node];
+                for _ in 0..num_hops {
+                    frontier = frontier.iter()
+                        .flat_map(|v| graph.neighbors_slice(*v).iter().cloned())
+                        .filter(|u| reached.insert(*u))
+                        .collect();
+                }
+                let reached: Vec<NodeIdx> = reached.into_iter().collect();
+                let (sub, _) = subgraph(&coo, &Tensor::of_slice(&reached)).unwrap();
+                assert_eq!(size, sub.size.0);
+            }
+        }
+
+        // Outgoing edges of the graph are the incoming ones of its transpose
+        let transposed = CooGraphStorage::new(coo.row_col.flip(&[0]), (n, n));
+        let csc_data = CscGraphStorage::try_from(&transposed).unwrap();
+        let csc = CscGraph::<i64, i64>::try_from(&csc_data).unwrap();
+        let expected = khop_sizes(&csc, &Tensor::of_slice(&nodes), 3).unwrap().select(1, 2);
+        assert!(k_hop_size(&graph, &nodes, 3).unwrap().equal(&expected));
+
+        assert_eq!(k_hop_size(&graph, &[], 2).unwrap().size(), vec![0]);
+        assert!(k_hop_size(&graph, &[n], 2).is_err());
+        assert!(k_hop_size(&graph, &[0], -1).is_err());
+    }
+
+    #[test]
+    fn test_ego_network() {
+        let (_x, _, coo) = load_karate_graph();
+        let graph_data = CscGraphStorage::try_from(&coo).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let ptrs: Vec<i64> = graph_data.ptrs.shallow_clone().into();
+
+        let anchors: Vec<i64> = (0..34).collect();
+        let sizes: Vec<i64> = khop_sizes(&graph, &Tensor::of_slice(&anchors), 2).unwrap().select(1, 1).into();
+        let networks = ego_networks(&graph, &Tensor::of_slice(&anchors), 2, None).unwrap();
+        for ((anchor, size), network) in anchors.iter().zip(sizes).zip(networks.iter()) {
+            let nodes: Vec<i64> = network.nodes.shallow_clone().into();
+            assert_eq!(nodes.len() as i64, size);
+            assert_eq!(nodes[network.anchor as usize], *anchor);
+
+            // Every edge is between retained nodes and is one of the masked edges of the original graph
+            let rows: Vec<i64> = network.graph.row().into();
+            let cols: Vec<i64> = network.graph.col().into();
+            let mask: Vec<bool> = network.edge_mask.shallow_clone().into();
+            let positions: Vec<usize> = (0..mask.len()).filter(|p| mask[*p]).collect();
+            assert_eq!(positions.len(), rows.len());
+            for ((i, j), p) in rows.iter().zip(cols.iter()).zip(positions) {
+                assert!(*i < size && *j < size);
+                assert_eq!(nodes[*i as usize], graph.indices[p]);
+                assert_eq!(nodes[*j as usize], ptrs.partition_point(|ptr| *ptr <= p as i64) as i64 - 1);
+            }
+            // The induced subgraph has all edges between the retained nodes
+            let expected = graph.indices.len() - (0..34)
+                .flat_map(|v| graph.neighbors_slice(v).iter().map(move |u| (*u, v)))
+                .filter(|(u, v)| !nodes.contains(u) || !nodes.contains(v))
+                .count();
+            assert_eq!(rows.len(), expected);
+        }
+
+        // Truncation keeps the anchor, then its lowest id neighbors, the same way every time
+        let network = ego_network(&graph, 33, 2, Some(5)).unwrap();
+        let nodes: Vec<i64> = network.nodes.shallow_clone().into();
+        let mut expected: Vec<i64> = graph.neighbors_slice(33)[..4].to_vec();
+        expected.push(33);
+        expected.sort_unstable();
+        assert_eq!(nodes, expected);
+        assert_eq!(network.anchor, 4);
+        for _ in 0..3 {
+            let again = ego_networks(&graph, &Tensor::of_slice(&[33_i64, 0]), 2, Some(5)).unwrap();
+            assert!(again[0].nodes.equal(&network.nodes));
+            assert!(again[0].graph.row_col.equal(&network.graph.row_col));
+            assert!(again[0].edge_mask.equal(&network.edge_mask));
+        }
+        assert_eq!(ego_network(&graph, 7, 0, None).unwrap().nodes.size(), vec![1]);
+
+        assert!(ego_network(&graph, 34, 2, None).is_err());
+        assert!(ego_network(&graph, 0, 2, Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_khop_sizes_approx() {
+        // Random undirected graph large enough to leave the linear counting range
+        let n = 3000;
+        let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+        let edges: Vec<(i64, i64)> = (0..2 * n).map(|_| (rng.gen_range(0..n), rng.gen_range(0..n))).collect();
+        let rows: Vec<i64> = edges.iter().flat_map(|(u, v)| [*u, *v]).collect();
+        let cols: Vec<i64> = edges.iter().flat_map(|(u, v)| [*v, *u]).collect();
+        let coo = CooGraphStorage::new(Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0), (n, n));
+        let graph_data = CscGraphStorage::try_from(&coo).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let seeds = Tensor::of_slice(&(0..50_i64).collect::<Vec<_>>());
+        let exact: Vec<i64> = khop_sizes(&graph, &seeds, 4).unwrap().view([-1]).into();
+        for precision in [8_u8, 10] {
+            let approx: Vec<f64> = khop_sizes_approx(&graph, &seeds, 4, precision).unwrap().view([-1]).into();
+            let std_error = 1.04 / ((1 << precision) as f64).sqrt();
+            let errors: Vec<f64> = exact.iter().zip(approx.iter())
+                .map(|(e, a)| (a - *e as f64).abs() / *e as f64)
+                .collect();
+            assert!(errors.iter().all(|e| *e < 5.0 * std_error), "{:?}", errors);
+            assert!(errors.iter().sum::<f64>() / (errors.len() as f64) < std_error);
+        }
+
+        assert!(khop_sizes_approx(&graph, &seeds, 4, 3).is_err());
+    }
+
+    #[test]
+    fn test_khop_sizes_approx_large_graph() {
+        // A path over a million nodes, sketches for all of them would take 64 GiB at the highest precision while
+        // the 3 hop ball of the seeds has a handful of nodes
+        let n = 1_000_000_i64;
+        let ptrs: Vec<i64> = (0..=n).map(|v| v.max(1) - 1).collect();
+        let indices: Vec<i64> = (0..n - 1).collect();
+        let graph = CscGraph::<i64, i64>::new(&ptrs, &indices);
+        let seeds = Tensor::of_slice(&[500_000_i64, 2, 0]);
+
+        let (nodes, layer_ends) = hop_layers(&graph, &[500_000, 2, 0], 3);
+        assert_eq!(layer_ends, vec![3, 5, 6, 7]);
+        assert_eq!(nodes.len(), 7);
+
+        let approx: Vec<f64> = khop_sizes_approx(&graph, &seeds, 3, 16).unwrap().view([-1]).into();
+        let exact: Vec<i64> = khop_sizes(&graph, &seeds, 3).unwrap().view([-1]).into();
+        assert_eq!(exact, vec![2, 3, 4, 2, 3, 3, 1, 1, 1]);
+        for (a, e) in approx.iter().zip(exact.iter()) {
+            assert!((a - *e as f64).abs() < 0.01, "{:?}", approx);
+        }
+        assert_eq!(khop_sizes_approx(&graph, &seeds, 0, 16).unwrap().size(), vec![3, 0]);
+    }
+
+    // Distances from `source` within `nodes` without passing `blocked`, -1 beyond max_dist
+    fn restricted_distances(graph: &CscGraph, nodes: &[NodeIdx], source: NodeIdx, blocked: NodeIdx, max_dist: i64) -> Vec<i64> {
+        let allowed: HashSet<NodeIdx> = nodes.iter().cloned().filter(|w| *w != blocked).collect();
+        let mut dist: HashMap<NodeIdx, i64> = [(source, 0)].iter().cloned().collect();
+        let mut frontier = vec![source];
+        while let Some(w) = frontier.first().cloned() {
+            frontier.remove(0);
+            if dist[&w] == max_dist {
+                continue;
+            }
+            for x in graph.neighbors_slice(w) {
+                if allowed.contains(x) && !dist.contains_key(x) {
+                    dist.insert(*x, dist[&w] + 1);
+                    frontier.push(*x);
+                }
+            }
+        }
+        nodes.iter().map(|w| dist.get(w).cloned().unwrap_or(-1)).collect()
+    }
+
+    #[test]
+    fn test_seal_subgraphs() {
+        let (_x, _, coo) = load_karate_graph();
+        let graph_data = CscGraphStorage::try_from(&coo).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let (us, vs) = (vec![0_i64, 33, 5, 12], vec![1_i64, 32, 16, 27]);
+        let pairs = Tensor::stack(&[Tensor::of_slice(&us), Tensor::of_slice(&vs)], 0);
+        let subgraphs = seal_subgraphs(&graph, &pairs, 2, None).unwrap();
+        let nodes: Vec<i64> = subgraphs.nodes.shallow_clone().into();
+        let labels: Vec<i64> = subgraphs.labels.shallow_clone().into();
+        let rows: Vec<i64> = subgraphs.row_col.select(0, 0).into();
+        let cols: Vec<i64> = subgraphs.row_col.select(0, 1).into();
+        let node_offsets: Vec<i64> = subgraphs.node_offsets.shallow_clone().into();
+        let edge_offsets: Vec<i64> = subgraphs.edge_offsets.shallow_clone().into();
+        assert_eq!(node_offsets.len(), 5);
+
+        for (p, (u, v)) in us.iter().zip(vs.iter()).enumerate() {
+            let (begin, end) = (node_offsets[p] as usize, node_offsets[p + 1] as usize);
+            let pair_nodes = &nodes[begin..end];
+            assert_eq!(&pair_nodes[..2], &[*u, *v]);
+
+            // The nodes are the union of both 2 hop neighborhoods
+            let mut expected: HashSet<NodeIdx> = [*u, *v].iter().cloned().collect();
+            for _ in 0..2 {
+                let neighbors: Vec<NodeIdx> = expected.iter().flat_map(|w| graph.neighbors_slice(*w).iter().cloned()).collect();
+                expected.extend(neighbors);
+            }
+            assert_eq!(pair_nodes.iter().cloned().collect::<HashSet<_>>(), expected);
+
+            // Labels follow the brute force distances to each target with the other one removed
+            let dist_u = restricted_distances(&graph, pair_nodes, *u, *v, 4);
+            let dist_v = restricted_distances(&graph, pair_nodes, *v, *u, 4);
+            for (i, label) in labels[begin..end].iter().enumerate() {
+                let (du, dv) = (dist_u[i], dist_v[i]);
+                let expected = if i < 2 {
+                    1
+                } else if du < 0 || dv < 0 {
+                    0
+                } else {
+                    let d = du + dv;
+                    1 + du.min(dv) + (d / 2) * (d / 2 + d % 2 - 1)
+                };
+                assert_eq!(*label, expected);
+            }
+
+            // Every other induced edge is kept, the target edge in either direction is absent
+            let mut edges = HashSet::new();
+            for e in edge_offsets[p] as usize..edge_offsets[p + 1] as usize {
+                let (i, j) = (rows[e] as usize, cols[e] as usize);
+                assert!(i < pair_nodes.len() && j < pair_nodes.len());
+                assert!(graph.has_edge(pair_nodes[j], pair_nodes[i]));
+                edges.insert((i, j));
+            }
+            assert!(!edges.contains(&(0, 1)) && !edges.contains(&(1, 0)));
+            let induced = pair_nodes.iter()
+                .flat_map(|w| graph.neighbors_slice(*w).iter().filter(|x| pair_nodes.contains(x)))
+                .count();
+            let target_edges = graph.neighbors_slice(*u).iter().filter(|x| *x == v).count()
+                + graph.neighbors_slice(*v).iter().filter(|x| *x == u).count();
+            assert_eq!(edges.len(), induced - target_edges);
+        }
+
+        // Truncation keeps both targets and is the same every time and for every batch
+        let truncated = seal_subgraphs(&graph, &pairs, 2, Some(6)).unwrap();
+        let sizes: Vec<i64> = (truncated.node_offsets.narrow(0, 1, 4) - truncated.node_offsets.narrow(0, 0, 4)).into();
+        assert!(sizes.iter().all(|size| *size <= 6));
+        for _ in 0..3 {
+            let again = seal_subgraphs(&graph, &pairs, 2, Some(6)).unwrap();
+            assert!(again.nodes.equal(&truncated.nodes));
+            assert!(again.row_col.equal(&truncated.row_col));
+            assert!(again.labels.equal(&truncated.labels));
+        }
+        let single = seal_subgraphs(&graph, &pairs.narrow(1, 1, 1), 2, Some(6)).unwrap();
+        let offsets: Vec<i64> = truncated.node_offsets.shallow_clone().into();
+        assert!(single.nodes.equal(&truncated.nodes.narrow(0, offsets[1], offsets[2] - offsets[1])));
+
+        assert!(seal_subgraphs(&graph, &pairs, 2, Some(1)).is_err());
+        assert!(seal_subgraphs(&graph, &pairs.transpose(0, 1), 2, None).is_err());
+        assert!(seal_subgraphs(&graph, &Tensor::of_slice(&[0_i64, 34]).view([2, 1]), 2, None).is_err());
+    }
+}
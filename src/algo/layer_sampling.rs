@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+use rand::Rng;
+use crate::data::{CooGraphBuilder, CsrGraph};
+use crate::utils::{AliasTable, NodeIdx, TensorConversionError, TensorResult, index_of_slice};
+
+pub struct LayerSample {
+    // Sorted unique nodes of the layer
+    pub nodes: Vec<NodeIdx>,
+    // Probability of drawing each node in a single draw
+    pub probs: Vec<f64>,
+    // How often each node was drawn
+    pub counts: Vec<usize>,
+    // Edges (row into `nodes`, col into the nodes of the layer above, csr edge position)
+    pub edges: CooGraphBuilder,
+    // Importance corrected entries of the normalized adjacency per edge, normalized to sum to 1 for every node of
+    // the layer above that has sampled neighbors
+    pub weights: Vec<f64>,
+}
+
+// The normalized adjacency D_out^-1/2 A D_in^-1/2, entry (v, u) is 1 / sqrt(d_out(v) d_in(u))
+struct NormalizedAdjacency<'a, 'g> {
+    graph: &'a CsrGraph<'g>,
+    in_degree: Vec<usize>,
+}
+
+impl<'a, 'g> NormalizedAdjacency<'a, 'g> {
+    fn new(graph: &'a CsrGraph<'g>) -> Self {
+        let mut in_degree = vec![0_usize; graph.node_count()];
+        for v in 0..graph.node_count() as NodeIdx {
+            for u in graph.neighbors_slice(v) {
+                in_degree[*u as usize] += 1;
+            }
+        }
+        Self { graph, in_degree }
+    }
+
+    fn entry(&self, v: NodeIdx, u: NodeIdx) -> f64 {
+        1.0 / (self.graph.out_degree(v) as f64 * self.in_degree[u as usize] as f64).sqrt()
+    }
+
+    // Squared column norms of the rows `rows`, normalized to sum to 1. Returns the sorted columns with a nonzero
+    // norm and their probabilities.
+    fn column_norms(&self, rows: impl Iterator<Item=NodeIdx>) -> (Vec<NodeIdx>, Vec<f64>) {
+        let mut norms: HashMap<NodeIdx, f64> = HashMap::new();
+        for v in rows {
+            let out_degree = self.graph.out_degree(v) as f64;
+            for u in self.graph.neighbors_slice(v) {
+                *norms.entry(*u).or_insert(0.0) += 1.0 / (out_degree * self.in_degree[*u as usize] as f64);
+            }
+        }
+
+        let mut norms: Vec<(NodeIdx, f64)> = norms.into_iter().collect();
+        norms.sort_unstable_by_key(|(u, _)| *u);
+        let total: f64 = norms.iter().map(|(_, q)| q).sum();
+        norms.into_iter().map(|(u, q)| (u, q / total)).unzip()
+    }
+
+    // Draws `t` columns with replacement and links them to the nodes of the layer above that have them as neighbor
+    fn sample_layer(
+        &self,
+        rng: &mut impl Rng,
+        upper: &[NodeIdx],
+        candidates: &[NodeIdx],
+        probs: &[f64],
+        alias: &AliasTable,
+        t: i64,
+    ) -> LayerSample {
+        let mut counts: HashMap<usize, usize> = HashMap::new();
+        for _ in 0..t.max(0) {
+            *counts.entry(alias.sample(rng)).or_insert(0) += 1;
+        }
+        let mut drawn: Vec<(usize, usize)> = counts.into_iter().collect();
+        drawn.sort_unstable();
+
+        let nodes: Vec<NodeIdx> = drawn.iter().map(|(i, _)| candidates[*i]).collect();
+        let probs: Vec<f64> = drawn.iter().map(|(i, _)| probs[*i]).collect();
+        let counts: Vec<usize> = drawn.iter().map(|(_, c)| *c).collect();
+        let edges = connect(self.graph, upper, &nodes);
+
+        let mut weights: Vec<f64> = edges.iter_edges()
+            .map(|(i, j)| {
+                let (i, v) = (i as usize, upper[j as usize]);
+                counts[i] as f64 * self.entry(v, nodes[i]) / (t as f64 * probs[i])
+            })
+            .collect();
+        let mut row_sums = vec![0.0; upper.len()];
+        for ((_, j), w) in edges.iter_edges().zip(weights.iter()) {
+            row_sums[j as usize] += w;
+        }
+        for ((_, j), w) in edges.iter_edges().zip(weights.iter_mut()) {
+            *w /= row_sums[j as usize];
+        }
+
+        LayerSample { nodes, probs, counts, edges, weights }
+    }
+}
+
+// Importance q(u) proportional to the squared column norm of the normalized adjacency D_out^-1/2 A D_in^-1/2,
+// which is sum over the in-neighbors v of u of 1 / (d_out(v) d_in(u)). Sums to 1 over all nodes.
+pub fn fastgcn_probabilities(graph: &CsrGraph) -> Vec<f64> {
+    let (nodes, probs) = NormalizedAdjacency::new(graph).column_norms(0..graph.node_count() as NodeIdx);
+    let mut dense = vec![0.0; graph.node_count()];
+    for (u, q) in nodes.into_iter().zip(probs) {
+        dense[u as usize] = q;
+    }
+    dense
+}
+
+// Links every sampled node to the nodes of the layer above that have it as a neighbor
+fn connect(graph: &CsrGraph, upper: &[NodeIdx], nodes: &[NodeIdx]) -> CooGraphBuilder {
+    let neighbors: Vec<NodeIdx> = upper.iter().flat_map(|v| graph.neighbors_slice(*v).iter().cloned()).collect();
+    let local = index_of_slice(nodes, &neighbors, true);
+    let mut edges = CooGraphBuilder::new();
+    let mut k = 0;
+    for (j, v) in upper.iter().enumerate() {
+        for ptr in graph.neighbors_range(*v) {
+            if local[k] != -1 {
+                edges.push_edge(local[k], j as NodeIdx, ptr as i64);
+            }
+            k += 1;
+        }
+    }
+    edges
+}
+
+// FastGCN layer sampling: every layer independently draws num_samples_per_layer[l] nodes with replacement from
+// the importance distribution, starting below the seeds. Aggregating over a layer is corrected as
+// sum_u counts[u] * A[v, u] x_u / (t * probs[u]) with t the number of draws of the layer.
+pub fn fastgcn_sample(
+    rng: &mut impl Rng,
+    graph: &CsrGraph,
+    seeds: &[NodeIdx],
+    num_samples_per_layer: &[i64],
+) -> TensorResult<Vec<LayerSample>> {
+    let adjacency = NormalizedAdjacency::new(graph);
+    let (candidates, probs) = adjacency.column_norms(0..graph.node_count() as NodeIdx);
+    let alias = match AliasTable::new(&probs) {
+        Some(alias) => alias,
+        None if num_samples_per_layer.iter().all(|t| *t == 0) => AliasTable::new(&[1.0]).unwrap(),
+        None => return Err(TensorConversionError::Unknown("graph has no edges to sample from".to_string())),
+    };
+
+    let mut layers: Vec<LayerSample> = Vec::with_capacity(num_samples_per_layer.len());
+    for t in num_samples_per_layer.iter() {
+        let upper = layers.last().map_or(seeds, |layer| layer.nodes.as_slice());
+        let layer = adjacency.sample_layer(rng, upper, &candidates, &probs, &alias, *t);
+        layers.push(layer);
+    }
+
+    Ok(layers)
+}
+
+// LADIES layer sampling: like FastGCN, but the candidates of every layer are restricted to the neighbors of the
+// layer above and the importance is recomputed from the rows of those nodes only, which lowers the variance.
+// A layer whose upper nodes have no neighbors is empty, and so are all layers below it.
+pub fn ladies_sample(
+    rng: &mut impl Rng,
+    graph: &CsrGraph,
+    seeds: &[NodeIdx],
+    num_samples_per_layer: &[i64],
+) -> Vec<LayerSample> {
+    let adjacency = NormalizedAdjacency::new(graph);
+    let mut layers: Vec<LayerSample> = Vec::with_capacity(num_samples_per_layer.len());
+    for t in num_samples_per_layer.iter() {
+        let upper = layers.last().map_or(seeds, |layer| layer.nodes.as_slice());
+        let (candidates, probs) = adjacency.column_norms(upper.iter().cloned());
+        let layer = match AliasTable::new(&probs) {
+            Some(alias) => adjacency.sample_layer(rng, upper, &candidates, &probs, &alias, *t),
+            None => LayerSample {
+                nodes: Vec::new(),
+                probs: Vec::new(),
+                counts: Vec::new(),
+                edges: CooGraphBuilder::new(),
+                weights: Vec::new(),
+            },
+        };
+        layers.push(layer);
+    }
+
+    layers
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use rand::SeedableRng;
+    use crate::data::{CsrGraph, CsrGraphStorage, load_karate_graph};
+    use super::{fastgcn_probabilities, fastgcn_sample, ladies_sample};
+
+    #[test]
+    fn test_fastgcn_sample() {
+        let (_x, _, coo) = load_karate_graph();
+        let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let probs = fastgcn_probabilities(&graph);
+        assert!((probs.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!(probs.iter().all(|q| *q > 0.0));
+
+        let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+        let seeds = vec![0_i64, 5, 33];
+        let layers = fastgcn_sample(&mut rng, &graph, &seeds, &[20, 10]).unwrap();
+        assert_eq!(layers.len(), 2);
+        let mut upper = seeds.clone();
+        for (layer, t) in layers.iter().zip([20, 10]) {
+            assert_eq!(layer.counts.iter().sum::<usize>(), t);
+            assert!(layer.nodes.windows(2).all(|w| w[0] < w[1]));
+            for (u, q) in layer.nodes.iter().zip(layer.probs.iter()) {
+                assert_eq!(*q, probs[*u as usize]);
+            }
+            for ((i, j), ptr) in layer.edges.iter_edges().zip(layer.edges.edge_index.iter()) {
+                let (u, v) = (layer.nodes[i as usize], upper[j as usize]);
+                assert_eq!(graph.get_by_ptr(*ptr as usize), u);
+                assert!(graph.neighbors_range(v).contains(&(*ptr as usize)));
+            }
+            upper = layer.nodes.clone();
+        }
+
+        // The importance weighted estimate of the neighbor sum of node 0 is unbiased
+        let exact = graph.out_degree(0) as f64;
+        let draws = 2000;
+        let mut estimate = 0.0;
+        for _ in 0..draws {
+            let layer = &fastgcn_sample(&mut rng, &graph, &[0], &[10]).unwrap()[0];
+            for (i, _) in layer.edges.iter_edges() {
+                estimate += layer.counts[i as usize] as f64 / (10.0 * layer.probs[i as usize]);
+            }
+        }
+        estimate /= draws as f64;
+        assert!((estimate - exact).abs() / exact < 0.1, "{} != {}", estimate, exact);
+    }
+
+    #[test]
+    fn test_ladies_sample() {
+        let (_x, _, coo) = load_karate_graph();
+        let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+        let seeds = vec![0_i64, 5, 33];
+        let layers = ladies_sample(&mut rng, &graph, &seeds, &[8, 4]);
+        assert_eq!(layers.len(), 2);
+
+        // The importance of the first layer only covers the rows of the seeds
+        let in_degree = |u: i64| (0..34).filter(|v| graph.neighbors_slice(*v).contains(&u)).count() as f64;
+        let norm = |u: i64| seeds.iter()
+            .filter(|v| graph.neighbors_slice(**v).contains(&u))
+            .map(|v| 1.0 / (graph.out_degree(*v) as f64 * in_degree(u)))
+            .sum::<f64>();
+        let total: f64 = (0..34).map(norm).sum();
+        for (u, q) in layers[0].nodes.iter().zip(layers[0].probs.iter()) {
+            assert!((q - norm(*u) / total).abs() < 1e-9);
+        }
+
+        let mut upper = seeds.clone();
+        for (layer, t) in layers.iter().zip([8, 4]) {
+            assert_eq!(layer.counts.iter().sum::<usize>(), t);
+            assert_eq!(layer.weights.len(), layer.edges.len());
+            // Every sampled node is a neighbor of the layer above
+            for (i, u) in layer.nodes.iter().enumerate() {
+                assert!(upper.iter().any(|v| graph.neighbors_slice(*v).contains(u)));
+                assert!(layer.edges.iter_edges().any(|(row, _)| row as usize == i));
+            }
+            let mut row_sums = vec![0.0; upper.len()];
+            for ((_, j), w) in layer.edges.iter_edges().zip(layer.weights.iter()) {
+                row_sums[j as usize] += w;
+            }
+            assert!(row_sums.iter().all(|s| *s == 0.0 || (s - 1.0).abs() < 1e-9));
+            upper = layer.nodes.clone();
+        }
+
+        // Nodes without neighbors leave the layers below empty
+        let layers = ladies_sample(&mut rng, &graph, &[], &[4, 4]);
+        assert!(layers.iter().all(|layer| layer.nodes.is_empty() && layer.edges.is_empty()));
+    }
+}
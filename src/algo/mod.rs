@@ -3,3 +3,19 @@ pub mod random_walk;
 pub mod negative_sampling;
 pub mod hgt_sampling;
 pub mod budget_sampling;
+pub mod coarsening;
+pub mod pool;
+pub mod two_hop_sampling;
+pub mod degree;
+pub mod neighbor_cache;
+pub mod spanning_tree;
+pub mod motifs;
+pub mod spmm;
+pub mod wl;
+pub mod centrality;
+pub mod gdc;
+pub mod temporal;
+pub mod layer_sampling;
+pub mod saint;
+pub mod khop;
+pub mod spatial;
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use rayon::prelude::*;
+use tch::Tensor;
+use crate::data::{CsrGraph, CsrGraphStorage, GraphError, NeighborOrdering};
+use crate::utils::{NodeIdx, parallel};
+
+// Column order of `count_motifs`, the 4-clique column is only present when requested
+pub const MOTIF_NAMES: [&str; 5] = ["wedge", "triangle", "star3", "four_cycle", "four_clique"];
+
+fn binomial(n: i64, k: i64) -> i64 {
+    match k {
+        2 => n * (n - 1) / 2,
+        3 => n * (n - 1) * (n - 2) / 6,
+        _ => unreachable!(),
+    }
+}
+
+fn count_node_motifs(graph: &CsrGraph, v: NodeIdx, include_four_cliques: bool) -> Vec<i64> {
+    let neighbors: Vec<NodeIdx> = graph.neighbors_slice(v).iter().cloned().filter(|&u| u != v).collect();
+    let d = neighbors.len() as i64;
+
+    // Triangles: edges between neighbors, O(d^2 log d)
+    let mut triangles = 0;
+    for (i, a) in neighbors.iter().enumerate() {
+        for b in neighbors[i + 1..].iter() {
+            if graph.has_edge(*a, *b) {
+                triangles += 1;
+            }
+        }
+    }
+
+    // 4-cycles through v: every opposite node w closes C(|N(v) & N(w)|, 2) cycles, O(sum of neighbor degrees)
+    let mut common: HashMap<NodeIdx, i64> = HashMap::new();
+    for a in neighbors.iter() {
+        for w in graph.neighbors_slice(*a) {
+            if *w != v && *w != *a {
+                *common.entry(*w).or_insert(0) += 1;
+            }
+        }
+    }
+    let four_cycles: i64 = common.values().filter(|&&c| c >= 2).map(|&c| binomial(c, 2)).sum();
+
+    let mut counts = vec![binomial(d, 2), triangles, binomial(d, 3), four_cycles];
+
+    // 4-cliques: triangles among the neighbors, O(d^3 log d) which dominates on hubs
+    if include_four_cliques {
+        let mut cliques = 0;
+        for (i, a) in neighbors.iter().enumerate() {
+            for (j, b) in neighbors.iter().enumerate().skip(i + 1) {
+                if !graph.has_edge(*a, *b) {
+                    continue;
+                }
+                for c in neighbors[j + 1..].iter() {
+                    if graph.has_edge(*a, *c) && graph.has_edge(*b, *c) {
+                        cliques += 1;
+                    }
+                }
+            }
+        }
+        counts.push(cliques);
+    }
+
+    counts
+}
+
+// Per node counts of (non-induced) motifs containing the node, wedges and stars are counted at their center.
+// The graph is assumed to be simple and undirected with sorted neighbors, parallel edges inflate the counts.
+pub fn count_motifs_unchecked(
+    graph: &CsrGraph,
+    include_four_cliques: bool,
+) -> Tensor {
+    let n = graph.node_count();
+    let num_motifs = if include_four_cliques { 5 } else { 4 };
+
+    let counts: Vec<i64> = parallel::install(|| (0..n as NodeIdx).into_par_iter()
+        .flat_map_iter(|v| count_node_motifs(graph, v, include_four_cliques))
+        .collect());
+
+    Tensor::of_slice(&counts).view([n as i64, num_motifs])
+}
+
+// `count_motifs_unchecked` on a storage, refusing graphs with parallel edges or self loops instead of inflating the
+// counts. Properties missing from the metadata are detected first.
+pub fn count_motifs(
+    graph: &CsrGraphStorage,
+    include_four_cliques: bool,
+) -> Result<Tensor, GraphError> {
+    let csr = CsrGraph::<i64, i64>::try_from(graph)?;
+    let mut meta = graph.meta;
+    if meta.is_multigraph.is_none() || meta.has_self_loops.is_none() || meta.is_sorted.is_none() {
+        meta = csr.detect_properties();
+    }
+    meta.require_simple()?;
+    if meta.is_sorted != Some(true) {
+        return Err(GraphError::InvalidOrdering { expected: NeighborOrdering::ById, got: graph.ordering });
+    }
+    Ok(count_motifs_unchecked(&csr, include_four_cliques))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::SmallRng;
+    use tch::Tensor;
+    use crate::data::{CooGraphStorage, CsrGraph, CsrGraphStorage, GraphError};
+
+    fn brute_force(n: usize, adj: &[Vec<bool>]) -> Vec<Vec<i64>> {
+        let mut counts = vec![vec![0_i64; 5]; n];
+        for a in 0..n {
+            for b in a + 1..n {
+                for c in b + 1..n {
+                    let tri = [a, b, c];
+                    // Wedges at every center adjacent to both others
+                    for (i, center) in tri.iter().enumerate() {
+                        let others: Vec<usize> = tri.iter().enumerate().filter(|(j, _)| *j != i).map(|(_, x)| *x).collect();
+                        if adj[*center][others[0]] && adj[*center][others[1]] {
+                            counts[*center][0] += 1;
+                        }
+                    }
+                    if adj[a][b] && adj[b][c] && adj[a][c] {
+                        for x in tri.iter() {
+                            counts[*x][1] += 1;
+                        }
+                    }
+
+                    for d in c + 1..n {
+                        let quad = [a, b, c, d];
+                        for (i, center) in quad.iter().enumerate() {
+                            if quad.iter().enumerate().all(|(j, x)| j == i || adj[*center][*x]) {
+                                counts[*center][2] += 1;
+                            }
+                        }
+                        // The three distinct cyclic orders of four nodes
+                        let cycles = [[a, b, c, d], [a, b, d, c], [a, c, b, d]];
+                        let num_cycles = cycles.iter()
+                            .filter(|cy| (0..4).all(|k| adj[cy[k]][cy[(k + 1) % 4]]))
+                            .count() as i64;
+                        let is_clique = (0..4).all(|i| (i + 1..4).all(|j| adj[quad[i]][quad[j]]));
+                        for x in quad.iter() {
+                            counts[*x][3] += num_cycles;
+                            if is_clique {
+                                counts[*x][4] += 1;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        counts
+    }
+
+    #[test]
+    fn test_count_motifs() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        for (n, p) in [(6_usize, 0.5), (9, 0.4), (12, 0.6), (12, 0.25)].iter().cloned() {
+            let edges: Vec<(usize, usize)> = (0..n)
+                .flat_map(|u| (u + 1..n).map(move |v| (u, v)))
+                .filter(|_| rng.gen_bool(p))
+                .collect();
+            let mut adj = vec![vec![false; n]; n];
+            let mut rows = Vec::new();
+            let mut cols = Vec::new();
+            for (u, v) in edges {
+                adj[u][v] = true;
+                adj[v][u] = true;
+                rows.extend_from_slice(&[u as i64, v as i64]);
+                cols.extend_from_slice(&[v as i64, u as i64]);
+            }
+            let coo = CooGraphStorage::new(
+                Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0), (n as i64, n as i64),
+            );
+            let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+            let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+            let expected = brute_force(n, &adj);
+            let counts = super::count_motifs_unchecked(&graph, true);
+            assert_eq!(counts.size(), vec![n as i64, 5]);
+            let counts: Vec<i64> = counts.view([-1]).into();
+            for v in 0..n {
+                assert_eq!(&counts[v * 5..(v + 1) * 5], expected[v].as_slice(), "node {} of graph with {} nodes", v, n);
+            }
+
+            let counts = super::count_motifs_unchecked(&graph, false);
+            assert_eq!(counts.size(), vec![n as i64, 4]);
+            let counts: Vec<i64> = counts.view([-1]).into();
+            for v in 0..n {
+                assert_eq!(&counts[v * 4..(v + 1) * 4], &expected[v][..4]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_count_motifs_simple_graphs() {
+        // Triangle with the edge 0 - 1 twice
+        let rows = Tensor::of_slice(&[0_i64, 1, 1, 2, 0, 2, 0, 1]);
+        let cols = Tensor::of_slice(&[1_i64, 0, 2, 1, 2, 0, 1, 0]);
+        let coo = CooGraphStorage::new(Tensor::stack(&[rows, cols], 0), (3, 3));
+        let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+        assert!(matches!(super::count_motifs(&graph_data, false), Err(GraphError::Multigraph)));
+
+        let (coalesced, _) = coo.coalesce().unwrap();
+        let graph_data = CsrGraphStorage::try_from(&coalesced).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let counts = super::count_motifs(&graph_data, false).unwrap();
+        assert!(counts.equal(&super::count_motifs_unchecked(&graph, false)));
+        assert_eq!(Vec::<i64>::from(counts.select(1, 1)), vec![1, 1, 1]);
+
+        let looped = CooGraphStorage::new(Tensor::cat(&[coalesced.row_col, Tensor::of_slice(&[2_i64, 2]).view([2, 1])], 1), (3, 3));
+        let graph_data = CsrGraphStorage::try_from(&looped).unwrap();
+        assert!(matches!(super::count_motifs(&graph_data, false), Err(GraphError::SelfLoops)));
+    }
+}
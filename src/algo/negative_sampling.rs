@@ -1,9 +1,13 @@
 use std::collections::HashMap;
 use rand::{Rng};
-use crate::data::{CooGraphBuilder, CsrGraph, SparseGraph, SparseGraphType, SparseGraphTypeTrait};
-use crate::utils::{EdgeType, NodeIdx, NodePtr, NodeType, RelType};
-
-pub fn negative_sample_neighbors_homogenous(
+use rayon::prelude::*;
+use tch::{Kind, Tensor};
+use std::convert::TryFrom;
+use crate::data::{CooGraphBuilder, CscGraph, CsrGraph, CsrGraphStorage, GraphError, NeighborOrdering, SparseGraph, SparseGraphType, SparseGraphTypeTrait};
+use crate::utils::{EdgeType, NodeIdx, NodePtr, NodeType, RelType, TensorConversionError, TensorResult, parallel, try_tensor_to_slice};
+
+// Assumes sorted neighbors, see `negative_sample_neighbors_homogenous`
+pub fn negative_sample_neighbors_homogenous_unchecked(
     rng: &mut impl Rng,
     graph: &CsrGraph,
     graph_size: (i64, i64),
@@ -47,6 +51,28 @@ pub fn negative_sample_neighbors_homogenous(
     (samples, edge_index, sample_count)
 }
 
+// Negatives for every input, refusing graphs whose neighbors aren't sorted since they are binary searched. The
+// sortedness is detected first when the metadata doesn't know it. Multigraphs need no coalescing: a pair is an edge
+// however often it occurs, so parallel edges never turn into negatives.
+pub fn negative_sample_neighbors_homogenous(
+    rng: &mut impl Rng,
+    graph: &CsrGraphStorage,
+    graph_size: (i64, i64),
+    inputs: &[NodeIdx],
+    num_neg: i64,
+    try_count: i64,
+) -> Result<(Vec<NodeIdx>, CooGraphBuilder, usize), GraphError> {
+    let csr = CsrGraph::<i64, i64>::try_from(graph)?;
+    let is_sorted = match graph.meta.is_sorted {
+        Some(is_sorted) => is_sorted,
+        None => graph.ordering == NeighborOrdering::ById || csr.detect_properties().is_sorted == Some(true),
+    };
+    if !is_sorted {
+        return Err(GraphError::InvalidOrdering { expected: NeighborOrdering::ById, got: graph.ordering });
+    }
+    Ok(negative_sample_neighbors_homogenous_unchecked(rng, &csr, graph_size, inputs, num_neg, try_count))
+}
+
 pub fn negative_sample_neighbors_heterogenous(
     rng: &mut impl Rng,
     node_types: &[NodeType],
@@ -132,14 +158,78 @@ pub fn negative_sample_neighbors_heterogenous(
 
 // TODO: benchmark current impl vs per thread retry
 
+fn batch_pairs(graph: &CscGraph, src: &Tensor, dst: &Tensor) -> TensorResult<(Vec<NodeIdx>, Vec<NodeIdx>)> {
+    if src.dim() != 1 || src.size() != dst.size() {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "src and dst must be of shape [B], got {:?} and {:?}", src.size(), dst.size()
+        ))));
+    }
+    let src = src.to_kind(Kind::Int64).contiguous();
+    let dst = dst.to_kind(Kind::Int64).contiguous();
+    let dst_data = try_tensor_to_slice::<i64>(&dst)?;
+    let node_count = graph.node_count() as i64;
+    if let Some(v) = dst_data.iter().find(|&&v| v < 0 || v >= node_count) {
+        return Err(TensorConversionError::Unknown(format!("dst node {} is out of bounds for {} nodes", v, node_count)));
+    }
+    Ok((try_tensor_to_slice::<i64>(&src)?.to_vec(), dst_data.to_vec()))
+}
+
+// mask[i, j] is true iff (src[i], dst[j]) is an edge, i.e. whether pairing src[i] with dst[j] would be a false
+// negative. The neighbors of every column have to be sorted.
+pub fn in_batch_negative_mask(
+    graph: &CscGraph,
+    src: &Tensor,
+    dst: &Tensor,
+) -> TensorResult<Tensor> {
+    let (src_data, dst_data) = batch_pairs(graph, src, dst)?;
+    let b = src_data.len();
+
+    let mut mask = vec![false; b * b];
+    if b > 0 {
+        parallel::install(|| mask.par_chunks_mut(b).zip(src_data.par_iter()).for_each(|(row, u)| {
+            for (is_edge, v) in row.iter_mut().zip(dst_data.iter()) {
+                *is_edge = graph.has_edge(*v, *u);
+            }
+        }));
+    }
+
+    Ok(Tensor::of_slice(&mask).view([b as i64, b as i64]))
+}
+
+// Positions j of the first `max_negatives` valid in-batch negatives dst[j] for every src[i], skipping the
+// positive itself and all true edges. Rows with fewer valid negatives are padded with -1.
+pub fn in_batch_negatives(
+    graph: &CscGraph,
+    src: &Tensor,
+    dst: &Tensor,
+    max_negatives: usize,
+) -> TensorResult<Tensor> {
+    let (src_data, dst_data) = batch_pairs(graph, src, dst)?;
+    let b = src_data.len();
+
+    let mut negatives = vec![-1_i64; b * max_negatives];
+    if b > 0 && max_negatives > 0 {
+        parallel::install(|| negatives.par_chunks_mut(max_negatives).enumerate().for_each(|(i, row)| {
+            let valid = dst_data.iter().enumerate()
+                .filter(|(j, v)| *j != i && !graph.has_edge(**v, src_data[i]))
+                .map(|(j, _)| j as i64);
+            for (slot, j) in row.iter_mut().zip(valid) {
+                *slot = j;
+            }
+        }));
+    }
+
+    Ok(Tensor::of_slice(&negatives).view([b as i64, max_negatives as i64]))
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
     use std::convert::TryFrom;
     use rand::SeedableRng;
     use tch::Tensor;
-    use crate::algo::negative_sampling::negative_sample_neighbors_heterogenous;
-    use crate::data::{CscGraph, CscGraphStorage, CsrGraph, CsrGraphStorage, load_fake_hetero_graph, Size};
+    use crate::algo::negative_sampling::{in_batch_negative_mask, in_batch_negatives, negative_sample_neighbors_heterogenous, negative_sample_neighbors_homogenous, negative_sample_neighbors_homogenous_unchecked};
+    use crate::data::{CooGraphStorage, CscGraph, CscGraphStorage, CsrGraph, CsrGraphStorage, GraphError, load_fake_hetero_graph, NeighborOrdering, Size};
     use crate::data::load_karate_graph;
     use crate::utils::{EdgeType, NodeIdx, NodeType, RelType, try_tensor_to_slice};
 
@@ -155,7 +245,7 @@ mod tests {
 
         let inputs: Vec<_> = (0..node_count).collect();
 
-        let (samples, edge_index, _sample_count) = super::negative_sample_neighbors_homogenous(
+        let (samples, edge_index, _sample_count) = negative_sample_neighbors_homogenous_unchecked(
             &mut rng,
             &graph,
             (node_count, node_count),
@@ -170,6 +260,41 @@ mod tests {
         }
     }
 
+    #[test]
+    pub fn test_negative_sample_neighbors_multigraph() {
+        // Node 0 is connected to 1, 2 and 3 three times each, so 4 is its only negative and its neighbors hold every
+        // node in runs of parallel edges
+        let rows: Vec<i64> = [1_i64, 2, 3].iter().flat_map(|v| [0, *v, 0, *v, 0, *v]).collect();
+        let cols: Vec<i64> = [1_i64, 2, 3].iter().flat_map(|v| [*v, 0, *v, 0, *v, 0]).collect();
+        let mut multigraph = CooGraphStorage::new(Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0), (5, 5));
+        assert_eq!(multigraph.detect_properties().unwrap().is_multigraph, Some(true));
+        let graph_data = CsrGraphStorage::try_from(&multigraph).unwrap();
+        assert_eq!(graph_data.meta.is_multigraph, Some(true));
+        assert_eq!(Vec::<i64>::from(&graph_data.indices)[..9], [1, 1, 1, 2, 2, 2, 3, 3, 3]);
+
+        let inputs = vec![0_i64, 1];
+        let sample = |graph_data: &CsrGraphStorage| negative_sample_neighbors_homogenous(
+            &mut rand::rngs::SmallRng::from_seed([0; 32]), graph_data, (5, 5), &inputs, 10, 50,
+        );
+        let (samples, edge_index, _) = sample(&graph_data).unwrap();
+        let negatives = |i: i64| edge_index.iter_edges().filter(move |(j, _)| *j == i).map(|(_, k)| samples[k as usize]);
+        assert_eq!(negatives(0).collect::<Vec<_>>(), vec![4; 10]);
+        assert!(negatives(1).all(|w| w != 0 && w != 1));
+
+        // Parallel edges don't change the negatives
+        let (coalesced, _) = multigraph.coalesce().unwrap();
+        let (coalesced_samples, coalesced_edges, _) = sample(&CsrGraphStorage::try_from(&coalesced).unwrap()).unwrap();
+        assert_eq!(samples, coalesced_samples);
+        assert_eq!(edge_index.edge_index, coalesced_edges.edge_index);
+
+        // Neighbors of unknown order are checked, unsorted ones refused
+        let unknown = CsrGraphStorage::new(graph_data.ptrs.shallow_clone(), graph_data.indices.shallow_clone(), None);
+        assert_eq!(sample(&unknown).unwrap().0, samples);
+        let reversed_first = Tensor::of_slice(&(0..9).rev().chain(9..18).collect::<Vec<i64>>());
+        let unsorted = CsrGraphStorage::new(graph_data.ptrs.shallow_clone(), graph_data.indices.index_select(0, &reversed_first), None);
+        assert!(matches!(sample(&unsorted), Err(GraphError::InvalidOrdering { expected: NeighborOrdering::ById, .. })));
+    }
+
     #[test]
     pub fn test_negative_sample_neighbors_heterogenous() {
         let (xs, coo_graphs) = load_fake_hetero_graph();
@@ -231,4 +356,44 @@ mod tests {
             }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn test_in_batch_negative_mask() {
+        let (_x, _, coo_graph) = load_karate_graph();
+        let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let rows: Vec<i64> = coo_graph.row().into();
+        let cols: Vec<i64> = coo_graph.col().into();
+        let edges: std::collections::HashSet<(i64, i64)> = rows.iter().cloned().zip(cols.iter().cloned()).collect();
+
+        // Source 0 appears twice, so its two mask rows have to match
+        let src_data = vec![0_i64, 1, 0, 33, 2];
+        let dst_data = vec![1_i64, 2, 8, 32, 1];
+        let b = src_data.len();
+        let mask = in_batch_negative_mask(&graph, &Tensor::of_slice(&src_data), &Tensor::of_slice(&dst_data)).unwrap();
+        assert_eq!(mask.size(), vec![b as i64, b as i64]);
+        let mask: Vec<bool> = mask.view([-1]).into();
+        for i in 0..b {
+            for j in 0..b {
+                assert_eq!(mask[i * b + j], edges.contains(&(src_data[i], dst_data[j])), "({}, {})", i, j);
+            }
+        }
+        assert_eq!(&mask[..b], &mask[2 * b..3 * b]);
+
+        let negatives = in_batch_negatives(&graph, &Tensor::of_slice(&src_data), &Tensor::of_slice(&dst_data), 3).unwrap();
+        assert_eq!(negatives.size(), vec![b as i64, 3]);
+        let negatives: Vec<i64> = negatives.view([-1]).into();
+        for i in 0..b {
+            let expected: Vec<i64> = (0..b)
+                .filter(|&j| j != i && !mask[i * b + j])
+                .map(|j| j as i64)
+                .chain(std::iter::repeat(-1))
+                .take(3)
+                .collect();
+            assert_eq!(&negatives[i * 3..(i + 1) * 3], expected.as_slice());
+        }
+
+        assert!(in_batch_negative_mask(&graph, &Tensor::of_slice(&[0_i64]), &Tensor::of_slice(&[34_i64])).is_err());
+        assert!(in_batch_negative_mask(&graph, &Tensor::of_slice(&[0_i64, 1]), &Tensor::of_slice(&[1_i64])).is_err());
+    }
+}
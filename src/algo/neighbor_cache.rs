@@ -0,0 +1,304 @@
+use std::collections::{BTreeMap, HashMap};
+use rand::Rng;
+use rand::rngs::SmallRng;
+use crate::algo::neighbor_sampling::{FanoutPolicy, LayerOffset, Sampler};
+use crate::data::{CooGraphBuilder, NeighborSource};
+use crate::utils::{EdgePtr, NodeIdx, NodePtr};
+use crate::utils::random::RngPool;
+
+// Node, fanout and whether the hop samples with replacement
+type CacheKey = (NodeIdx, usize, bool);
+
+pub struct NeighborCache {
+    capacity: usize,
+    seed: Option<u64>,
+    entries: HashMap<CacheKey, (Vec<EdgePtr<usize>>, u64)>,
+    recency: BTreeMap<u64, CacheKey>,
+    tick: u64,
+    hits: usize,
+    misses: usize,
+}
+
+impl NeighborCache {
+    // Only caches full expansions, which do not depend on the rng
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            seed: None,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    // Samples every node with an rng derived from (seed, node, fanout) so sampled sets can be cached as well
+    pub fn with_seed(capacity: usize, seed: u64) -> Self {
+        Self { seed: Some(seed), ..Self::new(capacity) }
+    }
+
+    // A new epoch seed invalidates the cached sampled sets
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        if seed != self.seed {
+            self.seed = seed;
+            self.clear();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    pub fn reset_counters(&mut self) {
+        self.hits = 0;
+        self.misses = 0;
+    }
+
+    fn node_rng(&self, seed: u64, node: NodeIdx, k: usize) -> SmallRng {
+        RngPool::new(seed).stream(&[node as u64, k as u64])
+    }
+
+    fn get(&mut self, key: &CacheKey) -> Option<&[EdgePtr<usize>]> {
+        let tick = self.tick;
+        match self.entries.get_mut(key) {
+            Some((edges, last_used)) => {
+                self.recency.remove(last_used);
+                self.recency.insert(tick, *key);
+                *last_used = tick;
+                self.tick += 1;
+                self.hits += 1;
+                Some(edges.as_slice())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    fn insert(&mut self, key: CacheKey, edges: Vec<EdgePtr<usize>>) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.entries.len() >= self.capacity {
+            let oldest = match self.recency.keys().next() {
+                Some(tick) => *tick,
+                None => break,
+            };
+            if let Some(evicted) = self.recency.remove(&oldest) {
+                self.entries.remove(&evicted);
+            }
+        }
+        self.recency.insert(self.tick, key);
+        self.entries.insert(key, (edges, self.tick));
+        self.tick += 1;
+    }
+}
+
+pub fn neighbor_sampling_homogenous_cached<
+    G: NeighborSource + ?Sized, S: Sampler, N: Into<FanoutPolicy> + Copy
+>(
+    rng: &mut impl Rng,
+    graph: &G,
+    inputs: &[NodeIdx],
+    num_neighbors: &[N],
+    sampler: &S,
+    cache: &mut NeighborCache,
+) -> (
+    Vec<NodeIdx>,
+    CooGraphBuilder,
+    Vec<LayerOffset>
+) {
+    let mut samples: Vec<NodeIdx> = Vec::new();
+    let mut layer_offsets: Vec<LayerOffset> = Vec::new();
+    let mut edge_index = CooGraphBuilder::new();
+
+    samples.extend_from_slice(inputs);
+
+    let (mut begin, mut end) = (0, samples.len());
+    for (hop, fanout) in num_neighbors.iter().cloned().enumerate() {
+        let fanout: FanoutPolicy = fanout.into();
+        let mut num_samples = fanout.fanout(0);
+        let mut sampler_state = sampler.init_hop(hop, num_samples);
+        let replace = sampler.replace_policy().at(hop).unwrap_or_else(|| panic!("no replacement flag for hop {}", hop));
+        let mut sampled: Vec<EdgePtr<usize>> = Vec::new();
+
+        layer_offsets.push((samples.len() as NodePtr, edge_index.len() as EdgePtr, samples.len() as NodePtr));
+
+        for i in begin..end {
+            let w = samples[i];
+            let neighbors_range = graph.edge_positions(w);
+            if neighbors_range.is_empty() {
+                continue;
+            }
+
+            let k = fanout.fanout(neighbors_range.len());
+            if k == 0 {
+                continue;
+            }
+
+            // The cache is consulted before sampling from the row indices
+            let key = (w, k, replace);
+            sampled.clear();
+            match cache.get(&key) {
+                Some(edges) => sampled.extend_from_slice(edges),
+                None => {
+                    if k != num_samples {
+                        sampler.resize(&mut sampler_state, k);
+                        num_samples = k;
+                    }
+
+                    let cacheable = match cache.seed {
+                        Some(seed) => {
+                            let mut node_rng = cache.node_rng(seed, w, k);
+                            sampled.extend(sampler.sample(&mut node_rng, &mut sampler_state, w, neighbors_range.clone()));
+                            true
+                        }
+                        None => {
+                            sampled.extend(sampler.sample(rng, &mut sampler_state, w, neighbors_range.clone()));
+                            sampler.is_exhaustive(k, neighbors_range.len())
+                        }
+                    };
+                    if cacheable {
+                        cache.insert(key, sampled.clone());
+                    }
+                }
+            }
+
+            for edge_ptr in sampled.iter().cloned() {
+                let v = graph.neighbor_at(edge_ptr);
+                let j = samples.len();
+
+                samples.push(v);
+                edge_index.push_edge(j as i64, i as i64, edge_ptr as i64);
+            }
+        }
+
+        begin = end;
+        end = samples.len();
+    }
+
+    (
+        samples,
+        edge_index,
+        layer_offsets,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use rand::SeedableRng;
+    use rand::rngs::SmallRng;
+    use crate::algo::neighbor_cache::{NeighborCache, neighbor_sampling_homogenous_cached};
+    use crate::algo::neighbor_sampling::{IdentityFilter, UnweightedSampler, neighbor_sampling_homogenous};
+    use crate::data::{CscGraph, CscGraphStorage, load_karate_graph};
+
+    #[test]
+    fn test_neighbor_cache_full_expansion() {
+        let (_x, _, coo_graph) = load_karate_graph();
+        let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let inputs = vec![0_i64, 1, 4, 5];
+        let inputs_state = vec![(); inputs.len()];
+        let num_neighbors = vec![100, 100];
+
+        let (expected, expected_edges, expected_offsets) = neighbor_sampling_homogenous(
+            &mut SmallRng::from_seed([0; 32]), &graph, &inputs, &num_neighbors,
+            &UnweightedSampler::<false>, &IdentityFilter, &inputs_state,
+        );
+
+        let mut cache = NeighborCache::new(1000);
+        for _ in 0..2 {
+            let (samples, edges, offsets) = neighbor_sampling_homogenous_cached(
+                &mut SmallRng::from_seed([0; 32]), &graph, &inputs, &num_neighbors,
+                &UnweightedSampler::<false>, &mut cache,
+            );
+            assert_eq!(samples, expected);
+            assert_eq!(edges.edge_index, expected_edges.edge_index);
+            assert_eq!(offsets, expected_offsets);
+        }
+
+        // Every expansion of the second call was served from the cache
+        let lookups = expected_offsets.last().unwrap().0 as usize;
+        assert_eq!(cache.hits() + cache.misses(), 2 * lookups);
+        assert_eq!(cache.len(), cache.misses());
+
+        // Sampling with replacement is never cached without a seed
+        let mut cache = NeighborCache::new(1000);
+        neighbor_sampling_homogenous_cached(
+            &mut SmallRng::from_seed([0; 32]), &graph, &inputs, &num_neighbors,
+            &UnweightedSampler::<true>, &mut cache,
+        );
+        assert!(cache.is_empty());
+        assert_eq!(cache.hits(), 0);
+    }
+
+    #[test]
+    fn test_neighbor_cache_seeded() {
+        let (_x, _, coo_graph) = load_karate_graph();
+        let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let inputs = vec![0_i64, 1, 4, 5, 0, 33];
+        let num_neighbors = vec![3, 2];
+
+        let mut uncached = NeighborCache::with_seed(0, 42);
+        let (expected, expected_edges, _) = neighbor_sampling_homogenous_cached(
+            &mut SmallRng::from_seed([0; 32]), &graph, &inputs, &num_neighbors,
+            &UnweightedSampler::<false>, &mut uncached,
+        );
+        assert!(uncached.is_empty());
+
+        let mut cache = NeighborCache::with_seed(1000, 42);
+        let (samples, edges, _) = neighbor_sampling_homogenous_cached(
+            &mut SmallRng::from_seed([1; 32]), &graph, &inputs, &num_neighbors,
+            &UnweightedSampler::<false>, &mut cache,
+        );
+        assert_eq!(samples, expected);
+        assert_eq!(edges.edge_index, expected_edges.edge_index);
+        // Node 0 is an input twice, so at least one hit
+        assert!(cache.hits() >= 1);
+        assert_eq!(cache.hits() + cache.misses(), uncached.misses());
+        assert_eq!(cache.len(), cache.misses());
+
+        // The least recently used entries are evicted first
+        let mut cache = NeighborCache::with_seed(2, 42);
+        neighbor_sampling_homogenous_cached(
+            &mut SmallRng::from_seed([0; 32]), &graph, &[0, 1, 4], &[3], &UnweightedSampler::<false>, &mut cache,
+        );
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.misses(), 3);
+        neighbor_sampling_homogenous_cached(
+            &mut SmallRng::from_seed([0; 32]), &graph, &[4, 1, 0], &[3], &UnweightedSampler::<false>, &mut cache,
+        );
+        assert_eq!((cache.hits(), cache.misses()), (2, 4));
+        assert_eq!(cache.len(), 2);
+
+        cache.set_seed(Some(7));
+        assert!(cache.is_empty());
+    }
+}
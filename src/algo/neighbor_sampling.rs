@@ -1,14 +1,16 @@
 #![allow(clippy::too_many_arguments)]
 
 use std::collections::HashMap;
-use std::ops::{Neg, RangeInclusive, Sub};
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::RangeInclusive;
 use std::slice::Iter;
-use num_traits::Float;
+use std::time::{Duration, Instant};
 use rand::{Rng};
-use rand::distributions::uniform::SampleUniform;
-use crate::data::EdgeAttr;
-use crate::data::graph::{CscGraph, CooGraphBuilder};
-use crate::utils::{EdgePtr, EdgeType, NodeType, RelType, replacement_sampling, reservoir_sampling, reservoir_sampling_weighted};
+use tch::{Kind, Tensor};
+use crate::data::{CscGraphStorage, CsrGraphStorage, EdgeAttrSource, SparseGraphStorage};
+use crate::data::graph::{CooGraphBuilder, NeighborOrdering, NeighborSource, SparseGraph};
+use crate::utils::{AliasTable, EdgePtr, EdgeType, NodeType, RelType, TensorConversionError, TensorResult, gumbel_top_k_sampling, replacement_sampling, try_tensor_to_slice, replacement_sampling_capped, reservoir_sampling, reservoir_sampling_weighted};
 use crate::utils::types::{NodeIdx, NodePtr};
 
 pub trait SamplingFilter {
@@ -33,61 +35,324 @@ pub const TEMPORAL_SAMPLE_STATIC: usize = 0;
 pub const TEMPORAL_SAMPLE_RELATIVE: usize = 1;
 pub const TEMPORAL_SAMPLE_DYNAMIC: usize = 2;
 
-pub struct TemporalFilter<'a, T, const FORWARD: bool, const MODE: usize> {
-    window: RangeInclusive<T>,
-    timestamps: EdgeAttr<'a, T>,
+// Reads the edge timestamps through `times` of the source, which panics on sources without them (see
+// `EdgeAttrSource`)
+pub struct TemporalFilter<'a, G: ?Sized, const FORWARD: bool, const MODE: usize> {
+    window: RangeInclusive<i64>,
+    source: &'a G,
 }
 
-impl<'a, T, const FORWARD: bool, const MODE: usize> TemporalFilter<'a, T, FORWARD, MODE> {
-    pub fn new(window: RangeInclusive<T>, timestamps: EdgeAttr<'a, T>) -> Self {
+impl<'a, G: NeighborSource + ?Sized, const FORWARD: bool, const MODE: usize> TemporalFilter<'a, G, FORWARD, MODE> {
+    pub fn new(window: RangeInclusive<i64>, source: &'a G) -> Self {
         TemporalFilter {
             window,
-            timestamps,
+            source,
         }
     }
+
+    #[inline(always)]
+    fn time_at(&self, src: NodeIdx, dst: EdgePtr<usize>) -> i64 {
+        self.source.time_at(src, dst).expect("the source has no edge timestamps")
+    }
 }
 
 impl<
-    'a, T: Copy + PartialOrd + Neg<Output=T> + Sub<Output=T>, const FORWARD: bool, const MODE: usize
-> SamplingFilter for TemporalFilter<'a, T, FORWARD, MODE> {
-    type State = T;
+    'a, G: NeighborSource + ?Sized, const FORWARD: bool, const MODE: usize
+> SamplingFilter for TemporalFilter<'a, G, FORWARD, MODE> {
+    type State = i64;
 
-    fn filter(&self, state: &Self::State, _src: NodeIdx, dst: EdgePtr<usize>) -> bool {
-        let t = self.timestamps.get(dst);
+    fn filter(&self, state: &Self::State, src: NodeIdx, dst: EdgePtr<usize>) -> bool {
+        let t = self.time_at(src, dst);
         match MODE {
-            TEMPORAL_SAMPLE_STATIC => self.window.contains(t),
+            TEMPORAL_SAMPLE_STATIC => self.window.contains(&t),
             TEMPORAL_SAMPLE_RELATIVE | TEMPORAL_SAMPLE_DYNAMIC => {
                 match FORWARD {
-                    true => self.window.contains(&(*t - *state)),
-                    false => self.window.contains(&(*t - *state).neg()),
+                    true => self.window.contains(&(t - *state)),
+                    false => self.window.contains(&(*state - t)),
                 }
             }
             _ => unreachable!(),
         }
     }
 
-    fn mutate(&self, state: &Self::State, _src: NodeIdx, dst: EdgePtr<usize>) -> Self::State {
+    fn mutate(&self, state: &Self::State, src: NodeIdx, dst: EdgePtr<usize>) -> Self::State {
         match MODE {
             TEMPORAL_SAMPLE_STATIC => *state,
             TEMPORAL_SAMPLE_RELATIVE => *state,
-            TEMPORAL_SAMPLE_DYNAMIC => *self.timestamps.get(dst),
+            TEMPORAL_SAMPLE_DYNAMIC => self.time_at(src, dst),
             _ => unreachable!(),
         }
     }
 }
 
+// Samplers draw edge positions, so parallel edges are sampled with their multiplicity and keep their own edge ids.
+// With `multigraph` false the parallel edges are collapsed instead: of every run of equal neighbors only the first
+// edge that passes `inner` remains a candidate. Parallel edges have to be adjacent, which holds for graphs sorted
+// by id (e.g. converted from coo).
+pub struct ParallelEdgeFilter<'g, G: ?Sized, F> {
+    graph: &'g G,
+    inner: F,
+    multigraph: bool,
+}
+
+impl<'g, G: NeighborSource + ?Sized, F: SamplingFilter> ParallelEdgeFilter<'g, G, F> {
+    pub fn new(graph: &'g G, inner: F, multigraph: bool) -> Self {
+        ParallelEdgeFilter { graph, inner, multigraph }
+    }
+}
+
+impl<'g, G: NeighborSource + ?Sized, F: SamplingFilter> SamplingFilter for ParallelEdgeFilter<'g, G, F> {
+    type State = F::State;
+
+    fn filter(&self, state: &Self::State, src: NodeIdx, dst: EdgePtr<usize>) -> bool {
+        if !self.inner.filter(state, src, dst) {
+            return false;
+        }
+        if self.multigraph {
+            return true;
+        }
+
+        let v = self.graph.neighbor_at(dst);
+        let start = self.graph.edge_positions(src).start;
+        !(start..dst).rev()
+            .take_while(|p| self.graph.neighbor_at(*p) == v)
+            .any(|p| self.inner.filter(state, src, p))
+    }
+
+    fn mutate(&self, state: &Self::State, src: NodeIdx, dst: EdgePtr<usize>) -> Self::State {
+        self.inner.mutate(state, src, dst)
+    }
+}
+
+// Drops the edges to masked neighbors before `inner` sees them, node_mask[v] true keeps v out of the sample (e.g. test
+// nodes during training). Nodes past the end of the mask count as masked, so a short mask can not leak nodes into
+// the sample. Without a mask every edge goes to `inner`.
+pub struct NodeMaskFilter<'a, G: ?Sized, F> {
+    graph: &'a G,
+    inner: F,
+    node_mask: Option<&'a [bool]>,
+}
+
+impl<'a, G: NeighborSource + ?Sized, F: SamplingFilter> NodeMaskFilter<'a, G, F> {
+    pub fn new(graph: &'a G, inner: F, node_mask: Option<&'a [bool]>) -> Self {
+        NodeMaskFilter { graph, inner, node_mask }
+    }
+}
+
+impl<'a, G: NeighborSource + ?Sized, F: SamplingFilter> SamplingFilter for NodeMaskFilter<'a, G, F> {
+    type State = F::State;
+
+    fn filter(&self, state: &Self::State, src: NodeIdx, dst: EdgePtr<usize>) -> bool {
+        if let Some(node_mask) = self.node_mask {
+            let v = self.graph.neighbor_at(dst) as usize;
+            if node_mask.get(v).cloned().unwrap_or(true) {
+                return false;
+            }
+        }
+        self.inner.filter(state, src, dst)
+    }
+
+    fn mutate(&self, state: &Self::State, src: NodeIdx, dst: EdgePtr<usize>) -> Self::State {
+        self.inner.mutate(state, src, dst)
+    }
+}
+
+// Event time of nodes without any incident edge
+pub const NO_EVENT_TIME: i64 = i64::MIN;
+
+// Folds the timestamps of the edge positions of every node into `times` with max (`last`) or min. When the blocks
+// are sorted by time only their last (or first) element is read.
+fn fold_event_times<G: NeighborSource + ?Sized>(
+    graph: &G,
+    nodes: &[NodeIdx],
+    last: bool,
+    sorted: bool,
+    times: &mut [i64],
+) {
+    for (t, v) in times.iter_mut().zip(nodes.iter()) {
+        if graph.degree(*v) == 0 {
+            continue;
+        }
+        let block = graph.times(*v).expect("the source has no edge timestamps");
+        let event = match (sorted, last) {
+            (true, true) => block[block.len() - 1],
+            (true, false) => block[0],
+            (false, true) => *block.iter().max().unwrap(),
+            (false, false) => *block.iter().min().unwrap(),
+        };
+        *t = match *t {
+            NO_EVENT_TIME => event,
+            t if last => t.max(event),
+            t => t.min(event),
+        };
+    }
+}
+
+fn fold_storage_event_times<Ty>(
+    graph: &SparseGraphStorage<Ty>,
+    timestamps: &Tensor,
+    nodes: &[NodeIdx],
+    last: bool,
+    times: &mut [i64],
+) -> TensorResult<()> {
+    if timestamps.size() != graph.indices.size() {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "timestamps must be of shape {:?}, got {:?}", graph.indices.size(), timestamps.size()
+        ))));
+    }
+    let view = SparseGraph::<Ty, i64, i64>::try_from(graph)?;
+    if let Some(v) = nodes.iter().find(|&&v| !(0..view.node_count() as NodeIdx).contains(&v)) {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "node {} is out of range for a graph with {} nodes", v, view.node_count()
+        ))));
+    }
+
+    let timestamps = timestamps.to_kind(Kind::Int64).contiguous();
+    let timestamps_data = try_tensor_to_slice::<i64>(&timestamps)?;
+    let sorted = graph.ordering == NeighborOrdering::ByTime;
+    fold_event_times(&EdgeAttrSource::new(&view).with_times(timestamps_data), nodes, last, sorted, times);
+    Ok(())
+}
+
+fn event_times(
+    graph: &CscGraphStorage,
+    timestamps: &Tensor,
+    nodes: &Tensor,
+    outgoing: Option<(&CsrGraphStorage, &Tensor)>,
+    last: bool,
+) -> TensorResult<Tensor> {
+    let nodes = nodes.to_kind(Kind::Int64).contiguous();
+    let nodes_data = try_tensor_to_slice::<i64>(&nodes)?;
+    let mut times = vec![NO_EVENT_TIME; nodes_data.len()];
+    fold_storage_event_times(graph, timestamps, nodes_data, last, &mut times)?;
+    if let Some((csr, csr_timestamps)) = outgoing {
+        fold_storage_event_times(csr, csr_timestamps, nodes_data, last, &mut times)?;
+    }
+    Ok(Tensor::of_slice(&times))
+}
+
+// Latest timestamp over the incoming edges of every node in `nodes`, and over the outgoing edges as well when a
+// csr graph with its own timestamps is given. The timestamps are aligned with the edge positions of the graph.
+// Isolated nodes get NO_EVENT_TIME.
+pub fn last_event_time(
+    graph: &CscGraphStorage,
+    timestamps: &Tensor,
+    nodes: &Tensor,
+    outgoing: Option<(&CsrGraphStorage, &Tensor)>,
+) -> TensorResult<Tensor> {
+    event_times(graph, timestamps, nodes, outgoing, true)
+}
+
+// Like `last_event_time`, but the earliest timestamp
+pub fn first_event_time(
+    graph: &CscGraphStorage,
+    timestamps: &Tensor,
+    nodes: &Tensor,
+    outgoing: Option<(&CsrGraphStorage, &Tensor)>,
+) -> TensorResult<Tensor> {
+    event_times(graph, timestamps, nodes, outgoing, false)
+}
+
+pub enum SeedTime<'a> {
+    Given(&'a [i64]),
+    // The seed time is derived from the edges of the seed, its last event time when sampling backwards in time and
+    // its first event time when sampling forward
+    Infer,
+}
+
+pub fn neighbor_sampling_homogenous_temporal<
+    G: NeighborSource + ?Sized, S: NeighborSource + ?Sized, N: Into<FanoutPolicy> + Copy, const FORWARD: bool,
+    const MODE: usize
+>(
+    rng: &mut impl Rng,
+    graph: &G,
+    inputs: &[NodeIdx],
+    num_neighbors: &[N],
+    sampler: &impl Sampler,
+    filter: &TemporalFilter<S, FORWARD, MODE>,
+    seed_time: SeedTime,
+    decay: Option<f64>,
+) -> TensorResult<(
+    Vec<NodeIdx>,
+    CooGraphBuilder,
+    Vec<LayerOffset>,
+)> {
+    let inferred;
+    let inputs_state = match seed_time {
+        SeedTime::Given(times) if times.len() == inputs.len() => times,
+        SeedTime::Given(times) => {
+            return Err(TensorConversionError::InvalidShape(Some(format!(
+                "expected {} seed times, got {}", inputs.len(), times.len()
+            ))));
+        }
+        SeedTime::Infer => {
+            let mut times = vec![NO_EVENT_TIME; inputs.len()];
+            fold_event_times(filter.source, inputs, !FORWARD, false, &mut times);
+            inferred = times;
+            &inferred
+        }
+    };
+
+    // The decay takes over from a uniform sampler and keeps whether it draws with replacement, other samplers would
+    // lose their weights or caps. tau = inf is uniform sampling.
+    match decay {
+        Some(tau) if tau.is_nan() || tau <= 0.0 => Err(TensorConversionError::Unknown(format!("decay must be positive, got {}", tau))),
+        Some(tau) if tau.is_finite() && !sampler.is_uniform() => Err(TensorConversionError::Unknown(
+            "decay can only be combined with uniform samplers without a repeat cap".to_string()
+        )),
+        Some(tau) if tau.is_finite() => match sampler.replace_policy() {
+            ReplacePolicy::Fixed(true) => {
+                let sampler = TemporalDecaySampler::<_, true>::new(filter.source, tau, FORWARD);
+                Ok(neighbor_sampling_homogenous(rng, graph, inputs, num_neighbors, &sampler, filter, inputs_state))
+            }
+            ReplacePolicy::Fixed(false) => {
+                let sampler = TemporalDecaySampler::<_, false>::new(filter.source, tau, FORWARD);
+                Ok(neighbor_sampling_homogenous(rng, graph, inputs, num_neighbors, &sampler, filter, inputs_state))
+            }
+            policy => {
+                let sampler = PerHopSampler::new(
+                    policy,
+                    num_neighbors.len(),
+                    TemporalDecaySampler::<_, false>::new(filter.source, tau, FORWARD),
+                    TemporalDecaySampler::<_, true>::new(filter.source, tau, FORWARD),
+                )?;
+                Ok(neighbor_sampling_homogenous(rng, graph, inputs, num_neighbors, &sampler, filter, inputs_state))
+            }
+        },
+        _ => Ok(neighbor_sampling_homogenous(rng, graph, inputs, num_neighbors, sampler, filter, inputs_state)),
+    }
+}
+
 
 pub trait Sampler {
     type State;
 
     fn init(&self, k: usize) -> Self::State;
 
+    fn resize(&self, state: &mut Self::State, k: usize);
+
+    // `src` are edge positions of `v`
     fn sample<'a>(
         &self,
         rng: &mut impl Rng,
         state: &'a mut Self::State,
+        v: NodeIdx,
         src: impl Iterator<Item=EdgePtr<usize>>,
     ) -> Iter<'a, EdgePtr<usize>>;
+
+    // Whether sampling k out of degree neighbors returns all of them without touching the rng
+    fn is_exhaustive(&self, _k: usize, _degree: usize) -> bool { false }
+
+    fn with_replacement(&self) -> bool { false }
+
+    // Called instead of `init` at the start of every hop, samplers that behave differently per hop override this
+    fn init_hop(&self, _hop: usize, k: usize) -> Self::State { self.init(k) }
+
+    fn replace_policy(&self) -> ReplacePolicy { ReplacePolicy::Fixed(self.with_replacement()) }
+
+    // Whether every eligible edge is equally likely and no other constraint applies, only then a temporal decay can
+    // take over the sampling
+    fn is_uniform(&self) -> bool { false }
 }
 
 pub struct UnweightedSampler<const REPLACE: bool>;
@@ -102,10 +367,15 @@ impl<const REPLACE: bool> Sampler for UnweightedSampler<REPLACE> {
         (Vec::new(), vec![0; k])
     }
 
+    fn resize(&self, state: &mut Self::State, k: usize) {
+        state.1.resize(k, 0);
+    }
+
     fn sample<'a>(
         &self,
         rng: &mut impl Rng,
         state: &'a mut Self::State,
+        _v: NodeIdx,
         src: impl Iterator<Item=EdgePtr<usize>>,
     ) -> Iter<'a, EdgePtr<usize>> {
         if REPLACE {
@@ -126,181 +396,1409 @@ impl<const REPLACE: bool> Sampler for UnweightedSampler<REPLACE> {
             state.1[0..n].iter()
         }
     }
+
+    fn is_exhaustive(&self, k: usize, degree: usize) -> bool {
+        !REPLACE && k >= degree
+    }
+
+    fn with_replacement(&self) -> bool {
+        REPLACE
+    }
+
+    fn is_uniform(&self) -> bool {
+        true
+    }
 }
 
-pub struct WeightedSampler<'w, W: Float + SampleUniform> {
-    pub weights: EdgeAttr<'w, W>,
+// Sampling with replacement where every edge is emitted at most `max_repeats` times per node. Once the cap binds
+// for all edges fewer than k neighbors are returned. Without a cap (or a cap of at least k) it draws exactly the
+// same samples as `UnweightedSampler::<true>`.
+pub struct ReplacementSampler {
+    pub max_repeats: Option<i64>,
 }
 
-impl<'w, W: Float + SampleUniform> WeightedSampler<'w, W> {
-    pub fn new(weights: EdgeAttr<'w, W>) -> Self {
-        Self { weights }
+impl ReplacementSampler {
+    pub fn new(max_repeats: Option<i64>) -> Self {
+        Self { max_repeats }
     }
 }
 
-impl<'w, W: Float + SampleUniform> Sampler for WeightedSampler<'w, W> {
-    type State = Vec<usize>;
+impl Sampler for ReplacementSampler {
+    type State = (
+        Vec<usize>,
+        Vec<usize>,
+        Vec<usize>,
+        Vec<usize>,
+    );
 
     fn init(&self, k: usize) -> Self::State {
-        vec![0; k]
+        (Vec::new(), vec![0; k], Vec::new(), Vec::new())
+    }
+
+    fn resize(&self, state: &mut Self::State, k: usize) {
+        state.1.resize(k, 0);
     }
 
     fn sample<'a>(
         &self,
         rng: &mut impl Rng,
         state: &'a mut Self::State,
+        _v: NodeIdx,
         src: impl Iterator<Item=EdgePtr<usize>>,
     ) -> Iter<'a, EdgePtr<usize>> {
-        let iter = src.map(|e| (e, *self.weights.get(e)));
-        let n = reservoir_sampling_weighted(rng, iter, state);
-        state[0..n].iter()
+        let (candidates, samples, counts, available) = state;
+        candidates.clear();
+        candidates.extend(src);
+
+        let n = if candidates.is_empty() {
+            0
+        } else {
+            match self.max_repeats {
+                Some(max_repeats) if (max_repeats.max(0) as usize) < samples.len() => replacement_sampling_capped(
+                    rng, candidates, samples, max_repeats.max(0) as usize, counts, available,
+                ),
+                _ => replacement_sampling(rng, candidates, samples),
+            }
+        };
+        samples[0..n].iter()
+    }
+
+    fn is_exhaustive(&self, _k: usize, _degree: usize) -> bool {
+        false
+    }
+
+    fn with_replacement(&self) -> bool {
+        true
+    }
+
+    fn is_uniform(&self) -> bool {
+        self.max_repeats.is_none()
     }
 }
 
-pub type LayerOffset = (NodePtr, EdgePtr, NodePtr);
+// The weights come from `weights` of the source, which panics on sources without them (see `EdgeAttrSource`)
+pub struct WeightedSampler<'w, G: ?Sized> {
+    pub source: &'w G,
+}
 
-pub fn neighbor_sampling_homogenous<
-    F: SamplingFilter
->(
-    rng: &mut impl Rng,
-    graph: &CscGraph,
-    inputs: &[NodeIdx],
-    num_neighbors: &[usize],
-    sampler: &impl Sampler,
-    filter: &F,
-    inputs_state: &[F::State],
-) -> (
-    Vec<NodeIdx>,
-    CooGraphBuilder,
-    Vec<LayerOffset>
-) {
-    // Initialize some data structures for the sampling process
-    let mut samples: Vec<NodeIdx> = Vec::new();
-    let mut states: Vec<F::State> = Vec::new();
+impl<'w, G: NeighborSource + ?Sized> WeightedSampler<'w, G> {
+    pub fn new(source: &'w G) -> Self {
+        Self { source }
+    }
+}
 
-    let mut layer_offsets: Vec<LayerOffset> = Vec::new();
-    let mut edge_index = CooGraphBuilder::new();
+impl<'w, G: NeighborSource + ?Sized> Sampler for WeightedSampler<'w, G> {
+    type State = Vec<usize>;
 
-    samples.extend_from_slice(inputs);
-    states.extend_from_slice(inputs_state);
+    fn init(&self, k: usize) -> Self::State {
+        vec![0; k]
+    }
 
-    let (mut begin, mut end) = (0, samples.len());
-    for num_samples in num_neighbors.iter().cloned() {
-        // Initialize the states
-        let mut sampler_state = sampler.init(num_samples);
+    fn resize(&self, state: &mut Self::State, k: usize) {
+        state.resize(k, 0);
+    }
 
-        // Add layer offset
-        layer_offsets.push((samples.len() as NodePtr, edge_index.len() as EdgePtr, samples.len() as NodePtr));
+    fn sample<'a>(
+        &self,
+        rng: &mut impl Rng,
+        state: &'a mut Self::State,
+        v: NodeIdx,
+        src: impl Iterator<Item=EdgePtr<usize>>,
+    ) -> Iter<'a, EdgePtr<usize>> {
+        let iter = src.map(|e| (e, self.source.weight_at(v, e).expect("the source has no edge weights")));
+        let n = reservoir_sampling_weighted(rng, iter, state);
+        state[0..n].iter()
+    }
 
-        for i in begin..end {
-            let w = samples[i];
-            let w_state = states[i];
+    fn is_exhaustive(&self, k: usize, degree: usize) -> bool {
+        k >= degree
+    }
+}
 
-            let neighbors_range = graph.neighbors_range(w);
-            if neighbors_range.is_empty() {
-                continue;
+// Weighted sampling with replacement from cumulative weights that are computed once, so repeated calls on the
+// same graph only binary search. This draws every sample independently, unlike the weighted reservoir sampling of
+// `WeightedSampler` which samples without replacement. cdf[e] is the sum of the weights of the edges of the node
+// of e up to and including e, which costs one f64 per edge on top of the weights of the source. Candidates that
+// are not a contiguous edge range (e.g. after a filter) are searched over their own prefix sums instead.
+pub struct WeightedReplacementSampler<'w, G: ?Sized> {
+    pub source: &'w G,
+    pub cdf: Vec<f64>,
+}
+
+impl<'w, G: NeighborSource + ?Sized> WeightedReplacementSampler<'w, G> {
+    pub fn new(source: &'w G) -> TensorResult<Self> {
+        let num_edges = (0..source.node_count() as NodeIdx).map(|v| source.edge_positions(v).end).max().unwrap_or(0);
+        let mut cdf = vec![0.0; num_edges];
+        for v in 0..source.node_count() as NodeIdx {
+            let weights = source.weights(v).ok_or_else(|| TensorConversionError::InvalidShape(Some(format!(
+                "weights must cover the {} edges of node {}", source.degree(v), v
+            ))))?;
+            if weights.iter().any(|w| w.is_nan() || *w < 0.0) {
+                return Err(TensorConversionError::Unknown("weights must be non negative".to_string()));
             }
 
-            let samples_filtered = neighbors_range.clone()
-                .filter(|edge_ptr| filter.filter(&w_state, w, *edge_ptr));
-            let samples_iter = sampler.sample(
-                rng, &mut sampler_state, samples_filtered,
-            );
+            let mut acc = 0.0;
+            for (c, w) in cdf[source.edge_positions(v)].iter_mut().zip(weights) {
+                acc += *w;
+                *c = acc;
+            }
+        }
+        Ok(Self { source, cdf })
+    }
+}
 
-            for edge_ptr in samples_iter {
-                let v = graph.get_by_ptr(*edge_ptr);
-                let j = samples.len();
-                let state = filter.mutate(&w_state, w, *edge_ptr);
+impl<'w, G: NeighborSource + ?Sized> Sampler for WeightedReplacementSampler<'w, G> {
+    type State = (
+        Vec<usize>,
+        Vec<f64>,
+        Vec<usize>,
+    );
 
-                samples.push(v);
-                states.push(state);
-                edge_index.push_edge(j as i64, i as i64, *edge_ptr as i64);
-            }
+    fn init(&self, k: usize) -> Self::State {
+        (Vec::new(), Vec::new(), vec![0; k])
+    }
+
+    fn resize(&self, state: &mut Self::State, k: usize) {
+        state.2.resize(k, 0);
+    }
+
+    fn sample<'a>(
+        &self,
+        rng: &mut impl Rng,
+        state: &'a mut Self::State,
+        v: NodeIdx,
+        src: impl Iterator<Item=EdgePtr<usize>>,
+    ) -> Iter<'a, EdgePtr<usize>> {
+        let (candidates, prefix, samples) = state;
+        candidates.clear();
+        candidates.extend(src);
+        let (first, last) = match (candidates.first(), candidates.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return samples[0..0].iter(),
+        };
+
+        // The cdf before `first` if the candidates are a contiguous run of the edges of `v`
+        let contiguous = last >= first && last - first + 1 == candidates.len();
+        let (cdf, lower) = if contiguous {
+            let lower = if first == self.source.edge_positions(v).start { 0.0 } else { self.cdf[first - 1] };
+            (&self.cdf[first..=last], lower)
+        } else {
+            prefix.clear();
+            let mut acc = 0.0;
+            prefix.extend(candidates.iter().map(|e| {
+                acc += self.source.weight_at(v, *e).expect("the source has no edge weights");
+                acc
+            }));
+            (&prefix[..], 0.0)
+        };
+        let total = cdf[cdf.len() - 1] - lower;
+        if total <= 0.0 {
+            return samples[0..0].iter();
         }
 
-        begin = end;
-        end = samples.len();
+        for dst in samples.iter_mut() {
+            let u = lower + rng.gen_range(0.0..total);
+            let i = cdf.partition_point(|c| *c <= u).min(cdf.len() - 1);
+            *dst = if contiguous { first + i } else { candidates[i] };
+        }
+        samples.iter()
     }
 
-    (
-        samples,
-        edge_index,
-        layer_offsets,
-    )
+    fn with_replacement(&self) -> bool {
+        true
+    }
 }
 
+// Samples the candidates with probability proportional to exp(-|t_seed - t| / tau), favouring the edges closest
+// to the seed time. Candidates all lie on one side of the seed time, so t_seed cancels out after normalizing and
+// the weights are relative to the closest candidate instead, which keeps large time gaps finite. Without
+// replacement it uses the Gumbel top-k trick, with replacement an alias table over the candidates.
+pub struct TemporalDecaySampler<'a, G: ?Sized, const REPLACE: bool> {
+    pub source: &'a G,
+    pub tau: f64,
+    pub forward: bool,
+}
 
-pub fn neighbor_sampling_heterogenous<
-    F: SamplingFilter
->(
-    rng: &mut impl Rng,
-    node_types: &[NodeType],
-    edge_types: &[EdgeType],
-    graphs: &HashMap<RelType, CscGraph>,
-    inputs: &HashMap<NodeType, &[NodeIdx]>,
-    num_neighbors: &HashMap<RelType, Vec<usize>>,
-    num_hops: usize,
-    sampler: &HashMap<RelType, impl Sampler>,
-    filter: &HashMap<RelType, F>,
-    inputs_state: &HashMap<NodeType, &[F::State]>,
-) -> (
-    HashMap<NodeType, Vec<NodeIdx>>,
-    HashMap<RelType, CooGraphBuilder>,
-    HashMap<RelType, Vec<LayerOffset>>,
-) {
-    // TODO: When same nodes occur in the same layer, we should only keep one (therefore reducing workload at nn side)
-    // See: https://i.imgur.com/mFurLy7.png
+impl<'a, G: NeighborSource + ?Sized, const REPLACE: bool> TemporalDecaySampler<'a, G, REPLACE> {
+    pub fn new(source: &'a G, tau: f64, forward: bool) -> Self {
+        Self { source, tau, forward }
+    }
+}
 
-    // Create a mapping to convert single string relations to edge type triplets:
-    let mut to_edge_types = HashMap::<RelType, EdgeType>::new();
-    for e @ (src_node_type, rel_type, dst_node_type) in edge_types {
-        to_edge_types.insert(format!("{}__{}__{}", src_node_type, rel_type, dst_node_type), e.clone());
+impl<'a, G: NeighborSource + ?Sized, const REPLACE: bool> Sampler for TemporalDecaySampler<'a, G, REPLACE> {
+    type State = (
+        Vec<(usize, f64)>,
+        Vec<usize>,
+        Vec<(f64, usize)>,
+    );
+
+    fn init(&self, k: usize) -> Self::State {
+        (Vec::new(), vec![0; k], Vec::new())
     }
 
-    // Initialize some data structures for the sampling process
-    let mut samples: HashMap<NodeType, Vec<NodeIdx>> = HashMap::new();
-    let mut states: HashMap<NodeType, Vec<F::State>> = HashMap::new();
+    fn resize(&self, state: &mut Self::State, k: usize) {
+        state.1.resize(k, 0);
+    }
 
-    for node_type in node_types {
-        let samples = samples
-            .entry(node_type.clone())
-            .or_insert_with(Vec::new);
-        if let Some(inputs) = inputs.get(node_type) {
-            samples.extend_from_slice(inputs);
+    fn sample<'b>(
+        &self,
+        rng: &mut impl Rng,
+        state: &'b mut Self::State,
+        v: NodeIdx,
+        src: impl Iterator<Item=EdgePtr<usize>>,
+    ) -> Iter<'b, EdgePtr<usize>> {
+        let (candidates, samples, keys) = state;
+        candidates.clear();
+        candidates.extend(src.map(|e| (e, 0.0)));
+        if candidates.is_empty() {
+            return samples[0..0].iter();
         }
 
-        let states = states
-            .entry(node_type.clone())
-            .or_insert_with(Vec::new);
-        if let Some(inputs_state) = inputs_state.get(node_type) {
-            states.extend_from_slice(inputs_state);
+        let time_at = |e: usize| self.source.time_at(v, e).expect("the source has no edge timestamps");
+        let times = candidates.iter().map(|(e, _)| time_at(*e));
+        let closest = if self.forward { times.min() } else { times.max() }.unwrap();
+        for (e, log_w) in candidates.iter_mut() {
+            let gap = (time_at(*e) - closest).abs();
+            *log_w = -(gap as f64) / self.tau;
         }
+
+        let n = if REPLACE {
+            let weights: Vec<f64> = candidates.iter().map(|(_, log_w)| log_w.exp()).collect();
+            let alias = AliasTable::new(&weights).unwrap();
+            for dst_val in samples.iter_mut() {
+                *dst_val = candidates[alias.sample(rng)].0;
+            }
+            samples.len()
+        } else if candidates.len() <= samples.len() {
+            for (dst_val, (e, _)) in samples.iter_mut().zip(candidates.iter()) {
+                *dst_val = *e;
+            }
+            candidates.len()
+        } else {
+            gumbel_top_k_sampling(rng, candidates, samples, keys)
+        };
+        samples[0..n].iter()
     }
 
-    let mut layer_offsets: HashMap<RelType, Vec<LayerOffset>> = graphs.keys()
-        .map(|rel_type| (rel_type.clone(), Vec::new()))
-        .collect();
-    let mut edge_index: HashMap<RelType, CooGraphBuilder> = graphs.keys()
-        .map(|rel_type| (rel_type.clone(), CooGraphBuilder::new()))
-        .collect();
+    fn is_exhaustive(&self, k: usize, degree: usize) -> bool {
+        !REPLACE && k >= degree
+    }
 
-    // Maintains begin/end indices for each node type
-    let mut slices: HashMap<NodeType, (usize, usize)> = samples.iter()
-        .map(|(node_type, samples)| (node_type.clone(), (0, samples.len())))
-        .collect();
+    fn with_replacement(&self) -> bool {
+        REPLACE
+    }
+}
 
-    for ell in 0..num_hops {
-        // Apply sampling for each relation type
-        for (rel_type, num_samples) in num_neighbors {
-            let num_samples = num_samples[ell];
-            let (src_node_type, _, dst_node_type) = &to_edge_types[rel_type];
-            let filter = &filter[rel_type];
-            let sampler = &sampler[rel_type];
+// Whether a sampler draws with replacement, either for all hops or one flag per hop
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplacePolicy {
+    Fixed(bool),
+    PerHop(Vec<bool>),
+}
 
-            // Initialize the states
-            let mut sampler_state = sampler.init(num_samples);
+impl ReplacePolicy {
+    // None for hops past the end of a per hop policy
+    pub fn at(&self, hop: usize) -> Option<bool> {
+        match self {
+            ReplacePolicy::Fixed(replace) => Some(*replace),
+            ReplacePolicy::PerHop(flags) => flags.get(hop).copied(),
+        }
+    }
 
-            // Select data
+    // Whether any hop draws with (or without, for `replace` false) replacement
+    pub fn uses(&self, replace: bool) -> bool {
+        match self {
+            ReplacePolicy::Fixed(fixed) => *fixed == replace,
+            ReplacePolicy::PerHop(flags) => flags.contains(&replace),
+        }
+    }
+
+    pub fn validate(&self, num_hops: usize) -> TensorResult<()> {
+        match self {
+            ReplacePolicy::PerHop(flags) if flags.len() != num_hops => Err(TensorConversionError::InvalidShape(Some(format!(
+                "expected a replacement flag for each of the {} hops, got {}", num_hops, flags.len()
+            )))),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<bool> for ReplacePolicy {
+    fn from(replace: bool) -> Self {
+        ReplacePolicy::Fixed(replace)
+    }
+}
+
+// Samples every hop with `without` or `with` depending on whether `replace` draws with replacement at that hop. The
+// policy is checked against the number of hops on construction, sampling more hops than that panics.
+pub struct PerHopSampler<A, B> {
+    replace: ReplacePolicy,
+    pub without: A,
+    pub with: B,
+}
+
+impl<A: Sampler, B: Sampler> PerHopSampler<A, B> {
+    pub fn new(replace: ReplacePolicy, num_hops: usize, without: A, with: B) -> TensorResult<Self> {
+        replace.validate(num_hops)?;
+        Ok(Self { replace, without, with })
+    }
+
+    fn replace_at(&self, hop: usize) -> bool {
+        self.replace.at(hop).unwrap_or_else(|| panic!("no replacement flag for hop {}", hop))
+    }
+}
+
+impl PerHopSampler<UnweightedSampler<false>, UnweightedSampler<true>> {
+    pub fn uniform(replace: ReplacePolicy, num_hops: usize) -> TensorResult<Self> {
+        Self::new(replace, num_hops, UnweightedSampler, UnweightedSampler)
+    }
+}
+
+impl<A: Sampler, B: Sampler> Sampler for PerHopSampler<A, B> {
+    type State = (bool, A::State, B::State);
+
+    // Outside of a hop the policy of the first hop is used
+    fn init(&self, k: usize) -> Self::State {
+        self.init_hop(0, k)
+    }
+
+    fn resize(&self, state: &mut Self::State, k: usize) {
+        if state.0 {
+            self.with.resize(&mut state.2, k);
+        } else {
+            self.without.resize(&mut state.1, k);
+        }
+    }
+
+    fn sample<'a>(
+        &self,
+        rng: &mut impl Rng,
+        state: &'a mut Self::State,
+        v: NodeIdx,
+        src: impl Iterator<Item=EdgePtr<usize>>,
+    ) -> Iter<'a, EdgePtr<usize>> {
+        if state.0 {
+            self.with.sample(rng, &mut state.2, v, src)
+        } else {
+            self.without.sample(rng, &mut state.1, v, src)
+        }
+    }
+
+    // The hop is not known here, so it has to hold for the samplers of all hops
+    fn is_exhaustive(&self, k: usize, degree: usize) -> bool {
+        (!self.replace.uses(false) || self.without.is_exhaustive(k, degree))
+            && (!self.replace.uses(true) || self.with.is_exhaustive(k, degree))
+    }
+
+    fn with_replacement(&self) -> bool {
+        self.replace_at(0)
+    }
+
+    fn init_hop(&self, hop: usize, k: usize) -> Self::State {
+        if self.replace_at(hop) {
+            (true, self.without.init(0), self.with.init(k))
+        } else {
+            (false, self.without.init(k), self.with.init(0))
+        }
+    }
+
+    fn replace_policy(&self) -> ReplacePolicy {
+        self.replace.clone()
+    }
+
+    fn is_uniform(&self) -> bool {
+        (!self.replace.uses(false) || self.without.is_uniform()) && (!self.replace.uses(true) || self.with.is_uniform())
+    }
+}
+
+pub type LayerOffset = (NodePtr, EdgePtr, NodePtr);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FanoutPolicy {
+    Fixed(usize),
+    // ceil(fraction * degree), optionally clamped to [min, max]
+    Fraction { fraction: f64, min: Option<usize>, max: Option<usize> },
+    // ceil(scale * sqrt(degree))
+    Sqrt(f64),
+    // The whole neighborhood
+    All,
+}
+
+impl FanoutPolicy {
+    pub fn fraction(fraction: f64) -> Self {
+        FanoutPolicy::Fraction { fraction, min: None, max: None }
+    }
+
+    // Negative counts (-1 by convention, see `validate_hetero_fanouts`) take the whole neighborhood
+    pub fn from_count(k: i64) -> Self {
+        if k < 0 { FanoutPolicy::All } else { FanoutPolicy::Fixed(k as usize) }
+    }
+
+    pub fn fanout(&self, degree: usize) -> usize {
+        match *self {
+            FanoutPolicy::Fixed(k) => k,
+            FanoutPolicy::Fraction { fraction, min, max } => {
+                let k = (fraction * degree as f64).ceil() as usize;
+                let k = min.map_or(k, |min| k.max(min));
+                max.map_or(k, |max| k.min(max))
+            }
+            FanoutPolicy::Sqrt(scale) => (scale * (degree as f64).sqrt()).ceil() as usize,
+            FanoutPolicy::All => degree,
+        }
+    }
+}
+
+impl From<usize> for FanoutPolicy {
+    fn from(k: usize) -> Self {
+        FanoutPolicy::Fixed(k)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HopStats {
+    pub hop: usize,
+    pub rel_type: Option<RelType>,
+    pub num_frontier: usize,
+    pub num_nodes: usize,
+    pub num_edges: usize,
+    // Frontier nodes without any neighbor passing the filter
+    pub num_empty: usize,
+    // Frontier nodes with more eligible neighbors than the fanout
+    pub num_truncated: usize,
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for HopStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "hop {}", self.hop)?;
+        if let Some(rel_type) = &self.rel_type {
+            write!(f, " [{}]", rel_type)?;
+        }
+        write!(
+            f, ": frontier={} nodes={} edges={} empty={} truncated={} time={:?}",
+            self.num_frontier, self.num_nodes, self.num_edges, self.num_empty, self.num_truncated, self.elapsed,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SamplerStats {
+    pub hops: Vec<HopStats>,
+    // Seeds without any sampled edge in the first hop, see `LonelySeedPolicy`
+    pub num_lonely_seeds: usize,
+    // Per frontier node fanin coverage, only collected when created through `with_coverage`
+    pub coverage: Option<HashMap<Option<RelType>, Coverage>>,
+}
+
+impl SamplerStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_coverage() -> Self {
+        Self { coverage: Some(HashMap::new()), ..Self::default() }
+    }
+
+    pub fn to_dict(&self) -> HashMap<String, Vec<usize>> {
+        let mut columns = hop_stats_columns(self.hops.iter());
+        columns.insert("num_lonely_seeds".to_string(), vec![self.num_lonely_seeds]);
+        columns
+    }
+
+    pub fn to_hetero_dict(&self) -> HashMap<RelType, HashMap<String, Vec<usize>>> {
+        let mut rel_types: Vec<&RelType> = self.hops.iter().filter_map(|s| s.rel_type.as_ref()).collect();
+        rel_types.sort();
+        rel_types.dedup();
+        rel_types.into_iter()
+            .map(|rel_type| {
+                let hops = self.hops.iter().filter(|s| s.rel_type.as_ref() == Some(rel_type));
+                (rel_type.clone(), hop_stats_columns(hops))
+            })
+            .collect()
+    }
+}
+
+// For every frontier node of every hop the number of sampled edges next to its true degree. `node` is the position
+// of the frontier node in the samples (of the destination node type), the true degree is the neighborhood size before
+// filtering, so a fanout exceeding every degree with a pass-all filter gives sampled == degree.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Coverage {
+    pub node: Vec<i64>,
+    pub hop: Vec<i64>,
+    pub sampled: Vec<i64>,
+    pub degree: Vec<i64>,
+}
+
+impl Coverage {
+    pub fn len(&self) -> usize {
+        self.node.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.node.is_empty()
+    }
+
+    pub fn push(&mut self, node: usize, hop: usize, sampled: usize, degree: usize) {
+        self.node.push(node as i64);
+        self.hop.push(hop as i64);
+        self.sampled.push(sampled as i64);
+        self.degree.push(degree as i64);
+    }
+
+    // Returns the [2, K] (node, hop) index and the aligned sampled counts and degrees
+    pub fn to_tensors(&self) -> (Tensor, Tensor, Tensor) {
+        let index = Tensor::stack(&[Tensor::of_slice(&self.node), Tensor::of_slice(&self.hop)], 0);
+        (index, Tensor::of_slice(&self.sampled), Tensor::of_slice(&self.degree))
+    }
+}
+
+fn hop_stats_columns<'a>(hops: impl Iterator<Item=&'a HopStats>) -> HashMap<String, Vec<usize>> {
+    let mut columns: HashMap<String, Vec<usize>> = HashMap::new();
+    for s in hops {
+        for (k, v) in [
+            ("hop", s.hop),
+            ("num_frontier", s.num_frontier),
+            ("num_nodes", s.num_nodes),
+            ("num_edges", s.num_edges),
+            ("num_empty", s.num_empty),
+            ("num_truncated", s.num_truncated),
+            ("elapsed_ns", s.elapsed.as_nanos() as usize),
+        ] {
+            columns.entry(k.to_string()).or_default().push(v);
+        }
+    }
+    columns
+}
+
+impl fmt::Display for SamplerStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for s in &self.hops {
+            writeln!(f, "{}", s)?;
+        }
+        if self.num_lonely_seeds > 0 {
+            writeln!(f, "lonely seeds: {}", self.num_lonely_seeds)?;
+        }
+        Ok(())
+    }
+}
+
+pub trait SamplingTracer {
+    // Checked at compile time so the untraced sampling loop stays as is
+    const ENABLED: bool;
+
+    fn record(&mut self, stats: HopStats);
+
+    fn record_lonely_seeds(&mut self, _num_lonely: usize) {}
+
+    // Coverage is only counted when asked for, it allocates per frontier node
+    fn coverage_enabled(&self) -> bool { false }
+
+    fn record_coverage(&mut self, _rel_type: Option<&RelType>, _coverage: Coverage) {}
+}
+
+pub struct NoTracer;
+
+impl SamplingTracer for NoTracer {
+    const ENABLED: bool = false;
+
+    fn record(&mut self, _stats: HopStats) {}
+}
+
+impl SamplingTracer for SamplerStats {
+    const ENABLED: bool = true;
+
+    fn record(&mut self, stats: HopStats) {
+        self.hops.push(stats);
+    }
+
+    fn record_lonely_seeds(&mut self, num_lonely: usize) {
+        self.num_lonely_seeds += num_lonely;
+    }
+
+    fn coverage_enabled(&self) -> bool {
+        self.coverage.is_some()
+    }
+
+    fn record_coverage(&mut self, rel_type: Option<&RelType>, coverage: Coverage) {
+        if let Some(all) = self.coverage.as_mut() {
+            let entry = all.entry(rel_type.cloned()).or_default();
+            entry.node.extend(coverage.node);
+            entry.hop.extend(coverage.hop);
+            entry.sampled.extend(coverage.sampled);
+            entry.degree.extend(coverage.degree);
+        }
+    }
+}
+
+impl<C: FnMut(HopStats)> SamplingTracer for C {
+    const ENABLED: bool = true;
+
+    fn record(&mut self, stats: HopStats) {
+        self(stats)
+    }
+}
+
+// What to do with seeds that end up without any sampled edge in the first hop, because they have no neighbors or none
+// that pass the filter. Models that expect at least one message per seed would otherwise silently get none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LonelySeedPolicy {
+    Keep,
+    // Adds the edge (i, i) with edge position -1 for every lonely seed i
+    SelfLoop,
+    Error,
+}
+
+impl LonelySeedPolicy {
+    // Bipartite samples index rows and cols into different lists, so a seed can't be connected to itself
+    pub fn validate(&self, bipartite: bool) -> TensorResult<()> {
+        if bipartite && *self == LonelySeedPolicy::SelfLoop {
+            return Err(TensorConversionError::Unknown("self loops for lonely seeds need a homogenous sample".to_string()));
+        }
+        Ok(())
+    }
+
+    // Self loops of a node type go to its first sampled relation onto itself, node types with seeds need one
+    pub fn validate_heterogenous(
+        &self,
+        edge_types: &[EdgeType],
+        inputs: &HashMap<NodeType, &[NodeIdx]>,
+        has_relation: impl Fn(&RelType) -> bool,
+    ) -> TensorResult<()> {
+        if *self != LonelySeedPolicy::SelfLoop {
+            return Ok(());
+        }
+        let loop_relations = self_loop_relations(edge_types, has_relation);
+        let mut missing: Vec<&NodeType> = inputs.iter()
+            .filter(|(node_type, inputs)| !inputs.is_empty() && !loop_relations.contains_key(*node_type))
+            .map(|(node_type, _)| node_type)
+            .collect();
+        missing.sort();
+        if !missing.is_empty() {
+            return Err(TensorConversionError::Unknown(format!(
+                "self loops for lonely seeds need a relation from and to the node types {:?}", missing
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn self_loop_relations(edge_types: &[EdgeType], has_relation: impl Fn(&RelType) -> bool) -> HashMap<NodeType, RelType> {
+    let mut loop_relations = HashMap::new();
+    for (src_node_type, rel_type, dst_node_type) in edge_types {
+        let rel_type = format!("{}__{}__{}", src_node_type, rel_type, dst_node_type);
+        if src_node_type == dst_node_type && has_relation(&rel_type) {
+            loop_relations.entry(src_node_type.clone()).or_insert(rel_type);
+        }
+    }
+    loop_relations
+}
+
+// Applies the policy to the output of a homogenous (also dedup) or bipartite sampler, whose seeds are the local ids
+// 0..inputs.len() of the cols. Self loops are appended to the edges of the first hop and the layer offsets of the
+// later hops are shifted accordingly. Returns the number of lonely seeds, an error lists their global ids.
+pub fn apply_lonely_seed_policy(
+    policy: LonelySeedPolicy,
+    inputs: &[NodeIdx],
+    edge_index: &mut CooGraphBuilder,
+    layer_offsets: &mut [LayerOffset],
+    bipartite: bool,
+) -> TensorResult<usize> {
+    policy.validate(bipartite)?;
+    if layer_offsets.is_empty() {
+        return Ok(0);
+    }
+
+    let first_hop_end = layer_offsets.get(1).map_or(edge_index.len(), |o| o.1 as usize);
+    let mut has_edge = vec![false; inputs.len()];
+    for col in &edge_index.cols[layer_offsets[0].1 as usize..first_hop_end] {
+        if let Some(seen) = has_edge.get_mut(*col as usize) {
+            *seen = true;
+        }
+    }
+    let lonely: Vec<usize> = (0..inputs.len()).filter(|i| !has_edge[*i]).collect();
+
+    match policy {
+        LonelySeedPolicy::Keep => {}
+        LonelySeedPolicy::SelfLoop => {
+            let seeds = lonely.iter().map(|i| *i as NodeIdx);
+            edge_index.rows.splice(first_hop_end..first_hop_end, seeds.clone());
+            edge_index.cols.splice(first_hop_end..first_hop_end, seeds);
+            edge_index.edge_index.splice(first_hop_end..first_hop_end, lonely.iter().map(|_| -1));
+            for offset in layer_offsets[1..].iter_mut() {
+                offset.1 += lonely.len() as EdgePtr;
+            }
+        }
+        LonelySeedPolicy::Error if !lonely.is_empty() => {
+            let ids: Vec<NodeIdx> = lonely.iter().map(|i| inputs[*i]).collect();
+            return Err(TensorConversionError::Unknown(format!("seeds without sampled neighbors: {:?}", ids)));
+        }
+        LonelySeedPolicy::Error => {}
+    }
+    Ok(lonely.len())
+}
+
+// Applies the policy to the output of the heterogenous sampler, a seed is lonely when none of the relations into its
+// node type sampled an edge for it in the first hop. Self loops go to the relation picked by
+// `LonelySeedPolicy::validate_heterogenous`, the error lists the global ids per node type.
+pub fn apply_lonely_seed_policy_heterogenous(
+    policy: LonelySeedPolicy,
+    edge_types: &[EdgeType],
+    inputs: &HashMap<NodeType, &[NodeIdx]>,
+    edge_index: &mut HashMap<RelType, CooGraphBuilder>,
+    layer_offsets: &mut HashMap<RelType, Vec<LayerOffset>>,
+) -> TensorResult<usize> {
+    let is_sampled = |rel_type: &RelType| layer_offsets.get(rel_type).is_some_and(|offsets| !offsets.is_empty());
+    if !layer_offsets.values().any(|offsets| !offsets.is_empty()) {
+        return Ok(0);
+    }
+    policy.validate_heterogenous(edge_types, inputs, is_sampled)?;
+    let first_hop_end = |edge_index: &CooGraphBuilder, offsets: &[LayerOffset]| {
+        offsets.get(1).map_or(edge_index.len(), |o| o.1 as usize)
+    };
+
+    let mut has_edge: HashMap<&NodeType, Vec<bool>> = inputs.iter()
+        .map(|(node_type, inputs)| (node_type, vec![false; inputs.len()]))
+        .collect();
+    for (src_node_type, rel_type, dst_node_type) in edge_types {
+        let rel_type = format!("{}__{}__{}", src_node_type, rel_type, dst_node_type);
+        let (edges, offsets) = match (edge_index.get(&rel_type), layer_offsets.get(&rel_type)) {
+            (Some(edges), Some(offsets)) if !offsets.is_empty() => (edges, offsets),
+            _ => continue,
+        };
+        if let Some(has_edge) = has_edge.get_mut(dst_node_type) {
+            for col in &edges.cols[offsets[0].1 as usize..first_hop_end(edges, offsets)] {
+                if let Some(seen) = has_edge.get_mut(*col as usize) {
+                    *seen = true;
+                }
+            }
+        }
+    }
+    let mut lonely: Vec<(&NodeType, Vec<usize>)> = has_edge.into_iter()
+        .map(|(node_type, has_edge)| (node_type, (0..has_edge.len()).filter(|i| !has_edge[*i]).collect::<Vec<_>>()))
+        .filter(|(_, lonely)| !lonely.is_empty())
+        .collect();
+    lonely.sort();
+    let num_lonely = lonely.iter().map(|(_, lonely)| lonely.len()).sum();
+
+    match policy {
+        LonelySeedPolicy::Keep => {}
+        LonelySeedPolicy::SelfLoop => {
+            let loop_relations = self_loop_relations(edge_types, is_sampled);
+            for (node_type, lonely) in &lonely {
+                let rel_type = &loop_relations[*node_type];
+                let (edges, offsets) = (edge_index.get_mut(rel_type).unwrap(), layer_offsets.get_mut(rel_type).unwrap());
+                let end = first_hop_end(edges, offsets);
+                let seeds = lonely.iter().map(|i| *i as NodeIdx);
+                edges.rows.splice(end..end, seeds.clone());
+                edges.cols.splice(end..end, seeds);
+                edges.edge_index.splice(end..end, lonely.iter().map(|_| -1));
+                for offset in offsets[1..].iter_mut() {
+                    offset.1 += lonely.len() as EdgePtr;
+                }
+            }
+        }
+        LonelySeedPolicy::Error if num_lonely > 0 => {
+            let ids: Vec<(&NodeType, Vec<NodeIdx>)> = lonely.iter()
+                .map(|(node_type, lonely)| (*node_type, lonely.iter().map(|i| inputs[*node_type][*i]).collect()))
+                .collect();
+            return Err(TensorConversionError::Unknown(format!("seeds without sampled neighbors: {:?}", ids)));
+        }
+        LonelySeedPolicy::Error => {}
+    }
+    Ok(num_lonely)
+}
+
+pub fn neighbor_sampling_homogenous<
+    G: NeighborSource + ?Sized, F: SamplingFilter, N: Into<FanoutPolicy> + Copy
+>(
+    rng: &mut impl Rng,
+    graph: &G,
+    inputs: &[NodeIdx],
+    num_neighbors: &[N],
+    sampler: &impl Sampler,
+    filter: &F,
+    inputs_state: &[F::State],
+) -> (
+    Vec<NodeIdx>,
+    CooGraphBuilder,
+    Vec<LayerOffset>
+) {
+    neighbor_sampling_homogenous_traced(
+        rng, graph, inputs, num_neighbors, sampler, filter, inputs_state, &mut NoTracer,
+    )
+}
+
+pub fn neighbor_sampling_homogenous_traced<
+    G: NeighborSource + ?Sized, F: SamplingFilter, N: Into<FanoutPolicy> + Copy, T: SamplingTracer
+>(
+    rng: &mut impl Rng,
+    graph: &G,
+    inputs: &[NodeIdx],
+    num_neighbors: &[N],
+    sampler: &impl Sampler,
+    filter: &F,
+    inputs_state: &[F::State],
+    tracer: &mut T,
+) -> (
+    Vec<NodeIdx>,
+    CooGraphBuilder,
+    Vec<LayerOffset>
+) {
+    sample_homogenous(rng, graph, inputs, num_neighbors, sampler, filter, inputs_state, None, tracer)
+}
+
+// Without `local_ids` every sampled edge adds a node, with them only newly discovered nodes are added
+#[allow(clippy::too_many_arguments)]
+fn sample_homogenous<
+    G: NeighborSource + ?Sized, F: SamplingFilter, N: Into<FanoutPolicy> + Copy, T: SamplingTracer
+>(
+    rng: &mut impl Rng,
+    graph: &G,
+    inputs: &[NodeIdx],
+    num_neighbors: &[N],
+    sampler: &impl Sampler,
+    filter: &F,
+    inputs_state: &[F::State],
+    mut local_ids: Option<LocalIds>,
+    tracer: &mut T,
+) -> (
+    Vec<NodeIdx>,
+    CooGraphBuilder,
+    Vec<LayerOffset>
+) {
+    // Initialize some data structures for the sampling process
+    let mut samples: Vec<NodeIdx> = Vec::new();
+    let mut states: Vec<F::State> = Vec::new();
+
+    let mut layer_offsets: Vec<LayerOffset> = Vec::new();
+    let mut edge_index = CooGraphBuilder::new();
+
+    if let Some(local_ids) = local_ids.as_mut() {
+        for (i, v) in inputs.iter().enumerate() {
+            local_ids.get_or_insert(*v, i as NodeIdx);
+        }
+    }
+    samples.extend_from_slice(inputs);
+    states.extend_from_slice(inputs_state);
+
+    let track_coverage = T::ENABLED && tracer.coverage_enabled();
+    let (mut begin, mut end) = (0, samples.len());
+    for (hop, fanout) in num_neighbors.iter().cloned().enumerate() {
+        let hop_start = if T::ENABLED { Some(Instant::now()) } else { None };
+        let (mut num_empty, mut num_truncated) = (0, 0);
+        let mut coverage = Coverage::default();
+        let num_edges_start = edge_index.len();
+
+        // Initialize the states
+        let fanout: FanoutPolicy = fanout.into();
+        let mut num_samples = fanout.fanout(0);
+        let mut sampler_state = sampler.init_hop(hop, num_samples);
+
+        // Add layer offset
+        layer_offsets.push((samples.len() as NodePtr, edge_index.len() as EdgePtr, samples.len() as NodePtr));
+
+        for i in begin..end {
+            let w = samples[i];
+            let w_state = states[i];
+
+            let neighbors_range = graph.edge_positions(w);
+            if neighbors_range.is_empty() {
+                if T::ENABLED { num_empty += 1; }
+                if track_coverage { coverage.push(i, hop, 0, 0); }
+                continue;
+            }
+
+            let k = fanout.fanout(neighbors_range.len());
+            if k != num_samples {
+                sampler.resize(&mut sampler_state, k);
+                num_samples = k;
+            }
+            if num_samples == 0 {
+                if T::ENABLED { num_truncated += 1; }
+                if track_coverage { coverage.push(i, hop, 0, neighbors_range.len()); }
+                continue;
+            }
+
+            let mut num_eligible = 0;
+            let samples_filtered = neighbors_range.clone()
+                .filter(|edge_ptr| filter.filter(&w_state, w, *edge_ptr))
+                .inspect(|_| if T::ENABLED { num_eligible += 1; });
+            let samples_iter = sampler.sample(
+                rng, &mut sampler_state, w, samples_filtered,
+            );
+            let num_sampled_start = edge_index.len();
+
+            for edge_ptr in samples_iter {
+                let v = graph.neighbor_at(*edge_ptr);
+                let (j, is_new) = match local_ids.as_mut() {
+                    Some(local_ids) => local_ids.get_or_insert(v, samples.len() as NodeIdx),
+                    None => (samples.len() as NodeIdx, true),
+                };
+                if is_new {
+                    samples.push(v);
+                    states.push(filter.mutate(&w_state, w, *edge_ptr));
+                }
+                edge_index.push_edge(j, i as i64, *edge_ptr as i64);
+            }
+
+            if track_coverage {
+                coverage.push(i, hop, edge_index.len() - num_sampled_start, neighbors_range.len());
+            }
+            if T::ENABLED {
+                if num_eligible == 0 {
+                    num_empty += 1;
+                } else if num_eligible > num_samples {
+                    num_truncated += 1;
+                }
+            }
+        }
+
+        if track_coverage {
+            tracer.record_coverage(None, coverage);
+        }
+        if let Some(hop_start) = hop_start {
+            tracer.record(HopStats {
+                hop,
+                rel_type: None,
+                num_frontier: end - begin,
+                num_nodes: samples.len() - end,
+                num_edges: edge_index.len() - num_edges_start,
+                num_empty,
+                num_truncated,
+                elapsed: hop_start.elapsed(),
+            });
+        }
+
+        begin = end;
+        end = samples.len();
+    }
+
+    (
+        samples,
+        edge_index,
+        layer_offsets,
+    )
+}
+
+// Like `neighbor_sampling_homogenous` on a graph with typed edges, every node samples up to
+// num_neighbors[hop][t] of its edges of type t. `type_ptrs` are the per type sub-ranges of a `TypedGraphStorage`
+// over `graph`. Additionally returns the type of every sampled edge.
+pub fn neighbor_sampling_typed<G: NeighborSource + ?Sized, F: SamplingFilter>(
+    rng: &mut impl Rng,
+    graph: &G,
+    type_ptrs: &[i64],
+    inputs: &[NodeIdx],
+    num_neighbors: &[Vec<usize>],
+    sampler: &impl Sampler,
+    filter: &F,
+    inputs_state: &[F::State],
+) -> TensorResult<(
+    Vec<NodeIdx>,
+    CooGraphBuilder,
+    Vec<LayerOffset>,
+    Vec<i64>,
+)> {
+    let num_types = match graph.node_count() {
+        0 => 0,
+        n => (type_ptrs.len().saturating_sub(1)) / n,
+    };
+    if type_ptrs.len() != graph.node_count() * num_types + 1 {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "type_ptrs of length {} do not match a graph with {} nodes", type_ptrs.len(), graph.node_count()
+        ))));
+    }
+    if let Some(fanouts) = num_neighbors.iter().find(|f| f.len() != num_types) {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "expected a fanout for each of the {} edge types, got {}", num_types, fanouts.len()
+        ))));
+    }
+
+    let mut samples: Vec<NodeIdx> = Vec::new();
+    let mut states: Vec<F::State> = Vec::new();
+    let mut sample_types: Vec<i64> = Vec::new();
+
+    let mut layer_offsets: Vec<LayerOffset> = Vec::new();
+    let mut edge_index = CooGraphBuilder::new();
+
+    samples.extend_from_slice(inputs);
+    states.extend_from_slice(inputs_state);
+
+    let (mut begin, mut end) = (0, samples.len());
+    for (hop, fanouts) in num_neighbors.iter().enumerate() {
+        let mut num_samples = fanouts.iter().cloned().max().unwrap_or(0);
+        let mut sampler_state = sampler.init_hop(hop, num_samples);
+
+        layer_offsets.push((samples.len() as NodePtr, edge_index.len() as EdgePtr, samples.len() as NodePtr));
+
+        for i in begin..end {
+            let w = samples[i];
+            let w_state = states[i];
+
+            for (t, k) in fanouts.iter().cloned().enumerate() {
+                let offset = w as usize * num_types + t;
+                let type_range = type_ptrs[offset] as usize..type_ptrs[offset + 1] as usize;
+                if type_range.is_empty() || k == 0 {
+                    continue;
+                }
+                if k != num_samples {
+                    sampler.resize(&mut sampler_state, k);
+                    num_samples = k;
+                }
+
+                let samples_filtered = type_range
+                    .filter(|edge_ptr| filter.filter(&w_state, w, *edge_ptr));
+                for edge_ptr in sampler.sample(rng, &mut sampler_state, w, samples_filtered) {
+                    let v = graph.neighbor_at(*edge_ptr);
+                    let j = samples.len();
+                    samples.push(v);
+                    states.push(filter.mutate(&w_state, w, *edge_ptr));
+                    sample_types.push(t as i64);
+                    edge_index.push_edge(j as i64, i as i64, *edge_ptr as i64);
+                }
+            }
+        }
+
+        begin = end;
+        end = samples.len();
+    }
+
+    Ok((
+        samples,
+        edge_index,
+        layer_offsets,
+        sample_types,
+    ))
+}
+
+// Graphs up to this many nodes use a dense global to local id table, larger ones a hash map
+const DENSE_LOCAL_IDS_LIMIT: usize = 1 << 20;
+
+enum LocalIds {
+    Dense(Vec<NodeIdx>),
+    Sparse(HashMap<NodeIdx, NodeIdx>),
+}
+
+impl LocalIds {
+    fn new(node_count: usize) -> Self {
+        if node_count <= DENSE_LOCAL_IDS_LIMIT {
+            LocalIds::Dense(vec![-1; node_count])
+        } else {
+            LocalIds::Sparse(HashMap::new())
+        }
+    }
+
+    // Returns the local id of `v` and whether it was newly assigned
+    #[inline(always)]
+    fn get_or_insert(&mut self, v: NodeIdx, next: NodeIdx) -> (NodeIdx, bool) {
+        match self {
+            LocalIds::Dense(ids) => {
+                let id = &mut ids[v as usize];
+                if *id < 0 {
+                    *id = next;
+                    (next, true)
+                } else {
+                    (*id, false)
+                }
+            }
+            LocalIds::Sparse(ids) => {
+                let id = *ids.entry(v).or_insert(next);
+                (id, id == next)
+            }
+        }
+    }
+}
+
+// Like `neighbor_sampling_homogenous`, but every node gets a single local id across all hops. The inputs
+// (expected to be unique) take ids 0..inputs.len(), other nodes are numbered in discovery order and only
+// newly discovered nodes are expanded in the next hop. The returned samples map local to global ids and
+// the edges refer to local ids. A node reached multiple times keeps the state of its first discovery.
+pub fn neighbor_sampling_homogenous_dedup<
+    G: NeighborSource + ?Sized, F: SamplingFilter, N: Into<FanoutPolicy> + Copy
+>(
+    rng: &mut impl Rng,
+    graph: &G,
+    inputs: &[NodeIdx],
+    num_neighbors: &[N],
+    sampler: &impl Sampler,
+    filter: &F,
+    inputs_state: &[F::State],
+) -> (
+    Vec<NodeIdx>,
+    CooGraphBuilder,
+    Vec<LayerOffset>
+) {
+    neighbor_sampling_homogenous_dedup_traced(rng, graph, inputs, num_neighbors, sampler, filter, inputs_state, &mut NoTracer)
+}
+
+pub fn neighbor_sampling_homogenous_dedup_traced<
+    G: NeighborSource + ?Sized, F: SamplingFilter, N: Into<FanoutPolicy> + Copy, T: SamplingTracer
+>(
+    rng: &mut impl Rng,
+    graph: &G,
+    inputs: &[NodeIdx],
+    num_neighbors: &[N],
+    sampler: &impl Sampler,
+    filter: &F,
+    inputs_state: &[F::State],
+    tracer: &mut T,
+) -> (
+    Vec<NodeIdx>,
+    CooGraphBuilder,
+    Vec<LayerOffset>
+) {
+    let local_ids = LocalIds::new(graph.node_count());
+    sample_homogenous(rng, graph, inputs, num_neighbors, sampler, filter, inputs_state, Some(local_ids), tracer)
+}
+
+// For every edge position of the reverse graph, the position of the same edge in the forward graph, when both were
+// converted from the same edge list (the reverse one transposed) and `perm` and `reverse_perm` are their perms
+pub fn bipartite_reverse_positions(perm: &[i64], reverse_perm: &[i64]) -> Vec<i64> {
+    let mut positions = vec![0_i64; perm.len()];
+    for (ptr, e) in perm.iter().enumerate() {
+        positions[*e as usize] = ptr as i64;
+    }
+    reverse_perm.iter().map(|e| positions[*e as usize]).collect()
+}
+
+// Samples on a bipartite graph of size (m, n) where `graph` maps dst nodes (0..n) to src nodes (0..m) and
+// `reverse` maps src nodes back to dst nodes. The inputs are dst nodes, even hops expand dst nodes through
+// `graph` and odd hops expand src nodes through `reverse`. Both sides keep their own deduplicated local ids
+// with the inputs first in the dst list. Rows always index the src list and cols the dst list. The edge
+// positions all refer to `graph`, edges drawn from `reverse` are mapped through `reverse_positions` (see
+// `bipartite_reverse_positions`), so a single edge attribute tensor serves every hop. Layer offsets point into
+// the list the hop adds nodes to.
+pub fn neighbor_sampling_bipartite<
+    G: NeighborSource + ?Sized, R: NeighborSource + ?Sized, N: Into<FanoutPolicy> + Copy
+>(
+    rng: &mut impl Rng,
+    graph: &G,
+    reverse: &R,
+    reverse_positions: &[i64],
+    inputs: &[NodeIdx],
+    num_neighbors: &[N],
+    sampler: &impl Sampler,
+) -> (
+    Vec<NodeIdx>,
+    Vec<NodeIdx>,
+    CooGraphBuilder,
+    Vec<LayerOffset>
+) {
+    let mut dst_ids = LocalIds::new(graph.node_count());
+    let mut src_ids = LocalIds::new(reverse.node_count());
+    let mut dst_samples: Vec<NodeIdx> = Vec::new();
+    let mut src_samples: Vec<NodeIdx> = Vec::new();
+
+    let mut layer_offsets: Vec<LayerOffset> = Vec::new();
+    let mut edge_index = CooGraphBuilder::new();
+
+    for (i, v) in inputs.iter().enumerate() {
+        dst_ids.get_or_insert(*v, i as NodeIdx);
+    }
+    dst_samples.extend_from_slice(inputs);
+
+    // Frontiers of both sides, src nodes found in hop i are expanded in hop i + 1
+    let (mut dst_begin, mut src_begin) = (0, 0);
+    for (hop, fanout) in num_neighbors.iter().cloned().enumerate() {
+        let fanout: FanoutPolicy = fanout.into();
+        let mut num_samples = fanout.fanout(0);
+        let mut sampler_state = sampler.init_hop(hop, num_samples);
+
+        let from_dst = hop % 2 == 0;
+        let (frontier, begin, ids, samples) = if from_dst {
+            (dst_samples.as_slice(), dst_begin, &mut src_ids, &mut src_samples)
+        } else {
+            (src_samples.as_slice(), src_begin, &mut dst_ids, &mut dst_samples)
+        };
+        let end = frontier.len();
+        let frontier = frontier[begin..end].to_vec();
+        layer_offsets.push((samples.len() as NodePtr, edge_index.len() as EdgePtr, samples.len() as NodePtr));
+
+        for (offset, w) in frontier.iter().enumerate() {
+            let i = (begin + offset) as i64;
+            let neighbors_range = if from_dst { graph.edge_positions(*w) } else { reverse.edge_positions(*w) };
+            if neighbors_range.is_empty() {
+                continue;
+            }
+
+            let k = fanout.fanout(neighbors_range.len());
+            if k != num_samples {
+                sampler.resize(&mut sampler_state, k);
+                num_samples = k;
+            }
+            if num_samples == 0 {
+                continue;
+            }
+
+            for edge_ptr in sampler.sample(rng, &mut sampler_state, *w, neighbors_range) {
+                let v = if from_dst { graph.neighbor_at(*edge_ptr) } else { reverse.neighbor_at(*edge_ptr) };
+                let (j, is_new) = ids.get_or_insert(v, samples.len() as NodeIdx);
+                if is_new {
+                    samples.push(v);
+                }
+                if from_dst {
+                    edge_index.push_edge(j, i, *edge_ptr as i64);
+                } else {
+                    edge_index.push_edge(i, j, reverse_positions[*edge_ptr]);
+                }
+            }
+        }
+
+        if from_dst {
+            dst_begin = end;
+        } else {
+            src_begin = end;
+        }
+    }
+
+    (
+        dst_samples,
+        src_samples,
+        edge_index,
+        layer_offsets,
+    )
+}
+
+// Checks a per relation, per hop fanout schedule for `neighbor_sampling_heterogenous`: every entry needs to name a
+// known relation and hold exactly `num_hops` fanouts, each either a sample count, 0 to disable the relation at
+// that hop or -1 to take all neighbors. Relations without an entry default to 0 at every hop.
+pub fn validate_hetero_fanouts(
+    edge_types: &[EdgeType],
+    num_neighbors: &HashMap<RelType, Vec<i64>>,
+    num_hops: usize,
+) -> TensorResult<()> {
+    for (rel_type, fanouts) in num_neighbors {
+        let known = edge_types.iter()
+            .any(|(src, rel, dst)| format!("{}__{}__{}", src, rel, dst) == *rel_type);
+        if !known {
+            return Err(TensorConversionError::Unknown(format!("fanout given for unknown relation {}", rel_type)));
+        }
+        if fanouts.len() != num_hops {
+            return Err(TensorConversionError::Unknown(format!(
+                "relation {} has {} fanouts, expected one for each of the {} hops", rel_type, fanouts.len(), num_hops
+            )));
+        }
+        if let Some(k) = fanouts.iter().find(|k| **k < -1) {
+            return Err(TensorConversionError::Unknown(format!("invalid fanout {} for relation {}", k, rel_type)));
+        }
+    }
+    Ok(())
+}
+
+pub fn neighbor_sampling_heterogenous<
+    G: NeighborSource, F: SamplingFilter, N: Into<FanoutPolicy> + Copy
+>(
+    rng: &mut impl Rng,
+    node_types: &[NodeType],
+    edge_types: &[EdgeType],
+    graphs: &HashMap<RelType, G>,
+    inputs: &HashMap<NodeType, &[NodeIdx]>,
+    num_neighbors: &HashMap<RelType, Vec<N>>,
+    num_hops: usize,
+    sampler: &HashMap<RelType, impl Sampler>,
+    filter: &HashMap<RelType, F>,
+    inputs_state: &HashMap<NodeType, &[F::State]>,
+) -> (
+    HashMap<NodeType, Vec<NodeIdx>>,
+    HashMap<RelType, CooGraphBuilder>,
+    HashMap<RelType, Vec<LayerOffset>>,
+) {
+    neighbor_sampling_heterogenous_traced(
+        rng, node_types, edge_types, graphs, inputs, num_neighbors, num_hops, sampler, filter, inputs_state,
+        &mut NoTracer,
+    )
+}
+
+pub fn neighbor_sampling_heterogenous_traced<
+    G: NeighborSource, F: SamplingFilter, N: Into<FanoutPolicy> + Copy, T: SamplingTracer
+>(
+    rng: &mut impl Rng,
+    node_types: &[NodeType],
+    edge_types: &[EdgeType],
+    graphs: &HashMap<RelType, G>,
+    inputs: &HashMap<NodeType, &[NodeIdx]>,
+    num_neighbors: &HashMap<RelType, Vec<N>>,
+    num_hops: usize,
+    sampler: &HashMap<RelType, impl Sampler>,
+    filter: &HashMap<RelType, F>,
+    inputs_state: &HashMap<NodeType, &[F::State]>,
+    tracer: &mut T,
+) -> (
+    HashMap<NodeType, Vec<NodeIdx>>,
+    HashMap<RelType, CooGraphBuilder>,
+    HashMap<RelType, Vec<LayerOffset>>,
+) {
+    // TODO: When same nodes occur in the same layer, we should only keep one (therefore reducing workload at nn side)
+    // See: https://i.imgur.com/mFurLy7.png
+
+    // Create a mapping to convert single string relations to edge type triplets:
+    let mut to_edge_types = HashMap::<RelType, EdgeType>::new();
+    for e @ (src_node_type, rel_type, dst_node_type) in edge_types {
+        to_edge_types.insert(format!("{}__{}__{}", src_node_type, rel_type, dst_node_type), e.clone());
+    }
+
+    // Initialize some data structures for the sampling process
+    let mut samples: HashMap<NodeType, Vec<NodeIdx>> = HashMap::new();
+    let mut states: HashMap<NodeType, Vec<F::State>> = HashMap::new();
+
+    for node_type in node_types {
+        let samples = samples
+            .entry(node_type.clone())
+            .or_insert_with(Vec::new);
+        if let Some(inputs) = inputs.get(node_type) {
+            samples.extend_from_slice(inputs);
+        }
+
+        let states = states
+            .entry(node_type.clone())
+            .or_insert_with(Vec::new);
+        if let Some(inputs_state) = inputs_state.get(node_type) {
+            states.extend_from_slice(inputs_state);
+        }
+    }
+
+    let mut layer_offsets: HashMap<RelType, Vec<LayerOffset>> = graphs.keys()
+        .map(|rel_type| (rel_type.clone(), Vec::new()))
+        .collect();
+    let mut edge_index: HashMap<RelType, CooGraphBuilder> = graphs.keys()
+        .map(|rel_type| (rel_type.clone(), CooGraphBuilder::new()))
+        .collect();
+
+    // Maintains begin/end indices for each node type
+    let mut slices: HashMap<NodeType, (usize, usize)> = samples.iter()
+        .map(|(node_type, samples)| (node_type.clone(), (0, samples.len())))
+        .collect();
+
+    let track_coverage = T::ENABLED && tracer.coverage_enabled();
+    for ell in 0..num_hops {
+        // Apply sampling for each relation type
+        for (rel_type, num_samples) in num_neighbors {
+            let (src_node_type, _, dst_node_type) = &to_edge_types[rel_type];
+
+            // Relations without a fanout for this hop are disabled, they only get a (empty) layer offset
+            let fanout: FanoutPolicy = match num_samples.get(ell) {
+                Some(n) => (*n).into(),
+                None => FanoutPolicy::Fixed(0),
+            };
+            if fanout == FanoutPolicy::Fixed(0) {
+                let src_len = samples[src_node_type].len();
+                let dst_len = samples[dst_node_type].len();
+                let edge_index = &edge_index[rel_type];
+                layer_offsets.get_mut(rel_type).unwrap()
+                    .push((src_len as NodePtr, edge_index.len() as EdgePtr, dst_len as NodePtr));
+                continue;
+            }
+
+            let hop_start = if T::ENABLED { Some(Instant::now()) } else { None };
+            let (mut num_empty, mut num_truncated) = (0, 0);
+            let mut coverage = Coverage::default();
+            let filter = &filter[rel_type];
+            let sampler = &sampler[rel_type];
+
+            // Initialize the states
+            let mut num_samples = fanout.fanout(0);
+            let mut sampler_state = sampler.init_hop(ell, num_samples);
+
+            // Select data
             let dst_samples = &samples[dst_node_type];
             let dst_states = &states[dst_node_type];
             // This should be safe so long we don't keep references to the samples' value (should copy them immediately)
@@ -311,6 +1809,7 @@ pub fn neighbor_sampling_heterogenous<
             let edge_index = edge_index.get_mut(rel_type).unwrap();
 
             // Add layer offset
+            let (src_start, edges_start) = (src_samples.len(), edge_index.len());
             layer_offsets.get_mut(rel_type).unwrap()
                 .push((src_samples.len() as NodePtr, edge_index.len() as EdgePtr, dst_samples.len() as NodePtr));
 
@@ -319,40 +1818,353 @@ pub fn neighbor_sampling_heterogenous<
                 let w = dst_samples[i];
                 let w_state = dst_states[i];
 
-                let neighbors_range = graph.neighbors_range(w);
-                if neighbors_range.is_empty() {
-                    continue;
-                }
+                let neighbors_range = graph.edge_positions(w);
+                if neighbors_range.is_empty() {
+                    if T::ENABLED { num_empty += 1; }
+                    if track_coverage { coverage.push(i, ell, 0, 0); }
+                    continue;
+                }
+
+                let k = fanout.fanout(neighbors_range.len());
+                if k != num_samples {
+                    sampler.resize(&mut sampler_state, k);
+                    num_samples = k;
+                }
+                if num_samples == 0 {
+                    if T::ENABLED { num_truncated += 1; }
+                    if track_coverage { coverage.push(i, ell, 0, neighbors_range.len()); }
+                    continue;
+                }
+
+                let mut num_eligible = 0;
+                let samples_filtered = neighbors_range.clone()
+                    .filter(|edge_ptr| filter.filter(&w_state, w, *edge_ptr))
+                    .inspect(|_| if T::ENABLED { num_eligible += 1; });
+                let samples_iter = sampler.sample(
+                    rng, &mut sampler_state, w, samples_filtered,
+                );
+                let num_sampled_start = src_samples.len();
+
+                for edge_ptr in samples_iter {
+                    let v = graph.neighbor_at(*edge_ptr);
+                    let j = src_samples.len();
+                    let state = filter.mutate(&w_state, w, *edge_ptr);
+
+                    src_samples.push(v);
+                    src_states.push(state);
+                    edge_index.push_edge(j as i64, i as i64, *edge_ptr as i64);
+                }
+
+                if track_coverage {
+                    coverage.push(i, ell, src_samples.len() - num_sampled_start, neighbors_range.len());
+                }
+                if T::ENABLED {
+                    if num_eligible == 0 {
+                        num_empty += 1;
+                    } else if num_eligible > num_samples {
+                        num_truncated += 1;
+                    }
+                }
+            }
+
+            if track_coverage {
+                tracer.record_coverage(Some(rel_type), coverage);
+            }
+            if let Some(hop_start) = hop_start {
+                tracer.record(HopStats {
+                    hop: ell,
+                    rel_type: Some(rel_type.clone()),
+                    num_frontier: end - begin,
+                    num_nodes: src_samples.len() - src_start,
+                    num_edges: edge_index.len() - edges_start,
+                    num_empty,
+                    num_truncated,
+                    elapsed: hop_start.elapsed(),
+                });
+            }
+        }
+
+        for (node_type, samples) in samples.iter() {
+            let (_, end) = slices[node_type];
+            *slices.get_mut(node_type).unwrap() = (end, samples.len());
+        }
+    }
+
+    (
+        samples,
+        edge_index,
+        layer_offsets,
+    )
+}
+
+pub fn gather_features(
+    features: &Tensor,
+    samples: &[NodeIdx],
+    node_count: usize,
+) -> TensorResult<Tensor> {
+    let size = features.size();
+    if size.is_empty() || size[0] != node_count as i64 {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "features must have {} rows, got shape {:?}", node_count, size
+        ))));
+    }
+
+    if let Some(v) = samples.iter().find(|&&v| v < 0 || v >= node_count as NodeIdx) {
+        return Err(TensorConversionError::Unknown(format!("sampled node {} is out of range", v)));
+    }
+
+    let index = Tensor::of_slice(samples).to_device(features.device());
+    Ok(features.index_select(0, &index))
+}
+
+pub fn gather_features_heterogenous(
+    features: &HashMap<NodeType, Tensor>,
+    samples: &HashMap<NodeType, Vec<NodeIdx>>,
+    node_counts: &HashMap<NodeType, usize>,
+) -> TensorResult<HashMap<NodeType, Tensor>> {
+    let mut result = HashMap::new();
+    for (node_type, x) in features.iter() {
+        // Node types that never appear as a destination have no known count, only the ids are checked
+        let node_count = node_counts.get(node_type).cloned().unwrap_or_else(|| x.size().first().cloned().unwrap_or(0) as usize);
+        let node_samples = samples.get(node_type).map(Vec::as_slice).unwrap_or(&[]);
+        result.insert(node_type.clone(), gather_features(x, node_samples, node_count)?);
+    }
+    Ok(result)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleFormat {
+    Coo,
+    // Additionally emits the sampled edges as a csc graph over all sampled nodes, without needing a sort. The
+    // block of a layer is its destination range, see `CscGraphStorage::col_slice`.
+    Csc,
+}
+
+pub struct SampleOutput {
+    // Global node ids of the sampled nodes
+    pub n_id: Tensor,
+    // Local (src, dst) indices into n_id
+    pub row_col: Tensor,
+    // Global ids of the sampled edges
+    pub e_id: Tensor,
+    pub layer_offsets: Vec<LayerOffset>,
+    // With `SampleFormat::Csc`, the indices are local src ids and the perm holds the global edge ids
+    pub csc: Option<CscGraphStorage>,
+}
+
+impl SampleOutput {
+    // Sampled edge positions index the csc graph, `perm` maps them back to the coo edges
+    pub fn new(
+        samples: &[NodeIdx],
+        edge_index: &CooGraphBuilder,
+        layer_offsets: Vec<LayerOffset>,
+        perm: Option<&Tensor>,
+    ) -> Self {
+        Self::with_format(samples, edge_index, layer_offsets, perm, SampleFormat::Coo)
+    }
+
+    pub fn with_format(
+        samples: &[NodeIdx],
+        edge_index: &CooGraphBuilder,
+        layer_offsets: Vec<LayerOffset>,
+        perm: Option<&Tensor>,
+        format: SampleFormat,
+    ) -> Self {
+        let (rows, cols, edge_ptrs) = edge_index.to_tensor();
+        let e_id = match perm {
+            Some(perm) => perm.index_select(0, &edge_ptrs.to_device(perm.device())),
+            None => edge_ptrs,
+        };
+
+        let csc = match format {
+            SampleFormat::Coo => None,
+            SampleFormat::Csc => {
+                let (ptrs, order) = edge_index.csc_order(samples.len());
+                let (indices, csc_perm) = if order.iter().enumerate().all(|(i, e)| i == *e) {
+                    (rows.shallow_clone(), e_id.shallow_clone())
+                } else {
+                    let order = Tensor::of_slice(&order.iter().map(|e| *e as i64).collect::<Vec<_>>());
+                    (rows.index_select(0, &order), e_id.index_select(0, &order.to_device(e_id.device())))
+                };
+                Some(CscGraphStorage::new(Tensor::of_slice(&ptrs), indices, Some(csc_perm)))
+            }
+        };
+
+        SampleOutput {
+            n_id: Tensor::of_slice(samples),
+            row_col: Tensor::stack(&[rows, cols], 0),
+            e_id,
+            layer_offsets,
+            csc,
+        }
+    }
+
+    pub fn gather_node_features(&self, x: &Tensor) -> Tensor {
+        x.index_select(0, &self.n_id.to_device(x.device()))
+    }
+
+    pub fn gather_edge_features(&self, edge_attr: &Tensor) -> Tensor {
+        edge_attr.index_select(0, &self.e_id.to_device(edge_attr.device()))
+    }
+
+    pub fn to_dgl_blocks(&self) -> Vec<DglBlock> {
+        dgl_blocks(&self.n_id, &self.row_col.select(0, 0), &self.row_col.select(0, 1), &self.e_id, &self.layer_offsets)
+    }
+
+    // The hops as bipartite message passing layers in flow order, from the outermost hop to the one into the seeds.
+    // Same split as `to_dgl_blocks`, so the local ids of both sides stay the sample ids and all tensors are views.
+    pub fn as_bipartite_blocks(&self) -> Vec<BipartiteBlock> {
+        let (num_nodes, num_edges) = (self.n_id.size()[0], self.e_id.size()[0]);
+        (0..self.layer_offsets.len()).rev()
+            .map(|hop| {
+                let num_dst = self.layer_offsets[hop].0;
+                let (num_src, hop_edges) = self.layer_offsets.get(hop + 1)
+                    .map_or((num_nodes, num_edges), |o| (o.0, o.1));
+                BipartiteBlock {
+                    src_nodes: self.n_id.narrow(0, 0, num_src),
+                    dst_nodes: self.n_id.narrow(0, 0, num_dst),
+                    edge_index: self.row_col.narrow(1, 0, hop_edges),
+                    e_id: self.e_id.narrow(0, 0, hop_edges),
+                    size: (num_src, num_dst),
+                }
+            })
+            .collect()
+    }
+}
+
+// A single hop as a bipartite graph: edge_index holds local (src, dst) indices into src_nodes and dst_nodes, which
+// are global ids, and size is (num_src, num_dst) like the `size` of a PyG message passing layer
+pub struct BipartiteBlock {
+    pub src_nodes: Tensor,
+    pub dst_nodes: Tensor,
+    pub edge_index: Tensor,
+    pub e_id: Tensor,
+    pub size: (i64, i64),
+}
+
+// Message passing block of a single hop in the layout of dgl.create_block: the src nodes are another prefix of the
+// sampled nodes that starts with the dst nodes, and NID / EID hold the global node and edge ids
+pub struct DglBlock {
+    pub src: Tensor,
+    pub dst: Tensor,
+    pub num_src_nodes: i64,
+    pub num_dst_nodes: i64,
+    pub src_nid: Tensor,
+    pub dst_nid: Tensor,
+    pub eid: Tensor,
+}
+
+// Splits a homogenous (also dedup) sample into one block per hop. Local ids grow with the hops, so hop h updates the
+// nodes in front of the layer offset of h from all nodes sampled up to and including h, through the edges of hops
+// 0..=h. Every block therefore is a prefix of the sample and all tensors are narrowed views. Blocks are in hop
+// order, the reverse of the input to output order dgl expects.
+pub fn dgl_blocks(
+    n_id: &Tensor,
+    rows: &Tensor,
+    cols: &Tensor,
+    e_id: &Tensor,
+    layer_offsets: &[LayerOffset],
+) -> Vec<DglBlock> {
+    let (num_nodes, num_edges) = (n_id.size()[0], rows.size()[0]);
+    (0..layer_offsets.len())
+        .map(|hop| {
+            let num_dst_nodes = layer_offsets[hop].0;
+            let (num_src_nodes, hop_edges) = layer_offsets.get(hop + 1)
+                .map_or((num_nodes, num_edges), |o| (o.0, o.1));
+            DglBlock {
+                src: rows.narrow(0, 0, hop_edges),
+                dst: cols.narrow(0, 0, hop_edges),
+                num_src_nodes,
+                num_dst_nodes,
+                src_nid: n_id.narrow(0, 0, num_src_nodes),
+                dst_nid: n_id.narrow(0, 0, num_dst_nodes),
+                eid: e_id.narrow(0, 0, hop_edges),
+            }
+        })
+        .collect()
+}
+
+// Output of `neighbor_sampling_heterogenous` keyed by node type and edge type triplet, the same layout as
+// torch_geometric.loader.utils.filter_hetero_data consumes
+pub struct HeteroSamplerOutput {
+    // Global node ids of the sampled nodes, the seeds come first
+    pub node: HashMap<NodeType, Tensor>,
+    // Local src / dst indices into `node` and the sampled edge positions
+    pub row: HashMap<EdgeType, Tensor>,
+    pub col: HashMap<EdgeType, Tensor>,
+    pub edge: HashMap<EdgeType, Tensor>,
+    // Index of the seed (within its node type) every node was sampled from and the hop it was sampled in
+    pub batch: HashMap<NodeType, Tensor>,
+    pub hop: HashMap<NodeType, Tensor>,
+    pub num_seeds: HashMap<NodeType, usize>,
+}
 
-                let samples_filtered = neighbors_range.clone()
-                    .filter(|edge_ptr| filter.filter(&w_state, w, *edge_ptr));
-                let samples_iter = sampler.sample(
-                    rng, &mut sampler_state, samples_filtered,
-                );
+impl HeteroSamplerOutput {
+    pub fn new(
+        edge_types: &[EdgeType],
+        inputs: &HashMap<NodeType, &[NodeIdx]>,
+        samples: &HashMap<NodeType, Vec<NodeIdx>>,
+        edge_index: &HashMap<RelType, CooGraphBuilder>,
+        layer_offsets: &HashMap<RelType, Vec<LayerOffset>>,
+    ) -> Self {
+        let mut to_edge_types = HashMap::<RelType, EdgeType>::new();
+        for e @ (src_node_type, rel_type, dst_node_type) in edge_types {
+            to_edge_types.insert(format!("{}__{}__{}", src_node_type, rel_type, dst_node_type), e.clone());
+        }
 
-                for edge_ptr in samples_iter {
-                    let v = graph.get_by_ptr(*edge_ptr);
-                    let j = src_samples.len();
-                    let state = filter.mutate(&w_state, w, *edge_ptr);
+        let num_seeds: HashMap<NodeType, usize> = samples.keys()
+            .map(|node_type| (node_type.clone(), inputs.get(node_type).map_or(0, |inputs| inputs.len())))
+            .collect();
+        let mut batch: HashMap<NodeType, Vec<i64>> = HashMap::new();
+        let mut hop: HashMap<NodeType, Vec<i64>> = HashMap::new();
+        for (node_type, samples) in samples.iter() {
+            let n = num_seeds[node_type];
+            batch.insert(node_type.clone(), (0..samples.len() as i64).map(|i| if i < n as i64 { i } else { -1 }).collect());
+            hop.insert(node_type.clone(), vec![0; samples.len()]);
+        }
 
-                    src_samples.push(v);
-                    src_states.push(state);
-                    edge_index.push_edge(j as i64, i as i64, *edge_ptr as i64);
+        // Every sampled node is added by exactly one edge whose dst was sampled in an earlier hop, so walking the
+        // edges hop by hop propagates the seed index from the dst to the src. Self loops of lonely seeds (edge
+        // index -1) add no node and are skipped, the seeds stay at hop 0.
+        let num_hops = layer_offsets.values().map(|offsets| offsets.len()).max().unwrap_or(0);
+        for ell in 0..num_hops {
+            for (rel_type, offsets) in layer_offsets.iter() {
+                if ell >= offsets.len() {
+                    continue;
+                }
+                let (src_node_type, _, dst_node_type) = &to_edge_types[rel_type];
+                let builder = &edge_index[rel_type];
+                let end = offsets.get(ell + 1).map_or(builder.len(), |o| o.1 as usize);
+                for e in (offsets[ell].1 as usize..end).filter(|e| builder.edge_index[*e] >= 0) {
+                    let (j, i) = (builder.rows[e] as usize, builder.cols[e] as usize);
+                    let b = batch[dst_node_type][i];
+                    batch.get_mut(src_node_type).unwrap()[j] = b;
+                    hop.get_mut(src_node_type).unwrap()[j] = ell as i64 + 1;
                 }
             }
         }
 
-        for (node_type, samples) in samples.iter() {
-            let (_, end) = slices[node_type];
-            *slices.get_mut(node_type).unwrap() = (end, samples.len());
+        let mut row = HashMap::new();
+        let mut col = HashMap::new();
+        let mut edge = HashMap::new();
+        for (rel_type, builder) in edge_index.iter() {
+            let edge_type = &to_edge_types[rel_type];
+            let (rows, cols, edge_ptrs) = builder.to_tensor();
+            row.insert(edge_type.clone(), rows);
+            col.insert(edge_type.clone(), cols);
+            edge.insert(edge_type.clone(), edge_ptrs);
         }
-    }
 
-    (
-        samples,
-        edge_index,
-        layer_offsets,
-    )
+        HeteroSamplerOutput {
+            node: samples.iter().map(|(node_type, samples)| (node_type.clone(), Tensor::of_slice(samples))).collect(),
+            row,
+            col,
+            edge,
+            batch: batch.into_iter().map(|(node_type, batch)| (node_type, Tensor::of_slice(&batch))).collect(),
+            hop: hop.into_iter().map(|(node_type, hop)| (node_type, Tensor::of_slice(&hop))).collect(),
+            num_seeds,
+        }
+    }
 }
 
 
@@ -361,8 +2173,9 @@ mod tests {
     use std::collections::{HashMap, VecDeque};
     use std::convert::TryFrom;
     use rand::{Rng, SeedableRng};
-    use crate::algo::neighbor_sampling::{IdentityFilter, LayerOffset, SamplingFilter, TemporalFilter, UnweightedSampler, WeightedSampler};
-    use crate::data::{CscGraph, CscGraphStorage, EdgeAttr, CooGraphBuilder};
+    use tch::{Device, Kind, Tensor};
+    use crate::algo::neighbor_sampling::{BipartiteBlock, Coverage, DglBlock, FanoutPolicy, HeteroSamplerOutput, HopStats, IdentityFilter, LayerOffset, LonelySeedPolicy, PerHopSampler, ReplacePolicy, ReplacementSampler, SampleFormat, SampleOutput, Sampler, SamplerStats, SamplingFilter, TemporalFilter, UnweightedSampler, WeightedSampler};
+    use crate::data::{CooGraphStorage, CscGraph, CscGraphStorage, EdgeAttr, EdgeAttrSource, CooGraphBuilder, MmapCscGraphStorage, NeighborSource};
     use crate::data::{load_fake_hetero_graph, load_karate_graph};
     use crate::utils::{EdgeType, NodeIdx, NodeType, RelType};
     use super::{TEMPORAL_SAMPLE_STATIC, TEMPORAL_SAMPLE_RELATIVE};
@@ -424,18 +2237,558 @@ mod tests {
                 }
             }
 
-            let mut path = head.clone();
-            path.push(v);
-            let mut path_edges = head_edges.clone();
-            path_edges.push(edge_idx);
+            let mut path = head.clone();
+            path.push(v);
+            let mut path_edges = head_edges.clone();
+            path_edges.push(edge_idx);
+
+            paths.push_back((path, path_edges));
+        }
+        paths.into()
+    }
+
+    #[test]
+    pub fn test_neighbor_sampling_homogenous() {
+        let (_x, _, coo_graph) = load_karate_graph();
+
+        let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+
+        let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let inputs = vec![0_i64, 1, 4, 5];
+        let inputs_state = vec![(); inputs.len()];
+
+        let num_neighbors = vec![4, 3];
+
+        let (samples, coo_builder, layer_offsets) = super::neighbor_sampling_homogenous(
+            &mut rng,
+            &graph,
+            &inputs,
+            &num_neighbors,
+            &UnweightedSampler::<true>,
+            &IdentityFilter,
+            &inputs_state,
+        );
+
+        validate_neighbor_samples(
+            &graph, &coo_builder, &samples, &samples, &layer_offsets, &num_neighbors
+        );
+    }
+
+    #[test]
+    pub fn test_neighbor_sampling_homogenous_weighted() {
+        let (_x, _, coo_graph) = load_karate_graph();
+
+        let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+
+        let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let weights_data = (0..graph.edge_count()).map(|_| rng.gen_range(0.2..5.0)).collect::<Vec<f64>>();
+        let source = EdgeAttrSource::new(&graph).with_weights(&weights_data);
+
+        let inputs = vec![0_i64, 1, 4, 5];
+        let inputs_state = vec![(); inputs.len()];
+        let num_neighbors = vec![4, 3];
+
+        let (samples, coo_builder, layer_offsets) = super::neighbor_sampling_homogenous(
+            &mut rng,
+            &graph,
+            &inputs,
+            &num_neighbors,
+            &WeightedSampler::new(&source),
+            &IdentityFilter,
+            &inputs_state,
+        );
+
+        validate_neighbor_samples(
+            &graph, &coo_builder, &samples, &samples, &layer_offsets, &num_neighbors
+        );
+    }
+
+    #[test]
+    pub fn test_weighted_neighbor_sampler() {
+        use crate::algo::neighbor_sampling::{Sampler, WeightedReplacementSampler};
+
+        let (_x, _, coo_graph) = load_karate_graph();
+        let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+        let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let weights_data = (0..graph.edge_count()).map(|_| rng.gen_range(0.2..5.0)).collect::<Vec<f64>>();
+        let source = EdgeAttrSource::new(&graph).with_weights(&weights_data);
+        let sampler = WeightedReplacementSampler::new(&source).unwrap();
+        assert_eq!(sampler.cdf.len(), graph.edge_count());
+
+        let inputs = vec![0_i64, 1, 4, 5];
+        let inputs_state = vec![(); inputs.len()];
+        let num_neighbors = vec![4, 3];
+        let (samples, coo_builder, layer_offsets) = super::neighbor_sampling_homogenous(
+            &mut rng, &graph, &inputs, &num_neighbors, &sampler, &IdentityFilter, &inputs_state,
+        );
+        validate_neighbor_samples(
+            &graph, &coo_builder, &samples, &samples, &layer_offsets, &num_neighbors
+        );
+
+        // Node 1 has the edges 0..3 with weights 1, 0 and 3, node 0 has no edges
+        let ptrs = [0_i64, 0, 3];
+        let indices = [0_i64, 1, 0];
+        let graph = CscGraph::<i64, i64>::new(&ptrs, &indices);
+        let weights_data = [1.0, 0.0, 3.0];
+        let source = EdgeAttrSource::new(&graph).with_weights(&weights_data);
+        let sampler = WeightedReplacementSampler::new(&source).unwrap();
+        let mut state = sampler.init(4000);
+        let mut counts = [0_usize; 3];
+        sampler.sample(&mut rng, &mut state, 1, graph.neighbors_range(1)).for_each(|e| counts[*e] += 1);
+        assert_eq!(counts[1], 0);
+        assert!((counts[2] as f64 / 4000.0 - 0.75).abs() < 0.05);
+        assert!(sampler.sample(&mut rng, &mut state, 0, graph.neighbors_range(0)).next().is_none());
+
+        // Filtered candidates and ranges cut off at the front only draw from the given edges
+        let sampled: Vec<usize> = sampler.sample(&mut rng, &mut state, 1, vec![0_usize, 2].into_iter()).cloned().collect();
+        assert!(sampled.iter().all(|e| *e == 0 || *e == 2));
+        let sampled: Vec<usize> = sampler.sample(&mut rng, &mut state, 1, 1..3).cloned().collect();
+        assert!(sampled.iter().all(|e| *e == 2));
+
+        // The cdf of a node starts over at its first edge
+        let ptrs = [0_i64, 2, 4];
+        let indices = [0_i64, 1, 0, 1];
+        let graph = CscGraph::<i64, i64>::new(&ptrs, &indices);
+        let weights_data = [1.0, 1.0, 1.0, 2.0];
+        let source = EdgeAttrSource::new(&graph).with_weights(&weights_data);
+        let sampler = WeightedReplacementSampler::new(&source).unwrap();
+        let mut state = sampler.init(4000);
+        let mut counts = [0_usize; 4];
+        sampler.sample(&mut rng, &mut state, 1, graph.neighbors_range(1)).for_each(|e| counts[*e] += 1);
+        assert_eq!(counts[0] + counts[1], 0);
+        assert!((counts[3] as f64 / 4000.0 - 2.0 / 3.0).abs() < 0.05);
+
+        assert!(WeightedReplacementSampler::new(&EdgeAttrSource::new(&graph).with_weights(&[1.0, -1.0, 1.0, 1.0])).is_err());
+        assert!(WeightedReplacementSampler::new(&EdgeAttrSource::new(&graph).with_weights(&[1.0])).is_err());
+        assert!(WeightedReplacementSampler::new(&graph).is_err());
+    }
+
+    #[test]
+    pub fn test_neighbor_sampling_homogenous_temporal() {
+        let (_x, _, coo_graph) = load_karate_graph();
+
+        let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+
+        let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let timestamps_data = (0..graph.edge_count()).map(|_| rng.gen_range(0..4)).collect::<Vec<i64>>();
+        let timestamps = EdgeAttr::new(&timestamps_data);
+        let source = EdgeAttrSource::new(&graph).with_times(&timestamps_data);
+
+        let inputs = vec![0_i64, 1, 4, 5];
+        let input_timestamps = vec![0_i64, 1, 2, 3];
+        let num_neighbors = vec![4, 3];
+
+        // Tests static window sampling
+        let filter = TemporalFilter::<_, false, TEMPORAL_SAMPLE_STATIC>::new(
+            0..=2, &source,
+        );
+        let (samples, coo_builder, layer_offsets) = super::neighbor_sampling_homogenous(
+            &mut rng,
+            &graph,
+            &inputs,
+            &num_neighbors,
+            &UnweightedSampler::<false>,
+            &filter,
+            &input_timestamps,
+        );
+
+        validate_neighbor_samples(
+            &graph, &coo_builder, &samples, &samples, &layer_offsets, &num_neighbors
+        );
+        let paths = samples_to_paths(&coo_builder, &samples, &inputs);
+        for (_path, edges) in paths {
+            for edge_idx in edges {
+                let edge_ptr = coo_builder.edge_index[edge_idx];
+                let t = timestamps.get(edge_ptr as usize);
+                assert!((0..=2).contains(t));
+            }
+        }
+
+        // Tests relative window sampling backward in time
+        let filter = TemporalFilter::<_, false, TEMPORAL_SAMPLE_RELATIVE>::new(
+            0..=2, &source,
+        );
+        let (samples, coo_builder, layer_offsets) = super::neighbor_sampling_homogenous(
+            &mut rng,
+            &graph,
+            &inputs,
+            &num_neighbors,
+            &UnweightedSampler::<false>,
+            &filter,
+            &input_timestamps,
+        );
+
+        validate_neighbor_samples(
+            &graph, &coo_builder, &samples, &samples, &layer_offsets, &num_neighbors
+        );
+        let paths = samples_to_paths(&coo_builder, &samples, &inputs);
+        for (_path, edges) in paths {
+            if let Some(edge_idx) = edges.first().cloned() {
+                let to_idx = coo_builder.cols[edge_idx];
+                let start_t = input_timestamps[to_idx as usize];
+
+                for edge_idx in edges {
+                    let edge_ptr = coo_builder.edge_index[edge_idx];
+                    let t = *timestamps.get(edge_ptr as usize);
+
+                    assert!(((start_t - 2)..=start_t).contains(&t));
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_neighbor_sampling_multigraph() {
+        use super::ParallelEdgeFilter;
+
+        // Node 0 has three parallel edges from node 1 and one from node 2
+        let rows = Tensor::of_slice(&[1_i64, 2, 1, 1, 0]);
+        let cols = Tensor::of_slice(&[0_i64, 0, 0, 0, 1]);
+        let coo = CooGraphStorage::new(Tensor::stack(&[rows, cols], 0), (3, 3));
+        let graph_data = CscGraphStorage::try_from(&coo).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let sample = |filter: &ParallelEdgeFilter<_, IdentityFilter>| super::neighbor_sampling_homogenous(
+            &mut rand::rngs::SmallRng::from_seed([0; 32]), &graph, &[0], &[10],
+            &UnweightedSampler::<false>, filter, &[()],
+        );
+        let (samples, edges, _) = sample(&ParallelEdgeFilter::new(&graph, IdentityFilter, true));
+        let mut edge_index = edges.edge_index.clone();
+        edge_index.sort_unstable();
+        assert_eq!(edge_index, vec![0, 1, 2, 3]);
+        assert_eq!(samples.iter().filter(|v| **v == 1).count(), 3);
+
+        let (samples, edges, _) = sample(&ParallelEdgeFilter::new(&graph, IdentityFilter, false));
+        let mut edge_index = edges.edge_index.clone();
+        edge_index.sort_unstable();
+        assert_eq!(edge_index, vec![0, 3]);
+        assert_eq!(samples.len(), 3);
+
+        // A collapsed run is represented by its first edge that passes the inner filter
+        let times = vec![5_i64, 1, 2, 1];
+        let source = EdgeAttrSource::new(&graph).with_times(&times);
+        let temporal = TemporalFilter::<_, false, TEMPORAL_SAMPLE_STATIC>::new(0..=2, &source);
+        let filter = ParallelEdgeFilter::new(&graph, temporal, false);
+        let (_, edges, _) = super::neighbor_sampling_homogenous(
+            &mut rand::rngs::SmallRng::from_seed([0; 32]), &graph, &[0], &[10],
+            &UnweightedSampler::<false>, &filter, &[0],
+        );
+        let mut edge_index = edges.edge_index.clone();
+        edge_index.sort_unstable();
+        assert_eq!(edge_index, vec![1, 3]);
+    }
+
+    #[test]
+    pub fn test_neighbor_sampling_node_mask() {
+        use super::NodeMaskFilter;
+
+        let (_x, _, coo_graph) = load_karate_graph();
+        let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+        let node_mask: Vec<bool> = (0..34).map(|_| rng.gen_bool(0.3)).collect();
+        let inputs: Vec<i64> = (0..34).filter(|v| !node_mask[*v as usize]).collect();
+        let sample = |filter: &NodeMaskFilter<_, IdentityFilter>, inputs: &[i64]| super::neighbor_sampling_homogenous(
+            &mut rand::rngs::SmallRng::from_seed([0; 32]), &graph, inputs, &[5, 5],
+            &UnweightedSampler::<false>, filter, &vec![(); inputs.len()],
+        );
+
+        // No masked node is ever sampled, while the unmasked neighbors still are
+        let (samples, edges, layer_offsets) = sample(&NodeMaskFilter::new(&graph, IdentityFilter, Some(&node_mask)), &inputs);
+        validate_neighbor_samples(&graph, &edges, &samples, &samples, &layer_offsets, &[5, 5]);
+        assert!(samples.len() > inputs.len());
+        assert!(samples.iter().all(|v| !node_mask[*v as usize]));
+
+        // A node with all of its neighbors masked has no samples
+        let mut all_masked = vec![false; 34];
+        for v in graph.neighbors_slice(0) {
+            all_masked[*v as usize] = true;
+        }
+        let (samples, edges, _) = sample(&NodeMaskFilter::new(&graph, IdentityFilter, Some(&all_masked)), &[0]);
+        assert_eq!(samples, vec![0]);
+        assert_eq!(edges.len(), 0);
+
+        // Nodes past the end of a short mask count as masked
+        let short_mask = &node_mask[..20];
+        let (samples, _, _) = sample(&NodeMaskFilter::new(&graph, IdentityFilter, Some(short_mask)), &inputs);
+        assert!(samples[inputs.len()..].iter().all(|v| *v < 20 && !node_mask[*v as usize]));
+
+        // Without a mask the filter changes nothing
+        let (samples, edges, _) = sample(&NodeMaskFilter::new(&graph, IdentityFilter, None), &inputs);
+        let (expected_samples, expected_edges, _) = super::neighbor_sampling_homogenous(
+            &mut rand::rngs::SmallRng::from_seed([0; 32]), &graph, &inputs, &[5, 5],
+            &UnweightedSampler::<false>, &IdentityFilter, &vec![(); inputs.len()],
+        );
+        assert_eq!(samples, expected_samples);
+        assert_eq!(edges.edge_index, expected_edges.edge_index);
+    }
+
+    #[test]
+    pub fn test_neighbor_sampling_typed() {
+        use crate::data::TypedCscGraphStorage;
+
+        let (_x, _, coo_graph) = load_karate_graph();
+        let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
+        let num_edges = graph_data.indices.size()[0];
+        let edge_types = Tensor::of_slice(&(0..num_edges).map(|i| (i * 7) % 3).collect::<Vec<i64>>());
+        let typed = TypedCscGraphStorage::new(&graph_data, &edge_types, 3).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&typed.graph).unwrap();
+        let type_ptrs = Vec::<i64>::from(&typed.type_ptrs);
+        let types_data = Vec::<i64>::from(&typed.edge_types);
+
+        // The sorted graph keeps the (edge, type) pairs of the original one
+        let perm = Vec::<i64>::from(typed.graph.perm().unwrap());
+        let base_perm = Vec::<i64>::from(graph_data.perm().unwrap());
+        let original = Vec::<i64>::from(&edge_types);
+        for (p, t) in perm.iter().zip(types_data.iter()) {
+            let position = base_perm.iter().position(|q| q == p).unwrap();
+            assert_eq!(original[position], *t);
+        }
+
+        let inputs = vec![0_i64, 1, 4, 5];
+        let num_neighbors = vec![vec![2, 0, 1], vec![1, 1, 1]];
+        let (samples, edges, layer_offsets, sample_types) = super::neighbor_sampling_typed(
+            &mut rand::rngs::SmallRng::from_seed([0; 32]), &graph, &type_ptrs, &inputs, &num_neighbors,
+            &UnweightedSampler::<false>, &IdentityFilter, &vec![(); inputs.len()],
+        ).unwrap();
+        assert_eq!(layer_offsets.len(), 2);
+        assert_eq!(sample_types.len(), edges.len());
+
+        let mut counts = HashMap::new();
+        for (k, (&i, &ptr)) in edges.cols.iter().zip(edges.edge_index.iter()).enumerate() {
+            let (dst, src) = (samples[i as usize], samples[edges.rows[k] as usize]);
+            assert_eq!(graph.neighbor_at(ptr as usize), src);
+            assert!(graph.neighbors_range(dst).contains(&(ptr as usize)));
+            assert_eq!(types_data[ptr as usize], sample_types[k]);
+            *counts.entry((i, sample_types[k])).or_insert(0) += 1;
+        }
+        for ((i, t), c) in counts {
+            let hop = if (i as usize) < inputs.len() { 0 } else { 1 };
+            assert!(c <= num_neighbors[hop][t as usize]);
+        }
+        // Node 0 has enough edges of type 0 to fill its fanout
+        assert_eq!(sample_types.iter().zip(edges.cols.iter()).filter(|(t, i)| **t == 0 && **i == 0).count(), 2);
+
+        assert!(super::neighbor_sampling_typed(
+            &mut rand::rngs::SmallRng::from_seed([0; 32]), &graph, &type_ptrs, &inputs, &[vec![1, 1]],
+            &UnweightedSampler::<false>, &IdentityFilter, &vec![(); inputs.len()],
+        ).is_err());
+        assert!(TypedCscGraphStorage::new(&graph_data, &edge_types, 2).is_err());
+    }
+
+    #[test]
+    pub fn test_event_time() {
+        use crate::data::CsrGraphStorage;
+        use super::{NO_EVENT_TIME, SeedTime};
+
+        // Edges src -> dst with their timestamps, node 4 is isolated
+        let rows = Tensor::of_slice(&[0_i64, 2, 3, 1, 0]);
+        let cols = Tensor::of_slice(&[1_i64, 1, 1, 2, 2]);
+        let times = Tensor::of_slice(&[5_i64, 3, 9, 4, 7]);
+        let coo = CooGraphStorage::new(Tensor::stack(&[rows, cols], 0), (5, 5));
+        let csc = CscGraphStorage::try_from(&coo).unwrap();
+        let csc_times = times.index_select(0, csc.perm().unwrap());
+        let csr = CsrGraphStorage::try_from(&coo).unwrap();
+        let csr_times = times.index_select(0, csr.perm().unwrap());
+
+        let nodes = Tensor::of_slice(&[1_i64, 2, 0, 4]);
+        let last: Vec<i64> = super::last_event_time(&csc, &csc_times, &nodes, None).unwrap().into();
+        assert_eq!(last, vec![9, 7, NO_EVENT_TIME, NO_EVENT_TIME]);
+        let first: Vec<i64> = super::first_event_time(&csc, &csc_times, &nodes, None).unwrap().into();
+        assert_eq!(first, vec![3, 4, NO_EVENT_TIME, NO_EVENT_TIME]);
+
+        let last: Vec<i64> = super::last_event_time(&csc, &csc_times, &nodes, Some((&csr, &csr_times))).unwrap().into();
+        assert_eq!(last, vec![9, 7, 7, NO_EVENT_TIME]);
+        let first: Vec<i64> = super::first_event_time(&csc, &csc_times, &nodes, Some((&csr, &csr_times))).unwrap().into();
+        assert_eq!(first, vec![3, 3, 5, NO_EVENT_TIME]);
+
+        // Time sorted blocks only read their ends
+        let by_time = csc.sort_neighbors_by_time(&csc_times).unwrap();
+        let by_time_times = times.index_select(0, by_time.perm().unwrap());
+        let last: Vec<i64> = super::last_event_time(&by_time, &by_time_times, &nodes, None).unwrap().into();
+        assert_eq!(last, vec![9, 7, NO_EVENT_TIME, NO_EVENT_TIME]);
+        let first: Vec<i64> = super::first_event_time(&by_time, &by_time_times, &nodes, None).unwrap().into();
+        assert_eq!(first, vec![3, 4, NO_EVENT_TIME, NO_EVENT_TIME]);
+
+        assert!(super::last_event_time(&csc, &times.narrow(0, 0, 2), &nodes, None).is_err());
+        assert!(super::last_event_time(&csc, &csc_times, &Tensor::of_slice(&[5_i64]), None).is_err());
+
+        // Inferring the seed times equals passing the last event times
+        let graph = CscGraph::<i64, i64>::try_from(&csc).unwrap();
+        let times_data: Vec<i64> = csc_times.shallow_clone().into();
+        let source = EdgeAttrSource::new(&graph).with_times(&times_data);
+        let filter = TemporalFilter::<_, false, TEMPORAL_SAMPLE_RELATIVE>::new(0..=3, &source);
+        let inputs = vec![1_i64, 2, 4];
+        let sample = |seed_time: SeedTime| super::neighbor_sampling_homogenous_temporal(
+            &mut rand::rngs::SmallRng::from_seed([0; 32]), &graph, &inputs, &[3, 3],
+            &UnweightedSampler::<false>, &filter, seed_time, None,
+        );
+        let (samples, edges, _) = sample(SeedTime::Infer).unwrap();
+        let (expected_samples, expected_edges, _) = sample(SeedTime::Given(&[9, 7, NO_EVENT_TIME])).unwrap();
+        assert_eq!(samples, expected_samples);
+        assert_eq!(edges.edge_index, expected_edges.edge_index);
+        // Hop one keeps the edges of node 1 in [6, 9] and of node 2 in [4, 7], hop two reaches node 1 again from
+        // seed 2 and keeps its edge at 5
+        let mut sampled_times: Vec<i64> = edges.edge_index.iter().map(|p| times_data[*p as usize]).collect();
+        sampled_times.sort_unstable();
+        assert_eq!(sampled_times, vec![4, 5, 7, 9]);
+        assert!(sample(SeedTime::Given(&[9])).is_err());
+    }
+
+    #[test]
+    pub fn test_neighbor_sampling_temporal_decay() {
+        use super::SeedTime;
+
+        // Node 0 has incoming edges from node i + 1 at time i, and a future edge from node 11 at time 20
+        let rows: Vec<i64> = (1..=11).collect();
+        let times: Vec<i64> = (0..10).chain([20]).collect();
+        let coo = CooGraphStorage::new(Tensor::stack(&[Tensor::of_slice(&rows), Tensor::zeros(&[11], (Kind::Int64, Device::Cpu))], 0), (12, 12));
+        let graph_data = CscGraphStorage::try_from(&coo).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let times_data: Vec<i64> = graph_data.permute_edge_attr(&Tensor::of_slice(&times)).into();
+        let source = EdgeAttrSource::new(&graph).with_times(&times_data);
+        let filter = TemporalFilter::<_, false, TEMPORAL_SAMPLE_RELATIVE>::new(0..=100, &source);
+
+        let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+        let mut sample_times = |k: usize, replace: bool, decay: Option<f64>| {
+            let (_, edges, _) = if replace {
+                super::neighbor_sampling_homogenous_temporal(
+                    &mut rng, &graph, &[0], &[k], &UnweightedSampler::<true>, &filter, SeedTime::Given(&[10]), decay,
+                )
+            } else {
+                super::neighbor_sampling_homogenous_temporal(
+                    &mut rng, &graph, &[0], &[k], &UnweightedSampler::<false>, &filter, SeedTime::Given(&[10]), decay,
+                )
+            }.unwrap();
+            edges.edge_index.iter().map(|p| times_data[*p as usize]).collect::<Vec<i64>>()
+        };
+
+        // Small tau prefers the most recent edge, which has probability 1 / sum_i e^-i
+        let expected = 1.0 / (0..10).map(|i| (-i as f64).exp()).sum::<f64>();
+        for replace in [false, true] {
+            let runs = 2000;
+            let recent = (0..runs).filter(|_| sample_times(1, replace, Some(1.0)) == vec![9]).count();
+            assert!((recent as f64 / runs as f64 - expected).abs() < 0.05, "{} runs of {} picked the newest edge", recent, runs);
+        }
+
+        // Future edges are never sampled
+        for replace in [false, true] {
+            for _ in 0..200 {
+                let sampled = sample_times(3, replace, Some(0.5));
+                assert_eq!(sampled.len(), 3);
+                assert!(sampled.iter().all(|t| *t <= 10));
+            }
+        }
+        assert_eq!(sample_times(20, false, Some(1.0)).len(), 10);
+
+        // Huge tau is uniform over the valid edges
+        let runs = 5000;
+        let mut counts = [0; 10];
+        for _ in 0..runs {
+            counts[sample_times(1, false, Some(1e12))[0] as usize] += 1;
+        }
+        assert!(counts.iter().all(|c| (*c as f64 / runs as f64 - 0.1).abs() < 0.03), "{:?}", counts);
+
+        // tau = inf draws exactly the uniform samples
+        let uniform = |decay: Option<f64>| {
+            let (samples, edges, _) = super::neighbor_sampling_homogenous_temporal(
+                &mut rand::rngs::SmallRng::from_seed([1; 32]), &graph, &[0], &[3, 2], &UnweightedSampler::<false>,
+                &filter, SeedTime::Given(&[10]), decay,
+            ).unwrap();
+            (samples, edges.edge_index)
+        };
+        assert_eq!(uniform(Some(f64::INFINITY)), uniform(None));
+        assert!(super::neighbor_sampling_homogenous_temporal(
+            &mut rand::rngs::SmallRng::from_seed([1; 32]), &graph, &[0], &[3], &UnweightedSampler::<false>,
+            &filter, SeedTime::Given(&[10]), Some(0.0),
+        ).is_err());
+
+        // The decay doesn't silently drop weights or repeat caps
+        let mut rng = rand::rngs::SmallRng::from_seed([1; 32]);
+        let weights_data = vec![1.0_f64; times_data.len()];
+        let weighted = EdgeAttrSource::new(&graph).with_weights(&weights_data);
+        assert!(super::neighbor_sampling_homogenous_temporal(
+            &mut rng, &graph, &[0], &[3], &WeightedSampler::new(&weighted), &filter,
+            SeedTime::Given(&[10]), Some(1.0),
+        ).is_err());
+        assert!(super::neighbor_sampling_homogenous_temporal(
+            &mut rng, &graph, &[0], &[3], &ReplacementSampler::new(Some(1)), &filter, SeedTime::Given(&[10]), Some(1.0),
+        ).is_err());
+        assert!(super::neighbor_sampling_homogenous_temporal(
+            &mut rng, &graph, &[0], &[3], &ReplacementSampler::new(Some(1)), &filter, SeedTime::Given(&[10]), None,
+        ).is_ok());
+        let per_hop = PerHopSampler::uniform(ReplacePolicy::PerHop(vec![true, false]), 2).unwrap();
+        assert!(super::neighbor_sampling_homogenous_temporal(
+            &mut rng, &graph, &[0], &[3, 2], &per_hop, &filter, SeedTime::Given(&[10]), Some(1.0),
+        ).is_ok());
+    }
+
+    #[test]
+    pub fn test_neighbor_sampling_backends() {
+        let (_x, _, coo_graph) = load_karate_graph();
+        let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("tch_geometric_backends_{}", std::process::id()));
+        graph_data.save(&dir).unwrap();
+        let mmap_data = MmapCscGraphStorage::load(&dir).unwrap();
+
+        let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+        let weights_data = (0..graph.edge_count()).map(|_| rng.gen_range(0.2..5.0)).collect::<Vec<f64>>();
+        let timestamps_data = (0..graph.edge_count()).map(|_| rng.gen_range(0..4)).collect::<Vec<i64>>();
+
+        let inputs = vec![0_i64, 1, 4, 5];
+        let inputs_state = vec![(); inputs.len()];
+        let input_timestamps = vec![0_i64, 1, 2, 3];
+        let num_neighbors = vec![4, 3];
+
+        // Every backend has to reproduce the samples of the plain csc graph exactly, with the edge attributes read
+        // through the source
+        let run = |backend: &dyn NeighborSource| {
+            let source = EdgeAttrSource::new(backend).with_weights(&weights_data).with_times(&timestamps_data);
+            let filter = TemporalFilter::<_, false, TEMPORAL_SAMPLE_RELATIVE>::new(0..=2, &source);
+            let unweighted = super::neighbor_sampling_homogenous(
+                &mut rand::rngs::SmallRng::from_seed([1; 32]), backend, &inputs, &num_neighbors,
+                &UnweightedSampler::<false>, &IdentityFilter, &inputs_state,
+            );
+            let weighted = super::neighbor_sampling_homogenous(
+                &mut rand::rngs::SmallRng::from_seed([1; 32]), backend, &inputs, &num_neighbors,
+                &WeightedSampler::new(&source), &IdentityFilter, &inputs_state,
+            );
+            let temporal = super::neighbor_sampling_homogenous(
+                &mut rand::rngs::SmallRng::from_seed([1; 32]), backend, &inputs, &num_neighbors,
+                &UnweightedSampler::<true>, &filter, &input_timestamps,
+            );
+            [unweighted, weighted, temporal].iter()
+                .map(|(samples, coo_builder, layer_offsets)| {
+                    validate_neighbor_samples(&graph, coo_builder, samples, samples, layer_offsets, &num_neighbors);
+                    (samples.clone(), coo_builder.edge_index.clone(), layer_offsets.clone())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let expected = run(&graph);
+        assert_eq!(run(&mmap_data), expected);
 
-            paths.push_back((path, path_edges));
-        }
-        paths.into()
+        let (samples, coo_builder, layer_offsets) = super::neighbor_sampling_homogenous(
+            &mut rand::rngs::SmallRng::from_seed([1; 32]), &mmap_data, &inputs, &num_neighbors,
+            &UnweightedSampler::<false>, &IdentityFilter, &inputs_state,
+        );
+        assert_eq!((samples, coo_builder.edge_index, layer_offsets), expected[0]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
-    pub fn test_neighbor_sampling_homogenous() {
+    pub fn test_neighbor_sampling_homogenous_stats() {
         let (_x, _, coo_graph) = load_karate_graph();
 
         let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
@@ -443,12 +2796,48 @@ mod tests {
         let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
         let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
 
-        let inputs = vec![0_i64, 1, 4, 5];
-        let inputs_state = vec![(); inputs.len()];
+        let timestamps_data = (0..graph.edge_count()).map(|_| rng.gen_range(0..4)).collect::<Vec<i64>>();
+        let source = EdgeAttrSource::new(&graph).with_times(&timestamps_data);
 
+        let inputs = vec![0_i64, 1, 4, 5];
+        let input_timestamps = vec![0_i64, 1, 2, 3];
         let num_neighbors = vec![4, 3];
 
-        let (samples, coo_builder, layer_offsets) = super::neighbor_sampling_homogenous(
+        let filter = TemporalFilter::<_, false, TEMPORAL_SAMPLE_STATIC>::new(
+            0..=1, &source,
+        );
+        let mut stats = SamplerStats::new();
+        let (samples, coo_builder, layer_offsets) = super::neighbor_sampling_homogenous_traced(
+            &mut rng,
+            &graph,
+            &inputs,
+            &num_neighbors,
+            &UnweightedSampler::<false>,
+            &filter,
+            &input_timestamps,
+            &mut stats,
+        );
+
+        assert_eq!(stats.hops.len(), num_neighbors.len());
+        for (hop, s) in stats.hops.iter().enumerate() {
+            let (begin, edges_begin, _) = layer_offsets[hop];
+            let (end, edges_end) = layer_offsets.get(hop + 1)
+                .map(|(end, edges_end, _)| (*end, *edges_end))
+                .unwrap_or((samples.len() as i64, coo_builder.len() as i64));
+
+            assert_eq!(s.hop, hop);
+            assert_eq!(s.num_nodes as i64, end - begin);
+            assert_eq!(s.num_edges as i64, edges_end - edges_begin);
+            assert!(s.num_empty + s.num_truncated <= s.num_frontier);
+        }
+        assert_eq!(stats.hops[0].num_frontier, inputs.len());
+        assert_eq!(stats.to_dict()["num_nodes"], stats.hops.iter().map(|s| s.num_nodes).collect::<Vec<_>>());
+
+        // The callback receives the same stats as the collector
+        let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+        let inputs_state = vec![(); inputs.len()];
+        let mut collected: Vec<HopStats> = Vec::new();
+        let (samples, _, layer_offsets) = super::neighbor_sampling_homogenous_traced(
             &mut rng,
             &graph,
             &inputs,
@@ -456,15 +2845,16 @@ mod tests {
             &UnweightedSampler::<true>,
             &IdentityFilter,
             &inputs_state,
+            &mut |s: HopStats| collected.push(s),
         );
 
-        validate_neighbor_samples(
-            &graph, &coo_builder, &samples, &samples, &layer_offsets, &num_neighbors
-        );
+        assert_eq!(collected.len(), num_neighbors.len());
+        assert_eq!(collected[0].num_nodes as i64, layer_offsets[1].0 - layer_offsets[0].0);
+        assert_eq!(collected[1].num_nodes as i64, samples.len() as i64 - layer_offsets[1].0);
     }
 
     #[test]
-    pub fn test_neighbor_sampling_homogenous_weighted() {
+    pub fn test_neighbor_sampling_homogenous_fanout_policy() {
         let (_x, _, coo_graph) = load_karate_graph();
 
         let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
@@ -472,101 +2862,502 @@ mod tests {
         let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
         let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
 
-        let weights_data = (0..graph.edge_count()).map(|_| rng.gen_range(0.2..5.0)).collect::<Vec<f64>>();
-        let weights = EdgeAttr::new(&weights_data);
-
         let inputs = vec![0_i64, 1, 4, 5];
         let inputs_state = vec![(); inputs.len()];
-        let num_neighbors = vec![4, 3];
 
-        let (samples, coo_builder, layer_offsets) = super::neighbor_sampling_homogenous(
+        // Fraction(1.0) expands the full neighborhood
+        let (_samples, coo_builder, _) = super::neighbor_sampling_homogenous(
             &mut rng,
             &graph,
             &inputs,
-            &num_neighbors,
-            &WeightedSampler::new(weights),
+            &[FanoutPolicy::fraction(1.0)],
+            &UnweightedSampler::<false>,
             &IdentityFilter,
             &inputs_state,
         );
+        let mut counts = vec![0_usize; inputs.len()];
+        for (_j, i) in coo_builder.iter_edges() {
+            counts[i as usize] += 1;
+        }
+        for (i, w) in inputs.iter().enumerate() {
+            assert_eq!(counts[i], graph.in_degree(*w));
+        }
 
-        validate_neighbor_samples(
-            &graph, &coo_builder, &samples, &samples, &layer_offsets, &num_neighbors
-        );
+        // Star graph where node 0 has 10 incoming neighbors
+        let rows: Vec<i64> = (1..=10).collect();
+        let cols: Vec<i64> = vec![0; 10];
+        let edge_index = Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0);
+        let star_data = CscGraphStorage::try_from(&CooGraphStorage::new(edge_index, (11, 11))).unwrap();
+        let star = CscGraph::<i64, i64>::try_from(&star_data).unwrap();
+
+        let cases = [
+            (FanoutPolicy::fraction(0.5), 5),
+            (FanoutPolicy::fraction(0.25), 3),
+            (FanoutPolicy::Fraction { fraction: 0.1, min: Some(4), max: None }, 4),
+            (FanoutPolicy::Fraction { fraction: 1.0, min: None, max: Some(2) }, 2),
+            (FanoutPolicy::Fraction { fraction: 2.0, min: None, max: None }, 10),
+            (FanoutPolicy::Sqrt(1.0), 4),
+            (FanoutPolicy::Fixed(0), 0),
+        ];
+        for (policy, expected) in cases.iter() {
+            let (samples, coo_builder, _) = super::neighbor_sampling_homogenous(
+                &mut rng,
+                &star,
+                &[0],
+                &[*policy],
+                &UnweightedSampler::<false>,
+                &IdentityFilter,
+                &[()],
+            );
+            assert_eq!(coo_builder.len(), *expected);
+            assert_eq!(samples.len(), expected + 1);
+        }
+
+        assert_eq!(FanoutPolicy::Sqrt(2.0).fanout(10), 7);
+        assert_eq!(FanoutPolicy::from(3).fanout(100), 3);
     }
 
     #[test]
-    pub fn test_neighbor_sampling_homogenous_temporal() {
+    pub fn test_neighbor_sampling_max_repeats() {
         let (_x, _, coo_graph) = load_karate_graph();
+        let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let sample = |inputs: &[i64], num_neighbors: &[usize], sampler: &ReplacementSampler| {
+            super::neighbor_sampling_homogenous(
+                &mut rand::rngs::SmallRng::from_seed([0; 32]), &graph, inputs, num_neighbors,
+                sampler, &IdentityFilter, &vec![(); inputs.len()],
+            )
+        };
+
+        // Node 11 only has a single neighbor
+        assert_eq!(graph.in_degree(11), 1);
+        let (_, edges, _) = sample(&[11], &[10], &ReplacementSampler::new(Some(3)));
+        assert_eq!(edges.len(), 3);
+        let (_, edges, _) = sample(&[11], &[10], &ReplacementSampler::new(Some(0)));
+        assert_eq!(edges.len(), 0);
+
+        // No edge of a sampled node is emitted more than max_repeats times
+        let inputs = vec![0_i64, 1, 4, 5, 11];
+        let (samples, edges, layer_offsets) = sample(&inputs, &[6, 4], &ReplacementSampler::new(Some(2)));
+        validate_neighbor_samples(&graph, &edges, &samples, &samples, &layer_offsets, &[6, 4]);
+        let mut counts = HashMap::new();
+        for (i, ptr) in edges.cols.iter().zip(edges.edge_index.iter()) {
+            *counts.entry((*i, *ptr)).or_insert(0) += 1;
+        }
+        assert!(counts.values().all(|c| *c <= 2));
 
-        let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+        // A cap of at least the fanout equals plain sampling with replacement
+        let (expected_samples, expected_edges, _) = super::neighbor_sampling_homogenous(
+            &mut rand::rngs::SmallRng::from_seed([0; 32]), &graph, &inputs, &[6, 4],
+            &UnweightedSampler::<true>, &IdentityFilter, &vec![(); inputs.len()],
+        );
+        for max_repeats in [None, Some(6), Some(10)] {
+            let (samples, edges, _) = sample(&inputs, &[6, 4], &ReplacementSampler::new(max_repeats));
+            assert_eq!(samples, expected_samples);
+            assert_eq!(edges.rows, expected_edges.rows);
+            assert_eq!(edges.cols, expected_edges.cols);
+            assert_eq!(edges.edge_index, expected_edges.edge_index);
+        }
+    }
 
+    #[test]
+    pub fn test_neighbor_sampling_replace_policy() {
+        // Star graph with center 0 and leaves 1..=5 that only neighbor the center
+        let rows = Tensor::of_slice(&[0_i64, 0, 0, 0, 0, 1, 2, 3, 4, 5]);
+        let cols = Tensor::of_slice(&[1_i64, 2, 3, 4, 5, 0, 0, 0, 0, 0]);
+        let coo_graph = CooGraphStorage::new(Tensor::stack(&[rows, cols], 0), (6, 6));
         let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
         let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let sample = |sampler: &PerHopSampler<UnweightedSampler<false>, UnweightedSampler<true>>| {
+            super::neighbor_sampling_homogenous(
+                &mut rand::rngs::SmallRng::from_seed([0; 32]), &graph, &[0], &[5, 3],
+                sampler, &IdentityFilter, &[()],
+            )
+        };
+        let edge_counts = |edges: &CooGraphBuilder, range: std::ops::Range<usize>| {
+            let mut counts = HashMap::new();
+            for e in range {
+                *counts.entry((edges.cols[e], edges.edge_index[e])).or_insert(0) += 1;
+            }
+            counts
+        };
+
+        let (samples, edges, layer_offsets) = sample(&PerHopSampler::uniform(ReplacePolicy::PerHop(vec![false, true]), 2).unwrap());
+        validate_neighbor_samples(&graph, &edges, &samples, &samples, &layer_offsets, &[5, 3]);
+        let hop_edges = layer_offsets[1].1 as usize;
+        // The first hop takes every leaf exactly once, the second draws the single edge of every leaf three times
+        let first = edge_counts(&edges, 0..hop_edges);
+        assert_eq!(first.len(), 5);
+        assert!(first.values().all(|c| *c == 1));
+        let second = edge_counts(&edges, hop_edges..edges.len());
+        assert_eq!(second.len(), 5);
+        assert!(second.values().all(|c| *c == 3));
+
+        // A fixed policy equals the plain sampler
+        let (expected_samples, expected_edges, _) = super::neighbor_sampling_homogenous(
+            &mut rand::rngs::SmallRng::from_seed([0; 32]), &graph, &[0], &[5, 3],
+            &UnweightedSampler::<true>, &IdentityFilter, &[()],
+        );
+        let (samples, edges, _) = sample(&PerHopSampler::uniform(true.into(), 2).unwrap());
+        assert_eq!(samples, expected_samples);
+        assert_eq!(edges.edge_index, expected_edges.edge_index);
+
+        assert!(ReplacePolicy::PerHop(vec![false, true]).validate(2).is_ok());
+        assert!(ReplacePolicy::PerHop(vec![false]).validate(2).is_err());
+        assert!(ReplacePolicy::Fixed(true).validate(3).is_ok());
+        assert_eq!(ReplacePolicy::PerHop(vec![false, true]).at(1), Some(true));
+        assert_eq!(ReplacePolicy::PerHop(vec![false, true]).at(5), None);
+        assert!(PerHopSampler::uniform(ReplacePolicy::PerHop(vec![false]), 2).is_err());
+
+        // Exhaustive only if the samplers of all hops are
+        let without = PerHopSampler::uniform(ReplacePolicy::PerHop(vec![false, false]), 2).unwrap();
+        assert!(without.is_exhaustive(5, 5));
+        let mixed = PerHopSampler::uniform(ReplacePolicy::PerHop(vec![false, true]), 2).unwrap();
+        assert!(!mixed.is_exhaustive(5, 5));
+    }
 
-        let timestamps_data = (0..graph.edge_count()).map(|_| rng.gen_range(0..4)).collect::<Vec<i64>>();
-        let timestamps = EdgeAttr::new(&timestamps_data);
+    #[test]
+    pub fn test_neighbor_sampling_homogenous_dedup() {
+        let (_x, _, coo_graph) = load_karate_graph();
+        let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
 
         let inputs = vec![0_i64, 1, 4, 5];
-        let input_timestamps = vec![0_i64, 1, 2, 3];
-        let num_neighbors = vec![4, 3];
+        let inputs_state = vec![(); inputs.len()];
+        let num_neighbors = vec![4, 3, 2];
+        let (samples, edges, layer_offsets) = super::neighbor_sampling_homogenous_dedup(
+            &mut rand::rngs::SmallRng::from_seed([0; 32]), &graph, &inputs, &num_neighbors,
+            &UnweightedSampler::<false>, &IdentityFilter, &inputs_state,
+        );
 
-        // Tests static window sampling
-        let filter = TemporalFilter::<i64, false, TEMPORAL_SAMPLE_STATIC>::new(
-            0..=2, timestamps.clone(),
+        assert_eq!(&samples[..inputs.len()], inputs.as_slice());
+        let mut unique = samples.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        assert_eq!(unique.len(), samples.len());
+        assert_eq!(layer_offsets.len(), num_neighbors.len());
+        assert!(edges.len() > samples.len() - inputs.len());
+
+        // Local ids map back to an edge of the graph
+        for (&j, (&i, &ptr)) in edges.rows.iter().zip(edges.cols.iter().zip(edges.edge_index.iter())) {
+            let (dst, src) = (samples[i as usize], samples[j as usize]);
+            assert!(graph.neighbors_range(dst).contains(&(ptr as usize)));
+            assert_eq!(graph.get_by_ptr(ptr as usize), src);
+        }
+
+        // Tracing doesn't change the samples, every hop only counts its newly discovered nodes
+        let mut stats = SamplerStats::new();
+        let traced = super::neighbor_sampling_homogenous_dedup_traced(
+            &mut rand::rngs::SmallRng::from_seed([0; 32]), &graph, &inputs, &num_neighbors,
+            &UnweightedSampler::<false>, &IdentityFilter, &inputs_state, &mut stats,
         );
-        let (samples, coo_builder, layer_offsets) = super::neighbor_sampling_homogenous(
-            &mut rng,
-            &graph,
-            &inputs,
-            &num_neighbors,
-            &UnweightedSampler::<false>,
-            &filter,
-            &input_timestamps,
+        assert_eq!((&traced.0, &traced.1.edge_index, &traced.2), (&samples, &edges.edge_index, &layer_offsets));
+        assert_eq!(stats.hops.len(), num_neighbors.len());
+        assert_eq!(stats.hops.iter().map(|s| s.num_nodes).sum::<usize>(), samples.len() - inputs.len());
+        assert_eq!(stats.hops.iter().map(|s| s.num_edges).sum::<usize>(), edges.len());
+
+        // Large graphs use the hash map based ids
+        let mut local_ids = super::LocalIds::Sparse(HashMap::new());
+        assert_eq!(local_ids.get_or_insert(7, 0), (0, true));
+        assert_eq!(local_ids.get_or_insert(3, 1), (1, true));
+        assert_eq!(local_ids.get_or_insert(7, 2), (0, false));
+    }
+
+    #[test]
+    pub fn test_neighbor_sampling_bipartite() {
+        // 5 users and 3 items
+        let interactions = [(0_i64, 0_i64), (1, 0), (1, 1), (2, 1), (3, 2), (4, 2), (4, 1)];
+        let users: Vec<i64> = interactions.iter().map(|e| e.0).collect();
+        let items: Vec<i64> = interactions.iter().map(|e| e.1).collect();
+        let user_item = CooGraphStorage::new(Tensor::stack(&[Tensor::of_slice(&users), Tensor::of_slice(&items)], 0), (5, 3));
+        let item_user = CooGraphStorage::new(Tensor::stack(&[Tensor::of_slice(&items), Tensor::of_slice(&users)], 0), (3, 5));
+        let graph_data = CscGraphStorage::try_from(&user_item).unwrap();
+        let reverse_data = CscGraphStorage::try_from(&item_user).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let reverse = CscGraph::<i64, i64>::try_from(&reverse_data).unwrap();
+
+        let reverse_positions = super::bipartite_reverse_positions(
+            &Vec::<i64>::from(graph_data.perm().unwrap()), &Vec::<i64>::from(reverse_data.perm().unwrap()),
         );
+        // A distinct attribute per interaction, in the original edge order
+        let edge_attr: Vec<i64> = (0..interactions.len() as i64).map(|e| 100 + e).collect();
+        let edge_attr_data: Vec<i64> = graph_data.permute_edge_attr(&Tensor::of_slice(&edge_attr)).into();
 
-        validate_neighbor_samples(
-            &graph, &coo_builder, &samples, &samples, &layer_offsets, &num_neighbors
+        let inputs = vec![0_i64];
+        let (dst_samples, src_samples, edges, layer_offsets) = super::neighbor_sampling_bipartite(
+            &mut rand::rngs::SmallRng::from_seed([0; 32]), &graph, &reverse, &reverse_positions, &inputs, &[10, 10, 10],
+            &UnweightedSampler::<false>,
         );
-        let paths = samples_to_paths(&coo_builder, &samples, &inputs);
-        for (_path, edges) in paths {
-            for edge_idx in edges {
-                let edge_ptr = coo_builder.edge_index[edge_idx];
-                let t = timestamps.get(edge_ptr as usize);
-                assert!((0..=2).contains(t));
-            }
+
+        // Item 0 -> users 0, 1 -> items 0, 1 -> users 1, 2, 4 of the newly reached item 1
+        assert_eq!(dst_samples, vec![0, 1]);
+        let mut users_sorted = src_samples.clone();
+        users_sorted.sort_unstable();
+        assert_eq!(users_sorted, vec![0, 1, 2, 4]);
+        assert_eq!(layer_offsets.iter().map(|o| o.1).collect::<Vec<_>>(), vec![0, 2, 5]);
+        assert_eq!(edges.len(), 2 + 3 + 3);
+
+        for (e, (&row, &col)) in edges.rows.iter().zip(edges.cols.iter()).enumerate() {
+            let (user, item) = (src_samples[row as usize], dst_samples[col as usize]);
+            assert!(interactions.contains(&(user, item)));
+            // Positions of every hop refer to the forward graph and read the attribute of that interaction
+            let ptr = edges.edge_index[e] as usize;
+            assert_eq!(graph.get_by_ptr(ptr), user);
+            let id = interactions.iter().position(|x| *x == (user, item)).unwrap() as i64;
+            assert_eq!(edge_attr_data[ptr], 100 + id);
         }
+    }
 
-        // Tests relative window sampling backward in time
-        let filter = TemporalFilter::<i64, false, TEMPORAL_SAMPLE_RELATIVE>::new(
-            0..=2, timestamps.clone(),
+    #[test]
+    pub fn test_gather_features() {
+        let (_x, _, coo_graph) = load_karate_graph();
+
+        let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+
+        let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let inputs = vec![0_i64, 1, 4, 5];
+        let inputs_state = vec![(); inputs.len()];
+        let (samples, _, _) = super::neighbor_sampling_homogenous(
+            &mut rng, &graph, &inputs, &[4, 3], &UnweightedSampler::<false>, &IdentityFilter, &inputs_state,
         );
+        let index = Tensor::of_slice(&samples);
+        let node_count = graph.node_count() as i64;
+
+        let x = Tensor::rand(&[node_count, 8], (Kind::Float, Device::Cpu));
+        let gathered = super::gather_features(&x, &samples, graph.node_count()).unwrap();
+        assert!(gathered.equal(&x.index_select(0, &index)));
+
+        let x = Tensor::arange(node_count * 6, (Kind::Int64, Device::Cpu)).view([node_count, 2, 3]);
+        let gathered = super::gather_features(&x, &samples, graph.node_count()).unwrap();
+        assert_eq!(gathered.size(), vec![samples.len() as i64, 2, 3]);
+        assert!(gathered.equal(&x.index_select(0, &index)));
+
+        let x = Tensor::zeros(&[node_count - 1, 8], (Kind::Float, Device::Cpu));
+        assert!(super::gather_features(&x, &samples, graph.node_count()).is_err());
+    }
+
+    #[test]
+    pub fn test_sample_output() {
+        let (x, _, coo_graph) = load_karate_graph();
+        let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let inputs = vec![0_i64, 1, 4, 5];
+        let inputs_state = vec![(); inputs.len()];
         let (samples, coo_builder, layer_offsets) = super::neighbor_sampling_homogenous(
-            &mut rng,
-            &graph,
-            &inputs,
-            &num_neighbors,
-            &UnweightedSampler::<false>,
-            &filter,
-            &input_timestamps,
+            &mut rand::rngs::SmallRng::from_seed([0; 32]), &graph, &inputs, &[4, 3],
+            &UnweightedSampler::<false>, &IdentityFilter, &inputs_state,
         );
+        let output = SampleOutput::new(&samples, &coo_builder, layer_offsets, graph_data.perm());
 
-        validate_neighbor_samples(
-            &graph, &coo_builder, &samples, &samples, &layer_offsets, &num_neighbors
-        );
-        let paths = samples_to_paths(&coo_builder, &samples, &inputs);
-        for (_path, edges) in paths {
-            if let Some(edge_idx) = edges.first().cloned() {
-                let to_idx = coo_builder.cols[edge_idx];
-                let start_t = input_timestamps[to_idx as usize];
+        let x_sampled = output.gather_node_features(&x);
+        assert_eq!(x_sampled.size()[0], samples.len() as i64);
+        for (i, v) in samples.iter().enumerate() {
+            assert!(x_sampled.select(0, i as i64).equal(&x.select(0, *v)));
+        }
 
-                for edge_idx in edges {
-                    let edge_ptr = coo_builder.edge_index[edge_idx];
-                    let t = *timestamps.get(edge_ptr as usize);
+        // Edge features are looked up by the original coo edge ids
+        let edge_attr = Tensor::arange(coo_graph.row_col.size()[1], (Kind::Int64, Device::Cpu)) * 10;
+        let e_attr: Vec<i64> = output.gather_edge_features(&edge_attr).into();
+        let coo_rows: Vec<i64> = coo_graph.row().into();
+        let coo_cols: Vec<i64> = coo_graph.col().into();
+        assert_eq!(e_attr.len(), coo_builder.len());
+        for ((j, i), attr) in coo_builder.iter_edges().zip(e_attr.iter()) {
+            let e = (*attr / 10) as usize;
+            assert_eq!((coo_rows[e], coo_cols[e]), (samples[j as usize], samples[i as usize]));
+        }
+    }
 
-                    assert!(((start_t - 2)..=start_t).contains(&t));
-                }
+    #[test]
+    pub fn test_dgl_blocks() {
+        let (_x, _, coo_graph) = load_karate_graph();
+        let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let inputs = vec![0_i64, 1, 4, 5];
+        for dedup in [false, true] {
+            let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+            let (samples, coo_builder, layer_offsets) = if dedup {
+                super::neighbor_sampling_homogenous_dedup(
+                    &mut rng, &graph, &inputs, &[4, 3, 2], &UnweightedSampler::<false>, &IdentityFilter, &[(); 4],
+                )
+            } else {
+                super::neighbor_sampling_homogenous(
+                    &mut rng, &graph, &inputs, &[4, 3, 2], &UnweightedSampler::<false>, &IdentityFilter, &[(); 4],
+                )
+            };
+            let output = SampleOutput::new(&samples, &coo_builder, layer_offsets.clone(), graph_data.perm());
+            let blocks: Vec<DglBlock> = output.to_dgl_blocks();
+            assert_eq!(blocks.len(), 3);
+
+            let mut prev_edges = 0;
+            for (hop, block) in blocks.iter().enumerate() {
+                let src: Vec<i64> = block.src.shallow_clone().into();
+                let dst: Vec<i64> = block.dst.shallow_clone().into();
+                assert_eq!(block.num_dst_nodes, layer_offsets[hop].0);
+                assert!(block.num_dst_nodes <= block.num_src_nodes);
+                assert!(src.iter().all(|j| *j < block.num_src_nodes));
+                assert!(dst.iter().all(|i| *i < block.num_dst_nodes));
+                // The dst nodes are the prefix of the src nodes
+                assert!(block.src_nid.narrow(0, 0, block.num_dst_nodes).equal(&block.dst_nid));
+                assert!(block.eid.equal(&output.e_id.narrow(0, 0, src.len() as i64)));
+
+                // Every edge into the dst nodes is part of the block
+                let num_edges = coo_builder.cols.iter().zip(coo_builder.rows.iter())
+                    .filter(|(i, j)| **i < block.num_dst_nodes && **j < block.num_src_nodes)
+                    .count();
+                assert_eq!(src.len(), num_edges);
+                assert!(src.len() >= prev_edges);
+                prev_edges = src.len();
+            }
+            assert_eq!(blocks[2].num_src_nodes, samples.len() as i64);
+            assert_eq!(prev_edges, coo_builder.len());
+        }
+    }
+
+    #[test]
+    pub fn test_bipartite_blocks() {
+        let (_x, _, coo_graph) = load_karate_graph();
+        let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let inputs = vec![0_i64, 1, 4, 5];
+        let (samples, coo_builder, layer_offsets) = super::neighbor_sampling_homogenous(
+            &mut rand::rngs::SmallRng::from_seed([0; 32]), &graph, &inputs, &[4, 3, 2],
+            &UnweightedSampler::<false>, &IdentityFilter, &[(); 4],
+        );
+        let output = SampleOutput::new(&samples, &coo_builder, layer_offsets, graph_data.perm());
+        let blocks: Vec<BipartiteBlock> = output.as_bipartite_blocks();
+        assert_eq!(blocks.len(), 3);
+
+        // The first block reads all sampled nodes, the last one writes the seeds
+        assert!(blocks[0].src_nodes.equal(&output.n_id));
+        let seeds: Vec<i64> = blocks[2].dst_nodes.shallow_clone().into();
+        assert_eq!(seeds, inputs);
+
+        for (block, dgl_block) in blocks.iter().zip(output.to_dgl_blocks().iter().rev()) {
+            let (num_src, num_dst) = block.size;
+            assert_eq!(block.src_nodes.size(), vec![num_src]);
+            assert_eq!(block.dst_nodes.size(), vec![num_dst]);
+            assert_eq!(block.edge_index.size()[0], 2);
+            let src: Vec<i64> = block.edge_index.select(0, 0).into();
+            let dst: Vec<i64> = block.edge_index.select(0, 1).into();
+            assert!(src.iter().all(|j| *j < num_src));
+            assert!(dst.iter().all(|i| *i < num_dst));
+            // Every edge connects the global nodes it was sampled between
+            let src_nodes: Vec<i64> = block.src_nodes.shallow_clone().into();
+            let dst_nodes: Vec<i64> = block.dst_nodes.shallow_clone().into();
+            for (j, i) in src.iter().zip(dst.iter()) {
+                assert!(graph.has_edge(dst_nodes[*i as usize], src_nodes[*j as usize]));
             }
+            assert!(block.edge_index.select(0, 0).equal(&dgl_block.src));
+            assert!(block.e_id.equal(&dgl_block.eid));
+        }
+    }
+
+    #[test]
+    pub fn test_sample_output_csc() {
+        let (_x, _, coo_graph) = load_karate_graph();
+        let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let inputs = vec![0_i64, 1, 4, 5];
+        let (samples, coo_builder, layer_offsets) = super::neighbor_sampling_homogenous(
+            &mut rand::rngs::SmallRng::from_seed([0; 32]), &graph, &inputs, &[4, 3],
+            &UnweightedSampler::<false>, &IdentityFilter, &[(); 4],
+        );
+        let output = SampleOutput::with_format(
+            &samples, &coo_builder, layer_offsets, graph_data.perm(), SampleFormat::Csc,
+        );
+        assert!(SampleOutput::new(&samples, &coo_builder, vec![], None).csc.is_none());
+        let csc = output.csc.as_ref().unwrap();
+
+        // Matches converting the coo output, up to the order within the neighbor blocks
+        let n = samples.len() as i64;
+        let converted = CscGraphStorage::try_from(&CooGraphStorage::new(output.row_col.shallow_clone(), (n, n))).unwrap();
+        assert!(csc.ptrs.equal(&converted.ptrs));
+        let sorted = csc.sort_neighbors_by_id().unwrap();
+        assert!(sorted.indices.equal(&converted.indices));
+        assert!(sorted.perm().unwrap().equal(&converted.permute_edge_attr(&output.e_id)));
+
+        // Edges pushed out of destination order are grouped as well
+        let mut builder = CooGraphBuilder::<i64, i64>::new();
+        for (src, dst, e) in [(3, 1, 10), (4, 0, 11), (5, 1, 12), (6, 0, 13)] {
+            builder.push_edge(src, dst, e);
         }
+        let output = SampleOutput::with_format(&[0, 1, 2, 3, 4, 5, 6], &builder, vec![], None, SampleFormat::Csc);
+        let csc = output.csc.unwrap();
+        assert_eq!(Vec::<i64>::from(&csc.ptrs), vec![0, 2, 4, 4, 4, 4, 4, 4]);
+        assert_eq!(Vec::<i64>::from(&csc.indices), vec![4, 6, 3, 5]);
+        assert_eq!(Vec::<i64>::from(csc.perm().unwrap()), vec![11, 13, 10, 12]);
+    }
+
+    #[test]
+    pub fn test_hetero_sampler_output() {
+        // Seeds of type b, hop 0 samples a from b and hop 1 samples b from a
+        let edge_types: Vec<EdgeType> = vec![
+            ("a".to_string(), "to".to_string(), "b".to_string()),
+            ("b".to_string(), "to".to_string(), "a".to_string()),
+        ];
+        let seeds = [10_i64, 11];
+        let inputs: HashMap<NodeType, &[NodeIdx]> = vec![("b".to_string(), &seeds[..])].into_iter().collect();
+        let samples: HashMap<NodeType, Vec<NodeIdx>> = vec![
+            ("a".to_string(), vec![20, 21]),
+            ("b".to_string(), vec![10, 11, 30, 31]),
+        ].into_iter().collect();
+
+        let mut a_to_b = CooGraphBuilder::new();
+        a_to_b.push_edge(0, 1, 5);
+        a_to_b.push_edge(1, 0, 6);
+        let mut b_to_a = CooGraphBuilder::new();
+        b_to_a.push_edge(2, 1, 7);
+        b_to_a.push_edge(3, 0, 8);
+        let edge_index: HashMap<RelType, CooGraphBuilder> = vec![
+            ("a__to__b".to_string(), a_to_b),
+            ("b__to__a".to_string(), b_to_a),
+        ].into_iter().collect();
+        let layer_offsets: HashMap<RelType, Vec<LayerOffset>> = vec![
+            ("a__to__b".to_string(), vec![(0, 0, 2), (2, 2, 2)]),
+            ("b__to__a".to_string(), vec![(2, 0, 0), (2, 0, 2)]),
+        ].into_iter().collect();
+
+        let output = HeteroSamplerOutput::new(&edge_types, &inputs, &samples, &edge_index, &layer_offsets);
+        assert_eq!(output.num_seeds["a"], 0);
+        assert_eq!(output.num_seeds["b"], 2);
+        assert_eq!(Vec::<i64>::from(&output.node["b"]), vec![10, 11, 30, 31]);
+        assert_eq!(Vec::<i64>::from(&output.batch["a"]), vec![1, 0]);
+        assert_eq!(Vec::<i64>::from(&output.hop["a"]), vec![1, 1]);
+        assert_eq!(Vec::<i64>::from(&output.batch["b"]), vec![0, 1, 0, 1]);
+        assert_eq!(Vec::<i64>::from(&output.hop["b"]), vec![0, 0, 2, 2]);
+        assert_eq!(Vec::<i64>::from(&output.row[&edge_types[1]]), vec![2, 3]);
+        assert_eq!(Vec::<i64>::from(&output.col[&edge_types[1]]), vec![1, 0]);
+        assert_eq!(Vec::<i64>::from(&output.edge[&edge_types[0]]), vec![5, 6]);
+    }
+
+    #[test]
+    pub fn test_hetero_sampler_output_self_loops() {
+        // Seed 10 samples node 30 in hop 0, seed 11 has no neighbors and gets a self loop
+        let edge_types: Vec<EdgeType> = vec![("b".to_string(), "to".to_string(), "b".to_string())];
+        let seeds = [10_i64, 11];
+        let inputs: HashMap<NodeType, &[NodeIdx]> = vec![("b".to_string(), &seeds[..])].into_iter().collect();
+        let samples: HashMap<NodeType, Vec<NodeIdx>> = vec![("b".to_string(), vec![10, 11, 30])].into_iter().collect();
+
+        let mut b_to_b = CooGraphBuilder::new();
+        b_to_b.push_edge(2, 0, 5);
+        let mut edge_index: HashMap<RelType, CooGraphBuilder> = vec![("b__to__b".to_string(), b_to_b)].into_iter().collect();
+        let mut layer_offsets: HashMap<RelType, Vec<LayerOffset>> =
+            vec![("b__to__b".to_string(), vec![(0, 0, 2), (2, 1, 3)])].into_iter().collect();
+        let num_lonely = super::apply_lonely_seed_policy_heterogenous(
+            LonelySeedPolicy::SelfLoop, &edge_types, &inputs, &mut edge_index, &mut layer_offsets,
+        ).unwrap();
+        assert_eq!(num_lonely, 1);
+
+        let output = HeteroSamplerOutput::new(&edge_types, &inputs, &samples, &edge_index, &layer_offsets);
+        assert_eq!(Vec::<i64>::from(&output.edge[&edge_types[0]]), vec![5, -1]);
+        assert_eq!(Vec::<i64>::from(&output.hop["b"]), vec![0, 0, 1]);
+        assert_eq!(Vec::<i64>::from(&output.batch["b"]), vec![0, 1, 0]);
     }
 
     #[test]
@@ -645,5 +3436,320 @@ mod tests {
                 &num_neighbors[rel_type],
             );
         }
+
+        let mut stats = SamplerStats::new();
+        let (_samples, coo_builders, layer_offsets) = super::neighbor_sampling_heterogenous_traced(
+            &mut rng,
+            &node_types,
+            &edge_types,
+            &graphs,
+            &inputs,
+            &num_neighbors,
+            num_hops,
+            &sampler,
+            &filter,
+            &inputs_state,
+            &mut stats,
+        );
+
+        assert_eq!(stats.hops.len(), num_hops * rel_types.len());
+        let stats_dict = stats.to_hetero_dict();
+        for rel_type in rel_types.iter() {
+            let offsets = &layer_offsets[rel_type];
+            let num_edges = &stats_dict[rel_type]["num_edges"];
+            assert_eq!(num_edges.len(), num_hops);
+            for hop in 0..num_hops {
+                let edges_end = offsets.get(hop + 1).map(|o| o.1).unwrap_or(coo_builders[rel_type].len() as i64);
+                assert_eq!(num_edges[hop] as i64, edges_end - offsets[hop].1);
+                assert_eq!(stats_dict[rel_type]["num_nodes"][hop], num_edges[hop]);
+            }
+        }
+
+        let mut stats = SamplerStats::with_coverage();
+        let (samples, _, _) = super::neighbor_sampling_heterogenous_traced(
+            &mut rng,
+            &node_types,
+            &edge_types,
+            &graphs,
+            &inputs,
+            &num_neighbors,
+            num_hops,
+            &sampler,
+            &filter,
+            &inputs_state,
+            &mut stats,
+        );
+        let coverage = stats.coverage.unwrap();
+        for rel_type in rel_types.iter() {
+            let (_, _, dst_node_type) = &to_edge_types[rel_type];
+            let coverage = &coverage[&Some(rel_type.clone())];
+            for k in 0..coverage.len() {
+                let node = samples[dst_node_type][coverage.node[k] as usize];
+                let fanout = num_neighbors[rel_type][coverage.hop[k] as usize] as i64;
+                assert_eq!(coverage.degree[k], graphs[rel_type].edge_positions(node).len() as i64);
+                assert_eq!(coverage.sampled[k], coverage.degree[k].min(fanout));
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_neighbor_sampling_heterogenous_fanout_schedule() {
+        let (xs, coo_graphs) = load_fake_hetero_graph();
+        let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+
+        let node_types: Vec<NodeType> = xs.keys().cloned().collect();
+        let mut edge_types: Vec<EdgeType> = coo_graphs.keys().cloned().collect();
+        edge_types.sort();
+        let graph_data: HashMap<RelType, CscGraphStorage> = coo_graphs.iter().map(|((src, rel, dst), coo_graph)| {
+            (format!("{}__{}__{}", src, rel, dst), CscGraphStorage::try_from(coo_graph).unwrap())
+        }).collect();
+        let graphs: HashMap<RelType, CscGraph> = graph_data.iter().map(|(rel_type, graph_data)| {
+            (rel_type.clone(), CscGraph::<i64, i64>::try_from(graph_data).unwrap())
+        }).collect();
+
+        let seeds = [0_i64, 1, 4, 5];
+        let inputs: HashMap<NodeType, &[NodeIdx]> = node_types.iter()
+            .map(|node_type| (node_type.clone(), &seeds[..]))
+            .collect();
+        let states = [(); 4];
+        let inputs_state: HashMap<NodeType, &[()]> = node_types.iter()
+            .map(|node_type| (node_type.clone(), &states[..]))
+            .collect();
+        let sampler = graphs.keys().map(|rel_type| (rel_type.clone(), UnweightedSampler::<false>)).collect();
+        let filter = graphs.keys().map(|rel_type| (rel_type.clone(), IdentityFilter)).collect();
+
+        // The first relation takes all neighbors at the first hop and is disabled at the second
+        let (src, rel, dst) = &edge_types[0];
+        let target = format!("{}__{}__{}", src, rel, dst);
+        let num_neighbors: HashMap<RelType, Vec<i64>> = graphs.keys()
+            .map(|rel_type| (rel_type.clone(), if *rel_type == target { vec![-1, 0] } else { vec![4, 3] }))
+            .collect();
+        super::validate_hetero_fanouts(&edge_types, &num_neighbors, 2).unwrap();
+        let fanouts: HashMap<RelType, Vec<FanoutPolicy>> = num_neighbors.iter()
+            .map(|(rel_type, ks)| (rel_type.clone(), ks.iter().map(|k| FanoutPolicy::from_count(*k)).collect()))
+            .collect();
+
+        let (_samples, coo_builders, layer_offsets) = super::neighbor_sampling_heterogenous(
+            &mut rng, &node_types, &edge_types, &graphs, &inputs, &fanouts, 2, &sampler, &filter, &inputs_state,
+        );
+
+        let offsets = &layer_offsets[&target];
+        assert_eq!(offsets.len(), 2);
+        assert_eq!(offsets[1].1, coo_builders[&target].len() as i64);
+        let degree_sum: usize = seeds.iter().map(|v| graphs[&target].in_degree(*v)).sum();
+        assert_eq!((offsets[1].1 - offsets[0].1) as usize, degree_sum);
+        assert!(degree_sum > 0);
+
+        let mut invalid = num_neighbors.clone();
+        invalid.insert(target.clone(), vec![-1]);
+        assert!(super::validate_hetero_fanouts(&edge_types, &invalid, 2).is_err());
+        invalid.insert(target.clone(), vec![-2, 3]);
+        assert!(super::validate_hetero_fanouts(&edge_types, &invalid, 2).is_err());
+        invalid.insert(target, vec![4, 3]);
+        invalid.insert("a__b__c".to_string(), vec![4, 3]);
+        assert!(super::validate_hetero_fanouts(&edge_types, &invalid, 2).is_err());
+    }
+
+    #[test]
+    pub fn test_sampling_coverage() {
+        let (_x, _, coo_graph) = load_karate_graph();
+        let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let inputs = vec![0_i64, 1, 4, 5];
+        let inputs_state = vec![(); inputs.len()];
+        let sample = |stats: &mut SamplerStats, num_neighbors: &[usize]| super::neighbor_sampling_homogenous_traced(
+            &mut rand::rngs::SmallRng::from_seed([0; 32]), &graph, &inputs, num_neighbors,
+            &UnweightedSampler::<false>, &IdentityFilter, &inputs_state, stats,
+        );
+
+        // Opting in doesn't change the samples
+        let mut stats = SamplerStats::new();
+        let (samples, edges, layer_offsets) = sample(&mut stats, &[4, 3]);
+        assert!(stats.coverage.is_none());
+        let mut stats = SamplerStats::with_coverage();
+        let traced = sample(&mut stats, &[4, 3]);
+        assert_eq!((traced.0, traced.1.edge_index, traced.2), (samples.clone(), edges.edge_index.clone(), layer_offsets.clone()));
+
+        // One entry per frontier node, together they account for every sampled edge
+        let coverage = &stats.coverage.as_ref().unwrap()[&None];
+        assert_eq!(coverage.len(), layer_offsets[1].2 as usize);
+        assert_eq!(coverage.sampled.iter().sum::<i64>() as usize, edges.len());
+        for k in 0..coverage.len() {
+            let node = samples[coverage.node[k] as usize];
+            assert_eq!(coverage.degree[k], graph.edge_positions(node).len() as i64);
+            assert_eq!(coverage.sampled[k], coverage.degree[k].min(if coverage.hop[k] == 0 { 4 } else { 3 }));
+        }
+
+        // A fanout exceeding every degree covers every neighborhood
+        let mut stats = SamplerStats::with_coverage();
+        sample(&mut stats, &[1000, 1000]);
+        let coverage = &stats.coverage.as_ref().unwrap()[&None];
+        assert!(!coverage.is_empty());
+        assert_eq!(coverage.sampled, coverage.degree);
+        let (index, sampled, degree) = coverage.to_tensors();
+        assert_eq!(index.size(), vec![2, coverage.len() as i64]);
+        assert_eq!(Vec::<i64>::from(index.select(0, 1)), coverage.hop);
+        assert_eq!(sampled, degree);
+
+        // The hub of a star covers fanout / degree of its neighborhood, the leaves all of it
+        let leaves: Vec<i64> = (1..=10).collect();
+        let row_col = Tensor::of_slice(&[leaves.clone(), vec![0; 10]].concat()).view([2, 10]);
+        let star = CooGraphStorage::new(Tensor::cat(&[row_col.shallow_clone(), row_col.flip(&[0])], 1), (11, 11));
+        let star_data = CscGraphStorage::try_from(&star).unwrap();
+        let star = CscGraph::<i64, i64>::try_from(&star_data).unwrap();
+        let mut stats = SamplerStats::with_coverage();
+        let _ = super::neighbor_sampling_homogenous_traced(
+            &mut rand::rngs::SmallRng::from_seed([0; 32]), &star, &[0, 3], &[3],
+            &UnweightedSampler::<false>, &IdentityFilter, &[(); 2], &mut stats,
+        );
+        assert_eq!(stats.coverage.unwrap()[&None], Coverage {
+            node: vec![0, 1],
+            hop: vec![0, 0],
+            sampled: vec![3, 1],
+            degree: vec![10, 1],
+        });
+    }
+
+    #[test]
+    pub fn test_lonely_seed_policy() {
+        // Node 2 is isolated, the only edge into node 3 is outside of the time window
+        let coo = CooGraphStorage::new(Tensor::of_slice(&[1_i64, 0, 0, 0, 1, 3]).view([2, 3]), (4, 4));
+        let graph_data = CscGraphStorage::try_from(&coo).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let timestamps_data = [0_i64, 0, 5];
+        let source = EdgeAttrSource::new(&graph).with_times(&timestamps_data);
+        let filter = TemporalFilter::<_, false, TEMPORAL_SAMPLE_STATIC>::new(0..=2, &source);
+        let inputs = vec![0_i64, 2, 3];
+        let num_neighbors = [2, 2];
+        let sample = |dedup: bool| {
+            let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+            if dedup {
+                super::neighbor_sampling_homogenous_dedup(
+                    &mut rng, &graph, &inputs, &num_neighbors, &UnweightedSampler::<false>, &filter, &[0; 3],
+                )
+            } else {
+                super::neighbor_sampling_homogenous(
+                    &mut rng, &graph, &inputs, &num_neighbors, &UnweightedSampler::<false>, &filter, &[0; 3],
+                )
+            }
+        };
+
+        let (_, mut edges, mut layer_offsets) = sample(false);
+        let (num_edges, offsets) = (edges.len(), layer_offsets.clone());
+        let num_lonely = super::apply_lonely_seed_policy(
+            LonelySeedPolicy::Keep, &inputs, &mut edges, &mut layer_offsets, false,
+        ).unwrap();
+        assert_eq!(num_lonely, 2);
+        assert_eq!((edges.len(), layer_offsets), (num_edges, offsets));
+
+        for dedup in [false, true] {
+            let (samples, mut edges, mut layer_offsets) = sample(dedup);
+            let hop_edges = layer_offsets[1].1 as usize;
+            assert_eq!(super::apply_lonely_seed_policy(
+                LonelySeedPolicy::SelfLoop, &inputs, &mut edges, &mut layer_offsets, false,
+            ).unwrap(), 2);
+            // The self loops close the first hop
+            assert_eq!(layer_offsets[1].1 as usize, hop_edges + 2);
+            let loops: Vec<(i64, i64, i64)> = (hop_edges..hop_edges + 2)
+                .map(|e| (edges.rows[e], edges.cols[e], edges.edge_index[e]))
+                .collect();
+            assert_eq!(loops, vec![(1, 1, -1), (2, 2, -1)]);
+            for e in (0..edges.len()).filter(|e| edges.edge_index[*e] != -1) {
+                assert_eq!(graph.get_by_ptr(edges.edge_index[e] as usize), samples[edges.rows[e] as usize]);
+            }
+        }
+
+        let (_, mut edges, mut layer_offsets) = sample(false);
+        let err = super::apply_lonely_seed_policy(
+            LonelySeedPolicy::Error, &inputs, &mut edges, &mut layer_offsets, false,
+        ).unwrap_err();
+        assert!(err.to_string().contains("[2, 3]"), "{}", err);
+        assert!(super::apply_lonely_seed_policy(
+            LonelySeedPolicy::Error, &[0], &mut edges, &mut layer_offsets, false,
+        ).is_ok());
+
+        // Bipartite samples can't hold self loops
+        assert!(LonelySeedPolicy::SelfLoop.validate(true).is_err());
+        assert!(LonelySeedPolicy::Error.validate(true).is_ok());
+
+        let mut stats = SamplerStats::new();
+        super::SamplingTracer::record_lonely_seeds(&mut stats, num_lonely);
+        assert_eq!(stats.to_dict()["num_lonely_seeds"], vec![2]);
+    }
+
+    #[test]
+    pub fn test_lonely_seed_policy_heterogenous() {
+        // Paper 0 is only cited and paper 1 only written, paper 2 and author 0 have no neighbors at all
+        let edge_types: Vec<EdgeType> = vec![
+            ("author".to_string(), "writes".to_string(), "paper".to_string()),
+            ("paper".to_string(), "cites".to_string(), "paper".to_string()),
+            ("paper".to_string(), "written_by".to_string(), "author".to_string()),
+        ];
+        let node_types: Vec<NodeType> = vec!["author".to_string(), "paper".to_string()];
+        let coos = [
+            CooGraphStorage::new(Tensor::of_slice(&[0_i64, 1]).view([2, 1]), (1, 3)),
+            CooGraphStorage::new(Tensor::of_slice(&[1_i64, 0]).view([2, 1]), (3, 3)),
+            CooGraphStorage::new(Tensor::of_slice(&[0_i64; 0]).view([2, 0]), (3, 1)),
+        ];
+        let graph_data: Vec<CscGraphStorage> = coos.iter().map(|coo| CscGraphStorage::try_from(coo).unwrap()).collect();
+        let rel_types: Vec<RelType> = edge_types.iter().map(|(src, rel, dst)| format!("{}__{}__{}", src, rel, dst)).collect();
+        let graphs: HashMap<RelType, CscGraph> = rel_types.iter().zip(&graph_data)
+            .map(|(rel_type, graph_data)| (rel_type.clone(), CscGraph::<i64, i64>::try_from(graph_data).unwrap()))
+            .collect();
+        let sampler = graphs.keys().map(|rel_type| (rel_type.clone(), UnweightedSampler::<false>)).collect();
+        let filter = graphs.keys().map(|rel_type| (rel_type.clone(), IdentityFilter)).collect();
+        let fanouts: HashMap<RelType, Vec<usize>> = graphs.keys().map(|rel_type| (rel_type.clone(), vec![2, 2])).collect();
+
+        let (papers, authors) = ([0_i64, 1, 2], [0_i64]);
+        let states = [(); 3];
+        let sample = |with_authors: bool| {
+            let mut inputs: HashMap<NodeType, &[NodeIdx]> = HashMap::new();
+            inputs.insert("paper".to_string(), &papers[..]);
+            let mut inputs_state: HashMap<NodeType, &[()]> = HashMap::new();
+            inputs_state.insert("paper".to_string(), &states[..]);
+            if with_authors {
+                inputs.insert("author".to_string(), &authors[..]);
+                inputs_state.insert("author".to_string(), &states[..1]);
+            }
+            let (_, edges, layer_offsets) = super::neighbor_sampling_heterogenous(
+                &mut rand::rngs::SmallRng::from_seed([0; 32]), &node_types, &edge_types, &graphs, &inputs, &fanouts, 2,
+                &sampler, &filter, &inputs_state,
+            );
+            (inputs, edges, layer_offsets)
+        };
+
+        // Seeds with an edge in any relation into their node type aren't lonely
+        let (inputs, mut edges, mut layer_offsets) = sample(true);
+        let num_edges = |edges: &HashMap<RelType, CooGraphBuilder>| -> HashMap<RelType, usize> {
+            edges.iter().map(|(rel_type, edges)| (rel_type.clone(), edges.len())).collect()
+        };
+        let expected = (num_edges(&edges), layer_offsets.clone());
+        assert_eq!(super::apply_lonely_seed_policy_heterogenous(
+            LonelySeedPolicy::Keep, &edge_types, &inputs, &mut edges, &mut layer_offsets,
+        ).unwrap(), 2);
+        assert_eq!((num_edges(&edges), layer_offsets.clone()), expected);
+
+        let err = super::apply_lonely_seed_policy_heterogenous(
+            LonelySeedPolicy::Error, &edge_types, &inputs, &mut edges, &mut layer_offsets,
+        ).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("author") && message.find("[0]") < message.find("[2]"), "{}", message);
+
+        // Authors have no relation onto themselves to hold their self loops
+        assert!(super::apply_lonely_seed_policy_heterogenous(
+            LonelySeedPolicy::SelfLoop, &edge_types, &inputs, &mut edges, &mut layer_offsets,
+        ).is_err());
+        assert!(LonelySeedPolicy::SelfLoop.validate_heterogenous(&edge_types, &inputs, |_| true).is_err());
+
+        let (inputs, mut edges, mut layer_offsets) = sample(false);
+        let cites = &rel_types[1];
+        let hop_edges = layer_offsets[cites][1].1 as usize;
+        assert_eq!(super::apply_lonely_seed_policy_heterogenous(
+            LonelySeedPolicy::SelfLoop, &edge_types, &inputs, &mut edges, &mut layer_offsets,
+        ).unwrap(), 1);
+        let cited = &edges[cites];
+        assert_eq!(layer_offsets[cites][1].1 as usize, hop_edges + 1);
+        assert_eq!((cited.rows[hop_edges], cited.cols[hop_edges], cited.edge_index[hop_edges]), (2, 2, -1));
+        assert!(edges[&rel_types[0]].edge_index.iter().all(|e| *e != -1));
     }
 }
\ No newline at end of file
@@ -0,0 +1,141 @@
+use std::cmp::Ordering;
+use tch::{Kind, Tensor};
+use crate::utils::{NodeIdx, TensorConversionError, TensorResult, try_tensor_to_slice};
+
+pub fn batch_nodes(batch: &[i64]) -> Vec<Vec<usize>> {
+    let num_graphs = batch.iter().max().map_or(0, |b| b + 1) as usize;
+    let mut nodes = vec![Vec::new(); num_graphs];
+    for (i, b) in batch.iter().enumerate() {
+        nodes[*b as usize].push(i);
+    }
+    nodes
+}
+
+pub fn topk_pool(
+    scores: &Tensor,
+    batch: &Tensor,
+    ratio: f64,
+) -> TensorResult<(Tensor, Tensor)> {
+    if ratio.is_nan() || ratio <= 0.0 {
+        return Err(TensorConversionError::Unknown(format!("ratio must be positive, got {}", ratio)));
+    }
+
+    let scores = scores.to_kind(Kind::Double);
+    let scores_data = try_tensor_to_slice::<f64>(&scores)?;
+    let batch_data = try_tensor_to_slice::<i64>(batch)?;
+    if scores_data.len() != batch_data.len() {
+        return Err(TensorConversionError::InvalidShape(Some("scores and batch must have the same length".to_string())));
+    }
+
+    let mut perm: Vec<NodeIdx> = Vec::new();
+    let mut perm_batch: Vec<i64> = Vec::new();
+    for (b, mut nodes) in batch_nodes(batch_data).into_iter().enumerate() {
+        if nodes.is_empty() {
+            continue;
+        }
+
+        // Every graph keeps at least one node. Equal scores keep the lower node index first.
+        let k = ((ratio * nodes.len() as f64).ceil() as usize).clamp(1, nodes.len());
+        nodes.sort_by(|&i, &j| scores_data[j].partial_cmp(&scores_data[i]).unwrap_or(Ordering::Equal));
+
+        perm.extend(nodes[..k].iter().map(|&i| i as NodeIdx));
+        perm_batch.resize(perm_batch.len() + k, b as i64);
+    }
+
+    Ok((Tensor::of_slice(&perm), Tensor::of_slice(&perm_batch)))
+}
+
+pub fn sort_pool(
+    x: &Tensor,
+    batch: &Tensor,
+    k: i64,
+) -> TensorResult<Tensor> {
+    if k <= 0 {
+        return Err(TensorConversionError::Unknown(format!("k must be positive, got {}", k)));
+    }
+
+    let size = x.size();
+    if size.len() != 2 {
+        return Err(TensorConversionError::InvalidShape(Some("x must be of shape [num_nodes, num_features]".to_string())));
+    }
+    let (num_nodes, num_features) = (size[0], size[1]);
+
+    let batch_data = try_tensor_to_slice::<i64>(batch)?;
+    if batch_data.len() != num_nodes as usize {
+        return Err(TensorConversionError::InvalidShape(Some("x and batch must have the same length".to_string())));
+    }
+
+    let last = if num_features > 0 {
+        x.select(1, num_features - 1).to_kind(Kind::Double).contiguous()
+    } else {
+        Tensor::zeros(&[num_nodes], (Kind::Double, x.device()))
+    };
+    let last_data = try_tensor_to_slice::<f64>(&last)?;
+
+    // Graphs with more than k nodes are truncated, smaller graphs are padded with the zero row at index num_nodes
+    let graphs = batch_nodes(batch_data);
+    let mut index: Vec<i64> = Vec::with_capacity(graphs.len() * k as usize);
+    for mut nodes in graphs {
+        nodes.sort_by(|&i, &j| last_data[j].partial_cmp(&last_data[i]).unwrap_or(Ordering::Equal));
+        nodes.truncate(k as usize);
+
+        index.extend(nodes.iter().map(|&i| i as i64));
+        index.resize(index.len() + k as usize - nodes.len(), num_nodes);
+    }
+
+    let num_graphs = (index.len() as i64) / k;
+    let padding = Tensor::zeros(&[1, num_features], (x.kind(), x.device()));
+    let out = Tensor::cat(&[x, &padding], 0)
+        .index_select(0, &Tensor::of_slice(&index).to_device(x.device()));
+
+    Ok(out.view([num_graphs, k * num_features]))
+}
+
+#[cfg(test)]
+mod tests {
+    use tch::Tensor;
+    use crate::utils::NodeIdx;
+
+    #[test]
+    fn test_topk_pool() {
+        let scores = Tensor::of_slice(&[0.1_f32, 0.9, 0.5, 0.3, 0.2, 0.8, 0.8, 0.0, 0.4, 1.0]);
+        let batch = Tensor::of_slice(&[0_i64, 0, 0, 0, 1, 1, 1, 1, 1, 2]);
+
+        let (perm, perm_batch) = super::topk_pool(&scores, &batch, 0.5).unwrap();
+        let perm: Vec<NodeIdx> = perm.into();
+        let perm_batch: Vec<i64> = perm_batch.into();
+
+        // ceil(0.5 * 4) = 2, ceil(0.5 * 5) = 3 and the single node graph keeps its node
+        assert_eq!(perm, vec![1, 2, 5, 6, 8, 9]);
+        assert_eq!(perm_batch, vec![0, 0, 1, 1, 1, 2]);
+
+        let (perm, _) = super::topk_pool(&scores, &batch, 0.01).unwrap();
+        let perm: Vec<NodeIdx> = perm.into();
+        assert_eq!(perm, vec![1, 5, 9]);
+
+        assert!(super::topk_pool(&scores, &batch, 0.0).is_err());
+    }
+
+    #[test]
+    fn test_sort_pool() {
+        let x = Tensor::of_slice(&[
+            1.0_f32, 0.5,
+            2.0, 0.9,
+            3.0, 0.1,
+            4.0, 0.9,
+            5.0, 0.2,
+        ]).view([5, 2]);
+        let batch = Tensor::of_slice(&[0_i64, 0, 0, 0, 1]);
+
+        let out = super::sort_pool(&x, &batch, 3).unwrap();
+        assert_eq!(out.size(), vec![2, 6]);
+        let out: Vec<f32> = out.view([-1]).into();
+
+        // Node 1 and 3 tie on the last channel and keep their order, node 2 is truncated
+        assert_eq!(&out[..6], &[2.0, 0.9, 4.0, 0.9, 1.0, 0.5]);
+        // The single node graph is padded with zeros
+        assert_eq!(&out[6..], &[5.0, 0.2, 0.0, 0.0, 0.0, 0.0]);
+
+        assert!(super::sort_pool(&x, &batch, 0).is_err());
+    }
+}
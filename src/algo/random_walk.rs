@@ -1,15 +1,46 @@
-use rand::{Rng};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use rand::Rng;
 use rand::rngs::SmallRng;
-use tch::{Kind, Scalar, Tensor};
-use crate::data::EdgeAttr;
-use crate::data::graph::CsrGraph;
-use crate::utils::{DefaultIx, reservoir_sampling, reservoir_sampling_weighted};
+use rayon::prelude::*;
+use tch::{Device, Kind, Scalar, Tensor};
+use crate::data::{EdgeAttr, SparseGraph, TypedGraphStorage};
+use crate::data::graph::NeighborSource;
+use crate::utils::{AliasTable, DefaultIx, NodeIdx, TensorConversionError, reservoir_sampling, reservoir_sampling_weighted};
+use crate::utils::parallel;
+use crate::utils::random::RngPool;
 use crate::utils::tensor::{TensorResult, try_tensor_to_slice, try_tensor_to_slice_mut};
 
+// Uniform step to a random neighbor, none for nodes without outgoing edges
+pub fn uniform_step<G: NeighborSource + ?Sized>(
+    rng: &mut impl Rng,
+    graph: &G,
+    cur: NodeIdx,
+) -> Option<NodeIdx> {
+    let neighbors = graph.neighbors(cur);
+    if neighbors.is_empty() {
+        None
+    } else {
+        Some(neighbors[rng.gen_range(0..neighbors.len())])
+    }
+}
+
+// Errors on the first start node that is not a node of the graph, naming both the node and its position
+fn check_start(start: &[NodeIdx], node_count: usize) -> TensorResult<()> {
+    match start.iter().enumerate().find(|(_, &v)| v < 0 || v as usize >= node_count) {
+        Some((i, v)) => Err(TensorConversionError::Unknown(format!(
+            "start node {} at index {} is out of bounds for a graph with {} nodes", v, i, node_count
+        ))),
+        None => Ok(()),
+    }
+}
+
 #[allow(non_snake_case)]
-pub fn random_walk(
+pub fn random_walk<G: NeighborSource + ?Sized>(
     rng: &mut SmallRng,
-    graph: &CsrGraph,
+    graph: &G,
     start: &Tensor,
     walk_length: i64,
     p: f32,
@@ -22,12 +53,15 @@ pub fn random_walk(
         (Kind::Int64, start.device()),
     );
 
+    if !(p > 0.0 && p.is_finite() && q > 0.0 && q.is_finite()) {
+        return Err(TensorConversionError::Unknown(format!("p and q must be positive, got p = {} and q = {}", p, q)));
+    }
     let start_data = try_tensor_to_slice::<i64>(start)?;
+    check_start(start_data, graph.node_count())?;
     let walks_data = try_tensor_to_slice_mut::<i64>(&mut walks)?;
 
     // Normalize the weights to compute rejection probabilities
-    let max_prob = vec![1.0 / p, 1.0, 1.0 / q].into_iter()
-        .max_by(|a, b| a.partial_cmp(b).expect("p or q may not be 0 or nan")).unwrap();
+    let max_prob = (1.0 / p).max(1.0).max(1.0 / q);
     // rejection prob for back to the previous node
     let prob0 = 1.0 / p / max_prob;
     // rejection prob for visiting the node with the distance of 1 between the previous node
@@ -41,7 +75,7 @@ pub fn random_walk(
         walks_data[i * L] = cur;
 
         for l in 0..walk_length as usize {
-            let neighbors = graph.neighbors_slice(cur);
+            let neighbors = graph.neighbors(cur);
             if neighbors.is_empty() {
                 break;
             }
@@ -74,12 +108,68 @@ pub fn random_walk(
     Ok(walks)
 }
 
+// Uniform walks that stop at nodes without outgoing edges, padded with -1. Returns the walks [S, walk_length + 1]
+// and the traversed edges [S, walk_length], edge ids when the perm of the csr storage is given and edge positions
+// otherwise. Walk i draws from stream i of `RngPool::new(seed)`.
+#[allow(non_snake_case)]
+pub fn uniform_random_walk<G: NeighborSource + Sync + ?Sized>(
+    graph: &G,
+    start: &Tensor,
+    walk_length: i64,
+    seed: u64,
+    perm: Option<&[i64]>,
+) -> TensorResult<(Tensor, Tensor)> {
+    let start_data = try_tensor_to_slice::<i64>(start)?;
+    check_start(start_data, graph.node_count())?;
+    if let Some(perm) = perm {
+        let num_positions = (0..graph.node_count() as NodeIdx).map(|v| graph.edge_positions(v).end).max().unwrap_or(0);
+        if perm.len() < num_positions {
+            return Err(TensorConversionError::InvalidShape(Some(format!(
+                "perm must cover {} edge positions, got {}", num_positions, perm.len()
+            ))));
+        }
+    }
+
+    let L = walk_length.max(0) as usize;
+    let mut walks = Tensor::full(&[start_data.len() as i64, L as i64 + 1], -1_i64, (Kind::Int64, start.device()));
+    let mut edges = Tensor::full(&[start_data.len() as i64, L as i64], -1_i64, (Kind::Int64, start.device()));
+    if L == 0 {
+        return Ok((start.view([-1, 1]).copy(), edges));
+    }
+    let walks_data = try_tensor_to_slice_mut::<i64>(&mut walks)?;
+    let edges_data = try_tensor_to_slice_mut::<i64>(&mut edges)?;
+
+    let pool = RngPool::new(seed);
+    parallel::install(|| walks_data.par_chunks_mut(L + 1)
+        .zip(edges_data.par_chunks_mut(L))
+        .zip(start_data.par_iter())
+        .enumerate()
+        .for_each(|(i, ((walk, walk_edges), n))| {
+            let mut rng = pool.stream(&[i as u64]);
+            let mut cur = *n;
+            walk[0] = cur;
+
+            for l in 0..L {
+                let neighbors = graph.edge_positions(cur);
+                if neighbors.is_empty() {
+                    break;
+                }
+                let edge_ptr = rng.gen_range(neighbors);
+                cur = graph.neighbor_at(edge_ptr);
+                walk[l + 1] = cur;
+                walk_edges[l] = perm.map_or(edge_ptr as i64, |perm| perm[edge_ptr]);
+            }
+        }));
+
+    Ok((walks, edges))
+}
+
 const NAN_TIMESTAMP: i64 = -1_i64;
 
 #[allow(non_snake_case)]
-pub fn tempo_random_walk(
+pub fn tempo_random_walk<G: NeighborSource + ?Sized>(
     rng: &mut SmallRng,
-    graph: &CsrGraph,
+    graph: &G,
     node_timestamps: &[i64],
     edge_timestamps: &EdgeAttr<i64>,
     start: &Tensor,
@@ -115,8 +205,8 @@ pub fn tempo_random_walk(
 
 
         for l in 0..(walk_length - 1) as usize {
-            let neighbors = edge_timestamps.get_range(graph.neighbors_range(cur)).into_iter()
-                .zip(graph.neighbors_slice(cur))
+            let neighbors = edge_timestamps.get_range(graph.edge_positions(cur)).into_iter()
+                .zip(graph.neighbors(cur))
                 .map(|(&edge_timestamp, &node_idx)| {
                     let node_timestamp = if edge_timestamp != NAN_TIMESTAMP {
                         edge_timestamp
@@ -182,9 +272,9 @@ impl BiasType {
 }
 
 #[allow(non_snake_case)]
-pub fn biased_tempo_random_walk(
+pub fn biased_tempo_random_walk<G: NeighborSource + ?Sized>(
     rng: &mut SmallRng,
-    graph: &CsrGraph,
+    graph: &G,
     node_timestamps: &[i64],
     edge_timestamps: &EdgeAttr<i64>,
     start: &Tensor,
@@ -224,8 +314,8 @@ pub fn biased_tempo_random_walk(
             }
 
             for l in 0..(walk_length - 1) as usize {
-                let neighbors_iter = edge_timestamps.get_range(graph.neighbors_range(cur)).into_iter()
-                    .zip(graph.neighbors_slice(cur))
+                let neighbors_iter = edge_timestamps.get_range(graph.edge_positions(cur)).into_iter()
+                    .zip(graph.neighbors(cur))
                     .map(|(&edge_timestamp, &node_idx)| {
                         let node_timestamp = if edge_timestamp != NAN_TIMESTAMP {
                             edge_timestamp
@@ -288,13 +378,301 @@ pub fn biased_tempo_random_walk(
 }
 
 
+pub enum TeleportSet<'a> {
+    // Jumps back to the start node of the walk
+    Start,
+    // Jumps to a uniformly drawn node of the set
+    UniformOver(&'a Tensor),
+    // Jumps to a node of the set drawn proportionally to its weight
+    WeightedOver(&'a Tensor, &'a Tensor),
+}
+
+// Walks that jump with probability r, or if there are no neighbors, to a node of the teleport set. Returns the
+// walks [S, walk_length + 1] and the traversed edge positions [S, walk_length], where jumps are -1. Walk i draws
+// from stream i of `RngPool::new(seed)`, so the result does not depend on the number of threads.
+#[allow(non_snake_case)]
+pub fn random_walk_with_teleport<G: NeighborSource + Sync + ?Sized>(
+    graph: &G,
+    start: &Tensor,
+    walk_length: i64,
+    r: f64,
+    teleport: TeleportSet,
+    seed: u64,
+) -> TensorResult<(Tensor, Tensor)> {
+    if !(0.0..=1.0).contains(&r) {
+        return Err(TensorConversionError::Unknown(format!("teleport probability must be in [0, 1], got {}", r)));
+    }
+    let n = graph.node_count();
+    let check_nodes = |nodes: &[NodeIdx]| match nodes.iter().find(|&&v| v < 0 || v as usize >= n) {
+        Some(v) => Err(TensorConversionError::Unknown(format!("node {} is out of bounds", v))),
+        None => Ok(()),
+    };
+
+    let start_data = try_tensor_to_slice::<i64>(start)?;
+    check_nodes(start_data)?;
+    let (targets, alias) = match &teleport {
+        TeleportSet::Start => (&[][..], None),
+        TeleportSet::UniformOver(nodes) | TeleportSet::WeightedOver(nodes, _) => {
+            let targets = try_tensor_to_slice::<i64>(nodes)?;
+            check_nodes(targets)?;
+            if targets.is_empty() {
+                return Err(TensorConversionError::Unknown("teleport set can not be empty".to_string()));
+            }
+            let alias = match &teleport {
+                TeleportSet::WeightedOver(_, weights) => {
+                    if weights.numel() != targets.len() {
+                        return Err(TensorConversionError::InvalidShape(Some(format!(
+                            "teleport weights must have {} values, got {}", targets.len(), weights.numel()
+                        ))));
+                    }
+                    let weights = weights.to_kind(Kind::Double).contiguous();
+                    Some(AliasTable::new(try_tensor_to_slice::<f64>(&weights)?).ok_or_else(|| {
+                        TensorConversionError::Unknown("teleport weights must be non negative with a positive sum".to_string())
+                    })?)
+                }
+                _ => None,
+            };
+            (targets, alias)
+        }
+    };
+
+    let L = walk_length as usize;
+    let mut walks = Tensor::full(&[start_data.len() as i64, L as i64 + 1], -1_i64, (Kind::Int64, start.device()));
+    let mut edges = Tensor::full(&[start_data.len() as i64, L as i64], -1_i64, (Kind::Int64, start.device()));
+    if L == 0 {
+        return Ok((start.view([-1, 1]).copy(), edges));
+    }
+    let walks_data = try_tensor_to_slice_mut::<i64>(&mut walks)?;
+    let edges_data = try_tensor_to_slice_mut::<i64>(&mut edges)?;
+
+    let pool = RngPool::new(seed);
+    let teleport_start = matches!(teleport, TeleportSet::Start);
+    parallel::install(|| walks_data.par_chunks_mut(L + 1)
+        .zip(edges_data.par_chunks_mut(L))
+        .zip(start_data.par_iter())
+        .enumerate()
+        .for_each(|(i, ((walk, walk_edges), n))| {
+            let mut rng = pool.stream(&[i as u64]);
+            let mut cur = *n;
+            walk[0] = cur;
+
+            for l in 0..L {
+                let neighbors = graph.edge_positions(cur);
+                if neighbors.is_empty() || rng.gen_bool(r) {
+                    cur = match &alias {
+                        _ if teleport_start => *n,
+                        Some(alias) => targets[alias.sample(&mut rng)],
+                        None => targets[rng.gen_range(0..targets.len())],
+                    };
+                } else {
+                    let edge_ptr = rng.gen_range(neighbors);
+                    cur = graph.neighbor_at(edge_ptr);
+                    walk_edges[l] = edge_ptr as i64;
+                }
+                walk[l + 1] = cur;
+            }
+        }));
+
+    Ok((walks, edges))
+}
+
+// Walks where the type of every step has to be allowed after the type of the previous step, `allowed` is a
+// boolean [T, T] matrix with allowed[prev, next]. The first step takes any type, or the types allowed after
+// `initial_type`. Candidates are drawn uniformly from the union of the allowed per type sub-ranges, walks without
+// an allowed transition stop early and are padded with -1. Also returns the edge type of every step. Walk i draws
+// from stream i of `RngPool::new(seed)`.
+#[allow(non_snake_case)]
+pub fn typed_random_walk<Ty: Sync>(
+    graph: &TypedGraphStorage<Ty>,
+    start: &Tensor,
+    walk_length: i64,
+    allowed: &Tensor,
+    initial_type: Option<i64>,
+    seed: u64,
+) -> TensorResult<(Tensor, Tensor)> {
+    let num_types = graph.num_types as usize;
+    if allowed.size() != vec![graph.num_types, graph.num_types] {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "allowed must be of shape [{}, {}], got {:?}", num_types, num_types, allowed.size()
+        ))));
+    }
+    if let Some(t) = initial_type.filter(|t| !(0..graph.num_types).contains(t)) {
+        return Err(TensorConversionError::Unknown(format!("initial type {} is out of bounds", t)));
+    }
+
+    let allowed = allowed.to_kind(Kind::Bool).contiguous();
+    let allowed_data = try_tensor_to_slice::<bool>(&allowed)?;
+    let allowed_next: Vec<Vec<usize>> = allowed_data.chunks(num_types.max(1))
+        .map(|row| (0..num_types).filter(|t| row[*t]).collect())
+        .collect();
+    let any_type: Vec<usize> = (0..num_types).collect();
+
+    let view = SparseGraph::<Ty, i64, i64>::try_from(&graph.graph)?;
+    let type_ptrs = try_tensor_to_slice::<i64>(&graph.type_ptrs)?;
+    let start_data = try_tensor_to_slice::<i64>(start)?;
+    if let Some(v) = start_data.iter().find(|&&v| !(0..view.node_count() as NodeIdx).contains(&v)) {
+        return Err(TensorConversionError::Unknown(format!("node {} is out of bounds", v)));
+    }
+
+    let L = walk_length.max(0) as usize;
+    let mut walks = Tensor::full(&[start_data.len() as i64, L as i64 + 1], -1_i64, (Kind::Int64, start.device()));
+    let mut types = Tensor::full(&[start_data.len() as i64, L as i64], -1_i64, (Kind::Int64, start.device()));
+    if L == 0 {
+        return Ok((start.view([-1, 1]).copy(), types));
+    }
+    let walks_data = try_tensor_to_slice_mut::<i64>(&mut walks)?;
+    let types_data = try_tensor_to_slice_mut::<i64>(&mut types)?;
+
+    let pool = RngPool::new(seed);
+    parallel::install(|| walks_data.par_chunks_mut(L + 1)
+        .zip(types_data.par_chunks_mut(L))
+        .zip(start_data.par_iter())
+        .enumerate()
+        .for_each(|(i, ((walk, walk_types), n))| {
+            let mut rng = pool.stream(&[i as u64]);
+            let mut cur = *n;
+            let mut candidates = match initial_type {
+                Some(t) => &allowed_next[t as usize],
+                None => &any_type,
+            };
+            walk[0] = cur;
+
+            for l in 0..L {
+                let ranges = candidates.iter().map(|t| {
+                    let offset = cur as usize * num_types + t;
+                    (*t, type_ptrs[offset] as usize..type_ptrs[offset + 1] as usize)
+                });
+                let total: usize = ranges.clone().map(|(_, r)| r.len()).sum();
+                if total == 0 {
+                    break;
+                }
+
+                let mut j = rng.gen_range(0..total);
+                let (t, edge_ptr) = ranges
+                    .filter(|(_, r)| !r.is_empty())
+                    .find_map(|(t, r)| if j < r.len() { Some((t, r.start + j)) } else { j -= r.len(); None })
+                    .unwrap();
+                cur = view.neighbor_at(edge_ptr);
+                walk[l + 1] = cur;
+                walk_types[l] = t as i64;
+                candidates = &allowed_next[t];
+            }
+        }));
+
+    Ok((walks, types))
+}
+
+fn walk_rows(walks: &Tensor) -> TensorResult<(Tensor, usize)> {
+    if walks.dim() != 2 {
+        return Err(TensorConversionError::InvalidShape(Some(format!("walks must be of shape [W, L], got {:?}", walks.size()))));
+    }
+    Ok((walks.contiguous(), walks.size()[1] as usize))
+}
+
+// Unique walks in order of first occurrence, together with how often each of them occurs
+pub fn dedup_walks(walks: &Tensor) -> TensorResult<(Tensor, Tensor)> {
+    let (walks_data, width) = walk_rows(walks)?;
+    let walks_data = try_tensor_to_slice::<i64>(&walks_data)?;
+    if width == 0 {
+        let count = walks.size()[0].min(1);
+        return Ok((walks.narrow(0, 0, count), Tensor::full(&[count], walks.size()[0], (Kind::Int64, walks.device()))));
+    }
+
+    // Only hashing runs in parallel, grouping compares the rows themselves so collisions are harmless
+    let hashes: Vec<u64> = parallel::install(|| walks_data.par_chunks(width)
+        .map(|row| {
+            let mut hasher = DefaultHasher::new();
+            row.hash(&mut hasher);
+            hasher.finish()
+        })
+        .collect());
+
+    let mut groups: HashMap<u64, Vec<usize>> = HashMap::new();
+    let mut unique: Vec<usize> = Vec::new();
+    let mut counts: Vec<i64> = Vec::new();
+    for (i, (row, hash)) in walks_data.chunks(width).zip(hashes.iter()).enumerate() {
+        let group = groups.entry(*hash).or_default();
+        match group.iter().find(|&&u| &walks_data[unique[u] * width..(unique[u] + 1) * width] == row) {
+            Some(u) => counts[*u] += 1,
+            None => {
+                group.push(unique.len());
+                unique.push(i);
+                counts.push(1);
+            }
+        }
+    }
+
+    let mut unique_data = Vec::with_capacity(unique.len() * width);
+    for i in unique.iter() {
+        unique_data.extend_from_slice(&walks_data[i * width..(i + 1) * width]);
+    }
+    Ok((
+        Tensor::of_slice(&unique_data).view([unique.len() as i64, width as i64]).to_device(walks.device()),
+        Tensor::of_slice(&counts).to_device(walks.device()),
+    ))
+}
+
+// Drops the trailing `pad_value`s of every walk, returns (lengths [W], values, offsets [W + 1])
+pub fn trim_padded(walks: &Tensor, pad_value: i64) -> TensorResult<(Tensor, Tensor, Tensor)> {
+    let (walks_data, width) = walk_rows(walks)?;
+    let walks_data = try_tensor_to_slice::<i64>(&walks_data)?;
+    let num_walks = walks.size()[0] as usize;
+
+    let lengths: Vec<i64> = if width == 0 {
+        vec![0; num_walks]
+    } else {
+        parallel::install(|| walks_data.par_chunks(width)
+            .map(|row| row.iter().rposition(|v| *v != pad_value).map_or(0, |p| p as i64 + 1))
+            .collect())
+    };
+
+    let mut offsets = Vec::with_capacity(num_walks + 1);
+    offsets.push(0_i64);
+    let mut values = Vec::new();
+    for (i, length) in lengths.iter().enumerate() {
+        values.extend_from_slice(&walks_data[i * width..i * width + *length as usize]);
+        offsets.push(values.len() as i64);
+    }
+
+    let device = walks.device();
+    Ok((
+        Tensor::of_slice(&lengths).to_device(device),
+        Tensor::of_slice(&values).to_device(device),
+        Tensor::of_slice(&offsets).to_device(device),
+    ))
+}
+
+// Inverse of `trim_padded`, pads the packed walks back to `width` columns
+pub fn pad_packed(values: &Tensor, offsets: &Tensor, width: i64, pad_value: i64) -> TensorResult<Tensor> {
+    let values_data = values.contiguous();
+    let values_data = try_tensor_to_slice::<i64>(&values_data)?;
+    let offsets_data = offsets.contiguous();
+    let offsets_data = try_tensor_to_slice::<i64>(&offsets_data)?;
+    if offsets_data.is_empty() || offsets_data[offsets_data.len() - 1] as usize != values_data.len() {
+        return Err(TensorConversionError::Unknown("offsets must end at the number of values".to_string()));
+    }
+
+    let num_walks = offsets_data.len() - 1;
+    let mut walks = Tensor::full(&[num_walks as i64, width], pad_value, (Kind::Int64, Device::Cpu));
+    let walks_data = try_tensor_to_slice_mut::<i64>(&mut walks)?;
+    for (i, w) in offsets_data.windows(2).enumerate() {
+        let (start, end) = (w[0] as usize, w[1] as usize);
+        if end < start || end - start > width as usize {
+            return Err(TensorConversionError::Unknown(format!("walk {} does not fit into {} columns", i, width)));
+        }
+        walks_data[i * width as usize..i * width as usize + end - start].copy_from_slice(&values_data[start..end]);
+    }
+
+    Ok(walks.to_device(values.device()))
+}
+
 #[cfg(test)]
 mod tests {
     use std::convert::{TryFrom};
     use rand::{Rng, SeedableRng};
-    use tch::Tensor;
-    use crate::algo::random_walk::{biased_tempo_random_walk, BiasType, random_walk, tempo_random_walk};
-    use crate::data::{CsrGraphStorage, CsrGraph, EdgeAttr};
+    use tch::{Device, Kind, Tensor};
+    use crate::algo::random_walk::{biased_tempo_random_walk, BiasType, TeleportSet, dedup_walks, pad_packed, random_walk, random_walk_with_teleport, tempo_random_walk, trim_padded, typed_random_walk, uniform_random_walk};
+    use crate::data::{CsrGraphStorage, CsrGraph, EdgeAttr, MmapCsrGraphStorage, TypedGraphStorage};
     use crate::data::load_karate_graph;
     use crate::utils::tensor::try_tensor_to_slice;
 
@@ -328,6 +706,74 @@ mod tests {
                 assert!(graph.has_edge(*prev, *curr));
             }
         }
+
+        // The memory-mapped backend walks the same paths
+        let dir = std::env::temp_dir().join(format!("tch_geometric_walk_{}", std::process::id()));
+        graph_data.save(&dir).unwrap();
+        let mmap_data = MmapCsrGraphStorage::load(&dir).unwrap();
+        let walks = random_walk(&mut rand::rngs::SmallRng::from_seed([1; 32]), &graph, &start, 10, 1.0, 1.5).unwrap();
+        let mmap_walks = random_walk(&mut rand::rngs::SmallRng::from_seed([1; 32]), &mmap_data, &start, 10, 1.0, 1.5).unwrap();
+        assert!(walks.equal(&mmap_walks));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_randomwalk_invalid_arguments() {
+        let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+        let (_x, _, coo_graph) = load_karate_graph();
+        let graph_data = CsrGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let start = Tensor::of_slice(&[0_i64, 1]);
+
+        assert!(random_walk(&mut rng, &graph, &start, 10, 0.0, 1.0).is_err());
+        assert!(random_walk(&mut rng, &graph, &start, 10, 1.0, -1.0).is_err());
+        assert!(random_walk(&mut rng, &graph, &start, 10, f32::NAN, 1.0).is_err());
+
+        let err = random_walk(&mut rng, &graph, &Tensor::of_slice(&[0_i64, 34]), 10, 1.0, 1.0).unwrap_err();
+        assert!(err.to_string().contains("start node 34 at index 1"));
+    }
+
+    #[test]
+    fn test_uniform_random_walk() {
+        // 0 -> 1 -> 2, where 2 is a sink
+        let coo_graph = crate::data::CooGraphStorage::new(Tensor::of_slice(&[0_i64, 1, 1, 2]).view([2, 2]), (3, 3));
+        let graph_data = CsrGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let (walks, edges) = uniform_random_walk(&graph, &Tensor::of_slice(&[0_i64, 2]), 4, 0, None).unwrap();
+        assert_eq!(Vec::<i64>::from(walks.view([-1])), vec![0, 1, 2, -1, -1, 2, -1, -1, -1, -1]);
+        assert_eq!(Vec::<i64>::from(edges.view([-1])), vec![0, 1, -1, -1, -1, -1, -1, -1]);
+        assert!(uniform_random_walk(&graph, &Tensor::of_slice(&[3_i64]), 4, 0, None).is_err());
+        assert!(uniform_random_walk(&graph, &Tensor::of_slice(&[0_i64]), 4, 0, Some(&[0])).is_err());
+
+        // Walks follow edges and only depend on the seed
+        let (_x, _, coo_graph) = load_karate_graph();
+        let graph_data = CsrGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let start = Tensor::of_slice(&(0..34_i64).collect::<Vec<_>>());
+        let (walks, edges) = uniform_random_walk(&graph, &start, 8, 3, None).unwrap();
+        let walks_data = Vec::<i64>::from(walks.view([-1]));
+        let edges_data = Vec::<i64>::from(edges.view([-1]));
+        for (walk, walk_edges) in walks_data.chunks(9).zip(edges_data.chunks(8)) {
+            for (l, e) in walk_edges.iter().enumerate() {
+                assert!(graph.has_edge(walk[l], walk[l + 1]));
+                assert_eq!(graph_data.indices.int64_value(&[*e]), walk[l + 1]);
+            }
+        }
+        let (same, _) = uniform_random_walk(&graph, &start, 8, 3, None).unwrap();
+        assert!(walks.equal(&same));
+
+        // With the perm the steps are edges of the coo graph
+        let perm: Vec<i64> = graph_data.permute_edge_attr(&Tensor::arange(graph_data.indices.size()[0], (Kind::Int64, Device::Cpu))).into();
+        let (same, edge_ids) = uniform_random_walk(&graph, &start, 8, 3, Some(&perm)).unwrap();
+        assert!(walks.equal(&same));
+        let row_col = Vec::<i64>::from(coo_graph.row_col.view([-1]));
+        let num_edges = row_col.len() / 2;
+        for (walk, walk_edges) in walks_data.chunks(9).zip(Vec::<i64>::from(edge_ids.view([-1])).chunks(8)) {
+            for (l, e) in walk_edges.iter().enumerate() {
+                assert_eq!((row_col[*e as usize], row_col[num_edges + *e as usize]), (walk[l], walk[l + 1]));
+            }
+        }
     }
 
     #[test]
@@ -435,4 +881,188 @@ mod tests {
             }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_random_walk_with_teleport() {
+        let (_x, _, coo_graph) = load_karate_graph();
+        let graph_data = CsrGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let start = Tensor::of_slice(&[0_i64, 1, 2, 3, 33]);
+
+        // Without teleports every step follows the recorded edge
+        let (walks, edges) = random_walk_with_teleport(&graph, &start, 12, 0.0, TeleportSet::Start, 0).unwrap();
+        assert_eq!(walks.size(), vec![5, 13]);
+        assert_eq!(edges.size(), vec![5, 12]);
+        let walks: Vec<i64> = walks.view([-1]).into();
+        let edges: Vec<i64> = edges.view([-1]).into();
+        for i in 0..5 {
+            for l in 0..12 {
+                let (prev, next, edge) = (walks[i * 13 + l], walks[i * 13 + l + 1], edges[i * 12 + l]);
+                assert!(graph.neighbors_range(prev).contains(&(edge as usize)));
+                assert_eq!(graph.get_by_ptr(edge as usize), next);
+            }
+        }
+
+        // With r = 1 only teleport targets appear after the start
+        let targets = Tensor::of_slice(&[4_i64, 10, 20]);
+        let (walks, edges) = random_walk_with_teleport(
+            &graph, &start, 8, 1.0, TeleportSet::UniformOver(&targets), 1,
+        ).unwrap();
+        let walks: Vec<i64> = walks.view([-1]).into();
+        let edges: Vec<i64> = edges.view([-1]).into();
+        assert!(edges.iter().all(|e| *e == -1));
+        for i in 0..5 {
+            assert_eq!(walks[i * 9], [0, 1, 2, 3, 33][i]);
+            assert!(walks[i * 9 + 1..(i + 1) * 9].iter().all(|v| [4, 10, 20].contains(v)));
+        }
+
+        let (walks, _) = random_walk_with_teleport(&graph, &start, 4, 1.0, TeleportSet::Start, 2).unwrap();
+        let walks: Vec<i64> = walks.view([-1]).into();
+        for i in 0..5 {
+            assert!(walks[i * 5..(i + 1) * 5].iter().all(|v| *v == walks[i * 5]));
+        }
+
+        // Weighted teleports follow the given distribution
+        let weights = Tensor::of_slice(&[1.0_f64, 2.0, 7.0]);
+        let start = Tensor::of_slice(&vec![0_i64; 100]);
+        let (walks, _) = random_walk_with_teleport(
+            &graph, &start, 100, 1.0, TeleportSet::WeightedOver(&targets, &weights), 3,
+        ).unwrap();
+        let walks: Vec<i64> = walks.narrow(1, 1, 100).contiguous().view([-1]).into();
+        for (target, p) in [(4_i64, 0.1), (10, 0.2), (20, 0.7)] {
+            let freq = walks.iter().filter(|v| **v == target).count() as f64 / walks.len() as f64;
+            assert!((freq - p).abs() < 0.02, "target {} has frequency {}", target, freq);
+        }
+
+        assert!(random_walk_with_teleport(&graph, &start, 4, 1.5, TeleportSet::Start, 0).is_err());
+        let empty = Tensor::of_slice::<i64>(&[]);
+        assert!(random_walk_with_teleport(&graph, &start, 4, 0.5, TeleportSet::UniformOver(&empty), 0).is_err());
+        let bad_weights = Tensor::of_slice(&[1.0_f64, -1.0, 1.0]);
+        assert!(random_walk_with_teleport(
+            &graph, &start, 4, 0.5, TeleportSet::WeightedOver(&targets, &bad_weights), 0,
+        ).is_err());
+    }
+
+    #[test]
+    fn test_random_walk_with_teleport_thread_count() {
+        let (_x, _, coo_graph) = load_karate_graph();
+        let graph_data = CsrGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let start = Tensor::of_slice(&(0..34_i64).collect::<Vec<_>>()).repeat(&[8]);
+
+        // Every walk has its own stream, so the walks do not depend on how they are scheduled
+        let walk = |num_threads: usize, seed: u64| {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(num_threads).build().unwrap();
+            let (walks, _) = pool.install(|| random_walk_with_teleport(&graph, &start, 10, 0.2, TeleportSet::Start, seed)).unwrap();
+            Vec::<i64>::from(walks.view([-1]))
+        };
+        assert_eq!(walk(1, 7), walk(4, 7));
+        assert_ne!(walk(4, 7), walk(4, 8));
+
+        let (walks, edges) = random_walk_with_teleport(&graph, &start, 0, 0.2, TeleportSet::Start, 0).unwrap();
+        assert_eq!(walks.size(), vec![272, 1]);
+        assert_eq!(edges.size(), vec![272, 0]);
+        assert_eq!(Vec::<i64>::from(walks.view([-1])), Vec::<i64>::from(&start));
+    }
+
+    #[test]
+    fn test_dedup_walks() {
+        let walks = Tensor::of_slice(&[
+            0_i64, 1, 2, -1,
+            3, 4, -1, -1,
+            0, 1, 2, -1,
+            0, 1, 2, 3,
+            3, 4, -1, -1,
+            0, 1, 2, -1,
+        ]).view([6, 4]);
+
+        let (unique, counts) = dedup_walks(&walks).unwrap();
+        assert_eq!(unique.size(), vec![3, 4]);
+        let unique: Vec<i64> = unique.view([-1]).into();
+        assert_eq!(unique, vec![0, 1, 2, -1, 3, 4, -1, -1, 0, 1, 2, 3]);
+        let counts: Vec<i64> = counts.into();
+        assert_eq!(counts, vec![3, 2, 1]);
+
+        // Random walks on a small graph repeat a lot
+        let (_x, _, coo_graph) = load_karate_graph();
+        let graph_data = CsrGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let start = Tensor::of_slice(&[0_i64, 1, 2, 3]).repeat(&[200]);
+        let walks = random_walk(&mut rand::rngs::SmallRng::from_seed([0; 32]), &graph, &start, 2, 1.0, 1.0).unwrap();
+        let (unique, counts) = dedup_walks(&walks).unwrap();
+        assert!(unique.size()[0] < 800);
+        assert_eq!(counts.sum(tch::Kind::Int64).int64_value(&[]), 800);
+    }
+
+    #[test]
+    fn test_typed_random_walk() {
+        let (_x, _, coo_graph) = load_karate_graph();
+        let graph_data = CsrGraphStorage::try_from(&coo_graph).unwrap();
+        let num_edges = graph_data.indices.size()[0];
+        let edge_types = Tensor::of_slice(&(0..num_edges).map(|i| (i * 7) % 3).collect::<Vec<i64>>());
+        let typed = TypedGraphStorage::new(&graph_data, &edge_types, 3).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&typed.graph).unwrap();
+        let types_data: Vec<i64> = typed.edge_types.shallow_clone().into();
+
+        // Types cycle 0 -> 1 -> 2 -> 0
+        let allowed = Tensor::of_slice(&[false, true, false, false, false, true, true, false, false]).view([3, 3]);
+        let start = Tensor::arange(34, (tch::Kind::Int64, tch::Device::Cpu));
+        let (walks, types) = typed_random_walk(&typed, &start, 8, &allowed, None, 0).unwrap();
+        assert_eq!(walks.size(), vec![34, 9]);
+        let walks_data = try_tensor_to_slice::<i64>(&walks).unwrap();
+        let walk_types = try_tensor_to_slice::<i64>(&types).unwrap();
+
+        let mut num_steps = 0;
+        for (walk, step_types) in walks_data.chunks(9).zip(walk_types.chunks(8)) {
+            for (l, t) in step_types.iter().enumerate() {
+                if *t < 0 {
+                    assert!(walk[l + 1..].iter().all(|v| *v == -1));
+                    assert!(step_types[l..].iter().all(|t| *t == -1));
+                    break;
+                }
+                num_steps += 1;
+                // The step is an edge of the recorded type
+                let (u, v) = (walk[l], walk[l + 1]);
+                assert!(graph.neighbors_range(u).any(|p| graph.get_by_ptr(p) == v && types_data[p] == *t));
+                if l > 0 {
+                    assert_eq!(*t, (step_types[l - 1] + 1) % 3);
+                }
+            }
+        }
+        assert!(num_steps > 34);
+
+        let (_, types) = typed_random_walk(&typed, &start, 4, &allowed, Some(2), 0).unwrap();
+        let first_types = Vec::<i64>::from(&types.select(1, 0));
+        assert!(first_types.iter().all(|t| *t == 0 || *t == -1));
+
+        // Without any allowed transition only the start nodes remain
+        let none = Tensor::zeros(&[3, 3], (tch::Kind::Bool, tch::Device::Cpu));
+        let (walks, _) = typed_random_walk(&typed, &start, 4, &none, Some(0), 0).unwrap();
+        assert!(walks.narrow(1, 1, 4).eq(-1).all().int64_value(&[]) == 1);
+
+        assert!(typed_random_walk(&typed, &start, 4, &none.narrow(0, 0, 2), None, 0).is_err());
+        assert!(typed_random_walk(&typed, &start, 4, &allowed, Some(3), 0).is_err());
+    }
+
+    #[test]
+    fn test_trim_padded() {
+        let walks = Tensor::of_slice(&[
+            0_i64, 1, 2, -1,
+            3, -1, -1, -1,
+            -1, -1, -1, -1,
+            5, -1, 6, 7,
+        ]).view([4, 4]);
+
+        let (lengths, values, offsets) = trim_padded(&walks, -1).unwrap();
+        let lengths_data: Vec<i64> = lengths.into();
+        assert_eq!(lengths_data, vec![3, 1, 0, 4]);
+        let values_data: Vec<i64> = values.shallow_clone().into();
+        assert_eq!(values_data, vec![0, 1, 2, 3, 5, -1, 6, 7]);
+        let offsets_data: Vec<i64> = offsets.shallow_clone().into();
+        assert_eq!(offsets_data, vec![0, 3, 4, 4, 8]);
+
+        let padded = pad_packed(&values, &offsets, 4, -1).unwrap();
+        assert!(padded.equal(&walks));
+        assert!(pad_packed(&values, &offsets, 3, -1).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use rand::Rng;
+use tch::Tensor;
+use crate::data::{CooGraphStorage, CsrGraph};
+use crate::utils::{AliasTable, NodeIdx, TensorConversionError, TensorResult};
+
+// GraphSAINT edge importance 1 / deg(u) + 1 / deg(v) of every csr edge position, normalized to sum to 1. Nodes
+// without neighbors contribute nothing. The graph is assumed to be undirected.
+pub fn saint_edge_probabilities(graph: &CsrGraph) -> Vec<f64> {
+    let inv_degree = |v: NodeIdx| match graph.out_degree(v) {
+        0 => 0.0,
+        d => 1.0 / d as f64,
+    };
+
+    let mut probs = vec![0.0; graph.edge_count()];
+    for v in 0..graph.node_count() as NodeIdx {
+        for ptr in graph.neighbors_range(v) {
+            probs[ptr] = inv_degree(v) + inv_degree(graph.get_by_ptr(ptr));
+        }
+    }
+
+    let total: f64 = probs.iter().sum();
+    if total > 0.0 {
+        probs.iter_mut().for_each(|q| *q /= total);
+    }
+    probs
+}
+
+// GraphSAINT edge sampler: draws `budget` edges with replacement from `saint_edge_probabilities` and induces the
+// subgraph on their endpoints. The subgraph keeps the global node ids (see `CooGraphStorage::relabel_nodes`).
+// Returns per subgraph edge (u, v) the aggregator normalization p_v / p_uv, with p_v the probability that v is
+// in the subgraph and p_uv the probability that both u and v are, both exact for m independent draws.
+pub fn saint_edge_sampler(
+    rng: &mut impl Rng,
+    graph: &CsrGraph,
+    budget: i64,
+) -> TensorResult<(CooGraphStorage, Tensor)> {
+    let n = graph.node_count();
+    let probs = saint_edge_probabilities(graph);
+    let m = budget.max(0);
+
+    let mut in_subgraph = vec![false; n];
+    if m > 0 {
+        let alias = AliasTable::new(&probs)
+            .ok_or_else(|| TensorConversionError::Unknown("graph has no edges to sample from".to_string()))?;
+        let sources: Vec<NodeIdx> = (0..n as NodeIdx)
+            .flat_map(|v| graph.neighbors_range(v).map(move |_| v))
+            .collect();
+        for _ in 0..m {
+            let ptr = alias.sample(rng);
+            in_subgraph[sources[ptr] as usize] = true;
+            in_subgraph[graph.get_by_ptr(ptr) as usize] = true;
+        }
+    }
+
+    // Mass of the edges touching every node, and of the edges between every pair of subgraph nodes. All edges
+    // between two subgraph nodes are part of the subgraph, so the pair masses only need the induced edges.
+    let mut node_mass = vec![0.0; n];
+    let mut pair_mass: HashMap<(NodeIdx, NodeIdx), f64> = HashMap::new();
+    let (mut rows, mut cols) = (Vec::new(), Vec::new());
+    for v in 0..n as NodeIdx {
+        for ptr in graph.neighbors_range(v) {
+            let u = graph.get_by_ptr(ptr);
+            node_mass[v as usize] += probs[ptr];
+            if u != v {
+                node_mass[u as usize] += probs[ptr];
+            }
+            if in_subgraph[v as usize] && in_subgraph[u as usize] {
+                *pair_mass.entry((v.min(u), v.max(u))).or_insert(0.0) += probs[ptr];
+                rows.push(v);
+                cols.push(u);
+            }
+        }
+    }
+
+    let m = m as f64;
+    let p_missing = |mass: f64| (1.0 - mass).max(0.0).powf(m);
+    let norm: Vec<f64> = rows.iter().zip(cols.iter())
+        .map(|(u, v)| {
+            let p_v = 1.0 - p_missing(node_mass[*v as usize]);
+            let p_uv = if u == v {
+                p_v
+            } else {
+                let (q_u, q_v) = (node_mass[*u as usize], node_mass[*v as usize]);
+                let q_uv = pair_mass[&(*u.min(v), *u.max(v))];
+                1.0 - p_missing(q_u) - p_missing(q_v) + p_missing(q_u + q_v - q_uv)
+            };
+            p_v / p_uv
+        })
+        .collect();
+
+    let row_col = Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0);
+    Ok((CooGraphStorage::new(row_col, (n as i64, n as i64)), Tensor::of_slice(&norm)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::convert::TryFrom;
+    use rand::SeedableRng;
+    use crate::data::{CsrGraph, CsrGraphStorage, load_karate_graph};
+    use crate::utils::AliasTable;
+    use super::{saint_edge_probabilities, saint_edge_sampler};
+
+    #[test]
+    fn test_saint_edge_sampler() {
+        let (_x, _, coo) = load_karate_graph();
+        let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let probs = saint_edge_probabilities(&graph);
+        assert!((probs.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+
+        let seed = [1; 32];
+        let budget = 15;
+        let (subgraph, norm) = saint_edge_sampler(&mut rand::rngs::SmallRng::from_seed(seed), &graph, budget).unwrap();
+        let rows: Vec<i64> = subgraph.row().into();
+        let cols: Vec<i64> = subgraph.col().into();
+        let norm: Vec<f64> = norm.into();
+        assert_eq!(norm.len(), rows.len());
+        assert!(norm.iter().all(|c| c.is_finite() && *c >= 1.0 - 1e-9));
+
+        // Replaying the draws gives the sampled edges
+        let mut rng = rand::rngs::SmallRng::from_seed(seed);
+        let alias = AliasTable::new(&probs).unwrap();
+        let mut endpoints = HashSet::new();
+        for _ in 0..budget {
+            let ptr = alias.sample(&mut rng);
+            let v = (0..34).find(|v| graph.neighbors_range(*v).contains(&ptr)).unwrap();
+            endpoints.insert(v);
+            endpoints.insert(graph.get_by_ptr(ptr));
+        }
+
+        let nodes: HashSet<i64> = rows.iter().chain(cols.iter()).cloned().collect();
+        assert_eq!(nodes, endpoints);
+        // The subgraph is induced
+        let num_induced: usize = endpoints.iter()
+            .map(|v| graph.neighbors_slice(*v).iter().filter(|u| endpoints.contains(u)).count())
+            .sum();
+        assert_eq!(rows.len(), num_induced);
+    }
+}
@@ -0,0 +1,332 @@
+use std::collections::{HashMap, VecDeque};
+use rand::Rng;
+use rand::seq::SliceRandom;
+use tch::Tensor;
+use crate::algo::random_walk::uniform_step;
+use crate::data::{CooGraphStorage, CsrGraph};
+use crate::utils::{NodeIdx, TensorConversionError, TensorResult, try_tensor_to_slice};
+use crate::utils::random::RngPool;
+
+fn connected_components(graph: &CsrGraph) -> Vec<usize> {
+    let n = graph.node_count();
+    let mut component = vec![usize::MAX; n];
+    let mut queue = VecDeque::new();
+    let mut count = 0;
+    for start in 0..n {
+        if component[start] != usize::MAX {
+            continue;
+        }
+        component[start] = count;
+        queue.push_back(start as NodeIdx);
+        while let Some(u) = queue.pop_front() {
+            for v in graph.neighbors_slice(u) {
+                if component[*v as usize] == usize::MAX {
+                    component[*v as usize] = count;
+                    queue.push_back(*v);
+                }
+            }
+        }
+        count += 1;
+    }
+    component
+}
+
+pub fn random_spanning_forest(
+    rng: &mut impl Rng,
+    graph: &CsrGraph,
+    roots: Option<&Tensor>,
+) -> TensorResult<(CooGraphStorage, Tensor)> {
+    let n = graph.node_count();
+
+    // Loop-erased walks need to be able to step back, so only undirected graphs are accepted
+    for u in 0..n as NodeIdx {
+        if let Some(v) = graph.neighbors_slice(u).iter().find(|&&v| !graph.has_edge(v, u)) {
+            return Err(TensorConversionError::Unknown(format!(
+                "graph must be undirected, edge ({}, {}) has no reverse", u, v
+            )));
+        }
+    }
+
+    let mut in_tree = vec![false; n];
+    if let Some(roots) = roots {
+        for r in try_tensor_to_slice::<i64>(roots)? {
+            if *r < 0 || *r as usize >= n {
+                return Err(TensorConversionError::Unknown(format!("root {} is out of bounds", r)));
+            }
+            in_tree[*r as usize] = true;
+        }
+    }
+
+    // Components without a given root are rooted at their lowest node
+    let component = connected_components(graph);
+    let num_components = component.iter().cloned().max().map_or(0, |c| c + 1);
+    let mut has_root = vec![false; num_components];
+    for (v, c) in component.iter().enumerate() {
+        if in_tree[v] {
+            has_root[*c] = true;
+        }
+    }
+    for (v, c) in component.iter().enumerate() {
+        if !has_root[*c] {
+            has_root[*c] = true;
+            in_tree[v] = true;
+        }
+    }
+
+    // Wilson's algorithm, overwriting `parent` while walking erases the loops
+    let mut parent: Vec<NodeIdx> = vec![-1; n];
+    for start in 0..n {
+        let mut u = start;
+        while !in_tree[u] {
+            let next = uniform_step(rng, graph, u as NodeIdx).expect("non root nodes always have neighbors");
+            parent[u] = next;
+            u = next as usize;
+        }
+
+        let mut u = start;
+        while !in_tree[u] {
+            in_tree[u] = true;
+            u = parent[u] as usize;
+        }
+    }
+
+    let mut rows = Vec::with_capacity(n);
+    let mut cols = Vec::with_capacity(n);
+    for (v, p) in parent.iter().enumerate() {
+        if *p != -1 {
+            rows.push(v as NodeIdx);
+            cols.push(*p);
+        }
+    }
+    let row_col = Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0);
+
+    Ok((
+        CooGraphStorage::new(row_col, (n as i64, n as i64)),
+        Tensor::of_slice(&parent),
+    ))
+}
+
+fn find_root(parent: &mut [usize], x: usize) -> usize {
+    let mut root = x;
+    while parent[root] != root {
+        root = parent[root];
+    }
+    let mut x = x;
+    while parent[x] != root {
+        let next = parent[x];
+        parent[x] = root;
+        x = next;
+    }
+    root
+}
+
+// DropEdge that never disconnects a component: the edges of a random spanning forest (union-find over the edges in
+// shuffled order) are always kept, every other edge is dropped with probability p. Edges are treated as undirected,
+// so (u, v) and (v, u) are kept or dropped together. The kept edges follow the csr order.
+pub fn drop_edge_spanning_safe(
+    graph: &CsrGraph,
+    p: f64,
+    seed: u64,
+) -> TensorResult<CooGraphStorage> {
+    if !(0.0..=1.0).contains(&p) {
+        return Err(TensorConversionError::Unknown(format!("p must be in [0, 1], got {}", p)));
+    }
+    let n = graph.node_count();
+    if let Some(v) = graph.indices.iter().find(|&&v| v < 0 || v as usize >= n) {
+        return Err(TensorConversionError::Unknown(format!("neighbor {} is out of bounds for {} nodes", v, n)));
+    }
+
+    // Every edge maps onto its undirected pair
+    let mut pair_ids: HashMap<(NodeIdx, NodeIdx), usize> = HashMap::new();
+    let mut pairs = Vec::new();
+    let mut edge_pair = Vec::with_capacity(graph.indices.len());
+    for u in 0..n as NodeIdx {
+        for v in graph.neighbors_slice(u) {
+            let key = (u.min(*v), u.max(*v));
+            edge_pair.push(*pair_ids.entry(key).or_insert_with(|| {
+                pairs.push(key);
+                pairs.len() - 1
+            }));
+        }
+    }
+
+    let mut rng = RngPool::new(seed).stream(&[]);
+    let mut order: Vec<usize> = (0..pairs.len()).collect();
+    order.shuffle(&mut rng);
+    let mut parent: Vec<usize> = (0..n).collect();
+    let mut keep = vec![false; pairs.len()];
+    for i in order {
+        let (u, v) = pairs[i];
+        let (a, b) = (find_root(&mut parent, u as usize), find_root(&mut parent, v as usize));
+        if a != b {
+            parent[a] = b;
+            keep[i] = true;
+        }
+    }
+    for k in keep.iter_mut().filter(|k| !**k) {
+        *k = rng.gen::<f64>() >= p;
+    }
+
+    let mut rows = Vec::new();
+    let mut cols = Vec::new();
+    let mut e = 0;
+    for u in 0..n as NodeIdx {
+        for v in graph.neighbors_slice(u) {
+            if keep[edge_pair[e]] {
+                rows.push(u);
+                cols.push(*v);
+            }
+            e += 1;
+        }
+    }
+    let row_col = Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0);
+    Ok(CooGraphStorage::new(row_col, (n as i64, n as i64)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use rand::SeedableRng;
+    use rand::rngs::SmallRng;
+    use tch::Tensor;
+    use crate::data::{CooGraphStorage, CsrGraph, CsrGraphStorage, load_karate_graph};
+    use crate::utils::NodeIdx;
+
+    fn find(uf: &mut [usize], x: usize) -> usize {
+        let mut r = x;
+        while uf[r] != r {
+            r = uf[r];
+        }
+        uf[x] = r;
+        r
+    }
+
+    // Returns the number of trees after adding all edges and panics on a cycle
+    fn check_forest(n: usize, coo: &CooGraphStorage) -> usize {
+        let rows: Vec<NodeIdx> = coo.row().into();
+        let cols: Vec<NodeIdx> = coo.col().into();
+        let mut uf: Vec<usize> = (0..n).collect();
+        for (u, v) in rows.iter().zip(cols.iter()) {
+            let (a, b) = (find(&mut uf, *u as usize), find(&mut uf, *v as usize));
+            assert_ne!(a, b, "edge ({}, {}) closes a cycle", u, v);
+            uf[a] = b;
+        }
+        (0..n).filter(|&x| find(&mut uf, x) == x).count()
+    }
+
+    #[test]
+    fn test_random_spanning_forest() {
+        let (x, _, coo_graph) = load_karate_graph();
+        let n = x.size()[0] as usize;
+        let graph_data = CsrGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        let (tree, parent) = super::random_spanning_forest(&mut rng, &graph, None).unwrap();
+        assert_eq!(tree.row_col.size(), vec![2, n as i64 - 1]);
+        assert_eq!(check_forest(n, &tree), 1);
+        let parent: Vec<NodeIdx> = parent.into();
+        assert_eq!(parent[0], -1);
+        for (v, p) in parent.iter().enumerate().skip(1) {
+            assert!(graph.has_edge(v as i64, *p));
+        }
+
+        let (_, parent_again) = super::random_spanning_forest(&mut SmallRng::seed_from_u64(0), &graph, None).unwrap();
+        let parent_again: Vec<NodeIdx> = parent_again.into();
+        assert_eq!(parent, parent_again);
+
+        // Two given roots split the component into a forest of two trees
+        let roots = Tensor::of_slice(&[5_i64, 20]);
+        let (forest, parent) = super::random_spanning_forest(&mut rng, &graph, Some(&roots)).unwrap();
+        assert_eq!(forest.row_col.size(), vec![2, n as i64 - 2]);
+        assert_eq!(check_forest(n, &forest), 2);
+        let parent: Vec<NodeIdx> = parent.into();
+        assert_eq!((parent[5], parent[20]), (-1, -1));
+        assert_ne!(parent[0], -1);
+    }
+
+    fn component_count(coo: &CooGraphStorage) -> usize {
+        let graph_data = CsrGraphStorage::try_from(coo).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        super::connected_components(&graph).iter().cloned().max().map_or(0, |c| c + 1)
+    }
+
+    #[test]
+    fn test_drop_edge_spanning_safe() {
+        let (x, _, coo_graph) = load_karate_graph();
+        let n = x.size()[0];
+        let graph_data = CsrGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        assert_eq!(component_count(&coo_graph), 1);
+
+        for seed in 0..10 {
+            let dropped = super::drop_edge_spanning_safe(&graph, 0.8, seed).unwrap();
+            assert_eq!(component_count(&dropped), 1);
+            assert!(dropped.row_col.size()[1] < graph.edge_count() as i64);
+            let rows: Vec<NodeIdx> = dropped.row().into();
+            let cols: Vec<NodeIdx> = dropped.col().into();
+            for (u, v) in rows.iter().zip(cols.iter()) {
+                assert!(graph.has_edge(*u, *v));
+            }
+        }
+
+        // Dropping everything that can be dropped leaves a spanning tree in both directions
+        let tree = super::drop_edge_spanning_safe(&graph, 1.0, 3).unwrap();
+        assert_eq!(tree.row_col.size(), vec![2, 2 * (n - 1)]);
+        assert_eq!(component_count(&tree), 1);
+        let kept = super::drop_edge_spanning_safe(&graph, 0.0, 3).unwrap();
+        assert_eq!(kept.row_col.size(), vec![2, graph.edge_count() as i64]);
+
+        let a = super::drop_edge_spanning_safe(&graph, 0.5, 7).unwrap();
+        let b = super::drop_edge_spanning_safe(&graph, 0.5, 7).unwrap();
+        assert!(a.row_col.equal(&b.row_col));
+        assert!(super::drop_edge_spanning_safe(&graph, 1.5, 0).is_err());
+    }
+
+    #[test]
+    fn test_drop_edge_spanning_safe_components() {
+        // A triangle, a path of two nodes and an isolated node keep their three components
+        let edges = [(0_i64, 1_i64), (1, 2), (2, 0), (3, 4)];
+        let mut rows = Vec::new();
+        let mut cols = Vec::new();
+        for (u, v) in edges.iter().cloned() {
+            rows.extend_from_slice(&[u, v]);
+            cols.extend_from_slice(&[v, u]);
+        }
+        let coo = CooGraphStorage::new(Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0), (6, 6));
+        let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        for seed in 0..10 {
+            let dropped = super::drop_edge_spanning_safe(&graph, 1.0, seed).unwrap();
+            assert_eq!(dropped.row_col.size(), vec![2, 6]);
+            assert_eq!(component_count(&dropped), 3);
+        }
+    }
+
+    #[test]
+    fn test_random_spanning_forest_components() {
+        // A triangle, a path of two nodes and an isolated node
+        let edges = [(0_i64, 1_i64), (1, 2), (2, 0), (3, 4)];
+        let mut rows = Vec::new();
+        let mut cols = Vec::new();
+        for (u, v) in edges.iter().cloned() {
+            rows.extend_from_slice(&[u, v]);
+            cols.extend_from_slice(&[v, u]);
+        }
+        let coo = CooGraphStorage::new(Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0), (6, 6));
+        let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let mut rng = SmallRng::seed_from_u64(3);
+        for _ in 0..10 {
+            let (forest, _) = super::random_spanning_forest(&mut rng, &graph, None).unwrap();
+            assert_eq!(forest.row_col.size(), vec![2, 6 - 3]);
+            assert_eq!(check_forest(6, &forest), 3);
+        }
+
+        let directed = CooGraphStorage::new(Tensor::stack(&[Tensor::of_slice(&[0_i64]), Tensor::of_slice(&[1_i64])], 0), (2, 2));
+        let graph_data = CsrGraphStorage::try_from(&directed).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        assert!(super::random_spanning_forest(&mut rng, &graph, None).is_err());
+    }
+}
@@ -0,0 +1,479 @@
+use std::collections::BTreeMap;
+use rayon::prelude::*;
+use tch::{Kind, Tensor};
+use crate::data::CooGraphStorage;
+use crate::utils::{parallel, TensorConversionError, TensorResult, try_tensor_to_slice};
+
+// Row major [N, D] point features as f64, all of them finite
+fn points(x: &Tensor) -> TensorResult<(Tensor, usize, usize)> {
+    if x.dim() != 2 {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "points must be of shape [N, D], got {:?}", x.size()
+        ))));
+    }
+    let (n, d) = (x.size()[0] as usize, x.size()[1] as usize);
+    let x = x.to_device(tch::Device::Cpu).to_kind(Kind::Double).contiguous();
+    if !try_tensor_to_slice::<f64>(&x)?.iter().all(|v| v.is_finite()) {
+        return Err(TensorConversionError::Unknown("points must be finite".to_string()));
+    }
+    Ok((x, n, d))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    // Squared euclidean distance
+    L2,
+    // One minus the cosine similarity, zero vectors are at distance 1 from every point
+    Cosine,
+}
+
+// Query points per distance block, only this many rows of the distance matrix exist at a time
+const BLOCK_SIZE: usize = 1024;
+
+// Bound on the error of the matmul distances relative to the scale of the points, far above the rounding error for
+// any realistic dimension
+const RELATIVE_TOLERANCE: f64 = 1e-8;
+
+// The example of every point, all points form a single example without `batch`
+fn batch_data(batch: Option<&Tensor>, n: usize) -> TensorResult<Vec<i64>> {
+    match batch {
+        Some(batch) => {
+            if batch.size() != [n as i64] {
+                return Err(TensorConversionError::InvalidShape(Some(format!(
+                    "batch must be of shape [{}], got {:?}", n, batch.size()
+                ))));
+            }
+            Ok(try_tensor_to_slice::<i64>(&batch.to_device(tch::Device::Cpu).to_kind(Kind::Int64).contiguous())?.to_vec())
+        }
+        None => Ok(vec![0; n]),
+    }
+}
+
+// The candidate neighbors of a point, with distances from the matmul which are only right up to `tolerance`
+struct Candidates<'a> {
+    // (approximate distance, index into the example)
+    approx: Vec<(f64, usize)>,
+    tolerance: f64,
+    members: &'a [i64],
+    exact: &'a dyn Fn(usize) -> f64,
+}
+
+impl Candidates<'_> {
+    // Approximate distance of the k-th nearest candidate, infinite if there are no more than k
+    fn kth_approx(&mut self, k: usize) -> f64 {
+        if k == 0 {
+            return f64::NEG_INFINITY;
+        }
+        if k >= self.approx.len() {
+            return f64::INFINITY;
+        }
+        self.approx.select_nth_unstable_by(k - 1, |a, b| a.0.total_cmp(&b.0));
+        self.approx[k - 1].0
+    }
+
+    // Every candidate that may be within `bound`, as (direct distance, id) nearest first with ties broken by id.
+    // Only these are computed directly, so the cost stays in line with the output.
+    fn within(&self, bound: f64) -> Vec<(f64, usize)> {
+        let mut within: Vec<(f64, usize)> = self.approx.iter()
+            .filter(|(approx, _)| *approx <= bound + self.tolerance)
+            .map(|&(_, j)| ((self.exact)(j), self.members[j] as usize))
+            .collect();
+        within.sort_unstable_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        within
+    }
+}
+
+// Calls `select` for every point with the candidates in its example, itself only with `loop_`, and collects the
+// neighbors it keeps. The inner products come from a matmul per block of query points and the candidates of a block
+// are selected in parallel. L2 distances are computed on centered points to limit the cancellation in
+// |a|^2 + |b|^2 - 2ab, the candidates near the cut of `select` are then recomputed directly on the original points.
+fn nearest_neighbors(
+    x: &Tensor,
+    metric: Metric,
+    batch: Option<&Tensor>,
+    loop_: bool,
+    select: impl Fn(&mut Candidates) -> Vec<i64> + Sync,
+) -> TensorResult<Vec<Vec<i64>>> {
+    let (x, n, d) = points(x)?;
+    let mut examples: BTreeMap<i64, Vec<i64>> = BTreeMap::new();
+    for (i, b) in batch_data(batch, n)?.into_iter().enumerate() {
+        examples.entry(b).or_default().push(i as i64);
+    }
+
+    let mut neighbors = vec![Vec::new(); n];
+    for members in examples.values() {
+        let original = x.index_select(0, &Tensor::of_slice(members)).contiguous();
+        let x = match metric {
+            Metric::L2 => &original - original.mean_dim(&[0], true, Kind::Double),
+            Metric::Cosine => {
+                let norms = (&original * &original).sum_dim_intlist(&[1], true, Kind::Double).sqrt().clamp_min(1e-12);
+                &original / norms
+            }
+        }.contiguous();
+        let sq_norms = (&x * &x).sum_dim_intlist(&[1], false, Kind::Double).contiguous();
+        let sq_norms = try_tensor_to_slice::<f64>(&sq_norms)?;
+        let x_t = x.tr();
+
+        // Direct distances, on the normalized points for cosine
+        let direct_points = match metric {
+            Metric::L2 => try_tensor_to_slice::<f64>(&original)?,
+            Metric::Cosine => try_tensor_to_slice::<f64>(&x)?,
+        };
+        let direct = |i: usize, j: usize| {
+            let (a, b) = (&direct_points[i * d..(i + 1) * d], &direct_points[j * d..(j + 1) * d]);
+            match metric {
+                Metric::L2 => a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum::<f64>(),
+                Metric::Cosine => 1.0 - a.iter().zip(b).map(|(a, b)| a * b).sum::<f64>(),
+            }
+        };
+        let tolerance = match metric {
+            Metric::L2 => {
+                let scale = (0..members.len())
+                    .map(|i| direct_points[i * d..(i + 1) * d].iter().map(|v| v * v).sum::<f64>())
+                    .fold(0.0, f64::max);
+                RELATIVE_TOLERANCE * 4.0 * scale
+            }
+            Metric::Cosine => RELATIVE_TOLERANCE,
+        };
+
+        for start in (0..members.len()).step_by(BLOCK_SIZE) {
+            let len = BLOCK_SIZE.min(members.len() - start);
+            let dots = x.narrow(0, start as i64, len as i64).matmul(&x_t).contiguous();
+            let dots = try_tensor_to_slice::<f64>(&dots)?;
+
+            let block: Vec<Vec<i64>> = parallel::install(|| (0..len).into_par_iter()
+                .map(|bi| {
+                    let i = start + bi;
+                    let mut approx: Vec<(f64, usize)> = dots[bi * members.len()..(bi + 1) * members.len()].iter()
+                        .enumerate()
+                        .filter(|(j, _)| loop_ || *j != i)
+                        .map(|(j, dot)| {
+                            let distance = match metric {
+                                Metric::L2 => sq_norms[i] + sq_norms[j] - 2.0 * dot,
+                                Metric::Cosine => 1.0 - dot,
+                            };
+                            (distance, j)
+                        })
+                        .collect();
+                    // The squared norms can overflow even for finite points, fall back to the direct distances
+                    let mut tolerance = tolerance;
+                    if approx.iter().any(|(distance, _)| !distance.is_finite()) || !tolerance.is_finite() {
+                        approx.iter_mut().for_each(|(distance, j)| *distance = direct(i, *j));
+                        tolerance = 0.0;
+                    }
+                    let exact = |j: usize| direct(i, j);
+                    select(&mut Candidates { approx, tolerance, members, exact: &exact })
+                })
+                .collect());
+            for (bi, block_neighbors) in block.into_iter().enumerate() {
+                neighbors[members[start + bi] as usize] = block_neighbors;
+            }
+        }
+    }
+    Ok(neighbors)
+}
+
+// Connects every point to its k nearest points of the same example (all points without `batch`), with `loop_` a
+// point counts as its own nearest neighbor. Like PyG the edges point from the neighbor (row) to the point (col), so
+// that the samplers aggregate over the neighborhood. Points with fewer than k candidates get all of them, ties are
+// broken by id. Exact, but every point is compared with its whole example.
+pub fn knn_graph(x: &Tensor, k: i64, metric: Metric, batch: Option<&Tensor>, loop_: bool) -> TensorResult<CooGraphStorage> {
+    if k < 0 {
+        return Err(TensorConversionError::Unknown(format!("k must be non negative, got {}", k)));
+    }
+
+    let neighbors = nearest_neighbors(x, metric, batch, loop_, |candidates| {
+        let bound = candidates.kth_approx(k as usize);
+        candidates.within(bound).into_iter().take(k as usize).map(|(_, j)| j as i64).collect()
+    })?;
+
+    Ok(neighbor_lists_to_coo(&neighbors))
+}
+
+// Connects every point to the points of its example within distance r (inclusive, by the directly computed
+// distance), as many as `max_num_neighbors` of them. With L2 r is the euclidean distance, with cosine the cosine
+// distance. Points with more candidates keep the nearest ones, ties are broken by id, so the cap drops the farthest
+// neighbors rather than arbitrary ones. Edges point from the neighbor to the point like in `knn_graph`.
+pub fn radius_graph(
+    x: &Tensor,
+    r: f64,
+    max_num_neighbors: i64,
+    metric: Metric,
+    batch: Option<&Tensor>,
+    loop_: bool,
+) -> TensorResult<CooGraphStorage> {
+    if r.is_nan() || r < 0.0 {
+        return Err(TensorConversionError::Unknown(format!("radius must be non negative, got {}", r)));
+    }
+    if max_num_neighbors < 0 {
+        return Err(TensorConversionError::Unknown(format!(
+            "max_num_neighbors must be non negative, got {}", max_num_neighbors
+        )));
+    }
+    let threshold = match metric {
+        Metric::L2 => r * r,
+        Metric::Cosine => r,
+    };
+
+    let neighbors = nearest_neighbors(x, metric, batch, loop_, |candidates| {
+        candidates.within(threshold).into_iter()
+            .filter(|(distance, _)| *distance <= threshold)
+            .take(max_num_neighbors as usize)
+            .map(|(_, j)| j as i64)
+            .collect()
+    })?;
+
+    Ok(neighbor_lists_to_coo(&neighbors))
+}
+
+// Clusters points by the voxel of the given size they fall into, separately per example when `batch` is given. The
+// grid starts at the per dimension minimum over all points and a voxel covers [start + v * size, start + (v + 1) * size),
+// so points exactly on a boundary belong to the upper voxel. `size` either has an entry per dimension or a single one
+// for all. Only occupied voxels get an id, the ids are consecutive in (batch, voxel coordinate) order.
+pub fn voxel_grid(pos: &Tensor, size: &[f64], batch: Option<&Tensor>) -> TensorResult<Tensor> {
+    let (pos, n, d) = points(pos)?;
+    if size.len() != 1 && size.len() != d {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "size must have 1 or {} entries, got {}", d, size.len()
+        ))));
+    }
+    if let Some(s) = size.iter().find(|s| s.is_nan() || **s <= 0.0) {
+        return Err(TensorConversionError::Unknown(format!("voxel size must be positive, got {}", s)));
+    }
+    let batch_data = batch_data(batch, n)?;
+    let pos_data = try_tensor_to_slice::<f64>(&pos)?;
+
+    let start: Vec<f64> = (0..d)
+        .map(|k| (0..n).map(|i| pos_data[i * d + k]).fold(f64::INFINITY, f64::min))
+        .collect();
+    let voxels: Vec<(i64, Vec<i64>)> = (0..n)
+        .map(|i| {
+            let voxel = (0..d)
+                .map(|k| ((pos_data[i * d + k] - start[k]) / size[k.min(size.len() - 1)]).floor() as i64)
+                .collect();
+            (batch_data[i], voxel)
+        })
+        .collect();
+
+    let mut ids: BTreeMap<&(i64, Vec<i64>), i64> = voxels.iter().map(|voxel| (voxel, 0)).collect();
+    for (id, v) in ids.values_mut().enumerate() {
+        *v = id as i64;
+    }
+    let cluster: Vec<i64> = voxels.iter().map(|voxel| ids[voxel]).collect();
+    Ok(Tensor::of_slice(&cluster))
+}
+
+fn neighbor_lists_to_coo(neighbors: &[Vec<i64>]) -> CooGraphStorage {
+    let n = neighbors.len() as i64;
+    let rows: Vec<i64> = neighbors.iter().flatten().cloned().collect();
+    let cols: Vec<i64> = neighbors.iter().enumerate()
+        .flat_map(|(i, neighbors)| neighbors.iter().map(move |_| i as i64))
+        .collect();
+    let row_col = Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0);
+    CooGraphStorage::new(row_col, (n, n))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::SmallRng;
+    use tch::Tensor;
+    use crate::data::CooGraphStorage;
+    use crate::algo::spatial::{Metric, knn_graph, radius_graph, voxel_grid};
+
+    fn pairs(graph: &CooGraphStorage) -> Vec<(i64, i64)> {
+        let rows: Vec<i64> = graph.row().into();
+        let cols: Vec<i64> = graph.col().into();
+        cols.into_iter().zip(rows).collect()
+    }
+
+    fn neighbors(x: &Tensor, k: i64, loop_: bool) -> Vec<(i64, i64)> {
+        pairs(&knn_graph(x, k, Metric::L2, None, loop_).unwrap())
+    }
+
+    #[test]
+    fn test_knn_graph() {
+        let x = Tensor::of_slice(&[0.0_f32, 1.0, 3.0, 7.0, 8.0]).view([5, 1]);
+        // (point, neighbor) pairs, nearest first
+        assert_eq!(neighbors(&x, 2, false), vec![
+            (0, 1), (0, 2),
+            (1, 0), (1, 2),
+            (2, 1), (2, 0),
+            (3, 4), (3, 2),
+            (4, 3), (4, 2),
+        ]);
+        assert_eq!(neighbors(&x, 2, true), vec![
+            (0, 0), (0, 1),
+            (1, 1), (1, 0),
+            (2, 2), (2, 1),
+            (3, 3), (3, 4),
+            (4, 4), (4, 3),
+        ]);
+
+        // Every other point when k exceeds them
+        let graph = knn_graph(&x, 10, Metric::L2, None, false).unwrap();
+        assert_eq!(graph.row_col.size(), vec![2, 20]);
+        assert_eq!(graph.size, (5, 5));
+        assert_eq!(knn_graph(&x, 0, Metric::L2, None, true).unwrap().row_col.size(), vec![2, 0]);
+
+        // Ties go to the lower id
+        let x = Tensor::of_slice(&[0.0_f64, 0.0, 1.0, 0.0, -1.0, 0.0, 0.0, 2.0]).view([4, 2]);
+        assert_eq!(neighbors(&x, 1, false)[0], (0, 1));
+
+        assert!(knn_graph(&Tensor::of_slice(&[0.0_f64, 1.0]), 1, Metric::L2, None, false).is_err());
+        assert!(knn_graph(&x, -1, Metric::L2, None, false).is_err());
+    }
+
+    #[test]
+    fn test_radius_graph() {
+        let x = Tensor::of_slice(&[0.0_f32, 1.0, 3.0, 7.0, 8.0, 2.5]).view([6, 1]);
+        // Points beyond r are excluded, the rest nearest first
+        let graph = radius_graph(&x, 2.0, 10, Metric::L2, None, false).unwrap();
+        assert_eq!(graph.size, (6, 6));
+        assert_eq!(pairs(&graph), vec![
+            (0, 1),
+            (1, 0), (1, 5), (1, 2),
+            (2, 5), (2, 1),
+            (3, 4),
+            (4, 3),
+            (5, 2), (5, 1),
+        ]);
+        // Distance exactly r is included
+        assert!(pairs(&radius_graph(&x, 1.0, 10, Metric::L2, None, false).unwrap()).contains(&(0, 1)));
+
+        // The cap keeps the nearest neighbors
+        let graph = radius_graph(&x, 2.0, 1, Metric::L2, None, true).unwrap();
+        assert_eq!(pairs(&graph), (0..6).map(|i| (i, i)).collect::<Vec<_>>());
+        let capped = pairs(&radius_graph(&x, 2.0, 2, Metric::L2, None, false).unwrap());
+        assert!(capped.contains(&(1, 5)) && capped.contains(&(1, 0)) && !capped.contains(&(1, 2)));
+        for i in 0..6 {
+            assert!(capped.iter().filter(|(c, _)| *c == i).count() <= 2);
+        }
+
+        assert_eq!(radius_graph(&x, 0.4, 10, Metric::L2, None, false).unwrap().row_col.size(), vec![2, 0]);
+        assert!(radius_graph(&x, -1.0, 10, Metric::L2, None, false).is_err());
+        assert!(radius_graph(&x, f64::NAN, 10, Metric::L2, None, false).is_err());
+        assert!(radius_graph(&x, 1.0, -1, Metric::L2, None, false).is_err());
+        assert!(radius_graph(&Tensor::of_slice(&[0.0_f64, f64::NAN]).view([2, 1]), 1.0, 10, Metric::L2, None, false).is_err());
+        assert!(knn_graph(&Tensor::of_slice(&[0.0_f64, f64::INFINITY]).view([2, 1]), 1, Metric::L2, None, false).is_err());
+
+        // Two clusters far apart, the centered squared norms are so large that the matmul distances within a cluster
+        // are off by more than the spacing. Boundary and tie decisions use the direct distances.
+        let x = Tensor::of_slice(&[0.0_f64, 1.0, 2.0, 1e8, 1e8 + 1.0]).view([5, 1]);
+        let edges = pairs(&radius_graph(&x, 1.0, 10, Metric::L2, None, false).unwrap());
+        assert_eq!(edges, vec![(0, 1), (1, 0), (1, 2), (2, 1), (3, 4), (4, 3)]);
+        assert_eq!(neighbors(&x, 1, false), vec![(0, 1), (1, 0), (2, 1), (3, 4), (4, 3)]);
+    }
+
+    // Direct distance between rows i and j of the [N, D] points
+    fn reference_distance(x: &[f64], d: usize, i: usize, j: usize, metric: Metric) -> f64 {
+        let (a, b) = (&x[i * d..(i + 1) * d], &x[j * d..(j + 1) * d]);
+        let dot: f64 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+        match metric {
+            Metric::L2 => a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum(),
+            Metric::Cosine => {
+                let norm = |v: &[f64]| v.iter().map(|v| v * v).sum::<f64>().sqrt();
+                1.0 - dot / (norm(a) * norm(b))
+            }
+        }
+    }
+
+    #[test]
+    fn test_nearest_neighbors_reference() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let (n, d) = (60, 5);
+        let data: Vec<f64> = (0..n * d).map(|_| rng.gen_range(-1.0..1.0)).collect();
+        let batch_data: Vec<i64> = (0..n).map(|_| rng.gen_range(0..3)).collect();
+        let x = Tensor::of_slice(&data).view([n as i64, d as i64]);
+        let batch = Tensor::of_slice(&batch_data);
+
+        for metric in [Metric::L2, Metric::Cosine] {
+            for with_batch in [false, true] {
+                let same_example = |i: usize, j: usize| !with_batch || batch_data[i] == batch_data[j];
+                let by_distance = |i: usize| {
+                    let mut candidates: Vec<(f64, usize)> = (0..n)
+                        .filter(|j| *j != i && same_example(i, *j))
+                        .map(|j| (reference_distance(&data, d, i, j, metric), j))
+                        .collect();
+                    candidates.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+                    candidates
+                };
+
+                let graph = knn_graph(&x, 4, metric, if with_batch { Some(&batch) } else { None }, false).unwrap();
+                let edges = pairs(&graph);
+                let expected: Vec<(i64, i64)> = (0..n)
+                    .flat_map(|i| by_distance(i).into_iter().take(4).map(move |(_, j)| (i as i64, j as i64)))
+                    .collect();
+                assert_eq!(edges, expected);
+
+                let r = match metric {
+                    Metric::L2 => 0.9,
+                    Metric::Cosine => 0.3,
+                };
+                let threshold = if metric == Metric::L2 { r * r } else { r };
+                let graph = radius_graph(&x, r, 100, metric, if with_batch { Some(&batch) } else { None }, false).unwrap();
+                let expected: Vec<(i64, i64)> = (0..n)
+                    .flat_map(|i| by_distance(i).into_iter()
+                        .filter(|(distance, _)| *distance <= threshold)
+                        .map(move |(_, j)| (i as i64, j as i64)))
+                    .collect();
+                assert_eq!(pairs(&graph), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_knn_graph_batch() {
+        // Two copies of the same points far apart from each other in different examples, and one example with a
+        // single point
+        let x = Tensor::of_slice(&[0.0_f64, 1.0, 3.0, 100.0, 101.0, 103.0, 50.0]).view([7, 1]);
+        let batch = Tensor::of_slice(&[0_i64, 0, 0, 2, 2, 2, 1]);
+        let edges = pairs(&knn_graph(&x, 5, Metric::L2, Some(&batch), false).unwrap());
+        let example = |v: i64| [0, 0, 0, 2, 2, 2, 1][v as usize];
+        assert!(edges.iter().all(|(i, j)| example(*i) == example(*j)));
+        assert!(edges.iter().all(|(i, j)| i != j));
+        assert_eq!(edges.len(), 12);
+        assert!(!edges.iter().any(|(i, _)| *i == 6));
+        assert_eq!(pairs(&knn_graph(&x, 1, Metric::L2, Some(&batch), true).unwrap())[6], (6, 6));
+
+        // Without the batch the single point does get neighbors
+        let edges = pairs(&knn_graph(&x, 1, Metric::L2, None, false).unwrap());
+        assert_eq!(edges[6], (6, 2));
+
+        // Far away points in the same direction are cosine neighbors, a zero vector is equally far from all
+        let x = Tensor::of_slice(&[1.0_f64, 0.0, 100.0, 1.0, 0.0, 1.0, 0.0, 0.0]).view([4, 2]);
+        let edges = pairs(&knn_graph(&x, 1, Metric::Cosine, None, false).unwrap());
+        assert_eq!(edges, vec![(0, 1), (1, 0), (2, 1), (3, 0)]);
+        let edges = pairs(&radius_graph(&x, 0.5, 10, Metric::Cosine, None, true).unwrap());
+        assert_eq!(edges, vec![(0, 0), (0, 1), (1, 1), (1, 0), (2, 2)]);
+
+        assert!(knn_graph(&x, 1, Metric::L2, Some(&Tensor::of_slice(&[0_i64, 1])), false).is_err());
+    }
+
+    #[test]
+    fn test_voxel_grid() {
+        let pos = Tensor::of_slice(&[
+            0.0_f32, 0.0,
+            0.5, 0.5,
+            1.0, 0.0,
+            1.5, 1.5,
+            0.2, 1.9,
+            3.5, 0.1,
+        ]).view([6, 2]);
+        // Unit voxels from (0, 0), the voxel (2, 0) between the last points stays empty
+        let cluster: Vec<i64> = voxel_grid(&pos, &[1.0], None).unwrap().into();
+        assert_eq!(cluster, vec![0, 0, 2, 3, 1, 4]);
+        // Per dimension sizes
+        let cluster: Vec<i64> = voxel_grid(&pos, &[2.0, 4.0], None).unwrap().into();
+        assert_eq!(cluster, vec![0, 0, 0, 0, 0, 1]);
+
+        // Examples never share a voxel
+        let batch = Tensor::of_slice(&[0_i64, 1, 0, 1, 1, 0]);
+        let cluster: Vec<i64> = voxel_grid(&pos, &[2.0], Some(&batch)).unwrap().into();
+        assert_eq!(cluster, vec![0, 2, 0, 2, 2, 1]);
+
+        assert!(voxel_grid(&pos, &[1.0, 1.0, 1.0], None).is_err());
+        assert!(voxel_grid(&pos, &[0.0], None).is_err());
+        assert!(voxel_grid(&pos, &[1.0], Some(&Tensor::of_slice(&[0_i64, 1]))).is_err());
+    }
+}
@@ -0,0 +1,796 @@
+use rayon::prelude::*;
+use tch::{Kind, Tensor};
+use crate::data::{CooGraphStorage, CscGraphStorage, CsrGraph};
+use crate::data::transform::{NormMode, normalize_weights};
+use crate::utils::{NodeIdx, parallel, TensorConversionError, TensorResult, try_tensor_to_slice};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reduce {
+    Sum,
+    Mean,
+    Max,
+    Min,
+    // Population standard deviation (no Bessel correction), so a single neighbor gives zero
+    Std,
+}
+
+// Aggregates x[j] over the neighbors j of every row, rows without neighbors are zero for every reduction
+pub fn spmm(
+    graph: &CsrGraph,
+    x: &Tensor,
+    reduce: Reduce,
+) -> TensorResult<Tensor> {
+    spmm_multi(graph, x, &[reduce]).map(|mut out| out.remove(0))
+}
+
+// Output rows processed at once when no chunk size is given, overridable through SPMM_CHUNK_ROWS_ENV
+pub const DEFAULT_SPMM_CHUNK_ROWS: usize = 1 << 14;
+pub const SPMM_CHUNK_ROWS_ENV: &str = "TCH_GEOMETRIC_SPMM_CHUNK_ROWS";
+
+fn default_chunk_rows() -> usize {
+    std::env::var(SPMM_CHUNK_ROWS_ENV).ok()
+        .and_then(|value| value.parse::<usize>().ok())
+        .filter(|rows| *rows > 0)
+        .unwrap_or(DEFAULT_SPMM_CHUNK_ROWS)
+}
+
+// Computes all `reduces` while gathering the neighbors only once, the output follows the order of `reduces`
+pub fn spmm_multi(
+    graph: &CsrGraph,
+    x: &Tensor,
+    reduces: &[Reduce],
+) -> TensorResult<Vec<Tensor>> {
+    spmm_multi_chunked(graph, x, reduces, None)
+}
+
+pub fn spmm_chunked(
+    graph: &CsrGraph,
+    x: &Tensor,
+    reduce: Reduce,
+    chunk_rows: Option<usize>,
+) -> TensorResult<Tensor> {
+    spmm_multi_chunked(graph, x, &[reduce], chunk_rows).map(|mut out| out.remove(0))
+}
+
+// Like `spmm_multi`, but only `chunk_rows` output rows are kept in double precision at a time before they are
+// written into the outputs, so the working set beyond x and the outputs is chunk_rows * len(reduces) * F doubles.
+// Float and double x are read in place, x of another kind is first copied as N * F doubles (and a non contiguous
+// x is copied contiguously). Without a chunk size DEFAULT_SPMM_CHUNK_ROWS (or the SPMM_CHUNK_ROWS_ENV variable)
+// is used. The result does not depend on the chunk size.
+pub fn spmm_multi_chunked(
+    graph: &CsrGraph,
+    x: &Tensor,
+    reduces: &[Reduce],
+    chunk_rows: Option<usize>,
+) -> TensorResult<Vec<Tensor>> {
+    spmm_impl(graph, x, reduces, chunk_rows, None)
+}
+
+// Like `spmm`, but reduces the messages edge_weight[e] * x[j], with `edge_weight` aligned with the edge positions
+// of `graph`. Mean still divides by the number of neighbors, as torch_sparse does.
+pub fn spmm_weighted(
+    graph: &CsrGraph,
+    x: &Tensor,
+    edge_weight: &Tensor,
+    reduce: Reduce,
+) -> TensorResult<Tensor> {
+    spmm_impl(graph, x, &[reduce], None, Some(edge_weight)).map(|mut out| out.remove(0))
+}
+
+fn spmm_impl(
+    graph: &CsrGraph,
+    x: &Tensor,
+    reduces: &[Reduce],
+    chunk_rows: Option<usize>,
+    edge_weight: Option<&Tensor>,
+) -> TensorResult<Vec<Tensor>> {
+    let shape = x.size();
+    if shape.is_empty() {
+        return Err(TensorConversionError::InvalidShape(Some("x must have at least one dimension".to_string())));
+    }
+    let chunk_rows = chunk_rows.unwrap_or_else(default_chunk_rows);
+    if chunk_rows == 0 {
+        return Err(TensorConversionError::Unknown("chunk_rows must be positive".to_string()));
+    }
+    let m = shape[0] as usize;
+    if let Some(j) = graph.indices.iter().find(|&&j| j < 0 || j as usize >= m) {
+        return Err(TensorConversionError::Unknown(format!("neighbor {} is out of bounds for {} rows of x", j, m)));
+    }
+
+    let n = graph.node_count();
+    let f = shape[1..].iter().product::<i64>() as usize;
+    let edge_weight = edge_weight.map(|w| w.to_kind(Kind::Double).contiguous());
+    let weight_data = edge_weight.as_ref().map(try_tensor_to_slice::<f64>).transpose()?;
+    if let Some(w) = weight_data.filter(|w| w.len() != graph.indices.len()) {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "edge_weight must have {} values, got {}", graph.indices.len(), w.len()
+        ))));
+    }
+
+    let mut out_shape = shape;
+    out_shape[0] = n as i64;
+    let outs: Vec<Tensor> = reduces.iter()
+        .map(|_| Tensor::zeros(&[n as i64, f as i64], (x.kind(), x.device())))
+        .collect();
+    let outs = match x.kind() {
+        Kind::Float => spmm_rows(graph, try_tensor_to_slice::<f32>(&x.contiguous())?, f, weight_data, reduces, chunk_rows, outs),
+        Kind::Double => spmm_rows(graph, try_tensor_to_slice::<f64>(&x.contiguous())?, f, weight_data, reduces, chunk_rows, outs),
+        _ => spmm_rows(graph, try_tensor_to_slice::<f64>(&x.to_kind(Kind::Double).contiguous())?, f, weight_data, reduces, chunk_rows, outs),
+    };
+    Ok(outs.into_iter().map(|out| out.reshape(&out_shape)).collect())
+}
+
+// Reduces the rows of x (N * F values of its own kind) in chunks of `chunk_rows` output rows into `outs`
+fn spmm_rows<T: Copy + Into<f64> + Sync>(
+    graph: &CsrGraph,
+    x_data: &[T],
+    f: usize,
+    weight_data: Option<&[f64]>,
+    reduces: &[Reduce],
+    chunk_rows: usize,
+    mut outs: Vec<Tensor>,
+) -> Vec<Tensor> {
+    let n = graph.node_count();
+    let r = reduces.len();
+    let needs_sum = reduces.iter().any(|reduce| matches!(reduce, Reduce::Sum | Reduce::Mean | Reduce::Std));
+    let needs_sq_sum = reduces.contains(&Reduce::Std);
+    let needs_max = reduces.contains(&Reduce::Max);
+    let needs_min = reduces.contains(&Reduce::Min);
+
+    // Every buffer row holds the reductions of one node next to each other
+    let mut buffer = vec![0.0_f64; chunk_rows.min(n) * r * f];
+    let mut begin = 0;
+    while begin < n && r * f > 0 {
+        let len = chunk_rows.min(n - begin);
+        let chunk = &mut buffer[..len * r * f];
+        chunk.iter_mut().for_each(|value| *value = 0.0);
+
+        parallel::install(|| chunk.par_chunks_mut(r * f).enumerate().for_each(|(i, row)| {
+            let neighbors = graph.neighbors_slice((begin + i) as NodeIdx);
+            if neighbors.is_empty() {
+                return;
+            }
+            let start = graph.ptrs[begin + i] as usize;
+
+            let mut sum = vec![0.0_f64; if needs_sum { f } else { 0 }];
+            let mut sq_sum = vec![0.0_f64; if needs_sq_sum { f } else { 0 }];
+            let mut max = vec![f64::NEG_INFINITY; if needs_max { f } else { 0 }];
+            let mut min = vec![f64::INFINITY; if needs_min { f } else { 0 }];
+            for (k, j) in neighbors.iter().enumerate() {
+                let x_j = x_data[*j as usize * f..(*j as usize + 1) * f].iter().map(|val| (*val).into());
+                let w = weight_data.map_or(1.0, |w| w[start + k]);
+                for (acc, val) in sum.iter_mut().zip(x_j.clone()) {
+                    *acc += w * val;
+                }
+                for (acc, val) in sq_sum.iter_mut().zip(x_j.clone()) {
+                    *acc += (w * val) * (w * val);
+                }
+                for (acc, val) in max.iter_mut().zip(x_j.clone()) {
+                    *acc = acc.max(w * val);
+                }
+                for (acc, val) in min.iter_mut().zip(x_j) {
+                    *acc = acc.min(w * val);
+                }
+            }
+
+            let count = neighbors.len() as f64;
+            for (reduce, dst) in reduces.iter().zip(row.chunks_mut(f)) {
+                match reduce {
+                    Reduce::Sum => dst.copy_from_slice(&sum),
+                    Reduce::Mean => {
+                        for (d, s) in dst.iter_mut().zip(sum.iter()) {
+                            *d = s / count;
+                        }
+                    }
+                    Reduce::Max => dst.copy_from_slice(&max),
+                    Reduce::Min => dst.copy_from_slice(&min),
+                    // E[x^2] - E[x]^2 can drop slightly below zero through cancellation
+                    Reduce::Std => {
+                        for ((d, s), sq) in dst.iter_mut().zip(sum.iter()).zip(sq_sum.iter()) {
+                            let mean = s / count;
+                            *d = (sq / count - mean * mean).max(0.0).sqrt();
+                        }
+                    }
+                }
+            }
+        }));
+
+        let chunk = Tensor::of_slice(chunk).view([len as i64, r as i64, f as i64]);
+        for (i, out) in outs.iter_mut().enumerate() {
+            out.narrow(0, begin as i64, len as i64).copy_(&chunk.select(1, i as i64));
+        }
+        begin += len;
+    }
+    outs
+}
+
+// Aggregates x[j] per relation, the output is [N, num_relations, F] where relations without edges at a node are
+// zero. `edge_type` is aligned with the edge positions of `graph`. With a weight of shape [num_relations, F, F_out]
+// every relation is transformed by its own matrix and the results are summed into [N, F_out].
+pub fn rgcn_aggregate(
+    graph: &CsrGraph,
+    edge_type: &Tensor,
+    x: &Tensor,
+    num_relations: i64,
+    reduce: Reduce,
+    weight: Option<&Tensor>,
+) -> TensorResult<Tensor> {
+    if x.dim() != 2 {
+        return Err(TensorConversionError::InvalidShape(Some(format!("x must be of shape [N, F], got {:?}", x.size()))));
+    }
+    if edge_type.size() != vec![graph.indices.len() as i64] {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "edge_type must be of shape [{}], got {:?}", graph.indices.len(), edge_type.size()
+        ))));
+    }
+    if num_relations < 0 {
+        return Err(TensorConversionError::Unknown(format!("num_relations must be non negative, got {}", num_relations)));
+    }
+    let (m, f) = (x.size()[0] as usize, x.size()[1] as usize);
+    if let Some(j) = graph.indices.iter().find(|&&j| j < 0 || j as usize >= m) {
+        return Err(TensorConversionError::Unknown(format!("neighbor {} is out of bounds for {} rows of x", j, m)));
+    }
+    let edge_type = edge_type.to_kind(Kind::Int64).contiguous();
+    let types = try_tensor_to_slice::<i64>(&edge_type)?;
+    if let Some(t) = types.iter().find(|&&t| !(0..num_relations).contains(&t)) {
+        return Err(TensorConversionError::Unknown(format!("edge type {} is out of bounds for {} relations", t, num_relations)));
+    }
+
+    let n = graph.node_count();
+    let r = num_relations as usize;
+    let x_data = x.to_kind(Kind::Double).contiguous();
+    let x_data = try_tensor_to_slice::<f64>(&x_data)?;
+
+    // Every output row holds the reductions of one node for all relations next to each other
+    let mut out = vec![0.0_f64; n * r * f];
+    if r * f > 0 {
+        parallel::install(|| out.par_chunks_mut(r * f).enumerate().for_each(|(v, row)| {
+            let (start, end) = (graph.ptrs[v] as usize, graph.ptrs[v + 1] as usize);
+            if start == end {
+                return;
+            }
+
+            let mut count = vec![0_usize; r];
+            let mut acc = vec![match reduce {
+                Reduce::Max => f64::NEG_INFINITY,
+                Reduce::Min => f64::INFINITY,
+                _ => 0.0,
+            }; r * f];
+            let mut sq_sum = vec![0.0_f64; if reduce == Reduce::Std { r * f } else { 0 }];
+            for (j, t) in graph.indices[start..end].iter().zip(types[start..end].iter()) {
+                let t = *t as usize;
+                let x_j = &x_data[*j as usize * f..(*j as usize + 1) * f];
+                count[t] += 1;
+                for (a, val) in acc[t * f..(t + 1) * f].iter_mut().zip(x_j.iter()) {
+                    *a = match reduce {
+                        Reduce::Max => a.max(*val),
+                        Reduce::Min => a.min(*val),
+                        _ => *a + val,
+                    };
+                }
+                if reduce == Reduce::Std {
+                    for (a, val) in sq_sum[t * f..(t + 1) * f].iter_mut().zip(x_j.iter()) {
+                        *a += val * val;
+                    }
+                }
+            }
+
+            for (t, c) in count.iter().enumerate().filter(|(_, c)| **c > 0) {
+                let c = *c as f64;
+                for k in t * f..(t + 1) * f {
+                    row[k] = match reduce {
+                        Reduce::Sum | Reduce::Max | Reduce::Min => acc[k],
+                        Reduce::Mean => acc[k] / c,
+                        Reduce::Std => {
+                            let mean = acc[k] / c;
+                            (sq_sum[k] / c - mean * mean).max(0.0).sqrt()
+                        }
+                    };
+                }
+            }
+        }));
+    }
+    let out = Tensor::of_slice(&out).view([n as i64, r as i64, f as i64]).to_kind(x.kind());
+
+    match weight {
+        None => Ok(out),
+        Some(weight) => {
+            if weight.dim() != 3 || weight.size()[..2] != [r as i64, f as i64] {
+                return Err(TensorConversionError::InvalidShape(Some(format!(
+                    "weight must be of shape [{}, {}, F_out], got {:?}", r, f, weight.size()
+                ))));
+            }
+            // [R, N, F] x [R, F, F_out] summed over the relations
+            Ok(out.transpose(0, 1).bmm(&weight.to_kind(x.kind())).sum_dim_intlist(&[0], false, x.kind()))
+        }
+    }
+}
+
+// Message x[src] * relation_weights[edge_type] of every edge of `edge_index`, as [E, F_out]. The edges are grouped
+// by relation so that every relation takes a single gather and matmul.
+pub fn relational_message(
+    x: &Tensor,
+    edge_index: &CooGraphStorage,
+    edge_type: &Tensor,
+    relation_weights: &Tensor,
+) -> TensorResult<Tensor> {
+    if x.dim() != 2 {
+        return Err(TensorConversionError::InvalidShape(Some(format!("x must be of shape [N, F], got {:?}", x.size()))));
+    }
+    let (m, f) = (x.size()[0], x.size()[1]);
+    if relation_weights.dim() != 3 || relation_weights.size()[1] != f {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "relation_weights must be of shape [R, {}, F_out], got {:?}", f, relation_weights.size()
+        ))));
+    }
+    let (num_relations, f_out) = (relation_weights.size()[0], relation_weights.size()[2]);
+    let row_col = edge_index.row_col.size();
+    if row_col.len() != 2 || row_col[0] != 2 {
+        return Err(TensorConversionError::InvalidShape(Some(format!("edge_index must be of shape [2, E], got {:?}", row_col))));
+    }
+    let num_edges = row_col[1];
+    if edge_type.size() != vec![num_edges] {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "edge_type must be of shape [{}], got {:?}", num_edges, edge_type.size()
+        ))));
+    }
+
+    let src = edge_index.row_col.select(0, 0).contiguous();
+    if let Some(j) = try_tensor_to_slice::<i64>(&src)?.iter().find(|&&j| j < 0 || j >= m) {
+        return Err(TensorConversionError::Unknown(format!("source node {} is out of bounds for {} rows of x", j, m)));
+    }
+    let edge_type = edge_type.to_kind(Kind::Int64).contiguous();
+    let mut counts = vec![0_i64; num_relations as usize];
+    for t in try_tensor_to_slice::<i64>(&edge_type)? {
+        if !(0..num_relations).contains(t) {
+            return Err(TensorConversionError::Unknown(format!("edge type {} is out of bounds for {} relations", t, num_relations)));
+        }
+        counts[*t as usize] += 1;
+    }
+
+    let weights = relation_weights.to_kind(x.kind());
+    let mut out = Tensor::zeros(&[num_edges, f_out], (x.kind(), x.device()));
+    let order = edge_type.argsort(0, false);
+    let mut offset = 0;
+    for (t, count) in counts.iter().enumerate().filter(|(_, count)| **count > 0) {
+        let edges = order.narrow(0, offset, *count);
+        let messages = x.index_select(0, &src.index_select(0, &edges)).mm(&weights.select(0, t as i64));
+        out.index_put_(&[Some(&edges)], &messages, false);
+        offset += count;
+    }
+    Ok(out)
+}
+
+// SIGN / SGC style propagated features [x, A x, ..., A^k x] where A is `graph` normalized with `mode`
+pub fn sign_features(
+    graph: &CsrGraph,
+    x: &Tensor,
+    k: usize,
+    mode: NormMode,
+) -> TensorResult<Vec<Tensor>> {
+    let mut out = Vec::with_capacity(k + 1);
+    sign_features_with(graph, x, k, mode, |_, features| out.push(features))?;
+    Ok(out)
+}
+
+// Like `sign_features`, but hands every hop to `callback` as soon as it is computed, so only two hops are kept in
+// double precision at a time. Hop 0 is x itself.
+pub fn sign_features_with(
+    graph: &CsrGraph,
+    x: &Tensor,
+    k: usize,
+    mode: NormMode,
+    mut callback: impl FnMut(usize, Tensor),
+) -> TensorResult<()> {
+    let shape = x.size();
+    let n = graph.node_count();
+    if shape.is_empty() || shape[0] != n as i64 {
+        return Err(TensorConversionError::InvalidShape(Some(format!("x must be of shape [{}, ...], got {:?}", n, shape))));
+    }
+    if let Some(j) = graph.indices.iter().find(|&&j| j < 0 || j as usize >= n) {
+        return Err(TensorConversionError::Unknown(format!("neighbor {} is out of bounds for {} nodes", j, n)));
+    }
+
+    // Row v aggregates its neighbors, which is the column v of the same pointers read as csc
+    let storage = CscGraphStorage::new(Tensor::of_slice(graph.ptrs), Tensor::of_slice(graph.indices), None);
+    let ones = Tensor::ones(&[graph.indices.len() as i64], (Kind::Double, tch::Device::Cpu));
+    let (_, weights) = normalize_weights(&storage, Some(&ones), mode, false)?;
+
+    // The hops stay in double precision, only the copies handed out are converted back
+    let mut cur = x.to_kind(Kind::Double);
+    callback(0, x.shallow_clone());
+    for hop in 1..=k {
+        cur = spmm_weighted(graph, &cur, &weights, Reduce::Sum)?;
+        callback(hop, cur.to_kind(x.kind()).to_device(x.device()));
+    }
+    Ok(())
+}
+
+pub type Aggregator = Reduce;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scaler {
+    Identity,
+    // Multiplies by log(deg + 1) / avg_deg_log
+    Amplification,
+    // Divides by log(deg + 1) / avg_deg_log
+    Attenuation,
+}
+
+// Principal Neighbourhood Aggregation, the output is [N, len(scalers) * len(aggregators) * F] ordered by scaler,
+// then aggregator. Isolated nodes are scaled as if they had degree one, their aggregations are zero anyway.
+pub fn pna_aggregate(
+    graph: &CsrGraph,
+    x: &Tensor,
+    aggregators: &[Aggregator],
+    scalers: &[Scaler],
+    avg_deg_log: f64,
+) -> TensorResult<Tensor> {
+    if x.dim() != 2 {
+        return Err(TensorConversionError::InvalidShape(Some(format!("x must be of shape [N, F], got {:?}", x.size()))));
+    }
+    if avg_deg_log.is_nan() || avg_deg_log <= 0.0 {
+        return Err(TensorConversionError::Unknown(format!("avg_deg_log must be positive, got {}", avg_deg_log)));
+    }
+
+    let n = graph.node_count();
+    let aggregated = Tensor::cat(&spmm_multi(graph, x, aggregators)?, 1);
+    let scale: Vec<f64> = (0..n)
+        .map(|v| ((graph.out_degree(v as NodeIdx).max(1) + 1) as f64).ln() / avg_deg_log)
+        .collect();
+    let scale = Tensor::of_slice(&scale).view([n as i64, 1]).to_kind(x.kind());
+
+    let scaled: Vec<Tensor> = scalers.iter()
+        .map(|scaler| match scaler {
+            Scaler::Identity => aggregated.shallow_clone(),
+            Scaler::Amplification => &aggregated * &scale,
+            Scaler::Attenuation => &aggregated / &scale,
+        })
+        .collect();
+    Ok(Tensor::cat(&scaled, 1).view([n as i64, -1]))
+}
+
+// GraphSAGE update input [x[v] || reduce over the neighbors j of v of neighbors[j]] as [N, 2F]. The self features
+// are the first N rows of x, which for sampled blocks are the destination nodes.
+fn sage_aggregate(
+    graph: &CsrGraph,
+    x: &Tensor,
+    neighbors: &Tensor,
+    reduce: Reduce,
+) -> TensorResult<Tensor> {
+    if x.dim() != 2 || neighbors.dim() != 2 {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "x and neighbor features must be of shape [N, F], got {:?} and {:?}", x.size(), neighbors.size()
+        ))));
+    }
+    let n = graph.node_count() as i64;
+    if x.size()[0] < n {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "x has {} rows, but the graph has {} nodes", x.size()[0], n
+        ))));
+    }
+    let aggregated = spmm(graph, neighbors, reduce)?;
+    Ok(Tensor::cat(&[x.narrow(0, 0, n), aggregated.to_kind(x.kind())], 1))
+}
+
+pub fn sage_mean_aggregate(
+    graph: &CsrGraph,
+    x: &Tensor,
+) -> TensorResult<Tensor> {
+    sage_aggregate(graph, x, x, Reduce::Mean)
+}
+
+pub fn sage_max_aggregate(
+    graph: &CsrGraph,
+    x: &Tensor,
+) -> TensorResult<Tensor> {
+    sage_aggregate(graph, x, x, Reduce::Max)
+}
+
+// Pooling aggregator, `pooled` is x after the user's linear layer and activation and is max reduced over the
+// neighbors, the self features are still taken from x
+pub fn sage_pool_aggregate(
+    graph: &CsrGraph,
+    x: &Tensor,
+    pooled: &Tensor,
+) -> TensorResult<Tensor> {
+    sage_aggregate(graph, x, pooled, Reduce::Max)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use tch::{Device, Kind, Tensor};
+    use crate::algo::spmm::{Reduce, Scaler, pna_aggregate, relational_message, rgcn_aggregate, sage_max_aggregate, sage_mean_aggregate, sage_pool_aggregate, sign_features, sign_features_with, spmm, spmm_chunked, spmm_multi, spmm_multi_chunked, spmm_weighted};
+    use crate::data::{CooGraphStorage, CsrGraph, CsrGraphStorage};
+    use crate::data::transform::NormMode;
+
+    #[test]
+    fn test_spmm_multi() {
+        let rows = Tensor::of_slice(&[0_i64, 0, 1, 2, 2, 2]);
+        let cols = Tensor::of_slice(&[1_i64, 2, 0, 0, 1, 3]);
+        let coo = CooGraphStorage::new(Tensor::stack(&[rows, cols], 0), (4, 4));
+        let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let x = Tensor::of_slice(&[1.0_f32, -2.0, 3.0, 4.0, -5.0, 6.0, 0.5, 8.0]).view([4, 2]);
+        let reduces = [Reduce::Max, Reduce::Sum, Reduce::Mean];
+        let out = spmm_multi(&graph, &x, &reduces).unwrap();
+        assert_eq!(out.len(), 3);
+
+        let max: Vec<f32> = out[0].view([-1]).into();
+        let sum: Vec<f32> = out[1].view([-1]).into();
+        let mean: Vec<f32> = out[2].view([-1]).into();
+        assert_eq!(max, vec![3.0, 6.0, 1.0, -2.0, 3.0, 8.0, 0.0, 0.0]);
+        assert_eq!(sum, vec![-2.0, 10.0, 1.0, -2.0, 4.5, 10.0, 0.0, 0.0]);
+        assert_eq!(mean, vec![-1.0, 5.0, 1.0, -2.0, 1.5, 10.0 / 3.0, 0.0, 0.0]);
+        assert_eq!(out[0].kind(), Kind::Float);
+
+        // A single pass matches the single reductions on higher dimensional features
+        let x = Tensor::rand(&[4, 3, 2], (Kind::Double, Device::Cpu));
+        let out = spmm_multi(&graph, &x, &reduces).unwrap();
+        for (reduce, multi) in reduces.iter().zip(out.iter()) {
+            let single = spmm(&graph, &x, *reduce).unwrap();
+            assert_eq!(single.size(), vec![4, 3, 2]);
+            assert!(single.equal(multi));
+        }
+
+        assert!(spmm(&graph, &Tensor::zeros(&[3, 2], (Kind::Float, Device::Cpu)), Reduce::Sum).is_err());
+    }
+
+    #[test]
+    fn test_spmm_min_std() {
+        let rows = Tensor::of_slice(&[0_i64, 0, 1, 2, 2, 2]);
+        let cols = Tensor::of_slice(&[1_i64, 2, 0, 0, 1, 3]);
+        let coo = CooGraphStorage::new(Tensor::stack(&[rows, cols], 0), (4, 4));
+        let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let x = Tensor::of_slice(&[1.0_f64, -2.0, 3.0, 4.0, -5.0, 6.0, 2.0, 8.0]).view([4, 2]);
+        let out = spmm_multi(&graph, &x, &[Reduce::Min, Reduce::Std]).unwrap();
+
+        let min: Vec<f64> = out[0].view([-1]).into();
+        assert_eq!(min, vec![-5.0, 4.0, 1.0, -2.0, 1.0, -2.0, 0.0, 0.0]);
+
+        // Node 0 averages {3, -5} and {4, 6}, node 2 averages {1, 3, 2} and {-2, 4, 8}
+        let std: Vec<f64> = out[1].view([-1]).into();
+        let expected = [
+            4.0, 1.0,
+            0.0, 0.0,
+            (2.0_f64 / 3.0).sqrt(), (152.0_f64 / 9.0).sqrt(),
+            0.0, 0.0,
+        ];
+        for (s, e) in std.iter().zip(expected.iter()) {
+            assert!((s - e).abs() < 1e-12, "{} != {}", s, e);
+        }
+    }
+
+    #[test]
+    fn test_spmm_weighted() {
+        let rows = Tensor::of_slice(&[0_i64, 0, 1, 2, 2, 2]);
+        let cols = Tensor::of_slice(&[1_i64, 2, 0, 0, 1, 3]);
+        let coo = CooGraphStorage::new(Tensor::stack(&[rows, cols], 0), (4, 4));
+        let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let x = Tensor::of_slice(&[1.0_f64, -2.0, 3.0, 4.0, -5.0, 6.0, 2.0, 8.0]).view([4, 2]);
+        let weights = Tensor::of_slice(&[2.0_f64, -1.0, 3.0, 0.5, 1.0, 4.0]);
+        let weighted = |reduce| -> Vec<f64> { spmm_weighted(&graph, &x, &weights, reduce).unwrap().view([-1]).into() };
+        assert_eq!(weighted(Reduce::Sum), vec![11.0, 2.0, 3.0, -6.0, 11.5, 35.0, 0.0, 0.0]);
+        assert_eq!(weighted(Reduce::Max), vec![6.0, 8.0, 3.0, -6.0, 8.0, 32.0, 0.0, 0.0]);
+        assert_eq!(weighted(Reduce::Mean), vec![5.5, 1.0, 3.0, -6.0, 11.5 / 3.0, 35.0 / 3.0, 0.0, 0.0]);
+
+        // Unit weights are the unweighted reduction
+        let ones = Tensor::ones(&[6], (Kind::Double, Device::Cpu));
+        assert!(spmm_weighted(&graph, &x, &ones, Reduce::Std).unwrap().equal(&spmm(&graph, &x, Reduce::Std).unwrap()));
+        assert!(spmm_weighted(&graph, &x, &weights.narrow(0, 0, 5), Reduce::Sum).is_err());
+    }
+
+    #[test]
+    fn test_pna_aggregate() {
+        let rows = Tensor::of_slice(&[0_i64, 0, 1, 2, 2, 2]);
+        let cols = Tensor::of_slice(&[1_i64, 2, 0, 0, 1, 3]);
+        let coo = CooGraphStorage::new(Tensor::stack(&[rows, cols], 0), (4, 4));
+        let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let x = Tensor::rand(&[4, 3], (Kind::Double, Device::Cpu));
+        let aggregators = [Reduce::Mean, Reduce::Max];
+        let scalers = [Scaler::Identity, Scaler::Amplification, Scaler::Attenuation];
+        let avg_deg_log = 2.0_f64.ln();
+        let out = pna_aggregate(&graph, &x, &aggregators, &scalers, avg_deg_log).unwrap();
+        assert_eq!(out.size(), vec![4, (aggregators.len() * scalers.len() * 3) as i64]);
+
+        let aggregated = Tensor::cat(&spmm_multi(&graph, &x, &aggregators).unwrap(), 1);
+        assert!(out.narrow(1, 0, 6).equal(&aggregated));
+        // Node 2 has three neighbors, so it is scaled by log(4) / log(2) = 2
+        let amplified: Tensor = out.narrow(1, 6, 6).select(0, 2) - aggregated.select(0, 2) * 2.0;
+        let attenuated: Tensor = out.narrow(1, 12, 6).select(0, 2) - aggregated.select(0, 2) / 2.0;
+        assert!(amplified.abs().max().double_value(&[]) < 1e-9);
+        assert!(attenuated.abs().max().double_value(&[]) < 1e-9);
+        // The isolated node stays zero and finite for every scaler
+        assert_eq!(out.select(0, 3).abs().sum(Kind::Double).double_value(&[]), 0.0);
+
+        let out = pna_aggregate(&graph, &x, &[Reduce::Sum], &[Scaler::Amplification], avg_deg_log).unwrap();
+        assert_eq!(out.size(), vec![4, 3]);
+        assert!(pna_aggregate(&graph, &x, &aggregators, &scalers, 0.0).is_err());
+        assert!(pna_aggregate(&graph, &x.view([4, 3, 1]), &aggregators, &scalers, avg_deg_log).is_err());
+    }
+
+    #[test]
+    fn test_rgcn_aggregate() {
+        // Node 0 has neighbors 1 (relation 0) and 2, 3 (relation 1), node 1 only has neighbor 0 (relation 1)
+        let rows = Tensor::of_slice(&[0_i64, 0, 0, 1]);
+        let cols = Tensor::of_slice(&[1_i64, 2, 3, 0]);
+        let coo = CooGraphStorage::new(Tensor::stack(&[rows, cols], 0), (4, 4));
+        let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let edge_type = Tensor::of_slice(&[0_i64, 1, 1, 1]);
+
+        let x = Tensor::of_slice(&[1.0_f32, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0]).view([4, 2]);
+        let sum = rgcn_aggregate(&graph, &edge_type, &x, 2, Reduce::Sum, None).unwrap();
+        assert_eq!(sum.size(), vec![4, 2, 2]);
+        assert_eq!(sum.kind(), Kind::Float);
+        let sum_data: Vec<f32> = sum.view([-1]).into();
+        assert_eq!(sum_data, vec![
+            3.0, 4.0, 12.0, 14.0,
+            0.0, 0.0, 1.0, 2.0,
+            0.0, 0.0, 0.0, 0.0,
+            0.0, 0.0, 0.0, 0.0,
+        ]);
+        let mean: Vec<f32> = rgcn_aggregate(&graph, &edge_type, &x, 2, Reduce::Mean, None).unwrap().view([-1]).into();
+        assert_eq!(&mean[..4], &[3.0, 4.0, 6.0, 7.0]);
+        let max: Vec<f32> = rgcn_aggregate(&graph, &edge_type, &x, 2, Reduce::Max, None).unwrap().view([-1]).into();
+        assert_eq!(&max[..8], &[3.0, 4.0, 7.0, 8.0, 0.0, 0.0, 1.0, 2.0]);
+
+        // A single relation matches spmm
+        let single = rgcn_aggregate(&graph, &Tensor::zeros(&[4], (Kind::Int64, Device::Cpu)), &x, 1, Reduce::Std, None).unwrap();
+        assert!(single.view([4, 2]).equal(&spmm(&graph, &x, Reduce::Std).unwrap()));
+
+        // Per relation weights are applied before summing over the relations
+        let weight = Tensor::stack(&[Tensor::eye(2, (Kind::Float, Device::Cpu)), Tensor::eye(2, (Kind::Float, Device::Cpu)) * 2.0], 0);
+        let out = rgcn_aggregate(&graph, &edge_type, &x, 2, Reduce::Sum, Some(&weight)).unwrap();
+        let out_data: Vec<f32> = out.view([-1]).into();
+        assert_eq!(out_data, vec![27.0, 32.0, 2.0, 4.0, 0.0, 0.0, 0.0, 0.0]);
+
+        assert!(rgcn_aggregate(&graph, &Tensor::of_slice(&[0_i64, 1, 2, 1]), &x, 2, Reduce::Sum, None).is_err());
+        assert!(rgcn_aggregate(&graph, &Tensor::of_slice(&[0_i64, 1]), &x, 2, Reduce::Sum, None).is_err());
+        assert!(rgcn_aggregate(&graph, &edge_type, &x, 2, Reduce::Sum, Some(&weight.narrow(0, 0, 1))).is_err());
+    }
+
+    #[test]
+    fn test_relational_message() {
+        let rows = Tensor::of_slice(&[0_i64, 2, 1, 3, 0]);
+        let cols = Tensor::of_slice(&[1_i64, 0, 2, 2, 3]);
+        let coo = CooGraphStorage::new(Tensor::stack(&[rows, cols], 0), (4, 4));
+        let edge_type = Tensor::of_slice(&[1_i64, 0, 2, 1, 0]);
+        let x = Tensor::rand(&[4, 3], (Kind::Double, Device::Cpu));
+        let weights = Tensor::rand(&[3, 3, 2], (Kind::Double, Device::Cpu));
+
+        let out = relational_message(&x, &coo, &edge_type, &weights).unwrap();
+        assert_eq!(out.size(), vec![5, 2]);
+        assert_eq!(out.kind(), Kind::Double);
+        for (e, (src, t)) in [(0_i64, 1_i64), (2, 0), (1, 2), (3, 1), (0, 0)].iter().enumerate() {
+            let expected = x.select(0, *src).view([1, 3]).mm(&weights.select(0, *t)).view([2]);
+            let diff: Tensor = out.select(0, e as i64) - expected;
+            assert!(diff.abs().max().double_value(&[]) < 1e-12);
+        }
+
+        // Relations without edges are skipped, an empty graph gives an empty message tensor
+        let out = relational_message(&x, &coo, &Tensor::of_slice(&[2_i64, 2, 2, 2, 2]), &weights).unwrap();
+        assert!(out.equal(&x.index_select(0, &coo.row_col.select(0, 0)).mm(&weights.select(0, 2))));
+        let empty = CooGraphStorage::new(Tensor::zeros(&[2, 0], (Kind::Int64, Device::Cpu)), (4, 4));
+        let out = relational_message(&x, &empty, &Tensor::zeros(&[0], (Kind::Int64, Device::Cpu)), &weights).unwrap();
+        assert_eq!(out.size(), vec![0, 2]);
+
+        assert!(relational_message(&x, &coo, &Tensor::of_slice(&[0_i64, 0, 3, 0, 0]), &weights).is_err());
+        assert!(relational_message(&x, &coo, &Tensor::of_slice(&[0_i64, 0]), &weights).is_err());
+        assert!(relational_message(&x, &coo, &edge_type, &weights.narrow(1, 0, 2)).is_err());
+        assert!(relational_message(&x.narrow(0, 0, 3), &coo, &edge_type, &weights).is_err());
+    }
+
+    #[test]
+    fn test_sign_features() {
+        let rows = Tensor::of_slice(&[0_i64, 0, 1, 2, 2, 2]);
+        let cols = Tensor::of_slice(&[1_i64, 2, 0, 0, 1, 3]);
+        let coo = CooGraphStorage::new(Tensor::stack(&[rows, cols], 0), (4, 4));
+        let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let x = Tensor::rand(&[4, 3], (Kind::Double, Device::Cpu));
+        let close = |a: &Tensor, b: &Tensor| (a - b).abs().max().double_value(&[]) < 1e-12;
+
+        let hops = sign_features(&graph, &x, 2, NormMode::Col).unwrap();
+        assert_eq!(hops.len(), 3);
+        assert!(hops[0].equal(&x));
+        let once = spmm(&graph, &x, Reduce::Mean).unwrap();
+        assert!(close(&hops[1], &once));
+        assert!(close(&hops[2], &spmm(&graph, &once, Reduce::Mean).unwrap()));
+
+        // Node 0 has neighbors 1 (one neighbor) and 2 (three neighbors)
+        let sym = sign_features(&graph, &x, 1, NormMode::Sym).unwrap();
+        let expected = x.select(0, 1) / 2.0_f64.sqrt() + x.select(0, 2) / 6.0_f64.sqrt();
+        assert!(close(&sym[1].select(0, 0), &expected));
+        // Node 3 has no neighbors and only occurs in the row of node 2
+        let row = sign_features(&graph, &x, 1, NormMode::Row).unwrap();
+        assert!(close(&row[1].select(0, 2), &(x.select(0, 0) / 2.0 + x.select(0, 1) / 2.0 + x.select(0, 3))));
+        assert_eq!(row[1].select(0, 3).abs().sum(Kind::Double).double_value(&[]), 0.0);
+
+        let mut seen = Vec::new();
+        let x_float = x.to_kind(Kind::Float).view([4, 3, 1]);
+        sign_features_with(&graph, &x_float, 3, NormMode::Col, |hop, features| {
+            assert_eq!(features.size(), vec![4, 3, 1]);
+            assert_eq!(features.kind(), Kind::Float);
+            seen.push(hop);
+        }).unwrap();
+        assert_eq!(seen, vec![0, 1, 2, 3]);
+        assert!(sign_features(&graph, &x.narrow(0, 0, 3), 1, NormMode::Col).is_err());
+    }
+
+    #[test]
+    fn test_spmm_chunked() {
+        let (n, e) = (11_i64, 40_i64);
+        let row_col = Tensor::randint(n, &[2, e], (Kind::Int64, Device::Cpu));
+        let coo = CooGraphStorage::new(row_col, (n, n));
+        let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+        let x = Tensor::rand(&[n, 3, 2], (Kind::Float, Device::Cpu));
+
+        let reduces = [Reduce::Sum, Reduce::Mean, Reduce::Max, Reduce::Min, Reduce::Std];
+        let expected = spmm_multi_chunked(&graph, &x, &reduces, Some(usize::MAX)).unwrap();
+        for chunk_rows in [1, 2, 3, 10, 11, 100] {
+            let out = spmm_multi_chunked(&graph, &x, &reduces, Some(chunk_rows)).unwrap();
+            for (a, b) in out.iter().zip(expected.iter()) {
+                assert_eq!(a.size(), vec![n, 3, 2]);
+                assert_eq!(a.kind(), Kind::Float);
+                assert!(a.equal(b));
+            }
+        }
+        assert!(spmm_chunked(&graph, &x, Reduce::Sum, Some(4)).unwrap().equal(&spmm(&graph, &x, Reduce::Sum).unwrap()));
+        assert!(spmm_chunked(&graph, &x, Reduce::Sum, Some(0)).is_err());
+
+        // Float x is read in place, as double or non contiguous it reduces the same values
+        let as_double = spmm_multi(&graph, &x.to_kind(Kind::Double), &reduces).unwrap();
+        let strided = x.transpose(1, 2).contiguous().transpose(1, 2);
+        assert!(!strided.is_contiguous());
+        let from_strided = spmm_multi(&graph, &strided, &reduces).unwrap();
+        for ((a, b), c) in expected.iter().zip(as_double.iter()).zip(from_strided.iter()) {
+            assert_eq!(b.kind(), Kind::Double);
+            assert!(a.equal(&b.to_kind(Kind::Float)));
+            assert!(a.equal(c));
+        }
+    }
+
+    #[test]
+    fn test_sage_aggregate() {
+        let rows = Tensor::of_slice(&[0_i64, 0, 1, 2, 2, 2]);
+        let cols = Tensor::of_slice(&[1_i64, 2, 0, 0, 1, 3]);
+        let coo = CooGraphStorage::new(Tensor::stack(&[rows, cols], 0), (4, 4));
+        let graph_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let graph = CsrGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let x = Tensor::of_slice(&[1.0_f32, -2.0, 3.0, 4.0, -5.0, 6.0, 0.5, 8.0]).view([4, 2]);
+        let out = sage_mean_aggregate(&graph, &x).unwrap();
+        assert_eq!(out.size(), vec![4, 4]);
+        assert!(out.narrow(1, 0, 2).equal(&x));
+        // Node 2 averages nodes 0, 1 and 3, the isolated node 3 aggregates to zero
+        let mean: Vec<f32> = out.narrow(1, 2, 2).select(0, 2).into();
+        assert_eq!(mean, vec![1.5, 10.0 / 3.0]);
+        let isolated: Vec<f32> = out.narrow(1, 2, 2).select(0, 3).into();
+        assert_eq!(isolated, vec![0.0, 0.0]);
+
+        let max: Vec<f32> = sage_max_aggregate(&graph, &x).unwrap().narrow(1, 2, 2).select(0, 0).into();
+        assert_eq!(max, vec![3.0, 6.0]);
+
+        let pooled = x.relu();
+        let out = sage_pool_aggregate(&graph, &x, &pooled).unwrap();
+        assert!(out.narrow(1, 0, 2).equal(&x));
+        let pool: Vec<f32> = out.narrow(1, 2, 2).select(0, 1).into();
+        assert_eq!(pool, vec![1.0, 0.0]);
+
+        // Bipartite blocks only keep the destination rows of x as self features
+        let block = CsrGraph::new(&graph.ptrs[..3], &graph.indices[..3]);
+        assert_eq!(sage_mean_aggregate(&block, &x).unwrap().size(), vec![2, 4]);
+        assert!(sage_mean_aggregate(&graph, &x.narrow(0, 0, 3)).is_err());
+        assert!(sage_mean_aggregate(&graph, &x.view([4, 2, 1])).is_err());
+    }
+}
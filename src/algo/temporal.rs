@@ -0,0 +1,177 @@
+use std::convert::TryFrom;
+use rayon::prelude::*;
+use tch::{Kind, Tensor};
+use crate::data::{CscGraph, CscGraphStorage, NeighborOrdering, NeighborSource};
+use crate::utils::{NodeIdx, parallel, TensorConversionError, TensorResult, try_tensor_to_slice, try_tensor_to_slice_mut};
+
+const QUERY_CHUNK: usize = 1024;
+
+// For every (node, time) query the k most recent edges strictly before `time`, or with `forward` the k oldest
+// edges strictly after it. Edges at exactly the query time are excluded in both directions so that an event never
+// sees itself. Returns [Q, k] neighbor ids, edge positions and timestamps in chronological order, padded with -1.
+// The graph has to be sorted by time, `timestamps` is aligned with its edge positions.
+pub fn recent_neighbors(
+    graph: &CscGraphStorage,
+    timestamps: &Tensor,
+    nodes: &Tensor,
+    times: &Tensor,
+    k: i64,
+    forward: bool,
+) -> TensorResult<(Tensor, Tensor, Tensor)> {
+    graph.require_ordering(NeighborOrdering::ByTime)
+        .map_err(|e| TensorConversionError::Unknown(e.to_string()))?;
+    if timestamps.size() != graph.indices.size() {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "timestamps must be of shape {:?}, got {:?}", graph.indices.size(), timestamps.size()
+        ))));
+    }
+    if nodes.size() != times.size() || nodes.dim() != 1 {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "nodes and times must be vectors of the same length, got {:?} and {:?}", nodes.size(), times.size()
+        ))));
+    }
+    if k < 0 {
+        return Err(TensorConversionError::Unknown(format!("k must be non negative, got {}", k)));
+    }
+
+    let view = CscGraph::<i64, i64>::try_from(graph)?;
+    let timestamps = timestamps.to_kind(Kind::Int64).contiguous();
+    let timestamps_data = try_tensor_to_slice::<i64>(&timestamps)?;
+    let nodes = nodes.to_kind(Kind::Int64).contiguous();
+    let nodes_data = try_tensor_to_slice::<i64>(&nodes)?;
+    let times = times.to_kind(Kind::Int64).contiguous();
+    let times_data = try_tensor_to_slice::<i64>(&times)?;
+    if let Some(v) = nodes_data.iter().find(|&&v| !(0..view.node_count() as NodeIdx).contains(&v)) {
+        return Err(TensorConversionError::Unknown(format!("node {} is out of bounds", v)));
+    }
+
+    let q = nodes_data.len() as i64;
+    let options = (Kind::Int64, graph.indices.device());
+    let mut neighbors = Tensor::full(&[q, k], -1_i64, options);
+    let mut positions = Tensor::full(&[q, k], -1_i64, options);
+    let mut neighbor_times = Tensor::full(&[q, k], -1_i64, options);
+    if q == 0 || k == 0 {
+        return Ok((neighbors, positions, neighbor_times));
+    }
+
+    let k = k as usize;
+    let neighbors_data = try_tensor_to_slice_mut::<i64>(&mut neighbors)?;
+    let positions_data = try_tensor_to_slice_mut::<i64>(&mut positions)?;
+    let neighbor_times_data = try_tensor_to_slice_mut::<i64>(&mut neighbor_times)?;
+
+    // The outputs are written in place, chunks of queries amortize the scheduling
+    parallel::install(|| neighbors_data.par_chunks_mut(k * QUERY_CHUNK)
+        .zip(positions_data.par_chunks_mut(k * QUERY_CHUNK))
+        .zip(neighbor_times_data.par_chunks_mut(k * QUERY_CHUNK))
+        .enumerate()
+        .for_each(|(chunk, ((neighbors, positions), neighbor_times))| {
+            let begin = chunk * QUERY_CHUNK;
+            for (i, ((row_neighbors, row_positions), row_times)) in neighbors.chunks_mut(k)
+                .zip(positions.chunks_mut(k))
+                .zip(neighbor_times.chunks_mut(k))
+                .enumerate()
+            {
+                let (v, t) = (nodes_data[begin + i], times_data[begin + i]);
+                let range = view.edge_positions(v);
+                let block = &timestamps_data[range.clone()];
+                let selected = if forward {
+                    let first = block.partition_point(|s| *s <= t);
+                    range.start + first..range.start + (first + k).min(block.len())
+                } else {
+                    let end = block.partition_point(|s| *s < t);
+                    range.start + end.saturating_sub(k)..range.start + end
+                };
+
+                for (j, p) in selected.enumerate() {
+                    row_neighbors[j] = view.neighbor_at(p);
+                    row_positions[j] = p as i64;
+                    row_times[j] = timestamps_data[p];
+                }
+            }
+        }));
+
+    Ok((neighbors, positions, neighbor_times))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use rand::{Rng, SeedableRng};
+    use tch::Tensor;
+    use crate::data::{CooGraphStorage, CscGraph, CscGraphStorage, load_karate_graph};
+    use super::recent_neighbors;
+
+    // Scan over all edges of the node, sorted by (time, position)
+    fn reference(graph: &CscGraph<i64, i64>, timestamps: &[i64], v: i64, t: i64, k: usize, forward: bool) -> Vec<(i64, i64, i64)> {
+        let mut edges: Vec<(i64, i64, i64)> = graph.neighbors_range(v)
+            .filter(|p| if forward { timestamps[*p] > t } else { timestamps[*p] < t })
+            .map(|p| (graph.get_by_ptr(p), p as i64, timestamps[p]))
+            .collect();
+        edges.sort_by_key(|(_, p, s)| (*s, *p));
+        if forward {
+            edges.truncate(k);
+        } else {
+            edges.drain(..edges.len().saturating_sub(k));
+        }
+        edges
+    }
+
+    fn rows(x: &Tensor) -> Vec<Vec<i64>> {
+        (0..x.size()[0]).map(|i| Vec::<i64>::from(&x.select(0, i))).collect()
+    }
+
+    #[test]
+    fn test_recent_neighbors() {
+        let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+        let (_x, _, coo) = load_karate_graph();
+        let csc = CscGraphStorage::try_from(&coo).unwrap();
+        let times: Vec<i64> = (0..csc.indices.size()[0]).map(|_| rng.gen_range(0..20)).collect();
+        assert!(recent_neighbors(&csc, &Tensor::of_slice(&times), &Tensor::of_slice(&[0_i64]), &Tensor::of_slice(&[5_i64]), 2, false).is_err());
+
+        let sorted = csc.sort_neighbors_by_time(&Tensor::of_slice(&times)).unwrap();
+        // Carry the timestamps along the sort via the composed perm
+        let perm_by_id: Vec<i64> = csc.perm().unwrap().into();
+        let perm_by_time: Vec<i64> = sorted.perm().unwrap().into();
+        let times_sorted: Vec<i64> = perm_by_time.iter().map(|e| times[perm_by_id.iter().position(|f| f == e).unwrap()]).collect();
+        let graph = CscGraph::<i64, i64>::try_from(&sorted).unwrap();
+
+        let nodes: Vec<i64> = (0..200).map(|_| rng.gen_range(0..34)).collect();
+        let query_times: Vec<i64> = (0..200).map(|_| rng.gen_range(0..22)).collect();
+        for forward in [false, true] {
+            let (neighbors, positions, neighbor_times) = recent_neighbors(
+                &sorted, &Tensor::of_slice(&times_sorted), &Tensor::of_slice(&nodes), &Tensor::of_slice(&query_times), 3, forward,
+            ).unwrap();
+            assert_eq!(neighbors.size(), vec![200, 3]);
+            let (neighbors, positions, neighbor_times) = (rows(&neighbors), rows(&positions), rows(&neighbor_times));
+            for (i, (v, t)) in nodes.iter().zip(query_times.iter()).enumerate() {
+                let expected = reference(&graph, &times_sorted, *v, *t, 3, forward);
+                for j in 0..3 {
+                    let actual = (neighbors[i][j], positions[i][j], neighbor_times[i][j]);
+                    assert_eq!(actual, expected.get(j).cloned().unwrap_or((-1, -1, -1)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_recent_neighbors_ties() {
+        // Node 0 has edges from 1, 2, 3 at times 5, 5, 7, which already is the time order
+        let row_col = Tensor::stack(&[Tensor::of_slice(&[1_i64, 2, 3]), Tensor::of_slice(&[0_i64, 0, 0])], 0);
+        let csc = CscGraphStorage::try_from(&CooGraphStorage::new(row_col, (4, 4))).unwrap();
+        let times = Tensor::of_slice(&[5_i64, 5, 7]);
+        let sorted = csc.sort_neighbors_by_time(&times).unwrap();
+        let run = |t: i64, forward: bool| {
+            let (neighbors, _, neighbor_times) = recent_neighbors(
+                &sorted, &times, &Tensor::of_slice(&[0_i64, 1]), &Tensor::of_slice(&[t, t]), 2, forward,
+            ).unwrap();
+            (rows(&neighbors), rows(&neighbor_times))
+        };
+
+        // Edges at the query time are excluded
+        assert_eq!(run(5, false), (vec![vec![-1, -1], vec![-1, -1]], vec![vec![-1, -1], vec![-1, -1]]));
+        assert_eq!(run(6, false).0, vec![vec![1, 2], vec![-1, -1]]);
+        assert_eq!(run(8, false), (vec![vec![2, 3], vec![-1, -1]], vec![vec![5, 7], vec![-1, -1]]));
+        assert_eq!(run(5, true).0, vec![vec![3, -1], vec![-1, -1]]);
+        assert_eq!(run(4, true).0, vec![vec![1, 2], vec![-1, -1]]);
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::{BTreeMap, HashMap};
+use rand::Rng;
+use crate::data::{CooGraphBuilder, CscGraph, CsrGraph};
+use crate::utils::{NodeIdx, reservoir_sampling};
+
+pub struct TwoHopSamples {
+    // Seed users come first, followed by the users sampled in the second hop
+    pub users: Vec<NodeIdx>,
+    pub items: Vec<NodeIdx>,
+    // (item, seed user) edges pointing into `user_items`
+    pub user_item: CooGraphBuilder,
+    // (user, item) edges pointing into `item_users`
+    pub item_user: CooGraphBuilder,
+}
+
+impl TwoHopSamples {
+    // Collapses user -> item -> user paths into direct (user, seed user, shared item count) edges on global ids
+    pub fn collapse(&self) -> (Vec<NodeIdx>, Vec<NodeIdx>, Vec<i64>) {
+        let mut item_seeds: Vec<Vec<NodeIdx>> = vec![Vec::new(); self.items.len()];
+        for (item, seed) in self.user_item.iter_edges() {
+            item_seeds[item as usize].push(self.users[seed as usize]);
+        }
+
+        let mut shared: BTreeMap<(NodeIdx, NodeIdx), Vec<NodeIdx>> = BTreeMap::new();
+        for (user, item) in self.item_user.iter_edges() {
+            let user = self.users[user as usize];
+            for seed in item_seeds[item as usize].iter().cloned() {
+                if seed != user {
+                    shared.entry((seed, user)).or_default().push(self.items[item as usize]);
+                }
+            }
+        }
+
+        let mut rows = Vec::with_capacity(shared.len());
+        let mut cols = Vec::with_capacity(shared.len());
+        let mut weights = Vec::with_capacity(shared.len());
+        for ((seed, user), mut items) in shared {
+            items.sort_unstable();
+            items.dedup();
+            rows.push(user);
+            cols.push(seed);
+            weights.push(items.len() as i64);
+        }
+        (rows, cols, weights)
+    }
+}
+
+fn local_index(
+    samples: &mut Vec<NodeIdx>,
+    mapping: &mut HashMap<NodeIdx, usize>,
+    v: NodeIdx,
+    dedup: bool,
+) -> usize {
+    if dedup {
+        *mapping.entry(v).or_insert_with(|| {
+            samples.push(v);
+            samples.len() - 1
+        })
+    } else {
+        samples.push(v);
+        samples.len() - 1
+    }
+}
+
+pub fn two_hop_sample(
+    rng: &mut impl Rng,
+    user_items: &CsrGraph,
+    item_users: &CscGraph,
+    seeds: &[NodeIdx],
+    k_items: usize,
+    k_users: usize,
+    dedup: bool,
+) -> TwoHopSamples {
+    let mut users: Vec<NodeIdx> = seeds.to_vec();
+    let mut items: Vec<NodeIdx> = Vec::new();
+    let mut user_mapping: HashMap<NodeIdx, usize> = HashMap::new();
+    let mut item_mapping: HashMap<NodeIdx, usize> = HashMap::new();
+    if dedup {
+        user_mapping.extend(seeds.iter().enumerate().map(|(i, &s)| (s, i)));
+    }
+
+    let mut user_item = CooGraphBuilder::new();
+    let mut item_user = CooGraphBuilder::new();
+    let mut buffer = vec![0; k_items.max(k_users)];
+
+    // First hop: items of the seed users
+    for (i, &u) in seeds.iter().enumerate() {
+        let neighbors_range = user_items.neighbors_range(u);
+        if neighbors_range.is_empty() || k_items == 0 {
+            continue;
+        }
+
+        let n = reservoir_sampling(rng, neighbors_range, &mut buffer[..k_items]);
+        for edge_ptr in buffer[..n].iter().cloned() {
+            let j = local_index(&mut items, &mut item_mapping, user_items.get_by_ptr(edge_ptr), dedup);
+            user_item.push_edge(j as i64, i as i64, edge_ptr as i64);
+        }
+    }
+
+    // Second hop: users of the sampled items. Deduplicated items are expanded only once.
+    for (j, item) in items.clone().into_iter().enumerate() {
+        let neighbors_range = item_users.neighbors_range(item);
+        if neighbors_range.is_empty() || k_users == 0 {
+            continue;
+        }
+
+        let n = reservoir_sampling(rng, neighbors_range, &mut buffer[..k_users]);
+        for edge_ptr in buffer[..n].iter().cloned() {
+            let k = local_index(&mut users, &mut user_mapping, item_users.get_by_ptr(edge_ptr), dedup);
+            item_user.push_edge(k as i64, j as i64, edge_ptr as i64);
+        }
+    }
+
+    TwoHopSamples {
+        users,
+        items,
+        user_item,
+        item_user,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use rand::SeedableRng;
+    use rand::rngs::SmallRng;
+    use tch::Tensor;
+    use crate::data::{CooGraphStorage, CscGraph, CscGraphStorage, CsrGraph, CsrGraphStorage};
+
+    #[test]
+    fn test_two_hop_sample() {
+        // Users 0..4 and items 0..3 where users 0 and 1 share items 0 and 1
+        let rows = Tensor::of_slice(&[0_i64, 0, 1, 1, 2, 2, 3]);
+        let cols = Tensor::of_slice(&[0_i64, 1, 0, 1, 1, 2, 2]);
+        let coo = CooGraphStorage::new(Tensor::stack(&[rows, cols], 0), (4, 3));
+        let csr_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let csr = CsrGraph::<i64, i64>::try_from(&csr_data).unwrap();
+        let csc_data = CscGraphStorage::try_from(&coo).unwrap();
+        let csc = CscGraph::<i64, i64>::try_from(&csc_data).unwrap();
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        let samples = super::two_hop_sample(&mut rng, &csr, &csc, &[0], 10, 10, true);
+        assert_eq!(samples.items, vec![0, 1]);
+        assert_eq!(samples.users, vec![0, 1, 2]);
+        assert_eq!(samples.user_item.len(), 2);
+        assert_eq!(samples.item_user.len(), 5);
+        for (k, j) in samples.item_user.iter_edges() {
+            assert!(csr.has_edge(samples.users[k as usize], samples.items[j as usize]));
+        }
+
+        let (rows, cols, weights) = samples.collapse();
+        assert_eq!(rows, vec![1, 2]);
+        assert_eq!(cols, vec![0, 0]);
+        assert_eq!(weights, vec![2, 1]);
+
+        // Without dedup every sampled item and user gets its own local id
+        let samples = super::two_hop_sample(&mut rng, &csr, &csc, &[0, 3], 10, 10, false);
+        assert_eq!(samples.items.len(), 3);
+        assert_eq!(samples.users.len(), 2 + 2 + 3 + 2);
+        let (rows, cols, weights) = samples.collapse();
+        assert_eq!(rows, vec![1, 2, 2]);
+        assert_eq!(cols, vec![0, 0, 3]);
+        assert_eq!(weights, vec![2, 1, 1]);
+
+        let samples = super::two_hop_sample(&mut rng, &csr, &csc, &[2], 1, 1, true);
+        assert_eq!(samples.items.len(), 1);
+        assert_eq!(samples.item_user.len(), 1);
+    }
+}
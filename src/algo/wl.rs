@@ -0,0 +1,102 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use rayon::prelude::*;
+use tch::{Kind, Tensor};
+use crate::data::CsrGraph;
+use crate::utils::{NodeIdx, parallel, TensorConversionError, TensorResult, try_tensor_to_slice};
+
+fn hash_one(value: impl Hash) -> u64 {
+    // DefaultHasher::new uses fixed keys, so hashes are stable between runs
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+// 1-WL color refinement, the hash covers the color histograms of all rounds. Without labels every node starts
+// with the same color. The graph is assumed to be undirected.
+pub fn wl_hash(
+    graph: &CsrGraph,
+    node_labels: Option<&Tensor>,
+    num_iterations: i64,
+) -> TensorResult<u64> {
+    let n = graph.node_count();
+    let mut colors: Vec<u64> = match node_labels {
+        Some(labels) => {
+            let labels = labels.to_kind(Kind::Int64).contiguous();
+            let labels_data = try_tensor_to_slice::<i64>(&labels)?;
+            if labels_data.len() != n {
+                return Err(TensorConversionError::InvalidShape(Some(format!(
+                    "node_labels must have {} entries, got {}", n, labels_data.len()
+                ))));
+            }
+            labels_data.iter().map(hash_one).collect()
+        }
+        None => vec![0; n],
+    };
+
+    let histogram = |colors: &[u64]| {
+        let mut sorted = colors.to_vec();
+        sorted.sort_unstable();
+        hash_one(sorted)
+    };
+
+    let mut round_hashes = vec![histogram(&colors)];
+    for _ in 0..num_iterations.max(0) {
+        colors = parallel::install(|| (0..n as NodeIdx).into_par_iter()
+            .map(|v| {
+                let mut neighbor_colors: Vec<u64> = graph.neighbors_slice(v).iter()
+                    .map(|u| colors[*u as usize])
+                    .collect();
+                neighbor_colors.sort_unstable();
+                hash_one((colors[v as usize], neighbor_colors))
+            })
+            .collect());
+        round_hashes.push(histogram(&colors));
+    }
+
+    Ok(hash_one((n, round_hashes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use tch::Tensor;
+    use crate::data::{CooGraphStorage, CsrGraph, CsrGraphStorage};
+    use super::wl_hash;
+
+    fn undirected(n: i64, edges: &[(i64, i64)]) -> CsrGraphStorage {
+        let rows: Vec<i64> = edges.iter().flat_map(|(u, v)| [*u, *v]).collect();
+        let cols: Vec<i64> = edges.iter().flat_map(|(u, v)| [*v, *u]).collect();
+        let coo = CooGraphStorage::new(Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0), (n, n));
+        CsrGraphStorage::try_from(&coo).unwrap()
+    }
+
+    fn hash(data: &CsrGraphStorage, labels: Option<&Tensor>) -> u64 {
+        let graph = CsrGraph::<i64, i64>::try_from(data).unwrap();
+        wl_hash(&graph, labels, 3).unwrap()
+    }
+
+    #[test]
+    fn test_wl_hash() {
+        let path = undirected(4, &[(0, 1), (1, 2), (2, 3)]);
+        let path_relabeled = undirected(4, &[(2, 0), (3, 1), (0, 3)]);
+        let star = undirected(4, &[(0, 1), (0, 2), (0, 3)]);
+        assert_eq!(hash(&path, None), hash(&path_relabeled, None));
+        assert_ne!(hash(&path, None), hash(&star, None));
+
+        // A triangle with a tail differs from a 4-cycle, even though both have four edges
+        let tailed = undirected(4, &[(0, 1), (1, 2), (2, 0), (2, 3)]);
+        let cycle = undirected(4, &[(0, 1), (1, 2), (2, 3), (3, 0)]);
+        assert_ne!(hash(&tailed, None), hash(&cycle, None));
+
+        // Labels follow the relabeling: path 0-1-2-3 maps to 2-0-3-1
+        let labels = Tensor::of_slice(&[1_i64, 2, 2, 1]);
+        let labels_relabeled = Tensor::of_slice(&[2_i64, 1, 1, 2]);
+        assert_eq!(hash(&path, Some(&labels)), hash(&path_relabeled, Some(&labels_relabeled)));
+        assert_ne!(hash(&path, Some(&labels)), hash(&path, Some(&Tensor::of_slice(&[1_i64, 1, 2, 2]))));
+        assert_ne!(hash(&path, Some(&labels)), hash(&path, None));
+
+        let graph = CsrGraph::<i64, i64>::try_from(&path).unwrap();
+        assert!(wl_hash(&graph, Some(&Tensor::of_slice(&[1_i64])), 3).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use tch::Tensor;
+use crate::data::graph::NeighborOrdering;
+use crate::data::storage::CsrGraphStorage;
+use crate::utils::{DefaultIx, IndexType, NodeIdx, TensorConversionError, TensorResult};
+
+// Growable adjacency list for building graphs edge by edge, `to_csr` turns it into the immutable compressed form
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AdjacencyList<Ix = DefaultIx> {
+    neighbors: Vec<Vec<NodeIdx<Ix>>>,
+    edge_count: usize,
+}
+
+impl<Ix: IndexType> AdjacencyList<Ix> {
+    pub fn new() -> Self {
+        Self { neighbors: Vec::new(), edge_count: 0 }
+    }
+
+    pub fn with_nodes(node_count: usize) -> Self {
+        Self { neighbors: vec![Vec::new(); node_count], edge_count: 0 }
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.neighbors.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edge_count
+    }
+
+    pub fn add_node(&mut self) -> NodeIdx<Ix> {
+        self.neighbors.push(Vec::new());
+        Ix::new(self.neighbors.len() - 1)
+    }
+
+    // Adds the edge u -> v, nodes past the end are added on the fly. Parallel edges and self loops are kept.
+    pub fn add_edge(&mut self, u: NodeIdx<Ix>, v: NodeIdx<Ix>) -> TensorResult<()> {
+        if u < Ix::default() || v < Ix::default() {
+            return Err(TensorConversionError::Unknown(format!(
+                "node ids must be non negative, got {:?} -> {:?}", u, v
+            )));
+        }
+        let required = u.index().max(v.index()) + 1;
+        if required > self.neighbors.len() {
+            self.neighbors.resize(required, Vec::new());
+        }
+        self.neighbors[u.index()].push(v);
+        self.edge_count += 1;
+        Ok(())
+    }
+
+    // Neighbors in insertion order, nodes that were never added have none
+    pub fn neighbors(&self, v: NodeIdx<Ix>) -> &[NodeIdx<Ix>] {
+        self.neighbors.get(v.index()).map_or(&[], |neighbors| neighbors.as_slice())
+    }
+
+    pub fn degree(&self, v: NodeIdx<Ix>) -> usize {
+        self.neighbors(v).len()
+    }
+
+    // Compressed copy with the neighbors of every node sorted by id and parallel edges next to each other, the
+    // same layout as a conversion from the equivalent `CooGraphStorage` but without a perm
+    pub fn to_csr(&self) -> CsrGraphStorage {
+        let mut ptrs = Vec::with_capacity(self.neighbors.len() + 1);
+        let mut indices = Vec::with_capacity(self.edge_count);
+        ptrs.push(0_i64);
+        for neighbors in self.neighbors.iter() {
+            let begin = indices.len();
+            indices.extend(neighbors.iter().map(|v| v.index() as i64));
+            indices[begin..].sort_unstable();
+            ptrs.push(indices.len() as i64);
+        }
+
+        CsrGraphStorage::new(Tensor::of_slice(&ptrs), Tensor::of_slice(&indices), None)
+            .with_ordering(NeighborOrdering::ById)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use rand::{Rng, SeedableRng};
+    use tch::Tensor;
+    use crate::data::{CooGraphStorage, CsrGraph, CsrGraphStorage, NeighborOrdering};
+    use crate::data::adjlist::AdjacencyList;
+
+    #[test]
+    fn test_adjacency_list() {
+        let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+        let n = 50;
+        let edges: Vec<(i64, i64)> = (0..400).map(|_| (rng.gen_range(0..n), rng.gen_range(0..n))).collect();
+
+        let mut adjlist = AdjacencyList::with_nodes(n as usize);
+        for (u, v) in edges.iter() {
+            adjlist.add_edge(*u, *v).unwrap();
+        }
+        assert_eq!(adjlist.node_count(), n as usize);
+        assert_eq!(adjlist.edge_count(), edges.len());
+        for v in 0..n {
+            assert_eq!(adjlist.degree(v), edges.iter().filter(|(u, _)| *u == v).count());
+        }
+
+        // Same ptrs and indices as a compressed graph built directly from the edge list
+        let rows: Vec<i64> = edges.iter().map(|(u, _)| *u).collect();
+        let cols: Vec<i64> = edges.iter().map(|(_, v)| *v).collect();
+        let coo = CooGraphStorage::new(Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0), (n, n));
+        let expected = CsrGraphStorage::try_from(&coo).unwrap();
+        let csr = adjlist.to_csr();
+        assert!(csr.ptrs.equal(&expected.ptrs));
+        assert!(csr.indices.equal(&expected.indices));
+        assert_eq!(csr.ordering, NeighborOrdering::ById);
+        assert!(CsrGraph::<i64, i64>::try_from(&csr).unwrap().validate_with_size(n as usize).is_ok());
+
+        // The list grows with the edges, isolated and unknown nodes have no neighbors
+        let mut adjlist = AdjacencyList::<i64>::new();
+        adjlist.add_edge(3, 1).unwrap();
+        adjlist.add_edge(3, 0).unwrap();
+        adjlist.add_edge(3, 1).unwrap();
+        assert_eq!(adjlist.add_node(), 4);
+        assert_eq!(adjlist.node_count(), 5);
+        assert_eq!(adjlist.neighbors(3), &[1, 0, 1]);
+        assert_eq!(adjlist.degree(0), 0);
+        assert_eq!(adjlist.degree(10), 0);
+        let csr = adjlist.to_csr();
+        let ptrs: Vec<i64> = csr.ptrs.into();
+        let indices: Vec<i64> = csr.indices.into();
+        assert_eq!(ptrs, vec![0, 0, 0, 0, 3, 3]);
+        assert_eq!(indices, vec![0, 1, 1]);
+
+        // Negative ids are rejected without touching the list
+        assert!(adjlist.add_edge(-1, 2).is_err());
+        assert!(adjlist.add_edge(2, -1).is_err());
+        assert_eq!((adjlist.node_count(), adjlist.edge_count()), (5, 3));
+    }
+}
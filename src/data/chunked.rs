@@ -0,0 +1,251 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use tch::{Kind, Tensor};
+use crate::data::{CscGraphStorage, NeighborOrdering, Size};
+use crate::utils::tensor::{TensorResult, TensorConversionError, try_tensor_to_slice};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkedStage {
+    // Counting the in-degrees of every column, reported per shard
+    Count,
+    // Writing edges into their column bucket, reported per shard
+    Partition,
+    // Sorting the buckets and appending them to the output, reported per bucket
+    Sort,
+}
+
+pub(crate) const PTRS_FILE: &str = "ptrs.npy";
+pub(crate) const INDICES_FILE: &str = "indices.npy";
+pub(crate) const PERM_FILE: &str = "perm.npy";
+// Only present for storages with an edge type, `convert_chunked` doesn't write it
+pub(crate) const EDGE_TYPE_FILE: &str = "edge_type.npy";
+
+pub(crate) fn io_error(e: impl ToString) -> TensorConversionError {
+    TensorConversionError::Unknown(e.to_string())
+}
+
+// Loads a [2, E] int64 shard
+fn read_shard(path: &Path) -> TensorResult<Tensor> {
+    let shard = Tensor::read_npy(path).map_err(io_error)?;
+    let shape = shard.size();
+    if shape.len() != 2 || shape[0] != 2 {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "shard {} must be of shape [2, E], got {:?}", path.display(), shape
+        ))));
+    }
+    if shard.kind() != Kind::Int64 {
+        return Err(TensorConversionError::InvalidDType(Kind::Int64, shard.kind()));
+    }
+    Ok(shard.contiguous())
+}
+
+fn write_npy_header(writer: &mut impl Write, len: usize) -> std::io::Result<()> {
+    let mut header = format!("{{'descr': '<i8', 'fortran_order': False, 'shape': ({},), }}", len);
+    // Magic, version and header length take 10 bytes, the data has to start 64 byte aligned
+    while (10 + header.len() + 1) % 64 != 0 {
+        header.push(' ');
+    }
+    header.push('\n');
+    writer.write_all(b"\x93NUMPY\x01\x00")?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())
+}
+
+// Streams COO shards, stored as [2, E] int64 npy files, into a CSC graph in `out_dir` while keeping at most
+// `chunk_edges` edges (unless a single column is larger) and the column counts in memory. Edge ids in the
+// resulting perm refer to the concatenation of the shards in the given order.
+pub fn convert_chunked(
+    shards: &[PathBuf],
+    size: Size,
+    out_dir: &Path,
+    chunk_edges: usize,
+    progress: &mut impl FnMut(ChunkedStage, usize, usize),
+) -> TensorResult<()> {
+    if chunk_edges == 0 {
+        return Err(TensorConversionError::Unknown("chunk_edges must be positive".to_string()));
+    }
+    let (m, n) = size;
+    fs::create_dir_all(out_dir).map_err(io_error)?;
+
+    // Pass 1: column counts, which directly give the column pointers
+    let mut counts = vec![0_usize; n as usize];
+    for (i, path) in shards.iter().enumerate() {
+        let shard = read_shard(path)?;
+        let row_col = try_tensor_to_slice::<i64>(&shard)?;
+        let (rows, cols) = row_col.split_at(row_col.len() / 2);
+        if let Some((r, c)) = rows.iter().zip(cols.iter()).find(|(r, c)| **r < 0 || **r >= m || **c < 0 || **c >= n) {
+            return Err(TensorConversionError::Unknown(format!(
+                "edge ({}, {}) in shard {} is out of bounds for size {:?}", r, c, path.display(), size
+            )));
+        }
+        for c in cols {
+            counts[*c as usize] += 1;
+        }
+        progress(ChunkedStage::Count, i + 1, shards.len());
+    }
+
+    let mut ptrs = Vec::with_capacity(n as usize + 1);
+    ptrs.push(0_i64);
+    for c in counts.iter() {
+        ptrs.push(ptrs.last().unwrap() + *c as i64);
+    }
+    let num_edges = *ptrs.last().unwrap() as usize;
+    Tensor::of_slice(&ptrs).write_npy(out_dir.join(PTRS_FILE)).map_err(io_error)?;
+
+    // Contiguous column ranges holding at most `chunk_edges` edges each
+    let mut bucket_starts: Vec<i64> = Vec::new();
+    let mut bucket_edges = 0;
+    for (c, count) in counts.iter().enumerate() {
+        if bucket_starts.is_empty() || (bucket_edges > 0 && bucket_edges + count > chunk_edges) {
+            bucket_starts.push(c as i64);
+            bucket_edges = 0;
+        }
+        bucket_edges += count;
+    }
+    drop(counts);
+
+    // Pass 2: (col, row, edge id) triples are appended to the bucket of their column
+    let bucket_paths: Vec<PathBuf> = (0..bucket_starts.len())
+        .map(|b| out_dir.join(format!("bucket_{}.tmp", b)))
+        .collect();
+    let mut writers = bucket_paths.iter()
+        .map(|path| File::create(path).map(BufWriter::new))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(io_error)?;
+    let mut edge_offset = 0_i64;
+    for (i, path) in shards.iter().enumerate() {
+        let shard = read_shard(path)?;
+        let row_col = try_tensor_to_slice::<i64>(&shard)?;
+        let (rows, cols) = row_col.split_at(row_col.len() / 2);
+        for (e, (r, c)) in rows.iter().zip(cols.iter()).enumerate() {
+            let bucket = bucket_starts.partition_point(|start| start <= c) - 1;
+            let writer = &mut writers[bucket];
+            for value in [*c, *r, edge_offset + e as i64] {
+                writer.write_all(&value.to_le_bytes()).map_err(io_error)?;
+            }
+        }
+        edge_offset += rows.len() as i64;
+        progress(ChunkedStage::Partition, i + 1, shards.len());
+    }
+    for mut writer in writers {
+        writer.flush().map_err(io_error)?;
+    }
+
+    // Pass 3: sort every bucket on its own and append it to the output
+    let mut indices_writer = BufWriter::new(File::create(out_dir.join(INDICES_FILE)).map_err(io_error)?);
+    let mut perm_writer = BufWriter::new(File::create(out_dir.join(PERM_FILE)).map_err(io_error)?);
+    write_npy_header(&mut indices_writer, num_edges).map_err(io_error)?;
+    write_npy_header(&mut perm_writer, num_edges).map_err(io_error)?;
+    for (b, path) in bucket_paths.iter().enumerate() {
+        let mut data = Vec::new();
+        BufReader::new(File::open(path).map_err(io_error)?).read_to_end(&mut data).map_err(io_error)?;
+        let mut edges: Vec<(i64, i64, i64)> = data.chunks_exact(24)
+            .map(|chunk| {
+                let value = |o: usize| {
+                    let mut bytes = [0_u8; 8];
+                    bytes.copy_from_slice(&chunk[o..o + 8]);
+                    i64::from_le_bytes(bytes)
+                };
+                (value(0), value(8), value(16))
+            })
+            .collect();
+        drop(data);
+        edges.sort_unstable();
+
+        for (_, r, e) in edges.iter() {
+            indices_writer.write_all(&r.to_le_bytes()).map_err(io_error)?;
+            perm_writer.write_all(&e.to_le_bytes()).map_err(io_error)?;
+        }
+        fs::remove_file(path).map_err(io_error)?;
+        progress(ChunkedStage::Sort, b + 1, bucket_paths.len());
+    }
+    indices_writer.flush().map_err(io_error)?;
+    perm_writer.flush().map_err(io_error)?;
+
+    Ok(())
+}
+
+pub fn load_chunked(out_dir: &Path) -> TensorResult<CscGraphStorage> {
+    let ptrs = Tensor::read_npy(out_dir.join(PTRS_FILE)).map_err(io_error)?;
+    let indices = Tensor::read_npy(out_dir.join(INDICES_FILE)).map_err(io_error)?;
+    let perm = Tensor::read_npy(out_dir.join(PERM_FILE)).map_err(io_error)?;
+    // Buckets are sorted by (col, row, edge id)
+    let mut graph = CscGraphStorage::new(ptrs, indices, Some(perm)).with_ordering(NeighborOrdering::ById);
+    let edge_type_path = out_dir.join(EDGE_TYPE_FILE);
+    if edge_type_path.exists() {
+        graph = graph.with_edge_type(Tensor::read_npy(edge_type_path).map_err(io_error)?);
+    }
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use std::path::PathBuf;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::SmallRng;
+    use tch::Tensor;
+    use crate::data::{CooGraphStorage, CscGraphStorage};
+    use crate::data::chunked::{ChunkedStage, convert_chunked, load_chunked};
+
+    #[test]
+    fn test_convert_chunked() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let (m, n) = (30_i64, 40_i64);
+        let shard_sizes = [50, 0, 120, 7];
+
+        let dir = std::env::temp_dir().join(format!("tch_geometric_chunked_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut shards = Vec::new();
+        let mut shard_tensors = Vec::new();
+        for (i, e) in shard_sizes.iter().enumerate() {
+            // Duplicate edges are likely with this many edges
+            let rows: Vec<i64> = (0..*e).map(|_| rng.gen_range(0..m)).collect();
+            let cols: Vec<i64> = (0..*e).map(|_| rng.gen_range(0..n / 2) * 2).collect();
+            let row_col = Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0);
+            let path = dir.join(format!("shard_{}.npy", i));
+            row_col.write_npy(&path).unwrap();
+            shards.push(path);
+            shard_tensors.push(row_col);
+        }
+
+        let row_col = Tensor::cat(&shard_tensors, 1);
+        let coo = CooGraphStorage::new(row_col.shallow_clone(), (m, n));
+        let expected = CscGraphStorage::try_from(&coo).unwrap();
+        let num_edges = row_col.size()[1];
+
+        for chunk_edges in [1_usize, 16, 1000] {
+            let out_dir = dir.join(format!("out_{}", chunk_edges));
+            let mut stages = Vec::new();
+            convert_chunked(&shards, (m, n), &out_dir, chunk_edges, &mut |stage, done, total| {
+                stages.push((stage, done, total));
+            }).unwrap();
+            assert_eq!(stages.iter().filter(|(s, _, _)| *s == ChunkedStage::Count).count(), shards.len());
+            assert_eq!(stages.last().map(|(s, done, total)| (*s, done == total)), Some((ChunkedStage::Sort, true)));
+
+            let result = load_chunked(&out_dir).unwrap();
+            assert!(result.ptrs.equal(&expected.ptrs));
+            assert!(result.indices.equal(&expected.indices));
+
+            // Duplicate edges may be ordered differently, so the perm is checked for consistency instead
+            let perm = result.perm().unwrap();
+            assert!(perm.sort(0, false).0.equal(&Tensor::arange(num_edges, (perm.kind(), perm.device()))));
+            let (rows, cols) = (coo.row(), coo.col());
+            assert!(rows.index_select(0, perm).equal(&result.indices));
+            assert!(cols.index_select(0, perm).equal(&cols.index_select(0, expected.perm().unwrap())));
+
+            let leftovers: Vec<PathBuf> = std::fs::read_dir(&out_dir).unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| path.extension().map(|ext| ext == "tmp").unwrap_or(false))
+                .collect();
+            assert!(leftovers.is_empty());
+        }
+
+        let bad = dir.join("bad.npy");
+        Tensor::of_slice(&[0_i64, 1, 2, n]).view([2, 2]).write_npy(&bad).unwrap();
+        assert!(convert_chunked(&[bad], (m, n), &dir.join("out_bad"), 10, &mut |_, _, _| {}).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,398 @@
+use rand::{Rng, SeedableRng};
+use rand::rngs::SmallRng;
+use tch::{Device, Kind, Tensor};
+use crate::data::CooGraphStorage;
+use crate::utils::tensor::{TensorConversionError, TensorResult, try_tensor_to_slice, try_tensor_to_slice_mut};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErdosRenyi {
+    // G(n, p): every pair is an edge with probability p
+    Probability(f64),
+    // G(n, m): m edges drawn uniformly
+    Count(i64),
+}
+
+impl From<f64> for ErdosRenyi {
+    fn from(p: f64) -> Self {
+        ErdosRenyi::Probability(p)
+    }
+}
+
+impl From<i64> for ErdosRenyi {
+    fn from(m: i64) -> Self {
+        ErdosRenyi::Count(m)
+    }
+}
+
+// Positions of the successes among `total` bernoulli(p) trials, skipping geometrically distributed gaps so the
+// cost is linear in the number of successes
+fn bernoulli_positions(rng: &mut impl Rng, total: i64, p: f64, mut f: impl FnMut(i64)) {
+    if p <= 0.0 || total <= 0 {
+        return;
+    }
+    if p >= 1.0 {
+        (0..total).for_each(f);
+        return;
+    }
+
+    let log_q = (1.0 - p).ln();
+    let mut pos = -1_i64;
+    loop {
+        let r: f64 = rng.gen();
+        let skip = ((1.0 - r).ln() / log_q).floor();
+        if skip >= (total - pos) as f64 {
+            return;
+        }
+        pos += 1 + skip as i64;
+        if pos >= total {
+            return;
+        }
+        f(pos);
+    }
+}
+
+fn check_probability(p: f64) -> TensorResult<()> {
+    if !(0.0..=1.0).contains(&p) {
+        return Err(TensorConversionError::Unknown(format!("edge probability must be in [0, 1], got {}", p)));
+    }
+    Ok(())
+}
+
+// Random directed graph on `n` nodes without self loops. With a probability every ordered pair is an edge
+// independently, with a count that many edges are drawn uniformly. Counted edges are drawn independently so a
+// dense graph may have a few duplicates, which keeps generating 1e8 edge graphs cheap. The same seed always gives
+// the same graph.
+pub fn erdos_renyi(n: i64, p_or_m: impl Into<ErdosRenyi>, seed: u64) -> TensorResult<CooGraphStorage> {
+    let mut rng = SmallRng::seed_from_u64(seed);
+    match p_or_m.into() {
+        ErdosRenyi::Probability(p) => {
+            check_probability(p)?;
+            let (mut rows, mut cols) = (Vec::new(), Vec::new());
+            bernoulli_positions(&mut rng, n.max(0) * (n - 1).max(0), p, |k| {
+                // Pair k is the (k % (n - 1))-th node other than u = k / (n - 1)
+                let (u, w) = (k / (n - 1), k % (n - 1));
+                rows.push(u);
+                cols.push(if w >= u { w + 1 } else { w });
+            });
+            let row_col = Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0);
+            Ok(CooGraphStorage::new(row_col, (n, n)))
+        }
+        ErdosRenyi::Count(m) => {
+            if m < 0 || (m > 0 && n < 2) {
+                return Err(TensorConversionError::Unknown(format!("can't draw {} edges between {} nodes", m, n)));
+            }
+
+            let mut row_col = Tensor::empty(&[2, m], (Kind::Int64, Device::Cpu));
+            let data = try_tensor_to_slice_mut::<i64>(&mut row_col)?;
+            let (rows, cols) = data.split_at_mut(m as usize);
+            for (u, v) in rows.iter_mut().zip(cols.iter_mut()) {
+                *u = rng.gen_range(0..n);
+                // Skip over u, so v is uniform among the other n - 1 nodes
+                let w = rng.gen_range(0..n - 1);
+                *v = if w >= *u { w + 1 } else { w };
+            }
+            Ok(CooGraphStorage::new(row_col, (n, n)))
+        }
+    }
+}
+
+// Preferential attachment graph: starting from `m` unconnected nodes every new node connects to `m` distinct
+// existing nodes, chosen with probability proportional to their degree. The graph is undirected and holds both
+// directions of its m (n - m) edges.
+pub fn barabasi_albert(n: i64, m: i64, seed: u64) -> TensorResult<CooGraphStorage> {
+    if m < 1 || m >= n {
+        return Err(TensorConversionError::Unknown(format!("need 1 <= m < n, got m = {} and n = {}", m, n)));
+    }
+
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let num_edges = (m * (n - m)) as usize;
+    let (mut rows, mut cols) = (Vec::with_capacity(2 * num_edges), Vec::with_capacity(2 * num_edges));
+    // Every node appears once per incident edge, so uniform draws from it are proportional to the degree
+    let mut repeated: Vec<i64> = Vec::with_capacity(2 * num_edges);
+    let mut targets: Vec<i64> = (0..m).collect();
+    for v in m..n {
+        for u in targets.iter() {
+            rows.extend_from_slice(&[v, *u]);
+            cols.extend_from_slice(&[*u, v]);
+            repeated.extend_from_slice(&[v, *u]);
+        }
+
+        targets.clear();
+        while targets.len() < m as usize {
+            let u = repeated[rng.gen_range(0..repeated.len())];
+            if !targets.contains(&u) {
+                targets.push(u);
+            }
+        }
+    }
+
+    let row_col = Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0);
+    Ok(CooGraphStorage::new(row_col, (n, n)))
+}
+
+// Directed graph whose nodes are split into consecutive blocks, an ordered pair (u, v) without self loops is an
+// edge with probability prob_matrix[block(u), block(v)]. Returns the block of every node as community labels.
+pub fn stochastic_block_model(
+    block_sizes: &[i64],
+    prob_matrix: &Tensor,
+    seed: u64,
+) -> TensorResult<(CooGraphStorage, Tensor)> {
+    let num_blocks = block_sizes.len() as i64;
+    if prob_matrix.size() != vec![num_blocks, num_blocks] {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "prob_matrix must be of shape [{}, {}], got {:?}", num_blocks, num_blocks, prob_matrix.size()
+        ))));
+    }
+    if let Some(size) = block_sizes.iter().find(|size| **size < 0) {
+        return Err(TensorConversionError::Unknown(format!("block sizes must be non negative, got {}", size)));
+    }
+    let probs = prob_matrix.to_kind(Kind::Double).contiguous();
+    let probs_data = try_tensor_to_slice::<f64>(&probs)?;
+    for p in probs_data {
+        check_probability(*p)?;
+    }
+
+    let offsets: Vec<i64> = block_sizes.iter()
+        .scan(0, |acc, size| {
+            let offset = *acc;
+            *acc += size;
+            Some(offset)
+        })
+        .collect();
+    let n: i64 = block_sizes.iter().sum();
+
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let (mut rows, mut cols) = (Vec::new(), Vec::new());
+    for a in 0..block_sizes.len() {
+        for b in 0..block_sizes.len() {
+            let (size_a, size_b) = (block_sizes[a], block_sizes[b]);
+            let p = probs_data[a * block_sizes.len() + b];
+            if a == b {
+                bernoulli_positions(&mut rng, size_a * (size_a - 1).max(0), p, |k| {
+                    let (u, w) = (k / (size_a - 1), k % (size_a - 1));
+                    rows.push(offsets[a] + u);
+                    cols.push(offsets[a] + if w >= u { w + 1 } else { w });
+                });
+            } else {
+                bernoulli_positions(&mut rng, size_a * size_b, p, |k| {
+                    rows.push(offsets[a] + k / size_b);
+                    cols.push(offsets[b] + k % size_b);
+                });
+            }
+        }
+    }
+
+    let labels: Vec<i64> = block_sizes.iter().enumerate()
+        .flat_map(|(block, size)| (0..*size).map(move |_| block as i64))
+        .collect();
+    let row_col = Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0);
+    Ok((CooGraphStorage::new(row_col, (n, n)), Tensor::of_slice(&labels)))
+}
+
+// Two dimensional lattice of height x width nodes, node r * width + c sits at (r, c) and is connected to the
+// nodes right and below of it. With `periodic` the last row and column also wrap around to the first, forming a
+// torus. Sides of length 1 or 2 don't wrap since that would only add self loops or duplicate edges. The graph is
+// undirected and holds both directions. Returns the [N, 2] float coordinates of the nodes as well.
+pub fn grid_graph(height: i64, width: i64, periodic: bool) -> TensorResult<(CooGraphStorage, Tensor)> {
+    if height < 0 || width < 0 {
+        return Err(TensorConversionError::Unknown(format!("grid sides must be non negative, got {}x{}", height, width)));
+    }
+
+    let n = height * width;
+    let (mut rows, mut cols) = (Vec::new(), Vec::new());
+    let mut connect = |u: i64, v: i64| {
+        rows.extend_from_slice(&[u, v]);
+        cols.extend_from_slice(&[v, u]);
+    };
+    for r in 0..height {
+        for c in 0..width {
+            let u = r * width + c;
+            if c + 1 < width {
+                connect(u, u + 1);
+            } else if periodic && width > 2 {
+                connect(u, r * width);
+            }
+            if r + 1 < height {
+                connect(u, u + width);
+            } else if periodic && height > 2 {
+                connect(u, c);
+            }
+        }
+    }
+
+    let coords: Vec<f32> = (0..n).flat_map(|u| [(u / width) as f32, (u % width) as f32]).collect();
+    let row_col = Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0);
+    Ok((CooGraphStorage::new(row_col, (n, n)), Tensor::of_slice(&coords).view([n, 2])))
+}
+
+#[cfg(test)]
+mod tests {
+    use tch::Tensor;
+    use crate::data::{barabasi_albert, erdos_renyi, grid_graph, stochastic_block_model};
+    use crate::utils::graphs_equal;
+
+    // Expected number of successes and their standard deviation for `total` bernoulli(p) trials
+    fn binomial_moments(total: i64, p: f64) -> (f64, f64) {
+        let total = total as f64;
+        (total * p, (total * p * (1.0 - p)).sqrt())
+    }
+
+    fn degrees(rows: &[i64], n: usize) -> Vec<usize> {
+        let mut degrees = vec![0; n];
+        rows.iter().for_each(|u| degrees[*u as usize] += 1);
+        degrees
+    }
+
+    #[test]
+    fn test_erdos_renyi() {
+        let graph = erdos_renyi(100, 1000, 0).unwrap();
+        assert_eq!(graph.row_col.size(), vec![2, 1000]);
+        assert_eq!(graph.size, (100, 100));
+
+        let rows: Vec<i64> = graph.row().into();
+        let cols: Vec<i64> = graph.col().into();
+        assert!(rows.iter().chain(cols.iter()).all(|v| (0..100).contains(v)));
+        assert!(rows.iter().zip(cols.iter()).all(|(u, v)| u != v));
+
+        assert!(graphs_equal(&graph, &erdos_renyi(100, 1000, 0).unwrap()));
+        assert!(!graphs_equal(&graph, &erdos_renyi(100, 1000, 1).unwrap()));
+
+        assert_eq!(erdos_renyi(1, 0, 0).unwrap().row_col.size(), vec![2, 0]);
+        assert!(erdos_renyi(1, 1, 0).is_err());
+        assert!(erdos_renyi(10, -1, 0).is_err());
+    }
+
+    #[test]
+    fn test_erdos_renyi_probability() {
+        let (n, p) = (2000, 0.01);
+        let graph = erdos_renyi(n, p, 0).unwrap();
+        let rows: Vec<i64> = graph.row().into();
+        let cols: Vec<i64> = graph.col().into();
+        assert!(rows.iter().zip(cols.iter()).all(|(u, v)| u != v));
+        // Every ordered pair is drawn at most once
+        let mut pairs: Vec<(i64, i64)> = rows.iter().cloned().zip(cols.iter().cloned()).collect();
+        pairs.sort_unstable();
+        pairs.dedup();
+        assert_eq!(pairs.len(), rows.len());
+
+        let (mean, std) = binomial_moments(n * (n - 1), p);
+        assert!((rows.len() as f64 - mean).abs() < 4.0 * std);
+
+        // Out-degrees are binomial(n - 1, p), so their variance matches (n - 1) p (1 - p)
+        let degrees = degrees(&rows, n as usize);
+        let mean_degree = degrees.iter().sum::<usize>() as f64 / n as f64;
+        let variance = degrees.iter().map(|d| (*d as f64 - mean_degree).powi(2)).sum::<f64>() / n as f64;
+        let expected = (n - 1) as f64 * p * (1.0 - p);
+        assert!((variance / expected - 1.0).abs() < 0.15, "{} vs {}", variance, expected);
+
+        assert!(graphs_equal(&graph, &erdos_renyi(n, p, 0).unwrap()));
+        assert_eq!(erdos_renyi(5, 1.0, 0).unwrap().row_col.size(), vec![2, 20]);
+        assert_eq!(erdos_renyi(5, 0.0, 0).unwrap().row_col.size(), vec![2, 0]);
+        assert!(erdos_renyi(5, 1.5, 0).is_err());
+    }
+
+    #[test]
+    fn test_barabasi_albert() {
+        let (n, m) = (10_000, 3);
+        let graph = barabasi_albert(n, m, 0).unwrap();
+        let rows: Vec<i64> = graph.row().into();
+        let cols: Vec<i64> = graph.col().into();
+        assert_eq!(rows.len() as i64, 2 * m * (n - m));
+        assert!(rows.iter().zip(cols.iter()).all(|(u, v)| u != v));
+
+        let degrees = degrees(&rows, n as usize);
+        assert!(degrees[m as usize..].iter().all(|d| *d >= m as usize));
+        // The degree tail follows P(deg >= k) ~ m^2 / k^2, far heavier than a binomial
+        let k = 30;
+        let tail = degrees.iter().filter(|d| **d >= k).count() as f64 / n as f64;
+        let expected = (m * m) as f64 / (k * k) as f64;
+        assert!(tail > expected / 2.0 && tail < expected * 2.0, "{} vs {}", tail, expected);
+        assert!(*degrees.iter().max().unwrap() > 100);
+
+        assert!(graphs_equal(&graph, &barabasi_albert(n, m, 0).unwrap()));
+        assert!(barabasi_albert(3, 3, 0).is_err());
+        assert!(barabasi_albert(3, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_stochastic_block_model() {
+        let block_sizes = [300_i64, 200, 100];
+        let probs = [
+            0.1, 0.01, 0.0,
+            0.01, 0.2, 0.02,
+            0.0, 0.02, 0.5,
+        ];
+        let prob_matrix = Tensor::of_slice(&probs).view([3, 3]);
+        let (graph, labels) = stochastic_block_model(&block_sizes, &prob_matrix, 0).unwrap();
+        assert_eq!(graph.size, (600, 600));
+
+        let labels: Vec<i64> = labels.into();
+        assert_eq!(labels.len(), 600);
+        assert_eq!((labels[299], labels[300], labels[599]), (0, 1, 2));
+
+        let rows: Vec<i64> = graph.row().into();
+        let cols: Vec<i64> = graph.col().into();
+        assert!(rows.iter().zip(cols.iter()).all(|(u, v)| u != v));
+        let mut counts = [0_i64; 9];
+        for (u, v) in rows.iter().zip(cols.iter()) {
+            counts[(labels[*u as usize] * 3 + labels[*v as usize]) as usize] += 1;
+        }
+        for a in 0..3 {
+            for b in 0..3 {
+                let pairs = if a == b { block_sizes[a] * (block_sizes[a] - 1) } else { block_sizes[a] * block_sizes[b] };
+                let (mean, std) = binomial_moments(pairs, probs[a * 3 + b]);
+                assert!((counts[a * 3 + b] as f64 - mean).abs() <= 4.0 * std, "block ({}, {})", a, b);
+            }
+        }
+
+        let (again, _) = stochastic_block_model(&block_sizes, &prob_matrix, 0).unwrap();
+        assert!(graphs_equal(&graph, &again));
+        assert!(stochastic_block_model(&block_sizes, &Tensor::of_slice(&[0.5_f64]), 0).is_err());
+        assert!(stochastic_block_model(&[2], &Tensor::of_slice(&[2.0_f64]).view([1, 1]), 0).is_err());
+    }
+
+    #[test]
+    fn test_grid_graph() {
+        let (graph, coords) = grid_graph(4, 5, false).unwrap();
+        assert_eq!(graph.size, (20, 20));
+        // 4 * 4 horizontal and 3 * 5 vertical edges in both directions
+        assert_eq!(graph.row_col.size(), vec![2, 2 * (16 + 15)]);
+        assert_eq!(coords.size(), vec![20, 2]);
+        let coords: Vec<f32> = coords.view([-1]).into();
+        assert_eq!(&coords[2 * 7..2 * 8], &[1.0, 2.0]);
+
+        let rows: Vec<i64> = graph.row().into();
+        let cols: Vec<i64> = graph.col().into();
+        // Neighbors are one step apart on the lattice
+        for (u, v) in rows.iter().zip(cols.iter()) {
+            let (u, v) = (2 * *u as usize, 2 * *v as usize);
+            assert_eq!((coords[u] - coords[v]).abs() + (coords[u + 1] - coords[v + 1]).abs(), 1.0);
+        }
+        let grid_degrees = degrees(&rows, 20);
+        for (u, d) in grid_degrees.iter().enumerate() {
+            let (r, c) = (u / 5, u % 5);
+            let on_border = (r == 0 || r == 3) as usize + (c == 0 || c == 4) as usize;
+            assert_eq!(*d, 4 - on_border, "node {}", u);
+        }
+        assert_eq!((grid_degrees[0], grid_degrees[4], grid_degrees[15], grid_degrees[19]), (2, 2, 2, 2));
+        assert_eq!(grid_degrees[6], 4);
+
+        let (torus, _) = grid_graph(4, 5, true).unwrap();
+        let rows: Vec<i64> = torus.row().into();
+        assert!(degrees(&rows, 20).iter().all(|d| *d == 4));
+        let cols: Vec<i64> = torus.col().into();
+        let mut pairs: Vec<(i64, i64)> = rows.iter().cloned().zip(cols.iter().cloned()).collect();
+        pairs.sort_unstable();
+        pairs.dedup();
+        assert_eq!(pairs.len(), 80);
+
+        // Short sides don't wrap around
+        let (ladder, _) = grid_graph(2, 3, true).unwrap();
+        let rows: Vec<i64> = ladder.row().into();
+        assert_eq!(degrees(&rows, 6), vec![3; 6]);
+
+        let (empty, coords) = grid_graph(0, 3, true).unwrap();
+        assert_eq!((empty.row_col.size(), coords.size()), (vec![2, 0], vec![0, 2]));
+        assert!(grid_graph(-1, 3, false).is_err());
+    }
+}
\ No newline at end of file
@@ -1,7 +1,13 @@
 use std::ops::Range;
+#[cfg(feature = "extension-module")]
+use pyo3::{
+    PyErr,
+    exceptions::PyValueError
+};
+use thiserror::Error;
 use tch::kind::Element;
 use tch::Tensor;
-use crate::utils::{EdgeIdx, EdgePtr};
+use crate::utils::{EdgeIdx, EdgePtr, TensorConversionError};
 use crate::utils::types::{DefaultIx, DefaultPtr, IndexType, NodeIdx, NodePtr};
 
 #[derive(Debug, Clone, Copy)]
@@ -30,6 +36,90 @@ impl SparseGraphTypeTrait for Csc {
     }
 }
 
+// Order of the indices within each neighbor block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NeighborOrdering {
+    Unsorted,
+    ById,
+    ByTime,
+}
+
+// Structural properties that algorithms assuming a simple graph check, None while unknown. They are either set
+// explicitly or found by a `detect_properties` pass.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GraphMeta {
+    pub is_multigraph: Option<bool>,
+    pub has_self_loops: Option<bool>,
+    pub is_sorted: Option<bool>,
+}
+
+impl GraphMeta {
+    pub fn with_multigraph(mut self, is_multigraph: bool) -> Self {
+        self.is_multigraph = Some(is_multigraph);
+        self
+    }
+
+    pub fn with_self_loops(mut self, has_self_loops: bool) -> Self {
+        self.has_self_loops = Some(has_self_loops);
+        self
+    }
+
+    pub fn with_sorted(mut self, is_sorted: bool) -> Self {
+        self.is_sorted = Some(is_sorted);
+        self
+    }
+
+    // What still holds for a subset of the edges in their current order: a simple graph stays simple and sorted
+    // neighbors stay sorted, while a multigraph may have lost its parallel edges
+    pub fn subset(&self) -> Self {
+        GraphMeta {
+            is_multigraph: self.is_multigraph.filter(|f| !*f),
+            has_self_loops: self.has_self_loops.filter(|f| !*f),
+            is_sorted: self.is_sorted.filter(|f| *f),
+        }
+    }
+
+    // Unknown properties pass, callers that can afford it detect them first
+    pub fn require_simple(&self) -> Result<(), GraphError> {
+        if self.is_multigraph == Some(true) {
+            return Err(GraphError::Multigraph);
+        }
+        if self.has_self_loops == Some(true) {
+            return Err(GraphError::SelfLoops);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum GraphError {
+    #[error("Graph must have at least one pointer")]
+    EmptyPtrs,
+    #[error("First pointer must be 0 but got {0}")]
+    InvalidFirstPtr(usize),
+    #[error("Pointer {ptr} at position {pos} is smaller than the previous pointer {prev}")]
+    DecreasingPtr { pos: usize, ptr: usize, prev: usize },
+    #[error("Last pointer must be {expected} (number of indices) but got {got}")]
+    InvalidLastPtr { expected: usize, got: usize },
+    #[error("Index {index} at position {pos} is out of bounds for {num_nodes} nodes")]
+    IndexOutOfBounds { pos: usize, index: usize, num_nodes: usize },
+    #[error("Neighbors must be ordered {expected:?} but are {got:?}, sort them first")]
+    InvalidOrdering { expected: NeighborOrdering, got: NeighborOrdering },
+    #[error("Graph has parallel edges, coalesce it first")]
+    Multigraph,
+    #[error("Graph has self loops, remove them first")]
+    SelfLoops,
+    #[error(transparent)]
+    Tensor(#[from] TensorConversionError),
+}
+
+#[cfg(feature = "extension-module")]
+impl From<GraphError> for PyErr {
+    fn from(error: GraphError) -> Self {
+        PyValueError::new_err(error.to_string())
+    }
+}
+
 #[derive(Debug)]
 pub struct SparseGraph<'a, Ty, Ptr = DefaultPtr, Ix = DefaultIx> {
     pub ptrs: &'a [NodePtr<Ptr>],
@@ -53,10 +143,74 @@ impl<'a, Ty, Ptr: IndexType, Ix: IndexType> SparseGraph<'a, Ty, Ptr, Ix> {
         self.ptrs.len() - 1
     }
 
+    pub fn validate(&self) -> Result<(), GraphError> {
+        if self.ptrs.is_empty() {
+            return Err(GraphError::EmptyPtrs);
+        }
+        self.validate_with_size(self.node_count())
+    }
+
+    // Only the pointers, which is O(N) instead of O(E)
+    pub fn validate_ptrs(&self) -> Result<(), GraphError> {
+        if self.ptrs.is_empty() {
+            return Err(GraphError::EmptyPtrs);
+        }
+        // Negative values wrap around and show up as out of range
+        if self.ptrs[0].index() != 0 {
+            return Err(GraphError::InvalidFirstPtr(self.ptrs[0].index()));
+        }
+        for (pos, w) in self.ptrs.windows(2).enumerate() {
+            if w[1] < w[0] {
+                return Err(GraphError::DecreasingPtr { pos: pos + 1, ptr: w[1].index(), prev: w[0].index() });
+            }
+        }
+        let last = self.ptrs[self.ptrs.len() - 1].index();
+        if last != self.indices.len() {
+            return Err(GraphError::InvalidLastPtr { expected: self.indices.len(), got: last });
+        }
+        Ok(())
+    }
+
+    // Bipartite graphs index into a different node set than the one their pointers cover
+    pub fn validate_with_size(&self, num_nodes: usize) -> Result<(), GraphError> {
+        self.validate_ptrs()?;
+        for (pos, index) in self.indices.iter().enumerate() {
+            if index.index() >= num_nodes {
+                return Err(GraphError::IndexOutOfBounds { pos, index: index.index(), num_nodes });
+            }
+        }
+        Ok(())
+    }
+
     pub fn edge_count(&self) -> usize {
         self.indices.len()
     }
 
+    // Single pass over the neighbor blocks. Parallel edges are adjacent in sorted blocks, unsorted ones are sorted in
+    // a scratch buffer first.
+    pub fn detect_properties(&self) -> GraphMeta {
+        let (mut is_multigraph, mut has_self_loops, mut is_sorted) = (false, false, true);
+        let mut scratch = Vec::new();
+        for x in 0..self.node_count() {
+            let neighbors = self.neighbors_slice(Ix::new(x));
+            has_self_loops |= neighbors.contains(&Ix::new(x));
+            let block_sorted = neighbors.windows(2).all(|w| w[0] <= w[1]);
+            is_sorted &= block_sorted;
+            if !is_multigraph {
+                let block = if block_sorted {
+                    neighbors
+                } else {
+                    scratch.clear();
+                    scratch.extend_from_slice(neighbors);
+                    scratch.sort_unstable();
+                    &scratch
+                };
+                is_multigraph = block.windows(2).any(|w| w[0] == w[1]);
+            }
+        }
+        GraphMeta { is_multigraph: Some(is_multigraph), has_self_loops: Some(has_self_loops), is_sorted: Some(is_sorted) }
+    }
+
     #[inline(always)]
     fn edge_offset(&self, node: NodeIdx<Ix>) -> EdgePtr<Ptr> {
         self.ptrs[node.index()]
@@ -77,6 +231,14 @@ impl<'a, Ty, Ptr: IndexType, Ix: IndexType> SparseGraph<'a, Ty, Ptr, Ix> {
         self.indices[ptr]
     }
 
+    // Yields (ptr node, index node) pairs, which is (src, dst) for csr and (dst, src) for csc
+    pub fn edges(&self) -> impl Iterator<Item = (NodeIdx<Ix>, NodeIdx<Ix>)> + '_ {
+        (0..self.node_count()).flat_map(move |i| {
+            let x = Ix::new(i);
+            self.neighbors_slice(x).iter().map(move |y| (x, *y))
+        })
+    }
+
     pub fn has_edge(&self, x: NodeIdx<Ix>, y: NodeIdx<Ix>) -> bool {
         let neighbors = self.neighbors_slice(x);
         neighbors.binary_search(&y).is_ok()
@@ -100,6 +262,141 @@ impl<'a, Ptr: IndexType, Ix: IndexType> CsrGraph<'a, Ptr, Ix> {
     }
 }
 
+// Read access to the neighborhoods the samplers and walks expand, object safe so backends can be mixed behind
+// `&dyn NeighborSource` while the generic entry points stay monomorphized. Only array-backed sources qualify (the
+// compressed graphs, the mmap backend and `EdgeAttrSource` over either), the neighbors of an `AdjacencyList` have
+// no edge positions to sample and key edge attributes by, it has to be compressed with `to_csr` first.
+pub trait NeighborSource {
+    fn node_count(&self) -> usize;
+
+    // Positions of the edges of `v`, as used for edge attributes and the sampled edge ids
+    fn edge_positions(&self, v: NodeIdx) -> Range<EdgePtr<usize>>;
+
+    fn neighbors(&self, v: NodeIdx) -> &[NodeIdx];
+
+    fn neighbor_at(&self, ptr: EdgePtr<usize>) -> NodeIdx;
+
+    fn degree(&self, v: NodeIdx) -> usize {
+        self.edge_positions(v).len()
+    }
+
+    // Requires sorted neighbors
+    fn has_edge(&self, x: NodeIdx, y: NodeIdx) -> bool {
+        self.neighbors(x).binary_search(&y).is_ok()
+    }
+
+    // Edge weights and timestamps of `v` in `edge_positions(v)` order, None for sources without them
+    fn weights(&self, _v: NodeIdx) -> Option<&[f64]> {
+        None
+    }
+
+    fn times(&self, _v: NodeIdx) -> Option<&[i64]> {
+        None
+    }
+
+    // Single edge lookups for the hot loops, `ptr` must be one of the edge positions of `v`
+    fn weight_at(&self, v: NodeIdx, ptr: EdgePtr<usize>) -> Option<f64> {
+        self.weights(v).map(|weights| weights[ptr - self.edge_positions(v).start])
+    }
+
+    fn time_at(&self, v: NodeIdx, ptr: EdgePtr<usize>) -> Option<i64> {
+        self.times(v).map(|times| times[ptr - self.edge_positions(v).start])
+    }
+}
+
+impl<'a, Ty> NeighborSource for SparseGraph<'a, Ty, i64, i64> {
+    #[inline(always)]
+    fn node_count(&self) -> usize {
+        self.ptrs.len() - 1
+    }
+
+    #[inline(always)]
+    fn edge_positions(&self, v: NodeIdx) -> Range<EdgePtr<usize>> {
+        self.neighbors_range(v)
+    }
+
+    #[inline(always)]
+    fn neighbors(&self, v: NodeIdx) -> &[NodeIdx] {
+        self.neighbors_slice(v)
+    }
+
+    #[inline(always)]
+    fn neighbor_at(&self, ptr: EdgePtr<usize>) -> NodeIdx {
+        self.indices[ptr]
+    }
+}
+
+// Adds edge weights and timestamps to a source, indexed by edge position like the sampled edge ids. The weighted
+// and temporal samplers read them through `weights(v)` and `times(v)`.
+pub struct EdgeAttrSource<'a, G: ?Sized> {
+    graph: &'a G,
+    weights: Option<&'a [f64]>,
+    times: Option<&'a [i64]>,
+}
+
+impl<'a, G: NeighborSource + ?Sized> EdgeAttrSource<'a, G> {
+    pub fn new(graph: &'a G) -> Self {
+        EdgeAttrSource { graph, weights: None, times: None }
+    }
+
+    pub fn with_weights(mut self, weights: &'a [f64]) -> Self {
+        self.weights = Some(weights);
+        self
+    }
+
+    pub fn with_times(mut self, times: &'a [i64]) -> Self {
+        self.times = Some(times);
+        self
+    }
+
+    pub fn graph(&self) -> &'a G {
+        self.graph
+    }
+}
+
+impl<'a, G: NeighborSource + ?Sized> NeighborSource for EdgeAttrSource<'a, G> {
+    #[inline(always)]
+    fn node_count(&self) -> usize {
+        self.graph.node_count()
+    }
+
+    #[inline(always)]
+    fn edge_positions(&self, v: NodeIdx) -> Range<EdgePtr<usize>> {
+        self.graph.edge_positions(v)
+    }
+
+    #[inline(always)]
+    fn neighbors(&self, v: NodeIdx) -> &[NodeIdx] {
+        self.graph.neighbors(v)
+    }
+
+    #[inline(always)]
+    fn neighbor_at(&self, ptr: EdgePtr<usize>) -> NodeIdx {
+        self.graph.neighbor_at(ptr)
+    }
+
+    // None as well when the attributes don't cover the edges of `v`
+    #[inline(always)]
+    fn weights(&self, v: NodeIdx) -> Option<&[f64]> {
+        self.weights.and_then(|weights| weights.get(self.graph.edge_positions(v)))
+    }
+
+    #[inline(always)]
+    fn times(&self, v: NodeIdx) -> Option<&[i64]> {
+        self.times.and_then(|times| times.get(self.graph.edge_positions(v)))
+    }
+
+    #[inline(always)]
+    fn weight_at(&self, _v: NodeIdx, ptr: EdgePtr<usize>) -> Option<f64> {
+        self.weights.map(|weights| weights[ptr])
+    }
+
+    #[inline(always)]
+    fn time_at(&self, _v: NodeIdx, ptr: EdgePtr<usize>) -> Option<i64> {
+        self.times.map(|times| times[ptr])
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct EdgeAttr<'a, T> {
     pub data: &'a [T],
@@ -149,6 +446,32 @@ impl<Ix: IndexType + Element, Ptr: IndexType + Element> CooGraphBuilder<Ix, Ptr>
         self.rows.iter().cloned().zip(self.cols.iter().cloned())
     }
 
+    // Pointers over the destinations 0..num_dst and the edge order that groups the edges by destination. Samplers
+    // push the edges grouped by destination already, then the order is the identity and nothing is moved.
+    pub fn csc_order(&self, num_dst: usize) -> (Vec<i64>, Vec<usize>) {
+        let mut ptrs = vec![0_i64; num_dst + 1];
+        for col in self.cols.iter() {
+            ptrs[col.index() + 1] += 1;
+        }
+        for i in 0..num_dst {
+            ptrs[i + 1] += ptrs[i];
+        }
+
+        let order = if self.cols.windows(2).all(|w| w[0] <= w[1]) {
+            (0..self.cols.len()).collect()
+        } else {
+            // Stable counting sort by destination
+            let mut next: Vec<usize> = ptrs[..num_dst].iter().map(|p| *p as usize).collect();
+            let mut order = vec![0; self.cols.len()];
+            for (e, col) in self.cols.iter().enumerate() {
+                order[next[col.index()]] = e;
+                next[col.index()] += 1;
+            }
+            order
+        };
+        (ptrs, order)
+    }
+
     pub fn to_tensor(&self) -> (Tensor, Tensor, Tensor) {
         let rows = Tensor::of_slice(&self.rows);
         let cols = Tensor::of_slice(&self.cols);
@@ -165,4 +488,86 @@ impl<Ix: IndexType + Element, Ptr: IndexType + Element> CooGraphBuilder<Ix, Ptr>
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use tch::Tensor;
+    use crate::data::{CooGraphStorage, CscGraphStorage, CsrGraphStorage};
+    use crate::data::graph::{CscGraph, CsrGraph, EdgeAttrSource, GraphError, NeighborSource};
+
+    #[test]
+    fn test_edges() {
+        let rows = [0_i64, 0, 1, 2, 2, 3];
+        let cols = [1_i64, 2, 2, 0, 3, 1];
+        let coo = CooGraphStorage::new(Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0), (4, 4));
+        let expected: Vec<(i64, i64)> = rows.iter().cloned().zip(cols.iter().cloned()).collect();
+
+        let csr_data = CsrGraphStorage::try_from(&coo).unwrap();
+        let csr = CsrGraph::<i64, i64>::try_from(&csr_data).unwrap();
+        assert_eq!(csr.edges().count(), csr.edge_count());
+        assert_eq!(csr.edges().collect::<Vec<_>>(), expected);
+
+        let csc_data = CscGraphStorage::try_from(&coo).unwrap();
+        let csc = CscGraph::<i64, i64>::try_from(&csc_data).unwrap();
+        let mut edges: Vec<(i64, i64)> = csc.edges().map(|(dst, src)| (src, dst)).collect();
+        edges.sort_unstable();
+        assert_eq!(edges, expected);
+    }
 
+    #[test]
+    fn test_validate() {
+        let ptrs = [0_i64, 2, 3, 3];
+        let indices = [1_i64, 2, 0];
+        assert!(CsrGraph::new(&ptrs, &indices).validate().is_ok());
+
+        let ptrs = [1_i64, 2, 3, 3];
+        assert!(matches!(CsrGraph::new(&ptrs, &indices).validate(), Err(GraphError::InvalidFirstPtr(1))));
+
+        let ptrs = [0_i64, 2, 1, 3];
+        assert!(matches!(
+            CsrGraph::new(&ptrs, &indices).validate(),
+            Err(GraphError::DecreasingPtr { pos: 2, ptr: 1, prev: 2 })
+        ));
+
+        let ptrs = [0_i64, 2, 3, 4];
+        assert!(matches!(
+            CsrGraph::new(&ptrs, &indices).validate(),
+            Err(GraphError::InvalidLastPtr { expected: 3, got: 4 })
+        ));
+
+        let ptrs = [0_i64, 2, 3, 3];
+        let indices = [1_i64, 3, 0];
+        assert!(matches!(
+            CsrGraph::new(&ptrs, &indices).validate(),
+            Err(GraphError::IndexOutOfBounds { pos: 1, index: 3, num_nodes: 3 })
+        ));
+        assert!(CsrGraph::new(&ptrs, &indices).validate_with_size(4).is_ok());
+        assert!(CsrGraph::new(&ptrs, &indices).validate_ptrs().is_ok());
+
+        let empty: [i64; 0] = [];
+        assert!(matches!(CsrGraph::new(&empty, &empty).validate(), Err(GraphError::EmptyPtrs)));
+    }
+
+    #[test]
+    fn test_edge_attr_source() {
+        let ptrs = [0_i64, 2, 3, 3];
+        let indices = [1_i64, 2, 0];
+        let graph = CsrGraph::new(&ptrs, &indices);
+        assert!(graph.weights(0).is_none() && graph.time_at(0, 1).is_none());
+
+        let weights = [0.5, 1.5, 2.5];
+        let times = [3_i64, 4, 5];
+        let source = EdgeAttrSource::new(&graph).with_weights(&weights).with_times(&times);
+        assert_eq!(source.neighbors(0), graph.neighbors(0));
+        assert_eq!(source.weights(0), Some(&weights[0..2]));
+        assert_eq!(source.times(1), Some(&times[2..3]));
+        assert_eq!(source.times(2), Some(&times[3..3]));
+        assert_eq!(source.weight_at(1, 2), Some(2.5));
+        assert_eq!(source.time_at(0, 1), Some(4));
+
+        // Attributes that don't cover the edges of a node are missing for it
+        let source = EdgeAttrSource::new(&graph).with_weights(&weights[..2]);
+        assert_eq!(source.weights(0), Some(&weights[0..2]));
+        assert!(source.weights(1).is_none() && source.times(0).is_none());
+    }
+}
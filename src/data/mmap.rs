@@ -0,0 +1,356 @@
+use std::convert::TryFrom;
+use std::fs::File;
+use std::ops::{Deref, Range};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use memmap2::Mmap;
+use crate::data::chunked::{EDGE_TYPE_FILE, INDICES_FILE, PTRS_FILE, io_error};
+use crate::data::graph::{Csc, Csr, GraphError, NeighborSource, SparseGraph};
+use crate::utils::{EdgePtr, NodeIdx};
+use crate::utils::tensor::{TensorResult, TensorConversionError};
+
+// A read-only 1-D int64 npy file, the data starts at `offset` in the mapping
+struct MmapArray {
+    mmap: Mmap,
+    offset: usize,
+    len: usize,
+}
+
+impl MmapArray {
+    fn open(path: &Path) -> TensorResult<Self> {
+        let file = File::open(path).map_err(io_error)?;
+        // The files are never written through the mapping, truncating them while mapped is unsupported
+        let mmap = unsafe { Mmap::map(&file) }.map_err(io_error)?;
+        let invalid = |reason: &str| TensorConversionError::Unknown(format!("{}: {}", path.display(), reason));
+
+        if mmap.len() < 10 || &mmap[..6] != b"\x93NUMPY" {
+            return Err(invalid("not an npy file"));
+        }
+        let (header_len, start) = match mmap[6] {
+            1 => (u16::from_le_bytes([mmap[8], mmap[9]]) as usize, 10),
+            2 | 3 if mmap.len() >= 12 => (u32::from_le_bytes([mmap[8], mmap[9], mmap[10], mmap[11]]) as usize, 12),
+            _ => return Err(invalid("unsupported npy version")),
+        };
+        let offset = start + header_len;
+        let header = mmap.get(start..offset)
+            .and_then(|header| std::str::from_utf8(header).ok())
+            .ok_or_else(|| invalid("invalid npy header"))?;
+
+        if !header.contains("'descr': '<i8'") {
+            return Err(invalid("data must be little endian int64"));
+        }
+        if !header.contains("'fortran_order': False") {
+            return Err(invalid("data must be in C order"));
+        }
+        let len = header.split("'shape': (").nth(1)
+            .and_then(|shape| shape.split(')').next())
+            .and_then(|shape| shape.strip_suffix(','))
+            .and_then(|len| len.trim().parse::<usize>().ok())
+            .ok_or_else(|| invalid("data must be one dimensional"))?;
+
+        // Mappings are page aligned, so only the header length decides the alignment
+        if offset % std::mem::align_of::<i64>() != 0 {
+            return Err(invalid("data is not aligned"));
+        }
+        if mmap.len() < offset + len * std::mem::size_of::<i64>() {
+            return Err(invalid("file is truncated"));
+        }
+
+        Ok(Self { mmap, offset, len })
+    }
+
+    fn as_slice(&self) -> &[i64] {
+        unsafe { std::slice::from_raw_parts(self.mmap[self.offset..].as_ptr() as *const i64, self.len) }
+    }
+}
+
+// Location of a storage exported with `SparseGraphStorage::share_memory`, only the path crosses process boundaries.
+// The segment has the `save` layout, so `MmapGraphStorage::load` and `load_chunked` read it as well.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedGraphHandle {
+    pub dir: PathBuf,
+}
+
+impl SharedGraphHandle {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    // A fresh segment name in `root`, unique within and across processes
+    pub(crate) fn create_in(root: &Path) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+        Self::new(root.join(format!("tch_geometric_shared_{}_{}", std::process::id(), id)))
+    }
+
+    // Where segments are written in order of preference. /dev/shm is often limited to 64MB in containers, segments
+    // that don't fit go to the temp dir, whose pages are shared through the page cache as well.
+    pub(crate) fn roots() -> Vec<PathBuf> {
+        let shm = Path::new("/dev/shm");
+        let mut roots = Vec::new();
+        if shm.is_dir() {
+            roots.push(shm.to_path_buf());
+        }
+        roots.push(std::env::temp_dir());
+        roots
+    }
+
+    // Frees the segment once no process needs to map it anymore, existing mappings stay valid until dropped
+    pub fn unlink(&self) -> TensorResult<()> {
+        std::fs::remove_dir_all(&self.dir).map_err(io_error)
+    }
+}
+
+// Owns an exported segment and unlinks it when dropped, also when the exporting process unwinds from a panic.
+// Workers only need the handle.
+#[derive(Debug)]
+pub struct SharedGraphGuard {
+    handle: SharedGraphHandle,
+    keep: bool,
+}
+
+impl SharedGraphGuard {
+    pub(crate) fn new(handle: SharedGraphHandle) -> Self {
+        Self { handle, keep: false }
+    }
+
+    // Leaves the segment to whoever unlinks the handle
+    pub fn into_handle(mut self) -> SharedGraphHandle {
+        self.keep = true;
+        self.handle.clone()
+    }
+}
+
+impl Deref for SharedGraphGuard {
+    type Target = SharedGraphHandle;
+
+    fn deref(&self) -> &SharedGraphHandle {
+        &self.handle
+    }
+}
+
+impl Drop for SharedGraphGuard {
+    fn drop(&mut self) {
+        if !self.keep && self.handle.dir.exists() {
+            let _ = self.handle.unlink();
+        }
+    }
+}
+
+// Memory-mapped ptrs, indices and edge type if saved, as written by `SparseGraphStorage::save` or
+// `convert_chunked`. Pages are shared between threads and forked workers instead of being copied.
+pub struct MmapGraphStorage<Ty> {
+    ptrs: MmapArray,
+    indices: MmapArray,
+    edge_type: Option<MmapArray>,
+    _phantom: std::marker::PhantomData<Ty>,
+}
+
+pub type MmapCscGraphStorage = MmapGraphStorage<Csc>;
+pub type MmapCsrGraphStorage = MmapGraphStorage<Csr>;
+
+impl<Ty> MmapGraphStorage<Ty> {
+    pub fn load(dir: &Path) -> TensorResult<Self> {
+        Ok(Self {
+            ptrs: MmapArray::open(&dir.join(PTRS_FILE))?,
+            indices: MmapArray::open(&dir.join(INDICES_FILE))?,
+            edge_type: Some(dir.join(EDGE_TYPE_FILE)).filter(|path| path.exists()).map(|path| MmapArray::open(&path)).transpose()?,
+            _phantom: std::marker::PhantomData,
+        })
+    }
+
+    // Maps a storage exported by another process, the pages are shared with it instead of copied
+    pub fn from_shared(handle: &SharedGraphHandle) -> TensorResult<Self> {
+        Self::load(&handle.dir)
+    }
+
+    pub fn ptrs(&self) -> &[i64] {
+        self.ptrs.as_slice()
+    }
+
+    pub fn indices(&self) -> &[i64] {
+        self.indices.as_slice()
+    }
+
+    pub fn edge_type(&self) -> Option<&[i64]> {
+        self.edge_type.as_ref().map(MmapArray::as_slice)
+    }
+
+    pub fn validate(&self) -> Result<(), GraphError> {
+        SparseGraph::<Ty, i64, i64>::new(self.ptrs(), self.indices()).validate()
+    }
+}
+
+impl<'a, Ty> TryFrom<&'a MmapGraphStorage<Ty>> for SparseGraph<'a, Ty, i64, i64> {
+    type Error = TensorConversionError;
+
+    fn try_from(value: &'a MmapGraphStorage<Ty>) -> Result<Self, Self::Error> {
+        Ok(SparseGraph::new(value.ptrs(), value.indices()))
+    }
+}
+
+impl<Ty> NeighborSource for MmapGraphStorage<Ty> {
+    #[inline(always)]
+    fn node_count(&self) -> usize {
+        self.ptrs.len - 1
+    }
+
+    #[inline(always)]
+    fn edge_positions(&self, v: NodeIdx) -> Range<EdgePtr<usize>> {
+        let ptrs = self.ptrs();
+        ptrs[v as usize] as usize..ptrs[v as usize + 1] as usize
+    }
+
+    #[inline(always)]
+    fn neighbors(&self, v: NodeIdx) -> &[NodeIdx] {
+        &self.indices()[self.edge_positions(v)]
+    }
+
+    #[inline(always)]
+    fn neighbor_at(&self, ptr: EdgePtr<usize>) -> NodeIdx {
+        self.indices()[ptr]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use rand::SeedableRng;
+    use rand::rngs::SmallRng;
+    use crate::algo::neighbor_sampling::{IdentityFilter, UnweightedSampler, neighbor_sampling_homogenous};
+    use tch::Tensor;
+    use crate::data::{CscGraph, CscGraphStorage, MmapCscGraphStorage, SharedGraphHandle, load_karate_graph};
+    use crate::data::chunked::load_chunked;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_mmap_graph_storage() {
+        assert_send_sync::<MmapCscGraphStorage>();
+
+        let (_x, _, coo_graph) = load_karate_graph();
+        let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
+        let graph = CscGraph::<i64, i64>::try_from(&graph_data).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("tch_geometric_mmap_{}", std::process::id()));
+        graph_data.save(&dir).unwrap();
+        let mmap_data = MmapCscGraphStorage::load(&dir).unwrap();
+        mmap_data.validate().unwrap();
+        let mmap_graph = CscGraph::<i64, i64>::try_from(&mmap_data).unwrap();
+        assert_eq!(mmap_graph.ptrs, graph.ptrs);
+        assert_eq!(mmap_graph.indices, graph.indices);
+
+        let inputs = vec![0_i64, 1, 4, 5];
+        let inputs_state = vec![(); inputs.len()];
+        let num_neighbors = vec![4, 3];
+        let (samples, edges, offsets) = neighbor_sampling_homogenous(
+            &mut SmallRng::from_seed([0; 32]), &graph, &inputs, &num_neighbors,
+            &UnweightedSampler::<false>, &IdentityFilter, &inputs_state,
+        );
+        let (mmap_samples, mmap_edges, mmap_offsets) = neighbor_sampling_homogenous(
+            &mut SmallRng::from_seed([0; 32]), &mmap_graph, &inputs, &num_neighbors,
+            &UnweightedSampler::<false>, &IdentityFilter, &inputs_state,
+        );
+        assert_eq!(samples, mmap_samples);
+        assert_eq!(edges.rows, mmap_edges.rows);
+        assert_eq!(edges.cols, mmap_edges.cols);
+        assert_eq!(edges.edge_index, mmap_edges.edge_index);
+        assert_eq!(offsets, mmap_offsets);
+
+        assert!(MmapCscGraphStorage::load(&dir.join("missing")).is_err());
+        assert!(mmap_data.edge_type().is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_edge_type_round_trip() {
+        let (_x, _, coo_graph) = load_karate_graph();
+        let num_edges = coo_graph.row_col.size()[1];
+        let edge_type = Tensor::arange(num_edges, (tch::Kind::Int64, tch::Device::Cpu)).remainder(3);
+        let graph_data = CscGraphStorage::try_from(&coo_graph.with_edge_type(edge_type)).unwrap();
+        let expected: Vec<i64> = graph_data.edge_type().unwrap().into();
+
+        let dir = std::env::temp_dir().join(format!("tch_geometric_edge_type_{}", std::process::id()));
+        graph_data.save(&dir).unwrap();
+        let loaded: Vec<i64> = load_chunked(&dir).unwrap().edge_type().unwrap().into();
+        assert_eq!(loaded, expected);
+        assert_eq!(MmapCscGraphStorage::load(&dir).unwrap().edge_type().unwrap(), &expected[..]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let handle = graph_data.share_memory().unwrap();
+        assert_eq!(MmapCscGraphStorage::from_shared(&handle).unwrap().edge_type().unwrap(), &expected[..]);
+        handle.unlink().unwrap();
+
+        let (ptrs, indices) = graph_data.as_raw_parts().unwrap();
+        let borrowed = unsafe { CscGraphStorage::from_raw_parts(ptrs, indices, graph_data.edge_type_raw().unwrap()) }.unwrap();
+        assert_eq!(Vec::<i64>::from(borrowed.edge_type().unwrap()), expected);
+    }
+
+    const SHARED_DIR_VAR: &str = "TCH_GEOMETRIC_TEST_SHARED_DIR";
+    const SHARED_OUT_VAR: &str = "TCH_GEOMETRIC_TEST_SHARED_OUT";
+
+    fn sample_shared(graph: &CscGraph<i64, i64>) -> Vec<i64> {
+        let inputs = vec![0_i64, 1, 4, 5];
+        let (samples, _, _) = neighbor_sampling_homogenous(
+            &mut SmallRng::from_seed([0; 32]), graph, &inputs, &[4, 3],
+            &UnweightedSampler::<false>, &IdentityFilter, &vec![(); inputs.len()],
+        );
+        samples
+    }
+
+    // Child half of `test_shared_memory`, only does something when spawned by it. The samples go to the file the
+    // parent names, the test harness owns stdout.
+    #[test]
+    fn test_shared_memory_child() {
+        let (dir, out) = match (std::env::var(SHARED_DIR_VAR), std::env::var(SHARED_OUT_VAR)) {
+            (Ok(dir), Ok(out)) => (dir, out),
+            _ => return,
+        };
+        let data = MmapCscGraphStorage::from_shared(&SharedGraphHandle::new(dir.into())).unwrap();
+        let samples = sample_shared(&CscGraph::try_from(&data).unwrap());
+        std::fs::write(out, format!("{:?}", samples)).unwrap();
+    }
+
+    #[test]
+    fn test_shared_memory() {
+        let (_x, _, coo_graph) = load_karate_graph();
+        let graph_data = CscGraphStorage::try_from(&coo_graph).unwrap();
+        let expected = sample_shared(&CscGraph::try_from(&graph_data).unwrap());
+
+        let guard = graph_data.share_memory().unwrap();
+        let other = graph_data.share_memory().unwrap();
+        assert_ne!(*guard, *other);
+        let other_dir = other.dir.clone();
+        drop(other);
+        assert!(!other_dir.exists());
+        let shared = MmapCscGraphStorage::from_shared(&guard).unwrap();
+        assert_eq!(sample_shared(&CscGraph::try_from(&shared).unwrap()), expected);
+
+        // A separate process maps the same segment and draws the same samples
+        let out = std::env::temp_dir().join(format!("tch_geometric_shared_out_{}", std::process::id()));
+        let status = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "data::mmap::tests::test_shared_memory_child", "--test-threads=1"])
+            .env(SHARED_DIR_VAR, &guard.dir)
+            .env(SHARED_OUT_VAR, &out)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), format!("{:?}", expected));
+        std::fs::remove_file(&out).unwrap();
+
+        // Handing the segment over keeps it past the guard
+        let handle = guard.into_handle();
+        assert!(MmapCscGraphStorage::from_shared(&handle).is_ok());
+        handle.unlink().unwrap();
+        assert!(MmapCscGraphStorage::from_shared(&handle).is_err());
+
+        // A root that can't hold the segment is skipped, nothing is left behind in it
+        let full = std::env::temp_dir().join(format!("tch_geometric_shared_full_{}", std::process::id()));
+        std::fs::write(&full, "").unwrap();
+        let guard = graph_data.share_memory_in(&[full.clone(), std::env::temp_dir()]).unwrap();
+        assert!(guard.dir.starts_with(std::env::temp_dir()) && !guard.dir.starts_with(&full));
+        assert!(MmapCscGraphStorage::from_shared(&guard).is_ok());
+        assert!(graph_data.share_memory_in(std::slice::from_ref(&full)).is_err());
+        std::fs::remove_file(&full).unwrap();
+    }
+}
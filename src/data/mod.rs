@@ -2,7 +2,14 @@ pub mod graph;
 pub mod storage;
 pub mod io;
 pub mod transform;
+pub mod chunked;
+pub mod mmap;
+pub mod generators;
+pub mod adjlist;
 
 pub use graph::*;
 pub use storage::*;
-pub use io::*;
\ No newline at end of file
+pub use io::*;
+pub use mmap::*;
+pub use generators::*;
+pub use adjlist::*;
\ No newline at end of file
@@ -1,16 +1,49 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 use std::convert::{TryFrom};
-use std::ops::Add;
-use tch::{Device, IndexOp, Tensor};
+use std::fs;
+use std::ops::{Add, Deref};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use rayon::prelude::*;
+use tch::{Device, IndexOp, Kind, Tensor};
 use tch::kind::Element;
-use crate::data::graph::{Csc, Csr, SparseGraph, SparseGraphType, SparseGraphTypeTrait};
-use crate::utils::tensor::{check_device, TensorResult, TensorConversionError, try_tensor_to_slice_mut, try_tensor_to_slice};
-use crate::utils::types::IndexType;
+use crate::data::chunked::{EDGE_TYPE_FILE, INDICES_FILE, PERM_FILE, PTRS_FILE, io_error};
+use crate::data::mmap::{SharedGraphGuard, SharedGraphHandle};
+use crate::data::graph::{Csc, Csr, GraphError, GraphMeta, NeighborOrdering, SparseGraph, SparseGraphType, SparseGraphTypeTrait};
+use crate::utils::parallel;
+use crate::utils::tensor::{check_device, prepare_index_tensor, TensorResult, TensorConversionError, try_tensor_to_slice_mut, try_tensor_to_slice};
+use crate::utils::types::{DefaultIx, DefaultPtr, EdgeType, IndexType, NodeIdx, NodePtr, NodeType, RelType};
 
 pub type Size = (i64, i64);
 
+// Duplicate edges keep their original relative order, so the perm is the same across runs and devices
+fn stable_argsort(key: &Tensor) -> Tensor {
+    key.sort_stable(true, 0, false).1
+}
+
+// Sorts by (major, minor) with a single composite key major * minor_size + minor when
+// major_size * minor_size fits into an i64, and with two stable sorts otherwise
+fn lexsort(major: &Tensor, minor: &Tensor, major_size: i64, minor_size: i64) -> Tensor {
+    match major_size.checked_mul(minor_size) {
+        Some(_) => stable_argsort(&(major * minor_size).add(minor)),
+        None => lexsort_stable(major, minor),
+    }
+}
+
+fn lexsort_stable(major: &Tensor, minor: &Tensor) -> Tensor {
+    let minor_perm = stable_argsort(minor);
+    let major_perm = stable_argsort(&major.index_select(0, &minor_perm));
+    minor_perm.index_select(0, &major_perm)
+}
+
 pub struct CooGraphStorage {
     pub row_col: Tensor,
     pub size: Size,
+    pub meta: GraphMeta,
+    // Relation of every edge for relational graphs stored homogeneously (R-GCN style), aligned with the columns of
+    // `row_col` and carried along whenever the edges are reordered
+    pub edge_type: Option<Tensor>,
 }
 
 impl CooGraphStorage {
@@ -18,9 +51,113 @@ impl CooGraphStorage {
         Self {
             row_col,
             size,
+            meta: GraphMeta::default(),
+            edge_type: None,
+        }
+    }
+
+    pub fn with_meta(mut self, meta: GraphMeta) -> Self {
+        self.meta = meta;
+        self
+    }
+
+    pub fn with_edge_type(mut self, edge_type: Tensor) -> Self {
+        self.edge_type = Some(edge_type);
+        self
+    }
+
+    pub fn edge_type(&self) -> Option<&Tensor> {
+        self.edge_type.as_ref()
+    }
+
+    // Relations are numbered from zero, so this is the largest edge type plus one
+    pub fn num_relations(&self) -> i64 {
+        match &self.edge_type {
+            Some(edge_type) if edge_type.numel() > 0 => edge_type.max().int64_value(&[]) + 1,
+            _ => 0,
         }
     }
 
+    fn edge_type_data(&self) -> TensorResult<Option<Tensor>> {
+        self.edge_type.as_ref()
+            .map(|edge_type| {
+                let num_edges = self.row_col.size()[1];
+                if edge_type.size() != [num_edges] {
+                    return Err(TensorConversionError::InvalidShape(Some(format!(
+                        "edge_type must be of shape [{}], got {:?}", num_edges, edge_type.size()
+                    ))));
+                }
+                Ok(prepare_index_tensor(edge_type)?.contiguous())
+            })
+            .transpose()
+    }
+
+    // Fills in the metadata with a single pass over the edges, sorted means ordered by (row, col) like `sort`
+    pub fn detect_properties(&mut self) -> TensorResult<GraphMeta> {
+        let row_col = prepare_index_tensor(&self.row_col)?.contiguous();
+        let (rows, cols) = try_tensor_to_slice::<i64>(&row_col)?.split_at(row_col.size()[1] as usize);
+        let mut seen = std::collections::HashSet::with_capacity(rows.len());
+        let (mut is_multigraph, mut has_self_loops, mut is_sorted) = (false, false, true);
+        for (i, (u, v)) in rows.iter().zip(cols.iter()).enumerate() {
+            is_multigraph |= !seen.insert((*u, *v));
+            has_self_loops |= u == v;
+            is_sorted &= i == 0 || (rows[i - 1], cols[i - 1]) <= (*u, *v);
+        }
+        self.meta = GraphMeta {
+            is_multigraph: Some(is_multigraph), has_self_loops: Some(has_self_loops), is_sorted: Some(is_sorted),
+        };
+        Ok(self.meta)
+    }
+
+    // Sorts the edges and merges parallel ones. Also returns the position of every original edge in the coalesced
+    // graph, to reduce edge attributes with. Edges of different types are distinct, they are ordered by type within
+    // a (row, col) pair and keep the graph a multigraph.
+    pub fn coalesce(&self) -> TensorResult<(CooGraphStorage, Tensor)> {
+        let row_col = prepare_index_tensor(&self.row_col)?.contiguous();
+        let (rows, cols) = try_tensor_to_slice::<i64>(&row_col)?.split_at(row_col.size()[1] as usize);
+        let edge_type = self.edge_type_data()?;
+        let types = edge_type.as_ref().map(try_tensor_to_slice::<i64>).transpose()?;
+        let key = |e: usize| (rows[e], cols[e], types.map_or(0, |t| t[e]));
+
+        let mut order: Vec<usize> = (0..rows.len()).collect();
+        parallel::install(|| order.par_sort_by_key(|e| key(*e)));
+
+        let (mut out_rows, mut out_cols, mut out_types) = (Vec::new(), Vec::new(), Vec::new());
+        let mut positions = vec![0_i64; rows.len()];
+        for (i, e) in order.iter().enumerate() {
+            if i == 0 || key(order[i - 1]) != key(*e) {
+                out_rows.push(rows[*e]);
+                out_cols.push(cols[*e]);
+                out_types.push(key(*e).2);
+            }
+            positions[*e] = out_rows.len() as i64 - 1;
+        }
+
+        let is_multigraph = (1..out_rows.len()).any(|i| (out_rows[i - 1], out_cols[i - 1]) == (out_rows[i], out_cols[i]));
+        let meta = GraphMeta {
+            has_self_loops: Some(out_rows.iter().zip(out_cols.iter()).any(|(u, v)| u == v)),
+            ..GraphMeta::default()
+        }.with_multigraph(is_multigraph).with_sorted(true);
+        let row_col = Tensor::stack(&[Tensor::of_slice(&out_rows), Tensor::of_slice(&out_cols)], 0);
+        let mut coalesced = CooGraphStorage::new(row_col, self.size).with_meta(meta);
+        if types.is_some() {
+            coalesced = coalesced.with_edge_type(Tensor::of_slice(&out_types));
+        }
+        Ok((coalesced, Tensor::of_slice(&positions)))
+    }
+
+    // Adds the reverse of every edge with the same type, the edges are sorted and parallel ones of the same type merged
+    pub fn to_undirected(&self) -> TensorResult<CooGraphStorage> {
+        let num_nodes = self.size.0.max(self.size.1);
+        let (row_col, edge_type) = undirected_row_col(&prepare_index_tensor(&self.row_col)?, self.edge_type_data()?.as_ref())?;
+        let mut undirected = CooGraphStorage::new(row_col, (num_nodes, num_nodes));
+        if let Some(edge_type) = edge_type {
+            undirected = undirected.with_edge_type(edge_type);
+        }
+        undirected.detect_properties()?;
+        Ok(undirected)
+    }
+
     pub fn row(&self) -> Tensor {
         self.row_col.select(0, 0)
     }
@@ -28,12 +165,78 @@ impl CooGraphStorage {
     pub fn col(&self) -> Tensor {
         self.row_col.select(0, 1)
     }
+
+    // Applies mapping[old_id] = new_id to both endpoints, edges touching a node mapped to -1 are dropped
+    pub fn relabel_nodes(&self, mapping: &Tensor) -> TensorResult<CooGraphStorage> {
+        let num_nodes = self.size.0.max(self.size.1);
+        if mapping.size() != vec![num_nodes] {
+            return Err(TensorConversionError::InvalidShape(Some(format!(
+                "mapping must have {} entries, got shape {:?}", num_nodes, mapping.size()
+            ))));
+        }
+
+        let mapping = mapping.to_device(self.row_col.device()).to_kind(Kind::Int64);
+        let row = mapping.index_select(0, &self.row());
+        let col = mapping.index_select(0, &self.col());
+        let keep = row.ge(0).logical_and(&col.ge(0));
+        let row_col = Tensor::stack(&[row.masked_select(&keep), col.masked_select(&keep)], 0);
+
+        let n = if num_nodes > 0 { (mapping.max().int64_value(&[]) + 1).max(0) } else { 0 };
+        let mut relabeled = CooGraphStorage::new(row_col, (n, n));
+        if let Some(edge_type) = self.edge_type_data()? {
+            relabeled = relabeled.with_edge_type(edge_type.masked_select(&keep));
+        }
+        Ok(relabeled)
+    }
+
+    // Sorts the edges by (row, col), perm[i] is the original position of the i-th sorted edge
+    pub fn sort(&self) -> (CooGraphStorage, Tensor) {
+        let perm = lexsort(&self.row(), &self.col(), self.size.0, self.size.1);
+        let row_col = self.row_col.index_select(1, &perm);
+
+        let mut sorted = CooGraphStorage::new(row_col, self.size).with_meta(self.meta.with_sorted(true));
+        sorted.edge_type = self.edge_type.as_ref().map(|edge_type| edge_type.index_select(0, &perm));
+        (sorted, perm)
+    }
+}
+
+// Compares edge sets, the multiplicity of parallel edges is ignored
+pub fn is_undirected(graph: &CooGraphStorage) -> TensorResult<bool> {
+    let (row, col) = (graph.row().contiguous(), graph.col().contiguous());
+    let row_data = try_tensor_to_slice::<i64>(&row)?;
+    let col_data = try_tensor_to_slice::<i64>(&col)?;
+
+    // Compare the sorted edge set with its transpose, self loops are their own reverse
+    let mut edges: Vec<(i64, i64)> = row_data.iter().cloned().zip(col_data.iter().cloned()).collect();
+    let mut transposed: Vec<(i64, i64)> = edges.iter().map(|&(u, v)| (v, u)).collect();
+    edges.sort_unstable();
+    edges.dedup();
+    transposed.sort_unstable();
+    transposed.dedup();
+
+    Ok(edges == transposed)
+}
+
+pub fn is_directed(graph: &CooGraphStorage) -> TensorResult<bool> {
+    is_undirected(graph).map(|undirected| !undirected)
+}
+
+fn is_identity(perm: &Tensor) -> bool {
+    perm.equal(&Tensor::arange(perm.size()[0], (perm.kind(), perm.device())))
 }
 
 pub struct SparseGraphStorage<Ty> {
     pub ptrs: Tensor,
     pub indices: Tensor,
-    pub perm: Option<Tensor>,
+    // Read through `perm()`, it can't be replaced as that would invalidate `perm_identity`
+    perm: Option<Tensor>,
+    pub ordering: NeighborOrdering,
+    // `is_sorted` refers to the neighbor blocks and is kept in line with `ordering`
+    pub meta: GraphMeta,
+    // Per edge position, see `CooGraphStorage::edge_type`
+    pub edge_type: Option<Tensor>,
+    // Cached `perm_is_identity`
+    perm_identity: OnceLock<bool>,
     _phantom: std::marker::PhantomData<Ty>,
 }
 
@@ -48,10 +251,86 @@ impl<Ty> SparseGraphStorage<Ty> {
     ) -> Self {
         Self {
             ptrs, indices, perm,
+            ordering: NeighborOrdering::Unsorted,
+            meta: GraphMeta::default(),
+            edge_type: None,
+            perm_identity: OnceLock::new(),
             _phantom: std::marker::PhantomData,
         }
     }
 
+    pub fn with_ordering(mut self, ordering: NeighborOrdering) -> Self {
+        self.ordering = ordering;
+        if ordering == NeighborOrdering::ById {
+            self.meta.is_sorted = Some(true);
+        }
+        self
+    }
+
+    pub fn with_meta(mut self, meta: GraphMeta) -> Self {
+        self.meta = meta;
+        self
+    }
+
+    pub fn with_edge_type(mut self, edge_type: Tensor) -> Self {
+        self.edge_type = Some(edge_type);
+        self
+    }
+
+    pub fn edge_type(&self) -> Option<&Tensor> {
+        self.edge_type.as_ref()
+    }
+
+    pub fn num_relations(&self) -> i64 {
+        match &self.edge_type {
+            Some(edge_type) if edge_type.numel() > 0 => edge_type.max().int64_value(&[]) + 1,
+            _ => 0,
+        }
+    }
+
+    // Original edge id of every stored edge
+    pub fn perm(&self) -> Option<&Tensor> {
+        self.perm.as_ref()
+    }
+
+    pub fn into_parts(self) -> (Tensor, Tensor, Option<Tensor>) {
+        (self.ptrs, self.indices, self.perm)
+    }
+
+    // Whether the i-th stored edge is the i-th original edge, no perm counts as the identity. Checked on the first
+    // call, conversions from an already sorted COO don't store a perm at all.
+    pub fn perm_is_identity(&self) -> bool {
+        *self.perm_identity.get_or_init(|| match &self.perm {
+            Some(perm) => is_identity(perm),
+            None => true,
+        })
+    }
+
+    // Reorders edge features given in the original edge order into the stored order, nothing is copied when the
+    // order is the same
+    pub fn permute_edge_attr(&self, attr: &Tensor) -> Tensor {
+        match &self.perm {
+            Some(perm) if !self.perm_is_identity() => attr.index_select(0, &perm.to_device(attr.device())),
+            _ => attr.shallow_clone(),
+        }
+    }
+
+    // The edge types of the given positions for a storage derived from this one
+    fn select_edge_type(mut self, source: &Self, positions: &Tensor) -> Self {
+        self.edge_type = source.edge_type.as_ref().map(|edge_type| edge_type.index_select(0, positions));
+        self
+    }
+
+    // Fills in the metadata with a single pass over the neighbor blocks, sorted blocks also set the ordering
+    pub fn detect_properties(&mut self) -> TensorResult<GraphMeta> {
+        let meta = SparseGraph::<Ty, i64, i64>::try_from(&*self)?.detect_properties();
+        self.meta = meta;
+        if meta.is_sorted == Some(true) && self.ordering == NeighborOrdering::Unsorted {
+            self.ordering = NeighborOrdering::ById;
+        }
+        Ok(meta)
+    }
+
     pub fn from_data(
         ptrs: Tensor,
         indices: Tensor,
@@ -59,9 +338,396 @@ impl<Ty> SparseGraphStorage<Ty> {
         Self {
             ptrs, indices,
             perm: None,
+            ordering: NeighborOrdering::Unsorted,
+            meta: GraphMeta::default(),
+            edge_type: None,
+            perm_identity: OnceLock::new(),
             _phantom: std::marker::PhantomData,
         }
     }
+
+    pub fn validate(&self) -> Result<(), GraphError> {
+        let ptrs = try_tensor_to_slice::<i64>(&self.ptrs)?;
+        let indices = try_tensor_to_slice::<i64>(&self.indices)?;
+        SparseGraph::<Ty, i64, i64>::new(ptrs, indices).validate()
+    }
+
+    // Writes the tensors as npy files into `dir`, the same layout `convert_chunked` produces
+    pub fn save(&self, dir: &Path) -> TensorResult<()> {
+        fs::create_dir_all(dir).map_err(io_error)?;
+        self.ptrs.write_npy(dir.join(PTRS_FILE)).map_err(io_error)?;
+        self.indices.write_npy(dir.join(INDICES_FILE)).map_err(io_error)?;
+        if let Some(perm) = &self.perm {
+            perm.write_npy(dir.join(PERM_FILE)).map_err(io_error)?;
+        }
+        if let Some(edge_type) = &self.edge_type {
+            prepare_index_tensor(edge_type)?.write_npy(dir.join(EDGE_TYPE_FILE)).map_err(io_error)?;
+        }
+        Ok(())
+    }
+
+    // Exports the tensors to shared memory (`/dev/shm` where available) for worker processes that are spawned instead
+    // of forked, they map the handle with `MmapGraphStorage::from_shared` instead of receiving a copy. The segment
+    // lives until the guard is dropped, or until `SharedGraphHandle::unlink` after `SharedGraphGuard::into_handle`.
+    pub fn share_memory(&self) -> TensorResult<SharedGraphGuard> {
+        self.share_memory_in(&SharedGraphHandle::roots())
+    }
+
+    // Tries the roots in order, a root the segment can't be written to (such as a full tmpfs) is cleaned up and skipped
+    pub(crate) fn share_memory_in(&self, roots: &[PathBuf]) -> TensorResult<SharedGraphGuard> {
+        let mut result = Err(TensorConversionError::Unknown("no directory to share the graph in".to_string()));
+        for root in roots {
+            let guard = SharedGraphGuard::new(SharedGraphHandle::create_in(root));
+            result = self.save(&guard.dir).map(|_| guard);
+            if result.is_ok() {
+                break;
+            }
+        }
+        result
+    }
+
+    pub fn require_ordering(&self, ordering: NeighborOrdering) -> Result<(), GraphError> {
+        if self.ordering != ordering {
+            return Err(GraphError::InvalidOrdering { expected: ordering, got: self.ordering });
+        }
+        Ok(())
+    }
+
+    // Binary searches the neighbors of `x`, which is only correct when they are sorted by id (by ordering or by the
+    // metadata)
+    pub fn has_edge(&self, x: NodeIdx, y: NodeIdx) -> Result<bool, GraphError> {
+        if self.meta.is_sorted != Some(true) {
+            self.require_ordering(NeighborOrdering::ById)?;
+        }
+        let graph = SparseGraph::<Ty, i64, i64>::try_from(self)?;
+        Ok(graph.has_edge(x, y))
+    }
+
+    pub fn sort_neighbors_by_id(&self) -> TensorResult<Self> {
+        if self.ordering == NeighborOrdering::ById {
+            return Ok(self.shallow_clone());
+        }
+        self.sort_neighbors_by_key(None, NeighborOrdering::ById)
+    }
+
+    // `times` holds one timestamp per edge position, ties are broken by id
+    pub fn sort_neighbors_by_time(&self, times: &Tensor) -> TensorResult<Self> {
+        if times.size() != self.indices.size() {
+            return Err(TensorConversionError::InvalidShape(Some(format!(
+                "times must be of shape {:?}, got {:?}", self.indices.size(), times.size()
+            ))));
+        }
+        self.sort_neighbors_by_key(Some(times), NeighborOrdering::ByTime)
+    }
+
+    fn shallow_clone(&self) -> Self {
+        let mut clone = Self::new(
+            self.ptrs.shallow_clone(), self.indices.shallow_clone(), self.perm.as_ref().map(|p| p.shallow_clone()),
+        ).with_meta(self.meta).with_ordering(self.ordering);
+        clone.edge_type = self.edge_type.as_ref().map(|t| t.shallow_clone());
+        clone
+    }
+
+    fn sort_neighbors_by_key(&self, key: Option<&Tensor>, ordering: NeighborOrdering) -> TensorResult<Self> {
+        self.sort_neighbors_by_key_with_perm(key, ordering).map(|(sorted, _)| sorted)
+    }
+
+    // Sorts every neighbor block by (key, id) in parallel and composes the permutation with the existing perm. Also
+    // returns the permutation of the positions, to carry along attributes aligned with the current positions.
+    fn sort_neighbors_by_key_with_perm(
+        &self,
+        key: Option<&Tensor>,
+        ordering: NeighborOrdering,
+    ) -> TensorResult<(Self, Tensor)> {
+        let ptrs_data = try_tensor_to_slice::<i64>(&self.ptrs)?;
+        let indices_data = try_tensor_to_slice::<i64>(&self.indices)?;
+        let key = key.map(|t| t.to_kind(Kind::Int64).contiguous());
+        let key_data = match key.as_ref() {
+            Some(t) => Some(try_tensor_to_slice::<i64>(t)?),
+            None => None,
+        };
+
+        let mut positions: Vec<i64> = (0..indices_data.len() as i64).collect();
+        let mut blocks: Vec<&mut [i64]> = Vec::with_capacity(ptrs_data.len().saturating_sub(1));
+        let mut rest = positions.as_mut_slice();
+        for w in ptrs_data.windows(2) {
+            let (block, tail) = rest.split_at_mut((w[1] - w[0]) as usize);
+            blocks.push(block);
+            rest = tail;
+        }
+        parallel::install(|| blocks.into_par_iter().for_each(|block| {
+            block.sort_by_key(|&p| (key_data.map_or(0, |t| t[p as usize]), indices_data[p as usize]));
+        }));
+
+        let local_perm = Tensor::of_slice(&positions).to_device(self.indices.device());
+        let perm = match &self.perm {
+            Some(perm) => perm.index_select(0, &local_perm),
+            None => local_perm.shallow_clone(),
+        };
+        let sorted = Self::new(
+            self.ptrs.shallow_clone(), self.indices.index_select(0, &local_perm), Some(perm),
+        ).with_meta(GraphMeta { is_sorted: None, ..self.meta }).with_ordering(ordering).select_edge_type(self, &local_perm);
+        Ok((sorted, local_perm))
+    }
+}
+
+impl CscGraphStorage {
+    // The subgraph of the destination columns start..end, column c of the slice is column start + c of this graph
+    // and the returned offset is `start`. Row ids stay global, so callers sampling more than one hop translate the
+    // sampled rows with the offset before feeding them back as columns. Indices and perm are narrowed views, without
+    // a perm the slice gets one so that it still maps to the edges of the full graph.
+    pub fn col_slice(&self, start: i64, end: i64) -> TensorResult<(CscGraphStorage, i64)> {
+        let num_cols = self.ptrs.size()[0] - 1;
+        if start < 0 || start > end || end > num_cols {
+            return Err(TensorConversionError::InvalidShape(Some(format!(
+                "invalid column slice {}..{} of a graph with {} columns", start, end, num_cols
+            ))));
+        }
+
+        let ptr_start = self.ptrs.int64_value(&[start]);
+        let ptr_end = self.ptrs.int64_value(&[end]);
+        let ptrs = self.ptrs.narrow(0, start, end - start + 1) - ptr_start;
+        let indices = self.indices.narrow(0, ptr_start, ptr_end - ptr_start);
+        let perm = match &self.perm {
+            Some(perm) => perm.narrow(0, ptr_start, ptr_end - ptr_start),
+            None => Tensor::arange_start(ptr_start, ptr_end, (Kind::Int64, self.indices.device())),
+        };
+
+        let mut slice = CscGraphStorage::new(ptrs, indices, Some(perm)).with_meta(self.meta.subset()).with_ordering(self.ordering);
+        slice.edge_type = self.edge_type.as_ref().map(|t| t.narrow(0, ptr_start, ptr_end - ptr_start));
+        Ok((slice, start))
+    }
+
+    // Restricts the graph to the edges with t_start <= edge_time < t_end, where `edge_time` holds one timestamp per
+    // edge position like for `sort_neighbors_by_time`. Only the retained edges are compacted, in their current order,
+    // so the neighbor ordering carries over. Like `col_slice` the perm maps into the edges of the full graph.
+    pub fn time_window_view(&self, edge_time: &Tensor, t_start: i64, t_end: i64) -> TensorResult<CscGraphStorage> {
+        if edge_time.size() != self.indices.size() {
+            return Err(TensorConversionError::InvalidShape(Some(format!(
+                "edge_time must be of shape {:?}, got {:?}", self.indices.size(), edge_time.size()
+            ))));
+        }
+        if t_start > t_end {
+            return Err(TensorConversionError::Unknown(format!("invalid time window [{}, {})", t_start, t_end)));
+        }
+
+        let ptrs = prepare_index_tensor(&self.ptrs)?;
+        let ptrs_data = try_tensor_to_slice::<i64>(&ptrs)?;
+        let edge_time = edge_time.to_device(Device::Cpu).to_kind(Kind::Int64).contiguous();
+        let time_data = try_tensor_to_slice::<i64>(&edge_time)?;
+
+        let mut out_ptrs = Vec::with_capacity(ptrs_data.len());
+        let mut positions = Vec::new();
+        out_ptrs.push(0);
+        for w in ptrs_data.windows(2) {
+            positions.extend((w[0]..w[1]).filter(|p| (t_start..t_end).contains(&time_data[*p as usize])));
+            out_ptrs.push(positions.len() as i64);
+        }
+
+        let device = self.indices.device();
+        let positions = Tensor::of_slice(&positions).to_device(device);
+        let perm = match &self.perm {
+            Some(perm) => perm.index_select(0, &positions),
+            None => positions.shallow_clone(),
+        };
+        Ok(CscGraphStorage::new(
+            Tensor::of_slice(&out_ptrs).to_device(device), self.indices.index_select(0, &positions), Some(perm),
+        ).with_meta(self.meta.subset()).with_ordering(self.ordering).select_edge_type(self, &positions))
+    }
+
+    // Merges a batch of new edges into the graph without sorting the existing ones again: only the batch is sorted,
+    // then every column is merged in one pass. The perm keeps the old edge ids and maps new edge i to E + i, with E
+    // the number of existing edges, which is the perm of converting the concatenated edge list. When the neighbors
+    // are sorted by id an old edge comes before an equal new one, otherwise the new edges are appended to their
+    // column and the result is unsorted. Duplicate edges are kept.
+    pub fn merge_coo(&self, new_edges: &CooGraphStorage) -> TensorResult<CscGraphStorage> {
+        let num_cols = self.ptrs.size()[0] - 1;
+        if new_edges.size.1 != num_cols {
+            return Err(TensorConversionError::InvalidShape(Some(format!(
+                "new edges must have {} columns, got {}", num_cols, new_edges.size.1
+            ))));
+        }
+        if self.edge_type.is_some() != new_edges.edge_type.is_some() {
+            return Err(TensorConversionError::Unknown("either both or none of the graphs must have edge types".to_string()));
+        }
+        let added = CscGraphStorage::try_from(new_edges)?;
+        let types = self.edge_type.as_ref().map(prepare_index_tensor).transpose()?.map(|t| t.contiguous());
+        let types_data = types.as_ref().map(try_tensor_to_slice::<i64>).transpose()?;
+        let added_types = added.edge_type.as_ref().map(|t| t.contiguous());
+        let added_types_data = added_types.as_ref().map(try_tensor_to_slice::<i64>).transpose()?;
+        let mut out_types = Vec::new();
+
+        let ptrs = prepare_index_tensor(&self.ptrs)?;
+        let indices = prepare_index_tensor(&self.indices)?;
+        let perm = self.perm.as_ref().map(prepare_index_tensor).transpose()?;
+        let ptrs_data = try_tensor_to_slice::<i64>(&ptrs)?;
+        let indices_data = try_tensor_to_slice::<i64>(&indices)?;
+        let perm_data = perm.as_ref().map(try_tensor_to_slice::<i64>).transpose()?;
+        let added_ptrs = try_tensor_to_slice::<i64>(&added.ptrs)?;
+        let added_indices = try_tensor_to_slice::<i64>(&added.indices)?;
+        // Sorted new edges come without a perm
+        let added_perm = added.perm.as_ref().map(try_tensor_to_slice::<i64>).transpose()?;
+
+        let num_edges = indices_data.len() + added_indices.len();
+        let offset = indices_data.len() as i64;
+        let by_id = self.ordering == NeighborOrdering::ById;
+        let (mut out_ptrs, mut out_indices, mut out_perm) =
+            (Vec::with_capacity(ptrs_data.len()), Vec::with_capacity(num_edges), Vec::with_capacity(num_edges));
+        out_ptrs.push(0);
+        for c in 0..num_cols as usize {
+            let (mut i, mut j) = (ptrs_data[c] as usize, added_ptrs[c] as usize);
+            let (old_end, new_end) = (ptrs_data[c + 1] as usize, added_ptrs[c + 1] as usize);
+            while i < old_end || j < new_end {
+                let take_old = j == new_end || (i < old_end && (!by_id || indices_data[i] <= added_indices[j]));
+                if take_old {
+                    out_indices.push(indices_data[i]);
+                    out_perm.push(perm_data.map_or(i as i64, |p| p[i]));
+                    out_types.extend(types_data.map(|t| t[i]));
+                    i += 1;
+                } else {
+                    out_indices.push(added_indices[j]);
+                    out_perm.push(offset + added_perm.map_or(j as i64, |p| p[j]));
+                    out_types.extend(added_types_data.map(|t| t[j]));
+                    j += 1;
+                }
+            }
+            out_ptrs.push(out_indices.len() as i64);
+        }
+
+        let device = self.indices.device();
+        let ordering = if by_id { NeighborOrdering::ById } else { NeighborOrdering::Unsorted };
+        let mut merged = CscGraphStorage::new(
+            Tensor::of_slice(&out_ptrs).to_device(device),
+            Tensor::of_slice(&out_indices).to_device(device),
+            Some(Tensor::of_slice(&out_perm).to_device(device)),
+        ).with_ordering(ordering);
+        if types_data.is_some() {
+            merged = merged.with_edge_type(Tensor::of_slice(&out_types).to_device(device));
+        }
+        Ok(merged)
+    }
+}
+
+fn require_raw<'a>(name: &str, tensor: &'a Tensor) -> TensorResult<&'a [i64]> {
+    if tensor.dim() != 1 || !tensor.is_contiguous() {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "{} must be a contiguous vector, got shape {:?} and strides {:?}", name, tensor.size(), tensor.stride()
+        ))));
+    }
+    try_tensor_to_slice::<i64>(tensor)
+}
+
+impl<Ty> SparseGraphStorage<Ty> {
+    // Borrowed views of ptrs and indices for zero-copy handoff. Fails unless both are contiguous int64 cpu vectors.
+    pub fn as_raw_parts(&self) -> TensorResult<(&[i64], &[i64])> {
+        Ok((require_raw("ptrs", &self.ptrs)?, require_raw("indices", &self.indices)?))
+    }
+
+    // Borrowed view of the edge type if any, with the same requirements as `as_raw_parts`
+    pub fn edge_type_raw(&self) -> TensorResult<Option<&[i64]>> {
+        self.edge_type.as_ref().map(|edge_type| require_raw("edge_type", edge_type)).transpose()
+    }
+
+    /// Wraps external buffers as a graph without copying them, the result borrows the buffers for 'a. The layout
+    /// (ptrs[0] == 0, non decreasing ptrs ending at indices.len(), indices in range) is validated.
+    ///
+    /// # Safety
+    ///
+    /// The tensors alias the buffers but do not carry their lifetime, so neither the tensors of the returned
+    /// storage nor anything derived from them without a copy (shallow clones, views) may outlive the buffers.
+    /// They must not be written to either, in place ops would write through to the borrowed memory.
+    pub unsafe fn from_raw_parts<'a>(
+        ptrs: &'a [i64],
+        indices: &'a [i64],
+        edge_type: Option<&'a [i64]>,
+    ) -> TensorResult<BorrowedGraphStorage<'a, Ty>> {
+        SparseGraph::<Ty, i64, i64>::new(ptrs, indices).validate()
+            .map_err(|e| TensorConversionError::Unknown(e.to_string()))?;
+        if let Some(edge_type) = edge_type.filter(|edge_type| edge_type.len() != indices.len()) {
+            return Err(TensorConversionError::InvalidShape(Some(format!(
+                "edge_type must have {} entries, got {}", indices.len(), edge_type.len()
+            ))));
+        }
+
+        let wrap = |data: &[i64]| Tensor::of_blob(
+            data.as_ptr() as *const u8, &[data.len() as i64], &[1], Kind::Int64, Device::Cpu,
+        );
+        let mut storage = SparseGraphStorage::from_data(wrap(ptrs), wrap(indices));
+        storage.edge_type = edge_type.map(wrap);
+        Ok(BorrowedGraphStorage {
+            storage,
+            _buffers: std::marker::PhantomData,
+        })
+    }
+}
+
+// A graph over borrowed buffers, see `SparseGraphStorage::from_raw_parts`
+pub struct BorrowedGraphStorage<'a, Ty> {
+    storage: SparseGraphStorage<Ty>,
+    _buffers: std::marker::PhantomData<&'a [i64]>,
+}
+
+impl<'a, Ty> Deref for BorrowedGraphStorage<'a, Ty> {
+    type Target = SparseGraphStorage<Ty>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.storage
+    }
+}
+
+// A graph whose edges carry a type in 0..num_types. The neighbor blocks are grouped by type, edges of node v with
+// type t occupy type_ptrs[v * num_types + t]..type_ptrs[v * num_types + t + 1].
+pub struct TypedGraphStorage<Ty> {
+    pub graph: SparseGraphStorage<Ty>,
+    pub edge_types: Tensor,
+    pub type_ptrs: Tensor,
+    pub num_types: i64,
+}
+
+pub type TypedCscGraphStorage = TypedGraphStorage<Csc>;
+
+impl<Ty> TypedGraphStorage<Ty> {
+    // `edge_types` is aligned with the edge positions of `graph`
+    pub fn new(graph: &SparseGraphStorage<Ty>, edge_types: &Tensor, num_types: i64) -> TensorResult<Self> {
+        if edge_types.size() != graph.indices.size() {
+            return Err(TensorConversionError::InvalidShape(Some(format!(
+                "edge_types must be of shape {:?}, got {:?}", graph.indices.size(), edge_types.size()
+            ))));
+        }
+        let edge_types = edge_types.to_kind(Kind::Int64);
+        if edge_types.numel() > 0 {
+            let (min, max) = (edge_types.min().int64_value(&[]), edge_types.max().int64_value(&[]));
+            if min < 0 || max >= num_types {
+                return Err(TensorConversionError::InvalidShape(Some(format!(
+                    "edge types must be in 0..{}, got {}..={}", num_types, min, max
+                ))));
+            }
+        }
+
+        let (sorted, local_perm) = graph.sort_neighbors_by_key_with_perm(Some(&edge_types), NeighborOrdering::Unsorted)?;
+        let edge_types = edge_types.index_select(0, &local_perm).contiguous();
+
+        // The blocks are sorted by type, so the prefix sum over the (node, type) counts yields the sub-ranges
+        let ptrs_data = try_tensor_to_slice::<i64>(&sorted.ptrs)?;
+        let types_data = try_tensor_to_slice::<i64>(&edge_types)?;
+        let num_nodes = ptrs_data.len().saturating_sub(1);
+        let mut type_ptrs = vec![0_i64; num_nodes * num_types as usize + 1];
+        for (v, w) in ptrs_data.windows(2).enumerate() {
+            for t in &types_data[w[0] as usize..w[1] as usize] {
+                type_ptrs[v * num_types as usize + *t as usize + 1] += 1;
+            }
+        }
+        for i in 1..type_ptrs.len() {
+            type_ptrs[i] += type_ptrs[i - 1];
+        }
+
+        Ok(Self {
+            graph: sorted,
+            edge_types,
+            type_ptrs: Tensor::of_slice(&type_ptrs),
+            num_types,
+        })
+    }
 }
 
 pub fn ind2ptr(
@@ -104,25 +770,29 @@ impl<Ty: SparseGraphTypeTrait> TryFrom<&CooGraphStorage> for SparseGraphStorage<
     type Error = TensorConversionError;
 
     fn try_from(value: &CooGraphStorage) -> Result<Self, Self::Error> {
-        let (row, col) = (value.row(), value.col());
+        let row_col = prepare_index_tensor(&value.row_col)?;
+        let (row, col) = (row_col.select(0, 0), row_col.select(0, 1));
         let size = value.size;
+        // Parallel edges and self loops carry over, sorted has a different meaning for compressed graphs
+        let meta = GraphMeta { is_sorted: None, ..value.meta };
+        let edge_type = value.edge_type_data()?;
 
-        match Ty::get_type() {
-            SparseGraphType::Csr => {
-                let perm = (&row * size.1).add(&col).argsort(0, false);
-                let row_ptrs = ind2ptr(&row.i(&perm), size.0)?;
-                let col_indices = col.i(&perm);
-
-                Ok(Self::new(row_ptrs, col_indices, Some(perm)))
-            }
-            SparseGraphType::Csc => {
-                let perm = (&col * size.0).add(&row).argsort(0, false);
-                let col_ptrs = ind2ptr(&col.i(&perm), size.1)?;
-                let row_indices = row.i(&perm);
+        // (major, minor) are (row, col) for csr and (col, row) for csc. Already sorted input is taken as is and
+        // stored without a perm.
+        let (major, minor, num_major, num_minor) = match Ty::get_type() {
+            SparseGraphType::Csr => (row, col, size.0, size.1),
+            SparseGraphType::Csc => (col, row, size.1, size.0),
+        };
+        let perm = lexsort(&major, &minor, num_major, num_minor);
+        let (ptrs, indices, perm) = if is_identity(&perm) {
+            (ind2ptr(&major, num_major)?, minor, None)
+        } else {
+            (ind2ptr(&major.i(&perm), num_major)?, minor.i(&perm), Some(perm))
+        };
 
-                Ok(Self::new(col_ptrs, row_indices, Some(perm)))
-            }
-        }
+        let mut graph = Self::new(ptrs, indices, perm).with_meta(meta).with_ordering(NeighborOrdering::ById);
+        graph.edge_type = edge_type.map(|t| graph.permute_edge_attr(&t));
+        Ok(graph)
     }
 }
 
@@ -140,14 +810,209 @@ impl<
 }
 
 
+// A compressed graph in plain Rust buffers, for pipelines that don't go through tch tensors otherwise. Like
+// `SparseGraphStorage`, perm[i] is the position in the original edge list of the i-th stored edge.
+pub struct SparseGraphOwned<Ty, Ptr = DefaultPtr, Ix = DefaultIx> {
+    pub ptrs: Vec<NodePtr<Ptr>>,
+    pub indices: Vec<NodeIdx<Ix>>,
+    pub perm: Vec<i64>,
+    _phantom: std::marker::PhantomData<Ty>,
+}
+
+pub type CscGraphOwned<Ptr = DefaultPtr, Ix = DefaultIx> = SparseGraphOwned<Csc, Ptr, Ix>;
+pub type CsrGraphOwned<Ptr = DefaultPtr, Ix = DefaultIx> = SparseGraphOwned<Csr, Ptr, Ix>;
+
+impl<Ty: SparseGraphTypeTrait, Ptr: IndexType, Ix: IndexType> SparseGraphOwned<Ty, Ptr, Ix> {
+    // Same layout as the conversion from a `CooGraphStorage`: neighbors sorted by id, duplicate edges in input order
+    pub fn from_edge_slices<T: Copy + Into<i64> + Sync>(rows: &[T], cols: &[T], size: Size) -> TensorResult<Self> {
+        if rows.len() != cols.len() {
+            return Err(TensorConversionError::InvalidShape(Some(format!(
+                "rows and cols must have the same length, got {} and {}", rows.len(), cols.len()
+            ))));
+        }
+        let (major, minor, major_size, minor_size) = match Ty::get_type() {
+            SparseGraphType::Csr => (rows, cols, size.0, size.1),
+            SparseGraphType::Csc => (cols, rows, size.1, size.0),
+        };
+        if let Some(i) = (0..rows.len()).find(|&i| {
+            let (x, y) = (major[i].into(), minor[i].into());
+            x < 0 || x >= major_size || y < 0 || y >= minor_size
+        }) {
+            return Err(TensorConversionError::Unknown(format!(
+                "edge ({}, {}) is out of bounds for size {:?}", rows[i].into(), cols[i].into(), size
+            )));
+        }
+
+        // The parallel sort is stable, so ties keep their edge order
+        let key = |e: &i64| -> (i64, i64) { (major[*e as usize].into(), minor[*e as usize].into()) };
+        let mut perm: Vec<i64> = (0..rows.len() as i64).collect();
+        parallel::install(|| perm.par_sort_by_key(key));
+
+        let mut counts = vec![0_usize; major_size as usize + 1];
+        for x in major {
+            counts[Into::<i64>::into(*x) as usize + 1] += 1;
+        }
+        for i in 1..counts.len() {
+            counts[i] += counts[i - 1];
+        }
+        let ptrs = counts.into_iter().map(Ptr::new).collect();
+        let indices = perm.iter().map(|e| Ix::new(key(e).1 as usize)).collect();
+
+        Ok(Self { ptrs, indices, perm, _phantom: std::marker::PhantomData })
+    }
+
+    pub fn as_graph(&self) -> SparseGraph<'_, Ty, Ptr, Ix> {
+        SparseGraph::new(&self.ptrs, &self.indices)
+    }
+}
+
+impl<Ty, Ptr: IndexType + Element, Ix: IndexType + Element> From<&SparseGraphOwned<Ty, Ptr, Ix>> for SparseGraphStorage<Ty> {
+    fn from(value: &SparseGraphOwned<Ty, Ptr, Ix>) -> Self {
+        SparseGraphStorage::new(
+            Tensor::of_slice(&value.ptrs), Tensor::of_slice(&value.indices), Some(Tensor::of_slice(&value.perm)),
+        ).with_ordering(NeighborOrdering::ById)
+    }
+}
+
+impl<Ty: SparseGraphTypeTrait> SparseGraphStorage<Ty> {
+    // Converts an edge list without building intermediate tensors, only the result is copied into tensors
+    pub fn from_edge_slices(rows: &[i64], cols: &[i64], size: Size) -> TensorResult<Self> {
+        Ok(Self::from(&SparseGraphOwned::<Ty>::from_edge_slices(rows, cols, size)?))
+    }
+
+    pub fn from_edge_slices_i32(rows: &[i32], cols: &[i32], size: Size) -> TensorResult<Self> {
+        Ok(Self::from(&SparseGraphOwned::<Ty>::from_edge_slices(rows, cols, size)?))
+    }
+}
+
+pub struct HeteroGraphStorage {
+    pub node_counts: HashMap<NodeType, i64>,
+    pub edges: HashMap<EdgeType, CooGraphStorage>,
+    reverse: HashMap<EdgeType, EdgeType>,
+    csc: HashMap<RelType, CscGraphStorage>,
+}
+
+impl HeteroGraphStorage {
+    pub fn new(
+        node_counts: HashMap<NodeType, i64>,
+        edges: HashMap<EdgeType, CooGraphStorage>,
+    ) -> Self {
+        Self {
+            node_counts,
+            edges,
+            reverse: HashMap::new(),
+            csc: HashMap::new(),
+        }
+    }
+
+    pub fn edge_types(&self) -> Vec<EdgeType> {
+        let mut edge_types: Vec<EdgeType> = self.edges.keys().cloned().collect();
+        edge_types.sort();
+        edge_types
+    }
+
+    pub fn insert_relation(&mut self, edge_type: EdgeType, coo: CooGraphStorage) {
+        self.csc.remove(&rel_type(&edge_type));
+        self.edges.insert(edge_type, coo);
+    }
+
+    pub fn set_reverse(&mut self, edge_type: EdgeType, reverse: EdgeType) {
+        self.reverse.insert(reverse.clone(), edge_type.clone());
+        self.reverse.insert(edge_type, reverse);
+    }
+
+    pub fn reverse_of(&self, edge_type: &EdgeType) -> Option<&EdgeType> {
+        self.reverse.get(edge_type)
+    }
+
+    // Merged self relations are coalesced, so parallel edges of such a relation collapse into one
+    pub fn add_reverse_relations(
+        &mut self,
+        suffix: &str,
+        merge_self_relations: bool,
+    ) -> TensorResult<()> {
+        for edge_type in self.edge_types() {
+            if self.reverse.contains_key(&edge_type) {
+                continue;
+            }
+
+            let (src, rel, dst) = edge_type.clone();
+            let coo = &self.edges[&edge_type];
+            if src == dst && merge_self_relations {
+                // Self relations become undirected and act as their own reverse
+                let merged = CooGraphStorage::new(
+                    undirected_row_col(&coo.row_col, None)?.0, (coo.size.0.max(coo.size.1), coo.size.0.max(coo.size.1)),
+                );
+                self.insert_relation(edge_type.clone(), merged);
+                self.set_reverse(edge_type.clone(), edge_type);
+                continue;
+            }
+
+            // Transposing the rows needs a copy, torch has no negative stride views
+            let reverse_type = (dst, format!("{}{}", suffix, rel), src);
+            if self.edges.contains_key(&reverse_type) {
+                self.set_reverse(edge_type, reverse_type);
+                continue;
+            }
+            let reverse = CooGraphStorage::new(
+                Tensor::stack(&[coo.col(), coo.row()], 0), (coo.size.1, coo.size.0),
+            );
+            self.insert_relation(reverse_type.clone(), reverse);
+            self.set_reverse(edge_type, reverse_type);
+        }
+        Ok(())
+    }
+
+    pub fn csc(&mut self) -> TensorResult<&HashMap<RelType, CscGraphStorage>> {
+        for (edge_type, coo) in self.edges.iter() {
+            if let Entry::Vacant(entry) = self.csc.entry(rel_type(edge_type)) {
+                entry.insert(CscGraphStorage::try_from(coo)?);
+            }
+        }
+        Ok(&self.csc)
+    }
+}
+
+fn rel_type((src, rel, dst): &EdgeType) -> RelType {
+    format!("{}__{}__{}", src, rel, dst)
+}
+
+// Both directions of every edge sorted by (row, col, edge type) without duplicates, edges of different types stay
+// distinct
+fn undirected_row_col(row_col: &Tensor, edge_type: Option<&Tensor>) -> TensorResult<(Tensor, Option<Tensor>)> {
+    let row_col = row_col.contiguous();
+    let data = try_tensor_to_slice::<i64>(&row_col)?;
+    let (row, col) = data.split_at(data.len() / 2);
+    let edge_type = edge_type.map(Tensor::contiguous);
+    let types = edge_type.as_ref().map(try_tensor_to_slice::<i64>).transpose()?;
+
+    let mut edges: Vec<(i64, i64, i64)> = Vec::with_capacity(data.len());
+    for (e, (u, v)) in row.iter().cloned().zip(col.iter().cloned()).enumerate() {
+        let t = types.map_or(0, |types| types[e]);
+        edges.push((u, v, t));
+        edges.push((v, u, t));
+    }
+    edges.sort_unstable();
+    edges.dedup();
+
+    let rows: Vec<i64> = edges.iter().map(|e| e.0).collect();
+    let cols: Vec<i64> = edges.iter().map(|e| e.1).collect();
+    let edge_type = types.map(|_| Tensor::of_slice(&edges.iter().map(|e| e.2).collect::<Vec<_>>()));
+    Ok((Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0), edge_type))
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use std::convert::{TryFrom, TryInto};
     use ndarray::{arr2, Array2};
-    use tch::Tensor;
-    use crate::data::storage::{CscGraphStorage, ind2ptr};
+    use rand::{Rng, SeedableRng};
+    use tch::{Device, Kind, Tensor};
+    use crate::algo::neighbor_sampling::{IdentityFilter, UnweightedSampler};
+    use crate::data::storage::{CscGraphOwned, CscGraphStorage, CsrGraphStorage, HeteroGraphStorage, SparseGraphStorage, ind2ptr, is_directed, is_undirected};
     use crate::data::CooGraphStorage;
-    use crate::data::graph::CscGraph;
+    use crate::data::graph::{CscGraph, GraphError, GraphMeta, NeighborOrdering};
+    use crate::utils::{EdgeType, NodeIdx, NodeType, RelType};
 
     #[test]
     fn test_ind2ptr() {
@@ -173,6 +1038,7 @@ mod tests {
         let coo_graph_data = CooGraphStorage::new(edge_index, (m, m) );
 
         let result = CscGraphStorage::try_from(&coo_graph_data).unwrap();
+        assert!(result.validate().is_ok());
         let graph: CscGraph<i64, i64> = (&result).try_into().unwrap();
 
         assert_eq!(graph.in_degree(0), 3);
@@ -181,5 +1047,574 @@ mod tests {
         assert_eq!(graph.in_degree(2), 2);
         assert_eq!(graph.neighbors_slice(0), [1, 2, 3]);
         assert_eq!(graph.neighbors_slice(1), [4, 5]);
+
+        // Transposed int32 edge lists are coerced
+        let edge_index = coo_graph_data.row_col.to_kind(tch::Kind::Int).transpose(0, 1).contiguous().transpose(0, 1);
+        let coerced = CscGraphStorage::try_from(&CooGraphStorage::new(edge_index, (m, m))).unwrap();
+        assert!(coerced.ptrs.equal(&result.ptrs));
+        assert!(coerced.indices.equal(&result.indices));
+    }
+
+    #[test]
+    fn test_is_undirected() {
+        let row_col = Tensor::stack(&[Tensor::of_slice(&[0_i64, 1, 1, 2, 2]), Tensor::of_slice(&[1_i64, 0, 2, 1, 2])], 0);
+        let graph = CooGraphStorage::new(row_col, (3, 3));
+        assert!(is_undirected(&graph).unwrap());
+        assert!(!is_directed(&graph).unwrap());
+
+        let row_col = Tensor::stack(&[Tensor::of_slice(&[0_i64, 1, 1, 2]), Tensor::of_slice(&[1_i64, 0, 2, 2])], 0);
+        let graph = CooGraphStorage::new(row_col, (3, 3));
+        assert!(!is_undirected(&graph).unwrap());
+        assert!(is_directed(&graph).unwrap());
+    }
+
+    #[test]
+    fn test_relabel_nodes() {
+        let row_col = Tensor::stack(&[Tensor::of_slice(&[0_i64, 1, 2, 3]), Tensor::of_slice(&[1_i64, 2, 3, 0])], 0);
+        let graph = CooGraphStorage::new(row_col, (4, 4));
+
+        let relabeled = graph.relabel_nodes(&Tensor::of_slice(&[3_i64, 2, 1, 0])).unwrap();
+        assert_eq!(relabeled.size, (4, 4));
+        let rows: Vec<i64> = relabeled.row().into();
+        let cols: Vec<i64> = relabeled.col().into();
+        assert_eq!(rows, vec![3, 2, 1, 0]);
+        assert_eq!(cols, vec![2, 1, 0, 3]);
+
+        // Dropping node 1 removes both of its edges and compacts the ids
+        let relabeled = graph.relabel_nodes(&Tensor::of_slice(&[0_i64, -1, 1, 2])).unwrap();
+        assert_eq!(relabeled.size, (3, 3));
+        let rows: Vec<i64> = relabeled.row().into();
+        let cols: Vec<i64> = relabeled.col().into();
+        assert_eq!(rows, vec![1, 2]);
+        assert_eq!(cols, vec![2, 0]);
+
+        assert!(graph.relabel_nodes(&Tensor::of_slice(&[0_i64, 1])).is_err());
+    }
+
+    #[test]
+    fn test_sort() {
+        let row_col = Tensor::stack(&[Tensor::of_slice(&[2_i64, 0, 1, 0, 2]), Tensor::of_slice(&[0_i64, 3, 1, 1, 2])], 0);
+        let graph = CooGraphStorage::new(row_col, (3, 4));
+
+        let (sorted, perm) = graph.sort();
+        assert_eq!(sorted.size, (3, 4));
+        let rows: Vec<i64> = sorted.row().into();
+        let cols: Vec<i64> = sorted.col().into();
+        assert_eq!(rows, vec![0, 0, 1, 2, 2]);
+        assert_eq!(cols, vec![1, 3, 1, 0, 2]);
+
+        // Applying the inverse permutation restores the original order
+        let restored = sorted.row_col.index_select(1, &perm.argsort(0, false));
+        assert!(restored.equal(&graph.row_col));
+        assert!(graph.row_col.index_select(1, &perm).equal(&sorted.row_col));
+    }
+
+    #[test]
+    fn test_conversion_deterministic() {
+        // Every edge appears three times
+        let rows = Tensor::of_slice(&[2_i64, 0, 1, 0, 2]).repeat(&[3]);
+        let cols = Tensor::of_slice(&[0_i64, 1, 1, 1, 2]).repeat(&[3]);
+        let coo = CooGraphStorage::new(Tensor::stack(&[rows, cols], 0), (3, 3));
+
+        let csc = CscGraphStorage::try_from(&coo).unwrap();
+        let perm: Vec<i64> = csc.perm.as_ref().unwrap().into();
+        // Ties are ordered by edge id
+        assert_eq!(perm, vec![0, 5, 10, 1, 3, 6, 8, 11, 13, 2, 7, 12, 4, 9, 14]);
+        for _ in 0..5 {
+            let again = CscGraphStorage::try_from(&coo).unwrap();
+            assert!(again.perm.unwrap().equal(csc.perm.as_ref().unwrap()));
+        }
+
+        let csr = CsrGraphStorage::try_from(&coo).unwrap();
+        let perm: Vec<i64> = csr.perm.unwrap().into();
+        assert_eq!(perm, vec![1, 3, 6, 8, 11, 13, 2, 7, 12, 0, 5, 10, 4, 9, 14]);
+        let (_, sort_perm) = coo.sort();
+        let sort_perm: Vec<i64> = sort_perm.into();
+        assert_eq!(sort_perm, perm);
+    }
+
+    #[test]
+    fn test_perm_is_identity() {
+        // Already sorted by (col, row)
+        let rows = Tensor::of_slice(&[1_i64, 2, 0, 2, 0, 1, 1]);
+        let cols = Tensor::of_slice(&[0_i64, 0, 1, 1, 2, 2, 2]);
+        let coo = CooGraphStorage::new(Tensor::stack(&[rows, cols], 0), (3, 3))
+            .with_edge_type(Tensor::of_slice(&[0_i64, 1, 0, 1, 0, 1, 2]));
+        let csc = CscGraphStorage::try_from(&coo).unwrap();
+        assert!(csc.perm().is_none());
+        assert!(csc.perm_is_identity());
+        assert!(csc.edge_type().unwrap().equal(coo.edge_type().unwrap()));
+        let attr = Tensor::of_slice(&[0.5_f64, 1.5, 2.5, 3.5, 4.5, 5.5, 6.5]);
+        assert!(csc.permute_edge_attr(&attr).equal(&attr));
+
+        // The same edges sorted by row need reordering
+        let csr = CsrGraphStorage::try_from(&coo).unwrap();
+        assert!(csr.perm().is_some());
+        assert!(!csr.perm_is_identity());
+        assert!(!csr.perm_is_identity());
+        let permuted: Vec<f64> = csr.permute_edge_attr(&attr).into();
+        assert_eq!(permuted, vec![2.5, 4.5, 0.5, 5.5, 6.5, 1.5, 3.5]);
+        let edge_type: Vec<i64> = csr.edge_type().unwrap().into();
+        assert_eq!(edge_type, vec![0, 0, 0, 1, 2, 1, 1]);
+
+        let without_perm = CscGraphStorage::new(csc.ptrs.shallow_clone(), csc.indices.shallow_clone(), None);
+        assert!(without_perm.perm_is_identity());
+        assert!(without_perm.permute_edge_attr(&attr).equal(&attr));
+        let identity = CscGraphStorage::new(csc.ptrs.shallow_clone(), csc.indices.shallow_clone(), Some(Tensor::arange(7, (Kind::Int64, Device::Cpu))));
+        assert!(identity.perm_is_identity());
+        assert!(identity.permute_edge_attr(&attr).equal(&attr));
+    }
+
+    #[test]
+    fn test_sort_overflow() {
+        // size.0 * size.1 overflows an i64, the composite key of the last two edges would wrap around
+        let big = 1_i64 << 32;
+        let rows = Tensor::of_slice(&[big - 1, 0, big - 1, 1, big - 2]);
+        let cols = Tensor::of_slice(&[big - 1, big - 1, 0, 5, big - 1]);
+        let graph = CooGraphStorage::new(Tensor::stack(&[rows.shallow_clone(), cols.shallow_clone()], 0), (big, big));
+        let (sorted, perm) = graph.sort();
+        let perm: Vec<i64> = perm.into();
+        assert_eq!(perm, vec![1, 3, 4, 2, 0]);
+        let rows_sorted: Vec<i64> = sorted.row().into();
+        assert_eq!(rows_sorted, vec![0, 1, big - 2, big - 1, big - 1]);
+
+        // Just below the boundary the composite key is used and agrees with the two pass sort
+        let (m, n) = (1_i64 << 31, (1_i64 << 32) - 1);
+        assert!(m.checked_mul(n).is_some());
+        let rows = Tensor::of_slice(&[m - 1, 0, m - 1, 3, 0, m - 1]);
+        let cols = Tensor::of_slice(&[n - 1, n - 1, 0, 2, n - 1, n - 2]);
+        let fast = super::lexsort(&rows, &cols, m, n);
+        assert!(fast.equal(&super::lexsort_stable(&rows, &cols)));
+        let fast: Vec<i64> = fast.into();
+        assert_eq!(fast, vec![1, 4, 3, 2, 5, 0]);
+    }
+
+    #[test]
+    fn test_neighbor_ordering() {
+        let row_col = Tensor::stack(&[Tensor::of_slice(&[2_i64, 0, 1, 0, 2, 1]), Tensor::of_slice(&[0_i64, 0, 1, 1, 2, 0])], 0);
+        let coo = CooGraphStorage::new(row_col, (3, 3));
+        let csc = CscGraphStorage::try_from(&coo).unwrap();
+        assert_eq!(csc.ordering, NeighborOrdering::ById);
+        assert!(csc.has_edge(0, 2).unwrap());
+        assert!(!csc.has_edge(2, 1).unwrap());
+
+        // Times per edge position: column 0 holds rows [0, 1, 2]
+        let times = Tensor::of_slice(&[5_i64, 1, 3, 2, 2, 0]);
+        let by_time = csc.sort_neighbors_by_time(&times).unwrap();
+        assert_eq!(by_time.ordering, NeighborOrdering::ByTime);
+        let indices: Vec<i64> = by_time.indices.shallow_clone().into();
+        assert_eq!(indices, vec![1, 2, 0, 0, 1, 2]);
+        assert!(matches!(by_time.has_edge(0, 2), Err(GraphError::InvalidOrdering { .. })));
+
+        // The composed perm still maps every position to its edge in the coo graph
+        let perm = by_time.perm.as_ref().unwrap();
+        assert!(coo.row().index_select(0, perm).equal(&by_time.indices));
+        assert!(by_time.sort_neighbors_by_time(&times.index_select(0, &Tensor::of_slice(&[1_i64, 2, 0, 3, 4, 5]))).unwrap()
+            .indices.equal(&by_time.indices));
+
+        let by_id = by_time.sort_neighbors_by_id().unwrap();
+        assert_eq!(by_id.ordering, NeighborOrdering::ById);
+        assert!(by_id.indices.equal(&csc.indices));
+        assert!(by_id.perm.as_ref().unwrap().equal(csc.perm.as_ref().unwrap()));
+        let again = by_id.sort_neighbors_by_id().unwrap();
+        assert!(again.indices.equal(&by_id.indices));
+        assert!(again.perm.unwrap().equal(by_id.perm.as_ref().unwrap()));
+
+        let unsorted = CscGraphStorage::new(csc.ptrs.shallow_clone(), by_time.indices.shallow_clone(), None);
+        assert!(matches!(unsorted.has_edge(0, 2), Err(GraphError::InvalidOrdering { .. })));
+        let known_sorted = CscGraphStorage::new(csc.ptrs.shallow_clone(), csc.indices.shallow_clone(), None)
+            .with_meta(GraphMeta::default().with_sorted(true));
+        assert!(known_sorted.has_edge(0, 2).unwrap());
+        let sorted = unsorted.sort_neighbors_by_id().unwrap();
+        assert!(sorted.has_edge(0, 2).unwrap());
+        assert!(csc.sort_neighbors_by_time(&Tensor::of_slice(&[1_i64])).is_err());
+    }
+
+    #[test]
+    fn test_col_slice() {
+        let (_x, _, coo) = crate::data::load_karate_graph();
+        let csc = CscGraphStorage::try_from(&coo).unwrap();
+        let full = CscGraph::<i64, i64>::try_from(&csc).unwrap();
+        let full_perm: Vec<i64> = csc.perm.as_ref().unwrap().into();
+
+        let (start, end) = (5, 20);
+        let (sliced, offset) = csc.col_slice(start, end).unwrap();
+        assert_eq!(offset, start);
+        assert_eq!(sliced.ordering, NeighborOrdering::ById);
+        let graph = CscGraph::<i64, i64>::try_from(&sliced).unwrap();
+        let perm: Vec<i64> = sliced.perm.as_ref().unwrap().into();
+        assert_eq!(graph.node_count(), (end - start) as usize);
+        for c in 0..(end - start) {
+            assert_eq!(graph.neighbors_slice(c), full.neighbors_slice(c + start));
+            let (local, global) = (graph.neighbors_range(c), full.neighbors_range(c + start));
+            assert_eq!(&perm[local], &full_perm[global]);
+        }
+
+        // Samplers run on the slice unchanged, the inputs are local column ids
+        let inputs = vec![0_i64, 3, 10];
+        let (samples, coo_builder, _) = crate::algo::neighbor_sampling::neighbor_sampling_homogenous(
+            &mut rand::rngs::SmallRng::from_seed([0; 32]), &graph, &inputs, &[4],
+            &UnweightedSampler::<false>, &IdentityFilter, &[(); 3],
+        );
+        for (&j, (&i, &ptr)) in coo_builder.rows.iter().zip(coo_builder.cols.iter().zip(coo_builder.edge_index.iter())) {
+            let global_ptr = perm[ptr as usize];
+            assert_eq!(coo.row().int64_value(&[global_ptr]), samples[j as usize]);
+            assert_eq!(coo.col().int64_value(&[global_ptr]), samples[i as usize] + offset);
+        }
+
+        // Without a perm the slice maps to the positions of the full graph
+        let unpermuted = CscGraphStorage::new(csc.ptrs.shallow_clone(), csc.indices.shallow_clone(), None);
+        let (sliced, _) = unpermuted.col_slice(start, end).unwrap();
+        let perm: Vec<i64> = sliced.perm.unwrap().into();
+        let first = full.neighbors_range(start).start as i64;
+        assert_eq!(perm, (first..first + perm.len() as i64).collect::<Vec<_>>());
+
+        let (empty, _) = csc.col_slice(7, 7).unwrap();
+        assert_eq!(empty.ptrs.size(), vec![1]);
+        assert_eq!(empty.indices.size(), vec![0]);
+        assert!(csc.col_slice(20, 5).is_err());
+        assert!(csc.col_slice(0, 35).is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_merge_coo() {
+        let (_x, _, coo) = crate::data::load_karate_graph();
+        let full = CscGraphStorage::try_from(&coo).unwrap();
+        let num_edges = coo.row_col.size()[1];
+        let split = 100;
+        let head = CooGraphStorage::new(coo.row_col.narrow(1, 0, split), coo.size);
+        let tail = CooGraphStorage::new(coo.row_col.narrow(1, split, num_edges - split), coo.size);
+
+        // Same as converting the concatenated edges from scratch, old and new ids included
+        let merged = CscGraphStorage::try_from(&head).unwrap().merge_coo(&tail).unwrap();
+        assert_eq!(merged.ordering, NeighborOrdering::ById);
+        assert!(merged.validate().is_ok());
+        assert!(merged.ptrs.equal(&full.ptrs));
+        assert!(merged.indices.equal(&full.indices));
+        assert!(merged.perm.as_ref().unwrap().equal(full.perm.as_ref().unwrap()));
+
+        // Duplicates are kept, the old copy of every edge comes first
+        let doubled = full.merge_coo(&coo).unwrap();
+        assert!(doubled.ptrs.equal(&(&full.ptrs * 2)));
+        let perm: Vec<i64> = doubled.perm.unwrap().into();
+        let indices: Vec<i64> = doubled.indices.into();
+        let full_perm: Vec<i64> = full.perm.as_ref().unwrap().into();
+        for k in 0..num_edges as usize {
+            assert_eq!(perm[2 * k], full_perm[k]);
+            assert_eq!(perm[2 * k + 1], full_perm[k] + num_edges);
+            assert_eq!(indices[2 * k], indices[2 * k + 1]);
+        }
+
+        // Unsorted neighbors get the new edges appended to their column
+        let rows = Tensor::of_slice(&[2_i64, 0, 1]);
+        let cols = Tensor::of_slice(&[0_i64, 0, 1]);
+        let unsorted = CscGraphStorage::new(Tensor::of_slice(&[0_i64, 2, 2]), Tensor::of_slice(&[1_i64, 0]), None);
+        let merged = unsorted.merge_coo(&CooGraphStorage::new(Tensor::stack(&[rows, cols], 0), (3, 2))).unwrap();
+        assert_eq!(merged.ordering, NeighborOrdering::Unsorted);
+        assert_eq!(Vec::<i64>::from(&merged.ptrs), vec![0, 4, 5]);
+        assert_eq!(Vec::<i64>::from(&merged.indices), vec![1, 0, 0, 2, 1]);
+        assert_eq!(Vec::<i64>::from(merged.perm.as_ref().unwrap()), vec![0, 1, 3, 2, 4]);
+
+        assert!(full.merge_coo(&CooGraphStorage::new(coo.row_col.shallow_clone(), (34, 35))).is_err());
+    }
+
+    #[test]
+    fn test_detect_properties() {
+        let coo = |rows: &[i64], cols: &[i64]| CooGraphStorage::new(
+            Tensor::stack(&[Tensor::of_slice(rows), Tensor::of_slice(cols)], 0), (3, 3),
+        );
+        let meta = |multi: bool, loops: bool, sorted: bool| {
+            GraphMeta::default().with_multigraph(multi).with_self_loops(loops).with_sorted(sorted)
+        };
+
+        let mut simple = coo(&[0, 0, 1, 2], &[1, 2, 2, 0]);
+        assert_eq!(simple.meta, GraphMeta::default());
+        assert_eq!(simple.detect_properties().unwrap(), meta(false, false, true));
+        assert_eq!(coo(&[1, 0, 2, 0], &[2, 1, 0, 2]).detect_properties().unwrap(), meta(false, false, false));
+        assert_eq!(coo(&[0, 1, 0], &[1, 1, 1]).detect_properties().unwrap(), meta(true, true, false));
+
+        // Conversions keep parallel edges and self loops, compressed graphs are sorted by id
+        let mut multi = coo(&[2, 0, 1, 2], &[0, 1, 1, 0]);
+        multi.detect_properties().unwrap();
+        let csc = CscGraphStorage::try_from(&multi).unwrap();
+        assert_eq!(csc.meta, meta(true, true, true));
+        let mut detected = CscGraphStorage::new(csc.ptrs.shallow_clone(), csc.indices.shallow_clone(), None);
+        assert_eq!(detected.ordering, NeighborOrdering::Unsorted);
+        assert_eq!(detected.detect_properties().unwrap(), meta(true, true, true));
+        assert_eq!(detected.ordering, NeighborOrdering::ById);
+        // A reversed block is unsorted, its duplicates are found anyway
+        let mut reversed = CscGraphStorage::new(csc.ptrs.shallow_clone(), Tensor::of_slice(&[2_i64, 2, 1, 0]), None);
+        assert_eq!(reversed.detect_properties().unwrap(), meta(true, true, false));
+        assert_eq!(reversed.ordering, NeighborOrdering::Unsorted);
+
+        // Only what holds for any subset of the edges survives slicing
+        let (slice, _) = csc.col_slice(1, 3).unwrap();
+        assert_eq!(slice.meta, GraphMeta::default().with_sorted(true));
+        let simple_csc = CscGraphStorage::try_from(&simple).unwrap();
+        assert_eq!(simple_csc.col_slice(0, 1).unwrap().0.meta, meta(false, false, true));
+        let by_time = simple_csc.sort_neighbors_by_time(&Tensor::of_slice(&[1_i64, 0, 0, 0])).unwrap();
+        assert_eq!(by_time.meta, GraphMeta { is_sorted: None, ..meta(false, false, true) });
+
+        // Coalescing merges parallel edges and maps every edge to its merged one
+        let (coalesced, positions) = multi.coalesce().unwrap();
+        assert_eq!(coalesced.meta, meta(false, true, true));
+        assert_eq!(Vec::<i64>::from(coalesced.row()), vec![0, 1, 2]);
+        assert_eq!(Vec::<i64>::from(coalesced.col()), vec![1, 1, 0]);
+        assert_eq!(Vec::<i64>::from(positions), vec![2, 0, 1, 2]);
+        let mut again = CooGraphStorage::new(coalesced.row_col.shallow_clone(), (3, 3));
+        assert_eq!(again.detect_properties().unwrap(), coalesced.meta);
+    }
+
+    #[test]
+    fn test_edge_type() {
+        let (_x, _, coo) = crate::data::load_karate_graph();
+        let num_edges = coo.row_col.size()[1];
+        let types_data: Vec<i64> = (0..num_edges).map(|e| (e * 7) % 3).collect();
+        let coo = coo.with_edge_type(Tensor::of_slice(&types_data));
+        assert_eq!(coo.num_relations(), 3);
+        assert_eq!(CooGraphStorage::new(coo.row_col.shallow_clone(), coo.size).num_relations(), 0);
+
+        // The types stay aligned with the reordered indices
+        let edge_ids = Tensor::arange(num_edges, (Kind::Int64, Device::Cpu));
+        let check = |edge_type: &Tensor, perm: &Tensor| {
+            let edge_type: Vec<i64> = edge_type.into();
+            let perm: Vec<i64> = perm.into();
+            assert!(perm.iter().zip(edge_type).all(|(e, t)| types_data[*e as usize] == t));
+        };
+        let csc = CscGraphStorage::try_from(&coo).unwrap();
+        check(csc.edge_type().unwrap(), &csc.permute_edge_attr(&edge_ids));
+        assert_eq!(csc.num_relations(), 3);
+        let csr = CsrGraphStorage::try_from(&coo).unwrap();
+        check(csr.edge_type().unwrap(), &csr.permute_edge_attr(&edge_ids));
+        let (sorted, perm) = coo.sort();
+        check(sorted.edge_type().unwrap(), &perm);
+        let times = Tensor::of_slice(&(0..num_edges).map(|e| (e * 13) % 5).collect::<Vec<_>>());
+        let by_time = csc.sort_neighbors_by_time(&times).unwrap();
+        check(by_time.edge_type().unwrap(), by_time.perm.as_ref().unwrap());
+        let window = csc.time_window_view(&times, 1, 3).unwrap();
+        check(window.edge_type().unwrap(), window.perm.as_ref().unwrap());
+        let (slice, _) = csc.col_slice(3, 10).unwrap();
+        check(slice.edge_type().unwrap(), slice.perm.as_ref().unwrap());
+
+        // Parallel edges only merge within a type
+        let rows = Tensor::of_slice(&[1_i64, 0, 1, 1]);
+        let cols = Tensor::of_slice(&[0_i64, 1, 0, 0]);
+        let typed = CooGraphStorage::new(Tensor::stack(&[rows, cols], 0), (2, 2))
+            .with_edge_type(Tensor::of_slice(&[1_i64, 0, 0, 1]));
+        let (coalesced, positions) = typed.coalesce().unwrap();
+        assert_eq!(Vec::<i64>::from(coalesced.row()), vec![0, 1, 1]);
+        assert_eq!(Vec::<i64>::from(coalesced.edge_type().unwrap()), vec![0, 0, 1]);
+        assert_eq!(Vec::<i64>::from(positions), vec![2, 0, 1, 2]);
+        assert_eq!(coalesced.meta.is_multigraph, Some(true));
+
+        let undirected = typed.to_undirected().unwrap();
+        assert_eq!(Vec::<i64>::from(undirected.row()), vec![0, 0, 1, 1]);
+        assert_eq!(Vec::<i64>::from(undirected.col()), vec![1, 1, 0, 0]);
+        assert_eq!(Vec::<i64>::from(undirected.edge_type().unwrap()), vec![0, 1, 0, 1]);
+        assert!(is_undirected(&undirected).unwrap());
+        assert_eq!(undirected.meta.is_multigraph, Some(true));
+
+        let merged = CscGraphStorage::try_from(&typed).unwrap().merge_coo(&typed).unwrap();
+        let merged_types: Vec<i64> = merged.edge_type().unwrap().into();
+        let merged_perm: Vec<i64> = merged.perm.as_ref().unwrap().into();
+        assert!(merged_perm.iter().zip(merged_types).all(|(e, t)| [1, 0, 0, 1][*e as usize % 4] == t));
+        assert!(csc.merge_coo(&CooGraphStorage::new(coo.row_col.shallow_clone(), coo.size)).is_err());
+        let truncated = CooGraphStorage::new(coo.row_col.shallow_clone(), coo.size).with_edge_type(Tensor::of_slice(&[0_i64]));
+        assert!(CscGraphStorage::try_from(&truncated).is_err());
+    }
+
+    #[test]
+    fn test_time_window_view() {
+        let (_x, _, coo) = crate::data::load_karate_graph();
+        let csc = CscGraphStorage::try_from(&coo).unwrap();
+        let num_edges = csc.indices.size()[0];
+        let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+        let times_data: Vec<i64> = (0..num_edges).map(|_| rng.gen_range(0..10)).collect();
+        let times = Tensor::of_slice(&times_data);
+        let degrees = |g: &CscGraphStorage| -> Vec<i64> { (g.ptrs.slice(0, 1, None, 1) - g.ptrs.slice(0, 0, -1, 1)).into() };
+
+        // The full range keeps every edge
+        let full = csc.time_window_view(&times, 0, 10).unwrap();
+        assert!(full.ptrs.equal(&csc.ptrs) && full.indices.equal(&csc.indices));
+        assert!(full.perm.as_ref().unwrap().equal(csc.perm.as_ref().unwrap()));
+
+        let window = csc.time_window_view(&times, 3, 5).unwrap();
+        assert!(window.validate().is_ok());
+        assert_eq!(window.ordering, NeighborOrdering::ById);
+        let ptrs: Vec<i64> = csc.ptrs.shallow_clone().into();
+        let expected: Vec<i64> = ptrs.windows(2)
+            .map(|w| (w[0]..w[1]).filter(|p| (3..5).contains(&times_data[*p as usize])).count() as i64)
+            .collect();
+        assert_eq!(degrees(&window), expected);
+        assert!(degrees(&window).iter().zip(degrees(&csc)).all(|(w, d)| *w <= d));
+        assert!(window.indices.size()[0] < num_edges);
+
+        // The perm still points at the matching edges of the coo graph
+        let rows: Vec<i64> = coo.row().into();
+        let perm: Vec<i64> = window.perm.as_ref().unwrap().into();
+        let indices: Vec<i64> = window.indices.shallow_clone().into();
+        assert!(perm.iter().zip(indices).all(|(e, v)| rows[*e as usize] == v));
+
+        assert_eq!(csc.time_window_view(&times, 4, 4).unwrap().indices.size(), vec![0]);
+        assert!(csc.time_window_view(&times, 5, 3).is_err());
+        assert!(csc.time_window_view(&times.narrow(0, 0, 10), 0, 10).is_err());
+    }
+
+    #[test]
+    fn test_from_edge_slices() {
+        fn assert_same<Ty>(a: &SparseGraphStorage<Ty>, b: &SparseGraphStorage<Ty>) {
+            assert!(a.ptrs.equal(&b.ptrs));
+            assert!(a.indices.equal(&b.indices));
+            let edge_ids = Tensor::arange(a.indices.size()[0], (Kind::Int64, Device::Cpu));
+            assert!(a.permute_edge_attr(&edge_ids).equal(&b.permute_edge_attr(&edge_ids)));
+            assert_eq!(a.ordering, b.ordering);
+        }
+
+        let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+        for (m, n, num_edges) in [(1, 1, 0), (5, 3, 40), (30, 70, 500)] {
+            // Small id ranges guarantee duplicate edges
+            let rows: Vec<i64> = (0..num_edges).map(|_| rng.gen_range(0..m)).collect();
+            let cols: Vec<i64> = (0..num_edges).map(|_| rng.gen_range(0..n)).collect();
+            let coo = CooGraphStorage::new(Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0), (m, n));
+
+            assert_same(&CscGraphStorage::from_edge_slices(&rows, &cols, (m, n)).unwrap(), &CscGraphStorage::try_from(&coo).unwrap());
+            assert_same(&CsrGraphStorage::from_edge_slices(&rows, &cols, (m, n)).unwrap(), &CsrGraphStorage::try_from(&coo).unwrap());
+
+            let rows_i32: Vec<i32> = rows.iter().map(|x| *x as i32).collect();
+            let cols_i32: Vec<i32> = cols.iter().map(|x| *x as i32).collect();
+            let coo_i32 = CooGraphStorage::new(coo.row_col.to_kind(tch::Kind::Int), (m, n));
+            assert_same(
+                &CscGraphStorage::from_edge_slices_i32(&rows_i32, &cols_i32, (m, n)).unwrap(),
+                &CscGraphStorage::try_from(&coo_i32).unwrap(),
+            );
+
+            // The owned graph can be sampled from directly
+            let owned = CscGraphOwned::<i64, i64>::from_edge_slices(&rows, &cols, (m, n)).unwrap();
+            let csc = CscGraphStorage::try_from(&coo).unwrap();
+            let graph = CscGraph::<i64, i64>::try_from(&csc).unwrap();
+            assert_eq!(owned.as_graph().ptrs, graph.ptrs);
+            assert_eq!(owned.as_graph().indices, graph.indices);
+        }
+
+        assert!(CscGraphStorage::from_edge_slices(&[0, 1], &[0], (2, 2)).is_err());
+        assert!(CscGraphStorage::from_edge_slices(&[0, 2], &[0, 1], (2, 2)).is_err());
+        assert!(CsrGraphStorage::from_edge_slices(&[0, -1], &[0, 1], (2, 2)).is_err());
+    }
+
+    #[test]
+    fn test_raw_parts() {
+        let (_x, _, coo) = crate::data::load_karate_graph();
+        let csr = CsrGraphStorage::try_from(&coo).unwrap();
+        let (ptrs, indices) = csr.as_raw_parts().unwrap();
+        assert_eq!(ptrs.len(), 35);
+        assert_eq!(indices.len(), csr.indices.size()[0] as usize);
+        assert!(csr.edge_type_raw().unwrap().is_none());
+
+        // The wrapped graph shares the buffers
+        let ptrs_buffer = ptrs.to_vec();
+        let indices_buffer = indices.to_vec();
+        let borrowed = unsafe { CsrGraphStorage::from_raw_parts(&ptrs_buffer, &indices_buffer, None) }.unwrap();
+        let (borrowed_ptrs, borrowed_indices) = borrowed.as_raw_parts().unwrap();
+        assert_eq!(borrowed_ptrs.as_ptr(), ptrs_buffer.as_ptr());
+        assert_eq!(borrowed_indices.as_ptr(), indices_buffer.as_ptr());
+        assert!(borrowed.validate().is_ok());
+        assert!(borrowed.indices.equal(&csr.indices));
+
+        // The edge type comes along
+        let types_buffer: Vec<i64> = (0..indices_buffer.len() as i64).map(|e| e % 3).collect();
+        let typed = unsafe { CsrGraphStorage::from_raw_parts(&ptrs_buffer, &indices_buffer, Some(&types_buffer)) }.unwrap();
+        assert_eq!(typed.edge_type_raw().unwrap().unwrap().as_ptr(), types_buffer.as_ptr());
+        assert_eq!(typed.num_relations(), 3);
+
+        // Invalid layouts and views are rejected
+        assert!(unsafe { CsrGraphStorage::from_raw_parts(&ptrs_buffer[1..], &indices_buffer, None) }.is_err());
+        assert!(unsafe { CsrGraphStorage::from_raw_parts(&ptrs_buffer, &indices_buffer[1..], None) }.is_err());
+        assert!(unsafe { CsrGraphStorage::from_raw_parts(&[], &[], None) }.is_err());
+        assert!(unsafe { CsrGraphStorage::from_raw_parts(&ptrs_buffer, &indices_buffer, Some(&types_buffer[1..])) }.is_err());
+        let strided = CsrGraphStorage::new(csr.ptrs.shallow_clone(), csr.indices.slice(0, 0, None, 2), None);
+        assert!(strided.as_raw_parts().is_err());
+        let floats = CsrGraphStorage::new(csr.ptrs.to_kind(tch::Kind::Float), csr.indices.shallow_clone(), None);
+        assert!(floats.as_raw_parts().is_err());
+        let strided_types = CsrGraphStorage::new(csr.ptrs.shallow_clone(), csr.indices.shallow_clone(), None)
+            .with_edge_type(Tensor::of_slice(&[types_buffer.clone(), types_buffer].concat()).slice(0, 0, None, 2));
+        assert!(strided_types.as_raw_parts().is_ok());
+        assert!(strided_types.edge_type_raw().is_err());
+    }
+
+    fn edge_type(src: &str, rel: &str, dst: &str) -> EdgeType {
+        (src.to_string(), rel.to_string(), dst.to_string())
+    }
+
+    fn hetero_graph() -> HeteroGraphStorage {
+        let mut edges = HashMap::new();
+        // Author 0 writes papers 0 and 1, author 1 writes paper 2
+        edges.insert(edge_type("author", "writes", "paper"), CooGraphStorage::new(
+            Tensor::stack(&[Tensor::of_slice(&[0_i64, 0, 1]), Tensor::of_slice(&[0_i64, 1, 2])], 0), (2, 3),
+        ));
+        edges.insert(edge_type("paper", "cites", "paper"), CooGraphStorage::new(
+            Tensor::stack(&[Tensor::of_slice(&[0_i64, 1]), Tensor::of_slice(&[1_i64, 2])], 0), (3, 3),
+        ));
+        let node_counts = vec![("author".to_string(), 2), ("paper".to_string(), 3)].into_iter().collect();
+        HeteroGraphStorage::new(node_counts, edges)
+    }
+
+    #[test]
+    fn test_add_reverse_relations() {
+        let mut graph = hetero_graph();
+        graph.add_reverse_relations("rev_", false).unwrap();
+        assert_eq!(graph.edge_types().len(), 4);
+
+        let writes = edge_type("author", "writes", "paper");
+        let rev_writes = edge_type("paper", "rev_writes", "author");
+        assert_eq!(graph.reverse_of(&writes), Some(&rev_writes));
+        assert_eq!(graph.reverse_of(&rev_writes), Some(&writes));
+        assert_eq!(graph.edges[&rev_writes].size, (3, 2));
+        assert_eq!(graph.edges[&rev_writes].row_col.size(), vec![2, 3]);
+        assert_eq!(graph.edges[&edge_type("paper", "rev_cites", "paper")].row_col.size(), vec![2, 2]);
+
+        // Declared reverses are not added twice
+        graph.add_reverse_relations("rev_", false).unwrap();
+        assert_eq!(graph.edge_types().len(), 4);
+
+        let mut graph = hetero_graph();
+        graph.add_reverse_relations("rev_", true).unwrap();
+        let cites = edge_type("paper", "cites", "paper");
+        assert_eq!(graph.edge_types().len(), 3);
+        assert_eq!(graph.reverse_of(&cites), Some(&cites));
+        assert_eq!(graph.edges[&cites].row_col.size(), vec![2, 4]);
+    }
+
+    #[test]
+    fn test_add_reverse_relations_sampling() {
+        let mut graph = hetero_graph();
+        graph.add_reverse_relations("rev_", false).unwrap();
+
+        let node_types: Vec<NodeType> = vec!["author".to_string(), "paper".to_string()];
+        let edge_types = graph.edge_types();
+        let graph_data = graph.csc().unwrap();
+        assert_eq!(graph_data.len(), 4);
+        let graphs: HashMap<RelType, CscGraph> = graph_data.iter().map(|(rel_type, graph_data)| {
+            (rel_type.clone(), CscGraph::<i64, i64>::try_from(graph_data).unwrap())
+        }).collect();
+        let rel_types: Vec<RelType> = graphs.keys().cloned().collect();
+
+        // Only the reverse relation leads from an author to papers
+        let inputs_data: Vec<NodeIdx> = vec![0];
+        let inputs = vec![("author".to_string(), &inputs_data[..])].into_iter().collect();
+        let inputs_state_data = [(); 1];
+        let inputs_state = vec![("author".to_string(), &inputs_state_data[..])].into_iter().collect();
+        let num_neighbors: HashMap<RelType, Vec<usize>> = rel_types.iter().cloned().map(|r| (r, vec![10])).collect();
+        let sampler = rel_types.iter().cloned().map(|r| (r, UnweightedSampler::<false>)).collect();
+        let filter = rel_types.iter().cloned().map(|r| (r, IdentityFilter)).collect();
+
+        let mut rng = rand::rngs::SmallRng::from_seed([0; 32]);
+        let (samples, coo_builders, _) = crate::algo::neighbor_sampling::neighbor_sampling_heterogenous(
+            &mut rng, &node_types, &edge_types, &graphs, &inputs, &num_neighbors, 1, &sampler, &filter, &inputs_state,
+        );
+
+        let mut papers = samples["paper"].clone();
+        papers.sort_unstable();
+        assert_eq!(papers, vec![0, 1]);
+        assert_eq!(coo_builders["paper__rev_writes__author"].len(), 2);
+    }
+}
@@ -1,7 +1,9 @@
 use std::ops::Add;
 use rayon::prelude::*;
-use tch::{Device, IndexOp, Tensor};
+use tch::{Device, IndexOp, Kind, Tensor};
 use tch::kind::Element;
+use crate::data::{CooGraphStorage, CscGraphStorage};
+use crate::utils::{index_of_slice, parallel};
 use crate::utils::tensor::{check_device, TensorResult, TensorConversionError, try_tensor_to_slice, tensor_to_slice_mut};
 
 pub fn csc_sort_edges(
@@ -16,7 +18,7 @@ pub fn csc_sort_edges(
     let col_ptrs_data = try_tensor_to_slice::<i64>(col_ptrs)?;
 
     // TODO: benchmark this implementation. Check if it's runtime is very different than serial or native rust one
-    col_ptrs_data.par_iter()
+    parallel::install(|| col_ptrs_data.par_iter()
         .zip(col_ptrs_data.par_iter().skip(1))
         .for_each(|(col_start, col_end)| {
             if col_end - col_start <= 1 {
@@ -28,7 +30,7 @@ pub fn csc_sort_edges(
                 .argsort(0, descending) + *col_start;
             let slice_perm = perm.i(&sorted_idx);
             new_perm.slice(0, *col_start, *col_end, 1).copy_(&slice_perm)
-        });
+        }));
 
     Ok(new_perm)
 }
@@ -40,7 +42,7 @@ pub fn csc_edge_cumsum<T: Element + Add<Output=T> + Default + Copy>(
     check_device!(col_ptrs, Device::Cpu);
 
     let col_ptrs_data = try_tensor_to_slice::<i64>(col_ptrs)?;
-    col_ptrs_data.par_iter()
+    parallel::install(|| col_ptrs_data.par_iter()
         .zip(col_ptrs_data.par_iter().skip(1))
         .for_each(|(col_start, col_end)| {
             if col_end - col_start <= 1 {
@@ -54,15 +56,398 @@ pub fn csc_edge_cumsum<T: Element + Add<Output=T> + Default + Copy>(
                 acc = acc + *row_data_val;
                 *row_data_val = acc;
             }
-        });
+        }));
 
     Ok(())
 }
 
+pub fn add_virtual_node(
+    coo: &CooGraphStorage,
+) -> (CooGraphStorage, i64) {
+    let (m, n) = coo.size;
+    let virtual_node = m.max(n);
+    let options = (coo.row_col.kind(), coo.row_col.device());
+
+    // Connect the virtual node in both directions to every existing node
+    let nodes = Tensor::arange(virtual_node, options);
+    let virtual_nodes = Tensor::full(&[virtual_node], virtual_node, options);
+    let row = Tensor::cat(&[coo.row(), virtual_nodes.shallow_clone(), nodes.shallow_clone()], 0);
+    let col = Tensor::cat(&[coo.col(), nodes, virtual_nodes], 0);
+
+    (
+        CooGraphStorage::new(Tensor::stack(&[row, col], 0), (virtual_node + 1, virtual_node + 1)),
+        virtual_node,
+    )
+}
+
+pub fn add_virtual_node_features(
+    x: &Tensor,
+    placeholder: Option<&Tensor>,
+) -> Tensor {
+    let mut size = x.size();
+    size[0] = 1;
+    let row = match placeholder {
+        Some(placeholder) => placeholder.reshape(&size).to_kind(x.kind()),
+        None => Tensor::zeros(&size, (x.kind(), x.device())),
+    };
+    Tensor::cat(&[x.shallow_clone(), row], 0)
+}
+
+pub fn subgraph(
+    coo: &CooGraphStorage,
+    nodes: &Tensor,
+) -> TensorResult<(CooGraphStorage, Tensor)> {
+    let num_nodes = coo.size.0.max(coo.size.1);
+    let nodes_data = try_tensor_to_slice::<i64>(nodes)?;
+
+    if let Some(v) = nodes_data.iter().find(|v| !(0..num_nodes).contains(*v)) {
+        return Err(TensorConversionError::Unknown(format!("node {} is out of bounds", v)));
+    }
+
+    // Keep the edges between selected nodes and relabel them to positions in `nodes`, the first position of a
+    // node listed twice
+    let (row, col) = (coo.row().contiguous(), coo.col().contiguous());
+    let row_local = index_of_slice(nodes_data, try_tensor_to_slice::<i64>(&row)?, false);
+    let col_local = index_of_slice(nodes_data, try_tensor_to_slice::<i64>(&col)?, false);
+    let mut rows = Vec::new();
+    let mut cols = Vec::new();
+    let mut edge_ids = Vec::new();
+    for (i, (u, v)) in row_local.into_iter().zip(col_local).enumerate() {
+        if u != -1 && v != -1 {
+            rows.push(u);
+            cols.push(v);
+            edge_ids.push(i as i64);
+        }
+    }
+
+    let n = nodes_data.len() as i64;
+    let row_col = Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0);
+    Ok((CooGraphStorage::new(row_col, (n, n)), Tensor::of_slice(&edge_ids)))
+}
+
+// Removes the nodes set in `mask` together with all their edges directly on the csc arrays. Without `relabel` the
+// graph keeps its size and removed columns are left empty, with it survivors are renumbered by their order and
+// their old ids are returned. Last is the original edge id (through perm, if any) of every kept edge, which is
+// also the perm of the result.
+pub fn remove_nodes(
+    storage: &CscGraphStorage,
+    mask: &Tensor,
+    relabel: bool,
+) -> TensorResult<(CscGraphStorage, Option<Tensor>, Tensor)> {
+    let ptrs_data = try_tensor_to_slice::<i64>(&storage.ptrs)?;
+    let indices_data = try_tensor_to_slice::<i64>(&storage.indices)?;
+    let perm = storage.perm().map(|perm| perm.contiguous());
+    let perm_data = match perm.as_ref() {
+        Some(perm) => Some(try_tensor_to_slice::<i64>(perm)?),
+        None => None,
+    };
+
+    let num_nodes = ptrs_data.len() as i64 - 1;
+    if mask.size() != vec![num_nodes] {
+        return Err(TensorConversionError::InvalidShape(Some(format!(
+            "mask must be of shape [{}], got {:?}", num_nodes, mask.size()
+        ))));
+    }
+    let mask = mask.to_kind(Kind::Bool).contiguous();
+    let mask_data = try_tensor_to_slice::<bool>(&mask)?;
+
+    // New id of every node, -1 for removed nodes
+    let mut mapping = vec![-1_i64; num_nodes as usize];
+    let mut old_ids = Vec::new();
+    for (v, removed) in mask_data.iter().enumerate() {
+        if !removed {
+            mapping[v] = if relabel { old_ids.len() as i64 } else { v as i64 };
+            old_ids.push(v as i64);
+        }
+    }
+
+    let mut ptrs = Vec::with_capacity(if relabel { old_ids.len() + 1 } else { ptrs_data.len() });
+    let mut indices = Vec::new();
+    let mut edge_ids = Vec::new();
+    ptrs.push(0);
+    for (w, removed) in mask_data.iter().enumerate() {
+        if !removed {
+            for ptr in ptrs_data[w] as usize..ptrs_data[w + 1] as usize {
+                let u = indices_data[ptr];
+                if u < 0 || u >= num_nodes {
+                    return Err(TensorConversionError::Unknown(format!("node {} is out of bounds", u)));
+                }
+                if mapping[u as usize] != -1 {
+                    indices.push(mapping[u as usize]);
+                    edge_ids.push(perm_data.map_or(ptr as i64, |perm| perm[ptr]));
+                }
+            }
+        }
+        if !removed || !relabel {
+            ptrs.push(indices.len() as i64);
+        }
+    }
+
+    let edge_ids = Tensor::of_slice(&edge_ids);
+    let result = CscGraphStorage::new(Tensor::of_slice(&ptrs), Tensor::of_slice(&indices), Some(edge_ids.shallow_clone()))
+        .with_ordering(storage.ordering);
+    Ok((result, if relabel { Some(Tensor::of_slice(&old_ids)) } else { None }, edge_ids))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormMode {
+    // D^-1/2 A D^-1/2 with in-degrees on both sides
+    Sym,
+    // Outgoing weights of every source node sum to one
+    Row,
+    // Incoming weights of every destination node sum to one
+    Col,
+}
+
+pub fn normalize_weights(
+    storage: &CscGraphStorage,
+    weights: Option<&Tensor>,
+    mode: NormMode,
+    add_self_loops: bool,
+) -> TensorResult<(CscGraphStorage, Tensor)> {
+    let col_ptrs = try_tensor_to_slice::<i64>(&storage.ptrs)?;
+    let row_indices = try_tensor_to_slice::<i64>(&storage.indices)?;
+    let num_edges = row_indices.len();
+    let node_count = col_ptrs.len().saturating_sub(1);
+
+    let kind = weights.map_or(Kind::Float, |w| w.kind());
+    let weights = match weights {
+        Some(weights) => {
+            let weights = weights.to_kind(Kind::Double).contiguous();
+            if weights.numel() != num_edges {
+                return Err(TensorConversionError::InvalidShape(Some("weights must have one value per edge".to_string())));
+            }
+            try_tensor_to_slice::<f64>(&weights)?.to_vec()
+        }
+        None => vec![1.0; num_edges],
+    };
+
+    // Missing self loops are inserted in row order with weight 1, the csc permutation is dropped in that case
+    let (ptrs, indices, mut values, perm) = if add_self_loops {
+        let mut ptrs = Vec::with_capacity(col_ptrs.len());
+        let mut indices = Vec::with_capacity(num_edges + node_count);
+        let mut values = Vec::with_capacity(num_edges + node_count);
+        ptrs.push(0_i64);
+        for col in 0..node_count {
+            let range = col_ptrs[col] as usize..col_ptrs[col + 1] as usize;
+            let mut has_loop = row_indices[range.clone()].contains(&(col as i64));
+            for edge_ptr in range {
+                let row = row_indices[edge_ptr];
+                if !has_loop && row > col as i64 {
+                    indices.push(col as i64);
+                    values.push(1.0);
+                    has_loop = true;
+                }
+                indices.push(row);
+                values.push(weights[edge_ptr]);
+            }
+            if !has_loop {
+                indices.push(col as i64);
+                values.push(1.0);
+            }
+            ptrs.push(indices.len() as i64);
+        }
+        (Tensor::of_slice(&ptrs), Tensor::of_slice(&indices), values, None)
+    } else {
+        (storage.ptrs.shallow_clone(), storage.indices.shallow_clone(), weights, storage.perm().map(Tensor::shallow_clone))
+    };
+
+    let ptrs_data = try_tensor_to_slice::<i64>(&ptrs)?;
+    let indices_data = try_tensor_to_slice::<i64>(&indices)?;
+    let mut in_degree = vec![0.0_f64; node_count];
+    let mut out_degree = vec![0.0_f64; node_count.max(indices_data.iter().max().map_or(0, |m| *m as usize + 1))];
+    for col in 0..node_count {
+        for edge_ptr in ptrs_data[col] as usize..ptrs_data[col + 1] as usize {
+            in_degree[col] += values[edge_ptr];
+            out_degree[indices_data[edge_ptr] as usize] += values[edge_ptr];
+        }
+    }
+
+    // Zero degree nodes get an inverse degree of 0 so they never produce NaNs
+    let inv = |d: f64, f: fn(f64) -> f64| if d == 0.0 { 0.0 } else { 1.0 / f(d) };
+    for col in 0..node_count {
+        for edge_ptr in ptrs_data[col] as usize..ptrs_data[col + 1] as usize {
+            let row = indices_data[edge_ptr] as usize;
+            values[edge_ptr] *= match mode {
+                NormMode::Sym => {
+                    let in_row = in_degree.get(row).cloned().unwrap_or(0.0);
+                    inv(in_row, f64::sqrt) * inv(in_degree[col], f64::sqrt)
+                }
+                NormMode::Row => inv(out_degree[row], |d| d),
+                NormMode::Col => inv(in_degree[col], |d| d),
+            };
+        }
+    }
+
+    Ok((
+        CscGraphStorage::new(ptrs, indices, perm).with_ordering(storage.ordering),
+        Tensor::of_slice(&values).to_kind(kind),
+    ))
+}
+
+// Uniform value in [0, 1) derived from the seed and a key, so every edge can be decided independently
+fn hash_uniform(seed: u64, a: u64, b: u64) -> f64 {
+    let mut z = seed ^ a.wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ b.wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+// Keeps every edge (u, v) with probability p = min(1, c (1 / deg_u + 1 / deg_v)) and returns 1 / p as the weight
+// of the kept edges, which preserves cuts in expectation. Degrees are the in-degrees from the ptrs. With
+// `undirected` both directions of an edge are kept or dropped together.
+pub fn sparsify(
+    storage: &CscGraphStorage,
+    c: f64,
+    undirected: bool,
+    seed: u64,
+) -> TensorResult<(CscGraphStorage, Tensor)> {
+    if c.is_nan() || c <= 0.0 {
+        return Err(TensorConversionError::Unknown(format!("c must be positive, got {}", c)));
+    }
+    let col_ptrs = try_tensor_to_slice::<i64>(&storage.ptrs)?;
+    let row_indices = try_tensor_to_slice::<i64>(&storage.indices)?;
+    let node_count = col_ptrs.len().saturating_sub(1);
+
+    let inv_degree = |x: i64| {
+        let x = x as usize;
+        let degree = if x < node_count { col_ptrs[x + 1] - col_ptrs[x] } else { 0 };
+        if degree == 0 { f64::INFINITY } else { 1.0 / degree as f64 }
+    };
+
+    let probs: Vec<Option<f64>> = parallel::install(|| (0..node_count).into_par_iter()
+        .flat_map_iter(|col| {
+            (col_ptrs[col] as usize..col_ptrs[col + 1] as usize).map(move |edge_ptr| {
+                let row = row_indices[edge_ptr];
+                let p = (c * (inv_degree(row) + inv_degree(col as i64))).min(1.0);
+                let u = if undirected {
+                    hash_uniform(seed, row.min(col as i64) as u64, row.max(col as i64) as u64)
+                } else {
+                    hash_uniform(seed, edge_ptr as u64, u64::MAX)
+                };
+                if u < p { Some(p) } else { None }
+            })
+        })
+        .collect());
+
+    let mut ptrs = Vec::with_capacity(col_ptrs.len());
+    let mut kept = Vec::new();
+    let mut weights = Vec::new();
+    ptrs.push(0_i64);
+    for col in 0..node_count {
+        let start = col_ptrs[col] as usize;
+        for (i, p) in probs[start..col_ptrs[col + 1] as usize].iter().enumerate() {
+            if let Some(p) = p {
+                kept.push((start + i) as i64);
+                weights.push(1.0 / p);
+            }
+        }
+        ptrs.push(kept.len() as i64);
+    }
+
+    let kept = Tensor::of_slice(&kept);
+    let perm = match storage.perm() {
+        Some(perm) => perm.index_select(0, &kept),
+        None => kept.shallow_clone(),
+    };
+    Ok((
+        CscGraphStorage::new(Tensor::of_slice(&ptrs), storage.indices.index_select(0, &kept), Some(perm))
+            .with_ordering(storage.ordering),
+        Tensor::of_slice(&weights),
+    ))
+}
+
+fn batch_positions(
+    batch: &[i64],
+) -> TensorResult<(Vec<i64>, Vec<i64>)> {
+    let mut counts: Vec<i64> = Vec::new();
+    let mut positions = Vec::with_capacity(batch.len());
+    for b in batch.iter().cloned() {
+        if b < 0 {
+            return Err(TensorConversionError::Unknown(format!("invalid graph index {}", b)));
+        }
+        if b as usize >= counts.len() {
+            counts.resize(b as usize + 1, 0);
+        }
+        positions.push(counts[b as usize]);
+        counts[b as usize] += 1;
+    }
+    Ok((counts, positions))
+}
+
+pub fn to_dense_batch(
+    x: &Tensor,
+    batch: &Tensor,
+) -> TensorResult<(Tensor, Tensor)> {
+    let size = x.size();
+    let batch_data = try_tensor_to_slice::<i64>(batch)?;
+    if size.is_empty() || size[0] != batch_data.len() as i64 {
+        return Err(TensorConversionError::InvalidShape(Some("x and batch must have the same length".to_string())));
+    }
+
+    let (counts, positions) = batch_positions(batch_data)?;
+    let num_graphs = counts.len() as i64;
+    let max_nodes = counts.iter().cloned().max().unwrap_or(0);
+
+    // Padding slots point at an extra zero row appended after the last node
+    let num_nodes = size[0];
+    let mut index = vec![num_nodes; (num_graphs * max_nodes) as usize];
+    for (i, (b, pos)) in batch_data.iter().zip(positions.iter()).enumerate() {
+        index[(b * max_nodes + pos) as usize] = i as i64;
+    }
+    let index = Tensor::of_slice(&index);
+    let mask = index.ne(num_nodes).view([num_graphs, max_nodes]);
+
+    let mut padding_size = size.clone();
+    padding_size[0] = 1;
+    let padding = Tensor::zeros(&padding_size, (x.kind(), x.device()));
+    let mut out_size = vec![num_graphs, max_nodes];
+    out_size.extend_from_slice(&size[1..]);
+    let out = Tensor::cat(&[x, &padding], 0)
+        .index_select(0, &index.to_device(x.device()))
+        .view(out_size.as_slice());
+
+    Ok((out, mask.to_device(x.device())))
+}
+
+pub fn to_dense_adj(
+    edge_index: &Tensor,
+    batch: &Tensor,
+) -> TensorResult<Tensor> {
+    let coo = CooGraphStorage::new(edge_index.shallow_clone(), (0, 0));
+    let (row, col) = (coo.row().contiguous(), coo.col().contiguous());
+    let row_data = try_tensor_to_slice::<i64>(&row)?;
+    let col_data = try_tensor_to_slice::<i64>(&col)?;
+    let batch_data = try_tensor_to_slice::<i64>(batch)?;
+
+    let (counts, positions) = batch_positions(batch_data)?;
+    let num_graphs = counts.len() as i64;
+    let max_nodes = counts.iter().cloned().max().unwrap_or(0);
+
+    // Duplicate edges are accumulated
+    let mut adj = vec![0.0_f32; (num_graphs * max_nodes * max_nodes) as usize];
+    for (u, v) in row_data.iter().zip(col_data.iter()) {
+        let (u, v) = (*u as usize, *v as usize);
+        if u >= batch_data.len() || v >= batch_data.len() {
+            return Err(TensorConversionError::Unknown(format!("edge ({}, {}) is out of bounds", u, v)));
+        }
+        let b = batch_data[u];
+        if batch_data[v] != b {
+            return Err(TensorConversionError::Unknown(format!("edge ({}, {}) connects different graphs", u, v)));
+        }
+        adj[((b * max_nodes + positions[u]) * max_nodes + positions[v]) as usize] += 1.0;
+    }
+
+    Ok(Tensor::of_slice(&adj).view([num_graphs, max_nodes, max_nodes]).to_device(edge_index.device()))
+}
+
 #[cfg(test)]
 mod tests {
-    use tch::Tensor;
-    use crate::data::transform::{csc_edge_cumsum, csc_sort_edges};
+    use std::convert::TryFrom;
+    use tch::{IndexOp, Kind, Tensor};
+    use crate::data::{CooGraphStorage, CscGraphStorage, load_karate_graph};
+    use crate::data::transform::{NormMode, add_virtual_node, add_virtual_node_features, csc_edge_cumsum, csc_sort_edges, normalize_weights, remove_nodes, sparsify, subgraph, to_dense_adj, to_dense_batch};
 
 
     #[test]
@@ -95,4 +480,236 @@ mod tests {
 
         assert_eq!(result_data, vec![9.0, 14.0, 22.0, 9.0, 19.0, 11.0, 12.0, 1.5]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_add_virtual_node() {
+        let (x, _, coo_graph) = load_karate_graph();
+        let num_nodes = coo_graph.size.0;
+        let num_edges = coo_graph.row_col.size()[1];
+
+        let (result, virtual_node) = add_virtual_node(&coo_graph);
+        assert_eq!(virtual_node, num_nodes);
+        assert_eq!(result.size, (num_nodes + 1, num_nodes + 1));
+        assert_eq!(result.row_col.size(), vec![2, num_edges + 2 * num_nodes]);
+
+        let rows: Vec<i64> = result.row().into();
+        let cols: Vec<i64> = result.col().into();
+        let degree = rows.iter().chain(cols.iter()).filter(|&&v| v == virtual_node).count();
+        assert_eq!(degree as i64, 2 * num_nodes);
+
+        let x_aug = add_virtual_node_features(&x, None);
+        assert_eq!(x_aug.size()[0], num_nodes + 1);
+        assert_eq!(x_aug.i(num_nodes).abs().sum(Kind::Double).double_value(&[]), 0.0);
+
+        let empty = CooGraphStorage::new(Tensor::empty(&[2, 0], (Kind::Int64, tch::Device::Cpu)), (3, 3));
+        let (result, virtual_node) = add_virtual_node(&empty);
+        assert_eq!(virtual_node, 3);
+        assert_eq!(result.row_col.size(), vec![2, 6]);
+    }
+
+    #[test]
+    fn test_subgraph() {
+        let row_col = Tensor::of_slice(&[0_i64, 1, 1, 2, 3, 1, 0, 2, 1, 0]).view((2, 5));
+        let coo_graph = CooGraphStorage::new(row_col, (4, 4));
+
+        let (result, edge_ids) = subgraph(&coo_graph, &Tensor::of_slice(&[2_i64, 1])).unwrap();
+        let rows: Vec<i64> = result.row().into();
+        let cols: Vec<i64> = result.col().into();
+        let edge_ids: Vec<i64> = edge_ids.into();
+
+        assert_eq!(result.size, (2, 2));
+        assert_eq!(rows, vec![1, 0]);
+        assert_eq!(cols, vec![0, 1]);
+        assert_eq!(edge_ids, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_remove_nodes() {
+        // Node 34 is isolated, node 33 is the largest hub
+        let (_x, _, coo) = load_karate_graph();
+        let coo = CooGraphStorage::new(coo.row_col.shallow_clone(), (35, 35));
+        let csc = CscGraphStorage::try_from(&coo).unwrap();
+        let rows: Vec<i64> = coo.row().into();
+        let cols: Vec<i64> = coo.col().into();
+
+        // (row, col, edge id) of every edge in the result, sorted
+        let triples = |graph: &CscGraphStorage| {
+            let ptrs: Vec<i64> = graph.ptrs.shallow_clone().into();
+            let indices: Vec<i64> = graph.indices.shallow_clone().into();
+            let perm: Vec<i64> = graph.perm().unwrap().shallow_clone().into();
+            let mut triples: Vec<(i64, i64, i64)> = (0..ptrs.len() - 1)
+                .flat_map(|w| (ptrs[w]..ptrs[w + 1]).map(move |ptr| (w, ptr as usize)))
+                .map(|(w, ptr)| (indices[ptr], w as i64, perm[ptr]))
+                .collect();
+            triples.sort_unstable();
+            triples
+        };
+
+        for removed in [vec![34_i64], vec![33], vec![0, 5, 33, 34], vec![]] {
+            let mut mask = vec![false; 35];
+            removed.iter().for_each(|v| mask[*v as usize] = true);
+            let mask_tensor = Tensor::of_slice(&mask);
+
+            for relabel in [false, true] {
+                let (result, old_ids, edge_ids) = remove_nodes(&csc, &mask_tensor, relabel).unwrap();
+                assert!(result.validate().is_ok());
+                assert!(edge_ids.equal(result.perm().unwrap()));
+
+                // Reference: mask the coo edges and relabel by hand
+                let kept: Vec<i64> = (0..35).filter(|v| !mask[*v as usize]).collect();
+                let new_id = |v: i64| if relabel { kept.iter().position(|u| *u == v).unwrap() as i64 } else { v };
+                let mut expected: Vec<(i64, i64, i64)> = rows.iter().zip(cols.iter()).enumerate()
+                    .filter(|(_, (u, v))| !mask[**u as usize] && !mask[**v as usize])
+                    .map(|(i, (u, v))| (new_id(*u), new_id(*v), i as i64))
+                    .collect();
+                expected.sort_unstable();
+                assert_eq!(triples(&result), expected);
+
+                if relabel {
+                    let old_ids: Vec<i64> = old_ids.unwrap().into();
+                    assert_eq!(old_ids, kept);
+                    assert_eq!(result.ptrs.size()[0] as usize, kept.len() + 1);
+                } else {
+                    assert!(old_ids.is_none());
+                    assert_eq!(result.ptrs.size()[0], 36);
+                }
+            }
+        }
+
+        assert!(remove_nodes(&csc, &Tensor::of_slice(&[false; 3]), true).is_err());
+    }
+
+    #[test]
+    fn test_to_dense_batch() {
+        let x = Tensor::of_slice(&[1_i64, 2, 3, 4, 5, 6, 7, 8, 9, 10]).view([5, 2]);
+        let batch = Tensor::of_slice(&[0_i64, 0, 0, 1, 1]);
+
+        let (out, mask) = to_dense_batch(&x, &batch).unwrap();
+        assert_eq!(out.size(), vec![2, 3, 2]);
+        let out: Vec<i64> = out.view([-1]).into();
+        assert_eq!(out, vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 0, 0]);
+        let mask: Vec<i64> = mask.to_kind(Kind::Int64).view([-1]).into();
+        assert_eq!(mask, vec![1, 1, 1, 1, 1, 0]);
+
+        // Graph 0 has nodes 0, 1, 2 and graph 1 has nodes 3, 4
+        let edge_index = Tensor::stack(&[
+            Tensor::of_slice(&[0_i64, 1, 1, 2, 3, 4, 3]),
+            Tensor::of_slice(&[1_i64, 0, 2, 1, 4, 3, 4]),
+        ], 0);
+        let adj = to_dense_adj(&edge_index, &batch).unwrap();
+        assert_eq!(adj.size(), vec![2, 3, 3]);
+        let adj: Vec<f32> = adj.view([-1]).into();
+        assert_eq!(adj, vec![
+            0.0, 1.0, 0.0,
+            1.0, 0.0, 1.0,
+            0.0, 1.0, 0.0,
+            0.0, 2.0, 0.0,
+            1.0, 0.0, 0.0,
+            0.0, 0.0, 0.0,
+        ]);
+
+        let edge_index = Tensor::stack(&[Tensor::of_slice(&[0_i64]), Tensor::of_slice(&[3_i64])], 0);
+        assert!(to_dense_adj(&edge_index, &batch).is_err());
+    }
+
+    #[test]
+    fn test_normalize_weights() {
+        // Node 2 already has a self loop and node 3 is isolated
+        let edges = [(0_i64, 1_i64, 2.0), (1, 0, 1.0), (1, 2, 3.0), (2, 2, 4.0), (0, 2, 0.5)];
+        let rows: Vec<i64> = edges.iter().map(|e| e.0).collect();
+        let cols: Vec<i64> = edges.iter().map(|e| e.1).collect();
+        let coo = CooGraphStorage::new(Tensor::stack(&[Tensor::of_slice(&rows), Tensor::of_slice(&cols)], 0), (4, 4));
+        let storage = CscGraphStorage::try_from(&coo).unwrap();
+        let perm: Vec<i64> = storage.perm().unwrap().into();
+        let weights: Vec<f64> = perm.iter().map(|&i| edges[i as usize].2).collect();
+        let weights = Tensor::of_slice(&weights);
+
+        for add_self_loops in [false, true] {
+            let mut dense = vec![vec![0.0_f64; 4]; 4];
+            for (r, c, w) in edges.iter().cloned() {
+                dense[r as usize][c as usize] = w;
+            }
+            if add_self_loops {
+                for (i, row) in dense.iter_mut().enumerate() {
+                    if row[i] == 0.0 {
+                        row[i] = 1.0;
+                    }
+                }
+            }
+            let row_sum: Vec<f64> = dense.iter().map(|r| r.iter().sum()).collect();
+            let col_sum: Vec<f64> = (0..4).map(|c| dense.iter().map(|r| r[c]).sum()).collect();
+            let inv = |d: f64| if d == 0.0 { 0.0 } else { 1.0 / d };
+
+            for mode in [NormMode::Sym, NormMode::Row, NormMode::Col] {
+                let (out, values) = normalize_weights(&storage, Some(&weights), mode, add_self_loops).unwrap();
+                let ptrs: Vec<i64> = (&out.ptrs).into();
+                let indices: Vec<i64> = (&out.indices).into();
+                let values: Vec<f64> = values.into();
+                assert_eq!(values.len(), if add_self_loops { 8 } else { 5 });
+
+                for c in 0..4 {
+                    for e in ptrs[c] as usize..ptrs[c + 1] as usize {
+                        let r = indices[e] as usize;
+                        let expected = dense[r][c] * match mode {
+                            NormMode::Sym => inv(col_sum[r].sqrt()) * inv(col_sum[c].sqrt()),
+                            NormMode::Row => inv(row_sum[r]),
+                            NormMode::Col => inv(col_sum[c]),
+                        };
+                        assert!((values[e] - expected).abs() < 1e-9);
+                    }
+                }
+            }
+        }
+
+        // Without weights every edge counts as 1
+        let (_, values) = normalize_weights(&storage, None, NormMode::Col, false).unwrap();
+        let values: Vec<f32> = values.into();
+        assert_eq!(values, vec![1.0, 1.0, 1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0]);
+    }
+
+    #[test]
+    fn test_sparsify() {
+        let (_x, _, coo_graph) = load_karate_graph();
+        let storage = CscGraphStorage::try_from(&coo_graph).unwrap();
+        let ptrs: Vec<i64> = (&storage.ptrs).into();
+        let degree = |x: i64| (ptrs[x as usize + 1] - ptrs[x as usize]) as f64;
+        let c = 0.3;
+
+        let (rows, cols) = (coo_graph.row(), coo_graph.col());
+        let rows: Vec<i64> = rows.into();
+        let cols: Vec<i64> = cols.into();
+        let prob = |r: i64, c_: i64| (c * (1.0 / degree(r) + 1.0 / degree(c_))).min(1.0);
+        let expected: f64 = rows.iter().zip(cols.iter()).map(|(r, c_)| prob(*r, *c_)).sum();
+        assert!(expected < rows.len() as f64);
+
+        for undirected in [false, true] {
+            let mut total = 0;
+            let num_seeds = 50;
+            for seed in 0..num_seeds {
+                let (out, weights) = sparsify(&storage, c, undirected, seed).unwrap();
+                out.validate().unwrap();
+                let perm: Vec<i64> = out.perm().unwrap().into();
+                let indices: Vec<i64> = (&out.indices).into();
+                let weights: Vec<f64> = weights.into();
+                assert_eq!(weights.len(), indices.len());
+                total += indices.len();
+
+                let mut kept = std::collections::HashSet::new();
+                for ((e, r), w) in perm.iter().zip(indices.iter()).zip(weights.iter()) {
+                    let (r_, c_) = (rows[*e as usize], cols[*e as usize]);
+                    assert_eq!(r_, *r);
+                    assert!((w - 1.0 / prob(r_, c_)).abs() < 1e-9);
+                    kept.insert((r_, c_));
+                }
+                if undirected {
+                    assert!(kept.iter().all(|(r, c_)| kept.contains(&(*c_, *r))));
+                }
+            }
+
+            let mean = total as f64 / num_seeds as f64;
+            assert!((mean - expected).abs() < 0.1 * expected, "{} vs {}", mean, expected);
+        }
+
+        assert!(sparsify(&storage, 0.0, false, 0).is_err());
+    }
+}
@@ -1,13 +1,28 @@
 #![allow(clippy::type_complexity)]
 
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use pyo3::exceptions::PyRuntimeError;
 use pyo3::prelude::*;
 
+// Last resort guard of the entry points, a panic (e.g. indexing with a malformed graph) is raised as a RuntimeError
+// with the panic message instead of unwinding into the interpreter
+fn catch_panics<T>(f: impl FnOnce() -> PyResult<T>) -> PyResult<T> {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = payload.downcast_ref::<&str>().map(|m| m.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        Err(PyRuntimeError::new_err(format!("internal error: {}", message)))
+    })
+}
 
 mod data {
+    use pyo3::exceptions::PyValueError;
     use pyo3::prelude::*;
+    use super::catch_panics;
     use std::convert::TryFrom;
     use tch::Tensor;
-    use crate::data::{CooGraphStorage, CscGraphStorage, CsrGraphStorage};
+    use crate::data::{CooGraphStorage, CscGraphStorage, CsrGraphStorage, SharedGraphHandle};
+    use crate::utils::{prepare_index_tensor, try_tensor_to_slice};
 
     #[derive(FromPyObject)]
     pub enum GraphSize {
@@ -24,18 +39,38 @@ mod data {
         }
     }
 
+    // Edge lists from python have to be [2, E] with rows in 0..m and cols in 0..n
+    fn check_row_col(row_col: &Tensor, (m, n): (i64, i64)) -> PyResult<Tensor> {
+        let row_col = prepare_index_tensor(row_col)?;
+        if row_col.dim() != 2 || row_col.size()[0] != 2 {
+            return Err(PyValueError::new_err(format!("row_col must be of shape [2, E], got {:?}", row_col.size())));
+        }
+        if m < 0 || n < 0 {
+            return Err(PyValueError::new_err(format!("size must be non negative, got ({}, {})", m, n)));
+        }
+        let data = row_col.contiguous();
+        let values = try_tensor_to_slice::<i64>(&data)?;
+        let (rows, cols) = values.split_at(values.len() / 2);
+        for (name, index, bound) in [("row", rows, m), ("col", cols, n)] {
+            if let Some((i, v)) = index.iter().enumerate().find(|(_, &v)| v < 0 || v >= bound) {
+                return Err(PyValueError::new_err(format!(
+                    "{} {} of edge {} is out of bounds for size {}", name, v, i, bound
+                )));
+            }
+        }
+        Ok(data)
+    }
+
     #[pyfunction]
     pub fn to_csc(
         row_col: Tensor,
         size: GraphSize,
     ) -> PyResult<(Tensor, Tensor, Option<Tensor>)> {
-        let size = size.to_tuple();
-        let coo_graph = CooGraphStorage::new(row_col, size);
-        let CscGraphStorage {
-            ptrs, indices, perm, ..
-        } = CscGraphStorage::try_from(&coo_graph)?;
-
-        Ok((ptrs, indices, perm))
+        catch_panics(move || {
+            let size = size.to_tuple();
+            let coo_graph = CooGraphStorage::new(check_row_col(&row_col, size)?, size);
+            Ok(CscGraphStorage::try_from(&coo_graph)?.into_parts())
+        })
     }
 
     #[pyfunction]
@@ -43,38 +78,131 @@ mod data {
         row_col: Tensor,
         size: GraphSize,
     ) -> PyResult<(Tensor, Tensor, Option<Tensor>)> {
-        let size = size.to_tuple();
-        let coo_graph = CooGraphStorage::new(row_col, size);
-        let CsrGraphStorage {
-            ptrs, indices, perm, ..
-        } = CsrGraphStorage::try_from(&coo_graph)?;
+        catch_panics(move || {
+            let size = size.to_tuple();
+            let coo_graph = CooGraphStorage::new(check_row_col(&row_col, size)?, size);
+            Ok(CsrGraphStorage::try_from(&coo_graph)?.into_parts())
+        })
+    }
 
-        Ok((ptrs, indices, perm))
+    // Returns the path of the shared memory segment, which is all a spawned worker needs to map the graph
+    #[pyfunction]
+    pub fn share_memory(
+        ptrs: Tensor,
+        indices: Tensor,
+        perm: Option<Tensor>,
+        edge_type: Option<Tensor>,
+    ) -> PyResult<String> {
+        catch_panics(move || {
+            let perm = perm.as_ref().map(prepare_index_tensor).transpose()?;
+            let mut graph_data = CscGraphStorage::new(prepare_index_tensor(&ptrs)?, prepare_index_tensor(&indices)?, perm);
+            if let Some(edge_type) = edge_type {
+                graph_data = graph_data.with_edge_type(prepare_index_tensor(&edge_type)?);
+            }
+            // Python unlinks the segment through `unlink_shared`
+            let handle = graph_data.share_memory()?.into_handle();
+            Ok(handle.dir.to_string_lossy().into_owned())
+        })
     }
 
+    #[pyfunction]
+    pub fn unlink_shared(
+        path: String,
+    ) -> PyResult<()> {
+        catch_panics(move || {
+            Ok(SharedGraphHandle::new(path.into()).unlink()?)
+        })
+    }
 
     pub fn module(_py: Python, m: &PyModule) -> PyResult<()> {
         m.add_function(wrap_pyfunction!(to_csc, m)?)?;
         m.add_function(wrap_pyfunction!(to_csr, m)?)?;
+        m.add_function(wrap_pyfunction!(share_memory, m)?)?;
+        m.add_function(wrap_pyfunction!(unlink_shared, m)?)?;
         Ok(())
     }
 }
 
 mod algo {
     use std::collections::HashMap;
-    use std::convert::TryInto;
-    use num_traits::Float;
     use pyo3::exceptions::PyValueError;
     use pyo3::prelude::*;
-    use rand::distributions::uniform::SampleUniform;
+    use pyo3::types::PyDict;
+    use rand::Rng;
     use tch::kind::Element;
-    use tch::Tensor;
+    use tch::{Device, Kind, Tensor};
     use crate::algo::hgt_sampling::Timestamp;
     use crate::algo::neighbor_sampling as ns;
     use crate::algo::neighbor_sampling::LayerOffset;
+    use crate::algo::degree::{BatchingStrategy, HomophilyKind};
     use crate::algo::random_walk::BiasType;
-    use crate::data::{CscGraph, CsrGraph, EdgeAttr, CooGraphBuilder, Size};
-    use crate::utils::{hashmap_from, EdgeType, NodeIdx, NodeType, RelType, TensorConversionError, TensorResult, try_tensor_to_slice, random};
+    use crate::algo::spatial::Metric;
+    use crate::data::{CscGraph, CsrGraph, CsrGraphStorage, EdgeAttr, EdgeAttrSource, CooGraphBuilder, NeighborSource, Size, TypedGraphStorage};
+    use crate::utils::{hashmap_from, EdgeType, NodeIdx, NodeType, RelType, TensorConversionError, TensorResult, prepare_index_tensor, prepare_index_tensors, try_tensor_to_slice, random};
+    use super::catch_panics;
+
+    // Pointers and indices of a compressed graph from python. Only the pointers are checked here, the neighbor
+    // indices of a relation may address another node type, entry points on square graphs call `validate` and the
+    // heterogenous ones check every relation against the size of its src type with `validate_relations`.
+    fn graph_slices<'a>(ptrs: &'a Tensor, indices: &'a Tensor) -> PyResult<(&'a [i64], &'a [i64])> {
+        if ptrs.dim() != 1 || indices.dim() != 1 {
+            return Err(PyValueError::new_err(format!(
+                "ptrs and indices must be one dimensional, got {:?} and {:?}", ptrs.size(), indices.size()
+            )));
+        }
+        let (ptrs_data, indices_data) = (try_tensor_to_slice::<i64>(ptrs)?, try_tensor_to_slice::<i64>(indices)?);
+        CscGraph::new(ptrs_data, indices_data).validate_ptrs()?;
+        Ok((ptrs_data, indices_data))
+    }
+
+    // Node count of a compressed graph, an empty ptrs tensor has no valid count
+    fn ptrs_node_count(ptrs: &Tensor) -> PyResult<usize> {
+        match ptrs.size().as_slice() {
+            [n] if *n > 0 => Ok(*n as usize - 1),
+            size => Err(PyValueError::new_err(format!("ptrs must hold at least one entry, got shape {:?}", size))),
+        }
+    }
+
+    fn relation_slices<'a>(
+        ptrs: &'a HashMap<RelType, Tensor>,
+        indices: &'a HashMap<RelType, Tensor>,
+        rel_type: &RelType,
+    ) -> PyResult<(&'a [i64], &'a [i64])> {
+        match (ptrs.get(rel_type), indices.get(rel_type)) {
+            (Some(ptrs), Some(indices)) => graph_slices(ptrs, indices),
+            _ => Err(PyValueError::new_err(format!("ptrs and indices must both be given for relation {}", rel_type))),
+        }
+    }
+
+    // The neighbors of a relation are nodes of its src type, so they are checked against the node count of that type.
+    // The count is known once the type is the dst of some relation, neighbors of other types are left to the algorithms.
+    fn validate_relations<'a, 'g: 'a>(
+        edge_types: &[EdgeType],
+        graph: impl Fn(&RelType) -> Option<&'a CscGraph<'g, i64, i64>>,
+    ) -> PyResult<()> {
+        let rel_key = |(src, rel, dst): &EdgeType| format!("{}__{}__{}", src, rel, dst);
+        let mut node_counts: HashMap<&NodeType, usize> = HashMap::new();
+        for edge_type in edge_types {
+            if let Some(graph) = graph(&rel_key(edge_type)) {
+                node_counts.insert(&edge_type.2, graph.node_count());
+            }
+        }
+        for edge_type in edge_types {
+            if let (Some(graph), Some(&count)) = (graph(&rel_key(edge_type)), node_counts.get(&edge_type.0)) {
+                graph.validate_with_size(count)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_nodes(name: &str, nodes: &[i64], node_count: usize) -> PyResult<()> {
+        match nodes.iter().enumerate().find(|(_, &v)| v < 0 || v as usize >= node_count) {
+            Some((i, v)) => Err(PyValueError::new_err(format!(
+                "{} contains node {} at index {}, which is out of bounds for a graph with {} nodes", name, v, i, node_count
+            ))),
+            None => Ok(()),
+        }
+    }
 
     #[derive(FromPyObject)]
     pub enum MixedData {
@@ -104,34 +232,96 @@ mod algo {
         }
     }
 
+    // Start nodes of walks, either a tensor or anything torch.as_tensor takes such as a numpy array or a list of ints
+    #[derive(FromPyObject)]
+    pub enum StartNodes {
+        Tensor(Tensor),
+        Array(PyObject),
+    }
+
+    impl StartNodes {
+        pub fn to_tensor(&self, py: Python) -> PyResult<Tensor> {
+            match self {
+                StartNodes::Tensor(t) => Ok(prepare_index_tensor(t)?),
+                // Shares the memory of numpy arrays instead of converting element by element
+                StartNodes::Array(array) => {
+                    let tensor: Tensor = py.import("torch")?.getattr("as_tensor")?.call1((array.as_ref(py),))?.extract()?;
+                    Ok(prepare_index_tensor(&tensor)?)
+                }
+            }
+        }
+    }
+
+    // Either a single replacement flag or one flag per hop
+    #[derive(FromPyObject)]
+    pub enum ReplaceFlags {
+        Fixed(bool),
+        PerHop(Vec<bool>),
+    }
+
+    impl ReplaceFlags {
+        pub fn to_policy(&self, num_hops: usize) -> TensorResult<ns::ReplacePolicy> {
+            let policy = match self {
+                ReplaceFlags::Fixed(replace) => ns::ReplacePolicy::Fixed(*replace),
+                ReplaceFlags::PerHop(flags) => ns::ReplacePolicy::PerHop(flags.clone()),
+            };
+            policy.validate(num_hops)?;
+            Ok(policy)
+        }
+    }
+
+    // With `max_repeats` every edge is drawn at most that many times at the hops with replacement
     #[derive(FromPyObject)]
     pub struct UniformSampler {
-        with_replacement: bool,
+        with_replacement: ReplaceFlags,
+        max_repeats: Option<i64>,
     }
 
+    impl UniformSampler {
+        pub fn validate(&self, num_hops: usize) -> PyResult<()> {
+            let replaces = match &self.with_replacement {
+                ReplaceFlags::Fixed(replace) => *replace,
+                ReplaceFlags::PerHop(flags) => flags.iter().any(|replace| *replace),
+            };
+            if self.max_repeats.is_some() && !replaces {
+                return Err(PyValueError::new_err("max_repeats requires sampling with replacement"));
+            }
+            self.with_replacement.to_policy(num_hops)?;
+            Ok(())
+        }
+    }
+
+    // Weighted reservoir sampling without replacement, or independent draws from cached cdfs with replacement
     #[derive(FromPyObject)]
     pub struct WeightedSampler {
         weights: MixedData,
+        with_replacement: bool,
     }
 
     impl WeightedSampler {
-        pub fn build_homogenous<T: Float + SampleUniform + Element>(&self) -> TensorResult<ns::WeightedSampler<T>> {
-            let weights = self.weights.build_homogenous::<T>()?;
-            Ok(ns::WeightedSampler::new(EdgeAttr::new(weights)))
+        // Samplers of the relations with weights, reading them from the sources of `attr_sources`
+        pub fn build_heterogenous<'a, G: NeighborSource>(
+            &self, sources: &'a HashMap<RelType, EdgeAttrSource<'a, G>>,
+        ) -> TensorResult<HashMap<RelType, ns::WeightedSampler<'a, EdgeAttrSource<'a, G>>>> {
+            self.weights.build_heterogenous::<f64>()?.into_keys()
+                .map(|k| Ok((k.clone(), ns::WeightedSampler::new(relation_source(sources, &k)?))))
+                .collect()
         }
 
-        pub fn build_heterogenous<T: Float + SampleUniform + Element>(&self) -> TensorResult<HashMap<RelType, ns::WeightedSampler<T>>> {
-            let weights = self.weights.build_heterogenous::<T>()?;
-            Ok(weights.into_iter()
-                .map(|(k, v)| (k, ns::WeightedSampler::new(EdgeAttr::new(v))))
-                .collect())
+        pub fn build_heterogenous_replacement<'a, G: NeighborSource>(
+            &self, sources: &'a HashMap<RelType, EdgeAttrSource<'a, G>>,
+        ) -> TensorResult<HashMap<RelType, ns::WeightedReplacementSampler<'a, EdgeAttrSource<'a, G>>>> {
+            self.weights.build_heterogenous::<f64>()?.into_keys()
+                .map(|k| Ok((k.clone(), ns::WeightedReplacementSampler::new(relation_source(sources, &k)?)?)))
+                .collect()
         }
     }
 
     #[derive(FromPyObject)]
     pub enum SamplerType {
-        Uniform(UniformSampler),
+        // Tried first, only weighted samplers have weights
         Weighted(WeightedSampler),
+        Uniform(UniformSampler),
     }
 
     #[derive(FromPyObject)]
@@ -144,21 +334,18 @@ mod algo {
 
     impl TemporalFilter {
         pub fn build_homogenous<
-            const FORWARD: bool, const MODE: usize
-        >(&self) -> TensorResult<ns::TemporalFilter<i64, FORWARD, MODE>> {
-            let timestamps_data = self.timestamps.build_homogenous::<i64>()?;
-            let window = self.window.0..=self.window.1;
-            Ok(ns::TemporalFilter::new(window, EdgeAttr::new(timestamps_data)))
+            'a, G: NeighborSource + ?Sized, const FORWARD: bool, const MODE: usize
+        >(&self, source: &'a G) -> ns::TemporalFilter<'a, G, FORWARD, MODE> {
+            ns::TemporalFilter::new(self.window.0..=self.window.1, source)
         }
 
+        // Filters of the relations with timestamps
         pub fn build_heterogenous<
-            const FORWARD: bool, const MODE: usize
-        >(&self) -> TensorResult<HashMap<RelType, ns::TemporalFilter<i64, FORWARD, MODE>>> {
-            let timestamps_data = self.timestamps.build_heterogenous::<i64>()?;
-            let window = self.window.0..=self.window.1;
-            Ok(timestamps_data.into_iter()
-                .map(|(k, v)| (k, ns::TemporalFilter::new(window.clone(), EdgeAttr::new(v))))
-                .collect())
+            'a, G: NeighborSource, const FORWARD: bool, const MODE: usize
+        >(&self, sources: &'a HashMap<RelType, EdgeAttrSource<'a, G>>) -> TensorResult<HashMap<RelType, ns::TemporalFilter<'a, EdgeAttrSource<'a, G>, FORWARD, MODE>>> {
+            self.timestamps.build_heterogenous::<i64>()?.into_keys()
+                .map(|k| Ok((k.clone(), ns::TemporalFilter::new(self.window.0..=self.window.1, relation_source(sources, &k)?))))
+                .collect()
         }
     }
 
@@ -167,6 +354,50 @@ mod algo {
         TemporalFilter((TemporalFilter, MixedData)),
     }
 
+    // The graph with the weights of a weighted sampler and the timestamps of a temporal filter, which the samplers
+    // and filters read through `NeighborSource`
+    fn attr_source<'a, G: NeighborSource>(
+        graph: &'a G, sampler: Option<&'a SamplerType>, filter: Option<&'a FilterType>,
+    ) -> TensorResult<EdgeAttrSource<'a, G>> {
+        let mut source = EdgeAttrSource::new(graph);
+        if let Some(SamplerType::Weighted(s)) = sampler {
+            source = source.with_weights(s.weights.build_homogenous::<f64>()?);
+        }
+        if let Some(FilterType::TemporalFilter((ft, _))) = filter {
+            source = source.with_times(ft.timestamps.build_homogenous::<i64>()?);
+        }
+        Ok(source)
+    }
+
+    fn attr_sources<'a, G: NeighborSource>(
+        graphs: &'a HashMap<RelType, G>, sampler: Option<&'a SamplerType>, filter: Option<&'a FilterType>,
+    ) -> TensorResult<HashMap<RelType, EdgeAttrSource<'a, G>>> {
+        let weights = match sampler {
+            Some(SamplerType::Weighted(s)) => s.weights.build_heterogenous::<f64>()?,
+            _ => HashMap::new(),
+        };
+        let times = match filter {
+            Some(FilterType::TemporalFilter((ft, _))) => ft.timestamps.build_heterogenous::<i64>()?,
+            _ => HashMap::new(),
+        };
+        Ok(graphs.iter().map(|(k, graph)| {
+            let mut source = EdgeAttrSource::new(graph);
+            if let Some(weights) = weights.get(k) {
+                source = source.with_weights(weights);
+            }
+            if let Some(times) = times.get(k) {
+                source = source.with_times(times);
+            }
+            (k.clone(), source)
+        }).collect())
+    }
+
+    fn relation_source<'a, 'b, G>(
+        sources: &'a HashMap<RelType, EdgeAttrSource<'b, G>>, rel_type: &RelType,
+    ) -> TensorResult<&'a EdgeAttrSource<'b, G>> {
+        sources.get(rel_type).ok_or_else(|| TensorConversionError::Unknown(format!("unknown relation {}", rel_type)))
+    }
+
     macro_rules! match_mixed_return {
         {
             match ($m:expr) {
@@ -184,6 +415,37 @@ mod algo {
         }
     }
 
+    #[allow(clippy::type_complexity)]
+    fn parallel_edge_filters<'g, F: ns::SamplingFilter>(
+        graphs: &'g HashMap<RelType, CscGraph<'g, i64, i64>>,
+        filters: HashMap<RelType, F>,
+        multigraph: bool,
+        node_masks: &HashMap<RelType, &'g [bool]>,
+    ) -> HashMap<RelType, ns::ParallelEdgeFilter<'g, CscGraph<'g, i64, i64>, ns::NodeMaskFilter<'g, CscGraph<'g, i64, i64>, F>>> {
+        filters.into_iter()
+            .map(|(k, f)| {
+                let f = ns::NodeMaskFilter::new(&graphs[&k], f, node_masks.get(&k).cloned());
+                let f = ns::ParallelEdgeFilter::new(&graphs[&k], f, multigraph);
+                (k, f)
+            })
+            .collect()
+    }
+
+    // Masks are one bool per node, with a node count it has to match exactly
+    fn prepare_node_mask(node_mask: &Tensor, node_count: Option<usize>) -> PyResult<Tensor> {
+        if node_mask.kind() != Kind::Bool || node_mask.dim() != 1 {
+            return Err(PyValueError::new_err(format!(
+                "node_mask must be a one dimensional bool tensor, got {:?} of shape {:?}", node_mask.kind(), node_mask.size()
+            )));
+        }
+        match node_count {
+            Some(n) if node_mask.size()[0] != n as i64 => Err(PyValueError::new_err(format!(
+                "node_mask must have {} values, got {}", n, node_mask.size()[0]
+            ))),
+            _ => Ok(node_mask.to_device(Device::Cpu).contiguous()),
+        }
+    }
+
     #[pyfunction]
     pub fn neighbor_sampling_homogenous(
         col_ptrs: Tensor,
@@ -192,6 +454,128 @@ mod algo {
         num_neighbors: Vec<usize>,
         sampler: Option<SamplerType>,
         filter: Option<FilterType>,
+        multigraph: Option<bool>,
+        lonely_seed_policy: Option<String>,
+        node_mask: Option<Tensor>,
+        dedup: Option<bool>,
+    ) -> PyResult<(
+        Tensor,
+        Tensor,
+        Tensor,
+        Tensor,
+        Vec<LayerOffset>
+    )> {
+        catch_panics(move || {
+            neighbor_sampling_homogenous_impl(
+                &col_ptrs, &row_indices, &inputs, &num_neighbors, &sampler, &filter, multigraph.unwrap_or(true),
+                &lonely_seed_policy, &node_mask, dedup.unwrap_or(false), &mut ns::NoTracer,
+            )
+        })
+    }
+
+    #[pyfunction]
+    pub fn neighbor_sampling_homogenous_traced(
+        py: Python,
+        col_ptrs: Tensor,
+        row_indices: Tensor,
+        inputs: Tensor,
+        num_neighbors: Vec<usize>,
+        sampler: Option<SamplerType>,
+        filter: Option<FilterType>,
+        multigraph: Option<bool>,
+        lonely_seed_policy: Option<String>,
+        coverage: Option<bool>,
+        node_mask: Option<Tensor>,
+        dedup: Option<bool>,
+    ) -> PyResult<PyObject> {
+        catch_panics(move || {
+            let mut stats = if coverage.unwrap_or(false) {
+                ns::SamplerStats::with_coverage()
+            } else {
+                ns::SamplerStats::new()
+            };
+            let (samples, rows, cols, edge_index, layer_offsets) = neighbor_sampling_homogenous_impl(
+                &col_ptrs, &row_indices, &inputs, &num_neighbors, &sampler, &filter, multigraph.unwrap_or(true),
+                &lonely_seed_policy, &node_mask, dedup.unwrap_or(false), &mut stats,
+            )?;
+
+            // The coverage is only appended when asked for, callers unpacking the six outputs keep working
+            let outputs = (samples, rows, cols, edge_index, layer_offsets, stats.to_dict());
+            Ok(match stats.coverage.as_ref() {
+                Some(coverage) => {
+                    let (samples, rows, cols, edge_index, layer_offsets, stats) = outputs;
+                    let coverage = coverage.get(&None).cloned().unwrap_or_default().to_tensors();
+                    (samples, rows, cols, edge_index, layer_offsets, stats, coverage).into_py(py)
+                }
+                None => outputs.into_py(py),
+            })
+        })
+    }
+
+    #[pyfunction]
+    pub fn neighbor_sampling_homogenous_with_features(
+        col_ptrs: Tensor,
+        row_indices: Tensor,
+        inputs: Tensor,
+        num_neighbors: Vec<usize>,
+        sampler: Option<SamplerType>,
+        filter: Option<FilterType>,
+        features: Tensor,
+        multigraph: Option<bool>,
+        lonely_seed_policy: Option<String>,
+        node_mask: Option<Tensor>,
+        dedup: Option<bool>,
+    ) -> PyResult<(
+        Tensor,
+        Tensor,
+        Tensor,
+        Tensor,
+        Vec<LayerOffset>,
+        Tensor,
+    )> {
+        catch_panics(move || {
+            let (samples, rows, cols, edge_index, layer_offsets) = neighbor_sampling_homogenous_impl(
+                &col_ptrs, &row_indices, &inputs, &num_neighbors, &sampler, &filter, multigraph.unwrap_or(true),
+                &lonely_seed_policy, &node_mask, dedup.unwrap_or(false), &mut ns::NoTracer,
+            )?;
+            let node_count = ptrs_node_count(&col_ptrs)?;
+            let x = ns::gather_features(&features, try_tensor_to_slice::<i64>(&samples)?, node_count)?;
+
+            Ok((
+                samples,
+                rows,
+                cols,
+                edge_index,
+                layer_offsets,
+                x,
+            ))
+        })
+    }
+
+    fn parse_lonely_seed_policy(name: &Option<String>) -> PyResult<ns::LonelySeedPolicy> {
+        match name.as_deref() {
+            None | Some("keep") => Ok(ns::LonelySeedPolicy::Keep),
+            Some("self_loop") => Ok(ns::LonelySeedPolicy::SelfLoop),
+            Some("error") => Ok(ns::LonelySeedPolicy::Error),
+            Some(name) => Err(PyValueError::new_err(format!(
+                "lonely_seed_policy must be one of keep, self_loop or error, got {}", name
+            ))),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn neighbor_sampling_homogenous_impl<T: ns::SamplingTracer>(
+        col_ptrs: &Tensor,
+        row_indices: &Tensor,
+        inputs: &Tensor,
+        num_neighbors: &[usize],
+        sampler: &Option<SamplerType>,
+        filter: &Option<FilterType>,
+        multigraph: bool,
+        lonely_seed_policy: &Option<String>,
+        node_mask: &Option<Tensor>,
+        dedup: bool,
+        tracer: &mut T,
     ) -> PyResult<(
         Tensor,
         Tensor,
@@ -199,19 +583,41 @@ mod algo {
         Tensor,
         Vec<LayerOffset>
     )> {
+        let lonely_seed_policy = parse_lonely_seed_policy(lonely_seed_policy)?;
+        lonely_seed_policy.validate(false)?;
         let mut rng = random::rng_get();
 
-        let ptrs = try_tensor_to_slice::<i64>(&col_ptrs)?;
-        let indices = try_tensor_to_slice::<i64>(&row_indices)?;
+        let (col_ptrs, row_indices) = (prepare_index_tensor(col_ptrs)?, prepare_index_tensor(row_indices)?);
+        let (ptrs, indices) = graph_slices(&col_ptrs, &row_indices)?;
         let graph = CscGraph::new(ptrs, indices);
+        graph.validate()?;
 
+        let inputs = prepare_index_tensor(inputs)?;
         let inputs_data = try_tensor_to_slice::<i64>(&inputs)?;
+        check_nodes("inputs", inputs_data, graph.node_count())?;
+        let node_mask = node_mask.as_ref().map(|mask| prepare_node_mask(mask, Some(graph.node_count()))).transpose()?;
+        let node_mask_data = node_mask.as_ref().map(try_tensor_to_slice::<bool>).transpose()?;
 
-        let (samples, edge_index, layer_offsets) = match_mixed_return! {
+        if let Some(SamplerType::Uniform(uniform)) = sampler.as_ref() {
+            uniform.validate(num_neighbors.len())?;
+        }
+        let source = attr_source(&graph, sampler.as_ref(), filter.as_ref())?;
+        let (samples, mut edge_index, mut layer_offsets) = match_mixed_return! {
             match (sampler.as_ref()) {
-                Some(SamplerType::Uniform(UniformSampler { with_replacement: true })) => ns::UnweightedSampler::<true>,
-                Some(SamplerType::Uniform(UniformSampler { with_replacement: false })) => ns::UnweightedSampler::<false>,
-                Some(SamplerType::Weighted(s@WeightedSampler { .. })) => s.build_homogenous::<f64>()?,
+                Some(SamplerType::Uniform(UniformSampler { with_replacement: ReplaceFlags::Fixed(true), max_repeats: Some(max_repeats) })) => {
+                    ns::ReplacementSampler::new(Some(*max_repeats))
+                },
+                Some(SamplerType::Uniform(UniformSampler { with_replacement: ReplaceFlags::Fixed(true), max_repeats: None })) => ns::UnweightedSampler::<true>,
+                Some(SamplerType::Uniform(UniformSampler { with_replacement: ReplaceFlags::Fixed(false), .. })) => ns::UnweightedSampler::<false>,
+                Some(SamplerType::Uniform(UniformSampler { with_replacement: flags @ ReplaceFlags::PerHop(_), max_repeats: Some(max_repeats) })) => {
+                    let policy = flags.to_policy(num_neighbors.len())?;
+                    ns::PerHopSampler::new(policy, num_neighbors.len(), ns::UnweightedSampler::<false>, ns::ReplacementSampler::new(Some(*max_repeats)))?
+                },
+                Some(SamplerType::Uniform(UniformSampler { with_replacement: flags @ ReplaceFlags::PerHop(_), max_repeats: None })) => {
+                    ns::PerHopSampler::uniform(flags.to_policy(num_neighbors.len())?, num_neighbors.len())?
+                },
+                Some(SamplerType::Weighted(WeightedSampler { with_replacement: false, .. })) => ns::WeightedSampler::new(&source),
+                Some(SamplerType::Weighted(WeightedSampler { with_replacement: true, .. })) => ns::WeightedReplacementSampler::new(&source)?,
                 _ => ns::UnweightedSampler::<false>,
             } ==> |sampler| {
                 match_mixed_return! {
@@ -219,47 +625,59 @@ mod algo {
                         Some(FilterType::TemporalFilter((ft @ TemporalFilter {
                             mode: ns::TEMPORAL_SAMPLE_STATIC, ..
                         }, inputs_state))) => (
-                            ft.build_homogenous::<true, {ns::TEMPORAL_SAMPLE_STATIC}>()?,
+                            ft.build_homogenous::<_, true, {ns::TEMPORAL_SAMPLE_STATIC}>(&source),
                             inputs_state.build_homogenous::<i64>()?,
                         ),
                         Some(FilterType::TemporalFilter((ft @ TemporalFilter {
                             forward: true, mode: ns::TEMPORAL_SAMPLE_RELATIVE, ..
                         }, inputs_state))) => (
-                            ft.build_homogenous::<true, {ns::TEMPORAL_SAMPLE_RELATIVE}>()?,
+                            ft.build_homogenous::<_, true, {ns::TEMPORAL_SAMPLE_RELATIVE}>(&source),
                             inputs_state.build_homogenous::<i64>()?,
                         ),
                         Some(FilterType::TemporalFilter((ft @ TemporalFilter {
                             forward: false, mode: ns::TEMPORAL_SAMPLE_RELATIVE, ..
                         }, inputs_state))) => (
-                            ft.build_homogenous::<false, {ns::TEMPORAL_SAMPLE_RELATIVE}>()?,
+                            ft.build_homogenous::<_, false, {ns::TEMPORAL_SAMPLE_RELATIVE}>(&source),
                             inputs_state.build_homogenous::<i64>()?,
                         ),
                         Some(FilterType::TemporalFilter((ft @ TemporalFilter {
                             forward: true, mode: ns::TEMPORAL_SAMPLE_DYNAMIC, ..
                         }, inputs_state))) => (
-                            ft.build_homogenous::<true, {ns::TEMPORAL_SAMPLE_DYNAMIC}>()?,
+                            ft.build_homogenous::<_, true, {ns::TEMPORAL_SAMPLE_DYNAMIC}>(&source),
                             inputs_state.build_homogenous::<i64>()?,
                         ),
                         Some(FilterType::TemporalFilter((ft @ TemporalFilter {
                             forward: false, mode: ns::TEMPORAL_SAMPLE_DYNAMIC, ..
                         }, inputs_state))) => (
-                            ft.build_homogenous::<false, {ns::TEMPORAL_SAMPLE_DYNAMIC}>()?,
+                            ft.build_homogenous::<_, false, {ns::TEMPORAL_SAMPLE_DYNAMIC}>(&source),
                             inputs_state.build_homogenous::<i64>()?,
                         ),
                         _ => (ns::IdentityFilter, &vec![(); inputs_data.len()][..]),
                     } ==> |(filter, inputs_state)| {
-                        Ok(crate::algo::neighbor_sampling::neighbor_sampling_homogenous(
-                            &mut rng, &graph, inputs_data, &num_neighbors, &sampler, &filter, inputs_state,
-                        )) as TensorResult<(Vec<NodeIdx>, CooGraphBuilder, Vec<ns::LayerOffset>)>
+                        let filter = ns::NodeMaskFilter::new(&graph, filter, node_mask_data);
+                        let filter = ns::ParallelEdgeFilter::new(&graph, filter, multigraph);
+                        Ok(if dedup {
+                            ns::neighbor_sampling_homogenous_dedup_traced(
+                                &mut rng, &graph, inputs_data, num_neighbors, &sampler, &filter, inputs_state, tracer,
+                            )
+                        } else {
+                            ns::neighbor_sampling_homogenous_traced(
+                                &mut rng, &graph, inputs_data, num_neighbors, &sampler, &filter, inputs_state, tracer,
+                            )
+                        }) as TensorResult<(Vec<NodeIdx>, CooGraphBuilder, Vec<ns::LayerOffset>)>
                     }
                 }
             }
         }?;
+        let num_lonely = ns::apply_lonely_seed_policy(
+            lonely_seed_policy, inputs_data, &mut edge_index, &mut layer_offsets, false,
+        )?;
+        tracer.record_lonely_seeds(num_lonely);
 
-        let samples = samples.try_into().expect("Can't convert vec into tensor");
-        let rows = edge_index.rows.try_into().expect("Can't convert vec into tensor");
-        let cols = edge_index.cols.try_into().expect("Can't convert vec into tensor");
-        let edge_index = edge_index.edge_index.try_into().expect("Can't convert vec into tensor");
+        let samples = Tensor::of_slice(&samples);
+        let rows = Tensor::of_slice(&edge_index.rows);
+        let cols = Tensor::of_slice(&edge_index.cols);
+        let edge_index = Tensor::of_slice(&edge_index.edge_index);
 
         Ok((
             samples,
@@ -270,6 +688,32 @@ mod algo {
         ))
     }
 
+    // One (src, dst, num_src_nodes, num_dst_nodes, src NID, dst NID, EID) tuple per hop for dgl.create_block, see
+    // `ns::dgl_blocks`. With the csc perm the EIDs are coo edge ids, self loops of lonely seeds keep -1.
+    #[allow(clippy::type_complexity)]
+    #[pyfunction]
+    pub fn to_dgl_blocks(
+        samples: Tensor,
+        rows: Tensor,
+        cols: Tensor,
+        edge_index: Tensor,
+        layer_offsets: Vec<LayerOffset>,
+        perm: Option<Tensor>,
+    ) -> PyResult<Vec<(Tensor, Tensor, i64, i64, Tensor, Tensor, Tensor)>> {
+        catch_panics(move || {
+            let e_id = match perm {
+                Some(perm) => {
+                    let missing = edge_index.lt(0);
+                    perm.index_select(0, &edge_index.clamp_min(0).to_device(perm.device())).masked_fill(&missing, -1)
+                }
+                None => edge_index,
+            };
+            Ok(ns::dgl_blocks(&samples, &rows, &cols, &e_id, &layer_offsets).into_iter()
+                .map(|b| (b.src, b.dst, b.num_src_nodes, b.num_dst_nodes, b.src_nid, b.dst_nid, b.eid))
+                .collect())
+        })
+    }
+
     #[allow(clippy::too_many_arguments)]
     #[pyfunction]
     pub fn neighbor_sampling_heterogenous(
@@ -278,10 +722,193 @@ mod algo {
         col_ptrs: HashMap<RelType, Tensor>,
         row_indices: HashMap<RelType, Tensor>,
         inputs: HashMap<NodeType, Tensor>,
-        num_neighbors: HashMap<RelType, Vec<usize>>,
+        num_neighbors: HashMap<RelType, Vec<i64>>,
+        num_hops: usize,
+        sampler: Option<SamplerType>,
+        filter: Option<FilterType>,
+        multigraph: Option<bool>,
+        lonely_seed_policy: Option<String>,
+        node_mask: Option<HashMap<NodeType, Tensor>>,
+    ) -> PyResult<(
+        HashMap<NodeType, Tensor>,
+        HashMap<RelType, Tensor>,
+        HashMap<RelType, Tensor>,
+        HashMap<RelType, Tensor>,
+        HashMap<RelType, Vec<LayerOffset>>,
+    )> {
+        catch_panics(move || {
+            neighbor_sampling_heterogenous_impl(
+                &node_types, &edge_types, &col_ptrs, &row_indices, &inputs, &num_neighbors, num_hops,
+                &sampler, &filter, multigraph.unwrap_or(true), &lonely_seed_policy, &node_mask, &mut ns::NoTracer,
+            )
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[pyfunction]
+    pub fn neighbor_sampling_heterogenous_traced(
+        py: Python,
+        node_types: Vec<NodeType>,
+        edge_types: Vec<EdgeType>,
+        col_ptrs: HashMap<RelType, Tensor>,
+        row_indices: HashMap<RelType, Tensor>,
+        inputs: HashMap<NodeType, Tensor>,
+        num_neighbors: HashMap<RelType, Vec<i64>>,
+        num_hops: usize,
+        sampler: Option<SamplerType>,
+        filter: Option<FilterType>,
+        multigraph: Option<bool>,
+        lonely_seed_policy: Option<String>,
+        coverage: Option<bool>,
+        node_mask: Option<HashMap<NodeType, Tensor>>,
+    ) -> PyResult<PyObject> {
+        catch_panics(move || {
+            let mut stats = if coverage.unwrap_or(false) {
+                ns::SamplerStats::with_coverage()
+            } else {
+                ns::SamplerStats::new()
+            };
+            let (samples, rows, cols, edge_indexes, layer_offsets) = neighbor_sampling_heterogenous_impl(
+                &node_types, &edge_types, &col_ptrs, &row_indices, &inputs, &num_neighbors, num_hops,
+                &sampler, &filter, multigraph.unwrap_or(true), &lonely_seed_policy, &node_mask, &mut stats,
+            )?;
+
+            // Like the homogenous variant the coverage is only appended when asked for
+            let outputs = (samples, rows, cols, edge_indexes, layer_offsets, stats.to_hetero_dict());
+            Ok(match stats.coverage.as_ref() {
+                Some(coverage) => {
+                    let (samples, rows, cols, edge_indexes, layer_offsets, stats) = outputs;
+                    let coverage: HashMap<RelType, (Tensor, Tensor, Tensor)> = coverage.iter()
+                        .filter_map(|(rel_type, coverage)| rel_type.clone().map(|rel_type| (rel_type, coverage.to_tensors())))
+                        .collect();
+                    (samples, rows, cols, edge_indexes, layer_offsets, stats, coverage).into_py(py)
+                }
+                None => outputs.into_py(py),
+            })
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[pyfunction]
+    pub fn neighbor_sampling_heterogenous_with_features(
+        node_types: Vec<NodeType>,
+        edge_types: Vec<EdgeType>,
+        col_ptrs: HashMap<RelType, Tensor>,
+        row_indices: HashMap<RelType, Tensor>,
+        inputs: HashMap<NodeType, Tensor>,
+        num_neighbors: HashMap<RelType, Vec<i64>>,
+        num_hops: usize,
+        sampler: Option<SamplerType>,
+        filter: Option<FilterType>,
+        features: HashMap<NodeType, Tensor>,
+        multigraph: Option<bool>,
+        lonely_seed_policy: Option<String>,
+        node_mask: Option<HashMap<NodeType, Tensor>>,
+    ) -> PyResult<(
+        HashMap<NodeType, Tensor>,
+        HashMap<RelType, Tensor>,
+        HashMap<RelType, Tensor>,
+        HashMap<RelType, Tensor>,
+        HashMap<RelType, Vec<LayerOffset>>,
+        HashMap<NodeType, Tensor>,
+    )> {
+        catch_panics(move || {
+            let (samples, rows, cols, edge_indexes, layer_offsets) = neighbor_sampling_heterogenous_impl(
+                &node_types, &edge_types, &col_ptrs, &row_indices, &inputs, &num_neighbors, num_hops,
+                &sampler, &filter, multigraph.unwrap_or(true), &lonely_seed_policy, &node_mask, &mut ns::NoTracer,
+            )?;
+
+            // Only destination node types have a known node count in csc format
+            let mut node_counts = HashMap::new();
+            for (src_node_type, rel_type, dst_node_type) in edge_types.iter() {
+                let rel_type = format!("{}__{}__{}", src_node_type, rel_type, dst_node_type);
+                if let Some(ptrs) = col_ptrs.get(&rel_type) {
+                    node_counts.insert(dst_node_type.clone(), ptrs_node_count(ptrs)?);
+                }
+            }
+            let samples_data: HashMap<NodeType, Vec<NodeIdx>> = samples.iter().map(|(node_type, tensor)| {
+                let data = try_tensor_to_slice::<i64>(tensor)?;
+                Ok((node_type.clone(), data.to_vec()))
+            }).collect::<PyResult<_>>()?;
+            let x = ns::gather_features_heterogenous(&features, &samples_data, &node_counts)?;
+
+            Ok((
+                samples,
+                rows,
+                cols,
+                edge_indexes,
+                layer_offsets,
+                x,
+            ))
+        })
+    }
+
+    impl ns::HeteroSamplerOutput {
+        // Keys follow the arguments of torch_geometric.loader.utils.filter_hetero_data, relations are keyed by
+        // their (src, rel, dst) triplet
+        pub fn to_py_dict(self, py: Python) -> PyResult<PyObject> {
+            let dict = PyDict::new(py);
+            dict.set_item("node_dict", self.node.into_py(py))?;
+            dict.set_item("row_dict", self.row.into_py(py))?;
+            dict.set_item("col_dict", self.col.into_py(py))?;
+            dict.set_item("edge_dict", self.edge.into_py(py))?;
+            dict.set_item("batch_dict", self.batch.into_py(py))?;
+            dict.set_item("hop_dict", self.hop.into_py(py))?;
+            dict.set_item("batch_size_dict", self.num_seeds.into_py(py))?;
+            Ok(dict.into_py(py))
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[pyfunction]
+    pub fn neighbor_sampling_heterogenous_hetero_data(
+        py: Python,
+        node_types: Vec<NodeType>,
+        edge_types: Vec<EdgeType>,
+        col_ptrs: HashMap<RelType, Tensor>,
+        row_indices: HashMap<RelType, Tensor>,
+        inputs: HashMap<NodeType, Tensor>,
+        num_neighbors: HashMap<RelType, Vec<i64>>,
         num_hops: usize,
         sampler: Option<SamplerType>,
         filter: Option<FilterType>,
+        multigraph: Option<bool>,
+        lonely_seed_policy: Option<String>,
+        node_mask: Option<HashMap<NodeType, Tensor>>,
+    ) -> PyResult<PyObject> {
+        catch_panics(move || {
+            let (samples, coo_builders, layer_offsets) = neighbor_sampling_heterogenous_builders(
+                &node_types, &edge_types, &col_ptrs, &row_indices, &inputs, &num_neighbors, num_hops,
+                &sampler, &filter, multigraph.unwrap_or(true), &lonely_seed_policy, &node_mask, &mut ns::NoTracer,
+            )?;
+
+            let inputs = prepare_index_tensors(&inputs)?;
+            let inputs_data: HashMap<NodeType, &[NodeIdx]> = inputs.iter().map(|(node_type, tensor)| {
+                let data = try_tensor_to_slice::<i64>(tensor)?;
+                Ok((node_type.clone(), data))
+            }).collect::<PyResult<_>>()?;
+            let output = ns::HeteroSamplerOutput::new(
+                &edge_types, &inputs_data, &samples, &coo_builders, &layer_offsets,
+            );
+            output.to_py_dict(py)
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn neighbor_sampling_heterogenous_impl<T: ns::SamplingTracer>(
+        node_types: &[NodeType],
+        edge_types: &[EdgeType],
+        col_ptrs: &HashMap<RelType, Tensor>,
+        row_indices: &HashMap<RelType, Tensor>,
+        inputs: &HashMap<NodeType, Tensor>,
+        num_neighbors: &HashMap<RelType, Vec<i64>>,
+        num_hops: usize,
+        sampler: &Option<SamplerType>,
+        filter: &Option<FilterType>,
+        multigraph: bool,
+        lonely_seed_policy: &Option<String>,
+        node_mask: &Option<HashMap<NodeType, Tensor>>,
+        tracer: &mut T,
     ) -> PyResult<(
         HashMap<NodeType, Tensor>,
         HashMap<RelType, Tensor>,
@@ -289,32 +916,135 @@ mod algo {
         HashMap<RelType, Tensor>,
         HashMap<RelType, Vec<LayerOffset>>,
     )> {
+        let (samples, coo_builders, layer_offsets) = neighbor_sampling_heterogenous_builders(
+            node_types, edge_types, col_ptrs, row_indices, inputs, num_neighbors, num_hops, sampler, filter, multigraph,
+            lonely_seed_policy, node_mask, tracer,
+        )?;
+
+        let samples: HashMap<NodeType, Tensor> = samples.into_iter().map(|(ty, samples)| {
+            (ty, Tensor::of_slice(&samples))
+        }).collect();
+        let mut rows = HashMap::new();
+        let mut cols = HashMap::new();
+        let mut edge_indexes = HashMap::new();
+        for (rel_type, coo_builder) in coo_builders.into_iter() {
+            let (row, col, edge_index) = coo_builder.to_tensor();
+            rows.insert(rel_type.clone(), row);
+            cols.insert(rel_type.clone(), col);
+            edge_indexes.insert(rel_type.clone(), edge_index);
+        }
+
+        Ok((
+            samples,
+            rows,
+            cols,
+            edge_indexes,
+            layer_offsets,
+        ))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn neighbor_sampling_heterogenous_builders<T: ns::SamplingTracer>(
+        node_types: &[NodeType],
+        edge_types: &[EdgeType],
+        col_ptrs: &HashMap<RelType, Tensor>,
+        row_indices: &HashMap<RelType, Tensor>,
+        inputs: &HashMap<NodeType, Tensor>,
+        num_neighbors: &HashMap<RelType, Vec<i64>>,
+        num_hops: usize,
+        sampler: &Option<SamplerType>,
+        filter: &Option<FilterType>,
+        multigraph: bool,
+        lonely_seed_policy: &Option<String>,
+        node_mask: &Option<HashMap<NodeType, Tensor>>,
+        tracer: &mut T,
+    ) -> PyResult<(
+        HashMap<NodeType, Vec<NodeIdx>>,
+        HashMap<RelType, CooGraphBuilder>,
+        HashMap<RelType, Vec<LayerOffset>>,
+    )> {
+        let lonely_seed_policy = parse_lonely_seed_policy(lonely_seed_policy)?;
         let mut rng = random::rng_get();
 
+        ns::validate_hetero_fanouts(edge_types, num_neighbors, num_hops)?;
+        if let Some(rel_type) = num_neighbors.keys().find(|rel_type| !col_ptrs.contains_key(*rel_type)) {
+            return Err(PyValueError::new_err(format!("fanout given for relation {} without a graph", rel_type)));
+        }
+        let num_neighbors: HashMap<RelType, Vec<ns::FanoutPolicy>> = num_neighbors.iter()
+            .map(|(rel_type, fanouts)| (rel_type.clone(), fanouts.iter().map(|k| ns::FanoutPolicy::from_count(*k)).collect()))
+            .collect();
+        let (col_ptrs, row_indices) = (prepare_index_tensors(col_ptrs)?, prepare_index_tensors(row_indices)?);
+        let inputs = prepare_index_tensors(inputs)?;
         let rel_types = col_ptrs.keys().cloned().collect::<Vec<_>>();
         let mut graphs = HashMap::new();
         for rel_type in rel_types.iter().cloned() {
-            let ptrs = try_tensor_to_slice::<i64>(&col_ptrs[&rel_type])?;
-            let indices = try_tensor_to_slice::<i64>(&row_indices[&rel_type])?;
+            let (ptrs, indices) = relation_slices(&col_ptrs, &row_indices, &rel_type)?;
             graphs.insert(rel_type, CscGraph::new(ptrs, indices));
         }
+        validate_relations(edge_types, |rel_type| graphs.get(rel_type))?;
 
         let inputs_data: HashMap<NodeType, &[i64]> = inputs.iter().map(|(node_type, tensor)| {
             let data = try_tensor_to_slice::<i64>(tensor)?;
             Ok((node_type.clone(), data))
         }).collect::<PyResult<_>>()?;
+        if num_hops > 0 {
+            lonely_seed_policy.validate_heterogenous(edge_types, &inputs_data, |rel_type| num_neighbors.contains_key(rel_type))?;
+        }
+
+        // The neighbors of a relation are of its src node type, so they are masked by the mask of that type. The node
+        // count of a type is known once it is the dst of some relation, other masks are only checked by the filter,
+        // which masks every node past their end.
+        let mut node_counts: HashMap<&NodeType, usize> = HashMap::new();
+        for (src_node_type, rel_type, dst_node_type) in edge_types.iter() {
+            if let Some(graph) = graphs.get(&format!("{}__{}__{}", src_node_type, rel_type, dst_node_type)) {
+                node_counts.insert(dst_node_type, graph.node_count());
+            }
+        }
+        let node_masks: HashMap<NodeType, Tensor> = node_mask.iter().flatten()
+            .map(|(node_type, mask)| Ok((node_type.clone(), prepare_node_mask(mask, node_counts.get(node_type).cloned())?)))
+            .collect::<PyResult<_>>()?;
+        let mut rel_masks: HashMap<RelType, &[bool]> = HashMap::new();
+        for (src_node_type, rel_type, dst_node_type) in edge_types.iter() {
+            if let Some(mask) = node_masks.get(src_node_type) {
+                rel_masks.insert(format!("{}__{}__{}", src_node_type, rel_type, dst_node_type), try_tensor_to_slice::<bool>(mask)?);
+            }
+        }
 
+        if let Some(SamplerType::Uniform(uniform)) = sampler.as_ref() {
+            uniform.validate(num_hops)?;
+        }
+        let sources = attr_sources(&graphs, sampler.as_ref(), filter.as_ref())?;
         let mut tmp = HashMap::new();
-        let (samples, coo_builders, layer_offsets) = match_mixed_return! {
+        let (samples, mut coo_builders, mut layer_offsets) = match_mixed_return! {
             match (sampler.as_ref()) {
-                Some(SamplerType::Uniform(UniformSampler { with_replacement: true })) => {
+                Some(SamplerType::Uniform(UniformSampler { with_replacement: ReplaceFlags::Fixed(true), max_repeats: Some(max_repeats) })) => {
+                    hashmap_from(rel_types.iter(), |_k| ns::ReplacementSampler::new(Some(*max_repeats)))
+                },
+                Some(SamplerType::Uniform(UniformSampler { with_replacement: ReplaceFlags::Fixed(true), max_repeats: None })) => {
                     hashmap_from(rel_types.iter(), |_k| ns::UnweightedSampler::<true>)
                 },
-                Some(SamplerType::Uniform(UniformSampler { with_replacement: false })) => {
+                Some(SamplerType::Uniform(UniformSampler { with_replacement: ReplaceFlags::Fixed(false), .. })) => {
                     hashmap_from(rel_types.iter(), |_k| ns::UnweightedSampler::<false>)
                 },
-                Some(SamplerType::Weighted(s@WeightedSampler { .. })) => {
-                    s.build_heterogenous::<f64>()?
+                Some(SamplerType::Uniform(UniformSampler { with_replacement: flags @ ReplaceFlags::PerHop(_), max_repeats: Some(max_repeats) })) => {
+                    let policy = flags.to_policy(num_hops)?;
+                    rel_types.iter()
+                        .map(|k| Ok((k.clone(), ns::PerHopSampler::new(
+                            policy.clone(), num_hops, ns::UnweightedSampler::<false>, ns::ReplacementSampler::new(Some(*max_repeats)),
+                        )?)))
+                        .collect::<TensorResult<HashMap<_, _>>>()?
+                },
+                Some(SamplerType::Uniform(UniformSampler { with_replacement: flags @ ReplaceFlags::PerHop(_), max_repeats: None })) => {
+                    let policy = flags.to_policy(num_hops)?;
+                    rel_types.iter()
+                        .map(|k| Ok((k.clone(), ns::PerHopSampler::uniform(policy.clone(), num_hops)?)))
+                        .collect::<TensorResult<HashMap<_, _>>>()?
+                },
+                Some(SamplerType::Weighted(s@WeightedSampler { with_replacement: false, .. })) => {
+                    s.build_heterogenous(&sources)?
+                },
+                Some(SamplerType::Weighted(s@WeightedSampler { with_replacement: true, .. })) => {
+                    s.build_heterogenous_replacement(&sources)?
                 },
                 _ => {
                     hashmap_from(rel_types.iter(), |_k| ns::UnweightedSampler::<false>)
@@ -325,31 +1055,31 @@ mod algo {
                         Some(FilterType::TemporalFilter((ft @ TemporalFilter {
                             mode: ns::TEMPORAL_SAMPLE_STATIC, ..
                         }, inputs_state))) => (
-                            ft.build_heterogenous::<true, {ns::TEMPORAL_SAMPLE_STATIC}>()?,
+                            ft.build_heterogenous::<_, true, {ns::TEMPORAL_SAMPLE_STATIC}>(&sources)?,
                             inputs_state.build_heterogenous::<i64>()?,
                         ),
                         Some(FilterType::TemporalFilter((ft @ TemporalFilter {
                             forward: true, mode: ns::TEMPORAL_SAMPLE_RELATIVE, ..
                         }, inputs_state))) => (
-                            ft.build_heterogenous::<true, {ns::TEMPORAL_SAMPLE_RELATIVE}>()?,
+                            ft.build_heterogenous::<_, true, {ns::TEMPORAL_SAMPLE_RELATIVE}>(&sources)?,
                             inputs_state.build_heterogenous::<i64>()?,
                         ),
                         Some(FilterType::TemporalFilter((ft @ TemporalFilter {
                             forward: false, mode: ns::TEMPORAL_SAMPLE_RELATIVE, ..
                         }, inputs_state))) => (
-                            ft.build_heterogenous::<false, {ns::TEMPORAL_SAMPLE_RELATIVE}>()?,
+                            ft.build_heterogenous::<_, false, {ns::TEMPORAL_SAMPLE_RELATIVE}>(&sources)?,
                             inputs_state.build_heterogenous::<i64>()?,
                         ),
                         Some(FilterType::TemporalFilter((ft @ TemporalFilter {
                             forward: true, mode: ns::TEMPORAL_SAMPLE_DYNAMIC, ..
                         }, inputs_state))) => (
-                            ft.build_heterogenous::<true, {ns::TEMPORAL_SAMPLE_DYNAMIC}>()?,
+                            ft.build_heterogenous::<_, true, {ns::TEMPORAL_SAMPLE_DYNAMIC}>(&sources)?,
                             inputs_state.build_heterogenous::<i64>()?,
                         ),
                         Some(FilterType::TemporalFilter((ft @ TemporalFilter {
                             forward: false, mode: ns::TEMPORAL_SAMPLE_DYNAMIC, ..
                         }, inputs_state))) => (
-                            ft.build_heterogenous::<false, {ns::TEMPORAL_SAMPLE_DYNAMIC}>()?,
+                            ft.build_heterogenous::<_, false, {ns::TEMPORAL_SAMPLE_DYNAMIC}>(&sources)?,
                             inputs_state.build_heterogenous::<i64>()?,
                         ),
                         _ => {
@@ -360,8 +1090,9 @@ mod algo {
                             )
                         },
                     } ==> |(filter, inputs_state)| {
-                        Ok(crate::algo::neighbor_sampling::neighbor_sampling_heterogenous(
-                            &mut rng, &node_types, &edge_types, &graphs, &inputs_data, &num_neighbors, num_hops, &sampler, &filter, &inputs_state,
+                        let filter = parallel_edge_filters(&graphs, filter, multigraph, &rel_masks);
+                        Ok(crate::algo::neighbor_sampling::neighbor_sampling_heterogenous_traced(
+                            &mut rng, node_types, edge_types, &graphs, &inputs_data, &num_neighbors, num_hops, &sampler, &filter, &inputs_state, tracer,
                         )) as TensorResult<(
                             HashMap<NodeType, Vec<NodeIdx>>,
                             HashMap<RelType, CooGraphBuilder>,
@@ -371,27 +1102,12 @@ mod algo {
                 }
             }
         }?;
+        let num_lonely = ns::apply_lonely_seed_policy_heterogenous(
+            lonely_seed_policy, edge_types, &inputs_data, &mut coo_builders, &mut layer_offsets,
+        )?;
+        tracer.record_lonely_seeds(num_lonely);
 
-        let samples: HashMap<NodeType, Tensor> = samples.into_iter().map(|(ty, samples)| {
-            (ty, samples.try_into().expect("Can't convert vec into tensor"))
-        }).collect();
-        let mut rows = HashMap::new();
-        let mut cols = HashMap::new();
-        let mut edge_indexes = HashMap::new();
-        for (rel_type, coo_builder) in coo_builders.into_iter() {
-            let (row, col, edge_index) = coo_builder.to_tensor();
-            rows.insert(rel_type.clone(), row);
-            cols.insert(rel_type.clone(), col);
-            edge_indexes.insert(rel_type.clone(), edge_index);
-        }
-
-        Ok((
-            samples,
-            rows,
-            cols,
-            edge_indexes,
-            layer_offsets,
-        ))
+        Ok((samples, coo_builders, layer_offsets))
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -414,71 +1130,75 @@ mod algo {
         HashMap<RelType, Tensor>,
         HashMap<RelType, Tensor>,
     )> {
-        let mut rng = random::rng_get();
-
-        let rel_types = col_ptrs.keys().cloned().collect::<Vec<_>>();
-        let mut graphs = HashMap::new();
-        for rel_type in rel_types.iter().cloned() {
-            let ptrs = try_tensor_to_slice::<i64>(&col_ptrs[&rel_type])?;
-            let indices = try_tensor_to_slice::<i64>(&row_indices[&rel_type])?;
-            let timestamps = if let Some(ts) = &row_timestamps {
-                if let Some(timestamps) = ts.get(&rel_type) {
-                    Some(EdgeAttr::new(try_tensor_to_slice::<Timestamp>(timestamps)?))
+        catch_panics(move || {
+            let mut rng = random::rng_get();
+
+            let (col_ptrs, row_indices) = (prepare_index_tensors(&col_ptrs)?, prepare_index_tensors(&row_indices)?);
+            let inputs = prepare_index_tensors(&inputs)?;
+            let rel_types = col_ptrs.keys().cloned().collect::<Vec<_>>();
+            let mut graphs = HashMap::new();
+            for rel_type in rel_types.iter().cloned() {
+                let (ptrs, indices) = relation_slices(&col_ptrs, &row_indices, &rel_type)?;
+                let timestamps = if let Some(ts) = &row_timestamps {
+                    if let Some(timestamps) = ts.get(&rel_type) {
+                        Some(EdgeAttr::new(try_tensor_to_slice::<Timestamp>(timestamps)?))
+                    } else {
+                        None
+                    }
                 } else {
                     None
-                }
+                };
+                graphs.insert(rel_type, (CscGraph::new(ptrs, indices), timestamps));
+            }
+            validate_relations(&edge_types, |rel_type| graphs.get(rel_type).map(|(graph, _)| graph))?;
+
+            let inputs_data: HashMap<NodeType, &[i64]> = inputs.iter().map(|(node_type, tensor)| {
+                let data = try_tensor_to_slice::<i64>(tensor)?;
+                Ok((node_type.clone(), data))
+            }).collect::<PyResult<_>>()?;
+
+            let input_timestamps_data: Option<HashMap<NodeType, &[Timestamp]>> = if let Some(input_timestamps) = input_timestamps.as_ref() {
+                Some( input_timestamps.iter().map(|(node_type, tensor)| {
+                    let data = try_tensor_to_slice::<Timestamp>(tensor)?;
+                    Ok((node_type.clone(), data))
+                }).collect::<PyResult<_>>()?)
             } else {
                 None
             };
-            graphs.insert(rel_type, (CscGraph::new(ptrs, indices), timestamps));
-        }
 
-        let inputs_data: HashMap<NodeType, &[i64]> = inputs.iter().map(|(node_type, tensor)| {
-            let data = try_tensor_to_slice::<i64>(tensor)?;
-            Ok((node_type.clone(), data))
-        }).collect::<PyResult<_>>()?;
-
-        let input_timestamps_data: Option<HashMap<NodeType, &[Timestamp]>> = if let Some(input_timestamps) = input_timestamps.as_ref() {
-            Some( input_timestamps.iter().map(|(node_type, tensor)| {
-                let data = try_tensor_to_slice::<Timestamp>(tensor)?;
-                Ok((node_type.clone(), data))
-            }).collect::<PyResult<_>>()?)
-        } else {
-            None
-        };
+            let timerange = timerange.map(|(start, end)| start..end);
 
-        let timerange = timerange.map(|(start, end)| start..end);
 
+            let (samples, samples_timestamps, coo_builders) = crate::algo::hgt_sampling::hgt_sampling(
+                &mut rng, &node_types, &edge_types, &graphs, &inputs_data, input_timestamps_data.as_ref(), &num_samples, num_hops, &timerange
+            );
 
-        let (samples, samples_timestamps, coo_builders) = crate::algo::hgt_sampling::hgt_sampling(
-            &mut rng, &node_types, &edge_types, &graphs, &inputs_data, input_timestamps_data.as_ref(), &num_samples, num_hops, &timerange
-        );
+            let samples: HashMap<NodeType, Tensor> = samples.into_iter().map(|(ty, samples)| {
+                (ty, Tensor::of_slice(&samples))
+            }).collect();
+            let samples_timestamps: HashMap<NodeType, Tensor> = samples_timestamps.into_iter().map(|(ty, samples_timestamps)| {
+                (ty, Tensor::of_slice(&samples_timestamps))
+            }).collect();
 
-        let samples: HashMap<NodeType, Tensor> = samples.into_iter().map(|(ty, samples)| {
-            (ty, samples.try_into().expect("Can't convert vec into tensor"))
-        }).collect();
-        let samples_timestamps: HashMap<NodeType, Tensor> = samples_timestamps.into_iter().map(|(ty, samples_timestamps)| {
-            (ty, samples_timestamps.try_into().expect("Can't convert vec into tensor"))
-        }).collect();
-
-        let mut rows = HashMap::new();
-        let mut cols = HashMap::new();
-        let mut edge_indexes = HashMap::new();
-        for (rel_type, coo_builder) in coo_builders.into_iter() {
-            let (row, col, edge_index) = coo_builder.to_tensor();
-            rows.insert(rel_type.clone(), row);
-            cols.insert(rel_type.clone(), col);
-            edge_indexes.insert(rel_type.clone(), edge_index);
-        }
+            let mut rows = HashMap::new();
+            let mut cols = HashMap::new();
+            let mut edge_indexes = HashMap::new();
+            for (rel_type, coo_builder) in coo_builders.into_iter() {
+                let (row, col, edge_index) = coo_builder.to_tensor();
+                rows.insert(rel_type.clone(), row);
+                cols.insert(rel_type.clone(), col);
+                edge_indexes.insert(rel_type.clone(), edge_index);
+            }
 
-        Ok((
-            samples,
-            samples_timestamps,
-            rows,
-            cols,
-            edge_indexes,
-        ))
+            Ok((
+                samples,
+                samples_timestamps,
+                rows,
+                cols,
+                edge_indexes,
+            ))
 
+        })
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -503,187 +1223,320 @@ mod algo {
         HashMap<RelType, Tensor>,
         HashMap<RelType, Tensor>,
     )> {
-        let mut rng = random::rng_get();
-
-        let rel_types = col_ptrs.keys().cloned().collect::<Vec<_>>();
-        let mut graphs = HashMap::new();
-        for rel_type in rel_types.iter().cloned() {
-            let ptrs = try_tensor_to_slice::<i64>(&col_ptrs[&rel_type])?;
-            let indices = try_tensor_to_slice::<i64>(&row_indices[&rel_type])?;
-            let timestamps = if let Some(ts) = &row_timestamps {
-                if let Some(timestamps) = ts.get(&rel_type) {
-                    Some(EdgeAttr::new(try_tensor_to_slice::<Timestamp>(timestamps)?))
+        catch_panics(move || {
+            let mut rng = random::rng_get();
+
+            let (col_ptrs, row_indices) = (prepare_index_tensors(&col_ptrs)?, prepare_index_tensors(&row_indices)?);
+            let inputs = prepare_index_tensors(&inputs)?;
+            let rel_types = col_ptrs.keys().cloned().collect::<Vec<_>>();
+            let mut graphs = HashMap::new();
+            for rel_type in rel_types.iter().cloned() {
+                let (ptrs, indices) = relation_slices(&col_ptrs, &row_indices, &rel_type)?;
+                let timestamps = if let Some(ts) = &row_timestamps {
+                    if let Some(timestamps) = ts.get(&rel_type) {
+                        Some(EdgeAttr::new(try_tensor_to_slice::<Timestamp>(timestamps)?))
+                    } else {
+                        None
+                    }
                 } else {
                     None
-                }
-            } else {
-                None
-            };
-            graphs.insert(rel_type, (CscGraph::new(ptrs, indices), timestamps));
-        }
-
-        let inputs_data: HashMap<NodeType, &[i64]> = inputs.iter().map(|(node_type, tensor)| {
-            let data = try_tensor_to_slice::<i64>(tensor)?;
-            Ok((node_type.clone(), data))
-        }).collect::<PyResult<_>>()?;
+                };
+                graphs.insert(rel_type, (CscGraph::new(ptrs, indices), timestamps));
+            }
+            validate_relations(&edge_types, |rel_type| graphs.get(rel_type).map(|(graph, _)| graph))?;
 
-        let input_timestamps_data: Option<HashMap<NodeType, &[Timestamp]>> = if let Some(input_timestamps) = input_timestamps.as_ref() {
-            Some( input_timestamps.iter().map(|(node_type, tensor)| {
-                let data = try_tensor_to_slice::<Timestamp>(tensor)?;
+            let inputs_data: HashMap<NodeType, &[i64]> = inputs.iter().map(|(node_type, tensor)| {
+                let data = try_tensor_to_slice::<i64>(tensor)?;
                 Ok((node_type.clone(), data))
-            }).collect::<PyResult<_>>()?)
-        } else {
-            None
-        };
-
-        let filter = if let Some((start, end)) = window {
-            Some(crate::algo::budget_sampling::TemporalFilter {
-                window: start..end,
-                forward,
-                relative,
-            })
-        } else {
-            None
-        };
+            }).collect::<PyResult<_>>()?;
 
-        let (
-            samples, samples_timestamps, coo_builders, layer_offsets
-        ) = crate::algo::budget_sampling::budget_neighbor_sampling_heterogenous(
-            &mut rng, &node_types, &edge_types, &graphs,
-            &inputs_data, input_timestamps_data.as_ref(),
-            &num_neighbors, num_hops, &filter
-        );
+            let input_timestamps_data: Option<HashMap<NodeType, &[Timestamp]>> = if let Some(input_timestamps) = input_timestamps.as_ref() {
+                Some( input_timestamps.iter().map(|(node_type, tensor)| {
+                    let data = try_tensor_to_slice::<Timestamp>(tensor)?;
+                    Ok((node_type.clone(), data))
+                }).collect::<PyResult<_>>()?)
+            } else {
+                None
+            };
 
-        let samples: HashMap<NodeType, Tensor> = samples.into_iter().map(|(ty, samples)| {
-            (ty, samples.try_into().expect("Can't convert vec into tensor"))
-        }).collect();
-        let samples_timestamps: HashMap<NodeType, Tensor> = samples_timestamps.into_iter().map(|(ty, samples_timestamps)| {
-            (ty, samples_timestamps.try_into().expect("Can't convert vec into tensor"))
-        }).collect();
+            let filter = if let Some((start, end)) = window {
+                Some(crate::algo::budget_sampling::TemporalFilter {
+                    window: start..end,
+                    forward,
+                    relative,
+                })
+            } else {
+                None
+            };
 
-        let mut rows = HashMap::new();
-        let mut cols = HashMap::new();
-        let mut edge_indexes = HashMap::new();
-        for (rel_type, coo_builder) in coo_builders.into_iter() {
-            let (row, col, edge_index) = coo_builder.to_tensor();
-            rows.insert(rel_type.clone(), row);
-            cols.insert(rel_type.clone(), col);
-            edge_indexes.insert(rel_type.clone(), edge_index);
-        }
+            let (
+                samples, samples_timestamps, coo_builders, layer_offsets
+            ) = crate::algo::budget_sampling::budget_neighbor_sampling_heterogenous(
+                &mut rng, &node_types, &edge_types, &graphs,
+                &inputs_data, input_timestamps_data.as_ref(),
+                &num_neighbors, num_hops, &filter
+            );
+
+            let samples: HashMap<NodeType, Tensor> = samples.into_iter().map(|(ty, samples)| {
+                (ty, Tensor::of_slice(&samples))
+            }).collect();
+            let samples_timestamps: HashMap<NodeType, Tensor> = samples_timestamps.into_iter().map(|(ty, samples_timestamps)| {
+                (ty, Tensor::of_slice(&samples_timestamps))
+            }).collect();
+
+            let mut rows = HashMap::new();
+            let mut cols = HashMap::new();
+            let mut edge_indexes = HashMap::new();
+            for (rel_type, coo_builder) in coo_builders.into_iter() {
+                let (row, col, edge_index) = coo_builder.to_tensor();
+                rows.insert(rel_type.clone(), row);
+                cols.insert(rel_type.clone(), col);
+                edge_indexes.insert(rel_type.clone(), edge_index);
+            }
 
-        Ok((
-            samples,
-            samples_timestamps,
-            rows,
-            cols,
-            edge_indexes,
-        ))
+            Ok((
+                samples,
+                samples_timestamps,
+                rows,
+                cols,
+                edge_indexes,
+            ))
+        })
     }
 
     #[pyfunction]
     pub fn random_walk(
+        py: Python,
         row_ptrs: Tensor,
         col_indices: Tensor,
-        start: Tensor,
+        start: StartNodes,
         walk_length: i64,
         p: f32,
         q: f32,
     ) -> PyResult<Tensor> {
-        let mut rng = random::rng_get();
+        catch_panics(move || {
+            let mut rng = random::rng_get();
+
+            let (row_ptrs, col_indices) = (prepare_index_tensor(&row_ptrs)?, prepare_index_tensor(&col_indices)?);
+            let (ptrs, indices) = graph_slices(&row_ptrs, &col_indices)?;
+            let graph = CsrGraph::new(ptrs, indices);
+            graph.validate()?;
+            let start = start.to_tensor(py)?;
+
+            let walks = py.allow_threads(|| crate::algo::random_walk::random_walk(
+                &mut rng,
+                &graph,
+                &start,
+                walk_length,
+                p,
+                q,
+            ))?;
+
+            Ok(walks)
+        })
+    }
 
-        let ptrs = try_tensor_to_slice::<i64>(&row_ptrs)?;
-        let indices = try_tensor_to_slice::<i64>(&col_indices)?;
-        let graph = CsrGraph::new(ptrs, indices);
-
-        let walks = crate::algo::random_walk::random_walk(
-            &mut rng,
-            &graph,
-            &start,
-            walk_length,
-            p,
-            q,
-        )?;
+    // Without a seed the walks draw from the global rng, see `set_global_seed`
+    #[pyfunction]
+    pub fn uniform_random_walk(
+        py: Python,
+        row_ptrs: Tensor,
+        col_indices: Tensor,
+        start: StartNodes,
+        walk_length: i64,
+        seed: Option<u64>,
+        return_edges: Option<bool>,
+        perm: Option<Tensor>,
+    ) -> PyResult<(Tensor, Option<Tensor>)> {
+        catch_panics(move || {
+            let seed = seed.unwrap_or_else(|| random::rng_get().gen());
+
+            let (row_ptrs, col_indices) = (prepare_index_tensor(&row_ptrs)?, prepare_index_tensor(&col_indices)?);
+            let (ptrs, indices) = graph_slices(&row_ptrs, &col_indices)?;
+            let graph = CsrGraph::new(ptrs, indices);
+            graph.validate()?;
+            let start = start.to_tensor(py)?;
+            let perm = perm.as_ref().map(prepare_index_tensor).transpose()?;
+            let perm_data = perm.as_ref().map(try_tensor_to_slice::<i64>).transpose()?;
+
+            let (walks, edges) = py.allow_threads(|| crate::algo::random_walk::uniform_random_walk(
+                &graph,
+                &start,
+                walk_length,
+                seed,
+                perm_data,
+            ))?;
+
+            Ok((walks, if return_edges.unwrap_or(false) { Some(edges) } else { None }))
+        })
+    }
 
-        Ok(walks)
+    #[pyfunction]
+    pub fn metapath_random_walk(
+        py: Python,
+        row_ptrs: Tensor,
+        col_indices: Tensor,
+        edge_types: Tensor,
+        num_types: i64,
+        start: StartNodes,
+        walk_length: i64,
+        allowed: Tensor,
+        initial_type: Option<i64>,
+        seed: Option<u64>,
+    ) -> PyResult<(Tensor, Tensor)> {
+        catch_panics(move || {
+            let seed = seed.unwrap_or_else(|| random::rng_get().gen());
+
+            let graph = CsrGraphStorage::new(prepare_index_tensor(&row_ptrs)?, prepare_index_tensor(&col_indices)?, None);
+            let graph = TypedGraphStorage::new(&graph, &edge_types, num_types)?;
+            let start = start.to_tensor(py)?;
+
+            let (walks, types) = py.allow_threads(|| crate::algo::random_walk::typed_random_walk(
+                &graph,
+                &start,
+                walk_length,
+                &allowed,
+                initial_type,
+                seed,
+            ))?;
+
+            Ok((walks, types))
+        })
+    }
+
+    #[pyfunction]
+    pub fn random_spanning_forest(
+        row_ptrs: Tensor,
+        col_indices: Tensor,
+        roots: Option<Tensor>,
+    ) -> PyResult<(Tensor, Tensor)> {
+        catch_panics(move || {
+            let mut rng = random::rng_get();
+
+            let (row_ptrs, col_indices) = (prepare_index_tensor(&row_ptrs)?, prepare_index_tensor(&col_indices)?);
+            let (ptrs, indices) = graph_slices(&row_ptrs, &col_indices)?;
+            let graph = CsrGraph::new(ptrs, indices);
+            graph.validate()?;
+
+            let (forest, parent) = crate::algo::spanning_tree::random_spanning_forest(
+                &mut rng,
+                &graph,
+                roots.as_ref().map(prepare_index_tensor).transpose()?.as_ref(),
+            )?;
+
+            Ok((forest.row_col, parent))
+        })
+    }
+
+    #[pyfunction]
+    pub fn drop_edge_spanning_safe(
+        row_ptrs: Tensor,
+        col_indices: Tensor,
+        p: f64,
+        seed: Option<u64>,
+    ) -> PyResult<Tensor> {
+        catch_panics(move || {
+            let seed = seed.unwrap_or_else(|| random::rng_get().gen());
+
+            let (row_ptrs, col_indices) = (prepare_index_tensor(&row_ptrs)?, prepare_index_tensor(&col_indices)?);
+            let (ptrs, indices) = graph_slices(&row_ptrs, &col_indices)?;
+            let graph = CsrGraph::new(ptrs, indices);
+            graph.validate()?;
+
+            let kept = crate::algo::spanning_tree::drop_edge_spanning_safe(&graph, p, seed)?;
+            Ok(kept.row_col)
+        })
     }
 
     #[pyfunction]
     pub fn tempo_random_walk(
+        py: Python,
         row_ptrs: Tensor,
         col_indices: Tensor,
         node_timestamps: Tensor,
         edge_timestamps: Tensor,
-        start: Tensor,
+        start: StartNodes,
         start_timestamps: Tensor,
         walk_length: i64,
         window: (i64, i64)
     ) -> PyResult<(Tensor, Tensor)> {
-        let mut rng = random::rng_get();
-
-        let ptrs = try_tensor_to_slice::<i64>(&row_ptrs)?;
-        let indices = try_tensor_to_slice::<i64>(&col_indices)?;
-        let graph = CsrGraph::new(ptrs, indices);
-
-        let node_timestamps_data = try_tensor_to_slice::<i64>(&node_timestamps)?;
-        let edge_timestamps_data = try_tensor_to_slice::<i64>(&edge_timestamps)?;
-
-        let (walks, walk_timestamps) = crate::algo::random_walk::tempo_random_walk(
-            &mut rng,
-            &graph,
-            &node_timestamps_data,
-            &EdgeAttr::new(&edge_timestamps_data),
-            &start,
-            &start_timestamps,
-            walk_length,
-            window,
-        )?;
-
-        Ok((walks, walk_timestamps))
+        catch_panics(move || {
+            let mut rng = random::rng_get();
+
+            let (row_ptrs, col_indices) = (prepare_index_tensor(&row_ptrs)?, prepare_index_tensor(&col_indices)?);
+            let (ptrs, indices) = graph_slices(&row_ptrs, &col_indices)?;
+            let graph = CsrGraph::new(ptrs, indices);
+            graph.validate()?;
+
+            let start = start.to_tensor(py)?;
+            check_nodes("start", try_tensor_to_slice::<i64>(&start)?, graph.node_count())?;
+            let node_timestamps_data = try_tensor_to_slice::<i64>(&node_timestamps)?;
+            let edge_timestamps_data = try_tensor_to_slice::<i64>(&edge_timestamps)?;
+
+            let (walks, walk_timestamps) = py.allow_threads(|| crate::algo::random_walk::tempo_random_walk(
+                &mut rng,
+                &graph,
+                &node_timestamps_data,
+                &EdgeAttr::new(&edge_timestamps_data),
+                &start,
+                &start_timestamps,
+                walk_length,
+                window,
+            ))?;
+
+            Ok((walks, walk_timestamps))
+        })
     }
 
     #[pyfunction]
     pub fn biased_tempo_random_walk(
+        py: Python,
         row_ptrs: Tensor,
         col_indices: Tensor,
         node_timestamps: Tensor,
         edge_timestamps: Tensor,
-        start: Tensor,
+        start: StartNodes,
         start_timestamps: Tensor,
         walk_length: i64,
         bias_type: String,
         forward: bool,
         retry_count: i64,
     ) -> PyResult<(Tensor, Tensor)> {
-        let mut rng = random::rng_get();
-
-        let ptrs = try_tensor_to_slice::<i64>(&row_ptrs)?;
-        let indices = try_tensor_to_slice::<i64>(&col_indices)?;
-        let graph = CsrGraph::new(ptrs, indices);
-
-        let node_timestamps_data = try_tensor_to_slice::<i64>(&node_timestamps)?;
-        let edge_timestamps_data = try_tensor_to_slice::<i64>(&edge_timestamps)?;
-
-        let bias_type = match bias_type.as_str() {
-            "uniform" => BiasType::Uniform,
-            "linear" => BiasType::Linear,
-            "exponential" => BiasType::Exponential,
-            _ => return Err(PyValueError::new_err(format!("Unknown bias type: {}", bias_type))),
-        };
+        catch_panics(move || {
+            let mut rng = random::rng_get();
+
+            let (row_ptrs, col_indices) = (prepare_index_tensor(&row_ptrs)?, prepare_index_tensor(&col_indices)?);
+            let (ptrs, indices) = graph_slices(&row_ptrs, &col_indices)?;
+            let graph = CsrGraph::new(ptrs, indices);
+            graph.validate()?;
+
+            let start = start.to_tensor(py)?;
+            check_nodes("start", try_tensor_to_slice::<i64>(&start)?, graph.node_count())?;
+            let node_timestamps_data = try_tensor_to_slice::<i64>(&node_timestamps)?;
+            let edge_timestamps_data = try_tensor_to_slice::<i64>(&edge_timestamps)?;
+
+            let bias_type = match bias_type.as_str() {
+                "uniform" => BiasType::Uniform,
+                "linear" => BiasType::Linear,
+                "exponential" => BiasType::Exponential,
+                _ => return Err(PyValueError::new_err(format!("Unknown bias type: {}", bias_type))),
+            };
 
-        let (walks, walk_timestamps) = crate::algo::random_walk::biased_tempo_random_walk(
-            &mut rng,
-            &graph,
-            &node_timestamps_data,
-            &EdgeAttr::new(&edge_timestamps_data),
-            &start,
-            &start_timestamps,
-            walk_length,
-            bias_type,
-            forward,
-            retry_count,
-        )?;
+            let (walks, walk_timestamps) = py.allow_threads(|| crate::algo::random_walk::biased_tempo_random_walk(
+                &mut rng,
+                &graph,
+                &node_timestamps_data,
+                &EdgeAttr::new(&edge_timestamps_data),
+                &start,
+                &start_timestamps,
+                walk_length,
+                bias_type,
+                forward,
+                retry_count,
+            ))?;
 
-        Ok((walks, walk_timestamps))
+            Ok((walks, walk_timestamps))
+        })
     }
 
     #[pyfunction]
@@ -695,28 +1548,38 @@ mod algo {
         num_neg: i64,
         try_count: i64,
     ) -> PyResult<(Tensor, Tensor, Tensor, usize)> {
-        let mut rng = random::rng_get();
-
-        let ptrs = try_tensor_to_slice::<i64>(&row_ptrs)?;
-        let indices = try_tensor_to_slice::<i64>(&col_indices)?;
-        let graph = CsrGraph::new(ptrs, indices);
-
-        let inputs = try_tensor_to_slice::<i64>(&inputs)?;
-
-        let (samples, edge_index, sample_count) = crate::algo::negative_sampling::negative_sample_neighbors_homogenous(
-            &mut rng,
-            &graph,
-            graph_size,
-            inputs,
-            num_neg,
-            try_count,
-        );
-
-        let samples = samples.try_into().expect("Can't convert vec into tensor");
-        let rows = edge_index.rows.try_into().expect("Can't convert vec into tensor");
-        let cols = edge_index.cols.try_into().expect("Can't convert vec into tensor");
+        catch_panics(move || {
+            let mut rng = random::rng_get();
+
+            let (row_ptrs, col_indices) = (prepare_index_tensor(&row_ptrs)?, prepare_index_tensor(&col_indices)?);
+            let node_count = graph_slices(&row_ptrs, &col_indices)?.0.len() - 1;
+            // The neighbors are binary searched, the sampler checks that they are sorted
+            let graph_data = CsrGraphStorage::new(row_ptrs, col_indices, None);
+
+            let inputs = prepare_index_tensor(&inputs)?;
+            let inputs = try_tensor_to_slice::<i64>(&inputs)?;
+            check_nodes("inputs", inputs, node_count)?;
+            if num_neg < 0 || try_count < 0 {
+                return Err(PyValueError::new_err(format!(
+                    "num_neg and try_count must be non negative, got {} and {}", num_neg, try_count
+                )));
+            }
 
-        Ok((samples, rows, cols, sample_count))
+            let (samples, edge_index, sample_count) = crate::algo::negative_sampling::negative_sample_neighbors_homogenous(
+                &mut rng,
+                &graph_data,
+                graph_size,
+                inputs,
+                num_neg,
+                try_count,
+            )?;
+
+            let samples = Tensor::of_slice(&samples);
+            let rows = Tensor::of_slice(&edge_index.rows);
+            let cols = Tensor::of_slice(&edge_index.cols);
+
+            Ok((samples, rows, cols, sample_count))
+        })
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -737,61 +1600,349 @@ mod algo {
         HashMap<RelType, Tensor>,
         HashMap<NodeType, usize>,
     )> {
-        let mut rng = random::rng_get();
+        catch_panics(move || {
+            let mut rng = random::rng_get();
+
+            let (row_ptrs, col_indices) = (prepare_index_tensors(&row_ptrs)?, prepare_index_tensors(&col_indices)?);
+            let inputs = prepare_index_tensors(&inputs)?;
+            let mut graphs = HashMap::new();
+            for rel_type in row_ptrs.keys().cloned() {
+                let (ptrs, indices) = relation_slices(&row_ptrs, &col_indices, &rel_type)?;
+                let size = sizes[&rel_type];
+                let graph = CsrGraph::new(ptrs, indices);
+                // The neighbors are binary searched
+                if graph.detect_properties().is_sorted != Some(true) {
+                    return Err(PyValueError::new_err(format!("the neighbors of {} must be sorted by id", rel_type)));
+                }
+                graphs.insert(rel_type, (graph, size));
+            }
 
-        let mut graphs = HashMap::new();
-        for rel_type in row_ptrs.keys().cloned() {
-            let ptrs = try_tensor_to_slice::<i64>(&row_ptrs[&rel_type])?;
-            let indices = try_tensor_to_slice::<i64>(&col_indices[&rel_type])?;
-            let size = sizes[&rel_type];
-            graphs.insert(rel_type, (CsrGraph::new(ptrs, indices), size));
-        }
+            let inputs_data: HashMap<NodeType, &[i64]> = inputs.iter().map(|(node_type, tensor)| {
+                let data = try_tensor_to_slice::<i64>(tensor)?;
+                Ok((node_type.clone(), data))
+            }).collect::<PyResult<_>>()?;
+
+            let (samples, edge_index, sample_count) = crate::algo::negative_sampling::negative_sample_neighbors_heterogenous(
+                &mut rng,
+                &node_types,
+                &edge_types,
+                &graphs,
+                &inputs_data,
+                num_neg,
+                try_count,
+                inbound,
+            );
+
+            let samples: HashMap<NodeType, Tensor> = samples.into_iter().map(|(ty, samples)| {
+                (ty, Tensor::of_slice(&samples))
+            }).collect();
+            let mut rows = HashMap::new();
+            let mut cols = HashMap::new();
+            for (rel_type, coo_builder) in edge_index.into_iter() {
+                let (row, col, _edge_index) = coo_builder.to_tensor();
+                rows.insert(rel_type.clone(), row);
+                cols.insert(rel_type.clone(), col);
+            }
 
-        let inputs_data: HashMap<NodeType, &[i64]> = inputs.iter().map(|(node_type, tensor)| {
-            let data = try_tensor_to_slice::<i64>(tensor)?;
-            Ok((node_type.clone(), data))
-        }).collect::<PyResult<_>>()?;
+            Ok((
+                samples,
+                rows,
+                cols,
+                sample_count,
+            ))
+        })
+    }
 
-        let (samples, edge_index, sample_count) = crate::algo::negative_sampling::negative_sample_neighbors_heterogenous(
-            &mut rng,
-            &node_types,
-            &edge_types,
-            &graphs,
-            &inputs_data,
-            num_neg,
-            try_count,
-            inbound,
-        );
+    #[pyfunction]
+    pub fn degree_histogram(
+        col_ptrs: Tensor,
+        row_indices: Tensor,
+        max_bins: Option<i64>,
+    ) -> PyResult<Tensor> {
+        catch_panics(move || {
+            let (col_ptrs, row_indices) = (prepare_index_tensor(&col_ptrs)?, prepare_index_tensor(&row_indices)?);
+            let (ptrs, indices) = graph_slices(&col_ptrs, &row_indices)?;
+            let graph = CscGraph::new(ptrs, indices);
 
-        let samples: HashMap<NodeType, Tensor> = samples.into_iter().map(|(ty, samples)| {
-            (ty, samples.try_into().expect("Can't convert vec into tensor"))
-        }).collect();
-        let mut rows = HashMap::new();
-        let mut cols = HashMap::new();
-        for (rel_type, coo_builder) in edge_index.into_iter() {
-            let (row, col, _edge_index) = coo_builder.to_tensor();
-            rows.insert(rel_type.clone(), row);
-            cols.insert(rel_type.clone(), col);
+            Ok(crate::algo::degree::degree_histogram(&graph, max_bins)?)
+        })
+    }
+
+    #[pyfunction]
+    pub fn degree_assortativity(
+        col_ptrs: Tensor,
+        row_indices: Tensor,
+    ) -> PyResult<Option<f64>> {
+        catch_panics(move || {
+            let (col_ptrs, row_indices) = (prepare_index_tensor(&col_ptrs)?, prepare_index_tensor(&row_indices)?);
+            let (ptrs, indices) = graph_slices(&col_ptrs, &row_indices)?;
+            let graph = CscGraph::new(ptrs, indices);
+            graph.validate()?;
+
+            Ok(crate::algo::degree::degree_assortativity(&graph))
+        })
+    }
+
+    #[pyfunction]
+    pub fn label_homophily(
+        col_ptrs: Tensor,
+        row_indices: Tensor,
+        labels: Tensor,
+        kind: String,
+    ) -> PyResult<Option<f64>> {
+        catch_panics(move || {
+            let (col_ptrs, row_indices) = (prepare_index_tensor(&col_ptrs)?, prepare_index_tensor(&row_indices)?);
+            let (ptrs, indices) = graph_slices(&col_ptrs, &row_indices)?;
+            let graph = CscGraph::new(ptrs, indices);
+            graph.validate()?;
+
+            let kind = match kind.as_str() {
+                "edge" => HomophilyKind::Edge,
+                "node" => HomophilyKind::Node,
+                "class_insensitive" => HomophilyKind::ClassInsensitive,
+                _ => return Err(PyValueError::new_err(format!(
+                    "kind must be one of edge, node or class_insensitive, got {}", kind
+                ))),
+            };
+            Ok(crate::algo::degree::label_homophily(&graph, &labels, kind)?)
+        })
+    }
+
+    #[pyfunction]
+    pub fn bucket_seeds_by_degree(
+        col_ptrs: Tensor,
+        row_indices: Tensor,
+        seeds: Tensor,
+        num_buckets: usize,
+        shuffle_within: Option<bool>,
+        seed: Option<u64>,
+        strategy: Option<String>,
+    ) -> PyResult<(Tensor, Tensor, Tensor)> {
+        catch_panics(move || {
+            let seed = seed.unwrap_or_else(|| random::rng_get().gen());
+            let (col_ptrs, row_indices) = (prepare_index_tensor(&col_ptrs)?, prepare_index_tensor(&row_indices)?);
+            let (ptrs, indices) = graph_slices(&col_ptrs, &row_indices)?;
+            let graph = CscGraph::new(ptrs, indices);
+            graph.validate()?;
+
+            let strategy = match strategy.as_deref() {
+                None | Some("homogeneous") => BatchingStrategy::Homogeneous,
+                Some("balanced") => BatchingStrategy::Balanced,
+                Some(strategy) => return Err(PyValueError::new_err(format!(
+                    "strategy must be one of homogeneous or balanced, got {}", strategy
+                ))),
+            };
+            let buckets = crate::algo::degree::bucket_seeds_by_degree(
+                &graph, &seeds, num_buckets, shuffle_within.unwrap_or(false), seed, strategy,
+            )?;
+            Ok((buckets.seeds, buckets.buckets, buckets.bucket_ptrs))
+        })
+    }
+
+    #[pyfunction]
+    pub fn ego_networks(
+        col_ptrs: Tensor,
+        row_indices: Tensor,
+        anchors: Tensor,
+        num_hops: usize,
+        max_nodes: Option<usize>,
+    ) -> PyResult<Vec<(Tensor, Tensor, i64, Tensor)>> {
+        catch_panics(move || {
+            let (col_ptrs, row_indices) = (prepare_index_tensor(&col_ptrs)?, prepare_index_tensor(&row_indices)?);
+            let (ptrs, indices) = graph_slices(&col_ptrs, &row_indices)?;
+            let graph = CscGraph::new(ptrs, indices);
+            graph.validate()?;
+
+            let networks = crate::algo::khop::ego_networks(&graph, &anchors, num_hops, max_nodes)?;
+            Ok(networks.into_iter()
+                .map(|network| (network.graph.row_col, network.nodes, network.anchor, network.edge_mask))
+                .collect())
+        })
+    }
+
+    #[pyfunction]
+    pub fn seal_subgraphs(
+        py: Python,
+        col_ptrs: Tensor,
+        row_indices: Tensor,
+        pairs: Tensor,
+        num_hops: usize,
+        max_nodes_per_pair: Option<usize>,
+    ) -> PyResult<(Tensor, Tensor, Tensor, Tensor, Tensor)> {
+        catch_panics(move || {
+            let (col_ptrs, row_indices) = (prepare_index_tensor(&col_ptrs)?, prepare_index_tensor(&row_indices)?);
+            let (ptrs, indices) = graph_slices(&col_ptrs, &row_indices)?;
+            let graph = CscGraph::new(ptrs, indices);
+            graph.validate()?;
+
+            let subgraphs = py.allow_threads(|| crate::algo::khop::seal_subgraphs(
+                &graph, &pairs, num_hops, max_nodes_per_pair,
+            ))?;
+            Ok((subgraphs.nodes, subgraphs.row_col, subgraphs.labels, subgraphs.node_offsets, subgraphs.edge_offsets))
+        })
+    }
+
+    #[pyfunction]
+    pub fn graph_summary(
+        col_ptrs: Tensor,
+        row_indices: Tensor,
+    ) -> PyResult<HashMap<String, f64>> {
+        catch_panics(move || {
+            let (col_ptrs, row_indices) = (prepare_index_tensor(&col_ptrs)?, prepare_index_tensor(&row_indices)?);
+            let (ptrs, indices) = graph_slices(&col_ptrs, &row_indices)?;
+            let graph = CscGraph::new(ptrs, indices);
+            graph.validate()?;
+
+            let summary = crate::algo::degree::graph_summary(&graph);
+            let mut result = HashMap::new();
+            result.insert("node_count".to_string(), summary.node_count as f64);
+            result.insert("edge_count".to_string(), summary.edge_count as f64);
+            result.insert("min_degree".to_string(), summary.min_degree as f64);
+            result.insert("mean_degree".to_string(), summary.mean_degree);
+            result.insert("median_degree".to_string(), summary.median_degree);
+            result.insert("max_degree".to_string(), summary.max_degree as f64);
+            result.insert("isolated_nodes".to_string(), summary.isolated_nodes as f64);
+            if let Some(alpha) = summary.power_law_alpha {
+                result.insert("power_law_alpha".to_string(), alpha);
+            }
+            Ok(result)
+        })
+    }
+
+    fn parse_metric(metric: &Option<String>) -> PyResult<Metric> {
+        match metric.as_deref() {
+            None | Some("l2") => Ok(Metric::L2),
+            Some("cosine") => Ok(Metric::Cosine),
+            Some(metric) => Err(PyValueError::new_err(format!(
+                "metric must be one of l2 or cosine, got {}", metric
+            ))),
         }
+    }
 
-        Ok((
-            samples,
-            rows,
-            cols,
-            sample_count,
-        ))
+    #[pyfunction]
+    pub fn knn_graph(
+        x: Tensor,
+        k: i64,
+        loop_: bool,
+        metric: Option<String>,
+        batch: Option<Tensor>,
+    ) -> PyResult<Tensor> {
+        catch_panics(move || {
+            let metric = parse_metric(&metric)?;
+            Ok(crate::algo::spatial::knn_graph(&x, k, metric, batch.as_ref(), loop_)?.row_col)
+        })
+    }
+
+    #[pyfunction]
+    pub fn radius_graph(
+        x: Tensor,
+        r: f64,
+        max_num_neighbors: i64,
+        loop_: bool,
+        metric: Option<String>,
+        batch: Option<Tensor>,
+    ) -> PyResult<Tensor> {
+        catch_panics(move || {
+            let metric = parse_metric(&metric)?;
+            Ok(crate::algo::spatial::radius_graph(&x, r, max_num_neighbors, metric, batch.as_ref(), loop_)?.row_col)
+        })
+    }
+
+    #[pyfunction]
+    pub fn voxel_grid(
+        pos: Tensor,
+        size: Vec<f64>,
+        batch: Option<Tensor>,
+    ) -> PyResult<Tensor> {
+        catch_panics(move || {
+            Ok(crate::algo::spatial::voxel_grid(&pos, &size, batch.as_ref())?)
+        })
+    }
+
+    #[pyfunction]
+    pub fn index_of(
+        haystack: Tensor,
+        needles: Tensor,
+        assume_sorted: bool,
+    ) -> PyResult<Tensor> {
+        catch_panics(move || {
+            Ok(crate::utils::index_of(&haystack, &needles, assume_sorted)?)
+        })
+    }
+
+    #[pyfunction]
+    pub fn set_num_threads(
+        num_threads: usize,
+    ) -> PyResult<()> {
+        catch_panics(move || {
+            Ok(crate::utils::parallel::set_num_threads(num_threads)?)
+        })
+    }
+
+    #[pyfunction]
+    pub fn set_allow_device_transfer(
+        allow: bool,
+    ) -> PyResult<()> {
+        catch_panics(move || {
+            crate::utils::set_allow_device_transfer(allow);
+            Ok(())
+        })
+    }
+
+    #[pyfunction]
+    pub fn set_global_seed(
+        seed: u64,
+    ) -> PyResult<()> {
+        catch_panics(move || {
+            crate::utils::random::set_global_seed(seed);
+            Ok(())
+        })
+    }
+
+    #[pyfunction]
+    pub fn derive_stream(
+        keys: Vec<u64>,
+    ) -> PyResult<u64> {
+        catch_panics(move || {
+            Ok(crate::utils::random::derive_stream(&keys))
+        })
     }
 
     pub fn module(_py: Python, m: &PyModule) -> PyResult<()> {
         m.add_function(wrap_pyfunction!(neighbor_sampling_homogenous, m)?)?;
+        m.add_function(wrap_pyfunction!(neighbor_sampling_homogenous_traced, m)?)?;
+        m.add_function(wrap_pyfunction!(neighbor_sampling_homogenous_with_features, m)?)?;
+        m.add_function(wrap_pyfunction!(to_dgl_blocks, m)?)?;
         m.add_function(wrap_pyfunction!(neighbor_sampling_heterogenous, m)?)?;
+        m.add_function(wrap_pyfunction!(neighbor_sampling_heterogenous_traced, m)?)?;
+        m.add_function(wrap_pyfunction!(neighbor_sampling_heterogenous_with_features, m)?)?;
+        m.add_function(wrap_pyfunction!(neighbor_sampling_heterogenous_hetero_data, m)?)?;
         m.add_function(wrap_pyfunction!(hgt_sampling, m)?)?;
         m.add_function(wrap_pyfunction!(budget_sampling, m)?)?;
         m.add_function(wrap_pyfunction!(random_walk, m)?)?;
+        m.add_function(wrap_pyfunction!(uniform_random_walk, m)?)?;
+        m.add_function(wrap_pyfunction!(metapath_random_walk, m)?)?;
+        m.add_function(wrap_pyfunction!(random_spanning_forest, m)?)?;
+        m.add_function(wrap_pyfunction!(drop_edge_spanning_safe, m)?)?;
         m.add_function(wrap_pyfunction!(tempo_random_walk, m)?)?;
         m.add_function(wrap_pyfunction!(biased_tempo_random_walk, m)?)?;
         m.add_function(wrap_pyfunction!(negative_sample_neighbors_homogenous, m)?)?;
         m.add_function(wrap_pyfunction!(negative_sample_neighbors_heterogenous, m)?)?;
+        m.add_function(wrap_pyfunction!(degree_histogram, m)?)?;
+        m.add_function(wrap_pyfunction!(degree_assortativity, m)?)?;
+        m.add_function(wrap_pyfunction!(label_homophily, m)?)?;
+        m.add_function(wrap_pyfunction!(graph_summary, m)?)?;
+        m.add_function(wrap_pyfunction!(bucket_seeds_by_degree, m)?)?;
+        m.add_function(wrap_pyfunction!(ego_networks, m)?)?;
+        m.add_function(wrap_pyfunction!(seal_subgraphs, m)?)?;
+        m.add_function(wrap_pyfunction!(knn_graph, m)?)?;
+        m.add_function(wrap_pyfunction!(radius_graph, m)?)?;
+        m.add_function(wrap_pyfunction!(voxel_grid, m)?)?;
+        m.add_function(wrap_pyfunction!(index_of, m)?)?;
+        m.add_function(wrap_pyfunction!(set_num_threads, m)?)?;
+        m.add_function(wrap_pyfunction!(set_allow_device_transfer, m)?)?;
+        m.add_function(wrap_pyfunction!(set_global_seed, m)?)?;
+        m.add_function(wrap_pyfunction!(derive_stream, m)?)?;
         Ok(())
     }
 }
@@ -0,0 +1,85 @@
+use tch::{Kind, Tensor};
+use crate::data::CooGraphStorage;
+use crate::utils::tensor::{TensorResult, try_tensor_to_slice};
+
+fn sorted_edges(graph: &CooGraphStorage, weights: Option<&Tensor>) -> TensorResult<Vec<((i64, i64), f64)>> {
+    let (row, col) = (graph.row().contiguous(), graph.col().contiguous());
+    let row_data = try_tensor_to_slice::<i64>(&row)?;
+    let col_data = try_tensor_to_slice::<i64>(&col)?;
+    let weights = weights.map(|w| w.to_kind(Kind::Double).contiguous());
+    let weights_data = match weights.as_ref() {
+        Some(w) => Some(try_tensor_to_slice::<f64>(w)?),
+        None => None,
+    };
+
+    let mut edges: Vec<((i64, i64), f64)> = row_data.iter().cloned().zip(col_data.iter().cloned())
+        .enumerate()
+        .map(|(i, e)| (e, weights_data.map_or(0.0, |w| w[i])))
+        .collect();
+    edges.sort_unstable_by_key(|(e, _)| *e);
+
+    // Duplicate edges are coalesced, their weights are summed
+    let mut coalesced: Vec<((i64, i64), f64)> = Vec::with_capacity(edges.len());
+    for (e, w) in edges {
+        match coalesced.last_mut() {
+            Some((last, acc)) if *last == e => *acc += w,
+            _ => coalesced.push((e, w)),
+        }
+    }
+
+    Ok(coalesced)
+}
+
+// Compares two graphs as edge sets, ignoring the edge order and duplicate edges
+pub fn graphs_equal(a: &CooGraphStorage, b: &CooGraphStorage) -> bool {
+    if a.size != b.size {
+        return false;
+    }
+    match (sorted_edges(a, None), sorted_edges(b, None)) {
+        (Ok(a_edges), Ok(b_edges)) => a_edges.len() == b_edges.len()
+            && a_edges.iter().zip(b_edges.iter()).all(|(x, y)| x.0 == y.0),
+        _ => false,
+    }
+}
+
+// Like `graphs_equal`, additionally the (summed) weights of every edge have to match up to a small tolerance
+pub fn graphs_equal_weighted(a: &CooGraphStorage, a_weights: &Tensor, b: &CooGraphStorage, b_weights: &Tensor) -> bool {
+    if a.size != b.size || a_weights.size() != vec![a.row_col.size()[1]] || b_weights.size() != vec![b.row_col.size()[1]] {
+        return false;
+    }
+    match (sorted_edges(a, Some(a_weights)), sorted_edges(b, Some(b_weights))) {
+        (Ok(a_edges), Ok(b_edges)) => a_edges.len() == b_edges.len()
+            && a_edges.iter().zip(b_edges.iter())
+            .all(|(x, y)| x.0 == y.0 && (x.1 - y.1).abs() <= 1e-6 * x.1.abs().max(y.1.abs()).max(1.0)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tch::Tensor;
+    use crate::data::CooGraphStorage;
+    use crate::utils::compare::{graphs_equal, graphs_equal_weighted};
+
+    fn coo(rows: &[i64], cols: &[i64], size: (i64, i64)) -> CooGraphStorage {
+        CooGraphStorage::new(Tensor::stack(&[Tensor::of_slice(rows), Tensor::of_slice(cols)], 0), size)
+    }
+
+    #[test]
+    fn test_graphs_equal() {
+        let a = coo(&[0, 1, 2, 2], &[1, 2, 0, 3], (4, 4));
+        let b = coo(&[2, 2, 0, 1, 0], &[3, 0, 1, 2, 1], (4, 4));
+        assert!(graphs_equal(&a, &b));
+        assert!(graphs_equal(&a, &a));
+        assert!(!graphs_equal(&a, &coo(&[0, 1, 2, 2], &[1, 2, 0, 3], (4, 5))));
+        assert!(!graphs_equal(&a, &coo(&[0, 1, 2], &[1, 2, 0], (4, 4))));
+        assert!(!graphs_equal(&a, &coo(&[0, 1, 2, 3], &[1, 2, 0, 2], (4, 4))));
+
+        let a_weights = Tensor::of_slice(&[1.0_f32, 2.0, 3.0, 4.0]);
+        let b_weights = Tensor::of_slice(&[4.0_f32, 3.0, 0.5, 2.0, 0.5]);
+        assert!(graphs_equal_weighted(&a, &a_weights, &b, &b_weights));
+        let b_weights = Tensor::of_slice(&[4.0_f32, 3.0, 1.0, 2.0, 0.5]);
+        assert!(!graphs_equal_weighted(&a, &a_weights, &b, &b_weights));
+        assert!(!graphs_equal_weighted(&a, &a_weights, &b, &a_weights));
+    }
+}
@@ -0,0 +1,87 @@
+use tch::{Kind, Tensor};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureNorm {
+    // Every row sums to 1, rows summing to 0 become zeros instead of NaN
+    RowSum,
+    // Zero mean and unit (population) standard deviation per column
+    ZScore,
+    // Unit euclidean norm per row
+    L2,
+}
+
+// Divides `x` by `denom` (broadcast along the rows or columns), entries with a zero denominator become 0
+fn safe_div(x: &Tensor, denom: &Tensor) -> Tensor {
+    let zero = denom.eq(0.0);
+    let denom = denom.masked_fill(&zero, 1.0);
+    (x / denom).masked_fill(&zero.expand_as(x), 0.0)
+}
+
+// Normalizes node features `x` of shape [num_nodes, num_features], integer features are converted to floats.
+// `RowSum` follows PyG's `NormalizeFeatures` on non-negative features except for its `clamp(min=1)` of the row
+// sums: only rows summing to exactly 0 keep their (zero) values, rows with a sum below 1 are still scaled to sum
+// to 1. Rows (or columns) without any mass become zeros in all modes.
+pub fn normalize_features(x: &Tensor, mode: FeatureNorm) -> Tensor {
+    let x = match x.kind() {
+        Kind::Half | Kind::Float | Kind::Double | Kind::BFloat16 => x.shallow_clone(),
+        _ => x.to_kind(Kind::Float),
+    };
+
+    match mode {
+        FeatureNorm::RowSum => safe_div(&x, &x.sum_dim_intlist(&[-1], true, x.kind())),
+        FeatureNorm::ZScore => {
+            let mean = x.mean_dim(&[0], true, x.kind());
+            safe_div(&(&x - mean), &x.std_dim(&[0], false, true))
+        }
+        FeatureNorm::L2 => safe_div(&x, &(&x * &x).sum_dim_intlist(&[-1], true, x.kind()).sqrt()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tch::Tensor;
+    use crate::utils::features::{FeatureNorm, normalize_features};
+
+    fn rows(x: &Tensor) -> Vec<Vec<f64>> {
+        let n = x.size()[0];
+        (0..n).map(|i| Vec::<f64>::from(&x.select(0, i).to_kind(tch::Kind::Double))).collect()
+    }
+
+    fn assert_close(actual: &Tensor, expected: &[&[f64]]) {
+        let actual = rows(actual);
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert_eq!(a.len(), e.len());
+            assert!(a.iter().zip(e.iter()).all(|(a, e)| (a - e).abs() < 1e-6), "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_normalize_features() {
+        // Bag of words features as in the PyG planetoid datasets, the last row is empty
+        let x = Tensor::of_slice(&[1_i64, 0, 1, 1, 0, 0, 1, 0, 1, 1, 1, 1, 0, 0, 0, 0]).view([4, 4]);
+
+        // Matches PyG's x / x.sum(-1, keepdim=True).clamp(min=1) as all non empty rows sum to at least 1
+        let normalized = normalize_features(&x, FeatureNorm::RowSum);
+        assert_eq!(normalized.kind(), tch::Kind::Float);
+        assert_close(&normalized, &[
+            &[1.0 / 3.0, 0.0, 1.0 / 3.0, 1.0 / 3.0],
+            &[0.0, 0.0, 1.0, 0.0],
+            &[0.25, 0.25, 0.25, 0.25],
+            &[0.0, 0.0, 0.0, 0.0],
+        ]);
+
+        // Unlike PyG a row summing to less than 1 is scaled up, an empty row stays zero
+        let x = Tensor::of_slice(&[0.1_f64, 0.3, 0.0, 0.0]).view([2, 2]);
+        assert_close(&normalize_features(&x, FeatureNorm::RowSum), &[&[0.25, 0.75], &[0.0, 0.0]]);
+
+        let x = Tensor::of_slice(&[3.0_f64, 4.0, 1.0, 0.0, 0.0, 0.0, 5.0, 1.0]).view([4, 2]);
+        let l2 = normalize_features(&x, FeatureNorm::L2);
+        assert_close(&l2, &[&[0.6, 0.8], &[1.0, 0.0], &[0.0, 0.0], &[0.98058068, 0.19611614]]);
+
+        // The second column is constant and becomes zero
+        let x = Tensor::of_slice(&[1.0_f64, 2.0, 3.0, 2.0, 5.0, 2.0]).view([3, 2]);
+        let sd = (8.0_f64 / 3.0).sqrt();
+        assert_close(&normalize_features(&x, FeatureNorm::ZScore), &[&[-2.0 / sd, 0.0], &[0.0, 0.0], &[2.0 / sd, 0.0]]);
+    }
+}
@@ -4,9 +4,13 @@ pub mod types;
 pub mod algo;
 pub mod iter;
 pub mod random;
+pub mod compare;
+pub mod parallel;
+pub mod features;
 
 pub use tensor::*;
 pub use sampling::*;
 pub use types::*;
 pub use algo::*;
 pub use iter::*;
+pub use compare::*;
@@ -0,0 +1,78 @@
+use std::sync::{Arc, RwLock};
+use lazy_static::lazy_static;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use crate::utils::tensor::{TensorConversionError, TensorResult};
+
+type PoolSlot = RwLock<Option<Arc<ThreadPool>>>;
+
+lazy_static! {
+    static ref POOL: PoolSlot = RwLock::new(None);
+}
+
+// Limits all parallel algorithms of the crate to a dedicated pool of `n` threads. By default the global rayon
+// pool is used, which spans all cores. Meant to be called once at startup, later calls return an error.
+pub fn set_num_threads(n: usize) -> TensorResult<()> {
+    configure(&POOL, n)
+}
+
+pub fn num_threads() -> usize {
+    num_threads_in(&POOL)
+}
+
+// Runs `op` inside the configured pool, parallel iterators in `op` then only use its threads
+pub fn install<R: Send>(op: impl FnOnce() -> R + Send) -> R {
+    install_in(&POOL, op)
+}
+
+fn configure(slot: &PoolSlot, n: usize) -> TensorResult<()> {
+    if n == 0 {
+        return Err(TensorConversionError::Unknown("number of threads must be positive".to_string()));
+    }
+
+    let mut pool = slot.write().unwrap();
+    if pool.is_some() {
+        return Err(TensorConversionError::Unknown("the thread pool is already configured".to_string()));
+    }
+    let new_pool = ThreadPoolBuilder::new()
+        .num_threads(n)
+        .thread_name(|i| format!("tch-geometric-{}", i))
+        .build()
+        .map_err(|e| TensorConversionError::Unknown(e.to_string()))?;
+    *pool = Some(Arc::new(new_pool));
+    Ok(())
+}
+
+fn num_threads_in(slot: &PoolSlot) -> usize {
+    match slot.read().unwrap().as_ref() {
+        Some(pool) => pool.current_num_threads(),
+        None => rayon::current_num_threads(),
+    }
+}
+
+fn install_in<R: Send>(slot: &PoolSlot, op: impl FnOnce() -> R + Send) -> R {
+    // The lock is not held while `op` runs, so nested calls do not deadlock
+    let pool = slot.read().unwrap().clone();
+    match pool {
+        Some(pool) => pool.install(op),
+        None => op(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::RwLock;
+    use crate::utils::parallel::{configure, install_in, num_threads_in};
+
+    // Uses its own slot, configuring the crate pool would leak into all other tests of the process
+    #[test]
+    fn test_set_num_threads() {
+        let slot = RwLock::new(None);
+        assert_eq!(num_threads_in(&slot), rayon::current_num_threads());
+        assert!(configure(&slot, 0).is_err());
+        configure(&slot, 2).unwrap();
+        assert_eq!(num_threads_in(&slot), 2);
+        assert_eq!(install_in(&slot, rayon::current_num_threads), 2);
+        assert_eq!(install_in(&slot, || install_in(&slot, || 1 + 1)), 2);
+        assert!(configure(&slot, 3).is_err());
+    }
+}
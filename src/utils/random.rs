@@ -1,9 +1,9 @@
 use std::cell::RefCell;
 use std::ops::DerefMut;
-use std::sync::Mutex;
+use std::sync::{Mutex, RwLock};
 use lazy_static::lazy_static;
 use rand::rngs::SmallRng;
-use rand::{SeedableRng};
+use rand::{Rng, SeedableRng};
 
 lazy_static! {
         static ref RNG: Mutex<RefCell<SmallRng>> = {
@@ -20,4 +20,110 @@ pub fn rng_get() -> SmallRng {
     let guard = RNG.lock().unwrap();
     let mut rng = guard.borrow_mut();
     SmallRng::from_rng(rng.deref_mut()).unwrap()
-}
\ No newline at end of file
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// Hands out independent, reproducible rng streams keyed by a path of integers, e.g. (epoch, batch, chunk). A
+// stream only depends on the base seed and its keys, so work keyed by item rather than by thread or chunk gives
+// the same result for any thread count and chunking. Keys are hashed in order with splitmix64, so (1, 2) and
+// (2, 1) are different streams and `pool.derive(&[a]).stream(&[b])` is `pool.stream(&[a, b])`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RngPool {
+    seed: u64,
+}
+
+impl RngPool {
+    pub fn new(seed: u64) -> Self {
+        RngPool { seed: splitmix64(seed) }
+    }
+
+    pub fn derive(&self, keys: &[u64]) -> RngPool {
+        let seed = keys.iter().fold(self.seed, |state, key| splitmix64(state ^ splitmix64(*key)));
+        RngPool { seed }
+    }
+
+    pub fn seed_of(&self, keys: &[u64]) -> u64 {
+        self.derive(keys).seed
+    }
+
+    pub fn stream(&self, keys: &[u64]) -> SmallRng {
+        SmallRng::seed_from_u64(self.seed_of(keys))
+    }
+}
+
+lazy_static! {
+    static ref POOL: RwLock<RngPool> = RwLock::new(RngPool::new(SmallRng::from_entropy().gen()));
+}
+
+// Seeds the global pool, and with it the rng of `rng_get` so that the following calls are reproducible
+pub fn set_global_seed(seed: u64) {
+    let pool = RngPool::new(seed);
+    *POOL.write().unwrap() = pool;
+    RNG.lock().unwrap().replace(pool.stream(&[]));
+}
+
+pub fn global_pool() -> RngPool {
+    *POOL.read().unwrap()
+}
+
+// Points the rng of `rng_get` to the stream of `keys` in the global pool and returns the seed of that stream. Calls
+// after e.g. derive_stream(&[epoch, batch]) then give the same result no matter which worker runs the batch.
+pub fn derive_stream(keys: &[u64]) -> u64 {
+    let seed = global_pool().seed_of(keys);
+    RNG.lock().unwrap().replace(SmallRng::seed_from_u64(seed));
+    seed
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+    use crate::utils::random::{RngPool, derive_stream, rng_get, set_global_seed};
+
+    fn draws(pool: &RngPool, keys: &[u64]) -> Vec<u64> {
+        let mut rng = pool.stream(keys);
+        (0..8).map(|_| rng.gen()).collect()
+    }
+
+    #[test]
+    fn test_rng_pool() {
+        let pool = RngPool::new(7);
+        assert_eq!(draws(&pool, &[1, 2]), draws(&RngPool::new(7), &[1, 2]));
+        assert_eq!(draws(&pool, &[1, 2]), draws(&pool.derive(&[1]), &[2]));
+        assert_ne!(draws(&pool, &[1, 2]), draws(&pool, &[2, 1]));
+        assert_ne!(draws(&pool, &[1, 2]), draws(&RngPool::new(8), &[1, 2]));
+        assert_ne!(draws(&pool, &[0]), draws(&pool, &[]));
+
+        // Items keyed by their index give the same values however they are chunked
+        let items: Vec<u64> = (0..100_u64).map(|i| pool.stream(&[3, i]).gen()).collect();
+        for chunk_size in [1, 7, 100] {
+            let chunked: Vec<u64> = (0..100_u64).collect::<Vec<_>>()
+                .chunks(chunk_size)
+                .flat_map(|chunk| chunk.iter().map(|i| pool.derive(&[3]).stream(&[*i]).gen::<u64>()).collect::<Vec<_>>())
+                .collect();
+            assert_eq!(chunked, items);
+        }
+        // Other epochs give other values
+        let epoch: Vec<u64> = (0..100_u64).map(|i| pool.stream(&[4, i]).gen()).collect();
+        assert_ne!(epoch, items);
+    }
+
+    #[test]
+    fn test_global_seed() {
+        set_global_seed(3);
+        let a: u64 = rng_get().gen();
+        set_global_seed(3);
+        assert_eq!(rng_get().gen::<u64>(), a);
+
+        let seed = derive_stream(&[1, 5]);
+        let b: u64 = rng_get().gen();
+        assert_eq!(derive_stream(&[1, 5]), seed);
+        assert_eq!(rng_get().gen::<u64>(), b);
+        assert_ne!(derive_stream(&[2, 5]), seed);
+    }
+}
@@ -68,6 +68,68 @@ pub fn replacement_sampling<T: Copy>(
     n
 }
 
+// Like `replacement_sampling`, but every position of `src` is emitted at most `max_repeats` times. Positions that
+// reached the cap are swapped out of `available`, so fewer than `dst.len()` values are returned once all of them
+// are exhausted. `counts` and `available` are scratch buffers that are reused between calls.
+pub fn replacement_sampling_capped<T: Copy>(
+    rng: &mut impl Rng,
+    src: &[T],
+    dst: &mut [T],
+    max_repeats: usize,
+    counts: &mut Vec<usize>,
+    available: &mut Vec<usize>,
+) -> usize {
+    counts.clear();
+    counts.resize(src.len(), 0);
+    available.clear();
+    if max_repeats > 0 {
+        available.extend(0..src.len());
+    }
+
+    let mut n = 0;
+    for dst_val in dst.iter_mut() {
+        if available.is_empty() {
+            break;
+        }
+        let i = rng.gen_range(0..available.len());
+        let j = available[i];
+        *dst_val = src[j];
+        counts[j] += 1;
+        if counts[j] >= max_repeats {
+            available.swap_remove(i);
+        }
+        n += 1
+    }
+    n
+}
+
+// Weighted sampling without replacement with the Gumbel top-k trick: the `dst.len()` values with the largest
+// log-weight plus Gumbel noise. Takes log-weights so no weights have to be exponentiated. `keys` is a scratch buffer.
+pub fn gumbel_top_k_sampling<T: Copy>(
+    rng: &mut impl Rng,
+    src: &[(T, f64)],
+    dst: &mut [T],
+    keys: &mut Vec<(f64, usize)>,
+) -> usize {
+    keys.clear();
+    for (i, (_, log_w)) in src.iter().enumerate() {
+        let u: f64 = rng.gen();
+        keys.push((log_w - (-u.ln()).ln(), i));
+    }
+
+    let n = dst.len().min(src.len());
+    if n == 0 {
+        return 0;
+    }
+    if n < keys.len() {
+        keys.select_nth_unstable_by(n - 1, |a, b| b.0.total_cmp(&a.0));
+    }
+    for (dst_val, (_, i)) in dst.iter_mut().zip(keys.iter()) {
+        *dst_val = src[*i].0;
+    }
+    n
+}
+
 pub fn replacement_sampling_range<T: Copy + SampleUniform + PartialOrd>(
     rng: &mut impl Rng,
     src: &Range<T>,
@@ -80,3 +142,94 @@ pub fn replacement_sampling_range<T: Copy + SampleUniform + PartialOrd>(
     }
     n
 }
+
+// Vose's alias method, O(n) to build and O(1) per sample
+pub struct AliasTable {
+    prob: Vec<f64>,
+    alias: Vec<usize>,
+}
+
+impl AliasTable {
+    // None if there are no weights, a weight is negative or not finite, or they sum to zero
+    pub fn new(weights: &[f64]) -> Option<Self> {
+        let n = weights.len();
+        let total: f64 = weights.iter().sum();
+        if n == 0 || weights.iter().any(|w| !w.is_finite() || *w < 0.0) || total <= 0.0 {
+            return None;
+        }
+
+        let mut prob: Vec<f64> = weights.iter().map(|w| w * n as f64 / total).collect();
+        let mut alias = vec![0; n];
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) = (0..n).partition(|&i| prob[i] < 1.0);
+        // Only pops a small one when there is a large one to pair it with, the last small one left over when the large
+        // ones run out is completed below
+        while let (Some(&s), Some(&l)) = (small.last(), large.last()) {
+            small.pop();
+            alias[s] = l;
+            prob[l] -= 1.0 - prob[s];
+            if prob[l] < 1.0 {
+                large.pop();
+                small.push(l);
+            }
+        }
+        // Leftovers are only off from one by rounding errors
+        for i in small.into_iter().chain(large) {
+            prob[i] = 1.0;
+        }
+
+        Some(Self { prob, alias })
+    }
+
+    pub fn len(&self) -> usize {
+        self.prob.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prob.is_empty()
+    }
+
+    pub fn sample(&self, rng: &mut impl Rng) -> usize {
+        let i = rng.gen_range(0..self.prob.len());
+        if rng.gen::<f64>() < self.prob[i] { i } else { self.alias[i] }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::SmallRng;
+    use crate::utils::AliasTable;
+
+    #[test]
+    fn test_alias_table() {
+        assert!(AliasTable::new(&[]).is_none());
+        assert!(AliasTable::new(&[1.0, -1.0]).is_none());
+        assert!(AliasTable::new(&[0.0, 0.0]).is_none());
+        assert!(AliasTable::new(&[1.0, f64::NAN]).is_none());
+
+        // Rounding leaves the second entry as the last small one without a large one to pair with, it keeps its own
+        // mass instead of leaking it to the alias default
+        let third = 1.0 / 3.0;
+        let weights = [0.0, third, 0.05, third, 0.1, 0.3, 0.7, third];
+        let table = AliasTable::new(&weights).unwrap();
+        assert_eq!(table.prob[1], 1.0);
+        assert!((0..weights.len()).all(|i| table.prob[i] == 1.0 || weights[table.alias[i]] > 0.0));
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        for weights in [vec![0.1, 0.2, 0.3, 0.4], vec![1.0, 1.0, 1.0], vec![0.7, 0.1, 0.1, 0.1, 1e-3, 0.0], vec![1.0 / 3.0; 7]] {
+            let table = AliasTable::new(&weights).unwrap();
+            assert_eq!(table.len(), weights.len());
+            assert!(table.prob.iter().all(|p| (0.0..=1.0).contains(p)));
+
+            let runs = 200_000;
+            let mut counts = vec![0; weights.len()];
+            for _ in 0..runs {
+                counts[table.sample(&mut rng)] += 1;
+            }
+            let total: f64 = weights.iter().sum();
+            for (c, w) in counts.iter().zip(weights.iter()) {
+                assert!((*c as f64 / runs as f64 - w / total).abs() < 0.005, "{:?} for {:?}", counts, weights);
+            }
+        }
+    }
+}
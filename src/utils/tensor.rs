@@ -3,9 +3,14 @@ use pyo3::{
     PyErr,
     exceptions::PyValueError
 };
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicBool, Ordering};
+use rayon::prelude::*;
 use thiserror::Error;
 use tch::kind::Element;
 use tch::{Device, Kind, Tensor};
+use crate::utils::parallel;
 
 #[derive(Error, Debug)]
 pub enum TensorConversionError {
@@ -67,4 +72,154 @@ pub fn try_tensor_to_slice_mut<T: Element>(tensor: &mut Tensor) -> Result<&mut [
 
 pub fn tensor_to_slice_mut<T: Element>(tensor: &mut Tensor) -> &mut [T] {
     unsafe { std::slice::from_raw_parts_mut(tensor.data_ptr() as *mut T, tensor.numel()) }
+}
+static ALLOW_DEVICE_TRANSFER: AtomicBool = AtomicBool::new(true);
+
+// Whether `prepare_index_tensor` may copy tensors from other devices to the cpu. When disabled those tensors are
+// rejected instead, which catches accidental round trips through the gpu.
+pub fn set_allow_device_transfer(allow: bool) {
+    ALLOW_DEVICE_TRANSFER.store(allow, Ordering::Relaxed);
+}
+
+// Turns any integral (or integer valued float) tensor into the contiguous cpu int64 tensor the graph algorithms
+// expect. Tensors that already are one are returned as a shallow clone, everything else is copied: once for
+// the device transfer, once for the cast and once for making a strided view contiguous, at most one copy per step.
+pub fn prepare_index_tensor(tensor: &Tensor) -> TensorResult<Tensor> {
+    let tensor = if tensor.device() != Device::Cpu {
+        if !ALLOW_DEVICE_TRANSFER.load(Ordering::Relaxed) {
+            return Err(TensorConversionError::InvalidDevice(Device::Cpu));
+        }
+        tensor.to_device(Device::Cpu)
+    } else {
+        tensor.shallow_clone()
+    };
+
+    let tensor = match tensor.kind() {
+        Kind::Int64 => tensor,
+        Kind::Uint8 | Kind::Int8 | Kind::Int16 | Kind::Int => tensor.to_kind(Kind::Int64),
+        Kind::Half | Kind::BFloat16 | Kind::Float | Kind::Double => {
+            let values = tensor.to_kind(Kind::Double).contiguous();
+            // 2^63 itself does not fit, so the upper bound is exclusive
+            let bound = 2.0_f64.powi(63);
+            let invalid = try_tensor_to_slice::<f64>(&values)?.iter()
+                .find(|v| v.fract() != 0.0 || !(-bound..bound).contains(*v));
+            if let Some(v) = invalid {
+                return Err(TensorConversionError::Unknown(format!("{} is not a valid index", v)));
+            }
+            values.to_kind(Kind::Int64)
+        }
+        kind => return Err(TensorConversionError::InvalidDType(Kind::Int64, kind)),
+    };
+
+    Ok(if tensor.is_contiguous() { tensor } else { tensor.contiguous() })
+}
+
+pub fn prepare_index_tensors<K: Clone + Eq + Hash>(tensors: &HashMap<K, Tensor>) -> TensorResult<HashMap<K, Tensor>> {
+    tensors.iter()
+        .map(|(k, tensor)| Ok((k.clone(), prepare_index_tensor(tensor)?)))
+        .collect()
+}
+
+// Position of every needle in the haystack, -1 if it is missing. Sorted haystacks are binary searched, others are
+// indexed by a hash map built once. With duplicate haystack entries the first occurrence wins.
+pub fn index_of_slice(haystack: &[i64], needles: &[i64], assume_sorted: bool) -> Vec<i64> {
+    let mut positions = vec![-1; needles.len()];
+    if haystack.is_empty() {
+        return positions;
+    }
+
+    if assume_sorted {
+        parallel::install(|| positions.par_iter_mut().zip(needles.par_iter()).for_each(|(pos, v)| {
+            let i = haystack.partition_point(|u| u < v);
+            if i < haystack.len() && haystack[i] == *v {
+                *pos = i as i64;
+            }
+        }));
+    } else {
+        let mut lookup: HashMap<i64, i64> = HashMap::with_capacity(haystack.len());
+        for (i, u) in haystack.iter().enumerate() {
+            lookup.entry(*u).or_insert(i as i64);
+        }
+        parallel::install(|| positions.par_iter_mut().zip(needles.par_iter()).for_each(|(pos, v)| {
+            if let Some(i) = lookup.get(v) {
+                *pos = *i;
+            }
+        }));
+    }
+    positions
+}
+
+// `index_of_slice` for int32 or int64 tensors, returns int64 positions of the shape of `needles`
+pub fn index_of(haystack: &Tensor, needles: &Tensor, assume_sorted: bool) -> TensorResult<Tensor> {
+    for tensor in [haystack, needles] {
+        if !matches!(tensor.kind(), Kind::Int | Kind::Int64) {
+            return Err(TensorConversionError::InvalidDType(Kind::Int64, tensor.kind()));
+        }
+    }
+    let haystack_data = prepare_index_tensor(&haystack.reshape(&[-1]))?;
+    let needles_data = prepare_index_tensor(&needles.reshape(&[-1]))?;
+    let positions = index_of_slice(
+        try_tensor_to_slice::<i64>(&haystack_data)?, try_tensor_to_slice::<i64>(&needles_data)?, assume_sorted,
+    );
+    Ok(Tensor::of_slice(&positions).view(needles.size().as_slice()).to_device(needles.device()))
+}
+
+#[cfg(test)]
+mod tests {
+    use tch::Tensor;
+    use crate::utils::{index_of, prepare_index_tensor, try_tensor_to_slice};
+
+    #[test]
+    fn test_prepare_index_tensor() {
+        let t = Tensor::of_slice(&[0_i64, 1, 2, 3, 4, 5]);
+        let prepared = prepare_index_tensor(&t).unwrap();
+        assert_eq!(prepared.data_ptr(), t.data_ptr());
+
+        // Strided views are made contiguous before they are read as a slice
+        let prepared = prepare_index_tensor(&t.slice(0, 0, None, 2)).unwrap();
+        assert_eq!(try_tensor_to_slice::<i64>(&prepared).unwrap(), &[0, 2, 4]);
+
+        let prepared = prepare_index_tensor(&Tensor::of_slice(&[3_i32, 1, 2])).unwrap();
+        assert_eq!(try_tensor_to_slice::<i64>(&prepared).unwrap(), &[3, 1, 2]);
+        let prepared = prepare_index_tensor(&Tensor::of_slice(&[3.0_f64, 1.0, 2.0])).unwrap();
+        assert_eq!(try_tensor_to_slice::<i64>(&prepared).unwrap(), &[3, 1, 2]);
+
+        assert!(prepare_index_tensor(&Tensor::of_slice(&[1.5_f64])).is_err());
+        assert!(prepare_index_tensor(&Tensor::of_slice(&[f64::NAN])).is_err());
+        assert!(prepare_index_tensor(&Tensor::of_slice(&[1e19_f64])).is_err());
+        assert!(prepare_index_tensor(&Tensor::of_slice(&[true, false])).is_err());
+    }
+
+    #[test]
+    fn test_index_of() {
+        let haystack = [7_i64, 3, 9, 3, 1, 12, 7];
+        let needles = [3_i64, 8, 7, 7, 12, -1, 1, 3];
+        // The first occurrence of a duplicate wins
+        let brute_force: Vec<i64> = needles.iter()
+            .map(|v| haystack.iter().position(|u| u == v).map_or(-1, |i| i as i64))
+            .collect();
+        let positions: Vec<i64> = index_of(&Tensor::of_slice(&haystack), &Tensor::of_slice(&needles), false).unwrap().into();
+        assert_eq!(positions, brute_force);
+        assert_eq!(positions, vec![1, -1, 0, 0, 5, -1, 4, 1]);
+
+        let mut sorted = haystack.to_vec();
+        sorted.sort_unstable();
+        let brute_force: Vec<i64> = needles.iter()
+            .map(|v| sorted.iter().position(|u| u == v).map_or(-1, |i| i as i64))
+            .collect();
+        let positions: Vec<i64> = index_of(&Tensor::of_slice(&sorted), &Tensor::of_slice(&needles), true).unwrap().into();
+        assert_eq!(positions, brute_force);
+
+        // int32 inputs, the result keeps the shape of the needles
+        let haystack = Tensor::of_slice(&[5_i32, 4, 3]);
+        let positions = index_of(&haystack, &Tensor::of_slice(&[3_i32, 5, 0, 4]).view([2, 2]), false).unwrap();
+        assert_eq!(positions.size(), vec![2, 2]);
+        assert_eq!(positions.kind(), tch::Kind::Int64);
+        assert_eq!(Vec::<i64>::from(positions.view([-1])), vec![2, 0, -1, 1]);
+
+        let empty = Tensor::of_slice::<i64>(&[]);
+        assert_eq!(Vec::<i64>::from(index_of(&empty, &Tensor::of_slice(&[1_i64, 2]), true).unwrap()), vec![-1, -1]);
+        assert_eq!(index_of(&Tensor::of_slice(&[1_i64]), &empty, false).unwrap().size(), vec![0]);
+        assert!(index_of(&Tensor::of_slice(&[1.0_f64]), &empty, false).is_err());
+    }
 }
\ No newline at end of file
@@ -1,5 +1,6 @@
 from typing import Union, Tuple, List, Optional, Dict
 
+import numpy as np
 from torch import Tensor
 from torch_geometric.typing import NodeType, EdgeType
 
@@ -7,16 +8,43 @@ from tch_geometric.utils import EdgeSampler, EdgeFilter
 
 LayerOffset = (int, int, int)
 RelType = str
+# Per-hop columns: hop, num_frontier, num_nodes, num_edges, num_empty, num_truncated, elapsed_ns, and
+# num_lonely_seeds with a single entry
+SamplerStats = Dict[str, List[int]]
+# "keep", "self_loop" (seeds without sampled edges get an edge to themselves with edge index -1) or "error"
+# Heterogenous self loops go to the first sampled relation from the node type of the seed to itself
+LonelySeedPolicy = str
+# Per frontier node per hop: [2, K] (frontier position, hop) index, sampled edge counts and true degrees
+Coverage = Tuple[Tensor, Tensor, Tensor]
+# Start nodes of random walks, numpy arrays and lists are converted to int64 tensors
+StartNodes = Union[Tensor, np.ndarray, List[int]]
 
 
-def to_csc(row_col: Tensor, size: Union[int, Tuple[int, int]]) -> Tuple[Tensor, Tensor, Tensor]:
+# The perm is None when the edges are already in compressed order
+def to_csc(row_col: Tensor, size: Union[int, Tuple[int, int]]) -> Tuple[Tensor, Tensor, Optional[Tensor]]:
     ...
 
 
-def to_csr(row_col: Tensor, size: Union[int, Tuple[int, int]]) -> Tuple[Tensor, Tensor, Tensor]:
+def to_csr(row_col: Tensor, size: Union[int, Tuple[int, int]]) -> Tuple[Tensor, Tensor, Optional[Tensor]]:
     ...
 
 
+# Writes the graph to shared memory and returns the segment path, see `SharedGraph` for loading it in workers
+def share_memory(
+        ptrs: Tensor,
+        indices: Tensor,
+        perm: Optional[Tensor] = None,
+        edge_type: Optional[Tensor] = None,
+) -> str:
+    ...
+
+
+def unlink_shared(path: str) -> None:
+    ...
+
+
+# node_mask is a bool tensor, masked nodes are never sampled as neighbors
+# With dedup every node gets a single local id across all hops, the seeds first, and samples maps them to global ids
 def neighbor_sampling_homogenous(
         col_ptrs: Tensor,
         row_indices: Tensor,
@@ -24,10 +52,61 @@ def neighbor_sampling_homogenous(
         num_neighbors: List[int],
         sampler: Optional[EdgeSampler],
         filter: Optional[Tuple[EdgeFilter, Tensor]],
+        multigraph: Optional[bool] = None,
+        lonely_seed_policy: Optional[LonelySeedPolicy] = None,
+        node_mask: Optional[Tensor] = None,
+        dedup: Optional[bool] = None,
 ) -> Tuple[Tensor, Tensor, Tensor, Tensor, List[LayerOffset]]:
     ...
 
 
+def neighbor_sampling_homogenous_traced(
+        col_ptrs: Tensor,
+        row_indices: Tensor,
+        inputs: Tensor,
+        num_neighbors: List[int],
+        sampler: Optional[EdgeSampler],
+        filter: Optional[Tuple[EdgeFilter, Tensor]],
+        multigraph: Optional[bool] = None,
+        lonely_seed_policy: Optional[LonelySeedPolicy] = None,
+        coverage: Optional[bool] = None,
+        node_mask: Optional[Tensor] = None,
+        dedup: Optional[bool] = None,
+) -> Union[
+    Tuple[Tensor, Tensor, Tensor, Tensor, List[LayerOffset], SamplerStats],
+    Tuple[Tensor, Tensor, Tensor, Tensor, List[LayerOffset], SamplerStats, Coverage],
+]:
+    # The coverage is only appended when coverage is set
+    ...
+
+
+def neighbor_sampling_homogenous_with_features(
+        col_ptrs: Tensor,
+        row_indices: Tensor,
+        inputs: Tensor,
+        num_neighbors: List[int],
+        sampler: Optional[EdgeSampler],
+        filter: Optional[Tuple[EdgeFilter, Tensor]],
+        features: Tensor,
+        multigraph: Optional[bool] = None,
+        lonely_seed_policy: Optional[LonelySeedPolicy] = None,
+        node_mask: Optional[Tensor] = None,
+        dedup: Optional[bool] = None,
+) -> Tuple[Tensor, Tensor, Tensor, Tensor, List[LayerOffset], Tensor]:
+    ...
+
+
+def to_dgl_blocks(
+        samples: Tensor,
+        rows: Tensor,
+        cols: Tensor,
+        edge_index: Tensor,
+        layer_offsets: List[LayerOffset],
+        perm: Optional[Tensor] = None,
+) -> List[Tuple[Tensor, Tensor, int, int, Tensor, Tensor, Tensor]]:
+    ...
+
+
 def neighbor_sampling_heterogenous(
         node_types: List[NodeType],
         edge_types: List[EdgeType],
@@ -38,12 +117,83 @@ def neighbor_sampling_heterogenous(
         num_hops: int,
         sampler: Optional[EdgeSampler],
         filter: Optional[Tuple[EdgeFilter, Tensor]],
+        multigraph: Optional[bool] = None,
+        lonely_seed_policy: Optional[LonelySeedPolicy] = None,
+        node_mask: Optional[Dict[NodeType, Tensor]] = None,
 ) -> Tuple[
     Dict[NodeType, Tensor], Dict[RelType, Tensor], Dict[RelType, Tensor], Dict[RelType, Tensor], List[LayerOffset]
 ]:
     ...
 
 
+def neighbor_sampling_heterogenous_traced(
+        node_types: List[NodeType],
+        edge_types: List[EdgeType],
+        col_ptrs: Dict[RelType, Tensor],
+        row_indices: Dict[RelType, Tensor],
+        inputs: Dict[NodeType, Tensor],
+        num_neighbors: Dict[RelType, List[int]],
+        num_hops: int,
+        sampler: Optional[EdgeSampler],
+        filter: Optional[Tuple[EdgeFilter, Tensor]],
+        multigraph: Optional[bool] = None,
+        lonely_seed_policy: Optional[LonelySeedPolicy] = None,
+        coverage: Optional[bool] = None,
+        node_mask: Optional[Dict[NodeType, Tensor]] = None,
+) -> Union[
+    Tuple[
+        Dict[NodeType, Tensor], Dict[RelType, Tensor], Dict[RelType, Tensor], Dict[RelType, Tensor], List[LayerOffset],
+        Dict[RelType, SamplerStats]
+    ],
+    Tuple[
+        Dict[NodeType, Tensor], Dict[RelType, Tensor], Dict[RelType, Tensor], Dict[RelType, Tensor], List[LayerOffset],
+        Dict[RelType, SamplerStats], Dict[RelType, Coverage]
+    ],
+]:
+    # The coverage is only appended when coverage is set
+    ...
+
+
+def neighbor_sampling_heterogenous_with_features(
+        node_types: List[NodeType],
+        edge_types: List[EdgeType],
+        col_ptrs: Dict[RelType, Tensor],
+        row_indices: Dict[RelType, Tensor],
+        inputs: Dict[NodeType, Tensor],
+        num_neighbors: Dict[RelType, List[int]],
+        num_hops: int,
+        sampler: Optional[EdgeSampler],
+        filter: Optional[Tuple[EdgeFilter, Tensor]],
+        features: Dict[NodeType, Tensor],
+        multigraph: Optional[bool] = None,
+        lonely_seed_policy: Optional[LonelySeedPolicy] = None,
+        node_mask: Optional[Dict[NodeType, Tensor]] = None,
+) -> Tuple[
+    Dict[NodeType, Tensor], Dict[RelType, Tensor], Dict[RelType, Tensor], Dict[RelType, Tensor], List[LayerOffset],
+    Dict[NodeType, Tensor]
+]:
+    ...
+
+
+def neighbor_sampling_heterogenous_hetero_data(
+        node_types: List[NodeType],
+        edge_types: List[EdgeType],
+        col_ptrs: Dict[RelType, Tensor],
+        row_indices: Dict[RelType, Tensor],
+        inputs: Dict[NodeType, Tensor],
+        num_neighbors: Dict[RelType, List[int]],
+        num_hops: int,
+        sampler: Optional[EdgeSampler],
+        filter: Optional[Tuple[EdgeFilter, Tensor]],
+        multigraph: Optional[bool] = None,
+        lonely_seed_policy: Optional[LonelySeedPolicy] = None,
+        node_mask: Optional[Dict[NodeType, Tensor]] = None,
+) -> Dict[str, Dict[Union[NodeType, EdgeType], Union[Tensor, int]]]:
+    # node_dict, row_dict, col_dict, edge_dict, batch_dict, hop_dict and batch_size_dict, as consumed by
+    # torch_geometric.loader.utils.filter_hetero_data
+    ...
+
+
 def hgt_sampling(
         node_types: List[NodeType],
         edge_types: List[EdgeType],
@@ -80,10 +230,11 @@ def budget_sampling(
     ...
 
 
+# node2vec walks of shape [W, walk_length + 1], walks that reach a node without neighbors are padded with -1
 def random_walk(
         row_ptrs: Tensor,
         col_indices: Tensor,
-        start: Tensor,
+        start: StartNodes,
         walk_length: int,
         p: float,
         q: float,
@@ -91,12 +242,60 @@ def random_walk(
     ...
 
 
+# Walks of shape [W, walk_length + 1] padded with -1 after sinks, and with `return_edges` the edges [W, walk_length]
+# of the steps, edge ids when the perm of `to_csr` is given and csr edge positions otherwise
+def uniform_random_walk(
+        row_ptrs: Tensor,
+        col_indices: Tensor,
+        start: StartNodes,
+        walk_length: int,
+        seed: Optional[int] = None,
+        return_edges: Optional[bool] = False,
+        perm: Optional[Tensor] = None,
+) -> Tuple[Tensor, Optional[Tensor]]:
+    ...
+
+
+# Walks where allowed[prev_type, next_type] has to hold for consecutive steps, returns the walks and the edge type
+# of every step
+def metapath_random_walk(
+        row_ptrs: Tensor,
+        col_indices: Tensor,
+        edge_types: Tensor,
+        num_types: int,
+        start: StartNodes,
+        walk_length: int,
+        allowed: Tensor,
+        initial_type: Optional[int] = None,
+        seed: Optional[int] = None,
+) -> Tuple[Tensor, Tensor]:
+    ...
+
+
+def random_spanning_forest(
+        row_ptrs: Tensor,
+        col_indices: Tensor,
+        roots: Optional[Tensor],
+) -> Tuple[Tensor, Tensor]:
+    ...
+
+
+# Row_col of the kept edges, a random spanning forest is never dropped so components stay connected
+def drop_edge_spanning_safe(
+        row_ptrs: Tensor,
+        col_indices: Tensor,
+        p: float,
+        seed: Optional[int] = None,
+) -> Tensor:
+    ...
+
+
 def tempo_random_walk(
         row_ptrs: Tensor,
         col_indices: Tensor,
         node_timestamps: Tensor,
         edge_timestamps: Tensor,
-        start: Tensor,
+        start: StartNodes,
         start_timestamps: Tensor,
         walk_length: int,
         window: Tuple[int, int],
@@ -109,7 +308,7 @@ def biased_tempo_random_walk(
         col_indices: Tensor,
         node_timestamps: Tensor,
         edge_timestamps: Tensor,
-        start: Tensor,
+        start: StartNodes,
         start_timestamps: Tensor,
         walk_length: int,
         walk_bias: str,
@@ -119,6 +318,8 @@ def biased_tempo_random_walk(
     ...
 
 
+# The neighbors must be sorted by id (as to_csr returns them), otherwise a ValueError is raised
+
 def negative_sample_neighbors_homogenous(
         row_ptrs: Tensor,
         col_indices: Tensor,
@@ -130,6 +331,8 @@ def negative_sample_neighbors_homogenous(
     ...
 
 
+# The neighbors must be sorted by id (as to_csr returns them), otherwise a ValueError is raised
+
 def negative_sample_neighbors_heterogenous(
         node_types: List[NodeType],
         edge_types: List[EdgeType],
@@ -144,3 +347,126 @@ def negative_sample_neighbors_heterogenous(
     Dict[NodeType, Tensor], Dict[RelType, Tensor], Dict[RelType, Tensor], Dict[NodeType, int]
 ]:
     ...
+
+
+def degree_histogram(
+        col_ptrs: Tensor,
+        row_indices: Tensor,
+        max_bins: Optional[int],
+) -> Tensor:
+    ...
+
+
+def graph_summary(
+        col_ptrs: Tensor,
+        row_indices: Tensor,
+) -> Dict[str, float]:
+    ...
+
+
+# None if there are no edges or all endpoints have the same in-degree
+def degree_assortativity(
+        col_ptrs: Tensor,
+        row_indices: Tensor,
+) -> Optional[float]:
+    ...
+
+
+# kind is "edge", "node" or "class_insensitive", nodes labeled -1 are left out
+def label_homophily(
+        col_ptrs: Tensor,
+        row_indices: Tensor,
+        labels: Tensor,
+        kind: str,
+) -> Optional[float]:
+    ...
+
+
+# (seeds, bucket per seed, bucket ptrs) with seeds split into in-degree buckets, strategy is "homogeneous" or "balanced"
+def bucket_seeds_by_degree(
+        col_ptrs: Tensor,
+        row_indices: Tensor,
+        seeds: Tensor,
+        num_buckets: int,
+        shuffle_within: Optional[bool] = None,
+        seed: Optional[int] = None,
+        strategy: Optional[str] = None,
+) -> Tuple[Tensor, Tensor, Tensor]:
+    ...
+
+
+# Per anchor: local row_col, global ids of the local nodes, local id of the anchor, edge mask over the csc positions
+def ego_networks(
+        col_ptrs: Tensor,
+        row_indices: Tensor,
+        anchors: Tensor,
+        num_hops: int,
+        max_nodes: Optional[int] = None,
+) -> List[Tuple[Tensor, Tensor, int, Tensor]]:
+    ...
+
+
+# SEAL subgraphs of the [2, P] pairs packed back to back: (nodes, local row_col, drnl labels, node offsets, edge offsets)
+def seal_subgraphs(
+        col_ptrs: Tensor,
+        row_indices: Tensor,
+        pairs: Tensor,
+        num_hops: int,
+        max_nodes_per_pair: Optional[int] = None,
+) -> Tuple[Tensor, Tensor, Tensor, Tensor, Tensor]:
+    ...
+
+
+# metric is "l2" or "cosine", with batch neighbors are only searched within the same example
+def knn_graph(
+        x: Tensor,
+        k: int,
+        loop_: bool,
+        metric: Optional[str] = None,
+        batch: Optional[Tensor] = None,
+) -> Tensor:
+    ...
+
+
+# r is a cosine distance with the cosine metric
+def radius_graph(
+        x: Tensor,
+        r: float,
+        max_num_neighbors: int,
+        loop_: bool,
+        metric: Optional[str] = None,
+        batch: Optional[Tensor] = None,
+) -> Tensor:
+    ...
+
+
+def voxel_grid(
+        pos: Tensor,
+        size: List[float],
+        batch: Optional[Tensor] = None,
+) -> Tensor:
+    ...
+
+
+def index_of(
+        haystack: Tensor,
+        needles: Tensor,
+        assume_sorted: bool,
+) -> Tensor:
+    ...
+
+
+def set_num_threads(num_threads: int) -> None:
+    ...
+
+
+def set_allow_device_transfer(allow: bool) -> None:
+    ...
+
+
+def set_global_seed(seed: int) -> None:
+    ...
+
+
+def derive_stream(keys: List[int]) -> int:
+    ...
@@ -1,7 +1,10 @@
+import functools
+import os
 from dataclasses import dataclass
-from typing import List
+from typing import Callable, List, Optional
 from typing import Union, Dict, Tuple
 
+import numpy as np
 import torch
 from torch import Tensor
 from torch_geometric.typing import EdgeType
@@ -31,7 +34,10 @@ class EdgeSampler:
 
 @dataclass
 class UniformEdgeSampler(EdgeSampler):
-    with_replacement: bool = False
+    # Either one flag for all hops or a flag per hop
+    with_replacement: Union[bool, List[bool]] = False
+    # Caps how often a single edge may be drawn at the hops with replacement, an error without any such hop
+    max_repeats: Optional[int] = None
 
     def validate(self, hetero: bool = False) -> None:
         pass
@@ -40,6 +46,8 @@ class UniformEdgeSampler(EdgeSampler):
 @dataclass
 class WeightedEdgeSampler(EdgeSampler):
     weights: MixedData
+    # Independent draws from per node cdfs that are built once per call, otherwise weighted reservoir sampling
+    with_replacement: bool = False
 
     def validate(self, hetero: bool = False) -> None:
         validate_mixeddata(self.weights, hetero=hetero, dtype=torch.float64)
@@ -65,3 +73,64 @@ class TemporalEdgeFilter:
 
     def validate(self, hetero: bool = False) -> None:
         validate_mixeddata(self.timestamps, hetero=hetero, dtype=torch.int64)
+
+
+@dataclass(frozen=True)
+class SharedGraph:
+    # Segment written by `tch_geometric.share_memory`, pickling only sends this path to the workers
+    path: str
+
+    @staticmethod
+    def share(
+            ptrs: Tensor,
+            indices: Tensor,
+            perm: Optional[Tensor] = None,
+            edge_type: Optional[Tensor] = None,
+    ) -> 'SharedGraph':
+        from tch_geometric.tch_geometric import share_memory
+        return SharedGraph(share_memory(ptrs, indices, perm, edge_type))
+
+    # Maps the segment copy-on-write, the pages stay shared between all processes as long as nobody writes to them
+    def load(self) -> Tuple[Tensor, Tensor, Optional[Tensor]]:
+        return self._load_array('ptrs.npy'), self._load_array('indices.npy'), self._load_array('perm.npy')
+
+    # None when the graph was shared without edge types
+    def load_edge_type(self) -> Optional[Tensor]:
+        return self._load_array('edge_type.npy')
+
+    def _load_array(self, name: str) -> Optional[Tensor]:
+        path = os.path.join(self.path, name)
+        if not os.path.exists(path):
+            return None
+        return torch.from_numpy(np.load(path, mmap_mode='c'))
+
+    # Only once the workers are done, graphs loaded before stay valid
+    def unlink(self) -> None:
+        from tch_geometric.tch_geometric import unlink_shared
+        unlink_shared(self.path)
+
+    # `with SharedGraph.share(...) as graph:` unlinks the segment on exit, also when the block raises
+    def __enter__(self) -> 'SharedGraph':
+        return self
+
+    def __exit__(self, *exc) -> None:
+        if os.path.exists(self.path):
+            self.unlink()
+
+
+_WORKER_GRAPHS: Dict[str, Tuple[Tensor, Tensor, Optional[Tensor]]] = {}
+
+
+def _load_worker_graphs(graphs: Dict[str, SharedGraph], worker_id: int) -> None:
+    for name, graph in graphs.items():
+        _WORKER_GRAPHS[name] = graph.load()
+
+
+# `worker_init_fn` for a DataLoader with spawned workers, each worker maps the graphs once and gets them by name
+# through `get_worker_graph`
+def shared_worker_init(graphs: Dict[str, SharedGraph]) -> Callable[[int], None]:
+    return functools.partial(_load_worker_graphs, graphs)
+
+
+def get_worker_graph(name: str) -> Tuple[Tensor, Tensor, Optional[Tensor]]:
+    return _WORKER_GRAPHS[name]
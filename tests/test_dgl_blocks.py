@@ -0,0 +1,78 @@
+import pytest
+import torch
+import tch_geometric as thg
+
+NUM_NODES = 64
+NUM_NEIGHBORS = [4, 3]
+
+
+@pytest.fixture
+def graph():
+    generator = torch.Generator().manual_seed(0)
+    edge_index = torch.randint(NUM_NODES, (2, 512), generator=generator)
+    col_ptrs, row_indices, perm = thg.to_csc(edge_index, NUM_NODES)
+    return edge_index, col_ptrs, row_indices, perm
+
+
+def sample_blocks(graph):
+    _, col_ptrs, row_indices, perm = graph
+    start = torch.arange(8)
+    samples, rows, cols, edge_ids, layer_offsets = thg.neighbor_sampling_homogenous(
+        col_ptrs, row_indices, start, NUM_NEIGHBORS, None, None, dedup=True
+    )
+    return start, thg.to_dgl_blocks(samples, rows, cols, edge_ids, layer_offsets, perm)
+
+
+# Sums the source features into the destination nodes using only the edge ids and the original edge index, the
+# sample is deduplicated so every global id has a single destination row
+def reference_aggregation(edge_index, eid, dst_nid, x):
+    position = {int(n): i for i, n in enumerate(dst_nid)}
+    out = torch.zeros(len(dst_nid), x.shape[1])
+    for e in eid.tolist():
+        out[position[int(edge_index[1, e])]] += x[edge_index[0, e]]
+    return out
+
+
+def test_blocks_follow_edge_index(graph):
+    edge_index = graph[0]
+    start, blocks = sample_blocks(graph)
+
+    assert len(blocks) == len(NUM_NEIGHBORS)
+    assert torch.equal(blocks[0][5], start)
+    for src, dst, num_src_nodes, num_dst_nodes, src_nid, dst_nid, eid in blocks:
+        assert len(src_nid) == num_src_nodes and len(dst_nid) == num_dst_nodes
+        # Destination nodes are the prefix of the source nodes
+        assert torch.equal(src_nid[:num_dst_nodes], dst_nid)
+        assert bool((eid >= 0).all())
+        assert torch.equal(src_nid[src], edge_index[0, eid])
+        assert torch.equal(dst_nid[dst], edge_index[1, eid])
+
+    # Every hop expands the sources of the previous one
+    for (_, _, num_src_nodes, _, src_nid, _, _), (_, _, _, num_dst_nodes, _, dst_nid, _) in zip(blocks, blocks[1:]):
+        assert num_src_nodes == num_dst_nodes
+        assert torch.equal(src_nid, dst_nid)
+
+
+def test_blocks_aggregate_like_reference(graph):
+    edge_index = graph[0]
+    _, blocks = sample_blocks(graph)
+    x = torch.rand(NUM_NODES, 3)
+
+    for src, dst, num_src_nodes, num_dst_nodes, src_nid, dst_nid, eid in blocks:
+        h = torch.zeros(num_dst_nodes, x.shape[1]).index_add_(0, dst, x[src_nid][src])
+        assert torch.allclose(h, reference_aggregation(edge_index, eid, dst_nid, x))
+
+
+def test_blocks_in_dgl(graph):
+    dgl = pytest.importorskip('dgl')
+    import dgl.function as fn
+
+    edge_index = graph[0]
+    _, blocks = sample_blocks(graph)
+    x = torch.rand(NUM_NODES, 3)
+
+    for src, dst, num_src_nodes, num_dst_nodes, src_nid, dst_nid, eid in blocks:
+        block = dgl.create_block((src, dst), num_src_nodes=num_src_nodes, num_dst_nodes=num_dst_nodes)
+        block.srcdata['h'] = x[src_nid]
+        block.update_all(fn.copy_u('h', 'm'), fn.sum('m', 'h'))
+        assert torch.allclose(block.dstdata['h'], reference_aggregation(edge_index, eid, dst_nid, x))
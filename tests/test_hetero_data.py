@@ -0,0 +1,64 @@
+import pytest
+import torch
+import torch_geometric as pyg
+from torch_geometric.data import HeteroData
+from torch_geometric.loader.utils import filter_hetero_data
+from torch_geometric.nn import HeteroConv, SAGEConv
+
+import tch_geometric as thg
+
+NUM_NEIGHBORS = [4, 3]
+
+
+@pytest.fixture
+def data() -> HeteroData:
+    torch.manual_seed(0)
+    return pyg.datasets.FakeHeteroDataset()[0]
+
+
+# The sampler output feeds filter_hetero_data directly and a hetero conv runs on the batch
+def test_hetero_data_forward(data):
+    col_ptrs, row_indices, perm_dict = {}, {}, {}
+    for edge_type in data.edge_types:
+        src, _, dst = edge_type
+        rel_type = '__'.join(edge_type)
+        size = (data[src].num_nodes, data[dst].num_nodes)
+        col_ptrs[rel_type], row_indices[rel_type], perm_dict[edge_type] = thg.to_csc(data[edge_type].edge_index, size)
+
+    seed_type = data.edge_types[0][2]
+    inputs = {seed_type: torch.arange(8, dtype=torch.long)}
+
+    out = thg.neighbor_sampling_heterogenous_hetero_data(
+        data.node_types, data.edge_types, col_ptrs, row_indices, inputs,
+        {rel_type: NUM_NEIGHBORS for rel_type in col_ptrs}, len(NUM_NEIGHBORS), None, None,
+    )
+    batch = filter_hetero_data(
+        data, out['node_dict'], out['row_dict'], out['col_dict'], out['edge_dict'], perm_dict,
+    )
+    for node_type, batch_size in out['batch_size_dict'].items():
+        batch[node_type].batch = out['batch_dict'][node_type]
+        batch[node_type].batch_size = batch_size
+
+    conv = HeteroConv({edge_type: SAGEConv((-1, -1), 32) for edge_type in batch.edge_types}, aggr='sum')
+    output = conv(batch.x_dict, batch.edge_index_dict)
+    assert output[seed_type].shape == (batch[seed_type].num_nodes, 32)
+    assert output[seed_type][:batch[seed_type].batch_size].shape == (8, 32)
+
+
+def test_hetero_data_self_loops_keep_seeds_at_hop_0():
+    # 1 -> 0 and 2 -> 0, paper 3 is never cited and gets a self loop
+    edge_index = torch.tensor([[1, 2], [0, 0]], dtype=torch.long)
+    rel_type = 'paper__cites__paper'
+    col_ptrs, row_indices, _ = thg.to_csc(edge_index, 4)
+    inputs = {'paper': torch.tensor([0, 3], dtype=torch.long)}
+
+    out = thg.neighbor_sampling_heterogenous_hetero_data(
+        ['paper'], [('paper', 'cites', 'paper')], {rel_type: col_ptrs}, {rel_type: row_indices}, inputs,
+        {rel_type: NUM_NEIGHBORS}, len(NUM_NEIGHBORS), None, None, lonely_seed_policy='self_loop',
+    )
+    edge_type = ('paper', 'cites', 'paper')
+    assert (out['edge_dict'][edge_type] == -1).sum().item() == 1
+    assert out['batch_size_dict']['paper'] == 2
+    assert out['hop_dict']['paper'][:2].tolist() == [0, 0]
+    assert out['batch_dict']['paper'][:2].tolist() == [0, 1]
+    assert (out['hop_dict']['paper'][2:] == 1).all()
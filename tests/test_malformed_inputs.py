@@ -0,0 +1,110 @@
+import pytest
+import torch
+import tch_geometric as thg
+from tch_geometric.utils import UniformEdgeSampler
+
+EDGE_INDEX = torch.tensor([[0, 1, 1, 2, 3], [1, 0, 2, 3, 0]], dtype=torch.long)
+NUM_NODES = 4
+
+
+@pytest.fixture
+def csc():
+    col_ptrs, row_indices, _ = thg.to_csc(EDGE_INDEX, NUM_NODES)
+    return col_ptrs, row_indices
+
+
+@pytest.fixture
+def csr():
+    row_ptrs, col_indices, _ = thg.to_csr(EDGE_INDEX, NUM_NODES)
+    return row_ptrs, col_indices
+
+
+def test_conversion_rejects_malformed_edge_index():
+    with pytest.raises(ValueError):
+        thg.to_csc(EDGE_INDEX.to(torch.bool), NUM_NODES)
+    with pytest.raises(ValueError):
+        thg.to_csc(EDGE_INDEX.to(torch.float) + 0.5, NUM_NODES)
+    with pytest.raises(ValueError, match='shape'):
+        thg.to_csr(EDGE_INDEX[0], NUM_NODES)
+    with pytest.raises(ValueError, match='out of bounds'):
+        thg.to_csr(EDGE_INDEX, 3)
+    with pytest.raises(ValueError, match='out of bounds'):
+        thg.to_csc(-EDGE_INDEX, NUM_NODES)
+
+
+def test_sampling_rejects_malformed_inputs(csc):
+    col_ptrs, row_indices = csc
+
+    # Negative fanouts do not fit the unsigned fanout type
+    with pytest.raises(OverflowError):
+        thg.neighbor_sampling_homogenous(col_ptrs, row_indices, torch.tensor([0]), [2, -1])
+    with pytest.raises(ValueError, match='inputs contains node 7'):
+        thg.neighbor_sampling_homogenous(col_ptrs, row_indices, torch.tensor([0, 7]), [2])
+    with pytest.raises(ValueError):
+        thg.neighbor_sampling_homogenous(col_ptrs, row_indices, torch.tensor([0.5]), [2])
+    with pytest.raises(ValueError, match='one dimensional'):
+        thg.neighbor_sampling_homogenous(col_ptrs.view(1, -1), row_indices, torch.tensor([0]), [2])
+    with pytest.raises(ValueError):
+        thg.neighbor_sampling_homogenous(col_ptrs[1:], row_indices, torch.tensor([0]), [2])
+    with pytest.raises(ValueError):
+        thg.neighbor_sampling_homogenous(col_ptrs, row_indices[:-1], torch.tensor([0]), [2])
+
+    # No seeds is not an error, it samples nothing
+    samples, rows, cols, edge_index, layer_offsets = thg.neighbor_sampling_homogenous(
+        col_ptrs, row_indices, torch.tensor([], dtype=torch.long), [2, 2],
+    )
+    assert samples.numel() == 0 and rows.numel() == 0
+
+
+def test_max_repeats_requires_replacement(csc):
+    col_ptrs, row_indices = csc
+    inputs = torch.tensor([0, 1])
+    with pytest.raises(ValueError, match='max_repeats'):
+        thg.neighbor_sampling_homogenous(col_ptrs, row_indices, inputs, [2, 2], UniformEdgeSampler(max_repeats=1), None)
+    with pytest.raises(ValueError, match='max_repeats'):
+        thg.neighbor_sampling_homogenous(
+            col_ptrs, row_indices, inputs, [2, 2], UniformEdgeSampler([False, False], max_repeats=1), None,
+        )
+    # The cap applies to the hop with replacement only, the other hop still draws distinct edges
+    samples, *_ = thg.neighbor_sampling_homogenous(
+        col_ptrs, row_indices, inputs, [2, 2], UniformEdgeSampler([False, True], max_repeats=1), None,
+    )
+    assert samples.numel() > 0
+
+
+def test_malformed_graph_raises_instead_of_crashing(csc):
+    col_ptrs, row_indices = csc
+    # The pointers are fine but a neighbor is out of range, this is caught inside instead of aborting
+    broken = row_indices.clone()
+    broken[0] = 100
+    with pytest.raises(ValueError, match='out of bounds'):
+        thg.neighbor_sampling_homogenous(col_ptrs, broken, torch.tensor([0, 1, 2, 3]), [4, 4])
+
+
+def test_entry_points_reject_out_of_range_rows(csc, csr):
+    col_ptrs, row_indices = csc
+    broken = row_indices.clone()
+    broken[0] = 100
+    with pytest.raises(ValueError, match='out of bounds'):
+        thg.ego_networks(col_ptrs, broken, torch.tensor([0]), 2)
+    with pytest.raises(ValueError, match='out of bounds'):
+        thg.seal_subgraphs(col_ptrs, broken, torch.tensor([[0], [1]]), 2)
+    with pytest.raises(ValueError, match='out of bounds'):
+        thg.bucket_seeds_by_degree(col_ptrs, broken, torch.tensor([0, 1]), 2)
+    with pytest.raises(ValueError, match='out of bounds'):
+        thg.graph_summary(col_ptrs, broken)
+
+    # A relation is checked against the node count of its src type
+    rel = 'paper__cites__paper'
+    with pytest.raises(ValueError, match='out of bounds'):
+        thg.neighbor_sampling_heterogenous(
+            ['paper'], [('paper', 'cites', 'paper')], {rel: col_ptrs}, {rel: broken},
+            {'paper': torch.tensor([0])}, {rel: [2]}, 1, None, None,
+        )
+
+    row_ptrs, col_indices = csr
+    broken = col_indices.clone()
+    broken[0] = -1
+    with pytest.raises(ValueError, match='out of bounds'):
+        thg.uniform_random_walk(row_ptrs, broken, torch.tensor([0]), 3)
+
@@ -0,0 +1,89 @@
+import numpy as np
+import pytest
+import torch
+import tch_geometric as thg
+
+# 0 -> 1 -> 2 -> 3 and 1 -> 3, node 3 is a sink
+EDGE_INDEX = torch.tensor([[0, 1, 1, 2], [1, 2, 3, 3]], dtype=torch.long)
+NUM_NODES = 4
+
+
+@pytest.fixture
+def csr():
+    row_ptrs, col_indices, perm = thg.to_csr(EDGE_INDEX, NUM_NODES)
+    return row_ptrs, col_indices, perm
+
+
+def test_uniform_seed_determinism(csr):
+    row_ptrs, col_indices, _ = csr
+    start = torch.arange(NUM_NODES).repeat(16)
+
+    walks_a, _ = thg.uniform_random_walk(row_ptrs, col_indices, start, 5, seed=42)
+    walks_b, _ = thg.uniform_random_walk(row_ptrs, col_indices, start, 5, seed=42)
+    assert torch.equal(walks_a, walks_b)
+
+    thg.set_global_seed(7)
+    walks_a = thg.random_walk(row_ptrs, col_indices, start, 5, 1.0, 0.5)
+    thg.set_global_seed(7)
+    walks_b = thg.random_walk(row_ptrs, col_indices, start, 5, 1.0, 0.5)
+    assert torch.equal(walks_a, walks_b)
+
+
+def test_sink_padding(csr):
+    row_ptrs, col_indices, perm = csr
+    walks, edges = thg.uniform_random_walk(row_ptrs, col_indices, torch.tensor([2, 3]), 3, seed=0, return_edges=True)
+
+    assert walks.dtype == torch.long
+    assert walks.shape == (2, 4)
+    assert walks.tolist() == [[2, 3, -1, -1], [3, -1, -1, -1]]
+    assert edges.shape == (2, 3)
+    # The edge index is already sorted, stored edge 3 is edge 3
+    assert perm is None
+    assert edges[0, 0].item() == 3
+    reversed_perm = torch.arange(EDGE_INDEX.shape[1] - 1, -1, -1)
+    _, edge_ids = thg.uniform_random_walk(
+        row_ptrs, col_indices, torch.tensor([2, 3]), 3, seed=0, return_edges=True, perm=reversed_perm,
+    )
+    assert edge_ids[0, 0].item() == 0
+    assert torch.equal(edge_ids[:, 1:], edges[:, 1:])
+    assert (edges[0, 1:] == -1).all() and (edges[1] == -1).all()
+
+    walks = thg.random_walk(row_ptrs, col_indices, torch.tensor([3]), 3, 1.0, 1.0)
+    assert walks.tolist() == [[3, -1, -1, -1]]
+
+
+def test_numpy_start(csr):
+    row_ptrs, col_indices, _ = csr
+    start = np.array([0, 1, 2], dtype=np.int64)
+
+    walks_np, _ = thg.uniform_random_walk(row_ptrs, col_indices, start, 4, seed=3)
+    walks_pt, _ = thg.uniform_random_walk(row_ptrs, col_indices, torch.from_numpy(start), 4, seed=3)
+    assert torch.equal(walks_np, walks_pt)
+    walks_list, _ = thg.uniform_random_walk(row_ptrs, col_indices, [0, 1, 2], 4, seed=3)
+    assert torch.equal(walks_list, walks_pt)
+
+
+def test_invalid_arguments(csr):
+    row_ptrs, col_indices, _ = csr
+    start = torch.tensor([0, 1])
+
+    with pytest.raises(Exception, match='p and q must be positive'):
+        thg.random_walk(row_ptrs, col_indices, start, 3, 0.0, 1.0)
+    with pytest.raises(Exception, match='p and q must be positive'):
+        thg.random_walk(row_ptrs, col_indices, start, 3, 1.0, -1.0)
+    with pytest.raises(Exception, match='start node 9 at index 1'):
+        thg.uniform_random_walk(row_ptrs, col_indices, torch.tensor([0, 9]), 3)
+
+
+def test_metapath(csr):
+    row_ptrs, col_indices, _ = csr
+    # Type 0 may only be followed by type 1 and the other way around
+    edge_types = torch.tensor([0, 1, 1, 0])
+    allowed = torch.tensor([[False, True], [True, False]])
+
+    walks, types = thg.metapath_random_walk(
+        row_ptrs, col_indices, edge_types, 2, torch.tensor([0]), 3, allowed, initial_type=None, seed=0,
+    )
+    assert walks.shape == (1, 4)
+    valid = types[0][types[0] >= 0]
+    assert ((valid[1:] + valid[:-1]) == 1).all()
@@ -0,0 +1,53 @@
+import multiprocessing
+import os
+
+import torch
+import tch_geometric as thg
+from tch_geometric.utils import SharedGraph, get_worker_graph, shared_worker_init
+
+EDGE_INDEX = torch.tensor([[0, 1, 1, 2, 3, 0, 2], [1, 0, 2, 3, 0, 3, 1]], dtype=torch.long)
+NUM_NODES = 4
+
+
+def sample(col_ptrs, row_indices):
+    thg.set_global_seed(7)
+    samples, *_ = thg.neighbor_sampling_homogenous(col_ptrs, row_indices, torch.tensor([0, 3]), [2, 2])
+    return samples
+
+
+def sample_in_worker(graph, queue):
+    shared_worker_init({'graph': graph})(0)
+    col_ptrs, row_indices, perm = get_worker_graph('graph')
+    queue.put((sample(col_ptrs, row_indices), perm))
+
+
+def test_shared_memory_spawned_worker():
+    col_ptrs, row_indices, perm = thg.to_csc(EDGE_INDEX, NUM_NODES)
+    graph = SharedGraph.share(col_ptrs, row_indices, perm)
+    try:
+        shared_ptrs, shared_indices, shared_perm = graph.load()
+        assert torch.equal(shared_ptrs, col_ptrs)
+        assert torch.equal(shared_indices, row_indices)
+        assert torch.equal(shared_perm, perm)
+        expected = sample(col_ptrs, row_indices)
+        assert torch.equal(sample(shared_ptrs, shared_indices), expected)
+
+        ctx = multiprocessing.get_context('spawn')
+        queue = ctx.Queue()
+        worker = ctx.Process(target=sample_in_worker, args=(graph, queue))
+        worker.start()
+        samples, worker_perm = queue.get(timeout=60)
+        worker.join()
+        assert worker.exitcode == 0
+        assert torch.equal(samples, expected)
+        assert torch.equal(worker_perm, perm)
+    finally:
+        graph.unlink()
+
+
+def test_shared_memory_without_perm():
+    col_ptrs, row_indices, _ = thg.to_csc(EDGE_INDEX, NUM_NODES)
+    with SharedGraph.share(col_ptrs, row_indices) as graph:
+        _, _, perm = graph.load()
+        assert perm is None
+    assert not os.path.exists(graph.path)